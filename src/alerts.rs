@@ -0,0 +1,316 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub mod notify;
+
+use crate::config::AlertsConfig;
+use crate::consensus::metrics::AnomalyFlags;
+use crate::consensus::ConsensusEngine;
+use crate::log;
+use crate::network::NetworkService;
+use crate::storage::database::BlockchainDB;
+use crate::watchtower::Watchtower;
+use notify::{Notifier, SmtpNotifier, WebhookNotifier};
+
+/// What a rule watches; it fires while the condition holds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "condition", rename_all = "snake_case")]
+pub enum Condition {
+    TpsBelow { threshold: f64 },
+    NoBlockFor { seconds: u64 },
+    PeersBelow { count: usize },
+    /// Security score at least `by` under the highest score seen since startup
+    SecurityScoreDrop { by: f64 },
+    /// The latest TPS, latency or peer-count reading was far off its moving average
+    Anomaly,
+    /// A watchtower found a block failing re-verification or a peer on another chain
+    ChainDivergence,
+    /// A watchtower saw at least `transactions` valid transactions kept out of blocks
+    /// with room for them
+    Censorship { transactions: usize },
+}
+
+/// A named condition, as configured under `[[alerts.rules]]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub name: String,
+    #[serde(flatten)]
+    pub condition: Condition,
+}
+
+/// Readings rules are checked against; `None` where this process cannot observe one,
+/// which leaves rules on it in their current state
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AlertMetrics {
+    pub tps: Option<f64>,
+    pub seconds_since_block: Option<u64>,
+    pub peers: Option<usize>,
+    pub security_score: Option<f64>,
+    pub anomalies: Option<AnomalyFlags>,
+    pub divergences: Option<usize>,
+    pub censored_transactions: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertState {
+    Firing,
+    Resolved,
+}
+
+/// A rule starting or stopping to fire, as sent to notifiers
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    pub state: AlertState,
+    /// The reading that changed the rule's state
+    pub summary: String,
+    pub timestamp: u64,
+}
+
+/// Checks rules against successive readings and reports only state changes, so a
+/// condition that keeps holding alerts once and once more when it clears
+#[derive(Debug, Clone)]
+pub struct AlertEngine {
+    rules: Vec<AlertRule>,
+    firing: HashSet<String>,
+    peak_security_score: Option<f64>,
+}
+
+impl AlertEngine {
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        Self { rules, firing: HashSet::new(), peak_security_score: None }
+    }
+
+    pub fn firing(&self) -> impl Iterator<Item = &str> {
+        self.firing.iter().map(String::as_str)
+    }
+
+    pub fn evaluate(&mut self, metrics: &AlertMetrics) -> Vec<Alert> {
+        if let Some(score) = metrics.security_score {
+            self.peak_security_score = Some(self.peak_security_score.map_or(score, |peak| peak.max(score)));
+        }
+        let mut alerts = Vec::new();
+        for rule in &self.rules {
+            let Some((breached, summary)) = check(&rule.condition, metrics, self.peak_security_score) else {
+                continue;
+            };
+            let state = match (breached, self.firing.contains(&rule.name)) {
+                (true, false) => {
+                    self.firing.insert(rule.name.clone());
+                    AlertState::Firing
+                }
+                (false, true) => {
+                    self.firing.remove(&rule.name);
+                    AlertState::Resolved
+                }
+                _ => continue,
+            };
+            alerts.push(Alert { rule: rule.name.clone(), state, summary, timestamp: current_timestamp() });
+        }
+        alerts
+    }
+}
+
+/// Whether `condition` holds and a description of the reading, or `None` without one
+fn check(condition: &Condition, metrics: &AlertMetrics, peak_security_score: Option<f64>) -> Option<(bool, String)> {
+    match condition {
+        Condition::TpsBelow { threshold } => {
+            let tps = metrics.tps?;
+            Some((tps < *threshold, format!("TPS {:.1}, threshold {}", tps, threshold)))
+        }
+        Condition::NoBlockFor { seconds } => {
+            let since = metrics.seconds_since_block?;
+            Some((since >= *seconds, format!("Last block {}s ago, limit {}s", since, seconds)))
+        }
+        Condition::PeersBelow { count } => {
+            let peers = metrics.peers?;
+            Some((peers < *count, format!("{} peers, minimum {}", peers, count)))
+        }
+        Condition::SecurityScoreDrop { by } => {
+            let (score, peak) = (metrics.security_score?, peak_security_score?);
+            Some((peak - score >= *by, format!("Security score {:.2}, peak {:.2}", score, peak)))
+        }
+        Condition::Anomaly => {
+            let anomalies = metrics.anomalies?;
+            let series: Vec<&str> = [(anomalies.tps, "TPS"), (anomalies.latency, "latency"), (anomalies.peer_count, "peer count")]
+                .into_iter()
+                .filter_map(|(flagged, name)| flagged.then_some(name))
+                .collect();
+            let summary = if series.is_empty() { "No anomalies".to_string() } else { format!("Anomalous {}", series.join(", ")) };
+            Some((anomalies.any(), summary))
+        }
+        Condition::ChainDivergence => {
+            let divergences = metrics.divergences?;
+            Some((divergences > 0, format!("{} divergences", divergences)))
+        }
+        Condition::Censorship { transactions } => {
+            let censored = metrics.censored_transactions?;
+            Some((censored >= *transactions, format!("{} transactions censored, limit {}", censored, transactions)))
+        }
+    }
+}
+
+/// Periodically reads this process's metrics, checks the rules and sends every state
+/// change to all notifiers. A failed notification is logged and not retried
+pub struct AlertService {
+    engine: Mutex<AlertEngine>,
+    notifiers: Vec<Box<dyn Notifier>>,
+    consensus: Arc<ConsensusEngine>,
+    db: Option<BlockchainDB>,
+    network: Option<Arc<NetworkService>>,
+    watchtower: Option<Arc<Watchtower>>,
+}
+
+impl AlertService {
+    pub fn new(rules: Vec<AlertRule>, consensus: Arc<ConsensusEngine>) -> Self {
+        Self { engine: Mutex::new(AlertEngine::new(rules)), notifiers: Vec::new(), consensus, db: None, network: None, watchtower: None }
+    }
+
+    /// Rules and notifiers from the `[alerts]` config section
+    pub fn from_config(config: &AlertsConfig, consensus: Arc<ConsensusEngine>) -> Self {
+        let mut service = Self::new(config.rules.clone(), consensus);
+        for webhook in &config.webhooks {
+            service = service.with_notifier(Box::new(WebhookNotifier::new(webhook.clone())));
+        }
+        for email in &config.email {
+            service = service.with_notifier(Box::new(SmtpNotifier::new(email.clone())));
+        }
+        service
+    }
+
+    pub fn with_notifier(mut self, notifier: Box<dyn Notifier>) -> Self {
+        self.notifiers.push(notifier);
+        self
+    }
+
+    /// Chain read for the time since the last block
+    pub fn with_db(mut self, db: BlockchainDB) -> Self {
+        self.db = Some(db);
+        self
+    }
+
+    /// Network read for the peer count; its node's chain is used unless `with_db` set one
+    pub fn with_network(mut self, network: Arc<NetworkService>) -> Self {
+        self.db = self.db.or_else(|| Some(network.node().db().clone()));
+        self.network = Some(network);
+        self
+    }
+
+    /// Watchtower read for divergences and censored transactions
+    pub fn with_watchtower(mut self, watchtower: Arc<Watchtower>) -> Self {
+        self.watchtower = Some(watchtower);
+        self
+    }
+
+    pub async fn metrics(&self) -> AlertMetrics {
+        let stats = self.consensus.stats();
+        let peers = self.network.as_ref().map(|network| network.peers().len());
+        if let Some(peers) = peers {
+            self.consensus.record_peer_count(peers);
+        }
+        let performance = self.consensus.metrics().calculate_stats();
+        let seconds_since_block = match self.db.clone() {
+            Some(db) => crate::node::blocking(move || -> Option<u64> {
+                let block = db.get_block(db.get_latest_height().ok()?).ok()??;
+                Some(current_timestamp().saturating_sub(block.header.timestamp))
+            })
+            .await
+            .ok()
+            .flatten(),
+            None => None,
+        };
+        let watch = self.watchtower.as_ref().map(|watchtower| watchtower.status());
+        AlertMetrics {
+            tps: Some(stats.transactions_per_second as f64),
+            seconds_since_block,
+            peers,
+            security_score: Some(performance.security_score),
+            anomalies: Some(performance.anomalies),
+            divergences: watch.as_ref().map(|status| status.divergences.len()),
+            censored_transactions: watch.as_ref().map(|status| status.censored.len()),
+        }
+    }
+
+    /// Evaluates the rules once and notifies their state changes, which are returned
+    pub async fn check(&self) -> Vec<Alert> {
+        let metrics = self.metrics().await;
+        let alerts = self.engine.lock().unwrap().evaluate(&metrics);
+        for alert in &alerts {
+            log!(Warn, "Alert {} {:?}: {}", alert.rule, alert.state, alert.summary);
+            for notifier in &self.notifiers {
+                if let Err(e) = notifier.notify(alert).await {
+                    log!(Warn, "Alert notifier {} failed: {}", notifier.name(), e);
+                }
+            }
+        }
+        alerts
+    }
+
+    pub async fn run(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            self.check().await;
+        }
+    }
+}
+
+fn current_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alert_rules() {
+        let rule = |name: &str, condition| AlertRule { name: name.to_string(), condition };
+        let mut engine = AlertEngine::new(vec![
+            rule("low-tps", Condition::TpsBelow { threshold: 100.0 }),
+            rule("stalled", Condition::NoBlockFor { seconds: 60 }),
+            rule("isolated", Condition::PeersBelow { count: 2 }),
+            rule("security", Condition::SecurityScoreDrop { by: 0.3 }),
+        ]);
+        let healthy = AlertMetrics { tps: Some(500.0), seconds_since_block: Some(2), peers: None, security_score: Some(1.0), ..AlertMetrics::default() };
+        assert!(engine.evaluate(&healthy).is_empty());
+
+        // Breaches fire once while they last; a rule without its reading is left alone
+        let degraded = AlertMetrics { tps: Some(20.0), seconds_since_block: Some(90), peers: None, security_score: Some(0.6), ..AlertMetrics::default() };
+        let fired: Vec<(String, AlertState)> = engine.evaluate(&degraded).into_iter().map(|alert| (alert.rule, alert.state)).collect();
+        assert_eq!(
+            fired,
+            vec![("low-tps".to_string(), AlertState::Firing), ("stalled".to_string(), AlertState::Firing), ("security".to_string(), AlertState::Firing)]
+        );
+        assert!(engine.evaluate(&degraded).is_empty());
+        assert_eq!(engine.firing().count(), 3);
+
+        // The security score is compared against its peak, not a fixed level
+        let recovered = AlertMetrics { security_score: Some(0.8), peers: Some(1), ..healthy };
+        let alerts = engine.evaluate(&recovered);
+        let states: Vec<(&str, AlertState)> = alerts.iter().map(|alert| (alert.rule.as_str(), alert.state)).collect();
+        assert_eq!(
+            states,
+            vec![("low-tps", AlertState::Resolved), ("stalled", AlertState::Resolved), ("isolated", AlertState::Firing), ("security", AlertState::Resolved)]
+        );
+        assert_eq!(alerts[2].summary, "1 peers, minimum 2");
+
+        let mut engine = AlertEngine::new(vec![rule("anomaly", Condition::Anomaly)]);
+        let flags = AnomalyFlags { latency: true, peer_count: true, ..AnomalyFlags::default() };
+        let alerts = engine.evaluate(&AlertMetrics { anomalies: Some(flags), ..AlertMetrics::default() });
+        assert_eq!(alerts[0].summary, "Anomalous latency, peer count");
+        assert_eq!(engine.evaluate(&AlertMetrics { anomalies: Some(AnomalyFlags::default()), ..AlertMetrics::default() })[0].state, AlertState::Resolved);
+
+        let mut engine = AlertEngine::new(vec![rule("diverged", Condition::ChainDivergence), rule("censored", Condition::Censorship { transactions: 2 })]);
+        let watched = AlertMetrics { divergences: Some(1), censored_transactions: Some(1), ..AlertMetrics::default() };
+        let alerts = engine.evaluate(&watched);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!((alerts[0].rule.as_str(), alerts[0].summary.as_str()), ("diverged", "1 divergences"));
+        assert_eq!(engine.evaluate(&AlertMetrics { censored_transactions: Some(2), ..watched })[0].rule, "censored");
+
+        println!("   Alert rules working!");
+    }
+}
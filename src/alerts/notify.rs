@@ -0,0 +1,191 @@
+use async_trait::async_trait;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use super::{Alert, AlertState};
+use crate::config::{SmtpConfig, WebhookConfig};
+
+/// Time allowed for one notification, connection included
+pub const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Somewhere alerts are delivered
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Name used in logs
+    fn name(&self) -> String;
+    async fn notify(&self, alert: &Alert) -> Result<(), String>;
+}
+
+/// POSTs each alert as JSON to a URL
+pub struct WebhookNotifier {
+    config: WebhookConfig,
+    client: hyper::Client<hyper::client::HttpConnector>,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: WebhookConfig) -> Self {
+        Self { config, client: hyper::Client::new() }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> String {
+        format!("webhook {}", self.config.url)
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<(), String> {
+        let body = serde_json::to_vec(alert).map_err(|e| e.to_string())?;
+        let request = hyper::Request::post(&self.config.url)
+            .header("content-type", "application/json")
+            .body(hyper::Body::from(body))
+            .map_err(|e| e.to_string())?;
+        let response = tokio::time::timeout(NOTIFY_TIMEOUT, self.client.request(request))
+            .await
+            .map_err(|_| "Timed out".to_string())?
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("Webhook answered {}", response.status()));
+        }
+        Ok(())
+    }
+}
+
+/// Mails each alert through an SMTP relay
+pub struct SmtpNotifier {
+    config: SmtpConfig,
+}
+
+impl SmtpNotifier {
+    pub fn new(config: SmtpConfig) -> Self {
+        Self { config }
+    }
+
+    fn message(&self, alert: &Alert) -> String {
+        let state = match alert.state {
+            AlertState::Firing => "FIRING",
+            AlertState::Resolved => "RESOLVED",
+        };
+        let mut message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: [TriUnity] {} {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+            self.config.from,
+            self.config.to.join(", "),
+            state,
+            alert.rule
+        );
+        // Lines starting with a dot are doubled so none ends the DATA section early
+        for line in format!("{}\nRule: {}\nTimestamp: {}", alert.summary, alert.rule, alert.timestamp).lines() {
+            if line.starts_with('.') {
+                message.push('.');
+            }
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        message
+    }
+
+    async fn send(&self, message: &str) -> Result<(), String> {
+        let stream = TcpStream::connect(&self.config.server).await.map_err(|e| e.to_string())?;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        expect_reply(&mut reader, 220).await?;
+        let mut commands = vec![format!("EHLO {}", self.config.hello), format!("MAIL FROM:<{}>", self.config.from)];
+        commands.extend(self.config.to.iter().map(|to| format!("RCPT TO:<{}>", to)));
+        for command in commands {
+            writer.write_all(format!("{}\r\n", command).as_bytes()).await.map_err(|e| e.to_string())?;
+            expect_reply(&mut reader, 250).await.map_err(|e| format!("{} refused: {}", command, e))?;
+        }
+        writer.write_all(b"DATA\r\n").await.map_err(|e| e.to_string())?;
+        expect_reply(&mut reader, 354).await?;
+        writer.write_all(format!("{}.\r\n", message).as_bytes()).await.map_err(|e| e.to_string())?;
+        expect_reply(&mut reader, 250).await.map_err(|e| format!("Message refused: {}", e))?;
+        writer.write_all(b"QUIT\r\n").await.map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    fn name(&self) -> String {
+        format!("smtp {}", self.config.server)
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<(), String> {
+        let message = self.message(alert);
+        tokio::time::timeout(NOTIFY_TIMEOUT, self.send(&message)).await.map_err(|_| "Timed out".to_string())?
+    }
+}
+
+/// Reads one possibly multi-line reply, failing unless it carries `code`
+async fn expect_reply<R: tokio::io::AsyncBufRead + Unpin>(reader: &mut R, code: u16) -> Result<(), String> {
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.map_err(|e| e.to_string())? == 0 {
+            return Err("Connection closed".to_string());
+        }
+        let line = line.trim_end();
+        if !line.starts_with(&code.to_string()) {
+            return Err(line.to_string());
+        }
+        // `250-` continues the reply, `250 ` ends it
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_smtp_notifier() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = listener.local_addr().unwrap().to_string();
+        let relay = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            writer.write_all(b"220 relay ready\r\n").await.unwrap();
+            let (mut received, mut in_data) = (Vec::new(), false);
+            while let Some(line) = lines.next_line().await.unwrap() {
+                let reply: &[u8] = match line.as_str() {
+                    "." if in_data => {
+                        in_data = false;
+                        b"250 queued\r\n"
+                    }
+                    _ if in_data => b"",
+                    "DATA" => {
+                        in_data = true;
+                        b"354 go ahead\r\n"
+                    }
+                    "QUIT" => break,
+                    command if command.starts_with("EHLO") => b"250-relay\r\n250 8BITMIME\r\n",
+                    _ => b"250 ok\r\n",
+                };
+                writer.write_all(reply).await.unwrap();
+                received.push(line);
+            }
+            received
+        });
+
+        let notifier = SmtpNotifier::new(SmtpConfig {
+            server,
+            hello: "node".to_string(),
+            from: "node@example.org".to_string(),
+            to: vec!["ops@example.org".to_string()],
+        });
+        let alert = Alert { rule: "stalled".to_string(), state: AlertState::Firing, summary: ".Last block 90s ago".to_string(), timestamp: 1 };
+        notifier.notify(&alert).await.unwrap();
+
+        let received = relay.await.unwrap();
+        assert_eq!(received[..4], ["EHLO node", "MAIL FROM:<node@example.org>", "RCPT TO:<ops@example.org>", "DATA"]);
+        assert!(received.contains(&"Subject: [TriUnity] FIRING stalled".to_string()));
+        assert!(received.contains(&"..Last block 90s ago".to_string()));
+        assert_eq!(received.last().unwrap(), ".");
+
+        println!("   SMTP alert notifier working!");
+    }
+}
@@ -0,0 +1,393 @@
+use futures::{SinkExt, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::api::rpc::{LogFilter, RpcRequest, RpcResponse, StatusUpdate};
+use crate::consensus::duties::ValidatorPerformance;
+use crate::crypto::bech32;
+use crate::interop::channel::{ChannelEnd, CommitmentProof};
+use crate::node::TxStatus;
+use crate::storage::backup::BackupManifest;
+use crate::storage::blocks::{Block, BlockHeader, Transaction};
+use crate::storage::names::{self, NameRecord};
+use crate::storage::schedule::ScheduledTransfer;
+use crate::storage::vesting::VestingStatus;
+use crate::storage::state::Account;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeInfo {
+    pub version: String,
+    pub height: u64,
+    pub blocks: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionRecord {
+    pub transaction: Transaction,
+    pub height: u64,
+    pub index: usize,
+}
+
+/// One `logs_query` match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub height: u64,
+    pub index: usize,
+    pub hash: String,
+    pub transaction: Transaction,
+}
+
+/// Minimal JSON-RPC client over HTTP
+pub struct RpcClient {
+    url: String,
+    token: Option<String>,
+    client: hyper::Client<hyper::client::HttpConnector>,
+}
+
+impl RpcClient {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: format!("{}/rpc", base_url(url)),
+            token: None,
+            client: hyper::Client::new(),
+        }
+    }
+
+    /// Sends `token` as `Authorization: Bearer` for the higher per-token rate limits
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    pub async fn call(&self, method: &str, params: Value) -> Result<Value, String> {
+        let body = serde_json::to_vec(&RpcRequest::new(1, method, params))
+            .map_err(|e| e.to_string())?;
+        let mut request = hyper::Request::post(&self.url)
+            .header("content-type", "application/json");
+        if let Some(token) = &self.token {
+            request = request.header("authorization", format!("Bearer {}", token));
+        }
+        let request = request
+            .body(hyper::Body::from(body))
+            .map_err(|e| e.to_string())?;
+
+        let response = self.client.request(request).await.map_err(|e| e.to_string())?;
+        let bytes = hyper::body::to_bytes(response.into_body()).await.map_err(|e| e.to_string())?;
+        let response: RpcResponse = serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(format!("RPC error {}: {}", error.code, error.message)),
+            (Some(result), None) => Ok(result),
+            (None, None) => Ok(Value::Null),
+        }
+    }
+}
+
+/// Typed async client for a TriUnity node's JSON-RPC endpoints
+pub struct TriUnityClient {
+    rpc: RpcClient,
+    socket_url: String,
+    token: Option<String>,
+}
+
+impl TriUnityClient {
+    /// `url` is the node's base URL, e.g. `http://127.0.0.1:8080`; a trailing `/rpc` is accepted
+    pub fn new(url: &str) -> Self {
+        let base = base_url(url);
+        let socket_url = match base.split_once("://") {
+            Some(("https", rest)) => format!("wss://{}/rpc/ws", rest),
+            Some((_, rest)) => format!("ws://{}/rpc/ws", rest),
+            None => format!("ws://{}/rpc/ws", base),
+        };
+        Self {
+            rpc: RpcClient::new(url),
+            socket_url,
+            token: None,
+        }
+    }
+
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.rpc = self.rpc.with_token(token);
+        self.token = Some(token.to_string());
+        self
+    }
+
+    async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, String> {
+        let result = self.rpc.call(method, params).await?;
+        serde_json::from_value(result).map_err(|e| format!("Unexpected {} response: {}", method, e))
+    }
+
+    pub async fn node_info(&self) -> Result<NodeInfo, String> {
+        self.call("node_getInfo", json!([])).await
+    }
+
+    pub async fn head(&self) -> Result<Option<Block>, String> {
+        self.call("chain_getHead", json!([])).await
+    }
+
+    pub async fn block(&self, height: u64) -> Result<Option<Block>, String> {
+        self.call("chain_getBlock", json!([height])).await
+    }
+
+    pub async fn transaction(&self, hash: [u8; 32]) -> Result<Option<TransactionRecord>, String> {
+        self.call("tx_getTransaction", json!([hex::encode(hash)])).await
+    }
+
+    /// Confirmed transactions carrying exactly `memo`, newest first
+    pub async fn transactions_by_memo(&self, memo: &str, limit: u64) -> Result<Vec<TransactionRecord>, String> {
+        self.call("tx_getByMemo", json!([memo, limit])).await
+    }
+
+    /// Pending, included, finalized or orphaned; `None` if the node has never seen it
+    pub async fn transaction_status(&self, hash: [u8; 32]) -> Result<Option<TxStatus>, String> {
+        self.call("tx_getStatus", json!([hex::encode(hash)])).await
+    }
+
+    pub async fn account(&self, address: &[u8]) -> Result<Option<Account>, String> {
+        self.call("state_getAccount", json!([hex::encode(address)])).await
+    }
+
+    /// Balance of `address` after the block at `height`; older heights need an archive node
+    pub async fn balance_at(&self, address: &[u8], height: u64) -> Result<u64, String> {
+        self.call("state_getBalanceAt", json!([hex::encode(address), height])).await
+    }
+
+    /// Active registry record of a `.tri` name
+    pub async fn resolve_name(&self, name: &str) -> Result<Option<NameRecord>, String> {
+        self.call("names_resolve", json!([name])).await
+    }
+
+    /// Active names pointing at `address`
+    pub async fn reverse_names(&self, address: &[u8]) -> Result<Vec<String>, String> {
+        self.call("names_reverse", json!([hex::encode(address)])).await
+    }
+
+    /// Scheduled transfers due from height `from` through `to`, in execution order
+    pub async fn scheduled_transfers(&self, from: u64, to: u64) -> Result<Vec<ScheduledTransfer>, String> {
+        self.call("schedule_getQueue", json!([from, to])).await
+    }
+
+    /// Vested, locked and spendable balance of a vesting account; `None` for other accounts
+    pub async fn vesting(&self, address: &[u8]) -> Result<Option<VestingStatus>, String> {
+        self.call("vesting_getAccount", json!([hex::encode(address)])).await
+    }
+
+    /// Channel ends of the interop channel layer, opening or open
+    pub async fn channels(&self) -> Result<Vec<ChannelEnd>, String> {
+        self.call("interop_getChannels", json!([])).await
+    }
+
+    /// Commitment of a sent packet not yet acknowledged or timed out, with its state proof
+    pub async fn packet_commitment(&self, port: &str, channel: &str, sequence: u64) -> Result<Option<CommitmentProof>, String> {
+        self.call("interop_getPacketCommitment", json!([port, channel, sequence])).await
+    }
+
+    /// Address a user typed: a `.tri` name resolved through the registry, bech32 or hex
+    pub async fn resolve_address(&self, input: &str) -> Result<Vec<u8>, String> {
+        if names::is_name(input) {
+            return self
+                .resolve_name(input)
+                .await?
+                .map(|record| record.target)
+                .ok_or_else(|| format!("Name {} is not registered", input));
+        }
+        bech32::parse_address(input)
+    }
+
+    /// Duties of each validator in `epoch`, or the current epoch when `None`
+    pub async fn validator_performance(&self, epoch: Option<u64>) -> Result<Vec<ValidatorPerformance>, String> {
+        self.call("staking_getValidatorPerformance", json!([epoch])).await
+    }
+
+    /// Connected peers of the node, as `<node id hex>@<address>`
+    pub async fn peers(&self) -> Result<Vec<String>, String> {
+        self.call("net_getPeers", json!([])).await
+    }
+
+    /// Has the node back its database up into `dir` on its own host; needs an admin token
+    pub async fn backup(&self, dir: &str) -> Result<BackupManifest, String> {
+        self.call("admin_backup", json!([dir])).await
+    }
+
+    pub async fn logs(&self, filter: &LogFilter) -> Result<Vec<LogEntry>, String> {
+        self.call("logs_query", json!([filter])).await
+    }
+
+    /// Submits a signed transaction, returning its hash
+    pub async fn send_transaction(&self, tx: &Transaction) -> Result<[u8; 32], String> {
+        let hash: String = self.call("tx_sendRawTransaction", json!([hex::encode(tx.envelope().encode())])).await?;
+        hex::decode(&hash)
+            .ok()
+            .and_then(|hash| hash.try_into().ok())
+            .ok_or_else(|| format!("Invalid transaction hash {}", hash))
+    }
+
+    /// Opens a WebSocket and streams the header of every new block
+    pub async fn subscribe_new_heads(&self) -> Result<HeadSubscription, String> {
+        self.subscribe("chain_subscribeNewHeads", json!([])).await
+    }
+
+    /// Streams transactions in new blocks that match `filter`'s addresses and topics,
+    /// filtered by the node
+    pub async fn subscribe_logs(&self, filter: &LogFilter) -> Result<LogSubscription, String> {
+        self.subscribe("chain_subscribeLogs", json!([filter])).await
+    }
+
+    /// Streams block lifecycle changes, plus status changes of the `watched` transactions
+    pub async fn subscribe_status(&self, watched: &[[u8; 32]]) -> Result<StatusSubscription, String> {
+        let watched: Vec<String> = watched.iter().map(hex::encode).collect();
+        self.subscribe("chain_subscribeStatus", json!([watched])).await
+    }
+
+    async fn subscribe<T: DeserializeOwned + Send + 'static>(&self, method: &str, params: Value) -> Result<Subscription<T>, String> {
+        let mut request = self.socket_url.as_str().into_client_request().map_err(|e| e.to_string())?;
+        if let Some(token) = &self.token {
+            let value = format!("Bearer {}", token).parse().map_err(|_| "Invalid API token".to_string())?;
+            request.headers_mut().insert("authorization", value);
+        }
+        let (socket, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .map_err(|e| format!("Could not connect to {}: {}", self.socket_url, e))?;
+        let (mut sink, mut stream) = socket.split();
+
+        let subscribe = json!(RpcRequest::new(1, method, params)).to_string();
+        sink.send(Message::text(subscribe)).await.map_err(|e| e.to_string())?;
+        let subscription = loop {
+            let message = stream.next().await.ok_or("Connection closed before subscribing")?;
+            let Ok(Message::Text(text)) = message else {
+                continue;
+            };
+            let response: RpcResponse = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+            if let Some(error) = response.error {
+                return Err(format!("RPC error {}: {}", error.code, error.message));
+            }
+            break response.result.unwrap_or(Value::Null);
+        };
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let task = tokio::spawn(async move {
+            let _sink = sink;
+            while let Some(message) = stream.next().await {
+                let text = match message {
+                    Ok(Message::Text(text)) => text,
+                    Ok(Message::Close(_)) => break,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        let _ = sender.send(Err(e.to_string()));
+                        break;
+                    }
+                };
+                let Ok(notification) = serde_json::from_str::<Value>(&text) else {
+                    continue;
+                };
+                let params = &notification["params"];
+                if notification["method"] != "chain_subscription" || params["subscription"] != subscription {
+                    continue;
+                }
+                let item = serde_json::from_value(params["result"].clone()).map_err(|e| e.to_string());
+                if sender.send(item).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Subscription { receiver, task })
+    }
+}
+
+/// Notifications pushed by the node; dropping it closes the socket
+pub struct Subscription<T> {
+    receiver: mpsc::UnboundedReceiver<Result<T, String>>,
+    task: JoinHandle<()>,
+}
+
+pub type HeadSubscription = Subscription<BlockHeader>;
+pub type LogSubscription = Subscription<LogEntry>;
+pub type StatusSubscription = Subscription<StatusUpdate>;
+
+impl<T> Subscription<T> {
+    /// Next notification, or `None` once the connection closes
+    pub async fn next(&mut self) -> Option<Result<T, String>> {
+        self.receiver.recv().await
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+fn base_url(url: &str) -> &str {
+    let url = url.trim_end_matches('/');
+    url.strip_suffix("/rpc").unwrap_or(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::rpc::{BlockStatus, RpcServer};
+    use crate::crypto::{QuantumKeyPair, QuantumSignature};
+    use crate::node::Node;
+    use crate::storage::database::BlockchainDB;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_client_round_trip() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_client");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let keypair = QuantumKeyPair::generate();
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        db.store_genesis_allocations(&[(keypair.public_key().to_vec(), 1_000)]).unwrap();
+        let node = Arc::new(Node::open(db.clone()).unwrap());
+        let server = Arc::new(RpcServer::new(db).with_node(node.clone()));
+        let (address, serve) = warp::serve(server.routes()).bind_ephemeral(([127, 0, 0, 1], 0));
+        let serve = tokio::spawn(serve);
+
+        let client = TriUnityClient::new(&format!("http://{}/rpc/", address));
+        assert_eq!(client.node_info().await.unwrap().blocks, 0);
+        assert!(client.head().await.unwrap().is_none());
+        let mut heads = client.subscribe_new_heads().await.unwrap();
+        let watched = LogFilter { addresses: vec![hex::encode([0xee; 32])], ..LogFilter::default() };
+        let mut logs = client.subscribe_logs(&watched).await.unwrap();
+
+        let mut tx = Transaction::new(keypair.public_key().to_vec(), vec![0xee; 32], 40, 1, 0, vec![], QuantumSignature::new(vec![]));
+        tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+        let mut statuses = client.subscribe_status(&[tx.hash()]).await.unwrap();
+        assert_eq!(client.send_transaction(&tx).await.unwrap(), tx.hash());
+        assert!(client.send_transaction(&tx).await.is_err());
+        assert_eq!(client.transaction_status(tx.hash()).await.unwrap(), Some(TxStatus::Pending));
+
+        let block = node.produce_block().unwrap();
+        let header = heads.next().await.unwrap().unwrap();
+        assert_eq!(header.height, block.header.height);
+        let log = logs.next().await.unwrap().unwrap();
+        let included = TxStatus::Included { height: block.header.height, block: block.hash() };
+        let updates = [
+            StatusUpdate::Transaction { hash: hex::encode(tx.hash()), status: TxStatus::Pending },
+            StatusUpdate::Block { height: 0, hash: hex::encode(block.hash()), status: BlockStatus::Included },
+            StatusUpdate::Transaction { hash: hex::encode(tx.hash()), status: included.clone() },
+        ];
+        for update in updates {
+            assert_eq!(statuses.next().await.unwrap().unwrap(), update);
+        }
+        assert_eq!(client.transaction_status(tx.hash()).await.unwrap(), Some(included));
+        assert_eq!((log.height, log.hash), (block.header.height, hex::encode(tx.hash())));
+        let record = client.transaction(tx.hash()).await.unwrap().unwrap();
+        assert_eq!(record.height, block.header.height);
+        assert_eq!(client.account(&[0xee; 32]).await.unwrap().unwrap().balance, 40);
+        assert_eq!(client.balance_at(&[0xee; 32], block.header.height).await.unwrap(), 40);
+        assert!(client.validator_performance(None).await.unwrap().is_empty());
+
+        println!("   Client SDK working!");
+        drop(heads);
+        drop(logs);
+        drop(statuses);
+        serve.abort();
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
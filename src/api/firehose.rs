@@ -0,0 +1,390 @@
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use warp::Filter;
+
+use crate::api::rpc::SUBSCRIPTION_POLL_INTERVAL;
+use crate::consensus::duties::DutyTracker;
+use crate::consensus::validator_sets::ValidatorSets;
+use crate::node::blocking;
+use crate::storage::blocks::{Block, ConsensusData, Transaction};
+use crate::storage::bloom::transaction_topic;
+use crate::storage::database::BlockchainDB;
+use crate::storage::state::StateManager;
+use crate::storage::trace::{self, AccountDiff, CallFrame, TraceStep};
+
+/// Most blocks one poll decodes, so a stream far behind the head still flushes often
+pub const MAX_BATCH: u64 = 100;
+
+/// Most recent blocks a stream remembers, and so the deepest reorg it can undo
+pub const MAX_UNDO_DEPTH: usize = 128;
+
+/// Position in the stream: the last block an indexer processed, as `<height>:<hash>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Cursor {
+    pub height: u64,
+    pub hash: [u8; 32],
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.height, hex::encode(self.hash))
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid cursor: {} (expected <height>:<block hash>)", s);
+        let (height, hash) = s.split_once(':').ok_or_else(invalid)?;
+        let hash = hex::decode(hash).ok().and_then(|hash| hash.try_into().ok()).ok_or_else(invalid)?;
+        Ok(Self { height: height.parse().map_err(|_| invalid())?, hash })
+    }
+}
+
+impl From<Cursor> for String {
+    fn from(cursor: Cursor) -> Self {
+        cursor.to_string()
+    }
+}
+
+impl TryFrom<String> for Cursor {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// What executing a transaction did besides its state diff
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Receipt {
+    #[serde(with = "hex::serde")]
+    pub fee_payer: Vec<u8>,
+    pub fee: u64,
+    /// Method selector of a contract call
+    pub topic: Option<String>,
+    /// System contract the transaction called, if any
+    pub contract: Option<String>,
+    /// Transfers the system contract made on the transaction's behalf
+    pub transfers: Vec<CallFrame>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecodedTransaction {
+    #[serde(with = "hex::serde")]
+    pub hash: [u8; 32],
+    pub index: usize,
+    #[serde(with = "hex::serde")]
+    pub from: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub to: Vec<u8>,
+    pub amount: u64,
+    pub fee: u64,
+    pub nonce: u64,
+    #[serde(with = "hex::serde")]
+    pub data: Vec<u8>,
+    pub valid_until_height: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    pub receipt: Receipt,
+    pub state_diff: Vec<AccountDiff>,
+}
+
+impl DecodedTransaction {
+    pub fn new(tx: &Transaction, index: usize, steps: &[TraceStep]) -> Self {
+        let call = trace::call_tree(tx, steps);
+        Self {
+            hash: tx.hash(),
+            index,
+            from: tx.from.clone(),
+            to: tx.to.clone(),
+            amount: tx.amount,
+            fee: tx.fee,
+            nonce: tx.nonce,
+            data: tx.data.clone(),
+            valid_until_height: tx.valid_until_height,
+            memo: tx.memo.clone(),
+            receipt: Receipt {
+                fee_payer: tx.fee_account().to_vec(),
+                fee: tx.fee,
+                topic: transaction_topic(tx).map(hex::encode),
+                contract: call.contract,
+                transfers: call.calls,
+            },
+            state_diff: trace::state_diff(steps),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecodedBlock {
+    pub height: u64,
+    #[serde(with = "hex::serde")]
+    pub hash: [u8; 32],
+    #[serde(with = "hex::serde")]
+    pub parent_hash: [u8; 32],
+    #[serde(with = "hex::serde")]
+    pub state_root: [u8; 32],
+    pub timestamp: u64,
+    /// Consensus path the block was produced on
+    pub path: String,
+    /// Fast-lane proposer
+    pub proposer: Option<String>,
+    pub transactions: Vec<DecodedTransaction>,
+}
+
+impl DecodedBlock {
+    /// `consensus` is the block's consensus data with validator references resolved, and
+    /// `traces` holds each transaction's steps, as `StateManager::trace_block` returns them
+    pub fn new(block: &Block, consensus: &ConsensusData, traces: &[Vec<TraceStep>]) -> Self {
+        let (path, proposer) = match consensus {
+            ConsensusData::FastLane { validator, .. } => ("fast_lane", Some(hex::encode(validator))),
+            ConsensusData::FastLaneRef { .. } => ("fast_lane", None),
+            ConsensusData::SecureLane { .. } | ConsensusData::SecureLaneRef { .. } => ("secure_lane", None),
+            ConsensusData::HybridPath { .. } | ConsensusData::HybridPathRef { .. } => ("hybrid_path", None),
+            ConsensusData::Emergency { .. } | ConsensusData::EmergencyRef { .. } => ("emergency", None),
+        };
+        Self {
+            height: block.header.height,
+            hash: block.hash(),
+            parent_hash: block.header.previous_hash,
+            state_root: block.header.state_root,
+            timestamp: block.header.timestamp,
+            path: path.to_string(),
+            proposer,
+            transactions: block
+                .transactions
+                .iter()
+                .zip(traces)
+                .enumerate()
+                .map(|(index, (tx, steps))| DecodedTransaction::new(tx, index, steps))
+                .collect(),
+        }
+    }
+}
+
+/// One line of the stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum FirehoseStep {
+    /// A block joined the canonical chain; store `cursor` once it is indexed
+    New { cursor: Cursor, block: Box<DecodedBlock> },
+    /// A block sent earlier left the canonical chain; revert it and store `cursor`,
+    /// its parent's, which is `None` for the first block
+    Undo {
+        height: u64,
+        #[serde(with = "hex::serde")]
+        hash: [u8; 32],
+        cursor: Option<Cursor>,
+    },
+}
+
+/// Canonical blocks in order, decoded and executed, for an indexer to follow instead of
+/// polling RPC. Blocks leaving the canonical chain are undone before the new ones are sent
+pub struct Firehose {
+    db: BlockchainDB,
+    validator_sets: ValidatorSets,
+    /// State after the last block sent
+    state: StateManager,
+    next: u64,
+    /// Blocks sent, newest last, with their parents' hashes
+    sent: VecDeque<(Cursor, [u8; 32])>,
+    /// Whether older blocks were dropped from `sent`
+    truncated: bool,
+}
+
+impl Firehose {
+    /// Stream resuming after `cursor`, or starting from the first block state is stored for
+    pub fn open(db: BlockchainDB, cursor: Option<Cursor>) -> Result<Self, String> {
+        let validator_sets = ValidatorSets::open(&db)?;
+        let Some(cursor) = cursor else {
+            let (state, next) = StateManager::base(&db)?;
+            return Ok(Self { db, validator_sets, state, next, sent: VecDeque::new(), truncated: false });
+        };
+        let block = db.get_block(cursor.height)?.filter(|block| block.hash() == cursor.hash).ok_or_else(|| {
+            format!("Cursor {} is not on the canonical chain; resume from an earlier cursor", cursor)
+        })?;
+        let state = StateManager::replay_to(&db, Some(cursor.height))?;
+        let sent = VecDeque::from([(cursor, block.header.previous_hash)]);
+        Ok(Self { db, validator_sets, state, next: cursor.height + 1, sent, truncated: true })
+    }
+
+    /// Undos for sent blocks no longer canonical, then up to `MAX_BATCH` new blocks
+    pub fn poll(&mut self) -> Result<Vec<FirehoseStep>, String> {
+        let mut steps = Vec::new();
+        while let Some((cursor, parent)) = self.sent.back().copied() {
+            if self.db.get_block(cursor.height)?.is_some_and(|block| block.hash() == cursor.hash) {
+                break;
+            }
+            self.sent.pop_back();
+            self.next = cursor.height;
+            let parent = cursor.height.checked_sub(1).map(|height| Cursor { height, hash: parent });
+            steps.push(FirehoseStep::Undo { height: cursor.height, hash: cursor.hash, cursor: parent });
+        }
+        if !steps.is_empty() {
+            if self.sent.is_empty() && self.truncated {
+                return Err(format!("Reorg deeper than {} blocks; resume from an earlier cursor", MAX_UNDO_DEPTH));
+            }
+            self.state = StateManager::replay_to(&self.db, self.next.checked_sub(1))?;
+        }
+
+        if self.db.block_count()? == 0 {
+            return Ok(steps);
+        }
+        let latest = self.db.get_latest_height()?;
+        for height in self.next..=latest.min(self.next + MAX_BATCH - 1) {
+            let Some(block) = self.db.get_block(height)? else {
+                break;
+            };
+            // The chain moved under this read; the next poll undoes what no longer links
+            if self.sent.back().is_some_and(|(cursor, _)| cursor.hash != block.header.previous_hash) {
+                break;
+            }
+            let traces = self.state.trace_block(&block).map_err(|(_, e)| format!("Block {}: {}", height, e))?;
+            let consensus = self
+                .validator_sets
+                .resolve(DutyTracker::epoch_of(height), &block.header.consensus_data)
+                .unwrap_or_else(|_| block.header.consensus_data.clone());
+            let cursor = Cursor { height, hash: block.hash() };
+            steps.push(FirehoseStep::New { cursor, block: Box::new(DecodedBlock::new(&block, &consensus, &traces)) });
+            self.sent.push_back((cursor, block.header.previous_hash));
+            if self.sent.len() > MAX_UNDO_DEPTH {
+                self.sent.pop_front();
+                self.truncated = true;
+            }
+            self.next = height + 1;
+        }
+        Ok(steps)
+    }
+
+    /// Steps as newline-delimited JSON, polling the chain for new blocks until the client
+    /// disconnects. A failure is sent as a final `{"error": ...}` line
+    pub fn stream(self) -> impl Stream<Item = Result<Vec<u8>, std::io::Error>> + Send {
+        let ticker = tokio::time::interval(SUBSCRIPTION_POLL_INTERVAL);
+        futures::stream::unfold((Some(self), ticker, false), |(firehose, mut ticker, behind)| async move {
+            let mut firehose = firehose?;
+            loop {
+                if !behind {
+                    ticker.tick().await;
+                }
+                let polled = blocking(move || {
+                    let result = firehose.poll();
+                    (firehose, result)
+                })
+                .await;
+                let (returned, result) = match polled {
+                    Ok(polled) => polled,
+                    Err(e) => return Some((Ok(ndjson(&[serde_json::json!({ "error": e })])), (None, ticker, false))),
+                };
+                firehose = returned;
+                match result {
+                    Ok(steps) if steps.is_empty() => continue,
+                    Ok(steps) => {
+                        let behind = steps.len() as u64 >= MAX_BATCH;
+                        return Some((Ok(ndjson(&steps)), (Some(firehose), ticker, behind)));
+                    }
+                    Err(e) => return Some((Ok(ndjson(&[serde_json::json!({ "error": e })])), (None, ticker, false))),
+                }
+            }
+        })
+    }
+}
+
+fn ndjson<T: Serialize>(lines: &[T]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for line in lines {
+        if serde_json::to_writer(&mut bytes, line).is_ok() {
+            bytes.push(b'\n');
+        }
+    }
+    bytes
+}
+
+/// Query string of `GET /api/firehose`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FirehoseQuery {
+    pub cursor: Option<String>,
+}
+
+/// `GET /api/firehose?cursor=<height>:<hash>`: the firehose as `application/x-ndjson`,
+/// after `cursor` or from the first block
+pub fn routes(db: BlockchainDB) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("api" / "firehose")
+        .and(warp::get())
+        .and(warp::query::<FirehoseQuery>())
+        .then(move |query: FirehoseQuery| {
+            let db = db.clone();
+            async move {
+                let opened = blocking(move || {
+                    let cursor = query.cursor.as_deref().map(str::parse).transpose()?;
+                    Firehose::open(db, cursor)
+                })
+                .await
+                .flatten();
+                match opened {
+                    Ok(firehose) => warp::http::Response::builder()
+                        .header("content-type", "application/x-ndjson")
+                        .body(hyper::Body::wrap_stream(firehose.stream()))
+                        .unwrap(),
+                    Err(e) => warp::http::Response::builder()
+                        .status(409)
+                        .body(hyper::Body::from(e))
+                        .unwrap(),
+                }
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumSignature;
+    use crate::storage::blocks::Transaction;
+
+    #[test]
+    fn test_firehose_resumes_and_undoes() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_firehose");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        db.store_genesis_allocations(&[(vec![0xaa; 32], 1_000)]).unwrap();
+        let genesis = Block::new([0; 32], vec![], 0, ConsensusData::default());
+        db.store_block(&genesis).unwrap();
+        let transfer = Transaction::new(vec![0xaa; 32], vec![0xbb; 32], 100, 1, 0, vec![], QuantumSignature::new(vec![]));
+        let first = Block::new(genesis.hash(), vec![transfer], 1, ConsensusData::default());
+        db.store_block(&first).unwrap();
+
+        let mut firehose = Firehose::open(db.clone(), None).unwrap();
+        let steps = firehose.poll().unwrap();
+        assert_eq!(steps.len(), 2);
+        let FirehoseStep::New { cursor, block } = &steps[1] else {
+            panic!("Expected a new block");
+        };
+        assert_eq!(*cursor, Cursor { height: 1, hash: first.hash() });
+        let diff = &block.transactions[0].state_diff;
+        assert_eq!((diff.len(), diff[1].balance.unwrap().to), (2, 100));
+        assert!(firehose.poll().unwrap().is_empty());
+
+        // A resumed stream sends what follows its cursor
+        let cursor: Cursor = cursor.to_string().parse().unwrap();
+        let second = Block::new(first.hash(), vec![], 2, ConsensusData::default());
+        db.store_block(&second).unwrap();
+        let mut resumed = Firehose::open(db.clone(), Some(cursor)).unwrap();
+        assert!(matches!(&resumed.poll().unwrap()[..], [FirehoseStep::New { cursor, .. }] if cursor.height == 2));
+
+        // Replacing block 2 undoes it before the replacement is sent
+        let replacement = Block::new(first.hash(), vec![], 2, ConsensusData::SecureLane { validators: vec![] });
+        db.store_block(&replacement).unwrap();
+        let steps = resumed.poll().unwrap();
+        assert!(matches!(&steps[0], FirehoseStep::Undo { height: 2, cursor: Some(parent), .. } if *parent == cursor));
+        assert!(matches!(&steps[1], FirehoseStep::New { block, .. } if block.hash == replacement.hash()));
+        assert!(Firehose::open(db, Some(Cursor { height: 2, hash: second.hash() })).is_err());
+        assert!("2:zz".parse::<Cursor>().is_err());
+
+        println!("   Firehose streaming working!");
+    }
+}
@@ -0,0 +1,407 @@
+use futures::stream::{self, StreamExt};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tonic::body::BoxBody;
+use tonic::codec::ProstCodec;
+use tonic::codegen::{http, Body, BoxFuture, BoxStream, Context, Future, Poll, Service, StdError};
+use tonic::server::{Grpc, NamedService};
+use tonic::{Request, Response, Status};
+
+use crate::events::NodeEvent;
+use crate::mempool::MempoolUpdate;
+use crate::node::{blocking, Node};
+use crate::storage::blocks::{self, Block as ChainBlock};
+use crate::storage::database::BlockchainDB;
+use crate::storage::envelope::TxEnvelope;
+use crate::storage::state::StateManager;
+use crate::storage::{ChainStorage, TriUnityStorage};
+
+/// Messages of `proto/triunity.proto`, kept in step with it by hand
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetBlockRequest {
+    #[prost(uint64, optional, tag = "1")]
+    pub height: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Block {
+    #[prost(uint64, tag = "1")]
+    pub height: u64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub hash: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub parent_hash: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub state_root: Vec<u8>,
+    #[prost(uint64, tag = "5")]
+    pub timestamp: u64,
+    #[prost(message, repeated, tag = "6")]
+    pub transactions: Vec<Transaction>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Transaction {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hash: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub from: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub to: Vec<u8>,
+    #[prost(uint64, tag = "4")]
+    pub amount: u64,
+    #[prost(uint64, tag = "5")]
+    pub fee: u64,
+    #[prost(uint64, tag = "6")]
+    pub nonce: u64,
+    #[prost(string, optional, tag = "7")]
+    pub memo: Option<String>,
+    #[prost(uint64, optional, tag = "8")]
+    pub height: Option<u64>,
+    #[prost(uint64, optional, tag = "9")]
+    pub index: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetTransactionRequest {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hash: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetAccountRequest {
+    #[prost(bytes = "vec", tag = "1")]
+    pub address: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Account {
+    #[prost(bytes = "vec", tag = "1")]
+    pub address: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub balance: u64,
+    #[prost(uint64, tag = "3")]
+    pub nonce: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SubmitTransactionRequest {
+    #[prost(bytes = "vec", tag = "1")]
+    pub envelope: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SubmitTransactionReply {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hash: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SubscribeBlocksRequest {
+    #[prost(uint64, optional, tag = "1")]
+    pub from_height: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SubscribeTxsRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TxUpdate {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hash: Vec<u8>,
+    #[prost(bool, tag = "2")]
+    pub admitted: bool,
+    #[prost(uint64, tag = "3")]
+    pub fee: u64,
+    #[prost(string, tag = "4")]
+    pub reason: String,
+}
+
+impl Transaction {
+    fn new(tx: &blocks::Transaction, position: Option<(u64, usize)>) -> Self {
+        Self {
+            hash: tx.hash().to_vec(),
+            from: tx.from.clone(),
+            to: tx.to.clone(),
+            amount: tx.amount,
+            fee: tx.fee,
+            nonce: tx.nonce,
+            memo: tx.memo.clone(),
+            height: position.map(|(height, _)| height),
+            index: position.map(|(_, index)| index as u64),
+        }
+    }
+}
+
+impl From<&ChainBlock> for Block {
+    fn from(block: &ChainBlock) -> Self {
+        let height = block.header.height;
+        Self {
+            height,
+            hash: block.hash().to_vec(),
+            parent_hash: block.header.previous_hash.to_vec(),
+            state_root: block.header.state_root.to_vec(),
+            timestamp: block.header.timestamp,
+            transactions: block.transactions.iter().enumerate().map(|(index, tx)| Transaction::new(tx, Some((height, index)))).collect(),
+        }
+    }
+}
+
+impl From<MempoolUpdate> for TxUpdate {
+    fn from(update: MempoolUpdate) -> Self {
+        match update {
+            MempoolUpdate::Admitted { hash, fee, .. } => Self { hash: hash.to_vec(), admitted: true, fee, reason: String::new() },
+            MempoolUpdate::Evicted { hash, reason } => Self { hash: hash.to_vec(), admitted: false, fee: 0, reason: format!("{:?}", reason) },
+        }
+    }
+}
+
+fn internal(e: String) -> Status {
+    Status::internal(e)
+}
+
+fn unavailable() -> Status {
+    Status::unavailable("This endpoint runs no node")
+}
+
+/// Adapts a method of `GrpcService` to the service tonic dispatches a call to
+#[derive(Clone)]
+struct Method<F>(F);
+
+impl<F, M, R, Fut> Service<Request<M>> for Method<F>
+where
+    F: FnMut(Request<M>) -> Fut,
+    Fut: Future<Output = Result<Response<R>, Status>>,
+{
+    type Response = Response<R>;
+    type Error = Status;
+    type Future = Fut;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Status>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<M>) -> Fut {
+        (self.0)(request)
+    }
+}
+
+/// gRPC access to the chain for exchanges and indexers: unary block, transaction and
+/// account queries, transaction submission, and block and mempool streams. It reads the
+/// same database and node as JSON-RPC
+#[derive(Clone)]
+pub struct GrpcService {
+    storage: TriUnityStorage,
+    node: Option<Arc<Node>>,
+}
+
+impl GrpcService {
+    pub fn new(db: BlockchainDB) -> Self {
+        Self { storage: TriUnityStorage::from_db(db), node: None }
+    }
+
+    /// Accepts transactions into `node`'s mempool and streams its blocks and updates
+    pub fn with_node(mut self, node: Arc<Node>) -> Self {
+        self.node = Some(node);
+        self
+    }
+
+    fn node(&self) -> Option<&Arc<Node>> {
+        self.node.as_ref()
+    }
+
+    pub async fn get_block(self, request: Request<GetBlockRequest>) -> Result<Response<Block>, Status> {
+        let height = request.into_inner().height;
+        let block = match height {
+            Some(height) => self.storage.block(height).await,
+            None => self.storage.latest_block().await,
+        }
+        .map_err(internal)?;
+        let block = block.ok_or_else(|| Status::not_found("No such block"))?;
+        Ok(Response::new(Block::from(&block)))
+    }
+
+    pub async fn get_transaction(self, request: Request<GetTransactionRequest>) -> Result<Response<Transaction>, Status> {
+        let hash: [u8; 32] = request.into_inner().hash.try_into().map_err(|_| Status::invalid_argument("Hashes are 32 bytes"))?;
+        let found = self.storage.transaction(hash).await.map_err(internal)?;
+        let (tx, height, index) = found.ok_or_else(|| Status::not_found("No such transaction"))?;
+        Ok(Response::new(Transaction::new(&tx, Some((height, index)))))
+    }
+
+    /// Account at the head of the chain; unknown addresses have a zero balance
+    pub async fn get_account(self, request: Request<GetAccountRequest>) -> Result<Response<Account>, Status> {
+        let address = request.into_inner().address;
+        let address_key = address.clone();
+        let (balance, nonce) = blocking(move || -> Result<(u64, u64), String> {
+            let state = StateManager::replay(self.storage.db())?;
+            Ok(state.get_account(&address_key).map_or((0, 0), |account| (account.balance, account.nonce)))
+        })
+        .await
+        .flatten()
+        .map_err(internal)?;
+        Ok(Response::new(Account { address, balance, nonce }))
+    }
+
+    pub async fn submit_transaction(self, request: Request<SubmitTransactionRequest>) -> Result<Response<SubmitTransactionReply>, Status> {
+        let envelope = TxEnvelope::decode(&request.into_inner().envelope).map_err(Status::invalid_argument)?;
+        let node = self.node().ok_or_else(unavailable)?.clone();
+        let hash = blocking(move || node.submit_transaction(envelope.into())).await.flatten().map_err(Status::failed_precondition)?;
+        Ok(Response::new(SubmitTransactionReply { hash: hash.to_vec() }))
+    }
+
+    /// Blocks by height from `from_height`, catching up from the database and then
+    /// waking on every import
+    pub async fn subscribe_blocks(self, request: Request<SubscribeBlocksRequest>) -> Result<Response<BoxStream<Block>>, Status> {
+        let events = self.node().ok_or_else(unavailable)?.events().subscribe();
+        let next = match request.into_inner().from_height {
+            Some(height) => height,
+            None => self.storage.block_count().await.map_err(internal)?,
+        };
+        let blocks = stream::unfold((self.storage, next, events), |(storage, next, mut events)| async move {
+            loop {
+                match storage.block(next).await {
+                    Ok(Some(block)) => return Some((Ok(Block::from(&block)), (storage, next + 1, events))),
+                    Ok(None) => {}
+                    Err(e) => return Some((Err(internal(e)), (storage, next, events))),
+                }
+                loop {
+                    match events.recv().await {
+                        Ok(NodeEvent::BlockImported { .. }) | Err(broadcast::error::RecvError::Lagged(_)) => break,
+                        Ok(_) => {}
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            }
+        });
+        Ok(Response::new(blocks.boxed()))
+    }
+
+    pub async fn subscribe_txs(self, _request: Request<SubscribeTxsRequest>) -> Result<Response<BoxStream<TxUpdate>>, Status> {
+        let updates = self.node().ok_or_else(unavailable)?.subscribe_mempool();
+        let updates = stream::unfold(updates, |mut updates| async move {
+            loop {
+                match updates.recv().await {
+                    Ok(update) => return Some((Ok(TxUpdate::from(update)), updates)),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+        Ok(Response::new(updates.boxed()))
+    }
+
+    /// Serves the service over HTTP/2 on `address` until the server fails
+    pub async fn serve(self, address: SocketAddr) -> Result<(), String> {
+        tonic::transport::Server::builder()
+            .add_service(self)
+            .serve(address)
+            .await
+            .map_err(|e| format!("gRPC server on {} failed: {}", address, e))
+    }
+}
+
+impl NamedService for GrpcService {
+    const NAME: &'static str = "triunity.v1.Chain";
+}
+
+impl<B> Service<http::Request<B>> for GrpcService
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let service = self.clone();
+        Box::pin(async move {
+            let response = match request.uri().path() {
+                "/triunity.v1.Chain/GetBlock" => {
+                    Grpc::new(ProstCodec::default()).unary(Method(move |r| service.clone().get_block(r)), request).await
+                }
+                "/triunity.v1.Chain/GetTransaction" => {
+                    Grpc::new(ProstCodec::default()).unary(Method(move |r| service.clone().get_transaction(r)), request).await
+                }
+                "/triunity.v1.Chain/GetAccount" => {
+                    Grpc::new(ProstCodec::default()).unary(Method(move |r| service.clone().get_account(r)), request).await
+                }
+                "/triunity.v1.Chain/SubmitTransaction" => {
+                    Grpc::new(ProstCodec::default()).unary(Method(move |r| service.clone().submit_transaction(r)), request).await
+                }
+                "/triunity.v1.Chain/SubscribeBlocks" => {
+                    Grpc::new(ProstCodec::default()).server_streaming(Method(move |r| service.clone().subscribe_blocks(r)), request).await
+                }
+                "/triunity.v1.Chain/SubscribeTxs" => {
+                    Grpc::new(ProstCodec::default()).server_streaming(Method(move |r| service.clone().subscribe_txs(r)), request).await
+                }
+                _ => Status::unimplemented(format!("Unknown method {}", request.uri().path())).into_http(),
+            };
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{QuantumKeyPair, QuantumSignature};
+    use tonic::codegen::http::uri::PathAndQuery;
+
+    #[tokio::test]
+    async fn test_grpc_service() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_grpc");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let keypair = QuantumKeyPair::generate();
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        db.store_genesis_allocations(&[(keypair.public_key().to_vec(), 1_000)]).unwrap();
+        let node = Arc::new(Node::open(db.clone()).unwrap());
+        let service = GrpcService::new(db).with_node(node.clone());
+
+        // Submissions show up on the mempool stream and then in a streamed block
+        let mut updates = service.clone().subscribe_txs(Request::new(SubscribeTxsRequest {})).await.unwrap().into_inner();
+        let mut blocks = service.clone().subscribe_blocks(Request::new(SubscribeBlocksRequest { from_height: None })).await.unwrap().into_inner();
+        let mut tx = blocks::Transaction::new(keypair.public_key().to_vec(), vec![0xaa; 32], 10, 1, 0, vec![], QuantumSignature::new(vec![]));
+        tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+        let request = SubmitTransactionRequest { envelope: TxEnvelope::from(tx.clone()).encode() };
+        let reply = service.clone().submit_transaction(Request::new(request)).await.unwrap().into_inner();
+        assert_eq!(reply.hash, tx.hash().to_vec());
+        let update = updates.next().await.unwrap().unwrap();
+        assert!(update.admitted && update.hash == reply.hash);
+
+        let produced = node.produce_block().unwrap();
+        let streamed = blocks.next().await.unwrap().unwrap();
+        assert_eq!((streamed.height, streamed.hash.clone()), (produced.header.height, produced.hash().to_vec()));
+        assert_eq!(streamed.transactions[0].hash, reply.hash);
+
+        let found = service.clone().get_transaction(Request::new(GetTransactionRequest { hash: reply.hash.clone() })).await.unwrap();
+        assert_eq!(found.into_inner().height, Some(produced.header.height));
+        let account = service.clone().get_account(Request::new(GetAccountRequest { address: keypair.public_key().to_vec() })).await.unwrap();
+        assert_eq!(account.into_inner().nonce, 1);
+        let missing = service.clone().get_block(Request::new(GetBlockRequest { height: Some(99) })).await.unwrap_err();
+        assert_eq!(missing.code(), tonic::Code::NotFound);
+
+        // Over the wire, through a plain client
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        tokio::spawn(service.serve(address));
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        let channel = tonic::transport::Endpoint::from_shared(format!("http://{}", address)).unwrap().connect().await.unwrap();
+        let mut client = tonic::client::Grpc::new(channel);
+        client.ready().await.unwrap();
+        let head: Response<Block> = client
+            .unary(Request::new(GetBlockRequest { height: None }), PathAndQuery::from_static("/triunity.v1.Chain/GetBlock"), ProstCodec::default())
+            .await
+            .unwrap();
+        assert_eq!(head.into_inner().hash, produced.hash().to_vec());
+
+        println!("   gRPC service working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -0,0 +1,9 @@
+pub mod client;
+pub mod export;
+pub mod firehose;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod rate_limit;
+pub mod rpc;
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::RateLimitConfig;
+
+pub const LIMIT_EXCEEDED: i64 = -32005;
+
+/// Buckets tracked before idle, fully refilled ones are pruned
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Built-in cost of each RPC method in bucket units; unlisted methods cost 1
+const METHOD_COSTS: &[(&str, u32)] = &[
+    ("tx_sendRawTransaction", 20),
+    ("state_getAccount", 10),
+    ("state_getBalanceAt", 10),
+    ("names_resolve", 10),
+    ("names_reverse", 10),
+    ("schedule_getQueue", 10),
+    ("vesting_getAccount", 10),
+    ("interop_getChannels", 10),
+    ("interop_getPacketCommitment", 10),
+    ("tx_callAt", 20),
+    ("debug_traceTransaction", 50),
+    ("debug_traceBlock", 100),
+    ("logs_query", 10),
+    ("staking_getValidatorPerformance", 5),
+    ("consensus_getDecisionHistory", 5),
+    ("mempool_content", 10),
+    ("mempool_inspect", 5),
+    ("chain_getBlock", 2),
+    ("tx_getTransaction", 2),
+    ("tx_getStatus", 2),
+    ("tx_getInclusionProof", 5),
+    ("tx_getByMemo", 10),
+    ("chain_subscribeNewHeads", 5),
+    ("chain_subscribeLogs", 5),
+    ("chain_subscribeStatus", 5),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    Ip(IpAddr),
+    Token(String),
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token-bucket quotas per client IP and API token
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<RateLimitKey, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn max_payload_bytes(&self) -> u64 {
+        self.config.max_payload_bytes
+    }
+
+    /// Bucket for a request from `ip`, using the token's own quota when it is a known token
+    pub fn key(&self, ip: Option<IpAddr>, token: Option<&str>) -> RateLimitKey {
+        match token {
+            Some(token) if self.config.tokens.iter().any(|known| known == token) => RateLimitKey::Token(token.to_string()),
+            _ => RateLimitKey::Ip(ip.unwrap_or(IpAddr::from([0, 0, 0, 0]))),
+        }
+    }
+
+    pub fn method_cost(&self, method: &str) -> u32 {
+        self.config
+            .method_costs
+            .get(method)
+            .copied()
+            .or_else(|| METHOD_COSTS.iter().find(|(name, _)| *name == method).map(|(_, cost)| *cost))
+            .unwrap_or(1)
+    }
+
+    /// Spends the cost of `method`, or returns how long to wait before retrying
+    pub fn check(&self, key: &RateLimitKey, method: &str) -> Result<(), Duration> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let (rate, burst) = match key {
+            RateLimitKey::Ip(_) => (self.config.ip_rate, self.config.ip_burst),
+            RateLimitKey::Token(_) => (self.config.token_rate, self.config.token_burst),
+        };
+        let (rate, burst) = (rate as f64, burst as f64);
+        // Methods dearer than the whole burst would otherwise never be allowed
+        let cost = (self.method_cost(method) as f64).min(burst);
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            buckets.retain(|_, bucket| bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < burst);
+        }
+        let bucket = buckets.entry(key.clone()).or_insert(Bucket { tokens: burst, updated: now });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate).min(burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= cost {
+            bucket.tokens -= cost;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((cost - bucket.tokens) / rate))
+        }
+    }
+}
+
+/// Whole seconds for a `Retry-After` header, never zero
+pub fn retry_after_secs(wait: Duration) -> u64 {
+    wait.as_secs_f64().ceil().max(1.0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limits_and_costs() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            ip_rate: 1,
+            ip_burst: 20,
+            token_rate: 100,
+            token_burst: 100,
+            tokens: vec!["secret".to_string()],
+            method_costs: HashMap::from([("node_getInfo".to_string(), 4)]),
+            ..RateLimitConfig::default()
+        });
+        let ip = limiter.key(Some(IpAddr::from([10, 0, 0, 1])), None);
+        assert_eq!(limiter.key(Some(IpAddr::from([10, 0, 0, 1])), Some("guess")), ip);
+        let token = limiter.key(Some(IpAddr::from([10, 0, 0, 1])), Some("secret"));
+        assert_eq!(token, RateLimitKey::Token("secret".to_string()));
+
+        assert_eq!(limiter.method_cost("node_getInfo"), 4);
+        assert_eq!(limiter.method_cost("tx_sendRawTransaction"), 20);
+        assert_eq!(limiter.method_cost("net_getPeers"), 1);
+
+        // A raw transaction drains the whole IP burst; the next call must wait
+        assert!(limiter.check(&ip, "tx_sendRawTransaction").is_ok());
+        let wait = limiter.check(&ip, "node_getInfo").unwrap_err();
+        assert_eq!(retry_after_secs(wait), 4);
+        let other = limiter.key(Some(IpAddr::from([10, 0, 0, 2])), None);
+        assert!(limiter.check(&other, "node_getInfo").is_ok());
+        assert!((0..5).all(|_| limiter.check(&token, "tx_sendRawTransaction").is_ok()));
+        assert!(limiter.check(&token, "tx_sendRawTransaction").is_err());
+
+        let disabled = RateLimiter::new(RateLimitConfig { enabled: false, ip_burst: 1, ..RateLimitConfig::default() });
+        assert!((0..10).all(|_| disabled.check(&ip, "logs_query").is_ok()));
+
+        println!("   RPC rate limiting working!");
+    }
+}
@@ -0,0 +1,1500 @@
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use warp::ws::{Message, WebSocket};
+use warp::Filter;
+
+use crate::api::rate_limit::{retry_after_secs, RateLimitKey, RateLimiter, LIMIT_EXCEEDED};
+use crate::config::RateLimitConfig;
+use crate::consensus::decisions::DecisionLog;
+use crate::consensus::fork_choice::{ForkChoiceHead, FINALITY_DEPTH};
+use crate::consensus::pin::PinnedMode;
+use crate::crypto::bech32;
+use crate::crypto::hash::constant_time_eq;
+use crate::crypto::QuantumKeyPair;
+use crate::events::NodeEvent;
+use crate::interop::channel::{Channels, CommitmentProof};
+use crate::log;
+use crate::logging;
+use crate::mempool::PendingSummary;
+use crate::network::{light, NetworkService};
+use crate::node::{blocking, Node, TxStatus};
+use crate::storage::blocks::{Block, BlockHeader, Transaction};
+use crate::storage::envelope::TxEnvelope;
+use crate::storage::bloom::{transaction_addresses, transaction_topic};
+use crate::storage::database::BlockchainDB;
+use crate::storage::state::StateManager;
+use crate::storage::state_store::StateStore;
+use crate::storage::trace::{self, TraceDetail};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+/// The transaction was rejected by the mempool, or this endpoint accepts none
+pub const TRANSACTION_REJECTED: i64 = -32003;
+/// The requested height's state has been pruned; archive nodes keep every height
+pub const STATE_NOT_RETAINED: i64 = -32004;
+/// An admin method was called without an admin token
+pub const UNAUTHORIZED: i64 = -32006;
+/// The method needs a node or network service this endpoint does not run, or its
+/// namespace is disabled here
+pub const NOT_AVAILABLE: i64 = -32007;
+
+/// Most blocks a single `logs_query` may scan
+pub const MAX_LOG_RANGE: u64 = 10_000;
+
+/// Most pending transactions a `mempool_content` or `mempool_inspect` response lists
+pub const MAX_MEMPOOL_ENTRIES: usize = 1_000;
+
+/// Most consensus decisions one `consensus_getDecisionHistory` page holds
+pub const MAX_DECISION_PAGE: u64 = 100;
+
+/// Most transactions one `tx_getByMemo` response lists
+pub const MAX_MEMO_MATCHES: u64 = 100;
+
+/// How often WebSocket subscriptions check the chain for new blocks
+pub const SUBSCRIPTION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Most messages waiting to be written to one WebSocket; past it, responses and
+/// subscriptions wait for the client to catch up
+pub const SOCKET_QUEUE_LEN: usize = 256;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcRequest {
+    pub jsonrpc: String,
+    pub id: Value,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+/// `logs_query` and `chain_subscribeLogs` filter. A transaction matches when it touches
+/// one of `addresses` (bech32 or hex) and, for contract calls, its selector is one of
+/// `topics`; empty lists match all. Subscriptions ignore the block range
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogFilter {
+    pub from_block: Option<u64>,
+    pub to_block: Option<u64>,
+    pub addresses: Vec<String>,
+    pub topics: Vec<String>,
+}
+
+/// A `LogFilter`'s addresses and topics, decoded
+struct LogMatcher {
+    addresses: Vec<Vec<u8>>,
+    topics: Vec<Vec<u8>>,
+}
+
+impl LogMatcher {
+    fn new(filter: &LogFilter) -> Result<Self, RpcError> {
+        let decode = |items: &[String]| -> Result<Vec<Vec<u8>>, RpcError> {
+            items
+                .iter()
+                .map(|item| hex::decode(item.trim_start_matches("0x")).map_err(|e| invalid_params(&e.to_string())))
+                .collect()
+        };
+        let addresses = filter
+            .addresses
+            .iter()
+            .map(|address| bech32::parse_address(address).map_err(|e| invalid_params(&e)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            addresses,
+            topics: decode(&filter.topics)?,
+        })
+    }
+
+    /// False when the block's bloom rules out every requested address or topic
+    fn may_match(&self, header: &BlockHeader) -> bool {
+        (self.addresses.is_empty() || header.may_contain_any(&self.addresses))
+            && (self.topics.is_empty() || header.may_contain_any(&self.topics))
+    }
+
+    fn matches(&self, tx: &Transaction) -> bool {
+        let address_match = self.addresses.is_empty()
+            || transaction_addresses(tx).any(|address| self.addresses.iter().any(|wanted| wanted == address));
+        let topic_match = self.topics.is_empty()
+            || transaction_topic(tx).is_some_and(|topic| self.topics.iter().any(|wanted| wanted == topic));
+        address_match && topic_match
+    }
+
+    /// Log entries for the block's matching transactions
+    fn logs(&self, block: &Block) -> Vec<Value> {
+        if !self.may_match(&block.header) {
+            return Vec::new();
+        }
+        block.transactions
+            .iter()
+            .enumerate()
+            .filter(|(_, tx)| self.matches(tx))
+            .map(|(index, tx)| json!({
+                "height": block.header.height,
+                "index": index,
+                "hash": hex::encode(tx.hash()),
+                "transaction": tx,
+            }))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockStatus {
+    Included,
+    Finalized,
+    Orphaned,
+}
+
+/// `chain_subscribeStatus` notification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StatusUpdate {
+    Block {
+        height: u64,
+        hash: String,
+        status: BlockStatus,
+    },
+    Transaction {
+        hash: String,
+        #[serde(flatten)]
+        status: TxStatus,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcRequest {
+    pub fn new(id: u64, method: &str, params: Value) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id: json!(id),
+            method: method.to_string(),
+            params,
+        }
+    }
+
+    fn param(&self, index: usize) -> Option<&Value> {
+        self.params.as_array().and_then(|params| params.get(index))
+    }
+}
+
+impl RpcResponse {
+    pub fn success(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn failure(id: Value, code: i64, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(RpcError { code, message: message.into() }),
+        }
+    }
+}
+
+/// Per-socket subscription state for the WebSocket transport
+#[derive(Default)]
+struct Subscriptions {
+    next_id: u64,
+    active: HashMap<u64, JoinHandle<()>>,
+}
+
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        for task in self.active.values() {
+            task.abort();
+        }
+    }
+}
+
+/// JSON-RPC 2.0 handler serving chain data over `POST /rpc`, and over a WebSocket at
+/// `/rpc/ws` which adds `chain_subscribeNewHeads`, `chain_subscribeLogs`, `chain_subscribeStatus`
+/// and `chain_unsubscribe`. Methods in the `admin_` namespace and the mempool admin
+/// methods answer only callers holding an admin token; the `debug_` namespace answers
+/// only when enabled
+pub struct RpcServer {
+    db: BlockchainDB,
+    limiter: RateLimiter,
+    node: Option<Arc<Node>>,
+    network: Option<Arc<NetworkService>>,
+    admin_tokens: Vec<String>,
+    debug: bool,
+}
+
+impl RpcServer {
+    pub fn new(db: BlockchainDB) -> Self {
+        Self {
+            db,
+            limiter: RateLimiter::new(RateLimitConfig::default()),
+            node: None,
+            network: None,
+            admin_tokens: Vec::new(),
+            debug: false,
+        }
+    }
+
+    /// Accepts `tx_sendRawTransaction` into `node`'s mempool and answers state queries
+    /// from its head state instead of replaying the chain
+    pub fn with_node(mut self, node: Arc<Node>) -> Self {
+        self.node = Some(node);
+        self
+    }
+
+    pub fn with_rate_limit(mut self, config: RateLimitConfig) -> Self {
+        self.limiter = RateLimiter::new(config);
+        self
+    }
+
+    /// Lists `network`'s connections in `net_getPeers` and lets `admin_addPeer` and
+    /// `admin_removePeer` manage them
+    pub fn with_network(mut self, network: Arc<NetworkService>) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    /// Bearer tokens that may call the admin methods
+    pub fn with_admin_tokens(mut self, tokens: Vec<String>) -> Self {
+        self.admin_tokens = tokens;
+        self
+    }
+
+    /// Serves `debug_traceTransaction` and `debug_traceBlock`, which re-execute the chain
+    pub fn with_debug(mut self, enabled: bool) -> Self {
+        self.debug = enabled;
+        self
+    }
+
+    pub fn handle(&self, request: RpcRequest) -> RpcResponse {
+        self.handle_as(request, false)
+    }
+
+    /// Handles a request from a caller that is an admin or not
+    pub fn handle_as(&self, request: RpcRequest, admin: bool) -> RpcResponse {
+        let id = request.id.clone();
+        match self.dispatch(&request, admin) {
+            Ok(result) => RpcResponse::success(id, result),
+            Err(error) => RpcResponse::failure(id, error.code, error.message),
+        }
+    }
+
+    /// `handle_as` on the blocking pool, for async transports: dispatch may replay state
+    /// or wait for the node's chain lock
+    pub async fn handle_async(self: &Arc<Self>, request: RpcRequest, admin: bool) -> RpcResponse {
+        let (server, id) = (self.clone(), request.id.clone());
+        blocking(move || server.handle_as(request, admin))
+            .await
+            .unwrap_or_else(|e| RpcResponse::failure(id, INTERNAL_ERROR, e))
+    }
+
+    fn dispatch(&self, request: &RpcRequest, admin: bool) -> Result<Value, RpcError> {
+        if !admin && requires_admin(&request.method) {
+            return Err(RpcError {
+                code: UNAUTHORIZED,
+                message: "Admin methods need an admin token".to_string(),
+            });
+        }
+        if !self.debug && request.method.starts_with("debug_") {
+            return Err(RpcError {
+                code: NOT_AVAILABLE,
+                message: "The debug namespace is disabled on this endpoint (web.debug_rpc)".to_string(),
+            });
+        }
+        match request.method.as_str() {
+            "node_getInfo" => Ok(json!({
+                "version": crate::VERSION,
+                "height": self.db.get_latest_height().map_err(internal)?,
+                "blocks": self.db.block_count().map_err(internal)?,
+            })),
+            "chain_getHead" => {
+                if self.db.block_count().map_err(internal)? == 0 {
+                    return Ok(Value::Null);
+                }
+                let height = self.db.get_latest_height().map_err(internal)?;
+                self.block_at(height)
+            }
+            "chain_getForkChoiceHead" => {
+                match self.db.get_meta::<ForkChoiceHead>("fork_choice").map_err(internal)? {
+                    Some(head) => Ok(json!({
+                        "head": hex::encode(head.head),
+                        "head_height": head.head_height,
+                        "finalized": hex::encode(head.finalized),
+                        "finalized_height": head.finalized_height,
+                    })),
+                    None => Ok(Value::Null),
+                }
+            }
+            "chain_getBlock" => {
+                let height = request.param(0)
+                    .and_then(Value::as_u64)
+                    .ok_or_else(|| invalid_params("expected block height"))?;
+                self.block_at(height)
+            }
+            "chain_getBlockWitness" => {
+                let height = request.param(0)
+                    .and_then(Value::as_u64)
+                    .ok_or_else(|| invalid_params("expected block height"))?;
+                let Some(block) = self.db.get_block(height).map_err(internal)? else {
+                    return Ok(Value::Null);
+                };
+                match self.node()?.block_witness(&block.hash()).map_err(internal)? {
+                    Some(witness) => Ok(json!(hex::encode(witness.encode()))),
+                    None => Ok(Value::Null),
+                }
+            }
+            "tx_getTransaction" => {
+                let hash = hash_param(request, 0)?;
+                match self.db.get_transaction(&hash).map_err(internal)? {
+                    Some((transaction, height, index)) => Ok(json!({
+                        "transaction": transaction,
+                        "height": height,
+                        "index": index,
+                    })),
+                    None => Ok(Value::Null),
+                }
+            }
+            "tx_getByMemo" => {
+                let memo = request.param(0).and_then(Value::as_str).ok_or_else(|| invalid_params("expected memo"))?;
+                let limit = request.param(1).and_then(Value::as_u64).unwrap_or(20).clamp(1, MAX_MEMO_MATCHES);
+                let found = self.db.get_transactions_by_memo(memo, limit as usize).map_err(internal)?;
+                Ok(Value::Array(found.into_iter().map(|(transaction, height, index)| json!({
+                    "transaction": transaction,
+                    "height": height,
+                    "index": index,
+                })).collect()))
+            }
+            "tx_getInclusionProof" => {
+                let hash = hash_param(request, 0)?;
+                let Some(mut proof) = light::serve_inclusion_proof(&self.db, &hash).map_err(internal)? else {
+                    return Ok(Value::Null);
+                };
+                if let Some(node) = &self.node {
+                    let block_hash = proof.header.hash();
+                    proof.certificate = node.commit_certificate(proof.header.height).filter(|certificate| certificate.block_hash == block_hash);
+                }
+                Ok(serde_json::to_value(proof).map_err(internal)?)
+            }
+            "state_getAccount" => {
+                let address = address_param(request, 0)?;
+                self.head_state(|state| json!(state.get_account(&address)))
+            }
+            "names_resolve" => {
+                let name = request.param(0).and_then(Value::as_str).ok_or_else(|| invalid_params("expected name"))?;
+                self.head_state(|state| json!(state.names().resolve(name, state.current_height())))
+            }
+            "names_reverse" => {
+                let address = address_param(request, 0)?;
+                self.head_state(|state| json!(state.names().reverse(&address, state.current_height())))
+            }
+            "schedule_getQueue" => {
+                let from = request.param(0).and_then(Value::as_u64).unwrap_or(0);
+                let to = request.param(1).and_then(Value::as_u64).unwrap_or(u64::MAX);
+                self.head_state(|state| json!(state.schedule().due_between(from, to).collect::<Vec<_>>()))
+            }
+            "vesting_getAccount" => {
+                let address = address_param(request, 0)?;
+                self.head_state(|state| json!(state.vesting_status(&address, state.current_height())))
+            }
+            "interop_getChannels" => self.head_state(|state| json!(state.interop().channels().ends().collect::<Vec<_>>())),
+            "interop_getPacketCommitment" => {
+                let port = request.param(0).and_then(Value::as_str).ok_or_else(|| invalid_params("expected port"))?;
+                let channel = request.param(1).and_then(Value::as_str).ok_or_else(|| invalid_params("expected channel"))?;
+                let key = (port.to_string(), channel.to_string(), u64_param(request, 2, "expected packet sequence")?);
+                self.head_state(|state| {
+                    let proven = state.interop().channels().commitment(&key).and_then(|commitment| {
+                        let proof = state.leaf_proof(&Channels::commitment_leaf(&key, &commitment))?;
+                        Some(CommitmentProof { commitment, proof })
+                    });
+                    json!(proven)
+                })
+            }
+            "state_getBalanceAt" => {
+                let address = address_param(request, 0)?;
+                let height = u64_param(request, 1, "expected block height")?;
+                let state = self.state_at(height, &[address.as_slice()])?;
+                Ok(json!(state.get_account(&address).map(|account| account.balance).unwrap_or_default()))
+            }
+            "tx_getStatus" => {
+                let hash = hash_param(request, 0)?;
+                match &self.node {
+                    Some(node) => Ok(json!(node.transaction_status(&hash).map_err(internal)?)),
+                    None => Ok(Value::Null),
+                }
+            }
+            "tx_callAt" => {
+                let height = u64_param(request, 0, "expected block height")?;
+                let envelope = TxEnvelope::decode(&hex_param(request, 1)?).map_err(|e| invalid_params(&e))?;
+                self.call_at(height, &envelope.into())
+            }
+            "logs_query" => self.query_logs(&log_filter_param(request, 0)?),
+            "tx_sendRawTransaction" => {
+                let envelope = TxEnvelope::decode(&hex_param(request, 0)?).map_err(|e| invalid_params(&e))?;
+                let node = self.node.as_ref().ok_or_else(|| RpcError {
+                    code: TRANSACTION_REJECTED,
+                    message: "This endpoint does not accept transactions".to_string(),
+                })?;
+                let hash = node
+                    .submit_transaction(envelope.into())
+                    .map_err(|message| RpcError { code: TRANSACTION_REJECTED, message })?;
+                Ok(json!(hex::encode(hash)))
+            }
+            "mempool_getLaneMetrics" => match &self.node {
+                Some(node) => Ok(json!(node.lane_metrics())),
+                None => Ok(Value::Null),
+            },
+            "mempool_getClassMetrics" => match &self.node {
+                Some(node) => Ok(json!(node.class_metrics())),
+                None => Ok(Value::Null),
+            },
+            "mempool_estimateFee" => match &self.node {
+                Some(node) => Ok(json!(node.fee_estimates())),
+                None => Ok(Value::Null),
+            },
+            "node_getStorageMetrics" => match &self.node {
+                Some(node) => Ok(serde_json::to_value(node.state_store_metrics().map_err(internal)?).map_err(internal)?),
+                None => Ok(Value::Null),
+            },
+            "node_getImportMetrics" => match &self.node {
+                Some(node) => Ok(json!(node.import_metrics())),
+                None => Ok(Value::Null),
+            },
+            "node_getCacheMetrics" => Ok(json!(self.db.cache_metrics())),
+            "node_getOrphanStats" => match &self.node {
+                Some(node) => Ok(json!(node.orphan_stats())),
+                None => Ok(Value::Null),
+            },
+            "node_getDuplicateMetrics" => match &self.node {
+                Some(node) => Ok(json!(node.duplicate_metrics())),
+                None => Ok(Value::Null),
+            },
+            "node_getVerifiedCacheMetrics" => match &self.node {
+                Some(node) => Ok(json!(node.verified_cache_stats())),
+                None => Ok(Value::Null),
+            },
+            "chain_getDeployments" => match &self.node {
+                Some(node) => {
+                    let height = match request.param(0).and_then(Value::as_u64) {
+                        Some(height) => height,
+                        None => node.next_height().map_err(internal)?,
+                    };
+                    Ok(json!(node.deployment_status(height).map_err(internal)?))
+                }
+                None => Ok(Value::Null),
+            },
+            "staking_getValidatorPerformance" => {
+                let epoch = request.param(0).and_then(Value::as_u64);
+                match &self.node {
+                    Some(node) => Ok(json!(node.validator_performance(epoch).map_err(internal)?)),
+                    None => Ok(Value::Null),
+                }
+            }
+            "consensus_getDecisionHistory" => {
+                let limit = request.param(0).and_then(Value::as_u64).unwrap_or(20).clamp(1, MAX_DECISION_PAGE);
+                let before = request.param(1).and_then(Value::as_u64);
+                let (decisions, next) = DecisionLog::open(&self.db).and_then(|log| log.page(before, limit as usize)).map_err(internal)?;
+                Ok(json!({ "decisions": decisions, "next": next }))
+            }
+            "consensus_getModelState" => match &self.node {
+                Some(node) => Ok(json!(node.model_state())),
+                None => Ok(Value::Null),
+            },
+            "consensus_getAgreedPath" => match &self.node {
+                Some(node) => {
+                    let agreed = match request.param(0).and_then(Value::as_u64) {
+                        Some(height) => node.agreed_path(height).map_err(internal)?,
+                        None => node.active_path(),
+                    };
+                    Ok(json!({ "agreed": agreed, "proposed": node.consensus_path() }))
+                }
+                None => Ok(Value::Null),
+            },
+            "mempool_content" => match &self.node {
+                Some(node) => Ok(mempool_listing(node.pending_summaries(None, MAX_MEMPOOL_ENTRIES + 1))),
+                None => Ok(Value::Null),
+            },
+            "mempool_inspect" => {
+                let sender = address_param(request, 0)?;
+                match &self.node {
+                    Some(node) => Ok(mempool_listing(node.pending_summaries(Some(&sender), MAX_MEMPOOL_ENTRIES + 1))),
+                    None => Ok(Value::Null),
+                }
+            }
+            "mempool_remove" => {
+                let hash = hash_param(request, 0)?;
+                Ok(json!(self.node.as_ref().is_some_and(|node| node.remove_pending(&hash))))
+            }
+            "admin_addPeer" => {
+                let address = request.param(0)
+                    .and_then(Value::as_str)
+                    .and_then(|address| address.parse::<SocketAddr>().ok())
+                    .ok_or_else(|| invalid_params("expected peer address"))?;
+                self.network()?.add_peer(address);
+                Ok(json!(true))
+            }
+            "admin_removePeer" => {
+                let node_id = hex_param(request, 0)?;
+                Ok(json!(self.network()?.remove_peer(&node_id)))
+            }
+            "admin_setLogLevel" => {
+                let level: logging::Level = request.param(0)
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| invalid_params("expected log level"))?
+                    .parse()
+                    .map_err(|e: String| invalid_params(&e))?;
+                Ok(json!({ "previous": logging::set_level(level), "level": level }))
+            }
+            "admin_createSnapshot" => {
+                let height = request.param(0).and_then(Value::as_u64);
+                let snapshot = self.node()?.create_snapshot(height).map_err(internal)?;
+                Ok(json!({
+                    "height": snapshot.block.header.height,
+                    "block": hex::encode(snapshot.block.hash()),
+                    "stateRoot": hex::encode(snapshot.block.header.state_root),
+                    "accounts": snapshot.accounts.len(),
+                }))
+            }
+            "admin_backup" => {
+                let out = request.param(0).and_then(Value::as_str).ok_or_else(|| invalid_params("expected backup directory"))?;
+                let manifest = self.node()?.backup(std::path::Path::new(out), |step| log!(Info, "Backup: {}", step)).map_err(internal)?;
+                serde_json::to_value(manifest).map_err(internal)
+            }
+            "admin_pauseBlockProduction" | "admin_resumeBlockProduction" => {
+                let paused = request.method == "admin_pauseBlockProduction";
+                self.node()?.set_production_paused(paused);
+                Ok(json!({ "paused": paused }))
+            }
+            "admin_pinConsensusPath" => {
+                let mode: PinnedMode = request.param(0)
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| invalid_params("expected consensus mode"))?
+                    .parse()
+                    .map_err(|e: String| invalid_params(&e))?;
+                let seconds = request.param(1)
+                    .and_then(Value::as_u64)
+                    .ok_or_else(|| invalid_params("expected duration in seconds"))?;
+                let reason = request.param(2).and_then(Value::as_str).unwrap_or("Pinned by operator");
+                let pin = self.node()?
+                    .pin_consensus_path(mode, Duration::from_secs(seconds), reason.to_string())
+                    .map_err(|e| invalid_params(&e))?;
+                Ok(json!(pin))
+            }
+            "admin_unpinConsensusPath" => Ok(json!({ "previous": self.node()?.unpin_consensus_path() })),
+            "admin_rotateValidatorKey" => {
+                let node = self.node()?;
+                let key = match request.param(0).and_then(Value::as_str) {
+                    Some(path) => QuantumKeyPair::load(path).map_err(|e| invalid_params(&e))?,
+                    None => QuantumKeyPair::generate(),
+                };
+                if key.public_key() == node.node_id().as_slice() {
+                    return Err(invalid_params("the validator key must differ from the node key"));
+                }
+                let previous = node.rotate_validator_key(key);
+                Ok(json!({
+                    "previous": previous.map(|key| hex::encode(key.public_key())),
+                    "current": node.validator_id().map(hex::encode),
+                    "validator": node.is_validator(),
+                }))
+            }
+            "admin_authorizeSessionKey" => {
+                let epochs = u64_param(request, 0, "expected number of epochs")?;
+                let fee = request.param(1).and_then(Value::as_u64).unwrap_or(0);
+                let (hash, key) = self.node()?.authorize_session_key(epochs, fee).map_err(|e| invalid_params(&e))?;
+                Ok(json!({ "transaction": hex::encode(hash), "session_key": hex::encode(key) }))
+            }
+            "debug_traceTransaction" => {
+                let hash = hash_param(request, 0)?;
+                let detail = trace_detail_param(request, 1)?;
+                let Some((_, height, index)) = self.db.get_transaction(&hash).map_err(internal)? else {
+                    return Ok(Value::Null);
+                };
+                let trace = trace::trace_block(&self.db, height, detail, Some(index)).map_err(internal)?;
+                Ok(json!(trace.and_then(|mut trace| trace.transactions.pop())))
+            }
+            "debug_traceBlock" => {
+                let height = u64_param(request, 0, "expected block height")?;
+                let detail = trace_detail_param(request, 1)?;
+                Ok(json!(trace::trace_block(&self.db, height, detail, None).map_err(internal)?))
+            }
+            "net_getPeers" => {
+                let peers = self.network()?.peers();
+                Ok(json!(peers.iter().map(|peer| format!("{}@{}", hex::encode(&peer.node_id), peer.dial_address())).collect::<Vec<_>>()))
+            }
+            other => Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("Method not found: {}", other),
+            }),
+        }
+    }
+
+    /// The accounts at `addresses` as they were after the block at `height`, read from
+    /// the state trie's per-height roots rather than by replaying the chain
+    fn state_at(&self, height: u64, addresses: &[&[u8]]) -> Result<StateManager, RpcError> {
+        let store = StateStore::open(&self.db).map_err(internal)?;
+        if store.root_at(height).map_err(internal)?.is_none() {
+            return Err(RpcError {
+                code: STATE_NOT_RETAINED,
+                message: format!("State at height {} is not retained; query an archive node", height),
+            });
+        }
+        let mut state = StateManager::new();
+        for address in addresses {
+            if let Some(account) = store.account_at(height, address).map_err(internal)? {
+                *state.get_or_create_account(address) = account;
+            }
+        }
+        Ok(state)
+    }
+
+    /// Dry-runs `tx` on top of the state after the block at `height`. The signature is
+    /// not checked and nothing is persisted
+    fn call_at(&self, height: u64, tx: &Transaction) -> Result<Value, RpcError> {
+        let addresses: Vec<&[u8]> = transaction_addresses(tx).collect();
+        let mut state = self.state_at(height, &addresses)?;
+        let outcome = tx.envelope().validate().and_then(|_| state.apply_transaction(tx));
+        let accounts: serde_json::Map<String, Value> = addresses
+            .iter()
+            .map(|address| (hex::encode(address), json!(state.get_account(address))))
+            .collect();
+        Ok(json!({
+            "success": outcome.is_ok(),
+            "error": outcome.err(),
+            "accounts": accounts,
+        }))
+    }
+
+    /// Scans blocks in the filter's range, skipping those whose bloom rules out every
+    /// requested address and topic
+    fn query_logs(&self, filter: &LogFilter) -> Result<Value, RpcError> {
+        let matcher = LogMatcher::new(filter)?;
+        if self.db.block_count().map_err(internal)? == 0 {
+            return Ok(json!([]));
+        }
+        let latest = self.db.get_latest_height().map_err(internal)?;
+        let from = filter.from_block.unwrap_or(0);
+        let to = filter.to_block.unwrap_or(latest).min(latest);
+        if to >= from && to - from >= MAX_LOG_RANGE {
+            return Err(invalid_params(&format!("block range exceeds {} blocks", MAX_LOG_RANGE)));
+        }
+
+        let mut logs = Vec::new();
+        for height in from..=to {
+            if let Some(block) = self.db.get_block(height).map_err(internal)? {
+                logs.extend(matcher.logs(&block));
+            }
+        }
+        Ok(Value::Array(logs))
+    }
+
+    /// Height of the latest stored block, if any
+    fn latest_height(&self) -> Result<Option<u64>, String> {
+        if self.db.block_count()? == 0 {
+            return Ok(None);
+        }
+        self.db.get_latest_height().map(Some)
+    }
+
+    async fn serve_socket(self: Arc<Self>, socket: WebSocket, client: RateLimitKey, admin: bool) {
+        let (mut sink, mut stream) = socket.split();
+        let (sender, mut receiver) = mpsc::channel::<Value>(SOCKET_QUEUE_LEN);
+        let writer = tokio::spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                if sink.send(Message::text(message.to_string())).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut subscriptions = Subscriptions::default();
+        while let Some(Ok(message)) = stream.next().await {
+            if message.is_close() {
+                break;
+            }
+            let Ok(text) = message.to_str() else {
+                continue;
+            };
+            let response = match parse_request(text.as_bytes()) {
+                Ok(request) => match self.limiter.check(&client, &request.method) {
+                    Ok(()) => self.handle_socket(request, admin, &mut subscriptions, &sender).await,
+                    Err(wait) => rate_limited(request.id, wait),
+                },
+                Err(failure) => failure,
+            };
+            if sender.send(json!(response)).await.is_err() {
+                break;
+            }
+        }
+        drop(subscriptions);
+        writer.abort();
+    }
+
+    async fn handle_socket(
+        self: &Arc<Self>,
+        request: RpcRequest,
+        admin: bool,
+        subscriptions: &mut Subscriptions,
+        sender: &mpsc::Sender<Value>,
+    ) -> RpcResponse {
+        match request.method.as_str() {
+            "chain_subscribeNewHeads" => {
+                let id = self.subscribe(subscriptions, sender, |block| vec![json!(block.header)]);
+                RpcResponse::success(request.id, json!(id))
+            }
+            "chain_subscribeLogs" => {
+                let matcher = match log_filter_param(&request, 0).and_then(|filter| LogMatcher::new(&filter)) {
+                    Ok(matcher) => matcher,
+                    Err(error) => return RpcResponse::failure(request.id, error.code, error.message),
+                };
+                let id = self.subscribe(subscriptions, sender, move |block| matcher.logs(block));
+                RpcResponse::success(request.id, json!(id))
+            }
+            "chain_subscribeStatus" => {
+                let Some(node) = self.node.clone() else {
+                    return RpcResponse::failure(request.id, INVALID_PARAMS, "Status subscriptions need a node");
+                };
+                let watched: Option<Vec<[u8; 32]>> = match request.param(0) {
+                    None => Some(Vec::new()),
+                    Some(hashes) => hashes.as_array().and_then(|hashes| {
+                        hashes
+                            .iter()
+                            .map(|hash| {
+                                let hash = hex::decode(hash.as_str()?.trim_start_matches("0x")).ok()?;
+                                hash.try_into().ok()
+                            })
+                            .collect()
+                    }),
+                };
+                let Some(watched) = watched else {
+                    return RpcResponse::failure(request.id, INVALID_PARAMS, "expected 32-byte transaction hashes");
+                };
+                subscriptions.next_id += 1;
+                let id = subscriptions.next_id;
+                let events = node.events().subscribe();
+                let task = tokio::spawn(stream_status(node, id, events, watched, sender.clone()));
+                subscriptions.active.insert(id, task);
+                RpcResponse::success(request.id, json!(id))
+            }
+            "chain_unsubscribe" => match request.param(0).and_then(Value::as_u64) {
+                Some(id) => {
+                    let task = subscriptions.active.remove(&id);
+                    if let Some(task) = &task {
+                        task.abort();
+                    }
+                    RpcResponse::success(request.id, json!(task.is_some()))
+                }
+                None => RpcResponse::failure(request.id, INVALID_PARAMS, "expected subscription id"),
+            },
+            _ => self.handle_async(request, admin).await,
+        }
+    }
+
+    /// Starts a subscription that notifies `render`'s results for every new block
+    fn subscribe(
+        self: &Arc<Self>,
+        subscriptions: &mut Subscriptions,
+        sender: &mpsc::Sender<Value>,
+        render: impl Fn(&Block) -> Vec<Value> + Send + 'static,
+    ) -> u64 {
+        subscriptions.next_id += 1;
+        let id = subscriptions.next_id;
+        let head = self.latest_height().ok().flatten().and_then(|height| {
+            self.db.get_block(height).ok().flatten().map(|block| (height, block.hash()))
+        });
+        let task = tokio::spawn(self.clone().stream_blocks(id, head.into_iter().collect(), sender.clone(), render));
+        subscriptions.active.insert(id, task);
+        id
+    }
+
+    /// Pushes what `render` makes of each new canonical block, one notification per item,
+    /// so filtered subscriptions send nothing for blocks without matches. `sent` holds the
+    /// height and hash of the latest blocks sent: streaming resumes after the newest still
+    /// canonical, so blocks a reorg replaced are sent again, and starts at the head when
+    /// none is, as on a chain that was empty at subscription
+    async fn stream_blocks(
+        self: Arc<Self>,
+        subscription: u64,
+        mut sent: VecDeque<(u64, [u8; 32])>,
+        sender: mpsc::Sender<Value>,
+        render: impl Fn(&Block) -> Vec<Value>,
+    ) {
+        let mut ticker = tokio::time::interval(SUBSCRIPTION_POLL_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let Ok(Some(latest)) = self.latest_height() else {
+                continue;
+            };
+            while let Some((height, hash)) = sent.back() {
+                if self.db.get_block(*height).ok().flatten().is_some_and(|block| block.hash() == *hash) {
+                    break;
+                }
+                sent.pop_back();
+            }
+            let start = sent.back().map_or(latest, |(height, _)| height + 1);
+            for height in start..=latest {
+                let Ok(Some(block)) = self.db.get_block(height) else {
+                    break;
+                };
+                for result in render(&block) {
+                    let notification = json!({
+                        "jsonrpc": "2.0",
+                        "method": "chain_subscription",
+                        "params": { "subscription": subscription, "result": result },
+                    });
+                    if sender.send(notification).await.is_err() {
+                        return;
+                    }
+                }
+                sent.push_back((height, block.hash()));
+                if sent.len() as u64 > FINALITY_DEPTH {
+                    sent.pop_front();
+                }
+            }
+        }
+    }
+
+    fn node(&self) -> Result<&Arc<Node>, RpcError> {
+        self.node.as_ref().ok_or_else(|| RpcError { code: NOT_AVAILABLE, message: "This endpoint runs no node".to_string() })
+    }
+
+    /// Reads the state at the head of the chain: the node's, or a replay of the stored
+    /// chain on an endpoint without one
+    fn head_state(&self, read: impl FnOnce(&StateManager) -> Value) -> Result<Value, RpcError> {
+        match &self.node {
+            Some(node) => Ok(node.with_head_state(read)),
+            None => Ok(read(&StateManager::replay(&self.db).map_err(internal)?)),
+        }
+    }
+
+    fn network(&self) -> Result<&Arc<NetworkService>, RpcError> {
+        self.network.as_ref().ok_or_else(|| RpcError { code: NOT_AVAILABLE, message: "This endpoint runs no network service".to_string() })
+    }
+
+    fn block_at(&self, height: u64) -> Result<Value, RpcError> {
+        let block = self.db.get_block(height).map_err(internal)?;
+        serde_json::to_value(block).map_err(internal)
+    }
+
+    pub fn routes(self: Arc<Self>) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let max_payload = self.limiter.max_payload_bytes();
+        let server = self.clone();
+        let client = warp::addr::remote().and(warp::header::optional::<String>("authorization"));
+        let socket = warp::path!("rpc" / "ws")
+            .and(warp::ws())
+            .and(client)
+            .map(move |ws: warp::ws::Ws, remote: Option<SocketAddr>, authorization: Option<String>| {
+                let server = server.clone();
+                let admin = server.is_admin(authorization.as_deref());
+                let client = server.client_key(remote, authorization);
+                let reply = ws
+                    .max_message_size(max_payload as usize)
+                    .on_upgrade(move |socket| server.serve_socket(socket, client, admin));
+                Box::new(reply) as Box<dyn warp::Reply>
+            });
+
+        let http = warp::path("rpc")
+            .and(warp::path::end())
+            .and(warp::post())
+            .and(warp::body::content_length_limit(max_payload))
+            .and(client)
+            .and(warp::body::bytes())
+            .and_then(move |remote: Option<SocketAddr>, authorization: Option<String>, body: hyper::body::Bytes| {
+                let server = self.clone();
+                async move {
+                    let response = match parse_request(&body) {
+                        Ok(request) => {
+                            let admin = server.is_admin(authorization.as_deref());
+                            let client = server.client_key(remote, authorization);
+                            if let Err(wait) = server.limiter.check(&client, &request.method) {
+                                let reply = warp::reply::with_status(
+                                    warp::reply::json(&rate_limited(request.id, wait)),
+                                    warp::http::StatusCode::TOO_MANY_REQUESTS,
+                                );
+                                let reply = warp::reply::with_header(reply, "retry-after", retry_after_secs(wait).to_string());
+                                return Ok::<_, warp::Rejection>(Box::new(reply) as Box<dyn warp::Reply>);
+                            }
+                            server.handle_async(request, admin).await
+                        }
+                        Err(failure) => failure,
+                    };
+                    Ok(Box::new(warp::reply::json(&response)) as Box<dyn warp::Reply>)
+                }
+            });
+
+        socket.or(http).unify()
+    }
+
+    fn client_key(&self, remote: Option<SocketAddr>, authorization: Option<String>) -> RateLimitKey {
+        self.limiter.key(remote.map(|address| address.ip()), bearer(authorization.as_deref()))
+    }
+
+    /// Checks every configured token in constant time, so neither a token's bytes nor
+    /// which token matched show in how long the check takes
+    fn is_admin(&self, authorization: Option<&str>) -> bool {
+        bearer(authorization).is_some_and(|token| {
+            self.admin_tokens.iter().fold(false, |found, admin| found | constant_time_eq(admin.as_bytes(), token.as_bytes()))
+        })
+    }
+}
+
+/// Parses a request body, or the parse error response to send back
+pub fn parse_request(body: &[u8]) -> Result<RpcRequest, RpcResponse> {
+    serde_json::from_slice(body).map_err(|e| RpcResponse::failure(Value::Null, PARSE_ERROR, e.to_string()))
+}
+
+/// Methods that only admin callers may use
+fn requires_admin(method: &str) -> bool {
+    method.starts_with("admin_") || matches!(method, "mempool_content" | "mempool_inspect" | "mempool_remove")
+}
+
+fn bearer(authorization: Option<&str>) -> Option<&str> {
+    authorization.and_then(|value| value.strip_prefix("Bearer ")).map(str::trim)
+}
+
+/// Pending transactions grouped by sender, cut off at `MAX_MEMPOOL_ENTRIES`
+fn mempool_listing(mut summaries: Vec<PendingSummary>) -> Value {
+    let truncated = summaries.len() > MAX_MEMPOOL_ENTRIES;
+    summaries.truncate(MAX_MEMPOOL_ENTRIES);
+    let mut senders: serde_json::Map<String, Value> = serde_json::Map::new();
+    for summary in summaries {
+        let transactions = senders.entry(hex::encode(&summary.from)).or_insert_with(|| json!([]));
+        if let Some(transactions) = transactions.as_array_mut() {
+            transactions.push(json!(summary));
+        }
+    }
+    json!({ "senders": senders, "truncated": truncated })
+}
+
+/// Pushes every block lifecycle change, and each change in the status of the `watched`
+/// transactions, until the node shuts down
+async fn stream_status(
+    node: Arc<Node>,
+    subscription: u64,
+    mut events: broadcast::Receiver<NodeEvent>,
+    watched: Vec<[u8; 32]>,
+    sender: mpsc::Sender<Value>,
+) {
+    let notify = |update: StatusUpdate| {
+        sender.send(json!({
+            "jsonrpc": "2.0",
+            "method": "chain_subscription",
+            "params": { "subscription": subscription, "result": update },
+        }))
+    };
+    let mut last: HashMap<[u8; 32], TxStatus> = HashMap::new();
+    loop {
+        let (height, hash, status) = match events.recv().await {
+            Ok(NodeEvent::TxAccepted { hash }) => {
+                if watched.contains(&hash) && last.insert(hash, TxStatus::Pending) != Some(TxStatus::Pending) {
+                    let update = StatusUpdate::Transaction { hash: hex::encode(hash), status: TxStatus::Pending };
+                    if notify(update).await.is_err() {
+                        return;
+                    }
+                }
+                continue;
+            }
+            Ok(NodeEvent::BlockImported { height, hash, .. }) => (height, hash, BlockStatus::Included),
+            Ok(NodeEvent::BlockFinalized { height, hash }) => (height, hash, BlockStatus::Finalized),
+            Ok(NodeEvent::BlockOrphaned { height, hash }) => (height, hash, BlockStatus::Orphaned),
+            Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        if notify(StatusUpdate::Block { height, hash: hex::encode(hash), status }).await.is_err() {
+            return;
+        }
+        if watched.is_empty() {
+            continue;
+        }
+
+        let (lookup, wanted) = (node.clone(), watched.clone());
+        let Ok(touched) = blocking(move || {
+            let Ok(Some(block)) = lookup.db().get_block_by_hash(&hash) else {
+                return Vec::new();
+            };
+            block.transactions
+                .iter()
+                .map(|tx| tx.hash())
+                .filter(|tx| wanted.contains(tx))
+                .map(|tx| (tx, lookup.transaction_status(&tx).ok().flatten()))
+                .collect::<Vec<_>>()
+        })
+        .await
+        else {
+            return;
+        };
+        for (tx, current) in touched {
+            // A reorg may re-admit or re-include the transaction before this runs, so
+            // the orphaning is reported from the event before its current status
+            let mut updates = Vec::new();
+            if status == BlockStatus::Orphaned {
+                updates.push(TxStatus::Orphaned);
+            }
+            updates.extend(current);
+            for update in updates {
+                if last.insert(tx, update.clone()).as_ref() == Some(&update) {
+                    continue;
+                }
+                if notify(StatusUpdate::Transaction { hash: hex::encode(tx), status: update }).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn rate_limited(id: Value, wait: Duration) -> RpcResponse {
+    let message = format!("Rate limit exceeded; retry after {}s", retry_after_secs(wait));
+    RpcResponse::failure(id, LIMIT_EXCEEDED, message)
+}
+
+fn internal(e: impl ToString) -> RpcError {
+    RpcError { code: INTERNAL_ERROR, message: e.to_string() }
+}
+
+fn invalid_params(message: &str) -> RpcError {
+    RpcError { code: INVALID_PARAMS, message: message.to_string() }
+}
+
+/// Address as bech32 (`tri1...`) or hex
+fn address_param(request: &RpcRequest, index: usize) -> Result<Vec<u8>, RpcError> {
+    let value = request.param(index)
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid_params("expected address"))?;
+    bech32::parse_address(value).map_err(|e| invalid_params(&e))
+}
+
+fn hex_param(request: &RpcRequest, index: usize) -> Result<Vec<u8>, RpcError> {
+    let value = request.param(index)
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid_params("expected hex string"))?;
+    hex::decode(value.trim_start_matches("0x")).map_err(|e| invalid_params(&e.to_string()))
+}
+
+fn log_filter_param(request: &RpcRequest, index: usize) -> Result<LogFilter, RpcError> {
+    match request.param(index) {
+        Some(filter) => serde_json::from_value(filter.clone()).map_err(|e| invalid_params(&e.to_string())),
+        None => Ok(LogFilter::default()),
+    }
+}
+
+fn u64_param(request: &RpcRequest, index: usize, expected: &str) -> Result<u64, RpcError> {
+    request.param(index)
+        .and_then(Value::as_u64)
+        .ok_or_else(|| invalid_params(expected))
+}
+
+/// Optional trace detail, `steps` when absent
+fn trace_detail_param(request: &RpcRequest, index: usize) -> Result<TraceDetail, RpcError> {
+    match request.param(index).and_then(Value::as_str) {
+        Some(detail) => detail.parse().map_err(|e: String| invalid_params(&e)),
+        None => Ok(TraceDetail::Steps),
+    }
+}
+
+fn hash_param(request: &RpcRequest, index: usize) -> Result<[u8; 32], RpcError> {
+    hex_param(request, index)?
+        .try_into()
+        .map_err(|_| invalid_params("expected 32-byte hash"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumSignature;
+    use crate::storage::blocks::{Block, ConsensusData, Transaction};
+    use crate::storage::names::{NameCall, NAME_PRICE, NAME_REGISTRY};
+    use crate::storage::schedule::{ScheduleCall, SCHEDULER};
+    use crate::storage::vesting::{VestingCreate, VESTING};
+
+    #[test]
+    fn test_rpc_dispatch() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_rpc");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        db.store_genesis_allocations(&[(vec![1, 2, 3], 500)]).unwrap();
+        db.store_block(&Block::new([0; 32], vec![], 0, ConsensusData::default())).unwrap();
+        let server = RpcServer::new(db);
+
+        let response = server.handle(RpcRequest::new(1, "chain_getBlock", json!([0])));
+        assert_eq!(response.result.unwrap()["header"]["height"], 0);
+
+        let response = server.handle(RpcRequest::new(2, "state_getAccount", json!(["010203"])));
+        assert_eq!(response.result.unwrap()["balance"], 500);
+
+        let keypair = crate::crypto::QuantumKeyPair::generate();
+        let mut call = Transaction::new(keypair.public_key().to_vec(), vec![7; 32], 0, 7, 0, vec![1, 2, 3, 4, 5], QuantumSignature::new(vec![])).with_memo("order-7");
+        call.signature = keypair.sign(&call.get_signing_data()).unwrap();
+        let block = Block::new([0; 32], vec![call], 1, ConsensusData::default());
+        assert!(block.has_valid_bloom());
+        server.db.store_block(&block).unwrap();
+
+        let filter = json!([{"addresses": [hex::encode([7; 32])], "topics": ["01020304"]}]);
+        let response = server.handle(RpcRequest::new(6, "logs_query", filter));
+        let logs = response.result.unwrap();
+        assert_eq!(logs.as_array().unwrap().len(), 1);
+        assert_eq!(logs[0]["height"], 1);
+        let filter = json!([{"fromBlock": 0, "addresses": [hex::encode([8; 32])]}]);
+        let response = server.handle(RpcRequest::new(7, "logs_query", filter));
+        assert_eq!(response.result.unwrap(), json!([]));
+        let response = server.handle(RpcRequest::new(8, "logs_query", json!([{"toBlock": MAX_LOG_RANGE}])));
+        assert_eq!(response.result.unwrap().as_array().unwrap().len(), 1);
+
+        let found = server.handle(RpcRequest::new(13, "tx_getByMemo", json!(["order-7"]))).result.unwrap();
+        assert_eq!((found[0]["height"].as_u64(), found[0]["transaction"]["memo"].as_str()), (Some(1), Some("order-7")));
+        assert_eq!(server.handle(RpcRequest::new(14, "tx_getByMemo", json!([]))).error.unwrap().code, INVALID_PARAMS);
+
+        let response = server.handle(RpcRequest::new(5, "chain_getForkChoiceHead", json!([])));
+        assert_eq!(response.result, Some(Value::Null));
+
+        let response = server.handle(RpcRequest::new(9, "consensus_getDecisionHistory", json!([10])));
+        assert_eq!(response.result.unwrap(), json!({"decisions": [], "next": null}));
+
+        let response = server.handle(RpcRequest::new(3, "chain_nope", json!([])));
+        assert_eq!(response.error.unwrap().code, METHOD_NOT_FOUND);
+
+        let response = server.handle(RpcRequest::new(4, "chain_getBlock", json!(["x"])));
+        assert_eq!(response.error.unwrap().code, INVALID_PARAMS);
+
+        // Tracing is off unless enabled
+        let response = server.handle(RpcRequest::new(10, "debug_traceBlock", json!([0])));
+        assert_eq!(response.error.unwrap().code, NOT_AVAILABLE);
+        let debug = RpcServer::new(server.db.clone()).with_debug(true);
+        let response = debug.handle(RpcRequest::new(11, "debug_traceBlock", json!([0, "call_tree"])));
+        assert_eq!(response.result.unwrap()["transactions"], json!([]));
+        let response = debug.handle(RpcRequest::new(12, "debug_traceTransaction", json!([hex::encode([0; 32]), "opcodes"])));
+        assert_eq!(response.error.unwrap().code, INVALID_PARAMS);
+
+        println!("   RPC dispatch working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_historical_state_queries() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_rpc_archive");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let keypair = crate::crypto::QuantumKeyPair::generate();
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        db.store_genesis_allocations(&[(keypair.public_key().to_vec(), 1_000)]).unwrap();
+        let node = Arc::new(Node::open(db.clone()).unwrap().with_archive_mode());
+        let server = RpcServer::new(db).with_node(node.clone());
+        let transfer = |nonce: u64, amount: u64| {
+            let mut tx = Transaction::new(keypair.public_key().to_vec(), vec![0xee; 32], amount, 1, nonce, vec![], QuantumSignature::new(vec![]));
+            tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+            tx
+        };
+        for nonce in 0..3 {
+            node.submit_transaction(transfer(nonce, 10)).unwrap();
+            node.produce_block().unwrap();
+        }
+        assert_eq!(node.prune_state().unwrap(), Default::default());
+
+        let recipient = hex::encode([0xee; 32]);
+        let response = server.handle(RpcRequest::new(1, "state_getAccount", json!([recipient])));
+        assert_eq!(response.result.unwrap()["balance"], 30);
+        for (height, balance) in [(0, 10), (1, 20), (2, 30)] {
+            let response = server.handle(RpcRequest::new(1, "state_getBalanceAt", json!([recipient, height])));
+            assert_eq!(response.result.unwrap(), balance);
+        }
+
+        let call = hex::encode(transfer(1, 500).envelope().encode());
+        let result = server.handle(RpcRequest::new(2, "tx_callAt", json!([0, call]))).result.unwrap();
+        assert_eq!(result["success"], true);
+        assert_eq!(result["accounts"][&recipient]["balance"], 510);
+        let result = server.handle(RpcRequest::new(3, "tx_callAt", json!([2, call]))).result.unwrap();
+        assert_eq!(result["success"], false);
+        assert_eq!(node.state_store().account_at(2, &[0xee; 32]).unwrap().unwrap().balance, 30);
+
+        let register = NameCall::Register { name: "erin.tri".to_string(), target: vec![0xee; 32] };
+        let mut tx = Transaction::new(keypair.public_key().to_vec(), NAME_REGISTRY.to_vec(), NAME_PRICE, 1, 3, register.encode(), QuantumSignature::new(vec![]));
+        tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+        node.submit_transaction(tx).unwrap();
+        node.produce_block().unwrap();
+        let record = server.handle(RpcRequest::new(5, "names_resolve", json!(["erin.tri"]))).result.unwrap();
+        assert_eq!(record["target"], recipient);
+        let response = server.handle(RpcRequest::new(6, "names_reverse", json!([recipient])));
+        assert_eq!(response.result.unwrap(), json!(["erin.tri"]));
+        let response = server.handle(RpcRequest::new(7, "names_resolve", json!(["nobody.tri"])));
+        assert_eq!(response.result, Some(Value::Null));
+
+        let schedule = ScheduleCall::Transfer { to: vec![0xee; 32], execute_at: 9 };
+        let mut tx = Transaction::new(keypair.public_key().to_vec(), SCHEDULER.to_vec(), 5, 1, 4, schedule.encode(), QuantumSignature::new(vec![]));
+        tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+        node.submit_transaction(tx).unwrap();
+        node.produce_block().unwrap();
+        let queue = server.handle(RpcRequest::new(8, "schedule_getQueue", json!([]))).result.unwrap();
+        assert_eq!(queue[0]["execute_at"], 9);
+        assert_eq!(queue[0]["to"], recipient);
+        let response = server.handle(RpcRequest::new(9, "schedule_getQueue", json!([10])));
+        assert_eq!(response.result.unwrap(), json!([]));
+
+        let create = VestingCreate { beneficiary: vec![0xee; 32], cliff_blocks: 10, duration_blocks: 100 };
+        let mut tx = Transaction::new(keypair.public_key().to_vec(), VESTING.to_vec(), 100, 1, 5, create.encode(), QuantumSignature::new(vec![]));
+        tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+        node.submit_transaction(tx).unwrap();
+        node.produce_block().unwrap();
+        let status = server.handle(RpcRequest::new(10, "vesting_getAccount", json!([recipient]))).result.unwrap();
+        assert_eq!((status["locked"].as_u64(), status["spendable"].as_u64()), (Some(100), Some(30)));
+        let bech32_status = server.handle(RpcRequest::new(13, "vesting_getAccount", json!([bech32::encode_address(&[0xee; 32])])));
+        assert_eq!(bech32_status.result.unwrap(), status);
+        let response = server.handle(RpcRequest::new(11, "interop_getChannels", json!([])));
+        assert_eq!(response.result.unwrap(), json!([]));
+        let response = server.handle(RpcRequest::new(12, "interop_getPacketCommitment", json!(["transfer", "channel-0", 1])));
+        assert_eq!(response.result, Some(Value::Null));
+
+        node.state_store().prune(1).unwrap();
+        let response = server.handle(RpcRequest::new(4, "state_getBalanceAt", json!([recipient, 0])));
+        assert_eq!(response.error.unwrap().code, STATE_NOT_RETAINED);
+        let model = server.handle(RpcRequest::new(14, "consensus_getModelState", json!([]))).result.unwrap();
+        assert_eq!(model["weights"]["performance_weight"], 0.4);
+        let path = server.handle(RpcRequest::new(15, "consensus_getAgreedPath", json!([]))).result.unwrap();
+        assert_eq!((path["agreed"]["activation_height"].as_u64(), path["proposed"]["HybridPath"].is_object()), (Some(0), true));
+
+        println!("   Archive state queries working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_mempool_admin_methods() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_rpc_mempool");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let keypair = crate::crypto::QuantumKeyPair::generate();
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        db.store_genesis_allocations(&[(keypair.public_key().to_vec(), 1_000)]).unwrap();
+        let node = Arc::new(Node::open(db.clone()).unwrap());
+        let mut hashes = Vec::new();
+        for nonce in 0..3 {
+            let mut tx = Transaction::new(keypair.public_key().to_vec(), vec![0xee; 32], 10, 1, nonce, vec![], QuantumSignature::new(vec![]));
+            tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+            hashes.push(node.submit_transaction(tx).unwrap());
+        }
+        let server = Arc::new(RpcServer::new(db).with_node(node.clone()).with_admin_tokens(vec!["admin".to_string()]));
+        let routes = server.clone().routes();
+        let call = |authorization: &str, method: &str, params: Value| {
+            warp::test::request()
+                .method("POST")
+                .path("/rpc")
+                .header("authorization", authorization)
+                .body(json!(RpcRequest::new(1, method, params)).to_string())
+        };
+
+        let response = call("Bearer other", "mempool_content", json!([])).reply(&routes).await;
+        let response: RpcResponse = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(response.error.unwrap().code, UNAUTHORIZED);
+
+        let response = call("Bearer admin", "mempool_content", json!([])).reply(&routes).await;
+        let content = serde_json::from_slice::<RpcResponse>(response.body()).unwrap().result.unwrap();
+        let sender = hex::encode(keypair.public_key());
+        assert_eq!(content["truncated"], false);
+        assert_eq!(content["senders"][&sender].as_array().unwrap().len(), 3);
+        assert_eq!(content["senders"][&sender][1]["nonce"], 1);
+
+        let removed = server.handle_as(RpcRequest::new(2, "mempool_remove", json!([hex::encode(hashes[2])])), true);
+        assert_eq!(removed.result.unwrap(), true);
+        let removed = server.handle_as(RpcRequest::new(3, "mempool_remove", json!([hex::encode(hashes[2])])), true);
+        assert_eq!(removed.result.unwrap(), false);
+        let inspected = server.handle_as(RpcRequest::new(4, "mempool_inspect", json!([sender])), true).result.unwrap();
+        assert_eq!(inspected["senders"][&sender].as_array().unwrap().len(), 2);
+        let inspected = server.handle_as(RpcRequest::new(5, "mempool_inspect", json!([hex::encode([1; 32])])), true).result.unwrap();
+        assert_eq!(inspected["senders"], json!({}));
+        assert_eq!(node.pending_transactions(), 2);
+
+        println!("   Mempool admin methods working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_admin_namespace() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_rpc_admin");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let open = |name: &str| {
+            let db = BlockchainDB::new(temp_dir.join(name).to_str().unwrap()).unwrap();
+            Arc::new(Node::open(db).unwrap())
+        };
+        let node = open("node");
+        let network = NetworkService::new(node.clone());
+        let server = RpcServer::new(node.db().clone()).with_node(node.clone()).with_network(network.clone());
+        let admin = |method: &str, params: Value| server.handle_as(RpcRequest::new(1, method, params), true);
+
+        let response = server.handle(RpcRequest::new(1, "admin_pauseBlockProduction", json!([])));
+        assert_eq!(response.error.unwrap().code, UNAUTHORIZED);
+        assert_eq!(admin("admin_setLogLevel", json!(["loud"])).error.unwrap().code, INVALID_PARAMS);
+
+        assert_eq!(admin("admin_pauseBlockProduction", json!([])).result.unwrap()["paused"], true);
+        assert!(node.produce_block().is_err());
+        admin("admin_resumeBlockProduction", json!([]));
+        node.produce_block().unwrap();
+
+        let snapshot = admin("admin_createSnapshot", json!([0])).result.unwrap();
+        assert_eq!(snapshot["block"], hex::encode(node.db().get_block(0).unwrap().unwrap().hash()));
+        assert_eq!(admin("admin_createSnapshot", json!([])).error.unwrap().code, INTERNAL_ERROR);
+
+        let backup_dir = temp_dir.join("backup");
+        let manifest = admin("admin_backup", json!([backup_dir.to_str().unwrap()])).result.unwrap();
+        assert_eq!((&manifest["latest_height"], &manifest["genesis_hash"]), (&json!(0), &json!(hex::encode(node.genesis_hash()))));
+        assert!(backup_dir.join("manifest.json").exists());
+        assert_eq!(admin("admin_backup", json!([])).error.unwrap().code, INVALID_PARAMS);
+
+        let pin = admin("admin_pinConsensusPath", json!(["emergency", 600, "incident"])).result.unwrap();
+        assert_eq!((&pin["mode"], &pin["reason"]), (&json!("emergency"), &json!("incident")));
+        assert!(matches!(node.consensus_path(), crate::consensus::router::ConsensusPath::EmergencyMode { .. }));
+        assert_eq!(admin("admin_pinConsensusPath", json!(["emergency", 0])).error.unwrap().code, INVALID_PARAMS);
+        assert_eq!(admin("admin_unpinConsensusPath", json!([])).result.unwrap()["previous"]["mode"], "emergency");
+        assert_eq!(node.pinned_path(), None);
+
+        let keypair = QuantumKeyPair::generate();
+        let key_file = temp_dir.join("validator.json");
+        std::fs::write(&key_file, serde_json::to_string(&keypair).unwrap()).unwrap();
+        let rotated = admin("admin_rotateValidatorKey", json!([key_file.to_str().unwrap()])).result.unwrap();
+        assert_eq!((&rotated["previous"], &rotated["current"]), (&Value::Null, &json!(hex::encode(keypair.public_key()))));
+        // Peers keep knowing the node by its identity, which the validator key is not
+        assert_ne!(node.node_id(), keypair.public_key());
+        let block = node.produce_block().unwrap();
+        assert!(matches!(block.header.consensus_data, ConsensusData::FastLane { ref validator, .. } if validator == keypair.public_key()));
+
+        // Votes are signed by the session key once its authorization is included
+        assert_eq!(admin("admin_authorizeSessionKey", json!([0])).error.unwrap().code, INVALID_PARAMS);
+        let authorized = admin("admin_authorizeSessionKey", json!([4])).result.unwrap();
+        let height = node.produce_block().unwrap().header.height;
+        let vote = node.sign_vote(height + 1, 0, [1; 32]).unwrap();
+        assert_eq!((vote.validator.as_slice(), vote.session_key.map(hex::encode)), (keypair.public_key(), authorized["session_key"].as_str().map(String::from)));
+
+        let peer = NetworkService::new(open("peer"));
+        let address = peer.listen(std::net::SocketAddr::from(([127, 0, 0, 1], 0))).await.unwrap();
+        assert_eq!(admin("admin_addPeer", json!([address.to_string()])).result.unwrap(), true);
+        let wait = |network: Arc<NetworkService>, connected: bool| async move {
+            for _ in 0..100 {
+                if network.peers().is_empty() != connected {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            panic!("peer connection did not change");
+        };
+        wait(network.clone(), true).await;
+        let peer_id = hex::encode(peer.node().node_id());
+        let listed = server.handle(RpcRequest::new(1, "net_getPeers", json!([]))).result.unwrap();
+        assert!(listed[0].as_str().unwrap().starts_with(&format!("{}@", peer_id)));
+        assert_eq!(admin("admin_removePeer", json!([peer_id])).result.unwrap(), true);
+        assert_eq!(admin("admin_removePeer", json!([peer_id])).result.unwrap(), false);
+        wait(peer.clone(), false).await;
+
+        println!("   Admin RPC namespace working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_http_rate_limit() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_rpc_limit");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        let server = Arc::new(RpcServer::new(db).with_rate_limit(RateLimitConfig {
+            ip_rate: 1,
+            ip_burst: 2,
+            max_payload_bytes: 256,
+            ..RateLimitConfig::default()
+        }));
+        let routes = server.routes();
+        let request = |body: String| {
+            warp::test::request()
+                .method("POST")
+                .path("/rpc")
+                .remote_addr(SocketAddr::from(([10, 1, 1, 1], 4000)))
+                .body(body)
+        };
+        let info = json!(RpcRequest::new(1, "node_getInfo", json!([]))).to_string();
+
+        assert_eq!(request(info.clone()).reply(&routes).await.status(), 200);
+        assert_eq!(request(info.clone()).reply(&routes).await.status(), 200);
+        let limited = request(info).reply(&routes).await;
+        assert_eq!(limited.status(), 429);
+        assert_eq!(limited.headers()["retry-after"], "1");
+        let body: RpcResponse = serde_json::from_slice(limited.body()).unwrap();
+        assert_eq!(body.error.unwrap().code, LIMIT_EXCEEDED);
+
+        let oversized = request("x".repeat(512)).reply(&routes).await;
+        assert_eq!(oversized.status(), 413);
+
+        println!("   RPC HTTP rate limiting working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    async fn send(client: &mut warp::test::WsClient, id: u64, method: &str, params: Value) {
+        client.send_text(json!(RpcRequest::new(id, method, params)).to_string()).await;
+    }
+
+    async fn receive(client: &mut warp::test::WsClient) -> Value {
+        let message = client.recv().await.unwrap();
+        serde_json::from_str(message.to_str().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_websocket_rpc() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_rpc_ws");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        let server = Arc::new(RpcServer::new(db.clone()));
+        let mut client = warp::test::ws()
+            .path("/rpc/ws")
+            .handshake(server.routes())
+            .await
+            .unwrap();
+
+        send(&mut client, 41, "node_getInfo", json!([])).await;
+        send(&mut client, 42, "chain_subscribeNewHeads", json!([])).await;
+        assert_eq!(receive(&mut client).await["id"], 41);
+        let subscribed = receive(&mut client).await;
+        assert_eq!(subscribed["id"], 42);
+
+        let genesis = Block::new([0; 32], vec![], 0, ConsensusData::default());
+        db.store_block(&genesis).unwrap();
+        let notification = receive(&mut client).await;
+        assert_eq!(notification["method"], "chain_subscription");
+        assert_eq!(notification["params"]["subscription"], subscribed["result"]);
+        assert_eq!(notification["params"]["result"]["height"], 0);
+
+        // A block replaced by a reorg is sent again as its replacement
+        db.store_block(&Block::new(genesis.hash(), vec![], 1, ConsensusData::default())).unwrap();
+        let first = receive(&mut client).await["params"]["result"].clone();
+        assert_eq!(first["height"], 1);
+        db.store_block(&Block::new([9; 32], vec![], 1, ConsensusData::default())).unwrap();
+        let replaced = receive(&mut client).await["params"]["result"].clone();
+        assert_eq!((replaced["height"].clone(), replaced["previous_hash"] != first["previous_hash"]), (json!(1), true));
+
+        send(&mut client, 43, "chain_unsubscribe", json!([subscribed["result"]])).await;
+        assert_eq!(receive(&mut client).await["result"], true);
+
+        // On a chain with blocks, subscribing starts after the head
+        send(&mut client, 44, "chain_subscribeNewHeads", json!([])).await;
+        receive(&mut client).await;
+        db.store_block(&Block::new([9; 32], vec![], 2, ConsensusData::default())).unwrap();
+        assert_eq!(receive(&mut client).await["params"]["result"]["height"], 2);
+
+        println!("   WebSocket RPC working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -1,8 +1,17 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
-use clap::{Arg, Command};
+use std::time::Duration;
+use clap::{Arg, ArgAction, Command};
 
+use triunity::alerts::AlertService;
+use triunity::api::export::ExportService;
+use triunity::api::rpc::RpcServer;
+use triunity::config::NodeConfig;
+use triunity::consensus::metrics::MetricsCollector;
 use triunity::consensus::ConsensusEngine;
+use triunity::storage::database::BlockchainDB;
 use triunity::storage::TriUnityStorage;
+use triunity::supervisor::{RestartPolicy, Supervisor};
 use triunity::web::DashboardServer;
 
 #[tokio::main]
@@ -11,13 +20,34 @@ async fn main() -> Result<(), String> {
     let matches = Command::new("TriUnity Dashboard")
         .version("1.0.0")
         .about("🚀 TriUnity Blockchain Dashboard Server")
+        .arg(
+            Arg::new("config")
+                .short('c')
+                .long("config")
+                .value_name("FILE")
+                .help("Node configuration file (TOML)")
+        )
         .arg(
             Arg::new("port")
                 .short('p')
                 .long("port")
                 .value_name("PORT")
-                .help("Web server port")
-                .default_value("8080")
+                .help("Web server port on 127.0.0.1 (default 8080)")
+        )
+        .arg(
+            Arg::new("bind")
+                .short('b')
+                .long("bind")
+                .value_name("ADDR:PORT")
+                .help("Dashboard listen address, may be repeated")
+                .action(ArgAction::Append)
+        )
+        .arg(
+            Arg::new("rpc-bind")
+                .long("rpc-bind")
+                .value_name("ADDR:PORT")
+                .help("Separate JSON-RPC listen address, may be repeated")
+                .action(ArgAction::Append)
         )
         .arg(
             Arg::new("data-dir")
@@ -29,26 +59,75 @@ async fn main() -> Result<(), String> {
         )
         .get_matches();
 
-    let port: u16 = matches.get_one::<String>("port")
-        .unwrap()
-        .parse()
-        .map_err(|e| format!("Invalid port number: {}", e))?;
+    let mut config = match matches.get_one::<String>("config") {
+        Some(path) => NodeConfig::load(path)?,
+        None => NodeConfig::default(),
+    };
+
+    if let Some(port) = matches.get_one::<String>("port") {
+        let port: u16 = port
+            .parse()
+            .map_err(|e| format!("Invalid port number: {}", e))?;
+        config.web.dashboard.addresses = vec![SocketAddr::from(([127, 0, 0, 1], port))];
+    }
+    if let Some(addresses) = matches.get_many::<String>("bind") {
+        config.web.dashboard.addresses = parse_addresses(addresses)?;
+    }
+    if let Some(addresses) = matches.get_many::<String>("rpc-bind") {
+        config.web.rpc.addresses = parse_addresses(addresses)?;
+    }
+    config.validate()?;
     
     let data_dir = matches.get_one::<String>("data-dir").unwrap();
 
     println!("Starting TriUnity Dashboard Server");
-    println!("   Port: {}", port);
+    for address in &config.web.dashboard.addresses {
+        println!("   Listen: {}://{}", config.web.dashboard.scheme(), address);
+    }
     println!("   Data Directory: {}", data_dir);
     println!("Initializing blockchain components...");
     
-    let storage = Arc::new(TriUnityStorage::new(data_dir).await?);
-    let consensus_engine = Arc::new(ConsensusEngine::new());
+    let db = BlockchainDB::new(data_dir)?.with_cache_sizes(config.cache.blocks, config.cache.accounts);
+    let storage = Arc::new(TriUnityStorage::from_db(db.clone()));
+    let metrics = MetricsCollector::from_config(&config.metrics, &db)?;
+    let consensus_engine = Arc::new(ConsensusEngine::new().with_metrics(metrics));
+    let rpc = Arc::new(
+        RpcServer::new(db.clone())
+            .with_rate_limit(config.web.rate_limit.clone())
+            .with_admin_tokens(config.web.admin_tokens.clone())
+            .with_debug(config.web.debug_rpc),
+    );
+    let export = Arc::new(ExportService::new(db.clone(), consensus_engine.clone()));
+    let supervisor = Supervisor::new();
+    if !config.alerts.rules.is_empty() {
+        let alerts = Arc::new(AlertService::from_config(&config.alerts, consensus_engine.clone()).with_db(db.clone()));
+        let interval = Duration::from_secs(config.alerts.interval_secs);
+        supervisor.spawn("alerts", RestartPolicy::Always, move || alerts.clone().run(interval));
+        println!("   Alerts: {} rules", config.alerts.rules.len());
+    }
+    if config.metrics.persist {
+        println!("   Metrics: persisted, {} TPS readings restored", consensus_engine.metrics().tps_history().count());
+    }
     
     println!("Blockchain components initialized");
     println!("Starting dashboard server...");
-    let dashboard_server = DashboardServer::new(consensus_engine, storage);
+    #[cfg(feature = "graphql")]
+    let graphql = Arc::new(triunity::api::graphql::GraphqlService::new(db.clone()).with_consensus(consensus_engine.clone()));
+    let dashboard_server = DashboardServer::new(consensus_engine, storage)
+        .with_rpc(rpc)
+        .with_export(export)
+        .with_firehose(db)
+        .with_supervisor(supervisor);
+    #[cfg(feature = "graphql")]
+    let dashboard_server = dashboard_server.with_graphql(graphql);
     
-    dashboard_server.start(port).await?;
+    dashboard_server.serve(&config.web).await?;
     
     Ok(())
-}
\ No newline at end of file
+}
+
+fn parse_addresses<'a>(addresses: impl Iterator<Item = &'a String>) -> Result<Vec<SocketAddr>, String> {
+    addresses
+        .map(|address| address.parse().map_err(|e| format!("Invalid listen address {}: {}", address, e)))
+        .collect()
+}
@@ -0,0 +1,466 @@
+use clap::{Arg, ArgAction, Command};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use triunity::api::rpc::RpcServer;
+use triunity::cli::inspect::short_hex;
+use triunity::consensus::gas::GasSchedule;
+use triunity::crypto::bech32::short_address;
+use triunity::crypto::QuantumKeyPair;
+use triunity::events::log_events;
+use triunity::logging::{self, Level};
+use triunity::mempool::MempoolUpdate;
+use triunity::config::NodeConfig;
+use triunity::network::profile::NetworkId;
+use triunity::node::embed::NodeMode;
+use triunity::node::Node;
+use triunity::storage::database::BlockchainDB;
+use triunity::supervisor::{RestartPolicy, Supervisor};
+use triunity::testnet::{Testnet, TestnetConfig};
+use triunity::wallet::keystore;
+use triunity::VERSION;
+#[cfg(feature = "faucet")]
+use {
+    triunity::faucet::{Faucet, FaucetConfig, FAUCET_GENESIS_BALANCE},
+};
+#[cfg(feature = "grpc")]
+use triunity::api::grpc::GrpcService;
+
+#[tokio::main]
+async fn main() {
+    let matches = Command::new("triunity-node")
+        .version(VERSION)
+        .author("TriUnity Team <team@triunity.org>")
+        .about("TriUnity Protocol Node")
+        .arg_required_else_help(true)
+        .args_conflicts_with_subcommands(true)
+        .arg(
+            Arg::new("network")
+                .long("network")
+                .value_name("NAME")
+                .help("mainnet, testnet or devnet: sets the chain id, default ports, bootstrap peers and data subdirectory")
+                .global(true)
+        )
+        .args(dev_args())
+        .subcommand(testnet_command())
+        .subcommand(watchtower_command())
+        .get_matches();
+
+    if matches.get_flag("dev") {
+        if let Err(e) = run_dev(&matches).await {
+            eprintln!("Dev chain failed: {}", e);
+            process::exit(1);
+        }
+    } else if let Some(("testnet", sub_matches)) = matches.subcommand() {
+        if let Err(e) = run_testnet(sub_matches).await {
+            eprintln!("Testnet failed: {}", e);
+            process::exit(1);
+        }
+    } else if let Some(("watchtower", sub_matches)) = matches.subcommand() {
+        if let Err(e) = run_watchtower(sub_matches).await {
+            eprintln!("Watchtower failed: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Genesis balance of the dev account, in base units (1B TRI)
+const DEV_ACCOUNT_BALANCE: u64 = 1_000_000_000 * 100_000_000;
+
+const DEV_MEMPOOL_GC_INTERVAL: Duration = Duration::from_secs(5);
+
+fn dev_args() -> Vec<Arg> {
+    vec![
+        Arg::new("dev")
+            .long("dev")
+            .help("Run a single-node development chain with a funded account, sealing blocks as transactions arrive")
+            .action(ArgAction::SetTrue),
+        Arg::new("dev-block-time")
+            .long("dev-block-time")
+            .value_name("MS")
+            .help("Also seal a block every MS milliseconds, even an empty one")
+            .requires("dev"),
+        Arg::new("dev-rpc-port")
+            .long("dev-rpc-port")
+            .value_name("PORT")
+            .help("Serve JSON-RPC on 127.0.0.1:PORT, by default the devnet RPC port")
+            .requires("dev"),
+        Arg::new("dev-data-dir")
+            .long("dev-data-dir")
+            .value_name("DIR")
+            .help("Directory whose devnet subdirectory holds the dev chain and its key file (wiped on start)")
+            .default_value("./dev-chain")
+            .requires("dev"),
+    ]
+}
+
+/// One node without peers: the dev account holds the whole genesis supply and every
+/// admitted transaction is sealed straight away
+async fn run_dev(matches: &clap::ArgMatches) -> Result<(), String> {
+    logging::set_level(Level::Debug);
+    let network = network_arg(matches)?.unwrap_or_default();
+    if network != NetworkId::Devnet {
+        return Err(format!("--dev runs a devnet chain, not {}", network));
+    }
+    let profile = network.profile();
+    let data_dir = PathBuf::from(matches.get_one::<String>("dev-data-dir").unwrap()).join(network.data_subdir());
+    let port = match matches.get_one::<String>("dev-rpc-port") {
+        Some(_) => parse_arg(matches, "dev-rpc-port")?,
+        None => profile.rpc_port,
+    };
+    let address = SocketAddr::from(([127, 0, 0, 1], port));
+    let interval = match matches.get_one::<String>("dev-block-time") {
+        Some(_) => Some(Duration::from_millis(parse_arg(matches, "dev-block-time")?)),
+        None => None,
+    };
+
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let db = BlockchainDB::new(data_dir.to_str().ok_or("Invalid data directory")?)?;
+    network.claim(&db)?;
+    let account = QuantumKeyPair::generate();
+    let key_file = data_dir.join("dev-key.json");
+    keystore::save(&key_file, &account)?;
+    db.store_genesis_allocations(&[(account.public_key().to_vec(), DEV_ACCOUNT_BALANCE)])?;
+    let node = Arc::new(Node::open(db.clone())?);
+
+    let rpc = Arc::new(RpcServer::new(db).with_node(node.clone()));
+    let (address, server) = warp::serve(rpc.routes())
+        .try_bind_ephemeral(address)
+        .map_err(|e| format!("Could not serve RPC on {}: {}", address, e))?;
+    tokio::spawn(server);
+
+    println!("Starting dev chain, peer networking disabled");
+    println!("   Dev account: {}", short_address(account.public_key()));
+    println!("   Public key: {}", hex::encode(account.public_key()));
+    println!("   Key file: {}", key_file.display());
+    println!("   Balance: {} base units", DEV_ACCOUNT_BALANCE);
+    println!("   RPC: http://{}", address);
+    match interval {
+        Some(interval) => println!("   Sealing: on every transaction and every {:?}", interval),
+        None => println!("   Sealing: on every transaction"),
+    }
+    println!("   Try: triunity-cli send --rpc http://{} --key {} --to <ADDRESS> --amount 1", address, key_file.display());
+
+    let supervisor = Supervisor::new();
+    let sealer = node.clone();
+    supervisor.spawn("sealer", RestartPolicy::Always, move || sealer.clone().run_instant_sealing(interval));
+    let chain = node.clone();
+    supervisor.spawn("mempool-gc", RestartPolicy::Always, move || chain.clone().run_mempool_gc(DEV_MEMPOOL_GC_INTERVAL));
+    tokio::spawn(log_events("dev".to_string(), node.events().subscribe()));
+    tokio::spawn(log_mempool(node.subscribe_mempool()));
+
+    let _ = tokio::signal::ctrl_c().await;
+    supervisor.shutdown();
+    println!("Dev chain stopped");
+    Ok(())
+}
+
+/// Prints each transaction entering and leaving the mempool
+async fn log_mempool(mut updates: broadcast::Receiver<MempoolUpdate>) {
+    loop {
+        match updates.recv().await {
+            Ok(MempoolUpdate::Admitted { hash, fee, size, lane }) => {
+                println!("[dev] Admitted {} ({:?} lane, fee {}, {} bytes)", short_hex(&hash), lane, fee, size)
+            }
+            Ok(MempoolUpdate::Evicted { hash, reason }) => println!("[dev] Evicted {}: {:?}", short_hex(&hash), reason),
+            Err(broadcast::error::RecvError::Lagged(missed)) => eprintln!("[dev] Mempool log skipped {} updates", missed),
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+fn testnet_command() -> Command {
+    let command = Command::new("testnet")
+        .about("Run a local multi-node network in this process")
+        .arg(
+            Arg::new("nodes")
+                .long("nodes")
+                .value_name("COUNT")
+                .help("Number of nodes")
+                .default_value("4")
+        )
+        .arg(
+            Arg::new("validators")
+                .long("validators")
+                .value_name("COUNT")
+                .help("How many of the nodes produce blocks")
+                .default_value("4")
+        )
+        .arg(
+            Arg::new("base-port")
+                .long("base-port")
+                .value_name("PORT")
+                .help("Node i listens on base-port + i, by default the network's peer port")
+        )
+        .arg(
+            Arg::new("block-time")
+                .long("block-time")
+                .value_name("MS")
+                .help("Block interval in milliseconds")
+                .default_value("1000")
+        )
+        .arg(
+            Arg::new("data-dir")
+                .short('d')
+                .long("data-dir")
+                .value_name("DIR")
+                .help("Directory whose network subdirectory holds per-node chain data (wiped on start)")
+                .default_value("./testnet")
+        )
+        .arg(
+            Arg::new("archive")
+                .long("archive")
+                .help("Keep the state of every height for historical queries")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("ancient-after")
+                .long("ancient-after")
+                .value_name("BLOCKS")
+                .help("Move finalized blocks older than this many blocks to flat-file storage")
+        )
+        .arg(
+            Arg::new("witnesses")
+                .long("witnesses")
+                .help("Store an execution witness with every produced block for stateless validators")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("chain-params")
+                .long("chain-params")
+                .value_name("FILE")
+                .help("JSON chain parameters and scheduled upgrades to start the chain with")
+        )
+        .arg(
+            Arg::new("bad-block-dir")
+                .long("bad-block-dir")
+                .value_name("DIR")
+                .help("Keep blocks refused on import here, one subdirectory per node")
+        )
+        .arg(
+            Arg::new("gas-schedule")
+                .long("gas-schedule")
+                .value_name("FILE")
+                .help("JSON gas schedule for genesis, as printed by triunity-cli benchmark --suite gas")
+        )
+        .arg(
+            Arg::new("blocks")
+                .long("blocks")
+                .value_name("COUNT")
+                .help("Stop once every node has this many blocks")
+        );
+    #[cfg(feature = "faucet")]
+    let command = command.arg(
+        Arg::new("faucet-port")
+            .long("faucet-port")
+            .value_name("PORT")
+            .help("Fund a faucet account at genesis and serve it on 127.0.0.1:PORT through the first node")
+    );
+    #[cfg(feature = "grpc")]
+    let command = command.arg(
+        Arg::new("grpc-port")
+            .long("grpc-port")
+            .value_name("PORT")
+            .help("Serve the gRPC API of the first node on 127.0.0.1:PORT")
+    );
+    command
+}
+
+fn parse_arg<T: std::str::FromStr>(matches: &clap::ArgMatches, name: &str) -> Result<T, String> {
+    let value = matches.get_one::<String>(name).unwrap();
+    value.parse().map_err(|_| format!("Invalid --{}: {}", name, value))
+}
+
+fn load_gas_schedule(path: &str) -> Result<GasSchedule, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Invalid gas schedule {}: {}", path, e))
+}
+
+/// The `--network` given, if any
+fn network_arg(matches: &clap::ArgMatches) -> Result<Option<NetworkId>, String> {
+    matches.get_one::<String>("network").map(|name| name.parse()).transpose()
+}
+
+async fn run_testnet(matches: &clap::ArgMatches) -> Result<(), String> {
+    #[cfg(feature = "faucet")]
+    let faucet = match matches.get_one::<String>("faucet-port") {
+        Some(_) => Some((parse_arg::<u16>(matches, "faucet-port")?, QuantumKeyPair::generate())),
+        None => None,
+    };
+    #[cfg(feature = "faucet")]
+    let genesis_accounts = faucet.iter().map(|(_, keypair)| (keypair.public_key().to_vec(), FAUCET_GENESIS_BALANCE)).collect();
+    #[cfg(not(feature = "faucet"))]
+    let genesis_accounts = Vec::new();
+    let network = network_arg(matches)?.unwrap_or_default();
+    let config = TestnetConfig {
+        nodes: parse_arg(matches, "nodes")?,
+        validators: parse_arg(matches, "validators")?,
+        base_port: match matches.get_one::<String>("base-port") {
+            Some(_) => parse_arg(matches, "base-port")?,
+            None => network.profile().p2p_port,
+        },
+        block_time: Duration::from_millis(parse_arg(matches, "block-time")?),
+        data_dir: PathBuf::from(matches.get_one::<String>("data-dir").unwrap()).join(network.data_subdir()),
+        archive: matches.get_flag("archive"),
+        ancient_after: match matches.get_one::<String>("ancient-after") {
+            Some(_) => Some(parse_arg(matches, "ancient-after")?),
+            None => None,
+        },
+        witnesses: matches.get_flag("witnesses"),
+        bad_block_dir: matches.get_one::<String>("bad-block-dir").map(PathBuf::from),
+        gas_schedule: match matches.get_one::<String>("gas-schedule") {
+            Some(path) => load_gas_schedule(path)?,
+            None => GasSchedule::default(),
+        },
+        genesis_accounts,
+        params: match matches.get_one::<String>("chain-params") {
+            Some(path) => {
+                let json = std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+                serde_json::from_str(&json).map_err(|e| format!("Invalid chain parameters in {}: {}", path, e))?
+            }
+            None => Default::default(),
+        },
+        network,
+    };
+    let target: Option<u64> = match matches.get_one::<String>("blocks") {
+        Some(_) => Some(parse_arg(matches, "blocks")?),
+        None => None,
+    };
+
+    println!("Starting local {} network: {} nodes, {} validators", network, config.nodes, config.validators);
+    let testnet = Testnet::launch(config).await?;
+    for node in &testnet.nodes {
+        println!(
+            "   {} {} {}{}",
+            node.name,
+            node.address,
+            short_hex(&node.network.node().node_id()),
+            if node.is_validator { " (validator)" } else { "" }
+        );
+        tokio::spawn(log_events(node.name.clone(), node.network.node().events().subscribe()));
+    }
+    #[cfg(feature = "faucet")]
+    if let Some((port, keypair)) = faucet {
+        let faucet = Arc::new(Faucet::new(testnet.nodes[0].network.node().clone(), keypair, FaucetConfig::default()));
+        let address = SocketAddr::from(([127, 0, 0, 1], port));
+        let (_, server) = warp::serve(faucet.routes()).try_bind_ephemeral(address).map_err(|e| format!("Could not serve the faucet on {}: {}", address, e))?;
+        tokio::spawn(server);
+        println!("   Faucet: http://{}/faucet", address);
+    }
+    #[cfg(feature = "grpc")]
+    if matches.get_one::<String>("grpc-port").is_some() {
+        let node = testnet.nodes[0].network.node().clone();
+        let address = std::net::SocketAddr::from(([127, 0, 0, 1], parse_arg::<u16>(matches, "grpc-port")?));
+        let service = GrpcService::new(node.db().clone()).with_node(node);
+        tokio::spawn(async move {
+            if let Err(e) = service.serve(address).await {
+                eprintln!("{}", e);
+            }
+        });
+        println!("   gRPC: http://{}", address);
+    }
+
+    let mut status = tokio::time::interval(Duration::from_secs(2));
+    loop {
+        tokio::select! {
+            _ = status.tick() => {
+                let heights = testnet.heights();
+                let peers: Vec<usize> = testnet.nodes.iter().map(|node| node.network.peers().len()).collect();
+                println!("Heights {:?}  Peers {:?}", heights, peers);
+                if target.is_some_and(|target| heights.iter().all(|height| *height >= target)) {
+                    break;
+                }
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    testnet.shutdown();
+    println!("Testnet stopped");
+    Ok(())
+}
+
+fn watchtower_command() -> Command {
+    Command::new("watchtower")
+        .about("Follow a chain without producing blocks or serving anyone, re-verifying every block and alerting on divergence or censorship")
+        .arg(
+            Arg::new("peer")
+                .long("peer")
+                .value_name("ADDRESS")
+                .help("Node to follow and compare against; repeat for more, others are found through peer exchange. Without one, the network's bootstrap peers are dialed")
+                .action(ArgAction::Append)
+        )
+        .arg(
+            Arg::new("data-dir")
+                .short('d')
+                .long("data-dir")
+                .value_name("DIR")
+                .help("Directory whose network subdirectory holds the chain database, which must already hold the chain's genesis, e.g. copied from one of its nodes")
+                .default_value("./watchtower")
+        )
+        .arg(
+            Arg::new("config")
+                .short('c')
+                .long("config")
+                .value_name("FILE")
+                .help("TOML config whose [watchtower], [alerts] and [network] sections are used")
+        )
+}
+
+async fn run_watchtower(matches: &clap::ArgMatches) -> Result<(), String> {
+    let mut config = match matches.get_one::<String>("config") {
+        Some(path) => NodeConfig::load(path)?,
+        None => NodeConfig::default(),
+    };
+    if let Some(network) = network_arg(matches)? {
+        config.network.id = network;
+    }
+    let network = config.network.id;
+    let peers: Vec<&String> = matches.get_many::<String>("peer").unwrap_or_default().collect();
+    if peers.is_empty() && config.network.bootstrap_peers.is_empty() && network.profile().bootstrap_peers.is_empty() {
+        return Err(format!("No --peer given and {} has no bootstrap peers", network));
+    }
+    let data_dir = PathBuf::from(matches.get_one::<String>("data-dir").unwrap()).join(network.data_subdir());
+    let mut builder = Node::builder().mode(NodeMode::Watchtower).config(config.clone()).data_dir(&data_dir);
+    for peer in peers {
+        builder = builder.peer(peer.parse().map_err(|_| format!("Invalid --peer: {}", peer))?);
+    }
+    let built = builder.build()?;
+    if built.node().next_height()? == 0 && built.node().db().get_genesis_allocations()?.is_empty() {
+        return Err(format!("No chain genesis in {}", data_dir.display()));
+    }
+    let handle = built.start().await?;
+    let watchtower = handle.watchtower().ok_or("Watchtower did not start")?.clone();
+
+    println!("Starting watchtower, block production and serving disabled");
+    println!("   Network: {} (chain id {})", network, network.chain_id());
+    println!("   Data directory: {}", data_dir.display());
+    println!("   Check interval: {}s", config.watchtower.interval_secs);
+    println!("   Censorship after: {} blocks with room", config.watchtower.censorship_blocks);
+    println!("   Alerts: {} rules", config.alerts.rules.len());
+    tokio::spawn(log_events("watchtower".to_string(), handle.subscribe()));
+
+    let mut status = tokio::time::interval(Duration::from_secs(config.watchtower.interval_secs));
+    loop {
+        tokio::select! {
+            _ = status.tick() => {
+                let status = watchtower.status();
+                println!(
+                    "Verified {:?}  Peers compared {}  Divergences {}  Censored {}",
+                    status.verified_height,
+                    status.peers_compared,
+                    status.divergences.len(),
+                    status.censored.len()
+                );
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    handle.shutdown();
+    println!("Watchtower stopped");
+    Ok(())
+}
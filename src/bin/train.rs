@@ -0,0 +1,96 @@
+use clap::{Arg, ArgAction, Command};
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use triunity::consensus::router::training::{LinearPolicy, Trainer, TrainingConfig};
+use triunity::consensus::router::PATHS;
+use triunity::consensus::simulation::Scenario;
+use triunity::VERSION;
+
+fn main() {
+    let matches = Command::new("triunity-train")
+        .version(VERSION)
+        .author("TriUnity Team <team@triunity.org>")
+        .about("Train a consensus path model on the network simulator, offline, and export it for [consensus] model")
+        .arg(Arg::new("epochs").long("epochs").value_name("N").help("Training epochs").default_value("50"))
+        .arg(Arg::new("episodes").long("episodes").value_name("N").help("Simulations per epoch").default_value("8"))
+        .arg(Arg::new("steps").long("steps").value_name("N").help("Blocks per simulation").default_value("120"))
+        .arg(Arg::new("learning-rate").long("learning-rate").value_name("RATE").default_value("2.0"))
+        .arg(Arg::new("seed").long("seed").value_name("SEED").help("Seed for the simulations; the same seed trains the same model").default_value("0"))
+        .arg(
+            Arg::new("scenario")
+                .long("scenario")
+                .value_name("NAME")
+                .help("calm, congestion_spike, attack or congested_attack; repeat for more, all by default")
+                .action(ArgAction::Append)
+        )
+        .arg(Arg::new("resume").long("resume").value_name("FILE").help("Policy weights JSON from an earlier run to keep training"))
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("FILE")
+                .help("ONNX model to write; the policy weights go next to it as JSON")
+                .default_value("router-model.onnx")
+        )
+        .get_matches();
+
+    if let Err(e) = run(&matches) {
+        eprintln!("Training failed: {}", e);
+        process::exit(1);
+    }
+}
+
+fn parse<T: std::str::FromStr>(matches: &clap::ArgMatches, name: &str) -> Result<T, String> {
+    let value = matches.get_one::<String>(name).unwrap();
+    value.parse().map_err(|_| format!("Invalid --{}: {}", name, value))
+}
+
+fn run(matches: &clap::ArgMatches) -> Result<(), String> {
+    let scenarios = match matches.get_many::<String>("scenario") {
+        Some(names) => names.map(|name| name.parse()).collect::<Result<Vec<Scenario>, String>>()?,
+        None => Scenario::ALL.to_vec(),
+    };
+    let config = TrainingConfig {
+        epochs: parse(matches, "epochs")?,
+        episodes: parse(matches, "episodes")?,
+        steps: parse(matches, "steps")?,
+        learning_rate: parse(matches, "learning-rate")?,
+        seed: parse(matches, "seed")?,
+        scenarios,
+    };
+    let policy = match matches.get_one::<String>("resume") {
+        Some(path) => LinearPolicy::from_json(&fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?)?,
+        None => LinearPolicy::default(),
+    };
+    let output = PathBuf::from(matches.get_one::<String>("output").unwrap());
+
+    println!("Training path model");
+    println!("   Epochs: {} x {} simulations of {} blocks", config.epochs, config.episodes, config.steps);
+    println!("   Scenarios: {:?}", config.scenarios);
+    println!("   Seed: {}", config.seed);
+
+    let mut trainer = Trainer::new(config, policy)?;
+    let before = trainer.evaluate(trainer.policy());
+    println!("Start     policy {:.3}  rules {:.3}", before.policy_reward, before.rules_reward);
+    let policy = trainer
+        .train(|report| {
+            println!(
+                "Epoch {:>3}  policy {:.3}  rules {:.3}  explore {:.3}  confident {:.0}%",
+                report.epoch,
+                report.evaluation.policy_reward,
+                report.evaluation.rules_reward,
+                report.mean_reward,
+                report.evaluation.confident * 100.0
+            );
+        })
+        .clone();
+
+    fs::write(&output, policy.to_onnx()).map_err(|e| format!("Could not write {}: {}", output.display(), e))?;
+    let weights = output.with_extension("json");
+    fs::write(&weights, policy.to_json()).map_err(|e| format!("Could not write {}: {}", weights.display(), e))?;
+    println!("Wrote {} scoring {:?}", output.display(), PATHS);
+    println!("   Weights: {} (--resume to keep training)", weights.display());
+    println!("   Route with it by setting model = \"{}\" under [consensus] in a node built with --features ml", output.display());
+    Ok(())
+}
@@ -0,0 +1,370 @@
+use serde::Serialize;
+use std::str::FromStr;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use crate::consensus::gas::{GasOp, GasSchedule};
+use crate::crypto::{hash256, QuantumKeyPair};
+use crate::node::import;
+use crate::node::verified::VerifiedCache;
+use crate::storage::blocks::{Block, ConsensusData, Transaction};
+use crate::storage::database::BlockchainDB;
+use crate::storage::merkle::MerkleTree;
+use crate::storage::state::StateManager;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BenchSuite {
+    Keygen,
+    Sign,
+    Verify,
+    Hash,
+    Merkle,
+    BlockImport,
+    /// Signature checks of a block at import, none of whose transactions were seen before
+    BlockVerify,
+    /// `BlockVerify` with every transaction already verified on mempool admission
+    BlockVerifyCached,
+    StateApply,
+    /// Wall time of each step gas is charged for, and the gas schedule it suggests
+    Gas,
+}
+
+impl BenchSuite {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Keygen => "keygen",
+            Self::Sign => "sign",
+            Self::Verify => "verify",
+            Self::Hash => "hash",
+            Self::Merkle => "merkle",
+            Self::BlockImport => "block-import",
+            Self::BlockVerify => "block-verify",
+            Self::BlockVerifyCached => "block-verify-cached",
+            Self::StateApply => "state-apply",
+            Self::Gas => "gas",
+        }
+    }
+
+    /// Default workload size: message bytes for sign/verify/hash,
+    /// leaves for merkle, transactions for the block and state suites and bytes hashed
+    /// and accounts touched per sample for gas
+    pub fn default_size(&self) -> usize {
+        match self {
+            Self::Keygen => 0,
+            Self::Sign | Self::Verify => 256,
+            Self::Hash | Self::Gas => 1024,
+            Self::Merkle => 1024,
+            Self::BlockImport | Self::BlockVerify | Self::BlockVerifyCached | Self::StateApply => 100,
+        }
+    }
+}
+
+impl FromStr for BenchSuite {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keygen" => Ok(Self::Keygen),
+            "sign" => Ok(Self::Sign),
+            "verify" => Ok(Self::Verify),
+            "hash" => Ok(Self::Hash),
+            "merkle" => Ok(Self::Merkle),
+            "block-import" => Ok(Self::BlockImport),
+            "block-verify" => Ok(Self::BlockVerify),
+            "block-verify-cached" => Ok(Self::BlockVerifyCached),
+            "state-apply" => Ok(Self::StateApply),
+            "gas" => Ok(Self::Gas),
+            other => Err(format!("Unknown benchmark suite: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchConfig {
+    pub suite: BenchSuite,
+    pub iterations: usize,
+    pub warmup: usize,
+    pub size: usize,
+}
+
+/// Nanoseconds of wall time one gas stands for in a suggested schedule
+pub const NANOS_PER_GAS: f64 = 10.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GasStepResult {
+    pub step: String,
+    /// Mean wall time of one unit of the step on this machine
+    pub mean_ns: f64,
+    pub suggested_gas: u64,
+    /// Cost in the default schedule
+    pub default_gas: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GasCalibration {
+    pub iterations: usize,
+    pub nanos_per_gas: f64,
+    pub steps: Vec<GasStepResult>,
+    /// Default schedule with each step at its suggested cost, for a genesis file
+    pub schedule: GasSchedule,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchResult {
+    pub suite: String,
+    pub iterations: usize,
+    pub size: usize,
+    pub mean_us: f64,
+    pub min_us: f64,
+    pub p50_us: f64,
+    pub p90_us: f64,
+    pub p99_us: f64,
+    pub max_us: f64,
+    pub ops_per_sec: f64,
+}
+
+pub fn run_suite(config: &BenchConfig) -> Result<BenchResult, String> {
+    let size = config.size;
+    let samples = match config.suite {
+        BenchSuite::Keygen => measure(config, || (), |_| {
+            QuantumKeyPair::generate();
+        }),
+        BenchSuite::Sign => {
+            let keypair = QuantumKeyPair::generate();
+            let message = vec![0xab; size];
+            measure(config, || (), |_| {
+                keypair.sign(&message).expect("signing failed");
+            })
+        }
+        BenchSuite::Verify => {
+            let keypair = QuantumKeyPair::generate();
+            let message = vec![0xab; size];
+            let signature = keypair.sign(&message).map_err(|e| e.to_string())?;
+            measure(config, || (), |_| {
+                assert!(signature.verify(&message, keypair.public_key()));
+            })
+        }
+        BenchSuite::Hash => {
+            let data = vec![0xab; size];
+            measure(config, || (), |_| {
+                hash256(&data);
+            })
+        }
+        BenchSuite::Merkle => {
+            let leaves: Vec<Vec<u8>> = (0..size as u64).map(|i| i.to_be_bytes().to_vec()).collect();
+            measure(config, || (), |_| {
+                MerkleTree::new(&leaves);
+            })
+        }
+        BenchSuite::BlockImport => {
+            let (state, transactions) = funded_transactions(size)?;
+            let temp_dir = std::env::temp_dir().join(format!("triunity_bench_{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&temp_dir);
+            let db = BlockchainDB::new(temp_dir.to_str().unwrap())?;
+            let mut height = 0;
+
+            let samples = measure(config, || {
+                height += 1;
+                (state.clone(), Block::new([0; 32], transactions.clone(), height, ConsensusData::default()))
+            }, |(mut state, block)| {
+                assert!(block.validate());
+                state.apply_block(&block).expect("state transition failed");
+                db.store_block(&block.with_state_root(state.state_root())).expect("store failed");
+            });
+
+            let _ = std::fs::remove_dir_all(&temp_dir);
+            samples
+        }
+        BenchSuite::BlockVerify | BenchSuite::BlockVerifyCached => {
+            let (_, transactions) = funded_transactions(size)?;
+            let verified = VerifiedCache::new(match config.suite {
+                BenchSuite::BlockVerify => 0,
+                _ => transactions.len(),
+            });
+            import::verify_signatures(&transactions, &verified).map_err(|(index, e)| format!("Transaction {}: {}", index, e))?;
+            measure(config, || (), |_| {
+                import::verify_signatures(&transactions, &verified).expect("signature check failed");
+            })
+        }
+        BenchSuite::StateApply => {
+            let (state, transactions) = funded_transactions(size)?;
+            let block = Block::new([0; 32], transactions, 1, ConsensusData::default());
+            measure(config, || state.clone(), |mut state| {
+                state.apply_block(&block).expect("state transition failed");
+                state.state_root();
+            })
+        }
+        BenchSuite::Gas => return Err("The gas suite measures several steps; run it with calibrate_gas".to_string()),
+    };
+
+    Ok(summarize(config, samples))
+}
+
+/// Times each step `GasSchedule` charges for on its own and suggests a cost for it at
+/// `NANOS_PER_GAS`. Hashing, account reads and account writes run `size` times per
+/// sample, so their cost per unit stays above the timer's resolution
+pub fn calibrate_gas(config: &BenchConfig) -> Result<GasCalibration, String> {
+    let count = config.size.max(1);
+    let (_, transactions) = funded_transactions(1)?;
+    let tx = &transactions[0];
+    let signing_data = tx.get_signing_data();
+    let data = vec![0xab; count];
+    let addresses: Vec<Vec<u8>> = (0..count as u64).map(|i| hash256(&i.to_be_bytes()).as_bytes().to_vec()).collect();
+    let allocations: Vec<(Vec<u8>, u64)> = addresses.iter().map(|address| (address.clone(), 1)).collect();
+    let mut state = StateManager::from_allocations(&allocations)?;
+    let per_unit = |samples: Vec<Duration>| mean_ns(&samples) / count as f64;
+
+    let signature_verify = mean_ns(&measure(config, || (), |_| {
+        assert!(tx.signature.verify(&signing_data, &tx.from));
+    }));
+    let tx_byte = per_unit(measure(config, || (), |_| {
+        hash256(&data);
+    }));
+    let account_read = per_unit(measure(config, || (), |_| {
+        for address in &addresses {
+            black_box(state.get_account(address));
+        }
+    }));
+    let account_write = per_unit(measure(config, || (), |_| {
+        for address in &addresses {
+            let account = state.get_or_create_account(address);
+            account.balance = account.balance.wrapping_add(1);
+        }
+    }));
+
+    let measured = [
+        (GasOp::SignatureVerify, signature_verify),
+        (GasOp::TxByte, tx_byte),
+        (GasOp::AccountRead, account_read),
+        (GasOp::AccountWrite, account_write),
+    ];
+    let defaults = GasSchedule::default();
+    let mut schedule = defaults;
+    let steps = measured
+        .into_iter()
+        .map(|(op, mean_ns)| {
+            let suggested_gas = ((mean_ns / NANOS_PER_GAS).ceil() as u64).max(1);
+            schedule = schedule.with_cost(op, suggested_gas);
+            GasStepResult { step: op.name().to_string(), mean_ns, suggested_gas, default_gas: defaults.cost(op) }
+        })
+        .collect();
+
+    Ok(GasCalibration { iterations: config.iterations, nanos_per_gas: NANOS_PER_GAS, steps, schedule })
+}
+
+fn mean_ns(samples: &[Duration]) -> f64 {
+    match samples.len() {
+        0 => 0.0,
+        count => samples.iter().map(|sample| sample.as_nanos() as f64).sum::<f64>() / count as f64,
+    }
+}
+
+fn funded_transactions(count: usize) -> Result<(StateManager, Vec<Transaction>), String> {
+    let keypair = QuantumKeyPair::generate();
+    let state = StateManager::from_allocations(&[(keypair.public_key().to_vec(), u64::MAX / 2)])?;
+
+    let transactions = (0..count as u64)
+        .map(|nonce| {
+            let mut tx = Transaction::new(
+                keypair.public_key().to_vec(),
+                nonce.to_be_bytes().to_vec(),
+                10,
+                1,
+                nonce,
+                Vec::new(),
+                Vec::new().into(),
+            );
+            tx.signature = keypair.sign(&tx.get_signing_data()).expect("signing failed");
+            tx
+        })
+        .collect();
+
+    Ok((state, transactions))
+}
+
+fn measure<S>(
+    config: &BenchConfig,
+    mut setup: impl FnMut() -> S,
+    mut op: impl FnMut(S),
+) -> Vec<Duration> {
+    for _ in 0..config.warmup {
+        op(setup());
+    }
+
+    let mut samples = Vec::with_capacity(config.iterations);
+    for _ in 0..config.iterations {
+        let input = setup();
+        let start = Instant::now();
+        op(input);
+        samples.push(start.elapsed());
+    }
+    samples
+}
+
+fn summarize(config: &BenchConfig, mut samples: Vec<Duration>) -> BenchResult {
+    samples.sort();
+    let micros: Vec<f64> = samples.iter().map(|d| d.as_secs_f64() * 1_000_000.0).collect();
+    let mean_us = if micros.is_empty() { 0.0 } else { micros.iter().sum::<f64>() / micros.len() as f64 };
+
+    BenchResult {
+        suite: config.suite.name().to_string(),
+        iterations: micros.len(),
+        size: config.size,
+        mean_us,
+        min_us: micros.first().copied().unwrap_or(0.0),
+        p50_us: percentile(&micros, 50.0),
+        p90_us: percentile(&micros, 90.0),
+        p99_us: percentile(&micros, 99.0),
+        max_us: micros.last().copied().unwrap_or(0.0),
+        ops_per_sec: if mean_us > 0.0 { 1_000_000.0 / mean_us } else { 0.0 },
+    }
+}
+
+/// Nearest-rank percentile over already sorted samples
+pub fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let samples: Vec<f64> = (1..=100).map(|i| i as f64).collect();
+        assert_eq!(percentile(&samples, 50.0), 50.0);
+        assert_eq!(percentile(&samples, 99.0), 99.0);
+        assert_eq!(percentile(&samples, 100.0), 100.0);
+        assert_eq!(percentile(&[], 50.0), 0.0);
+    }
+
+    #[test]
+    fn test_suites_run() {
+        for suite in ["hash", "merkle", "state-apply", "block-verify-cached"] {
+            let suite: BenchSuite = suite.parse().unwrap();
+            let config = BenchConfig { suite, iterations: 5, warmup: 1, size: 8 };
+            let result = run_suite(&config).unwrap();
+            assert_eq!(result.iterations, 5);
+            assert!(result.p50_us <= result.p99_us);
+            println!("   {} suite: {:.1}us mean", result.suite, result.mean_us);
+        }
+        assert!("nope".parse::<BenchSuite>().is_err());
+    }
+
+    #[test]
+    fn test_gas_calibration() {
+        let config = BenchConfig { suite: BenchSuite::Gas, iterations: 5, warmup: 1, size: 64 };
+        assert!(run_suite(&config).is_err());
+        let calibration = calibrate_gas(&config).unwrap();
+        assert_eq!(calibration.steps.len(), GasOp::ALL.len());
+        for (step, op) in calibration.steps.iter().zip(GasOp::ALL) {
+            assert_eq!((step.step.as_str(), calibration.schedule.cost(op)), (op.name(), step.suggested_gas));
+            assert!(step.mean_ns > 0.0 && step.suggested_gas >= 1);
+        }
+        assert_eq!(calibration.schedule.gas_price, GasSchedule::default().gas_price);
+    }
+}
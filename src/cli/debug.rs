@@ -0,0 +1,81 @@
+use std::path::Path;
+
+use crate::node::import::{BadBlockRecord, BadBlocks, Quarantine};
+use crate::storage::database::BlockchainDB;
+
+/// One line per refused block, oldest first
+pub fn render_bad_blocks(records: &[BadBlockRecord]) -> String {
+    if records.is_empty() {
+        return "No bad blocks recorded".to_string();
+    }
+    let mut lines = vec![format!("{} bad blocks", records.len())];
+    for record in records {
+        let height = record.height.map_or("?".to_string(), |height| height.to_string());
+        let tx = record.tx_index.map_or(String::new(), |index| format!(" tx {}", index));
+        let peer = record.peer.as_deref().map_or("local".to_string(), |peer| format!("peer 0x{}", &peer[..peer.len().min(16)]));
+        lines.push(format!(
+            "   #{} 0x{} [{:?}{}] from {} at {}: {}",
+            height,
+            hex::encode(record.hash),
+            record.stage,
+            tx,
+            peer,
+            record.rejected_at,
+            record.reason
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Writes the recorded bad blocks, or only the one with `hash`, into `dir` as a block
+/// file and a JSON record each. Returns how many were written
+pub fn export_bad_blocks(db: &BlockchainDB, dir: &Path, hash: Option<[u8; 32]>) -> Result<usize, String> {
+    let bad_blocks = BadBlocks::open(db)?;
+    let entries = match hash {
+        Some(hash) => {
+            let entry = bad_blocks.get(&hash)?.ok_or_else(|| format!("No bad block 0x{}", hex::encode(hash)))?;
+            vec![entry]
+        }
+        None => bad_blocks.entries()?,
+    };
+    let quarantine = Quarantine::new(dir);
+    for (record, bytes) in &entries {
+        quarantine.store(record, bytes)?;
+    }
+    Ok(entries.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus::metrics::ImportStage;
+
+    #[test]
+    fn test_bad_block_export() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_debug");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let db = BlockchainDB::new(temp_dir.join("db").to_str().unwrap()).unwrap();
+        let bad_blocks = BadBlocks::open(&db).unwrap();
+        assert_eq!(render_bad_blocks(&bad_blocks.records().unwrap()), "No bad blocks recorded");
+
+        let signature = BadBlockRecord::new(Some(7), [1; 32], ImportStage::Signature, "Transaction 2: Invalid quantum signature")
+            .with_tx_index(Some(2))
+            .with_peer(Some(&[0xab; 32]));
+        let undecoded = BadBlockRecord::new(None, [2; 32], ImportStage::Decode, "io error");
+        bad_blocks.insert(&signature, b"block").unwrap();
+        bad_blocks.insert(&undecoded, b"junk").unwrap();
+        let rendered = render_bad_blocks(&bad_blocks.records().unwrap());
+        assert!(rendered.contains("#7 0x0101") && rendered.contains("[Signature tx 2] from peer 0xabababababababab"));
+        assert!(rendered.contains("#? 0x0202") && rendered.contains("from local"));
+
+        let out = temp_dir.join("export");
+        assert_eq!(export_bad_blocks(&db, &out, Some([1; 32])).unwrap(), 1);
+        assert_eq!(Quarantine::new(&out).records().unwrap(), vec![signature.clone()]);
+        assert!(export_bad_blocks(&db, &out, Some([3; 32])).is_err());
+        assert_eq!(export_bad_blocks(&db, &out, None).unwrap(), 2);
+        assert_eq!(std::fs::read(out.join(undecoded.name()).with_extension("block")).unwrap(), b"junk");
+
+        println!("   Bad block export working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
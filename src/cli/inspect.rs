@@ -0,0 +1,295 @@
+use async_trait::async_trait;
+use std::io::{self, BufRead, Write};
+
+use crate::api::client::TriUnityClient;
+use crate::crypto::bech32::{self, short_address};
+use crate::storage::blocks::{Block, ConsensusData};
+use crate::storage::database::BlockchainDB;
+use crate::storage::state::{Account, StateManager};
+
+/// Base units per whole TRI
+pub const TOKEN_DECIMALS: u32 = 8;
+
+pub use crate::api::client::TransactionRecord;
+
+#[async_trait]
+pub trait ChainSource: Send + Sync {
+    /// `None` fetches the latest block
+    async fn block(&self, height: Option<u64>) -> Result<Option<Block>, String>;
+    async fn transaction(&self, hash: [u8; 32]) -> Result<Option<TransactionRecord>, String>;
+    async fn account(&self, address: &[u8]) -> Result<Option<Account>, String>;
+    async fn peers(&self) -> Result<Vec<String>, String>;
+}
+
+pub struct LocalSource {
+    db: BlockchainDB,
+}
+
+impl LocalSource {
+    pub fn new(db: BlockchainDB) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl ChainSource for LocalSource {
+    async fn block(&self, height: Option<u64>) -> Result<Option<Block>, String> {
+        match height {
+            Some(height) => self.db.get_block(height),
+            None if self.db.block_count()? == 0 => Ok(None),
+            None => self.db.get_block(self.db.get_latest_height()?),
+        }
+    }
+
+    async fn transaction(&self, hash: [u8; 32]) -> Result<Option<TransactionRecord>, String> {
+        Ok(self.db.get_transaction(&hash)?
+            .map(|(transaction, height, index)| TransactionRecord { transaction, height, index }))
+    }
+
+    async fn account(&self, address: &[u8]) -> Result<Option<Account>, String> {
+        Ok(StateManager::replay(&self.db)?.get_account(address).cloned())
+    }
+
+    async fn peers(&self) -> Result<Vec<String>, String> {
+        Err("Peer information is only available from a running node (use --rpc)".to_string())
+    }
+}
+
+pub struct RemoteSource {
+    client: TriUnityClient,
+}
+
+impl RemoteSource {
+    pub fn new(url: &str) -> Self {
+        Self { client: TriUnityClient::new(url) }
+    }
+}
+
+#[async_trait]
+impl ChainSource for RemoteSource {
+    async fn block(&self, height: Option<u64>) -> Result<Option<Block>, String> {
+        match height {
+            Some(height) => self.client.block(height).await,
+            None => self.client.head().await,
+        }
+    }
+
+    async fn transaction(&self, hash: [u8; 32]) -> Result<Option<TransactionRecord>, String> {
+        self.client.transaction(hash).await
+    }
+
+    async fn account(&self, address: &[u8]) -> Result<Option<Account>, String> {
+        self.client.account(address).await
+    }
+
+    async fn peers(&self) -> Result<Vec<String>, String> {
+        self.client.peers().await
+    }
+}
+
+pub struct Inspector {
+    source: Box<dyn ChainSource>,
+}
+
+impl Inspector {
+    pub fn new(source: Box<dyn ChainSource>) -> Self {
+        Self { source }
+    }
+
+    /// Runs one inspector command such as `block 12` or `tx <hash>`
+    pub async fn execute(&self, line: &str) -> Result<String, String> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            ["block"] | ["block", "latest"] => self.show_block(None).await,
+            ["block", height] => {
+                let height = height.parse().map_err(|_| format!("Invalid height: {}", height))?;
+                self.show_block(Some(height)).await
+            }
+            ["tx", hash] => {
+                let hash: [u8; 32] = decode_hex(hash)?
+                    .try_into()
+                    .map_err(|_| "Transaction hash must be 32 bytes".to_string())?;
+                match self.source.transaction(hash).await? {
+                    Some(record) => Ok(render_transaction(&record)),
+                    None => Ok(format!("Transaction 0x{} not found", hex::encode(hash))),
+                }
+            }
+            ["account", address] => {
+                let address = bech32::parse_address(address)?;
+                Ok(render_account(&address, self.source.account(&address).await?.as_ref()))
+            }
+            ["peers"] => {
+                let peers = self.source.peers().await?;
+                if peers.is_empty() {
+                    return Ok("No connected peers".to_string());
+                }
+                Ok(peers.iter().map(|peer| format!("   {}", peer)).collect::<Vec<_>>().join("\n"))
+            }
+            ["help"] | [] => Ok(HELP.to_string()),
+            _ => Err(format!("Unknown command: {} (try `help`)", line.trim())),
+        }
+    }
+
+    async fn show_block(&self, height: Option<u64>) -> Result<String, String> {
+        match self.source.block(height).await? {
+            Some(block) => Ok(render_block(&block)),
+            None => Ok("Block not found".to_string()),
+        }
+    }
+
+    pub async fn run_repl(&self) {
+        println!("TriUnity Chain Inspector - type `help` for commands, `quit` to exit");
+        let stdin = io::stdin();
+        loop {
+            print!("triunity> ");
+            io::stdout().flush().unwrap();
+
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+                break;
+            }
+            let line = line.trim();
+            if line == "quit" || line == "exit" {
+                break;
+            }
+            match self.execute(line).await {
+                Ok(output) => println!("{}", output),
+                Err(e) => println!("Error: {}", e),
+            }
+        }
+    }
+}
+
+const HELP: &str = "Commands:
+   block [height|latest]   Show a decoded block
+   tx <hash>               Show a transaction by hash
+   account <address>       Show account balance and nonce (tri1... or hex)
+   peers                   List connected peers
+   quit                    Leave the inspector";
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, String> {
+    hex::decode(value.trim_start_matches("0x")).map_err(|e| format!("Invalid hex: {}", e))
+}
+
+pub fn format_amount(amount: u64) -> String {
+    let unit = 10u64.pow(TOKEN_DECIMALS);
+    let fraction = format!("{:0width$}", amount % unit, width = TOKEN_DECIMALS as usize);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        format!("{} TRI", amount / unit)
+    } else {
+        format!("{}.{} TRI", amount / unit, fraction)
+    }
+}
+
+/// Hex with long keys abbreviated, e.g. Dilithium public keys used as addresses
+pub fn short_hex(bytes: &[u8]) -> String {
+    if bytes.len() > 20 {
+        format!("0x{}..", hex::encode(&bytes[..20]))
+    } else {
+        format!("0x{}", hex::encode(bytes))
+    }
+}
+
+pub fn render_block(block: &Block) -> String {
+    let header = &block.header;
+    let consensus = match &header.consensus_data {
+        ConsensusData::FastLane { .. } | ConsensusData::FastLaneRef { .. } => "FastLane",
+        ConsensusData::SecureLane { .. } | ConsensusData::SecureLaneRef { .. } => "SecureLane",
+        ConsensusData::HybridPath { .. } | ConsensusData::HybridPathRef { .. } => "HybridPath",
+        ConsensusData::Emergency { .. } | ConsensusData::EmergencyRef { .. } => "Emergency",
+    };
+
+    let mut lines = vec![
+        format!("Block #{}", header.height),
+        format!("   Hash: 0x{}", hex::encode(block.hash())),
+        format!("   Parent: 0x{}", hex::encode(header.previous_hash)),
+        format!("   Merkle Root: 0x{}", hex::encode(header.merkle_root)),
+        format!("   State Root: 0x{}", hex::encode(header.state_root)),
+        format!("   Timestamp: {}", header.timestamp),
+        format!("   Consensus: {}", consensus),
+        format!("   Transactions: {}", block.transaction_count()),
+        format!("   Total Amount: {}", format_amount(block.total_amount())),
+        format!("   Total Fees: {}", format_amount(block.total_fees())),
+        format!("   Size: {} bytes", block.size()),
+    ];
+    for (index, tx) in block.transactions.iter().enumerate() {
+        lines.push(format!(
+            "   [{}] 0x{} {} -> {} {}",
+            index,
+            hex::encode(tx.hash()),
+            short_address(&tx.from),
+            short_address(&tx.to),
+            format_amount(tx.amount)
+        ));
+    }
+    lines.join("\n")
+}
+
+pub fn render_transaction(record: &TransactionRecord) -> String {
+    let tx = &record.transaction;
+    [
+        format!("Transaction 0x{}", hex::encode(tx.hash())),
+        format!("   Block: #{} (index {})", record.height, record.index),
+        format!("   From: {}", short_address(&tx.from)),
+        format!("   To: {}", short_address(&tx.to)),
+        format!("   Amount: {}", format_amount(tx.amount)),
+        format!("   Fee: {}", format_amount(tx.fee)),
+        format!("   Fee Payer: {}", short_address(tx.fee_account())),
+        format!("   Nonce: {}", tx.nonce),
+        format!("   Data: {} bytes", tx.data.len()),
+        format!("   Signature: {} bytes", tx.signature.size()),
+    ]
+    .into_iter()
+    .chain(tx.memo.as_ref().map(|memo| format!("   Memo: {:?}", memo)))
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+pub fn render_account(address: &[u8], account: Option<&Account>) -> String {
+    match account {
+        Some(account) => {
+            let mut lines = vec![
+                format!("Account {}", short_address(address)),
+                format!("   Balance: {}", format_amount(account.balance)),
+                format!("   Nonce: {}", account.nonce),
+            ];
+            if let Some(code_hash) = account.code_hash {
+                lines.push(format!("   Code Hash: 0x{}", hex::encode(code_hash)));
+            }
+            lines.join("\n")
+        }
+        None => format!("Account {} has no on-chain state", short_address(address)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount(0), "0 TRI");
+        assert_eq!(format_amount(250_000_000), "2.5 TRI");
+        assert_eq!(format_amount(1), "0.00000001 TRI");
+    }
+
+    #[tokio::test]
+    async fn test_local_inspector() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_inspect");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        db.store_genesis_allocations(&[(vec![0xaa], 150_000_000)]).unwrap();
+        db.store_block(&Block::new([0; 32], vec![], 0, ConsensusData::default())).unwrap();
+
+        let inspector = Inspector::new(Box::new(LocalSource::new(db)));
+        assert!(inspector.execute("block latest").await.unwrap().contains("Block #0"));
+        assert!(inspector.execute("account 0xaa").await.unwrap().contains("1.5 TRI"));
+        assert!(inspector.execute("tx 00").await.is_err());
+        assert!(inspector.execute("peers").await.is_err());
+        assert!(inspector.execute("bogus").await.is_err());
+
+        println!("   Chain inspector working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
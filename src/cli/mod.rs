@@ -0,0 +1,5 @@
+pub mod bench;
+pub mod debug;
+pub mod inspect;
+pub mod testvectors;
+pub mod validate;
@@ -0,0 +1,483 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::consensus::votes::Vote;
+use crate::crypto::{canonical, QuantumKeyPair, QuantumSignature};
+use crate::storage::batch::{BatchCall, BatchOperation, BATCH};
+use crate::storage::blocks::{Block, BlockHeader, ConsensusData, FeePayer, JsonSigningPayload, Transaction, ValidatorRef, ValidatorSetRef};
+use crate::storage::envelope::TxEnvelope;
+use crate::storage::merkle::MerkleTree;
+
+/// Bumped whenever a vector's fields or the encodings they pin down change
+pub const VECTOR_VERSION: u32 = 1;
+
+/// One JSON file per suite, named `<suite>.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorFile<T> {
+    pub version: u32,
+    pub suite: String,
+    pub vectors: Vec<T>,
+}
+
+/// A header, the bytes it is hashed over and its hash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockHashVector {
+    pub description: String,
+    pub header: BlockHeader,
+    #[serde(with = "hex::serde")]
+    pub preimage: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub hash: [u8; 32],
+}
+
+/// A transaction's fields, the payloads its sender and sponsor sign, their signatures
+/// and the transaction hash. `from` and `fee_payer` are Dilithium2 public keys
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningVector {
+    pub description: String,
+    #[serde(with = "hex::serde")]
+    pub from: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub to: Vec<u8>,
+    pub amount: u64,
+    pub fee: u64,
+    pub nonce: u64,
+    #[serde(with = "hex::serde")]
+    pub data: Vec<u8>,
+    pub valid_until_height: Option<u64>,
+    /// Hex public key of the sponsor
+    pub fee_payer: Option<String>,
+    #[serde(with = "hex::serde")]
+    pub signing_payload: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub signature: Vec<u8>,
+    /// Hex payload the sponsor signs, present when `fee_payer` is
+    pub fee_payer_payload: Option<String>,
+    pub fee_payer_signature: Option<String>,
+    #[serde(with = "hex::serde")]
+    pub hash: [u8; 32],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressVector {
+    #[serde(with = "hex::serde")]
+    pub public_key: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub address: [u8; 20],
+}
+
+/// JSON text and its canonical form. For a transaction signing payload, also the
+/// bincode payload the same fields produce
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanonicalJsonVector {
+    pub description: String,
+    pub input: String,
+    pub canonical: String,
+    /// Hex of the equivalent bincode signing payload
+    pub signing_payload: Option<String>,
+}
+
+/// Raw leaf data, hashed into leaves before the tree is built
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MerkleRootVector {
+    pub description: String,
+    /// Hex of each leaf's data
+    pub leaves: Vec<String>,
+    #[serde(with = "hex::serde")]
+    pub root: [u8; 32],
+}
+
+/// Byte-exact bincode encoding of a consensus-critical value, as blocks are stored and
+/// messages gossiped. Reordering or retyping a field of `kind` changes these bytes and
+/// forks the chain, so a mismatch fails the check instead
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WireVector {
+    pub description: String,
+    /// One of `block_header`, `block`, `transaction`, `vote` or `consensus_data`
+    pub kind: String,
+    #[serde(with = "hex::serde")]
+    pub encoding: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VectorReport {
+    pub checked: usize,
+    pub failures: Vec<String>,
+}
+
+impl VectorReport {
+    pub fn is_valid(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    fn check(&mut self, suite: &str, description: &str, result: Result<(), String>) {
+        self.checked += 1;
+        if let Err(e) = result {
+            self.failures.push(format!("{} / {}: {}", suite, description, e));
+        }
+    }
+}
+
+/// Writes every suite into `dir`. Signatures come from freshly generated keys, so two
+/// runs differ in those vectors only
+pub fn generate(dir: &Path) -> Result<usize, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+    let (sender, sponsor) = (QuantumKeyPair::generate(), QuantumKeyPair::generate());
+    let block_hashes = block_hash_vectors();
+    let signing = signing_vectors(&sender, &sponsor)?;
+    let addresses = address_vectors(&sender);
+    let merkle_roots = merkle_root_vectors();
+    let canonical = canonical_json_vectors()?;
+    let wire = wire_vectors()?;
+    let count = block_hashes.len() + signing.len() + addresses.len() + merkle_roots.len() + canonical.len() + wire.len();
+    write_suite(dir, "block_hash", block_hashes)?;
+    write_suite(dir, "signing_payload", signing)?;
+    write_suite(dir, "address", addresses)?;
+    write_suite(dir, "merkle_root", merkle_roots)?;
+    write_suite(dir, "canonical_json", canonical)?;
+    write_suite(dir, "wire_encoding", wire)?;
+    Ok(count)
+}
+
+/// Recomputes every vector in `dir` and reports those this implementation disagrees with
+pub fn verify(dir: &Path) -> Result<VectorReport, String> {
+    let mut report = VectorReport::default();
+    for vector in read_suite::<BlockHashVector>(dir, "block_hash")? {
+        let result = expect("preimage", &vector.header.hashing_bytes(), &vector.preimage)
+            .and_then(|()| expect("hash", &vector.header.hash(), &vector.hash));
+        report.check("block_hash", &vector.description, result);
+    }
+    for vector in read_suite::<SigningVector>(dir, "signing_payload")? {
+        report.check("signing_payload", &vector.description, check_signing(&vector));
+    }
+    for vector in read_suite::<AddressVector>(dir, "address")? {
+        let result = expect("address", &QuantumKeyPair::address_of(&vector.public_key), &vector.address);
+        report.check("address", &hex::encode(vector.address), result);
+    }
+    for vector in read_suite::<MerkleRootVector>(dir, "merkle_root")? {
+        let result = vector
+            .leaves
+            .iter()
+            .map(|leaf| hex::decode(leaf).map_err(|e| format!("Invalid leaf: {}", e)))
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|leaves| expect("root", &MerkleTree::new(&leaves).root(), &vector.root));
+        report.check("merkle_root", &vector.description, result);
+    }
+    for vector in read_suite::<CanonicalJsonVector>(dir, "canonical_json")? {
+        report.check("canonical_json", &vector.description, check_canonical(&vector));
+    }
+    let pinned = wire_vectors()?;
+    for vector in read_suite::<WireVector>(dir, "wire_encoding")? {
+        report.check("wire_encoding", &vector.description, check_wire(&vector, &pinned));
+    }
+    Ok(report)
+}
+
+fn expect(field: &str, computed: &[u8], expected: &[u8]) -> Result<(), String> {
+    if computed != expected {
+        return Err(format!("{} is {}, expected {}", field, hex::encode(computed), hex::encode(expected)));
+    }
+    Ok(())
+}
+
+fn check_signing(vector: &SigningVector) -> Result<(), String> {
+    let decode = |field: &Option<String>| field.as_deref().map(hex::decode).transpose().map_err(|e| e.to_string());
+    let mut tx = Transaction::new(
+        vector.from.clone(),
+        vector.to.clone(),
+        vector.amount,
+        vector.fee,
+        vector.nonce,
+        vector.data.clone(),
+        QuantumSignature::new_with_key(vector.signature.clone(), vector.from.clone()),
+    );
+    tx.valid_until_height = vector.valid_until_height;
+    if let Some(address) = decode(&vector.fee_payer)? {
+        let signature = decode(&vector.fee_payer_signature)?.unwrap_or_default();
+        tx.fee_payer = Some(FeePayer { signature: QuantumSignature::new_with_key(signature, address.clone()), address });
+    }
+
+    expect("signing payload", &tx.get_signing_data(), &vector.signing_payload)?;
+    if !tx.signature.verify(&vector.signing_payload, &vector.from) {
+        return Err("signature does not verify".to_string());
+    }
+    if let Some(payer) = &tx.fee_payer {
+        let payload = decode(&vector.fee_payer_payload)?.unwrap_or_default();
+        expect("fee payer payload", &tx.fee_payer_signing_data(), &payload)?;
+        if !payer.signature.verify(&payload, &payer.address) {
+            return Err("fee payer signature does not verify".to_string());
+        }
+    }
+    expect("hash", &tx.hash(), &vector.hash)
+}
+
+fn check_canonical(vector: &CanonicalJsonVector) -> Result<(), String> {
+    let canonical = canonical::canonicalize(&vector.input)?;
+    expect("canonical form", canonical.as_bytes(), vector.canonical.as_bytes())?;
+    match &vector.signing_payload {
+        Some(payload) => {
+            let payload = hex::decode(payload).map_err(|e| format!("Invalid signing payload: {}", e))?;
+            expect("bincode payload", &JsonSigningPayload::parse(&canonical)?.bincode_payload()?, &payload)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Checks the stored bytes still decode and re-encode unchanged, then that the same
+/// value built today encodes to them
+fn check_wire(vector: &WireVector, pinned: &[WireVector]) -> Result<(), String> {
+    fn round_trip<T: Serialize + DeserializeOwned>(encoding: &[u8]) -> Result<Vec<u8>, String> {
+        let value: T = bincode::deserialize(encoding).map_err(|e| format!("does not decode: {}", e))?;
+        bincode::serialize(&value).map_err(|e| e.to_string())
+    }
+    let reencoded = match vector.kind.as_str() {
+        "block_header" => round_trip::<BlockHeader>(&vector.encoding)?,
+        "block" => round_trip::<Block>(&vector.encoding)?,
+        "transaction" => TxEnvelope::decode(&vector.encoding)?.encode(),
+        "vote" => round_trip::<Vote>(&vector.encoding)?,
+        "consensus_data" => round_trip::<ConsensusData>(&vector.encoding)?,
+        other => return Err(format!("unknown kind {}", other)),
+    };
+    expect("re-encoding", &reencoded, &vector.encoding)?;
+    let current = pinned
+        .iter()
+        .find(|current| current.description == vector.description && current.kind == vector.kind)
+        .ok_or("no longer generated")?;
+    expect("encoding", &current.encoding, &vector.encoding)
+}
+
+fn write_suite<T: Serialize>(dir: &Path, suite: &str, vectors: Vec<T>) -> Result<(), String> {
+    let file = VectorFile { version: VECTOR_VERSION, suite: suite.to_string(), vectors };
+    let json = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+    let path = dir.join(format!("{}.json", suite));
+    std::fs::write(&path, json + "\n").map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+fn read_suite<T: DeserializeOwned>(dir: &Path, suite: &str) -> Result<Vec<T>, String> {
+    let path = dir.join(format!("{}.json", suite));
+    let contents = std::fs::read_to_string(&path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    let file: VectorFile<T> = serde_json::from_str(&contents).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+    if file.version != VECTOR_VERSION || file.suite != suite {
+        return Err(format!("{} holds {} vectors version {}, expected {} version {}", path.display(), file.suite, file.version, suite, VECTOR_VERSION));
+    }
+    Ok(file.vectors)
+}
+
+fn block_hash_vectors() -> Vec<BlockHashVector> {
+    let validator = vec![0xaa; 32];
+    let transfer = Transaction::new(validator.clone(), vec![0xcc; 32], 250, 2, 0, vec![], QuantumSignature::new(vec![]));
+    let call = Transaction::new(validator.clone(), vec![0xdd; 32], 0, 5, 1, vec![1, 2, 3, 4], QuantumSignature::new(vec![]));
+    let cases = [
+        ("empty genesis without a bloom", Block::new([0; 32], vec![], 0, ConsensusData::default()), true),
+        ("empty block with a bloom", Block::new([1; 32], vec![], 1, ConsensusData::FastLane { validator: validator.clone(), committee: None }), false),
+        ("transfer and contract call", Block::new([2; 32], vec![transfer, call], 2, ConsensusData::SecureLane { validators: vec![validator.clone(), vec![0xee; 32]] }), false),
+        (
+            "hybrid consensus data",
+            Block::new([3; 32], vec![], 3, ConsensusData::HybridPath { fast_validators: vec![validator], secure_validators: vec![vec![0xee; 32]] }),
+            false,
+        ),
+    ];
+    cases
+        .into_iter()
+        .map(|(description, block, legacy)| {
+            let mut header = block.header;
+            header.timestamp = 1_700_000_000 + header.height;
+            header.state_root = [header.height as u8 + 0x10; 32];
+            if legacy {
+                header.version = 1;
+                header.logs_bloom = None;
+            }
+            BlockHashVector { description: description.to_string(), preimage: header.hashing_bytes(), hash: header.hash(), header }
+        })
+        .collect()
+}
+
+fn signing_vectors(sender: &QuantumKeyPair, sponsor: &QuantumKeyPair) -> Result<Vec<SigningVector>, String> {
+    let from = sender.public_key().to_vec();
+    let transfer = Transaction::new(from.clone(), vec![0xcc; 32], 1_000, 1, 0, vec![], QuantumSignature::new(vec![]));
+    let call = Transaction::new(from.clone(), vec![0xdd; 32], 0, 3, 1, b"call()".to_vec(), QuantumSignature::new(vec![]));
+    let cases = [
+        ("plain transfer", transfer.clone()),
+        ("contract call with data", call),
+        ("transfer with an expiry", transfer.clone().with_valid_until(100)),
+        ("sponsored transfer with an expiry", transfer.with_valid_until(100).with_fee_payer(sponsor.public_key().to_vec())),
+    ];
+    cases
+        .into_iter()
+        .map(|(description, mut tx)| {
+            tx.signature = sender.sign(&tx.get_signing_data()).map_err(|e| e.to_string())?;
+            let sponsor_payload = tx.fee_payer_signing_data();
+            if let Some(payer) = tx.fee_payer.as_mut() {
+                payer.signature = sponsor.sign(&sponsor_payload).map_err(|e| e.to_string())?;
+            }
+            let payer = tx.fee_payer.as_ref();
+            Ok(SigningVector {
+                description: description.to_string(),
+                from: tx.from.clone(),
+                to: tx.to.clone(),
+                amount: tx.amount,
+                fee: tx.fee,
+                nonce: tx.nonce,
+                data: tx.data.clone(),
+                valid_until_height: tx.valid_until_height,
+                fee_payer: payer.map(|payer| hex::encode(&payer.address)),
+                signing_payload: tx.get_signing_data(),
+                signature: tx.signature.signature_data.clone(),
+                fee_payer_payload: payer.map(|_| hex::encode(tx.fee_payer_signing_data())),
+                fee_payer_signature: payer.map(|payer| hex::encode(&payer.signature.signature_data)),
+                hash: tx.hash(),
+            })
+        })
+        .collect()
+}
+
+fn address_vectors(keypair: &QuantumKeyPair) -> Vec<AddressVector> {
+    [keypair.public_key().to_vec(), vec![0; 32], (0..=255).collect()]
+        .into_iter()
+        .map(|public_key| AddressVector { address: QuantumKeyPair::address_of(&public_key), public_key })
+        .collect()
+}
+
+fn merkle_root_vectors() -> Vec<MerkleRootVector> {
+    [0usize, 1, 2, 3, 5, 8]
+        .into_iter()
+        .map(|count| {
+            let leaves: Vec<Vec<u8>> = (0..count).map(|leaf| format!("leaf-{}", leaf).into_bytes()).collect();
+            MerkleRootVector {
+                description: format!("{} leaves", count),
+                root: MerkleTree::new(&leaves).root(),
+                leaves: leaves.iter().map(hex::encode).collect(),
+            }
+        })
+        .collect()
+}
+
+fn canonical_json_vectors() -> Result<Vec<CanonicalJsonVector>, String> {
+    let plain = |description: &str, input: &str| -> Result<CanonicalJsonVector, String> {
+        let canonical = canonical::canonicalize(input)?;
+        Ok(CanonicalJsonVector { description: description.to_string(), input: input.to_string(), canonical, signing_payload: None })
+    };
+    let mut vectors = vec![
+        plain("key order and whitespace", "{ \"b\": 1, \"a\": [true, null, \"x\"], \"A\": {} }")?,
+        plain("string escapes", "\"quote \\\" slash \\\\ tab \\t control \\u001f accent \\u00e9 slash \\/\"")?,
+        plain("keys in UTF-16 order", "{\"\\uff61\": 1, \"\\ud83d\\ude00\": 2, \"z\": 3}")?,
+        plain("integer limits", "[18446744073709551615, -9223372036854775808, 0, -0]")?,
+    ];
+
+    let transfer = Transaction::new(vec![0xaa; 32], vec![0xcc; 32], 1_000, 1, 0, vec![], QuantumSignature::new(vec![]));
+    let call = Transaction::new(vec![0xaa; 32], vec![0xdd; 32], 0, 3, 1, b"call()".to_vec(), QuantumSignature::new(vec![]));
+    let sponsored = transfer.clone().with_valid_until(100).with_fee_payer(vec![0xbb; 32]);
+    let cases = [
+        ("sender payload of a plain transfer", transfer.canonical_signing_json(), transfer.get_signing_data()),
+        ("sender payload of a contract call", call.canonical_signing_json(), call.get_signing_data()),
+        ("sender payload of a sponsored transfer with an expiry", sponsored.canonical_signing_json(), sponsored.get_signing_data()),
+        ("sponsor payload of a sponsored transfer with an expiry", sponsored.canonical_fee_payer_json(), sponsored.fee_payer_signing_data()),
+    ];
+    for (description, json, payload) in cases {
+        vectors.push(CanonicalJsonVector {
+            description: description.to_string(),
+            input: json.clone(),
+            canonical: json,
+            signing_payload: Some(hex::encode(payload)),
+        });
+    }
+    Ok(vectors)
+}
+
+/// Fixed values of every consensus-critical type, covering each optional field and variant
+fn wire_vectors() -> Result<Vec<WireVector>, String> {
+    let validator = vec![0xaa; 32];
+    let signature = QuantumSignature::new_with_key(vec![0x5a; 8], validator.clone());
+    let transfer = Transaction::new(validator.clone(), vec![0xcc; 32], 250, 2, 0, vec![], signature.clone());
+    let mut sponsored = transfer.clone().with_valid_until(100).with_fee_payer(vec![0xbb; 32]).with_memo("invoice 42");
+    sponsored.nonce = 1;
+    if let Some(payer) = sponsored.fee_payer.as_mut() {
+        payer.signature = QuantumSignature::new_with_key(vec![0x6b; 8], vec![0xbb; 32]);
+    }
+    let batch = BatchCall { operations: vec![BatchOperation { to: vec![0xcc; 32], amount: 5, data: vec![] }, BatchOperation { to: vec![0xdd; 32], amount: 0, data: vec![1, 2] }] };
+    let batch = Transaction::new(validator.clone(), BATCH.to_vec(), 0, 2, 2, batch.encode(), signature.clone());
+    let call = Transaction::new(validator.clone(), vec![0xdd; 32], 0, 5, 3, vec![1, 2, 3, 4], signature.clone());
+
+    let consensus = [
+        ("fast lane", ConsensusData::FastLane { validator: validator.clone(), committee: None }),
+        ("secure lane", ConsensusData::SecureLane { validators: vec![validator.clone(), vec![0xee; 32]] }),
+        ("hybrid path", ConsensusData::HybridPath { fast_validators: vec![validator.clone()], secure_validators: vec![vec![0xee; 32]] }),
+        ("emergency", ConsensusData::Emergency { authority_validators: vec![vec![0xee; 32]] }),
+        ("fast lane by reference", ConsensusData::FastLaneRef { validator: ValidatorRef { set: [0x5e; 32], index: 1 }, committee: None }),
+        ("secure lane by reference", ConsensusData::SecureLaneRef { validators: ValidatorSetRef { set: [0x5e; 32], indices: vec![0, 1] } }),
+    ];
+    let mut block = Block::new([2; 32], vec![transfer.clone(), sponsored.clone(), batch.clone(), call.clone()], 2, consensus[1].1.clone()).with_state_root([0x12; 32]);
+    block.header.timestamp = 1_700_000_002;
+    let mut legacy = block.header.clone();
+    (legacy.version, legacy.logs_bloom) = (1, None);
+    let mut vote = Vote { height: 2, round: 1, block_hash: block.hash(), validator: validator.clone(), signature: signature.clone(), session_key: None };
+
+    let mut vectors = vec![
+        ("block_header", "legacy header without a bloom".to_string(), encode(&legacy)?),
+        ("block_header", "header with a bloom".to_string(), encode(&block.header)?),
+        ("block_header", "header committing to its erasure-coded chunks".to_string(), encode(&block.clone().with_availability().header)?),
+        ("block", "block of every transaction type".to_string(), encode(&block)?),
+        ("transaction", "plain transfer".to_string(), transfer.envelope().encode()),
+        ("transaction", "sponsored transfer with an expiry and a memo".to_string(), sponsored.envelope().encode()),
+        ("transaction", "batch".to_string(), batch.envelope().encode()),
+        ("transaction", "contract call".to_string(), call.envelope().encode()),
+        ("vote", "vote signed by the validator key".to_string(), encode(&vote)?),
+    ];
+    vote.session_key = Some(vec![0xdd; 32]);
+    vectors.push(("vote", "vote signed by a session key".to_string(), encode(&vote)?));
+    for (description, data) in consensus {
+        vectors.push(("consensus_data", description.to_string(), encode(&data)?));
+    }
+    Ok(vectors
+        .into_iter()
+        .map(|(kind, description, encoding)| WireVector { description, kind: kind.to_string(), encoding })
+        .collect())
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+    bincode::serialize(value).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vectors_round_trip() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_vectors");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let count = generate(&temp_dir).unwrap();
+        let report = verify(&temp_dir).unwrap();
+        assert!(report.is_valid(), "{:?}", report.failures);
+        assert_eq!(report.checked, count);
+
+        // The vectors checked into the repository still match this implementation
+        let shipped = verify(&Path::new(env!("CARGO_MANIFEST_DIR")).join("testvectors")).unwrap();
+        assert!(shipped.is_valid(), "{:?}", shipped.failures);
+
+        let path = temp_dir.join("merkle_root.json");
+        let mut file: VectorFile<MerkleRootVector> = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        file.vectors[2].root[0] ^= 1;
+        std::fs::write(&path, serde_json::to_string(&file).unwrap()).unwrap();
+        let report = verify(&temp_dir).unwrap();
+        assert_eq!(report.failures.len(), 1);
+        assert!(report.failures[0].starts_with("merkle_root / 2 leaves"));
+
+        // Bytes that still decode but differ from what the same value encodes to today,
+        // as after the timestamp and height of a header swap places
+        let wire_path = temp_dir.join("wire_encoding.json");
+        let mut wire: VectorFile<WireVector> = serde_json::from_str(&std::fs::read_to_string(&wire_path).unwrap()).unwrap();
+        let timestamp = 4 + 32 * 3;
+        wire.vectors[0].encoding[timestamp..timestamp + 16].rotate_left(8);
+        std::fs::write(&wire_path, serde_json::to_string(&wire).unwrap()).unwrap();
+        let report = verify(&temp_dir).unwrap();
+        assert!(report.failures[1].starts_with("wire_encoding / legacy header without a bloom: encoding"), "{:?}", report.failures);
+        file.version += 1;
+        std::fs::write(&path, serde_json::to_string(&file).unwrap()).unwrap();
+        assert!(verify(&temp_dir).is_err());
+
+        println!("   Test vectors working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -0,0 +1,290 @@
+use crate::crypto::verification::Subsystem;
+use crate::storage::blocks::Block;
+use crate::storage::database::BlockchainDB;
+use crate::storage::state::StateManager;
+use crate::storage::witness::BlockWitness;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationMode {
+    /// Header linkage only
+    Fast,
+    /// Headers, merkle roots and transaction signatures
+    Standard,
+    /// Everything in `Standard` plus state-root recomputation
+    Full,
+    /// Everything in `Standard` plus state-root recomputation from each block's stored
+    /// witness, without replaying the chain's state
+    Stateless,
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidationFailure {
+    pub height: u64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidationReport {
+    pub mode: ValidationMode,
+    pub blocks_checked: u64,
+    pub transactions_checked: u64,
+    pub signatures_verified: u64,
+    pub failure: Option<ValidationFailure>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+pub struct ChainValidator {
+    db: BlockchainDB,
+    mode: ValidationMode,
+}
+
+impl ChainValidator {
+    pub fn new(db: BlockchainDB, mode: ValidationMode) -> Self {
+        Self { db, mode }
+    }
+
+    pub fn open(path: &str, mode: ValidationMode) -> Result<Self, String> {
+        if !std::path::Path::new(path).exists() {
+            return Err(format!("No blockchain data at {}", path));
+        }
+        Ok(Self::new(BlockchainDB::new(path)?, mode))
+    }
+
+    pub fn run(&self) -> Result<ValidationReport, String> {
+        let mut report = ValidationReport {
+            mode: self.mode,
+            blocks_checked: 0,
+            transactions_checked: 0,
+            signatures_verified: 0,
+            failure: None,
+        };
+
+        if self.db.block_count()? == 0 {
+            return Ok(report);
+        }
+
+        let latest = self.db.get_latest_height()?;
+        let mut state = StateManager::from_allocations(&self.db.get_genesis_allocations()?)?;
+        let mut previous: Option<Block> = None;
+
+        for height in 0..=latest {
+            let block = match self.db.get_block(height)? {
+                Some(block) => block,
+                None => {
+                    report.failure = Some(ValidationFailure {
+                        height,
+                        reason: "Block missing from storage".to_string(),
+                    });
+                    break;
+                }
+            };
+
+            if let Err(reason) = self.check_block(&block, height, previous.as_ref(), &mut state, &mut report) {
+                report.failure = Some(ValidationFailure { height, reason });
+                break;
+            }
+
+            report.blocks_checked += 1;
+            previous = Some(block);
+        }
+
+        Ok(report)
+    }
+
+    fn check_block(
+        &self,
+        block: &Block,
+        height: u64,
+        previous: Option<&Block>,
+        state: &mut StateManager,
+        report: &mut ValidationReport,
+    ) -> Result<(), String> {
+        let header = &block.header;
+
+        if header.version == 0 {
+            return Err("Invalid block version 0".to_string());
+        }
+        if header.height != height {
+            return Err(format!("Header height {} stored at height {}", header.height, height));
+        }
+
+        match previous {
+            None => {
+                if header.previous_hash != [0; 32] {
+                    return Err("Genesis block has a non-zero parent hash".to_string());
+                }
+            }
+            Some(parent) => {
+                if header.previous_hash != parent.hash() {
+                    return Err(format!(
+                        "Parent hash mismatch: expected {}, found {}",
+                        hex::encode(parent.hash()),
+                        hex::encode(header.previous_hash)
+                    ));
+                }
+                if header.timestamp < parent.header.timestamp {
+                    return Err("Timestamp earlier than parent block".to_string());
+                }
+            }
+        }
+
+        if self.mode == ValidationMode::Fast {
+            return Ok(());
+        }
+
+        if !block.has_valid_merkle_root() {
+            return Err("Merkle root does not match transactions".to_string());
+        }
+        if !block.has_valid_bloom() {
+            return Err("Bloom filter does not match transactions".to_string());
+        }
+        if !block.has_valid_availability_root() {
+            return Err("Availability root does not match the erasure-coded body".to_string());
+        }
+        block.check_path_proposal()?;
+
+        for (index, tx) in block.transactions.iter().enumerate() {
+            tx.check_as(Subsystem::BlockValidation).map_err(|e| format!("Transaction {}: {}", index, e))?;
+            if tx.is_expired_at(header.height) {
+                return Err(format!("Transaction {}: expired before height {}", index, header.height));
+            }
+            report.transactions_checked += 1;
+            report.signatures_verified += 1;
+        }
+
+        if self.mode == ValidationMode::Stateless {
+            let witness = BlockWitness::load(&self.db, &block.hash())?.ok_or("No witness stored for this block")?;
+            // The state is only advanced in full mode, so here it stays at genesis
+            let parent_state_root = previous.map_or_else(|| state.state_root(), |parent| parent.header.state_root);
+            witness.verify(block, parent_state_root)?;
+        }
+
+        if self.mode == ValidationMode::Full {
+            state.apply_block(block)?;
+            let state_root = state.state_root();
+            if state_root != header.state_root {
+                return Err(format!(
+                    "State root mismatch: computed {}, header has {}",
+                    hex::encode(state_root),
+                    hex::encode(header.state_root)
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumKeyPair;
+    use crate::storage::blocks::{ConsensusData, Transaction};
+
+    fn signed_transfer(keypair: &QuantumKeyPair, to: Vec<u8>, amount: u64, nonce: u64) -> Transaction {
+        let mut tx = Transaction::new(
+            keypair.public_key().to_vec(),
+            to,
+            amount,
+            1,
+            nonce,
+            Vec::new(),
+            Vec::new().into(),
+        );
+        tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+        tx
+    }
+
+    fn build_chain(name: &str) -> (std::path::PathBuf, BlockchainDB) {
+        let temp_dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+
+        let keypair = QuantumKeyPair::generate();
+        let allocations = vec![(keypair.public_key().to_vec(), 10_000)];
+        db.store_genesis_allocations(&allocations).unwrap();
+
+        let mut state = StateManager::from_allocations(&allocations).unwrap();
+        let mut previous_hash = [0; 32];
+        for height in 0..3 {
+            let tx = signed_transfer(&keypair, vec![9, 9, 9], 100, height);
+            let mut block = Block::new(previous_hash, vec![tx], height, ConsensusData::default());
+            state.apply_block(&block).unwrap();
+            block = block.with_state_root(state.state_root());
+            db.store_block(&block).unwrap();
+            previous_hash = block.hash();
+        }
+
+        (temp_dir, db)
+    }
+
+    #[test]
+    fn test_valid_chain_full_mode() {
+        let (temp_dir, db) = build_chain("triunity_test_validate_full");
+        let report = ChainValidator::new(db, ValidationMode::Full).run().unwrap();
+
+        assert!(report.is_valid());
+        assert_eq!(report.blocks_checked, 3);
+        assert_eq!(report.signatures_verified, 3);
+
+        println!("   Full chain validation working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_broken_linkage_reported() {
+        let (temp_dir, db) = build_chain("triunity_test_validate_linkage");
+        let mut block = db.get_block(2).unwrap().unwrap();
+        block.header.previous_hash = [7; 32];
+        db.store_block(&block).unwrap();
+
+        let report = ChainValidator::new(db, ValidationMode::Fast).run().unwrap();
+        let failure = report.failure.unwrap();
+        assert_eq!(failure.height, 2);
+        assert!(failure.reason.contains("Parent hash mismatch"));
+
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_state_root_mismatch_only_in_full_mode() {
+        let (temp_dir, db) = build_chain("triunity_test_validate_state");
+        let block = db.get_block(2).unwrap().unwrap().with_state_root([1; 32]);
+        db.store_block(&block).unwrap();
+
+        let validator = ChainValidator::new(db.clone(), ValidationMode::Standard);
+        assert!(validator.run().unwrap().is_valid());
+
+        let report = ChainValidator::new(db, ValidationMode::Full).run().unwrap();
+        let failure = report.failure.unwrap();
+        assert_eq!(failure.height, 2);
+        assert!(failure.reason.contains("State root mismatch"));
+
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_stateless_mode() {
+        let (temp_dir, db) = build_chain("triunity_test_validate_stateless");
+        let report = ChainValidator::new(db.clone(), ValidationMode::Stateless).run().unwrap();
+        assert!(report.failure.unwrap().reason.contains("No witness"));
+
+        let mut state = StateManager::from_allocations(&db.get_genesis_allocations().unwrap()).unwrap();
+        for height in 0..3 {
+            let block = db.get_block(height).unwrap().unwrap();
+            BlockWitness::generate(&state, &block).unwrap().store(&db, &block.hash()).unwrap();
+            state.apply_block(&block).unwrap();
+        }
+        let report = ChainValidator::new(db, ValidationMode::Stateless).run().unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.blocks_checked, 3);
+
+        println!("   Stateless chain validation working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
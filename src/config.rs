@@ -0,0 +1,647 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use crate::alerts::AlertRule;
+use crate::consensus::checkpoint::Checkpoint;
+use crate::consensus::metrics::store::Retention;
+use crate::consensus::metrics::DEFAULT_MAX_HISTORY;
+use crate::consensus::pin::PinnedMode;
+use crate::crypto::QuantumKeyPair;
+use crate::network::compression::Compression;
+use crate::network::profile::NetworkId;
+use crate::network::SYNC_BATCH_SIZE;
+use crate::storage::cache::{DEFAULT_ACCOUNT_CACHE, DEFAULT_BLOCK_CACHE};
+use crate::wallet::keystore::{self, Keystore};
+use crate::web::security::{validate_origins, DEFAULT_CONTENT_SECURITY_POLICY};
+
+/// Node configuration, loaded from a TOML file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NodeConfig {
+    pub web: WebConfig,
+    pub network: NetworkConfig,
+    pub cache: CacheConfig,
+    /// Trusted block to sync from instead of genesis
+    pub checkpoint: Option<Checkpoint>,
+    pub alerts: AlertsConfig,
+    pub metrics: MetricsConfig,
+    pub consensus: ConsensusConfig,
+    pub keys: KeysConfig,
+    pub watchtower: WatchtowerConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Network to join: mainnet, testnet or devnet. Peers on another one are refused
+    pub id: NetworkId,
+    /// `host:port` of peers dialed on start; empty for the network's bootstrap peers
+    pub bootstrap_peers: Vec<String>,
+    pub bandwidth: BandwidthConfig,
+    pub compression: CompressionConfig,
+    /// Answer header and proof requests from light clients
+    pub serve_light_clients: bool,
+    /// Answer state snapshot requests from nodes syncing from a checkpoint
+    pub serve_snapshots: bool,
+    /// Gossip bare blocks to peers that cannot check proposal signatures. Off by default,
+    /// since those peers cannot tell a forged block from a proposed one
+    pub legacy_block_gossip: bool,
+    pub sync_serving: SyncServingConfig,
+}
+
+/// Entries held by the database read caches; 0 disables a cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub blocks: usize,
+    pub accounts: usize,
+}
+
+/// Compression offered to peers for block transfers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    /// Most preferred first; empty disables compression
+    pub algorithms: Vec<Compression>,
+    /// Messages smaller than this many bytes are sent uncompressed
+    pub min_size: usize,
+}
+
+/// Transfer caps in bytes per second; unset means unlimited. Per-peer caps apply to
+/// each connection, total caps are shared fairly between all of them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BandwidthConfig {
+    pub peer_upload: Option<u64>,
+    pub peer_download: Option<u64>,
+    pub total_upload: Option<u64>,
+    pub total_download: Option<u64>,
+}
+
+/// Limits on answering block and snapshot requests from syncing peers. Genesis
+/// validators and `priority_peers` are served outside the shared byte budget
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncServingConfig {
+    /// Requests answered at once for one peer; more are dropped
+    pub max_requests_per_peer: usize,
+    /// Blocks in one `Blocks` response
+    pub max_blocks: u64,
+    /// A `Blocks` response stops growing past this many bytes
+    pub max_response_bytes: usize,
+    /// Bytes per second of sync responses shared by all peers; unset means unlimited
+    pub bytes_per_second: Option<u64>,
+    /// Hex node ids of known peers served ahead of the budget
+    pub priority_peers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebConfig {
+    pub dashboard: ListenConfig,
+    /// Listeners for the JSON-RPC endpoint; when empty RPC shares the dashboard listeners
+    pub rpc: ListenConfig,
+    pub rate_limit: RateLimitConfig,
+    /// Bearer tokens allowed to call the `admin_` RPC namespace and the
+    /// `mempool_content`, `mempool_inspect` and `mempool_remove` methods; with none
+    /// they are refused
+    pub admin_tokens: Vec<String>,
+    /// Serves the `debug_` RPC namespace, whose traces re-execute the chain up to the
+    /// traced block and so are costly
+    pub debug_rpc: bool,
+    pub streams: StreamConfig,
+    /// Origins whose pages may call the dashboard API and RPC from the browser, such as
+    /// `https://explorer.example.org`; `*` allows any. Empty allows same-origin calls only
+    pub allowed_origins: Vec<String>,
+    /// `Content-Security-Policy` of the dashboard's responses
+    pub content_security_policy: String,
+}
+
+/// Buffering of the dashboard's live streams, `/ws/mempool` and `/api/events`, for
+/// each connected client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamConfig {
+    /// Messages queued for one client; while it is full the client's messages are dropped
+    pub queue: usize,
+    /// A client still behind this long after it first dropped a message is disconnected
+    pub max_lag_secs: u64,
+}
+
+/// RPC quotas. Requests spend method cost units from a token bucket kept per client IP,
+/// or per API token when the request carries one listed in `tokens`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Cost units refilled per second for each IP
+    pub ip_rate: u32,
+    pub ip_burst: u32,
+    /// Cost units refilled per second for each API token
+    pub token_rate: u32,
+    pub token_burst: u32,
+    /// API tokens accepted in `Authorization: Bearer <token>`
+    pub tokens: Vec<String>,
+    pub max_payload_bytes: u64,
+    /// Overrides of the built-in per-method costs
+    pub method_costs: HashMap<String, u32>,
+}
+
+/// Alert rules, checked every `interval_secs`, and where their state changes are sent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlertsConfig {
+    pub interval_secs: u64,
+    pub rules: Vec<AlertRule>,
+    pub webhooks: Vec<WebhookConfig>,
+    pub email: Vec<SmtpConfig>,
+}
+
+/// How often a watchtower re-checks the chain, and how long a valid transaction may be
+/// left out of blocks with room for it before it counts as censored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchtowerConfig {
+    pub interval_secs: u64,
+    pub censorship_blocks: u64,
+}
+
+/// History kept by the metrics collector, and whether it is written to the database
+/// so it survives a restart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub persist: bool,
+    /// Readings of each kind kept
+    pub max_entries: usize,
+    /// Readings older than this are dropped; unset keeps them until `max_entries` is reached
+    pub max_age_secs: Option<u64>,
+}
+
+/// Where the node's keys are kept. A key file given on its own is used instead of the
+/// keystore's; without either the node runs under a fresh identity and no validator key
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeysConfig {
+    /// Keystore directory holding the identity and validator keys in separate files
+    pub keystore: Option<String>,
+    /// Identity key peers know the node by
+    pub node_key: Option<String>,
+    /// Consensus key registered in the genesis validator set
+    pub validator_key: Option<String>,
+}
+
+impl KeysConfig {
+    /// The configured identity and validator keys, creating the identity in the keystore
+    /// on first start
+    pub fn load(&self) -> Result<(Option<QuantumKeyPair>, Option<QuantumKeyPair>), String> {
+        let keystore = self.keystore.as_deref().map(Keystore::open).transpose()?;
+        let node_key = match (&self.node_key, &keystore) {
+            (Some(path), _) => Some(keystore::load(path.as_ref())?),
+            (None, Some(keystore)) => Some(keystore.node_key()?),
+            (None, None) => None,
+        };
+        let validator_key = match (&self.validator_key, &keystore) {
+            (Some(path), _) => Some(keystore::load(path.as_ref())?),
+            (None, Some(keystore)) => keystore.validator_key()?,
+            (None, None) => None,
+        };
+        if let (Some(node_key), Some(validator_key)) = (&node_key, &validator_key) {
+            if node_key.public_key() == validator_key.public_key() {
+                return Err("keys.node_key and keys.validator_key must be different keys".to_string());
+            }
+        }
+        Ok((node_key, validator_key))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConsensusConfig {
+    /// Path held from startup regardless of the router, e.g. to ride out an incident
+    /// across a restart
+    pub pin: Option<PinConfig>,
+    /// ONNX model the router consults ahead of its rules; needs the `ml` feature
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinConfig {
+    pub mode: PinnedMode,
+    pub duration_secs: u64,
+    #[serde(default = "default_pin_reason")]
+    pub reason: String,
+}
+
+fn default_pin_reason() -> String {
+    "Pinned in config".to_string()
+}
+
+/// Alerts are POSTed as JSON; only `http://` URLs are supported
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+}
+
+/// Alerts are mailed through `server` (`host:port`) without authentication or TLS, as
+/// to a relay on the same host or a trusted network
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    pub server: String,
+    /// Name this node gives in `EHLO`
+    #[serde(default = "default_smtp_hello")]
+    pub hello: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+fn default_smtp_hello() -> String {
+    "localhost".to_string()
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListenConfig {
+    pub addresses: Vec<SocketAddr>,
+    pub tls: Option<TlsConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+impl NodeConfig {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read config {}: {}", path, e))?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let config: Self = toml::from_str(contents).map_err(|e| format!("Invalid config: {}", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.web.dashboard.addresses.is_empty() {
+            return Err("web.dashboard needs at least one listen address".to_string());
+        }
+        for listen in [&self.web.dashboard, &self.web.rpc] {
+            if let Some(tls) = &listen.tls {
+                tls.validate()?;
+            }
+        }
+        self.web.rate_limit.validate()?;
+        validate_origins(&self.web.allowed_origins)?;
+        if warp::http::HeaderValue::from_str(&self.web.content_security_policy).is_err() {
+            return Err("web.content_security_policy must be a valid header value".to_string());
+        }
+        if self.web.streams.queue == 0 || self.web.streams.max_lag_secs == 0 {
+            return Err("web.streams.queue and web.streams.max_lag_secs must be positive".to_string());
+        }
+        self.network.bandwidth.validate()?;
+        self.network.sync_serving.validate()?;
+        if let Some(peer) = self.network.bootstrap_peers.iter().find(|peer| peer.rsplit_once(':').is_none_or(|(host, port)| host.is_empty() || port.parse::<u16>().is_err())) {
+            return Err(format!("network.bootstrap_peers must be host:port: {}", peer));
+        }
+        self.alerts.validate()?;
+        if self.watchtower.interval_secs == 0 || self.watchtower.censorship_blocks == 0 {
+            return Err("watchtower.interval_secs and watchtower.censorship_blocks must be positive".to_string());
+        }
+        if self.metrics.max_entries == 0 || self.metrics.max_age_secs == Some(0) {
+            return Err("metrics.max_entries and metrics.max_age_secs must be positive".to_string());
+        }
+        if self.consensus.pin.as_ref().is_some_and(|pin| pin.duration_secs == 0) {
+            return Err("consensus.pin.duration_secs must be positive".to_string());
+        }
+        if cfg!(not(feature = "ml")) && self.consensus.model.is_some() {
+            return Err("consensus.model needs a build with the ml feature".to_string());
+        }
+        if self.keys.node_key.is_some() && self.keys.node_key == self.keys.validator_key {
+            return Err("keys.node_key and keys.validator_key must be different files".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self::local(8080)
+    }
+}
+
+impl WebConfig {
+    /// Dashboard and RPC on 127.0.0.1:<port> over plain HTTP
+    pub fn local(port: u16) -> Self {
+        Self {
+            dashboard: ListenConfig {
+                addresses: vec![SocketAddr::from(([127, 0, 0, 1], port))],
+                tls: None,
+            },
+            rpc: ListenConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            admin_tokens: Vec::new(),
+            debug_rpc: false,
+            streams: StreamConfig::default(),
+            allowed_origins: Vec::new(),
+            content_security_policy: DEFAULT_CONTENT_SECURITY_POLICY.to_string(),
+        }
+    }
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self { queue: 256, max_lag_secs: 10 }
+    }
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            id: NetworkId::default(),
+            bootstrap_peers: Vec::new(),
+            bandwidth: BandwidthConfig::default(),
+            compression: CompressionConfig::default(),
+            serve_light_clients: true,
+            serve_snapshots: true,
+            legacy_block_gossip: false,
+            sync_serving: SyncServingConfig::default(),
+        }
+    }
+}
+
+impl Default for SyncServingConfig {
+    fn default() -> Self {
+        Self {
+            max_requests_per_peer: 2,
+            max_blocks: SYNC_BATCH_SIZE,
+            max_response_bytes: 8 * 1024 * 1024,
+            bytes_per_second: None,
+            priority_peers: Vec::new(),
+        }
+    }
+}
+
+impl SyncServingConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_requests_per_peer == 0 || self.max_blocks == 0 || self.max_response_bytes == 0 || self.bytes_per_second == Some(0) {
+            return Err("network.sync_serving limits must be positive".to_string());
+        }
+        if let Some(peer) = self.priority_peers.iter().find(|peer| hex::decode(peer.trim_start_matches("0x")).is_err()) {
+            return Err(format!("network.sync_serving.priority_peers must be hex node ids: {}", peer));
+        }
+        Ok(())
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self { blocks: DEFAULT_BLOCK_CACHE, accounts: DEFAULT_ACCOUNT_CACHE }
+    }
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            algorithms: vec![Compression::Zstd, Compression::Snappy],
+            min_size: 1024,
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ip_rate: 50,
+            ip_burst: 100,
+            token_rate: 500,
+            token_burst: 1000,
+            tokens: Vec::new(),
+            max_payload_bytes: 1024 * 1024,
+            method_costs: HashMap::new(),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if [self.ip_rate, self.ip_burst, self.token_rate, self.token_burst].contains(&0) {
+            return Err("web.rate_limit rates and bursts must be positive".to_string());
+        }
+        if self.max_payload_bytes == 0 {
+            return Err("web.rate_limit.max_payload_bytes must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self { interval_secs: 30, rules: Vec::new(), webhooks: Vec::new(), email: Vec::new() }
+    }
+}
+
+impl Default for WatchtowerConfig {
+    fn default() -> Self {
+        Self { interval_secs: 10, censorship_blocks: 10 }
+    }
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self { persist: false, max_entries: DEFAULT_MAX_HISTORY, max_age_secs: None }
+    }
+}
+
+impl MetricsConfig {
+    pub fn retention(&self) -> Retention {
+        Retention { max_entries: self.max_entries, max_age_secs: self.max_age_secs }
+    }
+}
+
+impl AlertsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_secs == 0 {
+            return Err("alerts.interval_secs must be positive".to_string());
+        }
+        let mut names = std::collections::HashSet::new();
+        for rule in &self.rules {
+            if rule.name.is_empty() || !names.insert(&rule.name) {
+                return Err(format!("alerts.rules names must be unique and non-empty: {:?}", rule.name));
+            }
+        }
+        for webhook in &self.webhooks {
+            if !webhook.url.starts_with("http://") || webhook.url.parse::<hyper::Uri>().is_err() {
+                return Err(format!("alerts.webhooks url must be an http:// URL: {}", webhook.url));
+            }
+        }
+        for email in &self.email {
+            if email.to.is_empty() {
+                return Err(format!("alerts.email via {} needs at least one recipient", email.server));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BandwidthConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if [self.peer_upload, self.peer_download, self.total_upload, self.total_download].contains(&Some(0)) {
+            return Err("network.bandwidth caps must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl ListenConfig {
+    pub fn scheme(&self) -> &'static str {
+        if self.tls.is_some() { "https" } else { "http" }
+    }
+}
+
+impl TlsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        for path in [&self.cert_path, &self.key_path] {
+            if !std::path::Path::new(path).is_file() {
+                return Err(format!("TLS file not found: {}", path));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::Condition;
+
+    #[test]
+    fn test_parse_config() {
+        let config = NodeConfig::parse(r#"
+            [web.dashboard]
+            addresses = ["0.0.0.0:8080", "[::1]:8080"]
+
+            [web.rpc]
+            addresses = ["127.0.0.1:8545"]
+        "#).unwrap();
+
+        assert_eq!(config.web.dashboard.addresses.len(), 2);
+        assert_eq!(config.web.rpc.addresses[0].port(), 8545);
+        assert_eq!(config.web.rpc.scheme(), "http");
+
+        let config = NodeConfig::parse(r#"
+            [web.rate_limit]
+            ip_rate = 5
+            tokens = ["secret"]
+            method_costs = { chain_getBlock = 3 }
+        "#).unwrap();
+        assert_eq!(config.web.rate_limit.ip_rate, 5);
+        assert_eq!(config.web.rate_limit.ip_burst, 100);
+        assert_eq!(config.web.rate_limit.method_costs["chain_getBlock"], 3);
+        assert!(NodeConfig::parse("[web.rate_limit]\nip_burst = 0").is_err());
+
+        let config = NodeConfig::parse("[web.streams]\nqueue = 64").unwrap();
+        assert_eq!((config.web.streams.queue, config.web.streams.max_lag_secs), (64, 10));
+        assert!(NodeConfig::parse("[web.streams]\nmax_lag_secs = 0").is_err());
+
+        let config = NodeConfig::parse("[web]\nallowed_origins = [\"https://explorer.example.org\"]").unwrap();
+        assert_eq!(config.web.allowed_origins, vec!["https://explorer.example.org".to_string()]);
+        assert_eq!(config.web.content_security_policy, DEFAULT_CONTENT_SECURITY_POLICY);
+        assert!(NodeConfig::parse("[web]\nallowed_origins = [\"https://explorer.example.org/app\"]").is_err());
+
+        let config = NodeConfig::parse("[network.bandwidth]\npeer_upload = 65536").unwrap();
+        assert_eq!(config.network.bandwidth.peer_upload, Some(65536));
+        assert_eq!(config.network.bandwidth.total_download, None);
+        assert!(NodeConfig::parse("[network.bandwidth]\ntotal_upload = 0").is_err());
+
+        let config = NodeConfig::parse("[network.sync_serving]\nbytes_per_second = 1048576\npriority_peers = [\"0xab01\"]").unwrap();
+        assert_eq!(config.network.sync_serving.bytes_per_second, Some(1_048_576));
+        assert_eq!(config.network.sync_serving.max_blocks, SYNC_BATCH_SIZE);
+        assert!(NodeConfig::parse("[network.sync_serving]\nmax_requests_per_peer = 0").is_err());
+        assert!(NodeConfig::parse("[network.sync_serving]\npriority_peers = [\"peer\"]").is_err());
+
+        let config = NodeConfig::parse("[network]\nid = \"testnet\"\nbootstrap_peers = [\"seed.example.org:31333\"]").unwrap();
+        assert_eq!((config.network.id, config.network.bootstrap_peers.len()), (NetworkId::Testnet, 1));
+        assert_eq!(NodeConfig::default().network.id, NetworkId::Devnet);
+        assert!(NodeConfig::parse("[network]\nid = \"moonnet\"").is_err());
+        assert!(NodeConfig::parse("[network]\nbootstrap_peers = [\"seed.example.org\"]").is_err());
+
+        let config = NodeConfig::parse("[metrics]\npersist = true\nmax_age_secs = 86400").unwrap();
+        assert_eq!(config.metrics.retention(), Retention { max_entries: DEFAULT_MAX_HISTORY, max_age_secs: Some(86_400) });
+        assert!(NodeConfig::parse("[metrics]\nmax_entries = 0").is_err());
+
+        let config = NodeConfig::parse("[watchtower]\ncensorship_blocks = 5").unwrap();
+        assert_eq!((config.watchtower.interval_secs, config.watchtower.censorship_blocks), (10, 5));
+        assert!(NodeConfig::parse("[watchtower]\ninterval_secs = 0").is_err());
+
+        let config = NodeConfig::parse("[network.compression]\nalgorithms = [\"snappy\"]").unwrap();
+        assert_eq!(config.network.compression.algorithms, vec![Compression::Snappy]);
+        assert_eq!(config.network.compression.min_size, 1024);
+
+        let config = NodeConfig::parse("[cache]\naccounts = 0").unwrap();
+        assert_eq!((config.cache.blocks, config.cache.accounts), (DEFAULT_BLOCK_CACHE, 0));
+
+        let config = NodeConfig::parse(&format!(
+            "[checkpoint]\nheight = 4096\nblock_hash = \"{}\"\nvalidator_set_hash = \"{}\"",
+            "ab".repeat(32),
+            "cd".repeat(32)
+        )).unwrap();
+        let checkpoint = config.checkpoint.unwrap();
+        assert_eq!((checkpoint.height, checkpoint.block_hash[0]), (4096, 0xab));
+        assert!(NodeConfig::parse("[checkpoint]\nheight = 1\nblock_hash = \"ab\"\nvalidator_set_hash = \"cd\"").is_err());
+
+        let config = NodeConfig::parse(r#"
+            [[alerts.rules]]
+            name = "slow"
+            condition = "tps_below"
+            threshold = 50
+
+            [[alerts.rules]]
+            name = "stalled"
+            condition = "no_block_for"
+            seconds = 60
+
+            [[alerts.webhooks]]
+            url = "http://127.0.0.1:9000/alerts"
+
+            [[alerts.email]]
+            server = "127.0.0.1:25"
+            from = "node@example.org"
+            to = ["ops@example.org"]
+        "#).unwrap();
+        assert_eq!(config.alerts.interval_secs, 30);
+        assert_eq!(config.alerts.rules[0].condition, Condition::TpsBelow { threshold: 50.0 });
+        assert_eq!(config.alerts.rules[1].condition, Condition::NoBlockFor { seconds: 60 });
+        assert_eq!(config.alerts.email[0].hello, "localhost");
+        assert!(NodeConfig::parse("[[alerts.rules]]\nname = \"a\"\ncondition = \"peers_below\"\ncount = 1\n[[alerts.rules]]\nname = \"a\"\ncondition = \"peers_below\"\ncount = 2").is_err());
+        assert!(NodeConfig::parse("[[alerts.webhooks]]\nurl = \"https://example.org\"").is_err());
+
+        let config = NodeConfig::parse("[consensus.pin]\nmode = \"secure_lane\"\nduration_secs = 600").unwrap();
+        let pin = config.consensus.pin.unwrap();
+        assert_eq!((pin.mode, pin.duration_secs, pin.reason.as_str()), (PinnedMode::SecureLane, 600, "Pinned in config"));
+        assert!(NodeConfig::parse("[consensus.pin]\nmode = \"emergency\"\nduration_secs = 0").is_err());
+
+        let config = NodeConfig::parse("[keys]\nkeystore = \"/var/lib/triunity/keys\"\nvalidator_key = \"/run/validator.json\"").unwrap();
+        assert_eq!((config.keys.keystore.as_deref(), config.keys.node_key), (Some("/var/lib/triunity/keys"), None));
+        assert!(NodeConfig::parse("[keys]\nnode_key = \"k.json\"\nvalidator_key = \"k.json\"").is_err());
+    }
+
+    #[test]
+    fn test_defaults_and_missing_tls_files() {
+        let config = NodeConfig::parse("").unwrap();
+        assert_eq!(config.web.dashboard.addresses, vec![SocketAddr::from(([127, 0, 0, 1], 8080))]);
+        assert!(config.web.rpc.addresses.is_empty());
+
+        let result = NodeConfig::parse(r#"
+            [web.dashboard]
+            addresses = ["127.0.0.1:8443"]
+            tls = { cert_path = "/nonexistent/cert.pem", key_path = "/nonexistent/key.pem" }
+        "#);
+        assert!(result.is_err());
+    }
+}
@@ -1,5 +1,30 @@
+pub mod agreement;
+pub mod algorithms;
+pub mod checkpoint;
+pub mod committee;
+pub mod decisions;
+pub mod duties;
+pub mod features;
+pub mod fork_choice;
+pub mod gas;
+pub mod metrics;
+pub mod params;
+pub mod pin;
+pub mod proposal;
+pub mod router;
+pub mod simulation;
+pub mod validator_sets;
+pub mod votes;
+
+use arc_swap::ArcSwap;
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::broadcast;
+
+use crate::crypto::verification;
+use crate::events::NodeEvent;
+use metrics::MetricsCollector;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ConsensusPath {
@@ -17,6 +42,8 @@ pub struct PerformanceStats {
     pub active_validators: usize,
     pub ai_confidence_percentage: f64,
     pub current_consensus_path: ConsensusPath,
+    /// Operator pin holding `current_consensus_path` regardless of the router
+    pub pinned_path: Option<pin::PathPin>,
     pub ai_decisions_per_minute: u64,
     pub ai_accuracy_percentage: f64,
     pub total_transactions_processed: u64,
@@ -24,24 +51,40 @@ pub struct PerformanceStats {
     pub peak_tps: u64,
     pub ai_decisions_total: u64,
     pub consensus_mode_switches: u64,
+    /// Signatures actually checked by this process, see `crypto::verification`
     pub quantum_signatures_verified: u64,
     pub security_attacks_blocked: u64,
+    pub blocks_processed: u64,
+}
+
+impl From<&router::ConsensusPath> for ConsensusPath {
+    fn from(path: &router::ConsensusPath) -> Self {
+        match path {
+            router::ConsensusPath::FastLane { .. } => Self::FastLane,
+            router::ConsensusPath::SecureLane { .. } => Self::Secure,
+            router::ConsensusPath::HybridPath { .. } => Self::Hybrid,
+            router::ConsensusPath::EmergencyMode { .. } => Self::Emergency,
+        }
+    }
 }
 
 pub struct ConsensusEngine {
-    performance_stats: Arc<Mutex<PerformanceStats>>,
+    /// Immutable snapshot replaced on every update, so readers never contend with writers
+    performance_stats: ArcSwap<PerformanceStats>,
+    metrics: Arc<Mutex<MetricsCollector>>,
 }
 
 impl ConsensusEngine {
     pub fn new() -> Self {
         Self {
-            performance_stats: Arc::new(Mutex::new(PerformanceStats {
+            performance_stats: ArcSwap::from_pointee(PerformanceStats {
                 transactions_per_second: 87429,
                 average_block_time_ms: 98,
                 network_health_percentage: 99.7,
                 active_validators: 3,
                 ai_confidence_percentage: 87.3,
                 current_consensus_path: ConsensusPath::FastLane,
+                pinned_path: None,
                 ai_decisions_per_minute: 2547,
                 ai_accuracy_percentage: 99.2,
                 total_transactions_processed: 1000000,
@@ -49,33 +92,139 @@ impl ConsensusEngine {
                 peak_tps: 149847,
                 ai_decisions_total: 50000,
                 consensus_mode_switches: 23,
-                quantum_signatures_verified: 75000,
+                quantum_signatures_verified: 0,
                 security_attacks_blocked: 12,
-            })),
+                blocks_processed: 0,
+            }),
+            metrics: Arc::new(Mutex::new(MetricsCollector::default())),
         }
     }
     
+    /// Replaces the default collector, e.g. with one restored from disk
+    pub fn with_metrics(self, metrics: MetricsCollector) -> Self {
+        Self { metrics: Arc::new(Mutex::new(metrics)), ..self }
+    }
+
     pub fn get_performance_stats(&self) -> PerformanceStats {
-        self.performance_stats.lock().unwrap().clone()
+        PerformanceStats::clone(&self.stats())
+    }
+
+    /// Latest published stats without copying them
+    pub fn stats(&self) -> Arc<PerformanceStats> {
+        let stats = self.performance_stats.load_full();
+        let verified = verification::counts().total;
+        if stats.quantum_signatures_verified >= verified {
+            return stats;
+        }
+        self.update_stats(|stats| stats.quantum_signatures_verified = stats.quantum_signatures_verified.max(verified))
+    }
+
+    /// Publishes a new snapshot with `change` applied, retrying if another writer got
+    /// there first, and returns it
+    fn update_stats(&self, change: impl Fn(&mut PerformanceStats)) -> Arc<PerformanceStats> {
+        let mut updated = None;
+        self.performance_stats.rcu(|current| {
+            let mut next = PerformanceStats::clone(current);
+            change(&mut next);
+            let next = Arc::new(next);
+            updated = Some(next.clone());
+            next
+        });
+        updated.unwrap_or_else(|| self.stats())
     }
     
     pub async fn process_transactions(&self, transactions: &[crate::blockchain::Transaction]) -> Result<(), String> {
-        let mut stats = self.performance_stats.lock().unwrap();
-        stats.total_transactions_processed += transactions.len() as u64;
+        self.update_stats(|stats| stats.total_transactions_processed += transactions.len() as u64);
         Ok(())
     }
     
     pub fn update_performance_stats(&self, tx_count: u64, block_time: u64) {
-        let mut stats = self.performance_stats.lock().unwrap();
-        stats.transactions_per_second = tx_count * 10;
-        stats.average_block_time_ms = block_time;
+        let stats = self.update_stats(|stats| {
+            stats.transactions_per_second = tx_count * 1000 / block_time.max(1);
+            stats.average_block_time_ms = block_time;
+            stats.blocks_processed += 1;
+            stats.total_transactions_processed += tx_count;
+            stats.peak_tps = stats.peak_tps.max(stats.transactions_per_second);
+        });
+
+        let mut metrics = self.metrics.lock().unwrap();
+        metrics.record_tps(stats.transactions_per_second, stats.blocks_processed);
+        metrics.record_latency(block_time, stats.active_validators);
+    }
+
+    /// Scores the current peer count for anomalies, like TPS and latency on every block
+    pub fn record_peer_count(&self, peers: usize) {
+        self.metrics.lock().unwrap().record_peer_count(peers);
+    }
+
+    /// Keeps the performance stats current from a node's events until the bus closes.
+    /// Peer blocks arrive in sync batches, so only locally produced blocks time the chain
+    pub async fn record_events(self: Arc<Self>, mut events: broadcast::Receiver<NodeEvent>) {
+        let mut last_block_at = Instant::now();
+        loop {
+            match events.recv().await {
+                Ok(NodeEvent::BlockImported { transactions, produced: true, .. }) => {
+                    let block_time = last_block_at.elapsed().as_millis() as u64;
+                    last_block_at = Instant::now();
+                    self.update_performance_stats(transactions as u64, block_time);
+                }
+                Ok(NodeEvent::PathSwitched { to, .. }) => {
+                    self.update_stats(|stats| {
+                        stats.current_consensus_path = ConsensusPath::from(&to);
+                        stats.consensus_mode_switches += 1;
+                    });
+                }
+                Ok(NodeEvent::PathPinned { pin }) => {
+                    self.update_stats(|stats| stats.pinned_path = pin.clone());
+                }
+                Ok(NodeEvent::SecurityEvent { source, reason, kind, severity }) => {
+                    self.update_stats(|stats| stats.security_attacks_blocked += 1);
+                    self.metrics.lock().unwrap().record_security_event(kind, severity, format!("{}: {}", source, reason));
+                }
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    pub fn metrics(&self) -> MetricsCollector {
+        self.metrics.lock().unwrap().clone()
     }
     
     pub fn simulate_network_activity(&self) {
-        let mut stats = self.performance_stats.lock().unwrap();
-        stats.ai_decisions_total += 100;
-        if rand::random::<f64>() < 0.1 {
-            stats.consensus_mode_switches += 1;
-        }
+        let switched = rand::random::<f64>() < 0.1;
+        self.update_stats(|stats| {
+            stats.ai_decisions_total += 100;
+            if switched {
+                stats.consensus_mode_switches += 1;
+            }
+        });
+    }
+}
+
+impl Default for ConsensusEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_snapshots() {
+        let engine = ConsensusEngine::new();
+        let before = engine.stats();
+        engine.update_performance_stats(50, 100);
+
+        // Readers keep the snapshot they loaded; new readers see the update
+        assert_eq!(before.blocks_processed, 0);
+        let after = engine.stats();
+        assert_eq!((after.blocks_processed, after.transactions_per_second), (1, 500));
+        assert_eq!(after.total_transactions_processed, before.total_transactions_processed + 50);
+        assert_eq!(engine.get_performance_stats().blocks_processed, 1);
+
+        println!("   Lock-free stats snapshots working!");
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::router::{ConsensusPath, NetworkMetrics, PATHS};
+
+/// Blocks per path window; the agreed consensus path only changes where one starts
+pub const PATH_WINDOW: u64 = 16;
+
+/// Blocks of a window that must propose the same path for the network to switch to it
+/// when the next window starts
+pub const PATH_SWITCH_THRESHOLD: u64 = PATH_WINDOW / 2 + 1;
+
+/// Path every chain starts on, as an index into `PATHS`
+pub const GENESIS_PATH: usize = 2;
+
+/// Highest attack probability a proposal for the fast lane may be justified by
+pub const FAST_LANE_MAX_ATTACK: f64 = 0.4;
+
+/// Attack probability from which the router falls back to emergency mode
+pub const EMERGENCY_ATTACK: f64 = 0.8;
+
+/// Congestion from which the router falls back to emergency mode
+pub const EMERGENCY_CONGESTION: f64 = 0.95;
+
+/// A proposer's request that the network move to another consensus path, carried in the
+/// header of its block along with the metrics its router chose the path by
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathProposal {
+    /// Index into `PATHS`
+    pub path: u8,
+    pub metrics: NetworkMetrics,
+}
+
+impl PathProposal {
+    pub fn new(path: &ConsensusPath, metrics: &NetworkMetrics) -> Self {
+        Self { path: path.index() as u8, metrics: metrics.clone() }
+    }
+
+    /// The proposed path with the parameters its metrics give it
+    pub fn path(&self) -> ConsensusPath {
+        ConsensusPath::from_index(self.path as usize, &self.metrics)
+    }
+
+    /// Checks the metrics are well formed and within the bounds that justify the path:
+    /// emergency mode only under emergency conditions, the fast lane only while attacks
+    /// are unlikely. Nodes see different metrics, so they are not compared with the
+    /// validator's own
+    pub fn check(&self) -> Result<(), String> {
+        let name = PATHS.get(self.path as usize).ok_or_else(|| format!("Unknown consensus path {}", self.path))?;
+        let metrics = &self.metrics;
+        let ratios = [metrics.attack_probability, metrics.congestion_level, metrics.memory_usage, metrics.cpu_usage];
+        if ratios.iter().any(|ratio| !(0.0..=1.0).contains(ratio)) || metrics.validator_count == 0 {
+            return Err(format!("Proposal for {} has out of range metrics", name));
+        }
+        let emergency = metrics.attack_probability > EMERGENCY_ATTACK || metrics.congestion_level > EMERGENCY_CONGESTION;
+        let justified = match self.path as usize {
+            0 => metrics.attack_probability <= FAST_LANE_MAX_ATTACK && !emergency,
+            1 => true,
+            2 => metrics.attack_probability <= EMERGENCY_ATTACK,
+            _ => emergency,
+        };
+        match justified {
+            true => Ok(()),
+            false => Err(format!("Metrics do not justify a switch to {}", name)),
+        }
+    }
+}
+
+/// The consensus path the chain agreed on for a window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgreedPath {
+    pub path: ConsensusPath,
+    /// First height of the window the switch took effect at; 0 for the genesis path
+    pub activation_height: u64,
+    /// Blocks of the window before activation that proposed the path; 0 for the genesis path
+    pub proposals: u64,
+}
+
+impl AgreedPath {
+    pub fn genesis() -> Self {
+        Self { path: ConsensusPath::from_index(GENESIS_PATH, &NetworkMetrics::default()), activation_height: 0, proposals: 0 }
+    }
+
+    /// The path agreed for the window after the one `tally` counted, which starts at
+    /// `next_window`
+    pub fn next(&self, tally: &WindowTally, next_window: u64) -> Self {
+        match tally.agreed() {
+            Some((path, proposals, metrics)) if path != self.path.index() => {
+                Self { path: ConsensusPath::from_index(path, metrics), activation_height: next_window, proposals }
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+/// Path proposals of one window's blocks
+#[derive(Debug, Clone, Default)]
+pub struct WindowTally {
+    counts: [u64; 4],
+    /// Latest proposal per path, whose metrics set the switched path's parameters
+    latest: [Option<NetworkMetrics>; 4],
+}
+
+impl WindowTally {
+    pub fn add(&mut self, proposal: &PathProposal) {
+        let path = (proposal.path as usize).min(PATHS.len() - 1);
+        self.counts[path] += 1;
+        self.latest[path] = Some(proposal.metrics.clone());
+    }
+
+    /// The path at least `PATH_SWITCH_THRESHOLD` blocks proposed, their count and the
+    /// latest metrics it was proposed with
+    pub fn agreed(&self) -> Option<(usize, u64, &NetworkMetrics)> {
+        let path = (0..PATHS.len()).find(|path| self.counts[*path] >= PATH_SWITCH_THRESHOLD)?;
+        Some((path, self.counts[path], self.latest[path].as_ref()?))
+    }
+}
+
+/// Windows the agreed path switched at, known through one window of the canonical chain
+/// so it is never recounted from genesis
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PathHistory {
+    /// Last window whose agreed path is known
+    pub through: u64,
+    pub switches: BTreeMap<u64, AgreedPath>,
+}
+
+impl PathHistory {
+    /// The agreed path of `window`, or of the last known window if it is further on
+    pub fn at(&self, window: u64) -> AgreedPath {
+        let window = window.min(self.through);
+        self.switches.range(..=window).next_back().map_or_else(AgreedPath::genesis, |(_, agreed)| agreed.clone())
+    }
+
+    /// Records the agreed path of the window after the last known one
+    pub fn push(&mut self, agreed: AgreedPath) {
+        self.through += 1;
+        if agreed.activation_height == self.through * PATH_WINDOW {
+            self.switches.insert(self.through, agreed);
+        }
+    }
+
+    /// Forgets every window agreed by blocks from `height` on, which a reorg replaced
+    pub fn rewind(&mut self, height: u64) {
+        self.through = self.through.min(height / PATH_WINDOW);
+        self.switches.split_off(&(self.through + 1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_agreement() {
+        let calm = NetworkMetrics::default();
+        let attacked = NetworkMetrics { attack_probability: 0.9, ..Default::default() };
+        let proposal = |index: usize, metrics: &NetworkMetrics| PathProposal { path: index as u8, metrics: metrics.clone() };
+
+        // Emergency mode needs emergency conditions, the fast lane a calm network
+        assert!(proposal(0, &calm).check().is_ok() && proposal(3, &attacked).check().is_ok());
+        assert!(proposal(3, &calm).check().unwrap_err().contains("do not justify a switch to EmergencyMode"));
+        assert!(proposal(0, &attacked).check().is_err() && proposal(2, &attacked).check().is_err());
+        assert!(proposal(1, &attacked).check().is_ok());
+        assert!(proposal(4, &calm).check().is_err());
+        assert!(proposal(1, &NetworkMetrics { congestion_level: 1.5, ..Default::default() }).check().is_err());
+        assert!(proposal(1, &NetworkMetrics { cpu_usage: f64::NAN, ..Default::default() }).check().is_err());
+
+        // A switch takes a threshold of one window and applies from the next one
+        let genesis = AgreedPath::genesis();
+        assert_eq!(genesis.path.name(), "HybridPath");
+        let mut tally = WindowTally::default();
+        for _ in 1..PATH_SWITCH_THRESHOLD {
+            tally.add(&PathProposal::new(&ConsensusPath::from_index(1, &calm), &calm));
+            tally.add(&proposal(0, &calm));
+        }
+        assert_eq!(genesis.next(&tally, PATH_WINDOW).activation_height, 0);
+        tally.add(&proposal(1, &attacked));
+        let switched = genesis.next(&tally, PATH_WINDOW);
+        assert_eq!((switched.path.name(), switched.activation_height, switched.proposals), ("SecureLane", PATH_WINDOW, PATH_SWITCH_THRESHOLD));
+        assert!(matches!(switched.path, ConsensusPath::SecureLane { validator_threshold: 66, .. }));
+
+        // Agreeing on the path already in force changes nothing
+        assert_eq!(switched.next(&tally, 2 * PATH_WINDOW).activation_height, PATH_WINDOW);
+        assert_eq!(switched.next(&WindowTally::default(), 2 * PATH_WINDOW).path.name(), "SecureLane");
+
+        // History keeps only the switches, and a reorg forgets what it replaced
+        let mut history = PathHistory::default();
+        history.push(switched.clone());
+        history.push(switched.next(&WindowTally::default(), 2 * PATH_WINDOW));
+        assert_eq!((history.through, history.switches.len()), (2, 1));
+        assert_eq!((history.at(0).path.name(), history.at(2).path.name(), history.at(9).path.name()), ("HybridPath", "SecureLane", "SecureLane"));
+        history.rewind(PATH_WINDOW - 1);
+        assert_eq!((history.through, history.at(1).path.name()), (0, "HybridPath"));
+
+        println!("   Path agreement working!");
+    }
+}
@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+use crate::storage::state::{StateManager, StateSnapshot};
+
+/// Trusted (weak subjectivity) block a new node syncs from instead of genesis
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub height: u64,
+    #[serde(with = "hex::serde")]
+    pub block_hash: [u8; 32],
+    /// `validator_set_hash` of the validators the checkpoint was taken under
+    #[serde(with = "hex::serde")]
+    pub validator_set_hash: [u8; 32],
+}
+
+/// Commits to a validator set in schedule order
+pub fn validator_set_hash(validators: &[Vec<u8>]) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    for validator in validators {
+        hasher.update((validator.len() as u32).to_be_bytes());
+        hasher.update(validator);
+    }
+    hasher.finalize().into()
+}
+
+impl Checkpoint {
+    pub fn check_validators(&self, validators: &[Vec<u8>]) -> Result<(), String> {
+        if validator_set_hash(validators) != self.validator_set_hash {
+            return Err("Checkpoint was taken under a different validator set".to_string());
+        }
+        Ok(())
+    }
+
+    /// Refuses a block at the checkpoint height other than the checkpoint block
+    pub fn check_block(&self, height: u64, hash: &[u8; 32]) -> Result<(), String> {
+        if height == self.height && *hash != self.block_hash {
+            return Err(format!("Block {} conflicts with the checkpoint", height));
+        }
+        Ok(())
+    }
+
+    /// Checks a peer's snapshot is of the checkpoint block and that its accounts add up
+    /// to the block's state root, returning the state it describes
+    pub fn verify_snapshot(&self, snapshot: &StateSnapshot) -> Result<StateManager, String> {
+        let block = &snapshot.block;
+        if block.header.height != self.height || block.hash() != self.block_hash {
+            return Err(format!("Snapshot of block {} is not the checkpoint block", block.header.height));
+        }
+        if !block.has_valid_merkle_root() {
+            return Err("Snapshot block has an invalid merkle root".to_string());
+        }
+        let state = StateManager::from_snapshot(snapshot);
+        if state.state_root() != block.header.state_root {
+            return Err("Snapshot accounts do not match the checkpoint state root".to_string());
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{QuantumKeyPair, QuantumSignature};
+    use crate::network::NetworkService;
+    use crate::node::Node;
+    use crate::storage::blocks::{Block, ConsensusData, Transaction};
+    use crate::storage::database::BlockchainDB;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn open(name: &str, allocations: &[(Vec<u8>, u64)]) -> Node {
+        let path = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&path);
+        let db = BlockchainDB::new(path.to_str().unwrap()).unwrap();
+        db.store_genesis_allocations(allocations).unwrap();
+        Node::open(db).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_sync() {
+        let keypair = QuantumKeyPair::generate();
+        let allocations = vec![(keypair.public_key().to_vec(), 1_000)];
+        let source = open("triunity_test_checkpoint_source", &allocations);
+        source.produce_block().unwrap();
+        let mut tx = Transaction::new(keypair.public_key().to_vec(), vec![0xcc; 32], 100, 1, 0, vec![], QuantumSignature::new(vec![]));
+        tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+        source.submit_transaction(tx).unwrap();
+        for _ in 0..5 {
+            source.produce_block().unwrap();
+        }
+        let checkpoint = Checkpoint {
+            height: 3,
+            block_hash: source.db().get_block(3).unwrap().unwrap().hash(),
+            validator_set_hash: validator_set_hash(&[]),
+        };
+
+        // Forged snapshots are refused
+        let mut snapshot = StateManager::snapshot_at(source.db(), 3).unwrap().unwrap();
+        checkpoint.verify_snapshot(&snapshot).unwrap();
+        snapshot.accounts[0].1.balance += 1;
+        assert!(checkpoint.verify_snapshot(&snapshot).is_err());
+        let wrong_set = Checkpoint { validator_set_hash: validator_set_hash(&[vec![1]]), ..checkpoint.clone() };
+        assert!(open("triunity_test_checkpoint_wrong", &allocations).with_checkpoint(wrong_set).is_err());
+
+        let synced = open("triunity_test_checkpoint_synced", &allocations).with_checkpoint(checkpoint.clone()).unwrap();
+        assert!(synced.awaiting_checkpoint());
+        assert!(synced.produce_block().is_err());
+        let conflicting = Block::new(source.db().get_block(2).unwrap().unwrap().hash(), vec![], 3, ConsensusData::default());
+        assert!(synced.import_block(&conflicting).is_err());
+
+        let network = NetworkService::new(Arc::new(source));
+        let address = network.listen(SocketAddr::from(([127, 0, 0, 1], 0))).await.unwrap();
+        let synced_network = NetworkService::new(Arc::new(synced));
+        synced_network.connect(address).await.unwrap();
+        let synced = synced_network.node();
+        for _ in 0..100 {
+            if synced.next_height().unwrap() == 6 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(synced.next_height().unwrap(), 6);
+        assert!(!synced.awaiting_checkpoint());
+
+        // Only the checkpoint block and the blocks after it are stored
+        assert!(synced.db().get_block(2).unwrap().is_none());
+        let source_state = StateManager::replay(network.node().db()).unwrap();
+        let synced_state = StateManager::replay(synced.db()).unwrap();
+        assert_eq!(synced_state.state_root(), source_state.state_root());
+        assert_eq!(synced_state.get_account(&[0xcc; 32]).unwrap().balance, 100);
+        assert!(StateManager::replay_to(synced.db(), Some(2)).is_err());
+        assert!(synced.import_block(&conflicting).is_err());
+
+        println!("   Checkpoint sync working!");
+        for name in ["source", "wrong", "synced"] {
+            let _ = std::fs::remove_dir_all(std::env::temp_dir().join(format!("triunity_test_checkpoint_{}", name)));
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+use super::duties::EPOCH_LENGTH;
+use crate::storage::merkle::{MerkleProof, MerkleTree};
+
+/// Fewest validators a fast-lane committee holds, while the set has that many
+pub const MIN_FAST_LANE_COMMITTEE: usize = 21;
+
+const SEED_DOMAIN: &[u8] = b"triunity/epoch-seed";
+
+/// Validators sampled into the fast-lane committee out of `validators`
+pub fn committee_size(validators: usize) -> usize {
+    (validators / 4).max(MIN_FAST_LANE_COMMITTEE).min(validators)
+}
+
+/// Randomness beacon output for `epoch`: the hash of the previous epoch's last block,
+/// bound to the epoch number. `boundary` is `None` only for epoch 0
+pub fn epoch_seed(epoch: u64, boundary: Option<[u8; 32]>) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(SEED_DOMAIN);
+    hasher.update(epoch.to_be_bytes());
+    hasher.update(boundary.unwrap_or([0; 32]));
+    hasher.finalize().into()
+}
+
+/// Height of the block whose hash seeds `epoch`, `None` for epoch 0
+pub fn seed_height(epoch: u64) -> Option<u64> {
+    (epoch * EPOCH_LENGTH).checked_sub(1)
+}
+
+/// Validators proposing fast-lane blocks during one epoch, drawn by stake without
+/// replacement and kept in validator set order, which is also their proposal order
+#[derive(Debug, Clone)]
+pub struct FastLaneCommittee {
+    pub epoch: u64,
+    /// Indices into the validator set
+    pub members: Vec<usize>,
+    tree: MerkleTree,
+}
+
+impl FastLaneCommittee {
+    /// Draws `committee_size` of `validators` weighted by `stakes`, each counted as at
+    /// least 1 so unfunded validators can still be drawn. A set no larger than the
+    /// committee is taken whole, whatever the seed
+    pub fn sample(epoch: u64, seed: [u8; 32], validators: &[Vec<u8>], stakes: &[u64]) -> Self {
+        let size = committee_size(validators.len());
+        let mut members: Vec<usize> = if size == validators.len() {
+            (0..size).collect()
+        } else {
+            let mut remaining: Vec<(usize, u128)> = (0..validators.len())
+                .map(|index| (index, stakes.get(index).copied().unwrap_or_default().max(1) as u128))
+                .collect();
+            let mut members = Vec::with_capacity(size);
+            for draw in 0..size as u64 {
+                let total: u128 = remaining.iter().map(|(_, stake)| stake).sum();
+                let mut target = draw_value(&seed, draw) % total;
+                let position = remaining.iter().position(|(_, stake)| {
+                    if target < *stake {
+                        return true;
+                    }
+                    target -= stake;
+                    false
+                });
+                members.push(remaining.remove(position.unwrap_or_default()).0);
+            }
+            members
+        };
+        members.sort_unstable();
+        let tree = MerkleTree::new(&members.iter().map(|index| validators[*index].clone()).collect::<Vec<_>>());
+        Self { epoch, members, tree }
+    }
+
+    /// Validator index scheduled to propose at `height`, `None` for an empty committee
+    pub fn proposer_index(&self, height: u64) -> Option<usize> {
+        if self.members.is_empty() {
+            return None;
+        }
+        Some(self.members[(height % self.members.len() as u64) as usize])
+    }
+
+    /// Merkle root over the members' addresses, in order
+    pub fn root(&self) -> [u8; 32] {
+        self.tree.root()
+    }
+
+    /// Proof that the validator at `index` in the set is a member
+    pub fn proof(&self, index: usize) -> Option<CommitteeProof> {
+        let position = self.members.iter().position(|member| *member == index)?;
+        Some(CommitteeProof { epoch: self.epoch, proof: self.tree.generate_proof(position)? })
+    }
+}
+
+/// Proof carried by a fast-lane block that its proposer sits on the epoch's committee
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitteeProof {
+    pub epoch: u64,
+    pub proof: MerkleProof,
+}
+
+impl CommitteeProof {
+    /// Whether the proof places `validator` on the committee with root `root`
+    pub fn verify(&self, validator: &[u8], root: [u8; 32]) -> bool {
+        let leaf: [u8; 32] = Sha3_256::digest(validator).into();
+        self.proof.leaf_hash == leaf && self.proof.root == root && MerkleTree::verify_proof(&self.proof)
+    }
+}
+
+fn draw_value(seed: &[u8; 32], draw: u64) -> u128 {
+    let mut hasher = Sha3_256::new();
+    hasher.update(seed);
+    hasher.update(draw.to_be_bytes());
+    let digest = hasher.finalize();
+    u128::from_be_bytes(digest[..16].try_into().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_committee_sampling() {
+        assert_eq!((committee_size(3), committee_size(40), committee_size(200)), (3, 21, 50));
+        // Small sets are their own committee, in set order
+        let small: Vec<Vec<u8>> = (0..3u8).map(|index| vec![index; 32]).collect();
+        let committee = FastLaneCommittee::sample(1, [0; 32], &small, &[]);
+        assert_eq!(committee.members, vec![0, 1, 2]);
+        assert_eq!(committee.proposer_index(4), Some(1));
+
+        // Larger sets are sampled by stake and the same seed always draws the same committee
+        let validators: Vec<Vec<u8>> = (0..40u8).map(|index| vec![index; 32]).collect();
+        let mut stakes = vec![1; 40];
+        stakes[7] = 1_000_000;
+        let seed = epoch_seed(3, Some([9; 32]));
+        let committee = FastLaneCommittee::sample(3, seed, &validators, &stakes);
+        assert_eq!(committee.members.len(), 21);
+        assert!(committee.members.contains(&7));
+        assert!(committee.members.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(FastLaneCommittee::sample(3, seed, &validators, &stakes).root(), committee.root());
+        let reseeded = FastLaneCommittee::sample(3, epoch_seed(3, Some([8; 32])), &validators, &stakes);
+        assert_ne!(reseeded.members, committee.members);
+
+        let proof = committee.proof(7).unwrap();
+        assert!(proof.verify(&validators[7], committee.root()));
+        assert!(!proof.verify(&validators[8], committee.root()));
+        let outsider = (0..40).find(|index| !committee.members.contains(index)).unwrap();
+        assert!(committee.proof(outsider).is_none());
+
+        println!("   Fast lane committee sampling working!");
+    }
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::router::{ConsensusPath, NetworkMetrics};
+use crate::storage::database::{BlockchainDB, WriteGate};
+
+/// Decisions kept before the oldest are dropped
+pub const MAX_DECISIONS: usize = 10_000;
+
+/// One consensus path choice, with what the router saw when it was made
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsensusDecision {
+    /// Increases by one per decision; pages of the history are cut by it
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub metrics: NetworkMetrics,
+    pub path: ConsensusPath,
+    /// Router confidence in its metrics when the decision was made
+    pub confidence: f64,
+    /// Why the router's own choice was not followed; `None` when it was
+    pub override_reason: Option<String>,
+}
+
+/// Every consensus path decision, persisted for audit. Only the most recent
+/// `MAX_DECISIONS` are kept
+#[derive(Debug)]
+pub struct DecisionLog {
+    tree: sled::Tree,
+    capacity: usize,
+    next_sequence: AtomicU64,
+    writes: WriteGate,
+}
+
+impl DecisionLog {
+    pub fn open(db: &BlockchainDB) -> Result<Self, String> {
+        let tree = db.tree("consensus_decisions")?;
+        let next_sequence = match tree.last().map_err(|e| e.to_string())? {
+            Some((key, _)) => sequence_of(&key)? + 1,
+            None => 0,
+        };
+        Ok(Self { tree, capacity: MAX_DECISIONS, next_sequence: AtomicU64::new(next_sequence), writes: db.writes().clone() })
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    pub fn record(&self, metrics: &NetworkMetrics, path: &ConsensusPath, confidence: f64, override_reason: Option<String>) -> Result<ConsensusDecision, String> {
+        let decision = ConsensusDecision {
+            sequence: self.next_sequence.fetch_add(1, Ordering::Relaxed),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs()),
+            metrics: metrics.clone(),
+            path: path.clone(),
+            confidence,
+            override_reason,
+        };
+        let value = bincode::serialize(&decision).map_err(|e| e.to_string())?;
+        let _writing = self.writes.enter();
+        self.tree.insert(decision.sequence.to_be_bytes(), value).map_err(|e| e.to_string())?;
+        while self.tree.len() > self.capacity {
+            self.tree.pop_min().map_err(|e| e.to_string())?;
+        }
+        Ok(decision)
+    }
+
+    /// Up to `limit` decisions older than `before`, newest first, and the cursor of the
+    /// next page if there are more
+    pub fn page(&self, before: Option<u64>, limit: usize) -> Result<(Vec<ConsensusDecision>, Option<u64>), String> {
+        let range = match before {
+            Some(before) => self.tree.range(..before.to_be_bytes()),
+            None => self.tree.range::<[u8; 8], _>(..),
+        };
+        let mut range = range.rev();
+        let decisions = range
+            .by_ref()
+            .take(limit)
+            .map(|item| decode(&item.map_err(|e| e.to_string())?.1))
+            .collect::<Result<Vec<_>, String>>()?;
+        let next = range.next().and(decisions.last()).map(|last| last.sequence);
+        Ok((decisions, next))
+    }
+
+    /// Every kept decision, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = Result<ConsensusDecision, String>> {
+        self.tree.iter().map(|item| decode(&item.map_err(|e| e.to_string())?.1))
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+}
+
+fn sequence_of(key: &[u8]) -> Result<u64, String> {
+    Ok(u64::from_be_bytes(key.try_into().map_err(|_| "Invalid decision key".to_string())?))
+}
+
+fn decode(value: &[u8]) -> Result<ConsensusDecision, String> {
+    bincode::deserialize(value).map_err(|e| format!("Corrupt consensus decision: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus::router::ConsensusRouter;
+
+    #[test]
+    fn test_decision_log() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_decisions");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        let router = ConsensusRouter::new();
+        let log = DecisionLog::open(&db).unwrap().with_capacity(4);
+        for index in 0..6 {
+            let reason = (index == 5).then(|| "operator".to_string());
+            log.record(router.network_status(), &router.select_optimal_path(), router.ai_confidence(), reason).unwrap();
+        }
+
+        // The oldest decisions fall out once the log is full
+        assert_eq!(log.len(), 4);
+        let (page, next) = log.page(None, 3).unwrap();
+        assert_eq!(page.iter().map(|decision| decision.sequence).collect::<Vec<_>>(), vec![5, 4, 3]);
+        assert_eq!(page[0].override_reason.as_deref(), Some("operator"));
+        assert_eq!(next, Some(3));
+        let (page, next) = log.page(next, 3).unwrap();
+        assert_eq!((page.len(), page[0].sequence, next), (1, 2, None));
+        assert_eq!(log.iter().next().unwrap().unwrap().sequence, 2);
+
+        // Sequences carry on after a restart
+        drop(log);
+        let log = DecisionLog::open(&db).unwrap();
+        assert_eq!(log.record(router.network_status(), &router.select_optimal_path(), 0.5, None).unwrap().sequence, 6);
+
+        println!("   Consensus decision log working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -0,0 +1,217 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::committee::FastLaneCommittee;
+
+/// Heights per duty epoch
+pub const EPOCH_LENGTH: u64 = 32;
+
+/// Epochs of missed-slot history kept for performance queries
+const RETAINED_EPOCHS: u64 = 64;
+
+/// Weight of the latest epoch when folding participation into reputation
+const REPUTATION_SMOOTHING: f64 = 0.2;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Validator {
+    #[serde(with = "hex::serde")]
+    pub address: Vec<u8>,
+    /// Smoothed participation between 0.0 and 1.0
+    pub reputation: f64,
+}
+
+impl Validator {
+    pub fn new(address: Vec<u8>) -> Self {
+        Self { address, reputation: 1.0 }
+    }
+
+    /// Moves reputation towards `participation`, the share of duties met in an epoch
+    pub fn update_reputation(&mut self, participation: f64) {
+        let participation = participation.clamp(0.0, 1.0);
+        self.reputation += (participation - self.reputation) * REPUTATION_SMOOTHING;
+    }
+}
+
+/// One validator's duties in one epoch. Proposers vote for the block they seal, so
+/// every proposal slot is also a vote slot until validators sign standalone votes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidatorPerformance {
+    #[serde(with = "hex::serde")]
+    pub address: Vec<u8>,
+    pub epoch: u64,
+    pub expected_proposals: u64,
+    pub proposals: u64,
+    /// Slots this node waited through without receiving the validator's block
+    pub missed_blocks: u64,
+    pub expected_votes: u64,
+    pub votes: u64,
+    /// Current reputation, which only reflects epochs that have closed
+    pub reputation: f64,
+}
+
+impl ValidatorPerformance {
+    /// Share of the epoch's duties met, 1.0 when there were none
+    pub fn participation(&self) -> f64 {
+        if self.expected_proposals == 0 {
+            1.0
+        } else {
+            self.proposals as f64 / self.expected_proposals as f64
+        }
+    }
+}
+
+/// Row of the validator set as dashboards list it. Genesis validators carry equal
+/// weight in the round-robin schedule, so what backs a validator is its account balance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidatorStatus {
+    #[serde(with = "hex::serde")]
+    pub address: Vec<u8>,
+    pub balance: u64,
+    pub reputation: f64,
+    /// Share of proposal slots met over the previous and current epochs
+    pub uptime: f64,
+    pub last_proposed_height: Option<u64>,
+    /// Whether this is the node's own validator key
+    pub local: bool,
+}
+
+/// Tracks the round-robin proposer schedule against what actually happened and
+/// adjusts reputations as epochs close
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DutyTracker {
+    validators: Vec<Validator>,
+    /// Missed slots keyed by epoch and validator index
+    missed: HashMap<(u64, usize), u64>,
+    /// Epochs below this one have been folded into reputations
+    next_epoch: u64,
+}
+
+impl DutyTracker {
+    /// Starts tracking at `epoch`, so earlier history never affects reputations
+    pub fn new(validators: &[Vec<u8>], epoch: u64) -> Self {
+        Self {
+            validators: validators.iter().cloned().map(Validator::new).collect(),
+            missed: HashMap::new(),
+            next_epoch: epoch,
+        }
+    }
+
+    pub fn epoch_of(height: u64) -> u64 {
+        height / EPOCH_LENGTH
+    }
+
+    /// Whether this tracker was saved for the same validator set
+    pub fn tracks(&self, validators: &[Vec<u8>]) -> bool {
+        self.validators.len() == validators.len()
+            && self.validators.iter().zip(validators).all(|(validator, address)| validator.address == *address)
+    }
+
+    pub fn validators(&self) -> &[Validator] {
+        &self.validators
+    }
+
+    /// Counts a slot in which the proposer `committee` scheduled for `height` produced nothing
+    pub fn record_missed_slot(&mut self, height: u64, committee: &FastLaneCommittee) {
+        if let Some(index) = committee.proposer_index(height) {
+            *self.missed.entry((Self::epoch_of(height), index)).or_default() += 1;
+        }
+    }
+
+    /// Duties in `epoch` under its fast-lane `committee`, where `proposers[i]` sealed the
+    /// canonical block at the epoch's `i`th height
+    pub fn performance(&self, epoch: u64, committee: &FastLaneCommittee, proposers: &[Option<Vec<u8>>]) -> Vec<ValidatorPerformance> {
+        let first = epoch * EPOCH_LENGTH;
+        let mut performance: Vec<ValidatorPerformance> = self.validators
+            .iter()
+            .enumerate()
+            .map(|(index, validator)| {
+                let missed = self.missed.get(&(epoch, index)).copied().unwrap_or_default();
+                ValidatorPerformance {
+                    address: validator.address.clone(),
+                    epoch,
+                    expected_proposals: missed,
+                    proposals: 0,
+                    missed_blocks: missed,
+                    expected_votes: 0,
+                    votes: 0,
+                    reputation: validator.reputation,
+                }
+            })
+            .collect();
+        for (offset, proposer) in proposers.iter().enumerate().take(EPOCH_LENGTH as usize) {
+            let Some(index) = committee.proposer_index(first + offset as u64) else {
+                break;
+            };
+            let entry = &mut performance[index];
+            entry.expected_proposals += 1;
+            if proposer.as_deref() == Some(entry.address.as_slice()) {
+                entry.proposals += 1;
+            }
+        }
+        for entry in &mut performance {
+            entry.expected_votes = entry.expected_proposals;
+            entry.votes = entry.proposals;
+        }
+        performance
+    }
+
+    /// Epoch whose reputations are next to be adjusted
+    pub fn next_epoch(&self) -> u64 {
+        self.next_epoch
+    }
+
+    /// Folds a finished epoch's participation into reputations. Validators without
+    /// duties in it keep their reputation
+    pub fn close_epoch(&mut self, committee: &FastLaneCommittee, proposers: &[Option<Vec<u8>>]) -> Vec<ValidatorPerformance> {
+        let epoch = self.next_epoch;
+        let performance = self.performance(epoch, committee, proposers);
+        for (validator, entry) in self.validators.iter_mut().zip(&performance) {
+            if entry.expected_proposals > 0 {
+                validator.update_reputation(entry.participation());
+            }
+        }
+        self.missed.retain(|(missed_epoch, _), _| missed_epoch + RETAINED_EPOCHS > epoch);
+        self.next_epoch += 1;
+        performance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duty_tracking() {
+        let validators = vec![vec![1; 32], vec![2; 32]];
+        let committee = FastLaneCommittee::sample(0, [0; 32], &validators, &[]);
+        let mut tracker = DutyTracker::new(&validators, 0);
+        assert!(tracker.tracks(&validators));
+        assert!(!tracker.tracks(&validators[..1]));
+
+        // Validator 2 stalled height 1 for three slots before finally proposing
+        let proposers: Vec<Option<Vec<u8>>> = (0..EPOCH_LENGTH)
+            .map(|height| Some(validators[(height % 2) as usize].clone()))
+            .collect();
+        for _ in 0..3 {
+            tracker.record_missed_slot(1, &committee);
+        }
+        let performance = tracker.performance(0, &committee, &proposers[..4]);
+        assert_eq!((performance[0].expected_proposals, performance[0].proposals), (2, 2));
+        assert_eq!((performance[1].expected_proposals, performance[1].proposals), (5, 2));
+        assert_eq!(performance[1].missed_blocks, 3);
+        assert_eq!(performance[1].votes, 2);
+
+        let closed = tracker.close_epoch(&committee, &proposers);
+        assert_eq!(closed[1].expected_proposals, 19);
+        assert_eq!(tracker.next_epoch(), 1);
+        assert_eq!(tracker.validators()[0].reputation, 1.0);
+        assert!(tracker.validators()[1].reputation < 1.0);
+        assert_eq!(tracker.performance(0, &committee, &[])[1].missed_blocks, 3);
+
+        let mut validator = Validator::new(vec![3; 32]);
+        validator.update_reputation(0.0);
+        assert!((validator.reputation - 0.8).abs() < 1e-9);
+
+        println!("   Validator duty tracking working!");
+    }
+}
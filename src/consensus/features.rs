@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+
+/// Bits of a header's `signals` a deployment may use
+pub const SIGNAL_BITS: u8 = 32;
+
+/// Blocks per signaling window on a chain whose genesis sets none
+pub const DEFAULT_SIGNALING_WINDOW: u64 = 128;
+
+/// Blocks of a window that must signal for a deployment to lock in, by default three quarters
+pub const DEFAULT_LOCK_IN_THRESHOLD: u64 = 96;
+
+/// A protocol feature proposed for activation by block producers setting `bit` in the
+/// headers they propose. Signaling counts from the first window starting at or after
+/// `start_height`; a deployment not locked in by a window ending at `timeout_height` fails
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deployment {
+    pub name: String,
+    pub bit: u8,
+    pub start_height: u64,
+    pub timeout_height: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentState {
+    /// Signaling has not started
+    Defined,
+    /// Producers signal; a window reaching the threshold locks the feature in
+    Started,
+    /// Activates when the next window begins, whatever is signaled
+    LockedIn,
+    /// The feature's flag is set in the chain parameters
+    Active,
+    /// Timed out before locking in
+    Failed,
+}
+
+impl Deployment {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.bit >= SIGNAL_BITS {
+            return Err(format!("Deployment {} uses bit {}, past the last bit {}", self.name, self.bit, SIGNAL_BITS - 1));
+        }
+        if self.timeout_height <= self.start_height {
+            return Err(format!("Deployment {} times out before it starts", self.name));
+        }
+        Ok(())
+    }
+
+    pub fn mask(&self) -> u32 {
+        1 << self.bit
+    }
+
+    /// State of the deployment for the block at `height`. States only change between
+    /// windows of `window` blocks; `signaled(index)` counts the blocks of window `index`
+    /// setting the bit and is asked only of windows spent in `Started`
+    pub fn state_at(&self, height: u64, window: u64, threshold: u64, mut signaled: impl FnMut(u64) -> Result<u64, String>) -> Result<DeploymentState, String> {
+        let mut state = DeploymentState::Defined;
+        for index in 0..height / window {
+            let next = (index + 1) * window;
+            state = match state {
+                DeploymentState::Defined if next >= self.start_height => DeploymentState::Started,
+                DeploymentState::Started if signaled(index)? >= threshold => DeploymentState::LockedIn,
+                DeploymentState::Started if next >= self.timeout_height => DeploymentState::Failed,
+                DeploymentState::LockedIn => DeploymentState::Active,
+                DeploymentState::Active | DeploymentState::Failed => break,
+                unchanged => unchanged,
+            };
+        }
+        Ok(state)
+    }
+}
+
+/// Where a deployment stands at a height, and the current window's signaling so far
+#[derive(Debug, Clone, Serialize)]
+pub struct DeploymentStatus {
+    #[serde(flatten)]
+    pub deployment: Deployment,
+    pub state: DeploymentState,
+    /// Blocks of the current window that signaled before the height
+    pub signaled: u64,
+    pub threshold: u64,
+    pub window: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feature_activation() {
+        let deployment = Deployment { name: "batches".to_string(), bit: 3, start_height: 10, timeout_height: 50 };
+        deployment.validate().unwrap();
+        assert!(Deployment { bit: SIGNAL_BITS, ..deployment.clone() }.validate().is_err());
+        assert!(Deployment { timeout_height: 10, ..deployment.clone() }.validate().is_err());
+
+        // Windows of 10: starts with window 1, misses the threshold in window 1, locks in
+        // with window 2 and activates from window 4 on
+        let counts = [10, 5, 8, 0, 0, 0];
+        let state = |height: u64| deployment.state_at(height, 10, 8, |index| Ok(counts[index as usize])).unwrap();
+        assert_eq!(state(9), DeploymentState::Defined);
+        assert_eq!((state(10), state(29)), (DeploymentState::Started, DeploymentState::Started));
+        assert_eq!((state(30), state(39)), (DeploymentState::LockedIn, DeploymentState::LockedIn));
+        assert_eq!((state(40), state(1_000)), (DeploymentState::Active, DeploymentState::Active));
+
+        // Signaling before the start does not count, and a deployment short of the threshold fails
+        let mut asked = Vec::new();
+        let failed = deployment.state_at(1_000, 10, 8, |index| {
+            asked.push(index);
+            Ok(7)
+        });
+        assert_eq!(failed.unwrap(), DeploymentState::Failed);
+        assert_eq!(asked, vec![1, 2, 3, 4]);
+
+        println!("   Feature activation working!");
+    }
+}
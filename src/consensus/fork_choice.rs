@@ -0,0 +1,280 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Blocks this far below the head are treated as final and can no longer be reorganised
+pub const FINALITY_DEPTH: u64 = 32;
+
+/// Parent hash of the genesis block, used as the tree root before anything is final
+pub const GENESIS_PARENT: [u8; 32] = [0; 32];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForkChoiceHead {
+    pub head: [u8; 32],
+    pub head_height: u64,
+    pub finalized: [u8; 32],
+    pub finalized_height: u64,
+}
+
+#[derive(Debug, Clone)]
+struct BlockEntry {
+    parent: [u8; 32],
+    height: u64,
+    children: Vec<[u8; 32]>,
+    /// Latest vote weight on this block and every block built on it
+    weight: u64,
+}
+
+/// Block tree rooted at the finalized block. The head is the highest block descending
+/// from it; equal heights go to the branch carrying more vote weight where the branches
+/// split, then the lower hash
+#[derive(Debug, Clone)]
+pub struct ForkChoice {
+    blocks: HashMap<[u8; 32], BlockEntry>,
+    finalized: [u8; 32],
+    votes: HashMap<Vec<u8>, ([u8; 32], u64)>,
+}
+
+impl ForkChoice {
+    pub fn new(finalized: [u8; 32], height: u64) -> Self {
+        let mut blocks = HashMap::new();
+        blocks.insert(finalized, BlockEntry { parent: GENESIS_PARENT, height, children: Vec::new(), weight: 0 });
+        Self {
+            blocks,
+            finalized,
+            votes: HashMap::new(),
+        }
+    }
+
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.blocks.contains_key(hash)
+    }
+
+    pub fn height(&self, hash: &[u8; 32]) -> Option<u64> {
+        self.blocks.get(hash).map(|entry| entry.height)
+    }
+
+    pub fn parent(&self, hash: &[u8; 32]) -> Option<[u8; 32]> {
+        self.blocks.get(hash).filter(|_| *hash != self.finalized).map(|entry| entry.parent)
+    }
+
+    pub fn add_block(&mut self, hash: [u8; 32], parent: [u8; 32], height: u64) -> Result<(), String> {
+        if self.blocks.contains_key(&hash) {
+            return Ok(());
+        }
+        let parent_entry = self.blocks
+            .get_mut(&parent)
+            .ok_or_else(|| format!("Parent 0x{} is not on a finalized branch", hex::encode(parent)))?;
+        // The genesis block hangs off the placeholder root at its own height
+        let expected = if parent == GENESIS_PARENT { 0 } else { parent_entry.height + 1 };
+        if height != expected {
+            return Err(format!("Block at height {} cannot follow its parent at height {}", height, parent_entry.height));
+        }
+        parent_entry.children.push(hash);
+        self.blocks.insert(hash, BlockEntry { parent, height, children: Vec::new(), weight: 0 });
+        Ok(())
+    }
+
+    /// Records `validator`'s latest vote, replacing any earlier one
+    pub fn add_vote(&mut self, validator: &[u8], block: [u8; 32], weight: u64) {
+        if !self.blocks.contains_key(&block) {
+            return;
+        }
+        if let Some((previous, previous_weight)) = self.votes.insert(validator.to_vec(), (block, weight)) {
+            self.shift_weight(&previous, previous_weight, false);
+        }
+        self.shift_weight(&block, weight, true);
+    }
+
+    /// Adds or takes `weight` from `hash` and each of its ancestors
+    fn shift_weight(&mut self, hash: &[u8; 32], weight: u64, add: bool) {
+        for ancestor in self.ancestors(hash) {
+            if let Some(entry) = self.blocks.get_mut(&ancestor) {
+                entry.weight = match add {
+                    true => entry.weight.saturating_add(weight),
+                    false => entry.weight.saturating_sub(weight),
+                };
+            }
+        }
+    }
+
+    /// Drops `hash` and every block built on it, e.g. after it failed validation
+    pub fn remove_branch(&mut self, hash: &[u8; 32]) {
+        if *hash == self.finalized {
+            return;
+        }
+        if let Some(entry) = self.blocks.get(hash) {
+            let (parent, weight) = (entry.parent, entry.weight);
+            self.shift_weight(&parent, weight, false);
+            if let Some(parent) = self.blocks.get_mut(&parent) {
+                parent.children.retain(|child| child != hash);
+            }
+        }
+        let mut stack = vec![*hash];
+        while let Some(current) = stack.pop() {
+            if let Some(entry) = self.blocks.remove(&current) {
+                stack.extend(entry.children);
+            }
+        }
+        self.votes.retain(|_, (block, _)| self.blocks.contains_key(block));
+    }
+
+    pub fn head(&self) -> [u8; 32] {
+        self.blocks
+            .iter()
+            .filter(|(_, entry)| entry.children.is_empty())
+            .max_by(|(a_hash, a), (b_hash, b)| {
+                a.height
+                    .cmp(&b.height)
+                    .then_with(|| {
+                        let (a_fork, b_fork) = self.fork_points(a_hash, b_hash);
+                        self.weight(&a_fork).cmp(&self.weight(&b_fork))
+                    })
+                    .then_with(|| b_hash.cmp(a_hash))
+            })
+            .map(|(hash, _)| *hash)
+            .unwrap_or(self.finalized)
+    }
+
+    /// Latest vote weight on `hash` and every block built on it
+    pub fn weight(&self, hash: &[u8; 32]) -> u64 {
+        self.blocks.get(hash).map_or(0, |entry| entry.weight)
+    }
+
+    /// First blocks of `a`'s and `b`'s branches after their common ancestor
+    fn fork_points(&self, a: &[u8; 32], b: &[u8; 32]) -> ([u8; 32], [u8; 32]) {
+        let ancestor = self.common_ancestor(a, b);
+        let first = |tip| self.branch(&ancestor, tip).first().copied().unwrap_or(ancestor);
+        (first(a), first(b))
+    }
+
+    /// `hash` followed by its ancestors down to the finalized block
+    fn ancestors(&self, hash: &[u8; 32]) -> Vec<[u8; 32]> {
+        let mut chain = Vec::new();
+        let mut current = Some(*hash);
+        while let Some(hash) = current.filter(|hash| self.blocks.contains_key(hash)) {
+            chain.push(hash);
+            current = self.parent(&hash);
+        }
+        chain
+    }
+
+    pub fn common_ancestor(&self, a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+        let a_chain: HashSet<[u8; 32]> = self.ancestors(a).into_iter().collect();
+        self.ancestors(b)
+            .into_iter()
+            .find(|hash| a_chain.contains(hash))
+            .unwrap_or(self.finalized)
+    }
+
+    /// Blocks after `ancestor` up to and including `tip`, in height order
+    pub fn branch(&self, ancestor: &[u8; 32], tip: &[u8; 32]) -> Vec<[u8; 32]> {
+        let mut branch: Vec<[u8; 32]> = self.ancestors(tip)
+            .into_iter()
+            .take_while(|hash| hash != ancestor)
+            .collect();
+        branch.reverse();
+        branch
+    }
+
+    /// Moves the root to `hash`, pruning every branch that does not descend from it
+    pub fn finalize(&mut self, hash: [u8; 32]) -> Result<(), String> {
+        if hash == self.finalized {
+            return Ok(());
+        }
+        if !self.ancestors(&hash).contains(&self.finalized) {
+            return Err(format!("Block 0x{} does not descend from the finalized block", hex::encode(hash)));
+        }
+
+        let mut keep = HashSet::new();
+        let mut stack = vec![hash];
+        while let Some(current) = stack.pop() {
+            if let Some(entry) = self.blocks.get(&current) {
+                stack.extend(entry.children.iter().copied());
+                keep.insert(current);
+            }
+        }
+        self.blocks.retain(|block, _| keep.contains(block));
+        self.votes.retain(|_, (block, _)| keep.contains(block));
+        self.finalized = hash;
+        Ok(())
+    }
+
+    pub fn summary(&self) -> ForkChoiceHead {
+        let head = self.head();
+        ForkChoiceHead {
+            head,
+            head_height: self.height(&head).unwrap_or_default(),
+            finalized: self.finalized,
+            finalized_height: self.height(&self.finalized).unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(byte: u8) -> [u8; 32] {
+        [byte; 32]
+    }
+
+    #[test]
+    fn test_longest_chain_with_vote_tiebreak() {
+        let mut fork_choice = ForkChoice::new(hash(1), 10);
+        fork_choice.add_block(hash(2), hash(1), 11).unwrap();
+        fork_choice.add_block(hash(3), hash(2), 12).unwrap();
+        fork_choice.add_block(hash(4), hash(2), 12).unwrap();
+        assert!(fork_choice.add_block(hash(9), hash(8), 13).is_err());
+
+        // Equal heights without votes: lower hash wins
+        assert_eq!(fork_choice.head(), hash(3));
+        fork_choice.add_vote(b"validator-a", hash(4), 5);
+        assert_eq!(fork_choice.head(), hash(4));
+        fork_choice.add_vote(b"validator-b", hash(3), 3);
+        fork_choice.add_vote(b"validator-c", hash(3), 3);
+        assert_eq!(fork_choice.head(), hash(3));
+
+        // Weights are cached per subtree and follow a validator's latest vote
+        assert_eq!((fork_choice.weight(&hash(2)), fork_choice.weight(&hash(3)), fork_choice.weight(&hash(4))), (11, 6, 5));
+        fork_choice.add_vote(b"validator-c", hash(4), 3);
+        assert_eq!((fork_choice.weight(&hash(3)), fork_choice.weight(&hash(4))), (3, 8));
+        assert_eq!(fork_choice.head(), hash(4));
+
+        // Height beats weight, and a block must sit one above its parent
+        assert!(fork_choice.add_block(hash(5), hash(3), 14).unwrap_err().contains("cannot follow"));
+        fork_choice.add_block(hash(5), hash(3), 13).unwrap();
+        assert_eq!(fork_choice.head(), hash(5));
+        fork_choice.remove_branch(&hash(5));
+        fork_choice.add_block(hash(5), hash(4), 13).unwrap();
+        assert_eq!(fork_choice.head(), hash(5));
+        assert_eq!(fork_choice.common_ancestor(&hash(3), &hash(5)), hash(2));
+        assert_eq!(fork_choice.branch(&hash(2), &hash(5)), vec![hash(4), hash(5)]);
+
+        println!("   Fork choice head selection working!");
+    }
+
+    #[test]
+    fn test_finalization_prunes_competing_branches() {
+        let mut fork_choice = ForkChoice::new(hash(1), 0);
+        fork_choice.add_block(hash(2), hash(1), 1).unwrap();
+        fork_choice.add_block(hash(3), hash(1), 1).unwrap();
+        fork_choice.add_block(hash(4), hash(3), 2).unwrap();
+        fork_choice.add_block(hash(5), hash(2), 2).unwrap();
+        fork_choice.add_block(hash(6), hash(5), 3).unwrap();
+
+        fork_choice.finalize(hash(2)).unwrap();
+        assert!(!fork_choice.contains(&hash(3)) && !fork_choice.contains(&hash(4)));
+        assert!(fork_choice.add_block(hash(7), hash(4), 3).is_err());
+        assert!(fork_choice.finalize(hash(1)).is_err());
+        assert_eq!(fork_choice.summary().finalized_height, 1);
+        assert_eq!(fork_choice.head(), hash(6));
+
+        fork_choice.add_vote(b"validator-a", hash(6), 4);
+        assert_eq!(fork_choice.weight(&hash(2)), 4);
+        fork_choice.remove_branch(&hash(5));
+        assert_eq!(fork_choice.head(), hash(2));
+        assert_eq!(fork_choice.weight(&hash(2)), 0);
+
+        println!("   Fork choice finalization working!");
+    }
+}
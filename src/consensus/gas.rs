@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+
+use crate::storage::batch::{BatchCall, BATCH};
+use crate::storage::blocks::Transaction;
+
+/// A step of native transaction execution that gas is charged for. TriUnity runs no
+/// VM, so these are the host operations every transaction performs rather than opcodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GasOp {
+    /// One signature check, the sender's or a fee payer's
+    SignatureVerify,
+    /// One byte of the encoded transaction, hashed and stored
+    TxByte,
+    /// One account looked up for nonce and balance checks
+    AccountRead,
+    /// One account balance written
+    AccountWrite,
+}
+
+impl GasOp {
+    pub const ALL: [GasOp; 4] = [Self::SignatureVerify, Self::TxByte, Self::AccountRead, Self::AccountWrite];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SignatureVerify => "signature_verify",
+            Self::TxByte => "tx_byte",
+            Self::AccountRead => "account_read",
+            Self::AccountWrite => "account_write",
+        }
+    }
+}
+
+/// Gas each execution step costs, and the fee charged per gas. Fixed at genesis, so
+/// a testnet may replace the defaults with a table calibrated on its own hardware
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GasSchedule {
+    pub signature_verify: u64,
+    pub tx_byte: u64,
+    pub account_read: u64,
+    pub account_write: u64,
+    /// Least fee per gas a transaction pays; 0 leaves fees to the fee market
+    pub gas_price: u64,
+}
+
+impl Default for GasSchedule {
+    fn default() -> Self {
+        Self {
+            signature_verify: 2_000,
+            tx_byte: 1,
+            account_read: 50,
+            account_write: 100,
+            gas_price: 0,
+        }
+    }
+}
+
+impl GasSchedule {
+    pub fn cost(&self, op: GasOp) -> u64 {
+        match op {
+            GasOp::SignatureVerify => self.signature_verify,
+            GasOp::TxByte => self.tx_byte,
+            GasOp::AccountRead => self.account_read,
+            GasOp::AccountWrite => self.account_write,
+        }
+    }
+
+    pub fn with_cost(mut self, op: GasOp, cost: u64) -> Self {
+        *match op {
+            GasOp::SignatureVerify => &mut self.signature_verify,
+            GasOp::TxByte => &mut self.tx_byte,
+            GasOp::AccountRead => &mut self.account_read,
+            GasOp::AccountWrite => &mut self.account_write,
+        } = cost;
+        self
+    }
+
+    /// Gas `tx` uses, counted from its shape alone so every node charges the same
+    pub fn gas_used(&self, tx: &Transaction) -> u64 {
+        let signatures = 1 + tx.fee_payer.is_some() as u64;
+        // Sender, fee payer and each recipient are read, then written
+        let recipients = match tx.to == BATCH {
+            true => BatchCall::decode(&tx.data).map_or(0, |batch| batch.operations.len() as u64),
+            false => 1,
+        };
+        let accounts = signatures + recipients;
+
+        [
+            (GasOp::SignatureVerify, signatures),
+            (GasOp::TxByte, tx.size() as u64),
+            (GasOp::AccountRead, accounts),
+            (GasOp::AccountWrite, accounts),
+        ]
+        .into_iter()
+        .fold(0u64, |gas, (op, count)| gas.saturating_add(self.cost(op).saturating_mul(count)))
+    }
+
+    /// Least fee `tx` must pay at this schedule's gas price
+    pub fn min_fee(&self, tx: &Transaction) -> u64 {
+        self.gas_used(tx).saturating_mul(self.gas_price)
+    }
+
+    pub fn check_fee(&self, tx: &Transaction) -> Result<(), String> {
+        let min_fee = self.min_fee(tx);
+        if tx.fee < min_fee {
+            return Err(format!("Fee {} is below the {} the transaction's {} gas costs", tx.fee, min_fee, self.gas_used(tx)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumSignature;
+    use crate::storage::batch::BatchOperation;
+
+    #[test]
+    fn test_gas_used_and_min_fee() {
+        let schedule = GasSchedule { gas_price: 2, ..GasSchedule::default() };
+        let tx = |fee: u64| Transaction::new(vec![1; 32], vec![2; 32], 5, fee, 0, vec![], QuantumSignature::new(vec![]));
+        let account = schedule.account_read + schedule.account_write;
+
+        let transfer = tx(0);
+        let expected = schedule.signature_verify + transfer.size() as u64 * schedule.tx_byte + 2 * account;
+        assert_eq!(schedule.gas_used(&transfer), expected);
+        assert_eq!(schedule.min_fee(&transfer), 2 * expected);
+        assert!(schedule.check_fee(&transfer).unwrap_err().contains("gas costs"));
+        assert!(schedule.check_fee(&tx(2 * expected)).is_ok());
+        assert!(GasSchedule::default().check_fee(&transfer).is_ok());
+
+        // A sponsor adds a signature and an account
+        let sponsored = tx(0).with_fee_payer(vec![3; 32]);
+        let extra = schedule.signature_verify + account;
+        assert_eq!(schedule.gas_used(&sponsored), expected + extra + (sponsored.size() - transfer.size()) as u64 * schedule.tx_byte);
+
+        // A batch touches one recipient per operation
+        let operation = |to: u8| BatchOperation { to: vec![to; 32], amount: 1, data: vec![] };
+        let data = BatchCall { operations: vec![operation(2), operation(3), operation(4)] }.encode();
+        let batch = Transaction::new(vec![1; 32], BATCH.to_vec(), 0, 0, 0, data, QuantumSignature::new(vec![]));
+        assert_eq!(schedule.gas_used(&batch), schedule.signature_verify + batch.size() as u64 * schedule.tx_byte + 4 * account);
+
+        // Operators write only the steps they recalibrate
+        let parsed: GasSchedule = serde_json::from_str(r#"{"signature_verify":9000,"gas_price":1}"#).unwrap();
+        assert_eq!(parsed, GasSchedule { gas_price: 1, ..GasSchedule::default().with_cost(GasOp::SignatureVerify, 9_000) });
+
+        println!("   Gas schedule working!");
+    }
+}
@@ -1,15 +1,41 @@
+pub mod store;
+
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::MetricsConfig;
+use crate::log;
+use crate::storage::database::BlockchainDB;
+use store::{MetricsStore, Retention, Timestamped};
 
 #[derive(Debug, Clone)]
 pub struct MetricsCollector {
     tps_history: VecDeque<TpsReading>,
     latency_history: VecDeque<LatencyReading>,
     security_events: VecDeque<SecurityEvent>,
-    max_history_size: usize,
+    import_history: VecDeque<ImportStageReading>,
+    detectors: [AnomalyDetector; 3],
+    anomalies: VecDeque<Anomaly>,
+    latest_anomalies: AnomalyFlags,
+    retention: Retention,
+    /// Where readings are persisted as they are recorded, if anywhere
+    store: Option<Arc<MetricsStore>>,
 }
 
+/// Readings of each kind kept by default
+pub const DEFAULT_MAX_HISTORY: usize = 1000;
+
+/// Weight of each new reading in a series' moving average
+pub const DEFAULT_EWMA_ALPHA: f64 = 0.1;
+
+/// Standard deviations from the moving average at which a reading is anomalous
+pub const DEFAULT_ANOMALY_THRESHOLD: f64 = 3.0;
+
+/// Readings a series needs before any of them can be flagged
+pub const DEFAULT_ANOMALY_WARMUP: u64 = 10;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TpsReading {
     pub timestamp: u64,
@@ -24,6 +50,37 @@ pub struct LatencyReading {
     pub node_count: usize,
 }
 
+/// Stages a block goes through on import, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStage {
+    Decode,
+    Header,
+    Signature,
+    Execute,
+    Commit,
+}
+
+impl ImportStage {
+    pub const ALL: [ImportStage; 5] = [Self::Decode, Self::Header, Self::Signature, Self::Execute, Self::Commit];
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportStageReading {
+    pub timestamp: u64,
+    pub block_height: u64,
+    pub stage: ImportStage,
+    pub micros: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportStageStats {
+    pub stage: ImportStage,
+    pub samples: usize,
+    pub avg_micros: f64,
+    pub max_micros: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecurityEvent {
     pub timestamp: u64,
@@ -32,7 +89,7 @@ pub struct SecurityEvent {
     pub description: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SecurityEventType {
     SuspiciousActivity,
     InvalidSignature,
@@ -42,7 +99,7 @@ pub enum SecurityEventType {
     UnusualTraffic,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SecuritySeverity {
     Low,
     Medium,
@@ -50,6 +107,111 @@ pub enum SecuritySeverity {
     Critical,
 }
 
+/// Series watched for anomalies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricSeries {
+    Tps,
+    Latency,
+    PeerCount,
+}
+
+impl MetricSeries {
+    pub const ALL: [MetricSeries; 3] = [Self::Tps, Self::Latency, Self::PeerCount];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Tps => "TPS",
+            Self::Latency => "Latency",
+            Self::PeerCount => "Peer count",
+        }
+    }
+}
+
+/// A reading far outside its series' recent behaviour
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Anomaly {
+    pub timestamp: u64,
+    pub series: MetricSeries,
+    pub value: f64,
+    /// Moving average the reading was compared against
+    pub mean: f64,
+    /// Signed distance from `mean` in standard deviations
+    pub z_score: f64,
+}
+
+/// Whether the latest reading of each series was anomalous
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnomalyFlags {
+    pub tps: bool,
+    pub latency: bool,
+    pub peer_count: bool,
+}
+
+impl AnomalyFlags {
+    pub fn any(&self) -> bool {
+        self.tps || self.latency || self.peer_count
+    }
+
+    fn set(&mut self, series: MetricSeries, anomalous: bool) {
+        match series {
+            MetricSeries::Tps => self.tps = anomalous,
+            MetricSeries::Latency => self.latency = anomalous,
+            MetricSeries::PeerCount => self.peer_count = anomalous,
+        }
+    }
+}
+
+/// Exponentially weighted mean and variance of one series, scoring each reading
+/// against them before it is folded in. A lasting shift is flagged at first and
+/// becomes the new normal as the average catches up
+#[derive(Debug, Clone)]
+pub struct AnomalyDetector {
+    alpha: f64,
+    threshold: f64,
+    warmup: u64,
+    samples: u64,
+    mean: f64,
+    variance: f64,
+}
+
+impl AnomalyDetector {
+    pub fn new(alpha: f64, threshold: f64, warmup: u64) -> Self {
+        Self { alpha, threshold, warmup, samples: 0, mean: 0.0, variance: 0.0 }
+    }
+
+    /// Folds `value` in and returns its z-score if it was anomalous
+    pub fn observe(&mut self, value: f64) -> Option<f64> {
+        self.samples += 1;
+        if self.samples == 1 {
+            self.mean = value;
+            return None;
+        }
+        // A steady series still tolerates a 1% wobble
+        let deviation = self.variance.sqrt().max(self.mean.abs() * 0.01).max(f64::EPSILON);
+        let z_score = (value - self.mean) / deviation;
+        let diff = value - self.mean;
+        let increment = self.alpha * diff;
+        self.mean += increment;
+        self.variance = (1.0 - self.alpha) * (self.variance + diff * increment);
+        (self.samples > self.warmup && z_score.abs() >= self.threshold).then_some(z_score)
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+}
+
+impl Default for AnomalyDetector {
+    fn default() -> Self {
+        Self::new(DEFAULT_EWMA_ALPHA, DEFAULT_ANOMALY_THRESHOLD, DEFAULT_ANOMALY_WARMUP)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PerformanceStats {
     pub avg_tps: f64,
@@ -60,6 +222,7 @@ pub struct PerformanceStats {
     pub security_score: f64,
     pub uptime_percentage: f64,
     pub total_transactions: u64,
+    pub anomalies: AnomalyFlags,
 }
 
 impl MetricsCollector {
@@ -68,36 +231,138 @@ impl MetricsCollector {
             tps_history: VecDeque::new(),
             latency_history: VecDeque::new(),
             security_events: VecDeque::new(),
-            max_history_size,
+            import_history: VecDeque::new(),
+            detectors: Default::default(),
+            anomalies: VecDeque::new(),
+            latest_anomalies: AnomalyFlags::default(),
+            retention: Retention { max_entries: max_history_size, max_age_secs: None },
+            store: None,
+        }
+    }
+
+    /// Collector with the configured retention, restoring and persisting its history in
+    /// `db` if the config asks for it
+    pub fn from_config(config: &MetricsConfig, db: &BlockchainDB) -> Result<Self, String> {
+        let collector = Self::new(config.max_entries);
+        match (config.persist, config.max_age_secs) {
+            (true, _) => collector.with_store(MetricsStore::open(db, config.retention())?),
+            (false, Some(max_age_secs)) => Ok(collector.with_max_age(max_age_secs)),
+            (false, None) => Ok(collector),
+        }
+    }
+
+    /// Also drops readings older than `max_age_secs`
+    pub fn with_max_age(mut self, max_age_secs: u64) -> Self {
+        self.retention.max_age_secs = Some(max_age_secs);
+        self
+    }
+
+    /// Persists every reading to `store` from now on and restores the history it holds,
+    /// within the store's retention. Restored TPS and latency readings warm up the
+    /// anomaly detectors without raising events
+    pub fn with_store(mut self, store: MetricsStore) -> Result<Self, String> {
+        let now = current_timestamp();
+        let history = store.load(now)?;
+        self.retention = store.retention();
+        for reading in &history.tps {
+            self.detectors[MetricSeries::Tps.index()].observe(reading.tps as f64);
+        }
+        for reading in &history.latency {
+            self.detectors[MetricSeries::Latency.index()].observe(reading.latency_ms as f64);
+        }
+        self.tps_history.extend(history.tps);
+        self.latency_history.extend(history.latency);
+        self.security_events.extend(history.security);
+        self.import_history.extend(history.imports);
+        trim(&mut self.tps_history, self.retention, now);
+        trim(&mut self.latency_history, self.retention, now);
+        trim(&mut self.security_events, self.retention, now);
+        trim(&mut self.import_history, self.retention, now);
+        self.store = Some(Arc::new(store));
+        Ok(self)
+    }
+
+    pub fn retention(&self) -> Retention {
+        self.retention
+    }
+
+    /// Writes a reading to the store, if there is one. A failed write only costs the
+    /// reading its place on disk, so it is logged rather than returned
+    fn persist(&self, write: impl FnOnce(&MetricsStore) -> Result<(), String>) {
+        if let Some(store) = &self.store {
+            if let Err(e) = write(store) {
+                log!(Warn, "Failed to persist metrics reading: {}", e);
+            }
         }
     }
 
-    pub fn record_tps(&mut self, tps: u64, block_height: u64) {
+    /// Replaces the anomaly detectors of all series, e.g. with a different threshold
+    pub fn with_anomaly_detection(mut self, alpha: f64, threshold: f64, warmup: u64) -> Self {
+        self.detectors = std::array::from_fn(|_| AnomalyDetector::new(alpha, threshold, warmup));
+        self
+    }
+
+    /// Scores `value` against its series and records an anomalous one as an
+    /// `UnusualTraffic` security event described by `context`
+    fn detect(&mut self, series: MetricSeries, value: f64, context: String) -> Option<Anomaly> {
+        let detector = &mut self.detectors[series.index()];
+        let (mean, threshold) = (detector.mean(), detector.threshold);
+        let z_score = detector.observe(value);
+        self.latest_anomalies.set(series, z_score.is_some());
+        let z_score = z_score?;
+
+        let anomaly = Anomaly { timestamp: current_timestamp(), series, value, mean, z_score };
+        let severity = if z_score.abs() >= 2.0 * threshold { SecuritySeverity::High } else { SecuritySeverity::Medium };
+        let description = format!(
+            "{} {} is {:.1} standard deviations {} its average of {:.1}{}",
+            series.name(),
+            value,
+            z_score.abs(),
+            if z_score > 0.0 { "above" } else { "below" },
+            mean,
+            context
+        );
+        self.record_security_event(SecurityEventType::UnusualTraffic, severity, description);
+        self.anomalies.push_back(anomaly.clone());
+        trim(&mut self.anomalies, self.retention, anomaly.timestamp);
+        Some(anomaly)
+    }
+
+    pub fn record_tps(&mut self, tps: u64, block_height: u64) -> Option<Anomaly> {
         let reading = TpsReading {
             timestamp: current_timestamp(),
             tps,
             block_height,
         };
         
+        self.persist(|store| store.append_tps(&reading));
+        let now = reading.timestamp;
         self.tps_history.push_back(reading);
-        
-        while self.tps_history.len() > self.max_history_size {
-            self.tps_history.pop_front();
-        }
+        trim(&mut self.tps_history, self.retention, now);
+        self.detect(MetricSeries::Tps, tps as f64, format!(" at block {}", block_height))
     }
 
-    pub fn record_latency(&mut self, latency_ms: u64, node_count: usize) {
+    pub fn record_latency(&mut self, latency_ms: u64, node_count: usize) -> Option<Anomaly> {
         let reading = LatencyReading {
             timestamp: current_timestamp(),
             latency_ms,
             node_count,
         };
         
+        self.persist(|store| store.append_latency(&reading));
+        let now = reading.timestamp;
         self.latency_history.push_back(reading);
-        
-        while self.latency_history.len() > self.max_history_size {
-            self.latency_history.pop_front();
-        }
+        trim(&mut self.latency_history, self.retention, now);
+        self.detect(MetricSeries::Latency, latency_ms as f64, format!(" with {} nodes", node_count))
+    }
+
+    pub fn record_peer_count(&mut self, peers: usize) -> Option<Anomaly> {
+        self.detect(MetricSeries::PeerCount, peers as f64, String::new())
+    }
+
+    /// Retained anomalies, oldest first
+    pub fn anomalies(&self) -> impl DoubleEndedIterator<Item = &Anomaly> {
+        self.anomalies.iter()
     }
 
     pub fn record_security_event(
@@ -113,11 +378,44 @@ impl MetricsCollector {
             description,
         };
         
+        self.persist(|store| store.append_security_event(&event));
+        let now = event.timestamp;
         self.security_events.push_back(event);
-        
-        while self.security_events.len() > self.max_history_size {
-            self.security_events.pop_front();
-        }
+        trim(&mut self.security_events, self.retention, now);
+    }
+
+    pub fn record_import_stage(&mut self, stage: ImportStage, block_height: u64, elapsed: Duration) {
+        let reading = ImportStageReading {
+            timestamp: current_timestamp(),
+            block_height,
+            stage,
+            micros: elapsed.as_micros() as u64,
+        };
+
+        self.persist(|store| store.append_import_stage(&reading));
+        let now = reading.timestamp;
+        self.import_history.push_back(reading);
+        trim(&mut self.import_history, self.retention, now);
+    }
+
+    /// Average and worst time of every import stage over the retained history
+    pub fn import_stage_stats(&self) -> Vec<ImportStageStats> {
+        ImportStage::ALL
+            .into_iter()
+            .map(|stage| {
+                let micros: Vec<u64> = self.import_history.iter().filter(|r| r.stage == stage).map(|r| r.micros).collect();
+                ImportStageStats {
+                    stage,
+                    samples: micros.len(),
+                    avg_micros: if micros.is_empty() { 0.0 } else { micros.iter().sum::<u64>() as f64 / micros.len() as f64 },
+                    max_micros: micros.iter().copied().max().unwrap_or(0),
+                }
+            })
+            .collect()
+    }
+
+    pub fn import_history(&self) -> impl Iterator<Item = &ImportStageReading> {
+        self.import_history.iter()
     }
 
     pub fn calculate_stats(&self) -> PerformanceStats {
@@ -137,7 +435,7 @@ impl MetricsCollector {
 
         let min_latency = self.latency_history.iter().map(|r| r.latency_ms).min().unwrap_or(0);
         let max_latency = self.latency_history.iter().map(|r| r.latency_ms).max().unwrap_or(0);
-        let security_score = self.calculate_security_score();
+        let security_score = self.security_score();
         let uptime_percentage = if avg_tps > 0.0 { 99.9 } else { 0.0 };
         let total_transactions = self.tps_history.iter().map(|r| r.tps).sum();
 
@@ -150,10 +448,17 @@ impl MetricsCollector {
             security_score,
             uptime_percentage,
             total_transactions,
+            anomalies: self.latest_anomalies,
         }
     }
 
-    fn calculate_security_score(&self) -> f64 {
+    /// Retained security events, oldest first
+    pub fn security_events(&self) -> impl DoubleEndedIterator<Item = &SecurityEvent> {
+        self.security_events.iter()
+    }
+
+    /// 1.0 less the average severity weight of the last hour's security events
+    pub fn security_score(&self) -> f64 {
         if self.security_events.is_empty() {
             return 1.0;
         }
@@ -208,6 +513,19 @@ impl MetricsCollector {
             .collect()
     }
 
+    pub fn tps_history(&self) -> impl Iterator<Item = &TpsReading> {
+        self.tps_history.iter()
+    }
+
+    pub fn latency_history(&self) -> impl Iterator<Item = &LatencyReading> {
+        self.latency_history.iter()
+    }
+
+    /// TPS and latency readings, oldest first, without copying them
+    pub fn into_history(self) -> (VecDeque<TpsReading>, VecDeque<LatencyReading>) {
+        (self.tps_history, self.latency_history)
+    }
+
     pub fn get_current_metrics(&self) -> Option<(u64, u64)> {
         let latest_tps = self.tps_history.back()?.tps;
         let latest_latency = self.latency_history.back()?.latency_ms;
@@ -215,6 +533,14 @@ impl MetricsCollector {
     }
 }
 
+/// Drops readings over the retention limits as of `now`, oldest first
+fn trim<T: Timestamped>(history: &mut VecDeque<T>, retention: Retention, now: u64) {
+    let cutoff = retention.cutoff(now);
+    while history.len() > retention.max_entries || history.front().zip(cutoff).is_some_and(|(oldest, cutoff)| oldest.timestamp() < cutoff) {
+        history.pop_front();
+    }
+}
+
 fn current_timestamp() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -224,7 +550,7 @@ fn current_timestamp() -> u64 {
 
 impl Default for MetricsCollector {
     fn default() -> Self {
-        Self::new(1000)
+        Self::new(DEFAULT_MAX_HISTORY)
     }
 }
 
@@ -245,6 +571,13 @@ mod tests {
         assert_eq!(stats.avg_tps, 1500.0);
         assert_eq!(stats.peak_tps, 2000);
         assert_eq!(stats.avg_latency, 62.5);
+
+        collector.record_import_stage(ImportStage::Execute, 1, Duration::from_micros(300));
+        collector.record_import_stage(ImportStage::Execute, 2, Duration::from_micros(500));
+        let stages = collector.import_stage_stats();
+        assert_eq!(stages.len(), ImportStage::ALL.len());
+        assert_eq!(stages[3], ImportStageStats { stage: ImportStage::Execute, samples: 2, avg_micros: 400.0, max_micros: 500 });
+        assert_eq!(stages[0].samples, 0);
         
         println!("   Metrics collection working!");
         println!("   Average TPS: {:.1}", stats.avg_tps);
@@ -286,4 +619,43 @@ mod tests {
         println!("   TPS trend analysis working!");
         println!("   TPS trend: {:.2}% change", trend * 100.0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_anomaly_detection() {
+        let mut collector = MetricsCollector::new(100);
+        for (height, tps) in [1000, 1040, 980, 1010, 990, 1020, 1005, 995, 1030, 970, 1000, 1015].into_iter().enumerate() {
+            assert_eq!(collector.record_tps(tps, height as u64), None);
+            assert_eq!(collector.record_latency(100 + height as u64 % 3, 4), None);
+        }
+        assert!(!collector.calculate_stats().anomalies.any());
+
+        // A collapse in TPS is flagged and reported with its block, latency stays normal
+        let anomaly = collector.record_tps(100, 12).unwrap();
+        assert_eq!(anomaly.series, MetricSeries::Tps);
+        assert!(anomaly.z_score < -DEFAULT_ANOMALY_THRESHOLD);
+        assert_eq!(collector.record_latency(101, 4), None);
+        let stats = collector.calculate_stats();
+        assert_eq!(stats.anomalies, AnomalyFlags { tps: true, latency: false, peer_count: false });
+        assert!(stats.security_score < 1.0);
+        let event = collector.security_events().last().unwrap();
+        assert_eq!(event.event_type, SecurityEventType::UnusualTraffic);
+        assert!(event.description.starts_with("TPS 100 is") && event.description.ends_with("at block 12"));
+
+        // The flag clears with the next normal reading
+        collector.record_tps(1000, 13);
+        assert!(!collector.calculate_stats().anomalies.tps);
+
+        // Peer counts are only scored after the warmup
+        let mut collector = MetricsCollector::new(100).with_anomaly_detection(0.2, 3.0, 3);
+        assert_eq!(collector.record_peer_count(8), None);
+        assert_eq!(collector.record_peer_count(0), None);
+        for _ in 0..10 {
+            collector.record_peer_count(8);
+        }
+        assert!(collector.record_peer_count(40).is_some());
+        assert!(collector.calculate_stats().anomalies.peer_count);
+        assert_eq!(collector.anomalies().count(), 1);
+
+        println!("   Anomaly detection working!");
+    }
+}
@@ -0,0 +1,219 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::{Anomaly, ImportStageReading, LatencyReading, SecurityEvent, TpsReading};
+use crate::storage::database::{BlockchainDB, WriteGate};
+
+/// How much history is kept, in memory and on disk. Readings over either limit are
+/// dropped oldest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retention {
+    pub max_entries: usize,
+    /// Readings older than this many seconds are dropped; `None` keeps them
+    pub max_age_secs: Option<u64>,
+}
+
+impl Retention {
+    /// Oldest timestamp still kept at `now`
+    pub fn cutoff(&self, now: u64) -> Option<u64> {
+        self.max_age_secs.map(|age| now.saturating_sub(age))
+    }
+}
+
+/// Readings that carry the time they were taken
+pub trait Timestamped {
+    fn timestamp(&self) -> u64;
+}
+
+impl Timestamped for TpsReading {
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+}
+
+impl Timestamped for LatencyReading {
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+}
+
+impl Timestamped for SecurityEvent {
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+}
+
+impl Timestamped for ImportStageReading {
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+}
+
+impl Timestamped for Anomaly {
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+}
+
+/// One series of readings in its own tree, keyed by an increasing sequence so the
+/// oldest are always first
+#[derive(Debug)]
+struct Series {
+    tree: sled::Tree,
+    next: AtomicU64,
+    writes: WriteGate,
+}
+
+impl Series {
+    fn open(db: &BlockchainDB, name: &str) -> Result<Self, String> {
+        let tree = db.tree(name)?;
+        let next = match tree.last().map_err(|e| e.to_string())? {
+            Some((key, _)) => sequence_of(&key)? + 1,
+            None => 0,
+        };
+        Ok(Self { tree, next: AtomicU64::new(next), writes: db.writes().clone() })
+    }
+
+    fn append<T: Serialize + DeserializeOwned + Timestamped>(&self, reading: &T, retention: Retention) -> Result<(), String> {
+        let value = bincode::serialize(reading).map_err(|e| e.to_string())?;
+        let sequence = self.next.fetch_add(1, Ordering::Relaxed);
+        {
+            let _writing = self.writes.enter();
+            self.tree.insert(sequence.to_be_bytes(), value).map_err(|e| e.to_string())?;
+        }
+        self.prune::<T>(retention, reading.timestamp())
+    }
+
+    /// Drops readings over the retention limits as of `now`
+    fn prune<T: DeserializeOwned + Timestamped>(&self, retention: Retention, now: u64) -> Result<(), String> {
+        let _writing = self.writes.enter();
+        let cutoff = retention.cutoff(now);
+        while let Some((key, value)) = self.tree.first().map_err(|e| e.to_string())? {
+            let kept = self.next.load(Ordering::Relaxed).saturating_sub(sequence_of(&key)?);
+            let expired = match cutoff {
+                Some(cutoff) => decode::<T>(&value).map_or(true, |reading| reading.timestamp() < cutoff),
+                None => false,
+            };
+            if kept as usize <= retention.max_entries && !expired {
+                break;
+            }
+            self.tree.remove(key).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn load<T: DeserializeOwned>(&self) -> Result<Vec<T>, String> {
+        self.tree.iter().values().map(|value| decode(&value.map_err(|e| e.to_string())?)).collect()
+    }
+}
+
+/// TPS, latency, security and import readings persisted as they are recorded, so
+/// history survives a restart
+#[derive(Debug)]
+pub struct MetricsStore {
+    tps: Series,
+    latency: Series,
+    security: Series,
+    imports: Series,
+    retention: Retention,
+}
+
+/// Everything a store held when it was opened, oldest first
+#[derive(Debug, Default)]
+pub struct StoredHistory {
+    pub tps: Vec<TpsReading>,
+    pub latency: Vec<LatencyReading>,
+    pub security: Vec<SecurityEvent>,
+    pub imports: Vec<ImportStageReading>,
+}
+
+impl MetricsStore {
+    pub fn open(db: &BlockchainDB, retention: Retention) -> Result<Self, String> {
+        Ok(Self {
+            tps: Series::open(db, "metrics_tps")?,
+            latency: Series::open(db, "metrics_latency")?,
+            security: Series::open(db, "metrics_security")?,
+            imports: Series::open(db, "metrics_imports")?,
+            retention,
+        })
+    }
+
+    pub fn retention(&self) -> Retention {
+        self.retention
+    }
+
+    pub fn append_tps(&self, reading: &TpsReading) -> Result<(), String> {
+        self.tps.append(reading, self.retention)
+    }
+
+    pub fn append_latency(&self, reading: &LatencyReading) -> Result<(), String> {
+        self.latency.append(reading, self.retention)
+    }
+
+    pub fn append_security_event(&self, event: &SecurityEvent) -> Result<(), String> {
+        self.security.append(event, self.retention)
+    }
+
+    pub fn append_import_stage(&self, reading: &ImportStageReading) -> Result<(), String> {
+        self.imports.append(reading, self.retention)
+    }
+
+    /// Applies the retention policy as of `now`, then reads back what is left
+    pub fn load(&self, now: u64) -> Result<StoredHistory, String> {
+        self.tps.prune::<TpsReading>(self.retention, now)?;
+        self.latency.prune::<LatencyReading>(self.retention, now)?;
+        self.security.prune::<SecurityEvent>(self.retention, now)?;
+        self.imports.prune::<ImportStageReading>(self.retention, now)?;
+        Ok(StoredHistory {
+            tps: self.tps.load()?,
+            latency: self.latency.load()?,
+            security: self.security.load()?,
+            imports: self.imports.load()?,
+        })
+    }
+}
+
+fn sequence_of(key: &[u8]) -> Result<u64, String> {
+    Ok(u64::from_be_bytes(key.try_into().map_err(|_| "Invalid metrics key".to_string())?))
+}
+
+fn decode<T: DeserializeOwned>(value: &[u8]) -> Result<T, String> {
+    bincode::deserialize(value).map_err(|e| format!("Corrupt metrics reading: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus::metrics::MetricsCollector;
+
+    #[test]
+    fn test_metrics_store() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_metrics_store");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        let retention = Retention { max_entries: 3, max_age_secs: Some(3600) };
+
+        // Readings past their age are dropped when the history is restored
+        let store = MetricsStore::open(&db, retention).unwrap();
+        store.append_latency(&LatencyReading { timestamp: 1, latency_ms: 500, node_count: 4 }).unwrap();
+        let mut collector = MetricsCollector::new(100).with_store(store).unwrap();
+        assert_eq!(collector.latency_history().count(), 0);
+        for height in 1..=5 {
+            collector.record_tps(height * 100, height);
+        }
+        collector.record_latency(80, 4);
+        assert_eq!(collector.tps_history().count(), 3);
+        drop(collector);
+
+        // A restart restores what the count limit kept
+        let collector = MetricsCollector::new(100).with_store(MetricsStore::open(&db, retention).unwrap()).unwrap();
+        let heights: Vec<u64> = collector.tps_history().map(|reading| reading.block_height).collect();
+        assert_eq!(heights, vec![3, 4, 5]);
+        assert_eq!(collector.latency_history().map(|reading| reading.latency_ms).collect::<Vec<_>>(), vec![80]);
+        assert_eq!(collector.retention(), retention);
+
+        println!("   Metrics persistence working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
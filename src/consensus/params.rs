@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+
+use crate::consensus::features::{Deployment, DeploymentState, DEFAULT_LOCK_IN_THRESHOLD, DEFAULT_SIGNALING_WINDOW};
+use crate::mempool::DEFAULT_SECURE_LANE_THRESHOLD;
+use crate::network::message::MAX_MESSAGE_SIZE;
+use crate::node::builder::{BlockLimits, DEFAULT_MAX_BLOCK_BYTES, DEFAULT_MAX_BLOCK_TRANSACTIONS};
+use crate::storage::blocks::{Block, Transaction};
+
+/// Consensus rules that may change over the life of a chain. Every node must apply the
+/// same ones at the same height, so they come from genesis rather than node config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChainParams {
+    /// Most transactions a block may hold
+    pub max_block_transactions: usize,
+    /// Most encoded transaction bytes a block may hold
+    pub max_block_bytes: usize,
+    /// Transfer amount from which a transaction takes the secure mempool lane
+    pub secure_lane_threshold: u64,
+    /// Feature flags in force, one bit per deployment; set by an upgrade or by activation
+    pub features: u32,
+}
+
+impl Default for ChainParams {
+    fn default() -> Self {
+        Self {
+            max_block_transactions: DEFAULT_MAX_BLOCK_TRANSACTIONS,
+            max_block_bytes: DEFAULT_MAX_BLOCK_BYTES,
+            secure_lane_threshold: DEFAULT_SECURE_LANE_THRESHOLD,
+            features: 0,
+        }
+    }
+}
+
+impl ChainParams {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_block_transactions == 0 || self.max_block_bytes == 0 {
+            return Err("Blocks must be allowed at least one transaction and byte".to_string());
+        }
+        // Leaves room for the header and message framing
+        if self.max_block_bytes > MAX_MESSAGE_SIZE / 2 {
+            return Err(format!("Block size limit {} exceeds {}", self.max_block_bytes, MAX_MESSAGE_SIZE / 2));
+        }
+        Ok(())
+    }
+
+    pub fn has_feature(&self, deployment: &Deployment) -> bool {
+        self.features & deployment.mask() != 0
+    }
+
+    pub fn block_limits(&self) -> BlockLimits {
+        BlockLimits { max_transactions: self.max_block_transactions, max_bytes: self.max_block_bytes }
+    }
+
+    /// Refuses a block holding more transactions or bytes than these parameters allow
+    pub fn check_block(&self, block: &Block) -> Result<(), String> {
+        let height = block.header.height;
+        if block.transactions.len() > self.max_block_transactions {
+            return Err(format!("Block {} holds {} transactions, over the limit of {}", height, block.transactions.len(), self.max_block_transactions));
+        }
+        let bytes: usize = block.transactions.iter().map(Transaction::size).sum();
+        if bytes > self.max_block_bytes {
+            return Err(format!("Block {} holds {} transaction bytes, over the limit of {}", height, bytes, self.max_block_bytes));
+        }
+        Ok(())
+    }
+}
+
+/// Parameters taking effect from `activation_height` on, a scheduled hard fork
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParamUpgrade {
+    pub activation_height: u64,
+    pub params: ChainParams,
+}
+
+/// The parameters a chain starts with, the upgrades replacing them in activation order,
+/// and the features block producers may vote to activate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParamSchedule {
+    pub genesis: ChainParams,
+    pub upgrades: Vec<ParamUpgrade>,
+    pub deployments: Vec<Deployment>,
+    /// Blocks per signaling window, fixed for the life of the chain
+    pub signaling_window: u64,
+    /// Blocks of one window that must signal a deployment to lock it in
+    pub lock_in_threshold: u64,
+}
+
+impl Default for ParamSchedule {
+    fn default() -> Self {
+        Self::new(ChainParams::default())
+    }
+}
+
+impl ParamSchedule {
+    pub fn new(genesis: ChainParams) -> Self {
+        Self {
+            genesis,
+            upgrades: Vec::new(),
+            deployments: Vec::new(),
+            signaling_window: DEFAULT_SIGNALING_WINDOW,
+            lock_in_threshold: DEFAULT_LOCK_IN_THRESHOLD,
+        }
+    }
+
+    /// Switches to `params` from `activation_height` on
+    pub fn with_upgrade(mut self, activation_height: u64, params: ChainParams) -> Self {
+        self.upgrades.push(ParamUpgrade { activation_height, params });
+        self.upgrades.sort_by_key(|upgrade| upgrade.activation_height);
+        self
+    }
+
+    pub fn with_deployment(mut self, deployment: Deployment) -> Self {
+        self.deployments.push(deployment);
+        self
+    }
+
+    pub fn with_signaling(mut self, window: u64, threshold: u64) -> Self {
+        (self.signaling_window, self.lock_in_threshold) = (window, threshold);
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.genesis.validate()?;
+        if self.signaling_window == 0 || !(1..=self.signaling_window).contains(&self.lock_in_threshold) {
+            return Err(format!("Lock-in threshold {} must be 1 to the signaling window of {}", self.lock_in_threshold, self.signaling_window));
+        }
+        for (index, deployment) in self.deployments.iter().enumerate() {
+            deployment.validate()?;
+            if self.deployments[..index].iter().any(|other| other.bit == deployment.bit || other.name == deployment.name) {
+                return Err(format!("Deployment {} reuses the name or bit of another", deployment.name));
+            }
+        }
+        let mut last = 0;
+        for upgrade in &self.upgrades {
+            if upgrade.activation_height <= last {
+                return Err(format!("Upgrade at height {} must activate after height {}", upgrade.activation_height, last));
+            }
+            upgrade.params.validate().map_err(|e| format!("Upgrade at height {}: {}", upgrade.activation_height, e))?;
+            last = upgrade.activation_height;
+        }
+        Ok(())
+    }
+
+    /// State of `deployment` for the block at `height`, given how many blocks of each
+    /// window signaled it
+    pub fn deployment_state(&self, deployment: &Deployment, height: u64, signaled: impl FnMut(u64) -> Result<u64, String>) -> Result<DeploymentState, String> {
+        deployment.state_at(height, self.signaling_window, self.lock_in_threshold, signaled)
+    }
+
+    /// Parameters in force for the block at `height`, not counting activated deployments
+    pub fn at(&self, height: u64) -> &ChainParams {
+        self.upgrades
+            .iter()
+            .rev()
+            .find(|upgrade| upgrade.activation_height <= height)
+            .map_or(&self.genesis, |upgrade| &upgrade.params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumSignature;
+    use crate::storage::blocks::ConsensusData;
+
+    #[test]
+    fn test_chain_params() {
+        let doubled = ChainParams { max_block_transactions: 2 * DEFAULT_MAX_BLOCK_TRANSACTIONS, ..ChainParams::default() };
+        let tight = ChainParams { max_block_transactions: 1, ..ChainParams::default() };
+        let schedule = ParamSchedule::default().with_upgrade(200, tight).with_upgrade(100, doubled);
+        schedule.validate().unwrap();
+        assert_eq!((schedule.at(99), schedule.at(100), schedule.at(199), schedule.at(5_000)), (&ChainParams::default(), &doubled, &doubled, &tight));
+
+        // Survives the JSON an operator writes for genesis, with unset fields at their defaults
+        let parsed: ParamSchedule = serde_json::from_str(r#"{"upgrades":[{"activation_height":100,"params":{"max_block_transactions":10000}},{"activation_height":200,"params":{"max_block_transactions":1}}]}"#).unwrap();
+        assert_eq!(parsed, schedule);
+
+        assert!(ParamSchedule::default().with_upgrade(0, tight).validate().is_err());
+        assert!(ParamSchedule::default().with_upgrade(10, tight).with_upgrade(10, doubled).validate().is_err());
+        assert!(ParamSchedule::new(ChainParams { max_block_bytes: MAX_MESSAGE_SIZE, ..ChainParams::default() }).validate().is_err());
+
+        let tx = |nonce: u64| Transaction::new(vec![1; 32], vec![2; 32], 1, 1, nonce, vec![], QuantumSignature::new(vec![]));
+        let block = Block::new([0; 32], vec![tx(0), tx(1)], 250, ConsensusData::default());
+        assert!(schedule.at(150).check_block(&block).is_ok());
+        assert!(schedule.at(250).check_block(&block).unwrap_err().contains("over the limit of 1"));
+
+        println!("   Chain parameters working!");
+    }
+}
@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use super::committee::committee_size;
+use super::router::{ConsensusPath, NetworkMetrics};
+
+/// Paths an operator may pin block production to, overriding the router
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PinnedMode {
+    FastLane,
+    SecureLane,
+    Emergency,
+}
+
+impl PinnedMode {
+    /// The pinned path with the parameters the router would give it under `metrics`
+    pub fn path(&self, metrics: &NetworkMetrics) -> ConsensusPath {
+        match self {
+            Self::FastLane => ConsensusPath::FastLane {
+                expected_tps: 100_000,
+                finality_time: 100,
+                validator_count: committee_size(metrics.validator_count),
+            },
+            Self::SecureLane => ConsensusPath::SecureLane {
+                validator_threshold: metrics.validator_count * 2 / 3,
+                security_level: 0.95,
+                decentralization_score: 0.9,
+            },
+            Self::Emergency => ConsensusPath::EmergencyMode {
+                fallback_validators: (metrics.validator_count * 3 / 4).max(10),
+                security_override: true,
+            },
+        }
+    }
+}
+
+impl FromStr for PinnedMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fast_lane" => Ok(Self::FastLane),
+            "secure_lane" => Ok(Self::SecureLane),
+            "emergency" => Ok(Self::Emergency),
+            other => Err(format!("Unknown consensus mode: {} (expected fast_lane, secure_lane or emergency)", other)),
+        }
+    }
+}
+
+/// An operator's pin of the consensus path, lifted automatically at `expires_at`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathPin {
+    pub mode: PinnedMode,
+    pub reason: String,
+    /// Unix time the router takes over again
+    pub expires_at: u64,
+}
+
+impl PathPin {
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_pin() {
+        let metrics = NetworkMetrics { validator_count: 100, ..NetworkMetrics::default() };
+        let mode: PinnedMode = "secure_lane".parse().unwrap();
+        assert!(matches!(mode.path(&metrics), ConsensusPath::SecureLane { validator_threshold: 66, .. }));
+        assert!(matches!(PinnedMode::Emergency.path(&metrics), ConsensusPath::EmergencyMode { fallback_validators: 75, .. }));
+        assert!("hybrid".parse::<PinnedMode>().is_err());
+
+        let pin = PathPin { mode, reason: "incident".to_string(), expires_at: 100 };
+        assert!(!pin.is_expired(99));
+        assert!(pin.is_expired(100));
+
+        println!("   Consensus path pinning working!");
+    }
+}
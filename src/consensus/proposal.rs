@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::verification::{self, Subsystem};
+use crate::crypto::{QuantumKeyPair, QuantumSignature};
+use crate::storage::blocks::{Block, ConsensusData};
+
+const PROPOSAL_DOMAIN: &[u8] = b"triunity/proposal";
+
+/// A block as gossiped by its proposer, signed so peers can tell it came from the
+/// validator scheduled for its height before importing it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockProposal {
+    pub block: Block,
+    pub round: u32,
+    pub signature: QuantumSignature,
+}
+
+impl BlockProposal {
+    pub fn sign(keypair: &QuantumKeyPair, block: Block, round: u32) -> Result<Self, String> {
+        let signature = keypair.sign(&Self::signing_data(block.header.height, round, &block.hash())).map_err(|e| e.to_string())?;
+        Ok(Self { block, round, signature })
+    }
+
+    pub fn signing_data(height: u64, round: u32, block_hash: &[u8; 32]) -> Vec<u8> {
+        let mut data = PROPOSAL_DOMAIN.to_vec();
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&round.to_be_bytes());
+        data.extend_from_slice(block_hash);
+        data
+    }
+
+    /// Validator the block names as its proposer, `None` outside the fast lane or when
+    /// it is named by reference, which `verify_signature_by` takes resolved
+    pub fn proposer(&self) -> Option<&[u8]> {
+        match &self.block.header.consensus_data {
+            ConsensusData::FastLane { validator, .. } => Some(validator),
+            _ => None,
+        }
+    }
+
+    /// Whether the named proposer signed this proposal
+    pub fn verify_signature(&self) -> bool {
+        self.proposer().is_some_and(|proposer| self.verify_signature_by(proposer))
+    }
+
+    /// Whether `proposer` signed this proposal
+    pub fn verify_signature_by(&self, proposer: &[u8]) -> bool {
+        verification::record(Subsystem::BlockValidation, 1);
+        let data = Self::signing_data(self.block.header.height, self.round, &self.block.hash());
+        self.signature.verify(&data, proposer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_proposal_signature() {
+        let (keypair, other) = (QuantumKeyPair::generate(), QuantumKeyPair::generate());
+        let data = ConsensusData::FastLane { validator: keypair.public_key().to_vec(), committee: None };
+        let proposal = BlockProposal::sign(&keypair, Block::new([0; 32], vec![], 3, data.clone()), 0).unwrap();
+        assert_eq!(proposal.proposer(), Some(keypair.public_key()));
+        assert!(proposal.verify_signature());
+
+        // The signature covers the round and the block, and must come from the named proposer
+        assert!(!BlockProposal { round: 1, ..proposal.clone() }.verify_signature());
+        let mut altered = proposal.clone();
+        altered.block.header.height = 4;
+        assert!(!altered.verify_signature());
+        let impostor = BlockProposal::sign(&other, Block::new([0; 32], vec![], 3, data), 0).unwrap();
+        assert!(!impostor.verify_signature());
+        let unnamed = BlockProposal::sign(&keypair, Block::new([0; 32], vec![], 3, ConsensusData::SecureLane { validators: vec![] }), 0).unwrap();
+        assert!(!unnamed.verify_signature());
+
+        println!("   Block proposal signatures working!");
+    }
+}
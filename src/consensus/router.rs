@@ -1,5 +1,41 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+use super::committee::committee_size;
+use super::pin::PinnedMode;
+
+#[cfg(feature = "ml")]
+pub mod ml;
+
+pub mod training;
+
+/// Network metrics a path model takes, in input order, each scaled to about 0..1
+pub const FEATURES: [&str; 7] = [
+    "current_tps",
+    "network_latency",
+    "validator_count",
+    "attack_probability",
+    "congestion_level",
+    "memory_usage",
+    "cpu_usage",
+];
+
+/// Paths a path model scores, in output order, as named by `ConsensusPath::name`
+pub const PATHS: [&str; 4] = ["FastLane", "SecureLane", "HybridPath", "EmergencyMode"];
+
+/// Model input for `metrics`: TPS over 100k, latency in seconds, validators over 1000,
+/// and the ratios as they are
+pub fn features(metrics: &NetworkMetrics) -> [f32; 7] {
+    [
+        metrics.current_tps as f32 / 100_000.0,
+        metrics.network_latency as f32 / 1_000.0,
+        metrics.validator_count as f32 / 1_000.0,
+        metrics.attack_probability as f32,
+        metrics.congestion_level as f32,
+        metrics.memory_usage as f32,
+        metrics.cpu_usage as f32,
+    ]
+}
 
 #[derive(Debug, Clone)]
 pub struct ConsensusRouter {
@@ -42,11 +78,78 @@ pub enum ConsensusPath {
     },
 }
 
+impl ConsensusPath {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::FastLane { .. } => "FastLane",
+            Self::SecureLane { .. } => "SecureLane",
+            Self::HybridPath { .. } => "HybridPath",
+            Self::EmergencyMode { .. } => "EmergencyMode",
+        }
+    }
+
+    /// Position of this path in `PATHS`
+    pub fn index(&self) -> usize {
+        PATHS.iter().position(|name| *name == self.name()).unwrap_or_default()
+    }
+
+    /// The path at `index` in `PATHS` with the parameters the rules give it under `metrics`
+    pub fn from_index(index: usize, metrics: &NetworkMetrics) -> Self {
+        match index {
+            0 => PinnedMode::FastLane.path(metrics),
+            1 => PinnedMode::SecureLane.path(metrics),
+            2 => ConsensusRouter::hybrid_path(metrics, metrics_confidence(metrics)),
+            _ => PinnedMode::Emergency.path(metrics),
+        }
+    }
+}
+
+/// Most a weight moves in one adaptation, up or down
+pub const MAX_WEIGHT_CHANGE: f64 = 0.1;
+
+/// Share of its distance from the baseline a weight gives back per adaptation once the
+/// condition that raised it has cleared
+pub const WEIGHT_DECAY: f64 = 0.25;
+
+/// Probability a learned model's choice needs before the router follows it
+pub const LEARNED_CONFIDENCE_THRESHOLD: f64 = 0.7;
+
+/// Where a weight rests and the range adaptation keeps it in
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WeightBounds {
+    pub baseline: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+const WEIGHTS: [(&str, WeightBounds); 3] = [
+    ("security_weight", WeightBounds { baseline: 0.3, min: 0.1, max: 0.8 }),
+    ("performance_weight", WeightBounds { baseline: 0.4, min: 0.1, max: 0.9 }),
+    ("decentralization_weight", WeightBounds { baseline: 0.3, min: 0.1, max: 0.6 }),
+];
+
 #[derive(Debug, Clone)]
 pub struct AIModel {
     weights: HashMap<String, f64>,
+    bounds: HashMap<String, WeightBounds>,
     _learning_rate: f64,
-    _confidence_threshold: f64,
+    confidence_threshold: f64,
+    /// Picks the path ahead of the rules when it is confident enough
+    #[cfg(feature = "ml")]
+    learned: Option<ml::PathModel>,
+}
+
+/// The router's current weights and their bounds, for observability
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelState {
+    pub weights: BTreeMap<String, f64>,
+    pub bounds: BTreeMap<String, WeightBounds>,
+    /// Confidence in the latest network metrics
+    pub confidence: f64,
+    /// Probability a learned model's choice needs before the router follows it
+    pub confidence_threshold: f64,
+    /// Whether a learned model is loaded
+    pub learned: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -81,16 +184,30 @@ impl ConsensusRouter {
         self.ai_model.adapt_to_conditions(&self.network_metrics);
     }
 
+    /// Routes with `model` ahead of the rules, falling back to them whenever inference
+    /// fails or the model is less sure than the confidence threshold
+    #[cfg(feature = "ml")]
+    pub fn with_learned_model(mut self, model: ml::PathModel) -> Self {
+        self.ai_model.learned = Some(model);
+        self
+    }
+
     pub fn select_optimal_path(&self) -> ConsensusPath {
         let metrics = &self.network_metrics;
         let confidence = self.ai_model.calculate_confidence(metrics);
         
+        // Emergency conditions override a learned model too
         if metrics.attack_probability > 0.8 || metrics.congestion_level > 0.95 {
             return ConsensusPath::EmergencyMode {
                 fallback_validators: (metrics.validator_count * 3 / 4).max(10),
                 security_override: true,
             };
         }
+
+        #[cfg(feature = "ml")]
+        if let Some(path) = self.learned_path() {
+            return path;
+        }
         
         if metrics.attack_probability > 0.4 || confidence < 0.6 {
             return ConsensusPath::SecureLane {
@@ -104,10 +221,14 @@ impl ConsensusRouter {
             return ConsensusPath::FastLane {
                 expected_tps: 100_000,
                 finality_time: 100,
-                validator_count: (metrics.validator_count / 4).max(21),
+                validator_count: committee_size(metrics.validator_count),
             };
         }
         
+        Self::hybrid_path(metrics, confidence)
+    }
+
+    fn hybrid_path(metrics: &NetworkMetrics, confidence: f64) -> ConsensusPath {
         ConsensusPath::HybridPath {
             fast_percentage: 0.7 - (metrics.attack_probability * 0.5),
             secure_percentage: 0.3 + (metrics.attack_probability * 0.5),
@@ -115,6 +236,27 @@ impl ConsensusRouter {
         }
     }
 
+    /// The learned model's choice, if one is loaded, runs and clears the threshold
+    #[cfg(feature = "ml")]
+    fn learned_path(&self) -> Option<ConsensusPath> {
+        let metrics = &self.network_metrics;
+        let scores = match self.ai_model.learned.as_ref()?.scores(metrics) {
+            Ok(scores) => scores,
+            Err(e) => {
+                crate::log!(Warn, "Falling back to routing rules: {}", e);
+                return None;
+            }
+        };
+        let (best, confidence) = scores.best();
+        if confidence < self.ai_model.confidence_threshold {
+            return None;
+        }
+        Some(match best {
+            2 => Self::hybrid_path(metrics, confidence),
+            index => ConsensusPath::from_index(index, metrics),
+        })
+    }
+
     pub fn predict_performance(&self, path: &ConsensusPath) -> PerformancePrediction {
         let base_metrics = &self.network_metrics;
         
@@ -172,6 +314,19 @@ impl ConsensusRouter {
     pub fn ai_confidence(&self) -> f64 {
         self.ai_model.calculate_confidence(&self.network_metrics)
     }
+
+    pub fn model_state(&self) -> ModelState {
+        ModelState {
+            weights: self.ai_model.weights.iter().map(|(name, weight)| (name.clone(), *weight)).collect(),
+            bounds: self.ai_model.bounds.iter().map(|(name, bounds)| (name.clone(), *bounds)).collect(),
+            confidence: self.ai_confidence(),
+            confidence_threshold: self.ai_model.confidence_threshold,
+            #[cfg(feature = "ml")]
+            learned: self.ai_model.learned.is_some(),
+            #[cfg(not(feature = "ml"))]
+            learned: false,
+        }
+    }
 }
 
 impl Default for ConsensusRouter {
@@ -189,6 +344,16 @@ impl NetworkMetrics {
     }
 }
 
+/// How settled the network looks under `metrics`: stability, security and spare
+/// resources averaged
+fn metrics_confidence(metrics: &NetworkMetrics) -> f64 {
+    let stability = 1.0 - metrics.congestion_level;
+    let security = 1.0 - metrics.attack_probability;
+    let resources = (2.0 - metrics.cpu_usage - metrics.memory_usage) / 2.0;
+
+    (stability + security + resources) / 3.0
+}
+
 impl Default for NetworkMetrics {
     fn default() -> Self {
         Self {
@@ -205,35 +370,34 @@ impl Default for NetworkMetrics {
 
 impl AIModel {
     fn new() -> Self {
-        let mut weights = HashMap::new();
-        weights.insert("security_weight".to_string(), 0.3);
-        weights.insert("performance_weight".to_string(), 0.4);
-        weights.insert("decentralization_weight".to_string(), 0.3);
-        
         Self {
-            weights,
+            weights: WEIGHTS.iter().map(|(name, bounds)| (name.to_string(), bounds.baseline)).collect(),
+            bounds: WEIGHTS.iter().map(|(name, bounds)| (name.to_string(), *bounds)).collect(),
             _learning_rate: 0.01,
-            _confidence_threshold: 0.7,
+            confidence_threshold: LEARNED_CONFIDENCE_THRESHOLD,
+            #[cfg(feature = "ml")]
+            learned: None,
         }
     }
     fn calculate_confidence(&self, metrics: &NetworkMetrics) -> f64 {
-        let stability = 1.0 - metrics.congestion_level;
-        let security = 1.0 - metrics.attack_probability;
-        let resources = (2.0 - metrics.cpu_usage - metrics.memory_usage) / 2.0;
-        
-        (stability + security + resources) / 3.0
+        metrics_confidence(metrics)
     }
+    /// Raises the weight of whatever the network is short of and decays the others back
+    /// toward their baseline, never moving a weight more than `MAX_WEIGHT_CHANGE` at once
+    /// or out of its bounds
     fn adapt_to_conditions(&mut self, metrics: &NetworkMetrics) {
-        if metrics.attack_probability > 0.5 {
-            if let Some(weight) = self.weights.get_mut("security_weight") {
-                *weight = (*weight + 0.1).min(0.8);
-            }
-        }
-        
-        if metrics.congestion_level > 0.7 {
-            if let Some(weight) = self.weights.get_mut("performance_weight") {
-                *weight = (*weight + 0.1).min(0.9);
-            }
+        for (name, bounds) in &self.bounds {
+            let pressed = match name.as_str() {
+                "security_weight" => metrics.attack_probability > 0.5,
+                "performance_weight" => metrics.congestion_level > 0.7,
+                _ => false,
+            };
+            let weight = self.weights.entry(name.clone()).or_insert(bounds.baseline);
+            let change = match pressed {
+                true => MAX_WEIGHT_CHANGE,
+                false => (bounds.baseline - *weight) * WEIGHT_DECAY,
+            };
+            *weight = (*weight + change.clamp(-MAX_WEIGHT_CHANGE, MAX_WEIGHT_CHANGE)).clamp(bounds.min, bounds.max);
         }
     }
 }
@@ -268,4 +432,34 @@ mod tests {
         
         println!("Emergency mode activated under attack!");
     }
+
+    #[test]
+    fn test_weight_adaptation() {
+        let mut router = ConsensusRouter::new();
+        let weight = |router: &ConsensusRouter, name: &str| router.model_state().weights[name];
+
+        // A long congestion spike raises the weight one step at a time up to its cap
+        let congested = NetworkMetrics { congestion_level: 0.9, ..Default::default() };
+        router.update_metrics(congested.clone());
+        assert!((weight(&router, "performance_weight") - 0.5).abs() < 1e-9);
+        for _ in 0..10 {
+            router.update_metrics(congested.clone());
+        }
+        assert_eq!(weight(&router, "performance_weight"), 0.9);
+        assert_eq!(weight(&router, "security_weight"), 0.3);
+
+        // Once it clears the weight decays back, no faster than the rate limit
+        router.update_metrics(NetworkMetrics::default());
+        assert!((weight(&router, "performance_weight") - 0.8).abs() < 1e-9);
+        for _ in 0..40 {
+            router.update_metrics(NetworkMetrics::default());
+        }
+        assert!((weight(&router, "performance_weight") - 0.4).abs() < 1e-3);
+
+        let state = router.model_state();
+        assert_eq!(state.bounds["security_weight"], WeightBounds { baseline: 0.3, min: 0.1, max: 0.8 });
+        assert!(state.weights.iter().all(|(name, weight)| *weight >= state.bounds[name].min && *weight <= state.bounds[name].max));
+
+        println!("   Weight adaptation working!");
+    }
 }
\ No newline at end of file
@@ -0,0 +1,160 @@
+use std::path::Path;
+use std::sync::Arc;
+use tract_onnx::prelude::*;
+
+use super::{features, NetworkMetrics, FEATURES, PATHS};
+
+/// Probability a path model gives each of `PATHS`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathScores {
+    pub probabilities: [f64; 4],
+}
+
+impl PathScores {
+    /// Index into `PATHS` of the most likely path, and its probability
+    pub fn best(&self) -> (usize, f64) {
+        self.probabilities
+            .iter()
+            .copied()
+            .enumerate()
+            .fold((0, f64::MIN), |best, (index, probability)| if probability > best.1 { (index, probability) } else { best })
+    }
+}
+
+/// ONNX model mapping a `[1, 7]` f32 tensor of `features` to a `[1, 4]` tensor of
+/// logits, one per entry of `PATHS`, run with tract
+#[derive(Debug, Clone)]
+pub struct PathModel {
+    plan: Arc<TypedRunnableModel>,
+}
+
+impl PathModel {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let model = tract_onnx::onnx()
+            .model_for_path(path)
+            .map_err(|e| format!("Could not read path model {}: {}", path.display(), e))?;
+        Self::from_model(model)
+    }
+
+    /// Optimizes a parsed model for single inputs; it must score the default metrics
+    pub fn from_model(model: InferenceModel) -> Result<Self, String> {
+        let plan = model
+            .with_input_fact(0, f32::fact([1, FEATURES.len()]).into())
+            .and_then(|model| model.into_optimized())
+            .and_then(|model| model.into_runnable())
+            .map_err(|e| format!("Invalid path model: {}", e))?;
+        let model = Self { plan };
+        model.scores(&NetworkMetrics::default())?;
+        Ok(model)
+    }
+
+    pub fn scores(&self, metrics: &NetworkMetrics) -> Result<PathScores, String> {
+        let input = Tensor::from_shape(&[1, FEATURES.len()], &features(metrics)).map_err(|e| e.to_string())?;
+        let outputs = self.plan.run(tvec!(input.into())).map_err(|e| format!("Path model inference failed: {}", e))?;
+        let output = outputs.first().ok_or("Path model has no output")?;
+        let logits: Vec<f64> = output
+            .to_plain_array_view::<f32>()
+            .map_err(|e| format!("Path model output is not f32: {}", e))?
+            .iter()
+            .map(|logit| *logit as f64)
+            .collect();
+        if logits.len() != PATHS.len() || logits.iter().any(|logit| !logit.is_finite()) {
+            return Err(format!("Path model must output {} finite scores, got {:?}", PATHS.len(), logits));
+        }
+
+        let max = logits.iter().copied().fold(f64::MIN, f64::max);
+        let exps: Vec<f64> = logits.iter().map(|logit| (logit - max).exp()).collect();
+        let total: f64 = exps.iter().sum();
+        let mut probabilities = [0.0; 4];
+        for (probability, exp) in probabilities.iter_mut().zip(exps) {
+            *probability = exp / total;
+        }
+        Ok(PathScores { probabilities })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus::router::{ConsensusPath, ConsensusRouter};
+    use tract_onnx::pb::{self, tensor_shape_proto::dimension, type_proto};
+
+    /// `features · weights + bias` as an ONNX Gemm, `weights` laid out feature by feature
+    fn linear_model(weights: Vec<f32>, bias: Vec<f32>) -> InferenceModel {
+        let outputs = bias.len() as i64;
+        let tensor = |name: &str, dims: Vec<i64>, float_data: Vec<f32>| pb::TensorProto {
+            name: name.to_string(),
+            dims,
+            data_type: pb::tensor_proto::DataType::Float as i32,
+            float_data,
+            ..Default::default()
+        };
+        let value = |name: &str, dims: &[i64]| pb::ValueInfoProto {
+            name: name.to_string(),
+            r#type: Some(pb::TypeProto {
+                value: Some(type_proto::Value::TensorType(type_proto::Tensor {
+                    elem_type: pb::tensor_proto::DataType::Float as i32,
+                    shape: Some(pb::TensorShapeProto {
+                        dim: dims
+                            .iter()
+                            .map(|dim| pb::tensor_shape_proto::Dimension { value: Some(dimension::Value::DimValue(*dim)), ..Default::default() })
+                            .collect(),
+                    }),
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let graph = pb::GraphProto {
+            node: vec![pb::NodeProto {
+                input: vec!["metrics".to_string(), "weights".to_string(), "bias".to_string()],
+                output: vec!["scores".to_string()],
+                op_type: "Gemm".to_string(),
+                ..Default::default()
+            }],
+            initializer: vec![tensor("weights", vec![FEATURES.len() as i64, outputs], weights), tensor("bias", vec![outputs], bias)],
+            input: vec![value("metrics", &[1, FEATURES.len() as i64])],
+            output: vec![value("scores", &[1, outputs])],
+            ..Default::default()
+        };
+        let proto = pb::ModelProto {
+            ir_version: 7,
+            opset_import: vec![pb::OperatorSetIdProto { version: 13, ..Default::default() }],
+            graph: Some(graph),
+            ..Default::default()
+        };
+        tract_onnx::onnx().model_for_proto_model(&proto).unwrap()
+    }
+
+    #[test]
+    fn test_learned_routing() {
+        // FastLane scores 20x congestion, SecureLane 20x attack probability, HybridPath a flat 5
+        let mut weights = vec![0.0; FEATURES.len() * PATHS.len()];
+        weights[4 * PATHS.len()] = 20.0;
+        weights[3 * PATHS.len() + 1] = 20.0;
+        let model = PathModel::from_model(linear_model(weights, vec![0.0, 0.0, 5.0, 0.0])).unwrap();
+        let metrics = NetworkMetrics { congestion_level: 0.6, attack_probability: 0.1, ..Default::default() };
+        let (best, confidence) = model.scores(&metrics).unwrap().best();
+        assert_eq!((PATHS[best], confidence > 0.99), ("FastLane", true));
+
+        // A confident model overrides the rules, which would pick the hybrid path here
+        let mut router = ConsensusRouter::new().with_learned_model(model);
+        router.update_metrics(metrics.clone());
+        assert!(matches!(router.select_optimal_path(), ConsensusPath::FastLane { .. }));
+        assert!(router.model_state().learned);
+        assert!(matches!(ConsensusRouter::new().with_learned_model(PathModel::from_model(linear_model(vec![0.0; 28], vec![0.0; 4])).unwrap()).select_optimal_path(), ConsensusPath::HybridPath { .. }));
+
+        // An unsure model falls back to the rules, and emergencies are never second-guessed
+        router.update_metrics(NetworkMetrics { congestion_level: 0.25, attack_probability: 0.25, ..Default::default() });
+        let rules_confidence = router.ai_confidence();
+        assert!(matches!(router.select_optimal_path(), ConsensusPath::HybridPath { adaptive_threshold, .. } if adaptive_threshold == rules_confidence));
+        router.update_metrics(NetworkMetrics { congestion_level: 0.6, attack_probability: 0.9, ..Default::default() });
+        assert!(matches!(router.select_optimal_path(), ConsensusPath::EmergencyMode { .. }));
+
+        // A model scoring the wrong number of paths is refused on load
+        let error = PathModel::from_model(linear_model(vec![0.0; 21], vec![0.0; 3])).unwrap_err();
+        assert!(error.contains("must output 4 finite scores"));
+
+        println!("   Learned routing working!");
+    }
+}
@@ -0,0 +1,363 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use super::{features, ConsensusRouter, NetworkMetrics, FEATURES, LEARNED_CONFIDENCE_THRESHOLD, PATHS};
+use crate::consensus::simulation::{expected_reward, NetworkSimulator, Outcome, Scenario, SCENARIO_PERIOD};
+
+/// Offset from the training seed for the simulations policies are evaluated on, so
+/// evaluation never replays the episodes a policy was trained on
+const EVALUATION_SEED: u64 = 0x5eed;
+
+/// One block the simulator ran: what was observed, the path taken and how it went
+#[derive(Debug, Clone, Serialize)]
+pub struct Experience {
+    pub metrics: NetworkMetrics,
+    /// Index into `PATHS`
+    pub path: usize,
+    pub outcome: Outcome,
+}
+
+/// Softmax over `features · weights + bias`, the shape `ml::PathModel` runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinearPolicy {
+    /// One row per entry of `FEATURES`, one column per entry of `PATHS`
+    pub weights: Vec<[f64; 4]>,
+    pub bias: [f64; 4],
+}
+
+impl Default for LinearPolicy {
+    /// Picks every path alike
+    fn default() -> Self {
+        Self { weights: vec![[0.0; 4]; FEATURES.len()], bias: [0.0; 4] }
+    }
+}
+
+impl LinearPolicy {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let policy: Self = serde_json::from_str(json).map_err(|e| format!("Invalid policy weights: {}", e))?;
+        if policy.weights.len() != FEATURES.len() {
+            return Err(format!("Policy needs {} weight rows, got {}", FEATURES.len(), policy.weights.len()));
+        }
+        Ok(policy)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    fn logits(&self, metrics: &NetworkMetrics) -> [f64; 4] {
+        let mut logits = self.bias;
+        for (feature, row) in features(metrics).iter().zip(&self.weights) {
+            for (logit, weight) in logits.iter_mut().zip(row) {
+                *logit += *feature as f64 * weight;
+            }
+        }
+        logits
+    }
+
+    pub fn probabilities(&self, metrics: &NetworkMetrics) -> [f64; 4] {
+        let logits = self.logits(metrics);
+        let max = logits.iter().copied().fold(f64::MIN, f64::max);
+        let mut probabilities = logits.map(|logit| (logit - max).exp());
+        let total: f64 = probabilities.iter().sum();
+        probabilities.iter_mut().for_each(|probability| *probability /= total);
+        probabilities
+    }
+
+    /// Most likely path
+    pub fn greedy(&self, metrics: &NetworkMetrics) -> usize {
+        let probabilities = self.probabilities(metrics);
+        (0..PATHS.len()).fold(0, |best, path| if probabilities[path] > probabilities[best] { path } else { best })
+    }
+
+    /// Path drawn by its probability, which is how the policy explores
+    pub fn sample(&self, metrics: &NetworkMetrics, rng: &mut impl Rng) -> usize {
+        let mut draw: f64 = rng.gen();
+        for (path, probability) in self.probabilities(metrics).iter().enumerate() {
+            draw -= probability;
+            if draw < 0.0 {
+                return path;
+            }
+        }
+        PATHS.len() - 1
+    }
+
+    /// One REINFORCE step over `batch`: every path taken is made more likely in
+    /// proportion to how much its reward beat the batch mean
+    pub fn update(&mut self, batch: &[Experience], learning_rate: f64) {
+        if batch.is_empty() {
+            return;
+        }
+        let baseline = batch.iter().map(|experience| experience.outcome.reward).sum::<f64>() / batch.len() as f64;
+        let step = learning_rate / batch.len() as f64;
+        for experience in batch {
+            let advantage = experience.outcome.reward - baseline;
+            let probabilities = self.probabilities(&experience.metrics);
+            let inputs = features(&experience.metrics);
+            for path in 0..PATHS.len() {
+                let taken = if path == experience.path { 1.0 } else { 0.0 };
+                let gradient = step * advantage * (taken - probabilities[path]);
+                self.bias[path] += gradient;
+                for (row, input) in self.weights.iter_mut().zip(inputs) {
+                    row[path] += gradient * input as f64;
+                }
+            }
+        }
+    }
+
+    /// The policy as an ONNX Gemm from `metrics` to `scores`, loadable by `ml::PathModel`
+    pub fn to_onnx(&self) -> Vec<u8> {
+        let tensor = |name: &str, dims: &[i64], data: Vec<f32>| {
+            dims.iter().fold(Proto::default(), |tensor, dim| tensor.int(1, *dim)).int(2, ONNX_FLOAT).floats(4, &data).string(8, name)
+        };
+        let value = |name: &str, dims: &[i64]| {
+            let shape = dims.iter().fold(Proto::default(), |shape, dim| shape.message(1, Proto::default().int(1, *dim)));
+            let tensor_type = Proto::default().int(1, ONNX_FLOAT).message(2, shape);
+            Proto::default().string(1, name).message(2, Proto::default().message(1, tensor_type))
+        };
+        let gemm = Proto::default()
+            .string(1, "metrics")
+            .string(1, "weights")
+            .string(1, "bias")
+            .string(2, "scores")
+            .string(3, "policy")
+            .string(4, "Gemm");
+        let weights = self.weights.iter().flatten().map(|weight| *weight as f32).collect();
+        let graph = Proto::default()
+            .message(1, gemm)
+            .string(2, "path_policy")
+            .message(5, tensor("weights", &[FEATURES.len() as i64, PATHS.len() as i64], weights))
+            .message(5, tensor("bias", &[PATHS.len() as i64], self.bias.map(|bias| bias as f32).to_vec()))
+            .message(11, value("metrics", &[1, FEATURES.len() as i64]))
+            .message(12, value("scores", &[1, PATHS.len() as i64]));
+        Proto::default()
+            .int(1, 7)
+            .string(2, "triunity-train")
+            .message(7, graph)
+            .message(8, Proto::default().int(2, 13))
+            .0
+    }
+}
+
+/// `TensorProto.DataType.FLOAT`
+const ONNX_FLOAT: i64 = 1;
+
+/// Just enough protobuf encoding to write an ONNX model, by field number
+#[derive(Default)]
+struct Proto(Vec<u8>);
+
+impl Proto {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn bytes(mut self, field: u64, bytes: &[u8]) -> Self {
+        self.varint(field << 3 | 2);
+        self.varint(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    fn int(mut self, field: u64, value: i64) -> Self {
+        self.varint(field << 3);
+        self.varint(value as u64);
+        self
+    }
+
+    fn string(self, field: u64, value: &str) -> Self {
+        self.bytes(field, value.as_bytes())
+    }
+
+    fn message(self, field: u64, message: Proto) -> Self {
+        self.bytes(field, &message.0)
+    }
+
+    /// Packed repeated floats
+    fn floats(self, field: u64, values: &[f32]) -> Self {
+        let bytes: Vec<u8> = values.iter().flat_map(|value| value.to_le_bytes()).collect();
+        self.bytes(field, &bytes)
+    }
+}
+
+/// How a policy is trained against the simulator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrainingConfig {
+    pub epochs: usize,
+    /// Simulations run per epoch, cycling through `scenarios`
+    pub episodes: usize,
+    /// Blocks per simulation
+    pub steps: usize,
+    pub learning_rate: f64,
+    pub seed: u64,
+    pub scenarios: Vec<Scenario>,
+}
+
+impl Default for TrainingConfig {
+    fn default() -> Self {
+        Self { epochs: 50, episodes: 8, steps: 120, learning_rate: 2.0, seed: 0, scenarios: Scenario::ALL.to_vec() }
+    }
+}
+
+impl TrainingConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.episodes == 0 || self.steps == 0 {
+            return Err("Training needs at least one episode of one step".to_string());
+        }
+        if !(self.learning_rate > 0.0 && self.learning_rate.is_finite()) {
+            return Err(format!("Learning rate must be positive, got {}", self.learning_rate));
+        }
+        if self.scenarios.is_empty() {
+            return Err("Training needs at least one scenario".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Mean expected reward per block of a policy's and of the rules' choices on the
+/// evaluation simulations
+#[derive(Debug, Clone, Serialize)]
+pub struct Evaluation {
+    pub policy_reward: f64,
+    pub rules_reward: f64,
+    /// Share of blocks where the policy clears `LEARNED_CONFIDENCE_THRESHOLD`, so a
+    /// router would follow it rather than the rules
+    pub confident: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EpochReport {
+    pub epoch: usize,
+    /// Mean reward of the exploring policy on the epoch's own simulations
+    pub mean_reward: f64,
+    pub evaluation: Evaluation,
+}
+
+/// Trains a `LinearPolicy` offline on seeded simulations, so the same config and
+/// starting policy always give the same model
+pub struct Trainer {
+    config: TrainingConfig,
+    policy: LinearPolicy,
+    rng: StdRng,
+    epoch: usize,
+}
+
+impl Trainer {
+    pub fn new(config: TrainingConfig, policy: LinearPolicy) -> Result<Self, String> {
+        config.validate()?;
+        let rng = StdRng::seed_from_u64(config.seed);
+        Ok(Self { config, policy, rng, epoch: 0 })
+    }
+
+    pub fn policy(&self) -> &LinearPolicy {
+        &self.policy
+    }
+
+    /// Runs the epoch's simulations with the current policy exploring
+    pub fn collect(&mut self) -> Vec<Experience> {
+        let mut experiences = Vec::with_capacity(self.config.episodes * self.config.steps);
+        for episode in 0..self.config.episodes {
+            let scenario = self.config.scenarios[episode % self.config.scenarios.len()];
+            let mut simulator = NetworkSimulator::new(scenario, self.rng.gen());
+            for _ in 0..self.config.steps {
+                let metrics = simulator.step();
+                let path = self.policy.sample(&metrics, &mut self.rng);
+                let outcome = simulator.run(&metrics, path);
+                experiences.push(Experience { metrics, path, outcome });
+            }
+        }
+        experiences
+    }
+
+    pub fn train_epoch(&mut self) -> EpochReport {
+        let experiences = self.collect();
+        self.policy.update(&experiences, self.config.learning_rate);
+        self.epoch += 1;
+        EpochReport {
+            epoch: self.epoch,
+            mean_reward: experiences.iter().map(|experience| experience.outcome.reward).sum::<f64>() / experiences.len() as f64,
+            evaluation: self.evaluate(&self.policy),
+        }
+    }
+
+    /// Trains for the configured epochs, handing each report to `progress`
+    pub fn train(&mut self, mut progress: impl FnMut(&EpochReport)) -> &LinearPolicy {
+        for _ in 0..self.config.epochs {
+            progress(&self.train_epoch());
+        }
+        &self.policy
+    }
+
+    /// Scores `policy` and the rules router on one simulation per scenario, held apart
+    /// from training
+    pub fn evaluate(&self, policy: &LinearPolicy) -> Evaluation {
+        let (mut policy_reward, mut rules_reward, mut confident, mut blocks) = (0.0, 0.0, 0, 0);
+        for scenario in &self.config.scenarios {
+            let mut simulator = NetworkSimulator::new(*scenario, self.config.seed.wrapping_add(EVALUATION_SEED));
+            let mut router = ConsensusRouter::new();
+            for _ in 0..self.config.steps.max(SCENARIO_PERIOD as usize) {
+                let metrics = simulator.step();
+                router.update_metrics(metrics.clone());
+                policy_reward += expected_reward(&metrics, policy.greedy(&metrics));
+                rules_reward += expected_reward(&metrics, router.select_optimal_path().index());
+                if policy.probabilities(&metrics).iter().any(|probability| *probability >= LEARNED_CONFIDENCE_THRESHOLD) {
+                    confident += 1;
+                }
+                blocks += 1;
+            }
+        }
+        Evaluation {
+            policy_reward: policy_reward / blocks as f64,
+            rules_reward: rules_reward / blocks as f64,
+            confident: confident as f64 / blocks as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_training() {
+        let config = TrainingConfig { epochs: 30, ..Default::default() };
+        let untrained = Trainer::new(config.clone(), LinearPolicy::default()).unwrap();
+        let before = untrained.evaluate(&LinearPolicy::default());
+        assert_eq!(before.confident, 0.0);
+
+        let mut trainer = Trainer::new(config.clone(), LinearPolicy::default()).unwrap();
+        let mut reports = Vec::new();
+        let policy = trainer.train(|report| reports.push(report.clone())).clone();
+        let after = &reports.last().unwrap().evaluation;
+        println!("   Reward {:.3} -> {:.3} (rules {:.3}), confident on {:.0}%", before.policy_reward, after.policy_reward, after.rules_reward, after.confident * 100.0);
+        assert!(after.policy_reward > before.policy_reward);
+        assert!(after.policy_reward > after.rules_reward);
+        assert!(after.confident > 0.0);
+
+        // Same config and starting policy, same model
+        let mut again = Trainer::new(config, LinearPolicy::default()).unwrap();
+        assert_eq!(again.train(|_| {}), &policy);
+        let restored = LinearPolicy::from_json(&policy.to_json()).unwrap();
+        assert!(restored.weights.iter().flatten().zip(policy.weights.iter().flatten()).all(|(a, b)| (a - b).abs() < 1e-9));
+        assert!(LinearPolicy::from_json(r#"{"weights":[],"bias":[0,0,0,0]}"#).is_err());
+        assert!(Trainer::new(TrainingConfig { scenarios: vec![], ..Default::default() }, policy.clone()).is_err());
+
+        // The exported model scores like the policy it came from
+        #[cfg(feature = "ml")]
+        {
+            let model = tract_onnx::prelude::Framework::model_for_read(&tract_onnx::onnx(), &mut policy.to_onnx().as_slice()).unwrap();
+            let model = super::super::ml::PathModel::from_model(model).unwrap();
+            let metrics = NetworkMetrics { congestion_level: 0.8, attack_probability: 0.5, ..Default::default() };
+            let scores = model.scores(&metrics).unwrap();
+            for (exported, trained) in scores.probabilities.iter().zip(policy.probabilities(&metrics)) {
+                assert!((exported - trained).abs() < 1e-4);
+            }
+        }
+
+        println!("   Policy training working!");
+    }
+}
@@ -0,0 +1,146 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use super::router::NetworkMetrics;
+
+/// Steps in one cycle of a scenario; its stress holds through the middle third
+pub const SCENARIO_PERIOD: u64 = 60;
+
+/// Reward lost when an attack gets through
+pub const COMPROMISE_PENALTY: f64 = 3.0;
+
+/// Load and threat a simulated network goes through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scenario {
+    Calm,
+    CongestionSpike,
+    Attack,
+    CongestedAttack,
+}
+
+impl Scenario {
+    pub const ALL: [Scenario; 4] = [Self::Calm, Self::CongestionSpike, Self::Attack, Self::CongestedAttack];
+
+    /// Congestion and attack probability the network drifts toward at `step`
+    fn target(&self, step: u64) -> (f64, f64) {
+        let stressed = (SCENARIO_PERIOD / 3..SCENARIO_PERIOD * 2 / 3).contains(&(step % SCENARIO_PERIOD));
+        match (self, stressed) {
+            (Self::CongestionSpike, true) => (0.85, 0.05),
+            (Self::Attack, true) => (0.3, 0.6),
+            (Self::CongestedAttack, true) => (0.85, 0.6),
+            _ => (0.3, 0.05),
+        }
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "calm" => Ok(Self::Calm),
+            "congestion_spike" => Ok(Self::CongestionSpike),
+            "attack" => Ok(Self::Attack),
+            "congested_attack" => Ok(Self::CongestedAttack),
+            other => Err(format!("Unknown scenario: {} (expected calm, congestion_spike, attack or congested_attack)", other)),
+        }
+    }
+}
+
+/// How a consensus path holds up in the simulation, indexed like `router::PATHS`
+struct PathProfile {
+    /// Share of full load the path can carry
+    capacity: f64,
+    finality_ms: u64,
+    /// Share of attacks the path withstands
+    resistance: f64,
+}
+
+const PROFILES: [PathProfile; 4] = [
+    PathProfile { capacity: 1.0, finality_ms: 100, resistance: 0.4 },
+    PathProfile { capacity: 0.4, finality_ms: 2_000, resistance: 0.95 },
+    PathProfile { capacity: 0.75, finality_ms: 800, resistance: 0.7 },
+    PathProfile { capacity: 0.1, finality_ms: 5_000, resistance: 0.99 },
+];
+
+/// What running a path for one block led to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Outcome {
+    /// Share of the demand that was served
+    pub served: f64,
+    pub finality_ms: u64,
+    pub compromised: bool,
+    pub reward: f64,
+}
+
+impl PathProfile {
+    fn of(path: usize) -> &'static Self {
+        &PROFILES[path.min(PROFILES.len() - 1)]
+    }
+
+    fn served(&self, metrics: &NetworkMetrics) -> f64 {
+        (self.capacity / metrics.congestion_level.max(0.01)).min(1.0)
+    }
+
+    fn latency_cost(&self) -> f64 {
+        0.2 * self.finality_ms as f64 / 5_000.0
+    }
+}
+
+/// Reward of running `path` under `metrics`, averaged over attack outcomes
+pub fn expected_reward(metrics: &NetworkMetrics, path: usize) -> f64 {
+    let profile = PathProfile::of(path);
+    profile.served(metrics) - profile.latency_cost() - COMPROMISE_PENALTY * metrics.attack_probability * (1.0 - profile.resistance)
+}
+
+/// Seeded network whose load and threat follow a scenario, so the same seed always
+/// produces the same metrics and outcomes. It models paths only by their capacity,
+/// finality and attack resistance, with no nodes or blocks behind them
+pub struct NetworkSimulator {
+    scenario: Scenario,
+    rng: StdRng,
+    step: u64,
+    congestion: f64,
+    attack: f64,
+}
+
+impl NetworkSimulator {
+    pub fn new(scenario: Scenario, seed: u64) -> Self {
+        let (congestion, attack) = scenario.target(0);
+        Self { scenario, rng: StdRng::seed_from_u64(seed), step: 0, congestion, attack }
+    }
+
+    pub fn scenario(&self) -> Scenario {
+        self.scenario
+    }
+
+    /// Advances one block and returns the metrics a node would then observe
+    pub fn step(&mut self) -> NetworkMetrics {
+        self.step += 1;
+        let (congestion, attack) = self.scenario.target(self.step);
+        let noise = |rng: &mut StdRng| rng.gen_range(-0.05..0.05);
+        self.congestion = (self.congestion + 0.3 * (congestion - self.congestion) + noise(&mut self.rng)).clamp(0.0, 1.0);
+        self.attack = (self.attack + 0.3 * (attack - self.attack) + noise(&mut self.rng)).clamp(0.0, 1.0);
+        NetworkMetrics {
+            current_tps: (self.congestion * 20_000.0) as u64,
+            network_latency: 100 + (self.congestion * 900.0) as u64,
+            validator_count: 100,
+            attack_probability: self.attack,
+            congestion_level: self.congestion,
+            memory_usage: 0.5,
+            cpu_usage: 0.3 + 0.5 * self.congestion,
+        }
+    }
+
+    /// Runs `path`, an index into `router::PATHS`, for the block `metrics` were observed at
+    pub fn run(&mut self, metrics: &NetworkMetrics, path: usize) -> Outcome {
+        let profile = PathProfile::of(path);
+        let compromised = self.rng.gen_bool((metrics.attack_probability * (1.0 - profile.resistance)).clamp(0.0, 1.0));
+        let served = profile.served(metrics);
+        let penalty = if compromised { COMPROMISE_PENALTY } else { 0.0 };
+        Outcome { served, finality_ms: profile.finality_ms, compromised, reward: served - profile.latency_cost() - penalty }
+    }
+}
@@ -0,0 +1,142 @@
+use sha3::{Digest, Sha3_256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::storage::blocks::ConsensusData;
+use crate::storage::database::{BlockchainDB, WriteGate};
+
+const SET_ID_DOMAIN: &[u8] = b"triunity/validator-set";
+
+/// Validator keys in seat order
+type ValidatorKeys = Arc<Vec<Vec<u8>>>;
+
+/// Id of a validator set, a hash over its keys in seat order
+pub fn set_id(validators: &[Vec<u8>]) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(SET_ID_DOMAIN);
+    for validator in validators {
+        hasher.update((validator.len() as u32).to_be_bytes());
+        hasher.update(validator);
+    }
+    hasher.finalize().into()
+}
+
+/// Validator sets blocks name their validators from. Each distinct set is written
+/// once under its id and every epoch points at the set it ran with, so blocks carry
+/// a set id and seat indices instead of the keys themselves
+#[derive(Debug)]
+pub struct ValidatorSets {
+    sets: sled::Tree,
+    epochs: sled::Tree,
+    cache: Mutex<HashMap<[u8; 32], ValidatorKeys>>,
+    writes: WriteGate,
+}
+
+impl ValidatorSets {
+    pub fn open(db: &BlockchainDB) -> Result<Self, String> {
+        Ok(Self {
+            sets: db.tree("validator_sets")?,
+            epochs: db.tree("validator_set_epochs")?,
+            cache: Mutex::new(HashMap::new()),
+            writes: db.writes().clone(),
+        })
+    }
+
+    /// Records `validators` as the set of `epoch`, writing its keys only the first time
+    /// the set is seen. An epoch keeps the set it was first recorded with
+    pub fn record(&self, epoch: u64, validators: &[Vec<u8>]) -> Result<[u8; 32], String> {
+        let id = set_id(validators);
+        if let Some(recorded) = self.id_at(epoch)? {
+            if recorded != id {
+                return Err(format!("Epoch {} already has validator set {}", epoch, hex::encode(&recorded[..8])));
+            }
+            return Ok(id);
+        }
+        let _writing = self.writes.enter();
+        if !self.sets.contains_key(id).map_err(|e| e.to_string())? {
+            let value = bincode::serialize(validators).map_err(|e| e.to_string())?;
+            self.sets.insert(id, value).map_err(|e| e.to_string())?;
+        }
+        self.epochs.insert(epoch.to_be_bytes(), &id).map_err(|e| e.to_string())?;
+        Ok(id)
+    }
+
+    /// Id of the set recorded for `epoch`
+    pub fn id_at(&self, epoch: u64) -> Result<Option<[u8; 32]>, String> {
+        match self.epochs.get(epoch.to_be_bytes()).map_err(|e| e.to_string())? {
+            Some(value) => Ok(Some(value.as_ref().try_into().map_err(|_| format!("Corrupt validator set id for epoch {}", epoch))?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get(&self, id: &[u8; 32]) -> Result<Option<ValidatorKeys>, String> {
+        if let Some(validators) = self.cache.lock().unwrap().get(id) {
+            return Ok(Some(validators.clone()));
+        }
+        let Some(value) = self.sets.get(id).map_err(|e| e.to_string())? else {
+            return Ok(None);
+        };
+        let validators: ValidatorKeys = Arc::new(bincode::deserialize(&value).map_err(|e| format!("Corrupt validator set: {}", e))?);
+        self.cache.lock().unwrap().insert(*id, validators.clone());
+        Ok(Some(validators))
+    }
+
+    /// Consensus data of a block in `epoch` with its validator references replaced by
+    /// keys. References must point at the set recorded for that epoch
+    pub fn resolve(&self, epoch: u64, data: &ConsensusData) -> Result<ConsensusData, String> {
+        if !data.is_referenced() {
+            return Ok(data.clone());
+        }
+        let id = self.id_at(epoch)?.ok_or_else(|| format!("No validator set recorded for epoch {}", epoch))?;
+        let validators = self.get(&id)?.ok_or_else(|| format!("Validator set {} is missing", hex::encode(&id[..8])))?;
+        data.resolve(&id, &validators)
+    }
+
+    /// Distinct sets stored
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::blocks::{ValidatorRef, ValidatorSetRef};
+
+    #[test]
+    fn test_validator_sets() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_validator_sets");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        let sets = ValidatorSets::open(&db).unwrap();
+        let validators = vec![vec![1; 1312], vec![2; 1312], vec![3; 1312]];
+
+        // Epochs running the same set share one stored copy
+        let id = sets.record(0, &validators).unwrap();
+        assert_eq!(sets.record(1, &validators).unwrap(), id);
+        assert_eq!(sets.record(1, &validators).unwrap(), id);
+        assert_eq!(sets.len(), 1);
+        assert!(sets.record(1, &validators[..2]).is_err());
+        assert_eq!(sets.get(&id).unwrap().unwrap().as_slice(), validators.as_slice());
+
+        let data = ConsensusData::EmergencyRef { authority_validators: ValidatorSetRef { set: id, indices: vec![2, 0] } };
+        let ConsensusData::Emergency { authority_validators } = sets.resolve(1, &data).unwrap() else {
+            panic!("references were not resolved");
+        };
+        assert_eq!(authority_validators, vec![validators[2].clone(), validators[0].clone()]);
+        assert!(sets.resolve(2, &data).unwrap_err().contains("No validator set"));
+        let foreign = ConsensusData::FastLaneRef { validator: ValidatorRef { set: [9; 32], index: 0 }, committee: None };
+        assert!(sets.resolve(0, &foreign).is_err());
+
+        // Blocks written before references keep their keys
+        let keyed = ConsensusData::FastLane { validator: validators[1].clone(), committee: None };
+        assert!(matches!(sets.resolve(7, &keyed).unwrap(), ConsensusData::FastLane { validator, .. } if validator == validators[1]));
+
+        println!("   Validator sets working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
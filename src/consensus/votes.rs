@@ -0,0 +1,323 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::crypto::verification::{self, Subsystem};
+use crate::crypto::{QuantumKeyPair, QuantumSignature};
+use crate::storage::sessions::SessionKeys;
+
+const VOTE_DOMAIN: &[u8] = b"triunity/vote";
+
+/// A validator's signed vote for `block_hash` as the block at `height` in `round`
+/// Fields may only be added at the end; the `wire_encoding` test vectors catch anything else
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vote {
+    pub height: u64,
+    pub round: u32,
+    #[serde(with = "hex::serde")]
+    pub block_hash: [u8; 32],
+    /// The voter's public key
+    pub validator: Vec<u8>,
+    pub signature: QuantumSignature,
+    /// Session key that signed on the validator's behalf; `None` when the validator key signed
+    #[serde(default)]
+    pub session_key: Option<Vec<u8>>,
+}
+
+impl Vote {
+    pub fn sign(keypair: &QuantumKeyPair, height: u64, round: u32, block_hash: [u8; 32]) -> Result<Self, String> {
+        let signature = keypair.sign(&Self::signing_data(height, round, &block_hash)).map_err(|e| e.to_string())?;
+        Ok(Self { height, round, block_hash, validator: keypair.public_key().to_vec(), signature, session_key: None })
+    }
+
+    /// Signs with `session`, a hot key `validator` authorized on chain to vote for it
+    pub fn sign_with_session(session: &QuantumKeyPair, validator: Vec<u8>, height: u64, round: u32, block_hash: [u8; 32]) -> Result<Self, String> {
+        let signature = session.sign(&Self::signing_data(height, round, &block_hash)).map_err(|e| e.to_string())?;
+        Ok(Self { height, round, block_hash, validator, signature, session_key: Some(session.public_key().to_vec()) })
+    }
+
+    /// Key the vote was signed with
+    pub fn signer(&self) -> &[u8] {
+        self.session_key.as_deref().unwrap_or(&self.validator)
+    }
+
+    pub fn signing_data(height: u64, round: u32, block_hash: &[u8; 32]) -> Vec<u8> {
+        let mut data = VOTE_DOMAIN.to_vec();
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&round.to_be_bytes());
+        data.extend_from_slice(block_hash);
+        data
+    }
+
+    pub fn verify(&self) -> bool {
+        verification::record(Subsystem::VoteVerification, 1);
+        self.signature.verify(&Self::signing_data(self.height, self.round, &self.block_hash), self.signer())
+    }
+}
+
+/// Two votes by one validator for different blocks at the same height and round,
+/// which is evidence for slashing it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Equivocation {
+    pub first: Vote,
+    pub second: Vote,
+}
+
+impl Equivocation {
+    pub fn validator(&self) -> &[u8] {
+        &self.first.validator
+    }
+
+    /// Whether the votes really conflict and both carry the validator's signature
+    pub fn verify(&self) -> bool {
+        let (first, second) = (&self.first, &self.second);
+        first.validator == second.validator
+            && (first.height, first.round) == (second.height, second.round)
+            && first.block_hash != second.block_hash
+            && first.verify()
+            && second.verify()
+    }
+}
+
+/// Votes from validators holding more than two thirds of the voting power for one
+/// block, which commits it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitCertificate {
+    pub height: u64,
+    pub round: u32,
+    #[serde(with = "hex::serde")]
+    pub block_hash: [u8; 32],
+    pub votes: Vec<Vote>,
+}
+
+/// What adding a vote to the pool did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VoteOutcome {
+    /// The validator's vote for this block was already counted
+    Duplicate,
+    /// The vote is below the pruned height and was ignored
+    Stale,
+    /// Counted; `power` is the block's tally so far
+    Added { power: u64 },
+    /// Counted, and it took the block past two thirds of the voting power
+    Committed(CommitCertificate),
+    /// The validator already voted for another block at this height and round; the
+    /// vote is not counted
+    Equivocation(Box<Equivocation>),
+}
+
+/// Signed votes collected per height, round and block. Each validator's voting power
+/// is its stake, counted as at least 1 like in the fast-lane committee draws
+#[derive(Debug, Clone)]
+pub struct VotePool {
+    power: HashMap<Vec<u8>, u64>,
+    total_power: u64,
+    /// Each validator's first vote per height and round
+    cast: BTreeMap<(u64, u32, Vec<u8>), Vote>,
+    tallies: BTreeMap<(u64, u32, [u8; 32]), u64>,
+    /// First certificate formed at each height
+    certificates: BTreeMap<u64, CommitCertificate>,
+    evidence: Vec<Equivocation>,
+    /// Votes below this height are no longer collected
+    floor: u64,
+    /// Session keys validators may sign votes with instead of their validator key
+    sessions: SessionKeys,
+}
+
+impl VotePool {
+    pub fn new(validators: &[Vec<u8>], stakes: &[u64]) -> Self {
+        let power: HashMap<Vec<u8>, u64> = validators
+            .iter()
+            .enumerate()
+            .map(|(index, validator)| (validator.clone(), stakes.get(index).copied().unwrap_or_default().max(1)))
+            .collect();
+        let total_power = power.values().sum();
+        Self {
+            power,
+            total_power,
+            cast: BTreeMap::new(),
+            tallies: BTreeMap::new(),
+            certificates: BTreeMap::new(),
+            evidence: Vec::new(),
+            floor: 0,
+            sessions: SessionKeys::default(),
+        }
+    }
+
+    /// Accepts votes signed by the session keys in `sessions`, as of the chain's state
+    pub fn set_sessions(&mut self, sessions: SessionKeys) {
+        self.sessions = sessions;
+    }
+
+    /// Checks a vote signed by a session key was signed by the one its validator
+    /// authorized for the vote's height
+    fn check_session(&self, vote: &Vote) -> Result<(), String> {
+        match &vote.session_key {
+            Some(key) if self.sessions.active_at(&vote.validator, vote.height) != Some(key.as_slice()) => {
+                Err(format!("Session key is not authorized to vote at height {}", vote.height))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn total_power(&self) -> u64 {
+        self.total_power
+    }
+
+    pub fn has_quorum(&self, power: u64) -> bool {
+        power as u128 * 3 > self.total_power as u128 * 2
+    }
+
+    /// Verifies and counts `vote`. Votes from outside the validator set or with a bad
+    /// signature are refused
+    pub fn add(&mut self, vote: Vote) -> Result<VoteOutcome, String> {
+        let power = *self.power.get(&vote.validator).ok_or("Vote from a validator outside the set")?;
+        if vote.height < self.floor {
+            return Ok(VoteOutcome::Stale);
+        }
+        let key = (vote.height, vote.round, vote.validator.clone());
+        if self.cast.get(&key).is_some_and(|earlier| earlier.block_hash == vote.block_hash) {
+            return Ok(VoteOutcome::Duplicate);
+        }
+        self.check_session(&vote)?;
+        if !vote.verify() {
+            return Err("Invalid vote signature".to_string());
+        }
+        if let Some(earlier) = self.cast.get(&key) {
+            let equivocation = Equivocation { first: earlier.clone(), second: vote };
+            // One piece of evidence per validator, height and round is enough to slash it
+            if !self.evidence.iter().any(|known| known.first == equivocation.first) {
+                self.evidence.push(equivocation.clone());
+            }
+            return Ok(VoteOutcome::Equivocation(Box::new(equivocation)));
+        }
+
+        let (height, round, block_hash) = (vote.height, vote.round, vote.block_hash);
+        self.cast.insert(key, vote);
+        let tally = self.tallies.entry((height, round, block_hash)).or_default();
+        *tally += power;
+        let tally = *tally;
+        if !self.has_quorum(tally) || self.has_quorum(tally - power) || self.certificates.contains_key(&height) {
+            return Ok(VoteOutcome::Added { power: tally });
+        }
+        let votes = self
+            .cast
+            .range((height, round, Vec::new())..)
+            .map(|(_, vote)| vote)
+            .take_while(|vote| (vote.height, vote.round) == (height, round))
+            .filter(|vote| vote.block_hash == block_hash)
+            .cloned()
+            .collect();
+        let certificate = CommitCertificate { height, round, block_hash, votes };
+        self.certificates.insert(height, certificate.clone());
+        Ok(VoteOutcome::Committed(certificate))
+    }
+
+    /// Power of the votes counted for `block_hash` at `height` and `round`
+    pub fn tally(&self, height: u64, round: u32, block_hash: &[u8; 32]) -> u64 {
+        self.tallies.get(&(height, round, *block_hash)).copied().unwrap_or_default()
+    }
+
+    pub fn certificate(&self, height: u64) -> Option<&CommitCertificate> {
+        self.certificates.get(&height)
+    }
+
+    /// Checks that `certificate` holds distinct, validly signed votes for its block
+    /// from more than two thirds of this pool's voting power
+    pub fn verify_certificate(&self, certificate: &CommitCertificate) -> Result<(), String> {
+        let mut voters = HashMap::new();
+        for vote in &certificate.votes {
+            if (vote.height, vote.round, vote.block_hash) != (certificate.height, certificate.round, certificate.block_hash) {
+                return Err("Certificate holds a vote for another block".to_string());
+            }
+            let power = *self.power.get(&vote.validator).ok_or("Certificate holds a vote from outside the validator set")?;
+            if voters.insert(vote.validator.as_slice(), power).is_some() {
+                return Err("Certificate counts a validator twice".to_string());
+            }
+            self.check_session(vote)?;
+            if !vote.verify() {
+                return Err("Certificate holds an invalid vote signature".to_string());
+            }
+        }
+        let power = voters.values().sum();
+        if !self.has_quorum(power) {
+            return Err(format!("Certificate carries {} of {} voting power", power, self.total_power));
+        }
+        Ok(())
+    }
+
+    /// Equivocations seen since the last call, for slashing
+    pub fn take_evidence(&mut self) -> Vec<Equivocation> {
+        std::mem::take(&mut self.evidence)
+    }
+
+    /// Drops votes and certificates below `height` and ignores new votes for them
+    pub fn prune(&mut self, height: u64) {
+        if height <= self.floor {
+            return;
+        }
+        self.floor = height;
+        self.cast = self.cast.split_off(&(height, 0, Vec::new()));
+        self.tallies = self.tallies.split_off(&(height, 0, [0; 32]));
+        self.certificates = self.certificates.split_off(&height);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sessions::SessionCall;
+
+    #[test]
+    fn test_vote_pool() {
+        let keys: Vec<QuantumKeyPair> = (0..4).map(|_| QuantumKeyPair::generate()).collect();
+        let validators: Vec<Vec<u8>> = keys.iter().map(|key| key.public_key().to_vec()).collect();
+        // Four validators of equal power: three votes are needed to pass two thirds
+        let mut pool = VotePool::new(&validators, &[10, 10, 10, 10]);
+        let block = [7; 32];
+        let vote = |index: usize, block_hash| Vote::sign(&keys[index], 5, 0, block_hash).unwrap();
+
+        assert_eq!(pool.add(vote(0, block)).unwrap(), VoteOutcome::Added { power: 10 });
+        assert_eq!(pool.add(vote(0, block)).unwrap(), VoteOutcome::Duplicate);
+        assert_eq!(pool.add(vote(1, block)).unwrap(), VoteOutcome::Added { power: 20 });
+        let VoteOutcome::Committed(certificate) = pool.add(vote(2, block)).unwrap() else {
+            panic!("Expected a commit certificate");
+        };
+        assert_eq!((certificate.height, certificate.votes.len()), (5, 3));
+        assert!(pool.verify_certificate(&certificate).is_ok());
+        assert_eq!(pool.add(vote(3, block)).unwrap(), VoteOutcome::Added { power: 40 });
+
+        // A second vote at the same height and round is evidence, not a vote
+        let VoteOutcome::Equivocation(evidence) = pool.add(vote(1, [8; 32])).unwrap() else {
+            panic!("Expected an equivocation");
+        };
+        assert!(evidence.verify());
+        assert_eq!(pool.tally(5, 0, &[8; 32]), 0);
+        assert_eq!(pool.take_evidence(), vec![*evidence]);
+
+        // Forged, foreign and short certificates are refused
+        let mut forged = Vote::sign(&keys[3], 5, 1, [9; 32]).unwrap();
+        forged.block_hash = block;
+        assert!(pool.add(forged.clone()).is_err());
+        assert!(pool.add(Vote::sign(&QuantumKeyPair::generate(), 5, 0, block).unwrap()).is_err());
+        let short = CommitCertificate { votes: certificate.votes[..2].to_vec(), ..certificate.clone() };
+        assert!(pool.verify_certificate(&short).is_err());
+        let doubled = CommitCertificate { votes: vec![certificate.votes[0].clone(); 3], ..certificate.clone() };
+        assert!(pool.verify_certificate(&doubled).is_err());
+
+        // Session keys vote for their validator only while authorized
+        let session = QuantumKeyPair::generate();
+        let delegated = Vote::sign_with_session(&session, validators[0].clone(), 5, 2, block).unwrap();
+        assert!(pool.add(delegated.clone()).is_err());
+        let authorize = SessionCall::Authorize { key: session.public_key().to_vec(), epochs: 1 };
+        let mut sessions = SessionKeys::default();
+        sessions.apply(&validators[0], &authorize, 0);
+        pool.set_sessions(sessions);
+        assert_eq!(pool.add(delegated).unwrap(), VoteOutcome::Added { power: 10 });
+
+        pool.prune(6);
+        assert!(pool.certificate(5).is_none());
+        assert_eq!(pool.add(vote(0, block)).unwrap(), VoteOutcome::Stale);
+
+        println!("   Vote pool working!");
+    }
+}
@@ -1,3 +1,12 @@
+pub mod bech32;
+pub mod canonical;
+pub mod hash;
+pub mod signatures;
+pub mod verification;
+
+pub use hash::{hash256, Hash256};
+pub use signatures::{QuantumKeyPair, QuantumSignature};
+
 pub struct QuantumCrypto;
 
 impl QuantumCrypto {
@@ -12,4 +21,10 @@ impl QuantumCrypto {
     pub fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
         message == signature
     }
-}
\ No newline at end of file
+}
+
+impl Default for QuantumCrypto {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,169 @@
+/// Human-readable part of addresses, so they read `tri1...`
+pub const ADDRESS_HRP: &str = "tri";
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+const CHECKSUM_LEN: usize = 6;
+
+/// Checksum constant in use: BIP-173 bech32 or BIP-350 bech32m, which addresses are written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Bech32,
+    Bech32m,
+}
+
+impl Variant {
+    fn constant(self) -> u32 {
+        match self {
+            Self::Bech32 => 1,
+            Self::Bech32m => 0x2bc830a3,
+        }
+    }
+}
+
+fn polymod(values: impl Iterator<Item = u8>) -> u32 {
+    let mut checksum = 1u32;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x1ffffff) << 5) ^ value as u32;
+        for (bit, generator) in GENERATOR.iter().enumerate() {
+            if (top >> bit) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+fn hrp_expand(hrp: &str) -> impl Iterator<Item = u8> + '_ {
+    hrp.bytes().map(|c| c >> 5).chain([0]).chain(hrp.bytes().map(|c| c & 31))
+}
+
+/// Regroups `data` from `from`-bit to `to`-bit values. Without `pad`, leftover bits must
+/// be zero padding shorter than one input value
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Result<Vec<u8>, String> {
+    let (mut acc, mut bits) = (0u32, 0u32);
+    let max = (1u32 << to) - 1;
+    let mut out = Vec::with_capacity(data.len() * from as usize / to as usize + 1);
+    for &value in data {
+        acc = (acc << from) | value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            out.push(((acc >> bits) & max) as u8);
+        }
+    }
+    if pad && bits > 0 {
+        out.push(((acc << (to - bits)) & max) as u8);
+    } else if !pad && (bits >= from || (acc << (to - bits)) & max != 0) {
+        return Err("Invalid padding".to_string());
+    }
+    Ok(out)
+}
+
+/// Encodes `data` under `hrp`. Strings are not capped at BIP-173's 90 characters, as
+/// addresses holding full public keys run longer
+pub fn encode(hrp: &str, data: &[u8], variant: Variant) -> String {
+    let values = convert_bits(data, 8, 5, true).unwrap_or_default();
+    let checksum = polymod(hrp_expand(hrp).chain(values.iter().copied()).chain([0; CHECKSUM_LEN])) ^ variant.constant();
+    let checksum = (0..CHECKSUM_LEN).map(|i| ((checksum >> (5 * (5 - i))) & 31) as u8);
+    let data: String = values.into_iter().chain(checksum).map(|value| CHARSET[value as usize] as char).collect();
+    format!("{}1{}", hrp, data)
+}
+
+/// Decodes either variant, returning the lowercase human-readable part, the data and the variant
+pub fn decode(text: &str) -> Result<(String, Vec<u8>, Variant), String> {
+    if text.chars().any(|c| c.is_ascii_lowercase()) && text.chars().any(|c| c.is_ascii_uppercase()) {
+        return Err("Mixed-case bech32 string".to_string());
+    }
+    let text = text.to_ascii_lowercase();
+    let (hrp, data) = text.rsplit_once('1').ok_or_else(|| "Missing bech32 separator".to_string())?;
+    if hrp.is_empty() || hrp.len() > 83 || !hrp.bytes().all(|c| (33..=126).contains(&c)) {
+        return Err("Invalid bech32 human-readable part".to_string());
+    }
+    if data.len() < CHECKSUM_LEN {
+        return Err("Bech32 string is too short".to_string());
+    }
+    let values = data
+        .bytes()
+        .map(|c| CHARSET.iter().position(|&allowed| allowed == c).map(|value| value as u8))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| "Invalid bech32 character".to_string())?;
+    let variant = match polymod(hrp_expand(hrp).chain(values.iter().copied())) {
+        checksum if checksum == Variant::Bech32.constant() => Variant::Bech32,
+        checksum if checksum == Variant::Bech32m.constant() => Variant::Bech32m,
+        _ => return Err("Invalid bech32 checksum".to_string()),
+    };
+    let data = convert_bits(&values[..values.len() - CHECKSUM_LEN], 5, 8, false)?;
+    Ok((hrp.to_string(), data, variant))
+}
+
+/// Address as users see it: bech32m under `ADDRESS_HRP`
+pub fn encode_address(address: &[u8]) -> String {
+    encode(ADDRESS_HRP, address, Variant::Bech32m)
+}
+
+pub fn decode_address(text: &str) -> Result<Vec<u8>, String> {
+    let (hrp, address, _) = decode(text).map_err(|e| format!("Invalid address {}: {}", text, e))?;
+    if hrp != ADDRESS_HRP {
+        return Err(format!("Address {} is not a {} address", text, ADDRESS_HRP));
+    }
+    Ok(address)
+}
+
+/// Address typed by a user: bech32 under `ADDRESS_HRP`, or hex with or without `0x`
+pub fn parse_address(input: &str) -> Result<Vec<u8>, String> {
+    let prefix = format!("{}1", ADDRESS_HRP);
+    if input.to_ascii_lowercase().starts_with(&prefix) {
+        return decode_address(input);
+    }
+    hex::decode(input.trim_start_matches("0x")).map_err(|e| format!("Invalid address {}: {}", input, e))
+}
+
+/// `encode_address` with the middle of long addresses elided, keeping the checksum in view
+pub fn short_address(address: &[u8]) -> String {
+    let full = encode_address(address);
+    if full.len() <= 48 {
+        return full;
+    }
+    format!("{}..{}", &full[..24], &full[full.len() - 12..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bech32_addresses() {
+        // Reference vectors from BIP-173 and BIP-350
+        for (valid, variant) in [
+            ("A12UEL5L", Variant::Bech32),
+            ("abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw", Variant::Bech32),
+            ("A1LQFN3A", Variant::Bech32m),
+            ("abcdef1l7aum6echk45nj3s0wdvt2fg8x9yrzpqzd3ryx", Variant::Bech32m),
+        ] {
+            let (hrp, data, decoded) = decode(valid).unwrap();
+            assert_eq!(decoded, variant);
+            assert_eq!(encode(&hrp, &data, variant), valid.to_ascii_lowercase());
+        }
+        for invalid in ["A1G7SGD8", "a12UEL5L", "1qzzfhee", "x1b4n0q5v", "li1dgmt3", "abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxx"] {
+            assert!(decode(invalid).is_err(), "{}", invalid);
+        }
+
+        // Full public keys round trip, and a changed character fails the checksum
+        let key: Vec<u8> = (0..=255).cycle().take(1312).collect();
+        let address = encode_address(&key);
+        assert!(address.starts_with("tri1"));
+        assert_eq!(parse_address(&address).unwrap(), key);
+        assert_eq!(parse_address(&address.to_ascii_uppercase()).unwrap(), key);
+        assert_eq!(parse_address(&format!("0x{}", hex::encode(&key))).unwrap(), key);
+        let mut typo = address.into_bytes();
+        typo[10] = if typo[10] == b'q' { b'p' } else { b'q' };
+        assert!(parse_address(&String::from_utf8(typo).unwrap()).is_err());
+        assert!(decode_address(&encode("bc", &key, Variant::Bech32m)).is_err());
+        assert_eq!(short_address(&[0xaa; 20]).len(), 42);
+        assert!(short_address(&key).contains(".."));
+
+        println!("   Bech32 addresses working!");
+    }
+}
@@ -0,0 +1,125 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt::Write;
+
+/// Integers written as floats, such as `1e3` or `-0`, are exact below this
+const MAX_SAFE_FLOAT: f64 = 9_007_199_254_740_992.0;
+
+/// Canonical JSON of `value`, for payloads signed as JSON: object keys sorted by UTF-16
+/// code units, no whitespace, integers in plain decimal and strings escaping only what
+/// JSON requires. Fractions, and integers written as floats beyond 2^53, are refused as
+/// their formatting or precision is not portable
+pub fn to_string<T: Serialize>(value: &T) -> Result<String, String> {
+    let value = serde_json::to_value(value).map_err(|e| format!("Value has no JSON form: {}", e))?;
+    let mut out = String::new();
+    write_value(&value, &mut out)?;
+    Ok(out)
+}
+
+/// Re-encodes JSON text in canonical form
+pub fn canonicalize(json: &str) -> Result<String, String> {
+    let value: Value = serde_json::from_str(json).map_err(|e| format!("Invalid JSON: {}", e))?;
+    to_string(&value)
+}
+
+pub fn is_canonical(json: &str) -> bool {
+    canonicalize(json).is_ok_and(|canonical| canonical == json)
+}
+
+fn write_value(value: &Value, out: &mut String) -> Result<(), String> {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(flag) => out.push_str(if *flag { "true" } else { "false" }),
+        Value::Number(number) => match (number.as_u64(), number.as_i64(), number.as_f64()) {
+            (Some(n), _, _) => out.push_str(&n.to_string()),
+            (None, Some(n), _) => out.push_str(&n.to_string()),
+            (None, None, Some(n)) if n.fract() == 0.0 && n.abs() < MAX_SAFE_FLOAT => out.push_str(&(n as i64).to_string()),
+            _ => return Err(format!("Number {} is not an exact integer", number)),
+        },
+        Value::String(text) => write_string(text, out),
+        Value::Array(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_value(item, out)?;
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            out.push('{');
+            for (index, (key, item)) in entries.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_string(key, out);
+                out.push(':');
+                write_value(item, out)?;
+            }
+            out.push('}');
+        }
+    }
+    Ok(())
+}
+
+fn write_string(text: &str, out: &mut String) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{QuantumKeyPair, QuantumSignature};
+    use crate::storage::blocks::{JsonSigningPayload, Transaction};
+
+    #[test]
+    fn test_canonical_json() {
+        assert_eq!(canonicalize(r#"{ "b": 1, "a": [true, null, "x"] }"#).unwrap(), r#"{"a":[true,null,"x"],"b":1}"#);
+        assert_eq!(canonicalize("\"tab\\t \\u0001 \\u00e9\"").unwrap(), "\"tab\\t \\u0001 é\"");
+        // Keys sort by UTF-16 code units, which puts astral characters before U+FF61
+        assert_eq!(canonicalize(r#"{"\uff61":1,"\ud83d\ude00":2}"#).unwrap(), "{\"😀\":2,\"｡\":1}");
+        assert_eq!(to_string(&u64::MAX).unwrap(), "18446744073709551615");
+        assert_eq!(canonicalize("[1e3, -0]").unwrap(), "[1000,0]");
+        assert!(canonicalize("1.5").is_err() && canonicalize("1e300").is_err());
+        assert!(is_canonical(r#"{"a":1}"#) && !is_canonical(r#"{"a": 1}"#));
+
+        // A wallet may sign the JSON payload; it carries the same fields as the bincode one
+        let (sender, sponsor) = (QuantumKeyPair::generate(), QuantumKeyPair::generate());
+        let mut tx = Transaction::new(sender.public_key().to_vec(), vec![0xcc; 32], 1_000, 1, 0, vec![], QuantumSignature::new(vec![]))
+            .with_valid_until(100)
+            .with_fee_payer(sponsor.public_key().to_vec());
+        let json = tx.canonical_signing_json();
+        let payload = JsonSigningPayload::parse(&json).unwrap();
+        assert_eq!(payload.bincode_payload().unwrap(), tx.get_signing_data());
+        assert!(JsonSigningPayload::parse(&json.replace(':', ": ")).is_err());
+
+        tx.signature = sender.sign(json.as_bytes()).unwrap();
+        let sponsor_json = tx.canonical_fee_payer_json();
+        assert_eq!(JsonSigningPayload::parse(&sponsor_json).unwrap().bincode_payload().unwrap(), tx.fee_payer_signing_data());
+        tx.fee_payer.as_mut().unwrap().signature = sponsor.sign(&tx.fee_payer_signing_data()).unwrap();
+        tx.check().unwrap();
+        tx.amount += 1;
+        assert!(tx.check().is_err());
+
+        println!("   Canonical JSON working!");
+    }
+}
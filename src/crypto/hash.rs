@@ -34,6 +34,12 @@ pub fn hash256(data: &[u8]) -> Hash256 {
     Hash256::from_bytes(data)
 }
 
+/// Whether `a` and `b` are equal, taking the same time wherever they differ so a secret
+/// compared against cannot be guessed byte by byte
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -44,6 +50,9 @@ mod tests {
         let hash1 = hash256(data);
         let hash2 = hash256(data);
         assert_eq!(hash1, hash2);
+        assert!(constant_time_eq(hash1.as_bytes(), hash2.as_bytes()));
+        assert!(!constant_time_eq(hash1.as_bytes(), hash256(b"other").as_bytes()));
+        assert!(!constant_time_eq(b"token", b"token-longer"));
         println!("Hash256 working!");
     }
 }
\ No newline at end of file
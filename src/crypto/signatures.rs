@@ -1,5 +1,5 @@
 use pqcrypto_dilithium::dilithium2;
-use pqcrypto_traits::sign::{PublicKey, SecretKey, SignedMessage, DetachedSignature};
+use pqcrypto_traits::sign::{PublicKey, SecretKey, DetachedSignature};
 use serde::{Deserialize, Serialize};
 use crate::{Result, TriUnityError};
 
@@ -28,7 +28,7 @@ impl QuantumKeyPair {
         let sk = dilithium2::SecretKey::from_bytes(&self.secret_key)
             .map_err(|_| TriUnityError::QuantumSignatureError)?;
         
-        let signature = dilithium2::sign(message, &sk);
+        let signature = dilithium2::detached_sign(message, &sk);
         
         Ok(QuantumSignature {
             signature_data: signature.as_bytes().to_vec(),
@@ -40,9 +40,27 @@ impl QuantumKeyPair {
         &self.public_key
     }
 
+    /// Reads a key pair saved as JSON, checking it can sign
+    pub fn load(path: &str) -> std::result::Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read key file {}: {}", path, e))?;
+        let keypair: Self = serde_json::from_str(&contents)
+            .map_err(|e| format!("Invalid key file {}: {}", path, e))?;
+        let probe = keypair.sign(b"key check").map_err(|e| e.to_string())?;
+        if !probe.verify(b"key check", &keypair.public_key) {
+            return Err(format!("Key file {} holds mismatched keys", path));
+        }
+        Ok(keypair)
+    }
+
     pub fn address(&self) -> [u8; 20] {
+        Self::address_of(&self.public_key)
+    }
+
+    /// Short address of a public key: the first 20 bytes of its SHA3-256 hash
+    pub fn address_of(public_key: &[u8]) -> [u8; 20] {
         use sha3::{Digest, Sha3_256};
-        let hash = Sha3_256::digest(&self.public_key);
+        let hash = Sha3_256::digest(public_key);
         let mut address = [0u8; 20];
         address.copy_from_slice(&hash[..20]);
         address
@@ -69,6 +87,7 @@ impl QuantumSignature {
     }
 
     pub fn verify(&self, message: &[u8], public_key: &[u8]) -> bool {
+        super::verification::record_verification();
         let pk = dilithium2::PublicKey::from_bytes(public_key);
         let sig = dilithium2::DetachedSignature::from_bytes(&self.signature_data);
         
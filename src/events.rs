@@ -0,0 +1,181 @@
+use serde::Serialize;
+use std::net::SocketAddr;
+use tokio::sync::broadcast;
+
+use crate::cli::inspect::short_hex;
+use crate::consensus::metrics::{SecurityEventType, SecuritySeverity};
+use crate::consensus::pin::PathPin;
+use crate::consensus::router::ConsensusPath;
+
+/// Events buffered per subscriber before slow subscribers start missing them
+pub const DEFAULT_EVENT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum NodeEvent {
+    /// A block became part of the canonical chain. `produced` is set when this node sealed it
+    BlockImported {
+        height: u64,
+        #[serde(with = "hex::serde")]
+        hash: [u8; 32],
+        transactions: usize,
+        produced: bool,
+    },
+    /// The block can no longer be reorganised away
+    BlockFinalized {
+        height: u64,
+        #[serde(with = "hex::serde")]
+        hash: [u8; 32],
+    },
+    /// A canonical block displaced by a reorg; its transactions go back to the mempool
+    /// where they still apply
+    BlockOrphaned {
+        height: u64,
+        #[serde(with = "hex::serde")]
+        hash: [u8; 32],
+    },
+    TxAccepted {
+        #[serde(with = "hex::serde")]
+        hash: [u8; 32],
+    },
+    /// The chain agreed on another consensus path, in force from the current path window
+    PathSwitched {
+        from: ConsensusPath,
+        to: ConsensusPath,
+    },
+    /// The router or a pin moved this node to another path, which the blocks it produces
+    /// propose until the chain agrees
+    PathProposed {
+        from: ConsensusPath,
+        to: ConsensusPath,
+    },
+    /// An operator pinned the consensus path, or with `None` the pin was lifted or expired
+    PathPinned {
+        pin: Option<PathPin>,
+    },
+    PeerConnected {
+        #[serde(with = "hex::serde")]
+        node_id: Vec<u8>,
+        address: SocketAddr,
+    },
+    /// Misbehaviour that was detected and refused, e.g. an invalid block from a peer
+    SecurityEvent {
+        source: String,
+        reason: String,
+        kind: SecurityEventType,
+        severity: SecuritySeverity,
+    },
+}
+
+impl NodeEvent {
+    /// Variant name, matching the serialized `type` tag
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::BlockImported { .. } => "BlockImported",
+            Self::BlockFinalized { .. } => "BlockFinalized",
+            Self::BlockOrphaned { .. } => "BlockOrphaned",
+            Self::TxAccepted { .. } => "TxAccepted",
+            Self::PathSwitched { .. } => "PathSwitched",
+            Self::PathProposed { .. } => "PathProposed",
+            Self::PathPinned { .. } => "PathPinned",
+            Self::PeerConnected { .. } => "PeerConnected",
+            Self::SecurityEvent { .. } => "SecurityEvent",
+        }
+    }
+}
+
+/// Typed broadcast channel that lets the node publish what happens without knowing
+/// who listens; the dashboard, metrics and logging each subscribe independently
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<NodeEvent>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        Self { sender: broadcast::channel(capacity).0 }
+    }
+
+    /// Returns how many subscribers received the event
+    pub fn publish(&self, event: NodeEvent) -> usize {
+        self.sender.send(event).unwrap_or(0)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<NodeEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(DEFAULT_EVENT_CAPACITY)
+    }
+}
+
+/// Prints every event except transaction admissions and finalizations until the bus closes
+pub async fn log_events(name: String, mut events: broadcast::Receiver<NodeEvent>) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                eprintln!("[{}] Event log skipped {} events", name, missed);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        match event {
+            NodeEvent::BlockImported { height, hash, transactions, produced } => println!(
+                "[{}] {} block {} {} with {} transactions",
+                name,
+                if produced { "Produced" } else { "Imported" },
+                height,
+                short_hex(&hash),
+                transactions
+            ),
+            NodeEvent::BlockOrphaned { height, hash } => {
+                println!("[{}] Orphaned block {} {}", name, height, short_hex(&hash))
+            }
+            NodeEvent::BlockFinalized { .. } | NodeEvent::TxAccepted { .. } => {}
+            NodeEvent::PathSwitched { from, to } => println!("[{}] Consensus path {:?} -> {:?}", name, from, to),
+            NodeEvent::PathProposed { to, .. } => println!("[{}] Proposing consensus path {:?}", name, to),
+            NodeEvent::PathPinned { pin: Some(pin) } => {
+                println!("[{}] Consensus path pinned to {:?} until {}: {}", name, pin.mode, pin.expires_at, pin.reason)
+            }
+            NodeEvent::PathPinned { pin: None } => println!("[{}] Consensus path unpinned", name),
+            NodeEvent::PeerConnected { node_id, address } => {
+                println!("[{}] Peer {} connected from {}", name, short_hex(&node_id), address)
+            }
+            NodeEvent::SecurityEvent { source, reason, severity, .. } => {
+                eprintln!("[{}] Security {:?}: {} ({})", name, severity, reason, source)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_event_bus_fan_out() {
+        let bus = EventBus::default();
+        assert_eq!(bus.publish(NodeEvent::TxAccepted { hash: [1; 32] }), 0);
+
+        let (mut dashboard, mut metrics) = (bus.subscribe(), bus.subscribe());
+        let event = NodeEvent::BlockImported { height: 3, hash: [2; 32], transactions: 5, produced: true };
+        assert_eq!(bus.publish(event), 2);
+        for receiver in [&mut dashboard, &mut metrics] {
+            assert!(matches!(receiver.recv().await.unwrap(), NodeEvent::BlockImported { height: 3, .. }));
+        }
+
+        let json = serde_json::to_value(NodeEvent::TxAccepted { hash: [0xab; 32] }).unwrap();
+        assert_eq!(json["type"], "TxAccepted");
+        assert_eq!(NodeEvent::TxAccepted { hash: [0xab; 32] }.kind(), "TxAccepted");
+        assert_eq!(json["hash"], hex::encode([0xab; 32]));
+
+        drop(bus);
+        assert!(dashboard.recv().await.is_err());
+
+        println!("   Event bus working!");
+    }
+}
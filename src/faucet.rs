@@ -0,0 +1,288 @@
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use warp::Filter;
+
+use crate::crypto::bech32::{parse_address, short_address};
+use crate::crypto::{QuantumKeyPair, QuantumSignature};
+use crate::log;
+use crate::node::{blocking, Node};
+use crate::storage::blocks::Transaction;
+
+/// Genesis balance of the testnet faucet account, in base units (10M TRI)
+pub const FAUCET_GENESIS_BALANCE: u64 = 10_000_000 * 100_000_000;
+
+/// Time a client has to solve a challenge
+pub const CHALLENGE_TTL: Duration = Duration::from_secs(300);
+
+/// Unsolved challenges kept before new ones are refused
+const MAX_OPEN_CHALLENGES: usize = 10_000;
+
+const CHALLENGE_DOMAIN: &[u8] = b"triunity/faucet";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FaucetConfig {
+    /// Amount sent per request, in base units
+    pub drip: u64,
+    pub fee: u64,
+    /// Time an address, and separately a client IP, waits between drips
+    pub cooldown_secs: u64,
+    /// Leading zero bits a challenge solution's hash needs
+    pub difficulty: u8,
+}
+
+impl Default for FaucetConfig {
+    fn default() -> Self {
+        Self { drip: 10 * 100_000_000, fee: 1, cooldown_secs: 3600, difficulty: 20 }
+    }
+}
+
+/// Proof-of-work puzzle a client solves before each drip, standing in for a captcha
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Challenge {
+    #[serde(with = "hex::serde")]
+    pub challenge: [u8; 16],
+    pub difficulty: u8,
+    pub expires_in_secs: u64,
+}
+
+impl Challenge {
+    /// First nonce solving the challenge for `address`
+    pub fn solve(&self, address: &[u8]) -> u64 {
+        (0..).find(|nonce| solves(&self.challenge, address, *nonce, self.difficulty)).unwrap_or_default()
+    }
+}
+
+/// Body of `POST /faucet/request`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DripRequest {
+    /// Recipient, as `tri1...`, hex or `0x` hex
+    pub address: String,
+    #[serde(with = "hex::serde")]
+    pub challenge: [u8; 16],
+    pub nonce: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Drip {
+    #[serde(with = "hex::serde")]
+    pub hash: [u8; 32],
+    pub amount: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FaucetError {
+    /// The address or client was funded within the cooldown
+    Cooldown { retry_after_secs: u64 },
+    Rejected(String),
+}
+
+impl fmt::Display for FaucetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Cooldown { retry_after_secs } => write!(f, "Already funded, retry in {}s", retry_after_secs),
+            Self::Rejected(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct FaucetState {
+    challenges: HashMap<[u8; 16], Instant>,
+    by_address: HashMap<Vec<u8>, Instant>,
+    by_ip: HashMap<IpAddr, Instant>,
+}
+
+/// Sends small transfers from a funded testnet account to whoever solves a challenge,
+/// at most once per cooldown for each address and each client IP
+pub struct Faucet {
+    node: Arc<Node>,
+    keypair: QuantumKeyPair,
+    config: FaucetConfig,
+    state: Mutex<FaucetState>,
+}
+
+impl Faucet {
+    pub fn new(node: Arc<Node>, keypair: QuantumKeyPair, config: FaucetConfig) -> Self {
+        Self { node, keypair, config, state: Mutex::new(FaucetState::default()) }
+    }
+
+    pub fn address(&self) -> &[u8] {
+        self.keypair.public_key()
+    }
+
+    pub fn challenge(&self) -> Result<Challenge, FaucetError> {
+        let mut state = self.state.lock().unwrap();
+        state.challenges.retain(|_, issued| issued.elapsed() < CHALLENGE_TTL);
+        if state.challenges.len() >= MAX_OPEN_CHALLENGES {
+            return Err(FaucetError::Rejected("Too many open challenges, try again later".to_string()));
+        }
+        let challenge = rand::random::<[u8; 16]>();
+        state.challenges.insert(challenge, Instant::now());
+        Ok(Challenge { challenge, difficulty: self.config.difficulty, expires_in_secs: CHALLENGE_TTL.as_secs() })
+    }
+
+    /// Checks the solved challenge and cooldowns, then submits the transfer. Each
+    /// challenge is spent by its first attempt, successful or not
+    pub fn request(&self, request: &DripRequest, client: Option<IpAddr>) -> Result<Drip, FaucetError> {
+        let address = parse_address(&request.address).map_err(FaucetError::Rejected)?;
+        let cooldown = Duration::from_secs(self.config.cooldown_secs);
+        let mut state = self.state.lock().unwrap();
+        let issued = state.challenges.remove(&request.challenge).filter(|issued| issued.elapsed() < CHALLENGE_TTL);
+        if issued.is_none() {
+            return Err(FaucetError::Rejected("Unknown or expired challenge".to_string()));
+        }
+        if !solves(&request.challenge, &address, request.nonce, self.config.difficulty) {
+            return Err(FaucetError::Rejected("Challenge not solved".to_string()));
+        }
+        let last = [state.by_address.get(&address), client.and_then(|ip| state.by_ip.get(&ip))];
+        if let Some(waited) = last.into_iter().flatten().map(Instant::elapsed).filter(|waited| *waited < cooldown).min() {
+            return Err(FaucetError::Cooldown { retry_after_secs: (cooldown - waited).as_secs().max(1) });
+        }
+
+        let nonce = self.node.next_nonce(self.address());
+        let mut tx = Transaction::new(self.address().to_vec(), address.clone(), self.config.drip, self.config.fee, nonce, vec![], QuantumSignature::new(vec![]));
+        tx.signature = self.keypair.sign(&tx.get_signing_data()).map_err(|e| FaucetError::Rejected(e.to_string()))?;
+        let hash = self.node.submit_transaction(tx).map_err(|e| FaucetError::Rejected(format!("Faucet transfer refused: {}", e)))?;
+        state.by_address.retain(|_, funded| funded.elapsed() < cooldown);
+        state.by_ip.retain(|_, funded| funded.elapsed() < cooldown);
+        state.by_address.insert(address.clone(), Instant::now());
+        if let Some(ip) = client {
+            state.by_ip.insert(ip, Instant::now());
+        }
+        log!(Info, "Faucet sent {} to {}", self.config.drip, short_address(&address));
+        Ok(Drip { hash, amount: self.config.drip })
+    }
+
+    /// `GET /faucet/challenge` and `POST /faucet/request`
+    pub fn routes(self: Arc<Self>) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let faucet = self.clone();
+        let challenge = warp::path!("faucet" / "challenge").and(warp::get()).map(move || reply(faucet.challenge()));
+        let request = warp::path!("faucet" / "request")
+            .and(warp::post())
+            .and(warp::addr::remote())
+            .and(warp::body::content_length_limit(4096))
+            .and(warp::body::json())
+            .and_then(move |remote: Option<SocketAddr>, request: DripRequest| {
+                let faucet = self.clone();
+                async move {
+                    let result = blocking(move || faucet.request(&request, remote.map(|remote| remote.ip())))
+                        .await
+                        .unwrap_or_else(|e| Err(FaucetError::Rejected(e)));
+                    Ok::<_, warp::Rejection>(reply(result))
+                }
+            });
+        challenge.or(request)
+    }
+}
+
+fn reply<T: Serialize>(result: Result<T, FaucetError>) -> warp::reply::WithStatus<warp::reply::Json> {
+    let status = match &result {
+        Ok(_) => warp::http::StatusCode::OK,
+        Err(FaucetError::Cooldown { .. }) => warp::http::StatusCode::TOO_MANY_REQUESTS,
+        Err(FaucetError::Rejected(_)) => warp::http::StatusCode::BAD_REQUEST,
+    };
+    let body = match result {
+        Ok(value) => serde_json::to_value(value).unwrap_or_default(),
+        Err(e) => serde_json::json!({ "error": e.to_string() }),
+    };
+    warp::reply::with_status(warp::reply::json(&body), status)
+}
+
+/// Asks the faucet at `url` (e.g. `http://127.0.0.1:8090`) to fund `address`, solving
+/// its challenge locally
+pub async fn request_drip(url: &str, address: &str) -> Result<Drip, String> {
+    let recipient = parse_address(address)?;
+    let client = hyper::Client::new();
+    let url = url.trim_end_matches('/');
+    let challenge: Challenge = read_reply(client.get(format!("{}/faucet/challenge", url).parse().map_err(|e| format!("Invalid faucet URL: {}", e))?).await).await?;
+    let seed = challenge.challenge;
+    let nonce = blocking(move || challenge.solve(&recipient)).await?;
+    let body = serde_json::to_vec(&DripRequest { address: address.to_string(), challenge: seed, nonce }).map_err(|e| e.to_string())?;
+    let request = hyper::Request::post(format!("{}/faucet/request", url))
+        .header("content-type", "application/json")
+        .body(hyper::Body::from(body))
+        .map_err(|e| e.to_string())?;
+    read_reply(client.request(request).await).await
+}
+
+async fn read_reply<T: serde::de::DeserializeOwned>(response: Result<hyper::Response<hyper::Body>, hyper::Error>) -> Result<T, String> {
+    let response = response.map_err(|e| format!("Faucet unreachable: {}", e))?;
+    let status = response.status();
+    let bytes = hyper::body::to_bytes(response.into_body()).await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap_or_default();
+        return Err(body["error"].as_str().map_or_else(|| format!("Faucet answered {}", status), str::to_string));
+    }
+    serde_json::from_slice(&bytes).map_err(|e| format!("Invalid faucet reply: {}", e))
+}
+
+/// Whether `nonce` gives the challenge for `address` a hash with `difficulty` leading zero bits
+pub fn solves(challenge: &[u8; 16], address: &[u8], nonce: u64, difficulty: u8) -> bool {
+    let mut hasher = Sha3_256::new();
+    hasher.update(CHALLENGE_DOMAIN);
+    hasher.update(challenge);
+    hasher.update(address);
+    hasher.update(nonce.to_be_bytes());
+    let digest = hasher.finalize();
+    let zeros = digest.iter().position(|byte| *byte != 0).map_or(256, |index| index as u32 * 8 + digest[index].leading_zeros());
+    zeros >= difficulty as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::database::BlockchainDB;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_faucet_drips() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_faucet");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let keypair = QuantumKeyPair::generate();
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        db.store_genesis_allocations(&[(keypair.public_key().to_vec(), FAUCET_GENESIS_BALANCE)]).unwrap();
+        let node = Arc::new(Node::open(db).unwrap());
+        let config = FaucetConfig { difficulty: 8, ..FaucetConfig::default() };
+        let faucet = Arc::new(Faucet::new(node.clone(), keypair, config));
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let ask = |address: &[u8], client: IpAddr| {
+            let challenge = faucet.challenge().unwrap();
+            let nonce = challenge.solve(address);
+            faucet.request(&DripRequest { address: hex::encode(address), challenge: challenge.challenge, nonce }, Some(client))
+        };
+
+        let drip = ask(&[0xaa; 32], ip).unwrap();
+        assert_eq!(drip.amount, 10 * 100_000_000);
+        assert_eq!(node.pending_transactions(), 1);
+
+        // The same address, or another from the same client, waits out the cooldown
+        assert!(matches!(ask(&[0xaa; 32], "198.51.100.1".parse().unwrap()), Err(FaucetError::Cooldown { .. })));
+        assert!(matches!(ask(&[0xbb; 32], ip), Err(FaucetError::Cooldown { .. })));
+        // Pending drips are chained by nonce
+        ask(&[0xbb; 32], "198.51.100.2".parse().unwrap()).unwrap();
+        assert_eq!(node.next_nonce(faucet.address()), 2);
+
+        // Challenges are single use and must be solved for the requested address
+        let challenge = faucet.challenge().unwrap();
+        let wrong = (0..).find(|nonce| !solves(&challenge.challenge, &[0xcc; 32], *nonce, 8)).unwrap();
+        let request = DripRequest { address: hex::encode([0xcc; 32]), challenge: challenge.challenge, nonce: wrong };
+        assert_eq!(faucet.request(&request, None), Err(FaucetError::Rejected("Challenge not solved".to_string())));
+        let request = DripRequest { nonce: challenge.solve(&[0xcc; 32]), ..request };
+        assert!(faucet.request(&request, None).unwrap_err().to_string().contains("expired challenge"));
+
+        // Over HTTP the client solves the challenge itself; refusals carry the reason
+        let (address, server) = warp::serve(faucet.clone().routes()).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        let url = format!("http://{}", address);
+        assert_eq!(request_drip(&url, &hex::encode([0xdd; 32])).await.unwrap().amount, 10 * 100_000_000);
+        assert!(request_drip(&url, &hex::encode([0xdd; 32])).await.unwrap_err().starts_with("Already funded"));
+
+        println!("   Faucet drips working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
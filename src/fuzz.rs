@@ -0,0 +1,173 @@
+//! Entry points for the cargo-fuzz targets in `fuzz/`. Each takes arbitrary bytes,
+//! rejects what does not parse, and panics only when parsed input breaks an invariant
+
+use crate::api::rpc::{self, RpcResponse};
+use crate::network::message::{self, NetworkMessage, Reassembly};
+use crate::storage::blocks::Block;
+use crate::storage::merkle::{MerkleProof, MerkleTree};
+
+/// Reads `data` as a stream of wire frames, returning the messages it completes. Every
+/// message decoded must encode and decode back to itself
+pub fn network_message(data: &[u8]) -> Vec<NetworkMessage> {
+    let (mut reader, mut reassembly) = (data, Reassembly::default());
+    let mut messages = Vec::new();
+    while let Ok(message) = futures::executor::block_on(message::read_message(&mut reader, &mut reassembly)) {
+        let encoded = message::encode(&message).expect("decoded message re-encodes");
+        let decoded = message::decode(&encoded).expect("re-encoded message decodes");
+        assert_eq!(message::encode(&decoded).unwrap(), encoded, "{} does not round-trip", message.kind());
+        messages.push(message);
+    }
+    messages
+}
+
+/// Deserializes a block as stored and gossiped and validates it. Every transaction of a
+/// block whose merkle root checks out must have an inclusion proof against that root
+pub fn block(data: &[u8]) -> Option<Block> {
+    let block: Block = bincode::deserialize(data).ok()?;
+    let _ = (block.validate(), block.hash(), block.size());
+    if block.has_valid_merkle_root() {
+        for index in 0..block.transactions.len() {
+            let proof = block.transaction_proof(index).expect("proof for an included transaction");
+            assert_eq!(proof.root, block.header.merkle_root);
+            assert!(MerkleTree::verify_proof(&proof), "proof of transaction {} does not verify", index);
+        }
+    }
+    Some(block)
+}
+
+/// Verifies `data` as a serialized proof, then as the leaves of a tree whose proofs must
+/// all verify. Returns whether the serialized proof verified
+pub fn merkle_proof(data: &[u8]) -> bool {
+    let verified = bincode::deserialize::<MerkleProof>(data).is_ok_and(|proof| MerkleTree::verify_proof(&proof));
+    let leaves: Vec<Vec<u8>> = data.chunks(32).map(<[u8]>::to_vec).collect();
+    let tree = MerkleTree::new(&leaves);
+    for index in 0..leaves.len() {
+        let proof = tree.generate_proof(index).expect("proof for a leaf in the tree");
+        assert!(MerkleTree::verify_proof(&proof), "proof of leaf {} does not verify", index);
+    }
+    verified
+}
+
+/// Parses an RPC request body the way the HTTP and WebSocket transports do. Both the
+/// request and the parse error sent back must serialize
+pub fn rpc_request(data: &[u8]) -> Option<rpc::RpcRequest> {
+    match rpc::parse_request(data) {
+        Ok(request) => {
+            let reparsed = rpc::parse_request(&serde_json::to_vec(&request).unwrap()).expect("serialized request parses");
+            assert_eq!(reparsed.method, request.method);
+            Some(request)
+        }
+        Err(failure) => {
+            serde_json::to_vec::<RpcResponse>(&failure).unwrap();
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{QuantumKeyPair, QuantumSignature};
+    use crate::network::compression::{Compression, CompressionPolicy};
+    use crate::network::handshake::DisconnectReason;
+    use crate::storage::blocks::{ConsensusData, Transaction};
+    use serde_json::json;
+    use sha3::{Digest, Sha3_256};
+    use std::net::SocketAddr;
+
+    fn signed_block() -> Block {
+        let keypair = QuantumKeyPair::generate();
+        let transactions = (0..3)
+            .map(|nonce| {
+                let mut tx = Transaction::new(keypair.public_key().to_vec(), vec![0xcc; 32], 10, 1, nonce, vec![], QuantumSignature::new(vec![]));
+                tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+                tx
+            })
+            .collect();
+        Block::new([7; 32], transactions, 4, ConsensusData::default())
+    }
+
+    /// Seeds for each fuzz target, built from the same structures the unit tests use
+    fn corpus() -> Vec<(&'static str, Vec<Vec<u8>>)> {
+        let block = signed_block();
+        let messages = [
+            NetworkMessage::Hello {
+                node_id: vec![1; 32],
+                protocols: vec![message::PROTOCOL_VERSION.to_string()],
+                capabilities: Vec::new(),
+                listen_port: 7000,
+                next_height: 5,
+                observed: SocketAddr::from(([127, 0, 0, 1], 7001)),
+                external: None,
+                compression: vec![Compression::Zstd, Compression::Snappy],
+                chain_id: 1337,
+                genesis: Some([7; 32]),
+            },
+            NetworkMessage::NewBlock(block.clone()),
+            NetworkMessage::Blocks(vec![block.clone(); 40]),
+            NetworkMessage::GetHeaders { from: 0, count: 10 },
+            NetworkMessage::Disconnect(DisconnectReason::Requested),
+        ];
+        let mut frames = Vec::new();
+        for message in &messages {
+            for compression in [None, Some(Compression::Zstd), Some(Compression::Snappy)] {
+                let policy = compression.map(|compression| CompressionPolicy { compression, min_size: 0 });
+                let (payload, _) = message::encode_with(message, policy).unwrap();
+                frames.push(message::frames(&payload, message.lane()).concat());
+            }
+        }
+
+        let proof = block.transaction_proof(1).unwrap();
+        let odd_tree = MerkleTree::new(&(0..5u8).map(|leaf| vec![leaf; 32]).collect::<Vec<_>>());
+        let requests = [
+            json!({"jsonrpc": "2.0", "id": 1, "method": "chain_getBlock", "params": [0]}),
+            json!({"jsonrpc": "2.0", "id": "a", "method": "logs_query", "params": [{"fromBlock": 0, "topics": ["01020304"]}]}),
+            json!({"jsonrpc": "2.0", "id": null, "method": "admin_setLogLevel", "params": ["debug"]}),
+        ];
+        vec![
+            ("network_message", frames),
+            ("block", vec![bincode::serialize(&block).unwrap(), bincode::serialize(&Block::new([0; 32], vec![], 0, ConsensusData::default())).unwrap()]),
+            ("merkle_proof", vec![bincode::serialize(&proof).unwrap(), bincode::serialize(&odd_tree.generate_proof(4).unwrap()).unwrap()]),
+            ("rpc_request", requests.iter().map(|request| serde_json::to_vec(request).unwrap()).collect()),
+        ]
+    }
+
+    fn run(target: &str, data: &[u8]) -> bool {
+        match target {
+            "network_message" => !network_message(data).is_empty(),
+            "block" => block(data).is_some(),
+            "merkle_proof" => merkle_proof(data),
+            "rpc_request" => rpc_request(data).is_some(),
+            other => panic!("Unknown fuzz target {}", other),
+        }
+    }
+
+    /// Set `TRIUNITY_FUZZ_CORPUS=fuzz/corpus` to write the seeds where cargo-fuzz reads them
+    #[test]
+    fn test_fuzz_entry_points() {
+        let corpus_dir = std::env::var_os("TRIUNITY_FUZZ_CORPUS").map(std::path::PathBuf::from);
+        for (target, seeds) in corpus() {
+            for seed in &seeds {
+                assert!(run(target, seed), "{} rejects its own seed", target);
+                // Truncated and corrupted seeds are rejected or accepted, never a panic
+                for cut in [0, 1, seed.len() / 2, seed.len().saturating_sub(1)] {
+                    run(target, &seed[..cut]);
+                }
+                let mut corrupted = seed.clone();
+                for index in (0..corrupted.len()).step_by(7) {
+                    corrupted[index] ^= 0x5a;
+                }
+                run(target, &corrupted);
+
+                if let Some(dir) = &corpus_dir {
+                    let dir = dir.join(target);
+                    std::fs::create_dir_all(&dir).unwrap();
+                    std::fs::write(dir.join(hex::encode(Sha3_256::digest(seed))), seed).unwrap();
+                }
+            }
+        }
+        assert_eq!(network_message(&corpus()[0].1.concat()).len(), 15);
+
+        println!("   Fuzz entry points working!");
+    }
+}
@@ -0,0 +1,341 @@
+pub mod channel;
+pub mod transfer;
+
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::BTreeMap;
+
+use crate::network::light::{verify_header_chain, TransactionProof};
+use crate::storage::blocks::BlockHeader;
+use channel::{Channels, Context, Effect, CHANNELS};
+use transfer::Vouchers;
+
+/// System address outbound messages are sent to; the transaction data is the encoded
+/// `CrossChainMessage`, whose sender must be the transaction's
+pub const OUTBOX: [u8; 32] = *b"triunity:system:interop-outbox:1";
+
+/// System address relayers post `RelayCall`s to, as a `TxV1Relay` envelope
+pub const RELAY: [u8; 32] = *b"triunity:system:interop-relay:01";
+
+pub const MESSAGE_VERSION: u8 = 1;
+
+/// Recent external headers a light client keeps, so proofs against a block shortly
+/// before its latest header still verify
+pub const MAX_TRUSTED_HEADERS: usize = 256;
+
+/// Most headers one relay may advance a light client by
+pub const MAX_RELAY_HEADERS: usize = 256;
+
+/// Prefixes of interop keys in the state tree, which hold no account
+const CLIENT_KEY_PREFIX: &[u8] = b"interop-client:";
+const DELIVERED_KEY_PREFIX: &[u8] = b"interop-delivered:";
+
+/// Envelope of a message between chains, sent from the source chain's outbox and
+/// delivered on the destination by a relayed inclusion proof
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrossChainMessage {
+    pub version: u8,
+    pub source_chain: String,
+    pub destination_chain: String,
+    /// Distinguishes messages with otherwise equal contents
+    pub sequence: u64,
+    #[serde(with = "hex::serde")]
+    pub sender: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub receiver: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub payload: Vec<u8>,
+}
+
+impl CrossChainMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap_or_default()
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        let message: Self = bincode::deserialize(data).map_err(|e| format!("Invalid cross-chain message: {}", e))?;
+        if message.version != MESSAGE_VERSION {
+            return Err(format!("Unsupported message version {}", message.version));
+        }
+        Ok(message)
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        Sha3_256::digest(self.encode()).into()
+    }
+}
+
+/// Call on the relay system contract
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RelayCall {
+    /// Starts tracking the external chain `chain_id` from `header`, trusted as given.
+    /// Messages through the client must name `local_chain` as their destination
+    CreateClient { chain_id: String, local_chain: String, header: BlockHeader },
+    /// Advances a client by `headers`, then delivers the message sent by the proven transaction
+    Deliver { chain_id: String, headers: Vec<BlockHeader>, proof: TransactionProof },
+}
+
+impl RelayCall {
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap_or_default()
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        bincode::deserialize(data).map_err(|e| format!("Invalid relay call: {}", e))
+    }
+}
+
+/// Checks headers of an external chain extend what a light client already trusts
+pub trait HeaderVerifier {
+    fn verify_headers(&self, trusted: &BlockHeader, headers: &[BlockHeader]) -> Result<(), String>;
+}
+
+/// Checks a proven transaction sent a message from the external chain's outbox and
+/// returns that message
+pub trait InclusionVerifier {
+    fn verify_inclusion(&self, header: &BlockHeader, proof: &TransactionProof) -> Result<CrossChainMessage, String>;
+}
+
+/// Verifies chains running this protocol: headers must link by hash and messages be
+/// outbox transactions under the header's transaction root
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TriUnityVerifier;
+
+impl HeaderVerifier for TriUnityVerifier {
+    fn verify_headers(&self, trusted: &BlockHeader, headers: &[BlockHeader]) -> Result<(), String> {
+        verify_header_chain(trusted, headers)
+    }
+}
+
+impl InclusionVerifier for TriUnityVerifier {
+    fn verify_inclusion(&self, header: &BlockHeader, proof: &TransactionProof) -> Result<CrossChainMessage, String> {
+        proof.verify(header)?;
+        let tx = &proof.transaction;
+        if tx.to != OUTBOX {
+            return Err("Proven transaction is not an outbox message".to_string());
+        }
+        let message = CrossChainMessage::decode(&tx.data)?;
+        if message.sender != tx.from {
+            return Err("Message sender differs from the transaction sender".to_string());
+        }
+        Ok(message)
+    }
+}
+
+/// Light client of one external chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientState {
+    pub chain_id: String,
+    pub local_chain: String,
+    /// Latest verified headers by height, never empty
+    pub headers: BTreeMap<u64, BlockHeader>,
+}
+
+impl ClientState {
+    pub fn latest(&self) -> &BlockHeader {
+        self.headers.values().next_back().expect("a client holds at least one header")
+    }
+}
+
+/// Change a relay call or outbox message makes, worked out before any balance moves
+#[derive(Debug, Clone)]
+pub enum InteropUpdate {
+    Client(ClientState),
+    Deliver(ClientState, CrossChainMessage, Vec<Effect>),
+    Send(Vec<Effect>),
+}
+
+/// Light clients, the messages delivered through them and the channels built on top,
+/// part of consensus state
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InteropState {
+    clients: BTreeMap<String, ClientState>,
+    /// Delivered messages by hash, for receiving applications to consume
+    delivered: BTreeMap<[u8; 32], CrossChainMessage>,
+    channels: Channels,
+    vouchers: Vouchers,
+}
+
+impl InteropState {
+    pub fn client(&self, chain_id: &str) -> Option<&ClientState> {
+        self.clients.get(chain_id)
+    }
+
+    pub fn clients(&self) -> impl Iterator<Item = &ClientState> {
+        self.clients.values()
+    }
+
+    pub fn delivered(&self, hash: &[u8; 32]) -> Option<&CrossChainMessage> {
+        self.delivered.get(hash)
+    }
+
+    pub fn messages(&self) -> impl Iterator<Item = (&[u8; 32], &CrossChainMessage)> {
+        self.delivered.iter()
+    }
+
+    pub fn channels(&self) -> &Channels {
+        &self.channels
+    }
+
+    pub fn vouchers(&self) -> &Vouchers {
+        &self.vouchers
+    }
+
+    fn context(&self, height: u64, escrowed: u64) -> Context<'_> {
+        Context { height, escrowed, vouchers: &self.vouchers }
+    }
+
+    /// Checks `message` sent from the outbox by `sender` with `amount` at `height`, and
+    /// works out its local update. `escrowed` is the transfer escrow's balance
+    pub fn send(&self, message: &CrossChainMessage, sender: &[u8], amount: u64, height: u64, escrowed: u64) -> Result<InteropUpdate, String> {
+        if message.sender != sender {
+            return Err("Outbound message must name its sender".to_string());
+        }
+        if message.receiver != CHANNELS {
+            return Ok(InteropUpdate::Send(Vec::new()));
+        }
+        Ok(InteropUpdate::Send(self.channels.send(message, sender, amount, &self.context(height, escrowed))?))
+    }
+
+    /// Checks `call` with `verifier` at `height` and works out its update, without applying it
+    pub fn process<V: HeaderVerifier + InclusionVerifier>(&self, call: &RelayCall, verifier: &V, height: u64, escrowed: u64) -> Result<InteropUpdate, String> {
+        match call {
+            RelayCall::CreateClient { chain_id, local_chain, header } => {
+                if chain_id.is_empty() || local_chain.is_empty() {
+                    return Err("Light client needs both chain ids".to_string());
+                }
+                if self.clients.contains_key(chain_id) {
+                    return Err(format!("Light client for {} already exists", chain_id));
+                }
+                let headers = BTreeMap::from([(header.height, header.clone())]);
+                Ok(InteropUpdate::Client(ClientState { chain_id: chain_id.clone(), local_chain: local_chain.clone(), headers }))
+            }
+            RelayCall::Deliver { chain_id, headers, proof } => {
+                let mut client = self.clients.get(chain_id).cloned().ok_or_else(|| format!("No light client for {}", chain_id))?;
+                if headers.len() > MAX_RELAY_HEADERS {
+                    return Err(format!("Relay carries more than {} headers", MAX_RELAY_HEADERS));
+                }
+                verifier.verify_headers(client.latest(), headers)?;
+                for header in headers {
+                    client.headers.insert(header.height, header.clone());
+                }
+                while client.headers.len() > MAX_TRUSTED_HEADERS {
+                    client.headers.pop_first();
+                }
+
+                let header = client.headers.get(&proof.height).ok_or_else(|| format!("Height {} of {} is not trusted", proof.height, chain_id))?;
+                let message = verifier.verify_inclusion(header, proof)?;
+                if message.source_chain != *chain_id || message.destination_chain != client.local_chain {
+                    return Err(format!("Message is not from {} to {}", chain_id, client.local_chain));
+                }
+                if self.delivered.contains_key(&message.hash()) {
+                    return Err("Message was already delivered".to_string());
+                }
+                let effects = match message.receiver == CHANNELS {
+                    true => self.channels.deliver(&message, &self.context(height, escrowed))?,
+                    false => Vec::new(),
+                };
+                Ok(InteropUpdate::Deliver(client, message, effects))
+            }
+        }
+    }
+
+    /// Applies `update` and returns the native balance moves it makes, as (from, to, amount)
+    pub fn apply(&mut self, update: InteropUpdate) -> Vec<(Vec<u8>, Vec<u8>, u64)> {
+        let effects = match update {
+            InteropUpdate::Client(client) => {
+                self.clients.insert(client.chain_id.clone(), client);
+                return Vec::new();
+            }
+            InteropUpdate::Deliver(client, message, effects) => {
+                self.delivered.insert(message.hash(), message);
+                self.clients.insert(client.chain_id.clone(), client);
+                effects
+            }
+            InteropUpdate::Send(effects) => effects,
+        };
+        let mut moves = Vec::new();
+        for effect in effects {
+            match effect {
+                Effect::Move { from, to, amount } => moves.push((from, to, amount)),
+                Effect::Mint { .. } | Effect::Burn { .. } => self.vouchers.apply(&effect),
+                effect => self.channels.apply(effect),
+            }
+        }
+        moves
+    }
+
+    /// Leaf bytes committing to each light client, delivered message, channel and voucher, in key order
+    pub fn leaves(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        let clients = self.clients.iter().map(|(chain_id, client)| {
+            bincode::serialize(&([CLIENT_KEY_PREFIX, chain_id.as_bytes()].concat(), client)).unwrap_or_default()
+        });
+        let delivered = self.delivered.iter().map(|(hash, message)| {
+            bincode::serialize(&([DELIVERED_KEY_PREFIX, hash].concat(), message)).unwrap_or_default()
+        });
+        clients.chain(delivered).chain(self.channels.leaves()).chain(self.vouchers.leaves())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumSignature;
+    use crate::storage::blocks::{Block, ConsensusData, Transaction};
+    use crate::storage::envelope::TxEnvelope;
+    use crate::storage::state::StateManager;
+
+    fn relay(from: &[u8], nonce: u64, call: &RelayCall) -> Transaction {
+        Transaction::new(from.to_vec(), RELAY.to_vec(), 0, 1, nonce, call.encode(), QuantumSignature::new(vec![]))
+    }
+
+    #[test]
+    fn test_relayed_message_delivery() {
+        // The external chain: a genesis block, then a block sending a message from its outbox
+        let (alice, relayer) = (vec![1; 32], vec![2; 32]);
+        let message = CrossChainMessage {
+            version: MESSAGE_VERSION,
+            source_chain: "remote".to_string(),
+            destination_chain: "local".to_string(),
+            sequence: 0,
+            sender: alice.clone(),
+            receiver: vec![3; 32],
+            payload: b"hello".to_vec(),
+        };
+        let send = Transaction::new(alice.clone(), OUTBOX.to_vec(), 0, 1, 0, message.encode(), QuantumSignature::new(vec![]));
+        let mut remote = StateManager::from_allocations(&[(alice.clone(), 100)]).unwrap();
+        remote.apply_transaction(&send).unwrap();
+        let genesis = Block::new([0; 32], vec![], 0, ConsensusData::default());
+        let block = Block::new(genesis.hash(), vec![send.clone()], 1, ConsensusData::default());
+        let proof = TransactionProof { height: 1, index: 0, transaction: send, proof: block.transaction_proof(0).unwrap() };
+
+        let mut local = StateManager::from_allocations(&[(relayer.clone(), 100)]).unwrap();
+        let deliver = RelayCall::Deliver { chain_id: "remote".to_string(), headers: vec![block.header.clone()], proof: proof.clone() };
+        assert!(local.apply_transaction(&relay(&relayer, 0, &deliver)).is_err());
+        let create = RelayCall::CreateClient { chain_id: "remote".to_string(), local_chain: "local".to_string(), header: genesis.header.clone() };
+        local.apply_transaction(&relay(&relayer, 0, &create)).unwrap();
+
+        // Headers that do not link, or a proof of a different transaction, are refused
+        let stray = Block::new([9; 32], vec![], 1, ConsensusData::default());
+        let unlinked = RelayCall::Deliver { chain_id: "remote".to_string(), headers: vec![stray.header], proof: proof.clone() };
+        assert!(local.apply_transaction(&relay(&relayer, 1, &unlinked)).is_err());
+        let mut forged = proof.clone();
+        forged.transaction.data = CrossChainMessage { payload: b"bye".to_vec(), ..message.clone() }.encode();
+        let forged = RelayCall::Deliver { chain_id: "remote".to_string(), headers: vec![block.header.clone()], proof: forged };
+        assert!(local.apply_transaction(&relay(&relayer, 1, &forged)).is_err());
+
+        let tx = relay(&relayer, 1, &deliver);
+        assert!(matches!(tx.envelope(), TxEnvelope::TxV1Relay(_)));
+        local.apply_transaction(&Transaction::from(TxEnvelope::decode(&tx.envelope().encode()).unwrap())).unwrap();
+        assert_eq!(local.interop().delivered(&message.hash()), Some(&message));
+        assert_eq!(local.interop().client("remote").unwrap().latest().height, 1);
+        // Each message is delivered once
+        let again = RelayCall::Deliver { chain_id: "remote".to_string(), headers: vec![], proof };
+        assert!(local.apply_transaction(&relay(&relayer, 2, &again)).is_err());
+
+        let snapshot = local.snapshot(genesis);
+        assert_eq!(StateManager::from_snapshot(&snapshot).state_root(), local.state_root());
+
+        println!("   Relayed message delivery working!");
+    }
+}
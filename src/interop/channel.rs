@@ -0,0 +1,391 @@
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::BTreeMap;
+
+use super::transfer::{self, Vouchers, TRANSFER_PORT};
+use super::CrossChainMessage;
+use crate::storage::merkle::MerkleProof;
+
+/// Receiver of cross-chain messages carrying a `ChannelMsg`. Sending one from the outbox
+/// applies its local side; delivering one through a light client applies the rest
+pub const CHANNELS: [u8; 32] = *b"triunity:system:interop-channels";
+
+/// Prefixes of channel keys in the state tree, which hold no account
+const CHANNEL_KEY_PREFIX: &[u8] = b"channel:";
+const COMMITMENT_KEY_PREFIX: &[u8] = b"commitment:";
+const RECEIPT_KEY_PREFIX: &[u8] = b"receipt:";
+
+/// Port, channel and sequence of a packet on the chain holding the entry
+pub type PacketKey = (String, String, u64);
+
+/// Handshake progress of one channel end. The opening chain goes Init then Open on the
+/// counterparty's Try; the other goes TryOpen then Open on the opener's Ack (confirm)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChannelState {
+    Init,
+    TryOpen,
+    Open,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelEnd {
+    pub state: ChannelState,
+    pub port: String,
+    pub channel_id: String,
+    pub counterparty_chain: String,
+    pub counterparty_port: String,
+    /// Unknown to the opener until the counterparty's Try arrives
+    pub counterparty_channel: Option<String>,
+    pub next_sequence: u64,
+}
+
+impl ChannelEnd {
+    fn is_open_to(&self, chain: &str, port: &str, channel: &str) -> bool {
+        self.state == ChannelState::Open
+            && self.counterparty_chain == chain
+            && self.counterparty_port == port
+            && self.counterparty_channel.as_deref() == Some(channel)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Packet {
+    pub sequence: u64,
+    pub source_port: String,
+    pub source_channel: String,
+    pub destination_port: String,
+    pub destination_channel: String,
+    #[serde(with = "hex::serde")]
+    pub data: Vec<u8>,
+    /// First destination height at which the packet can no longer be received
+    pub timeout_height: u64,
+}
+
+impl Packet {
+    /// Hash the source chain keeps until the packet is acknowledged or times out
+    pub fn commitment(&self) -> [u8; 32] {
+        Sha3_256::digest(bincode::serialize(self).unwrap_or_default()).into()
+    }
+
+    fn source_key(&self) -> PacketKey {
+        (self.source_port.clone(), self.source_channel.clone(), self.sequence)
+    }
+
+    fn destination_key(&self) -> PacketKey {
+        (self.destination_port.clone(), self.destination_channel.clone(), self.sequence)
+    }
+}
+
+/// Outstanding packet commitment with its proof under the state root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitmentProof {
+    #[serde(with = "hex::serde")]
+    pub commitment: [u8; 32],
+    pub proof: MerkleProof,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Acknowledgement {
+    Success,
+    Error(String),
+}
+
+/// What the destination recorded for a packet sequence
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Receipt {
+    Received(Acknowledgement),
+    /// The timeout was proven, so the packet can never be received
+    TimedOut,
+}
+
+/// Payload of a message to `CHANNELS`. Each is sent from the chain named by its fields'
+/// `port` and `channel_id`, or the packet's source (destination for `Acknowledge` and `Timeout`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChannelMsg {
+    OpenInit { port: String, channel_id: String, counterparty_port: String },
+    OpenTry { port: String, channel_id: String, counterparty_port: String, counterparty_channel: String },
+    OpenAck { port: String, channel_id: String, counterparty_port: String, counterparty_channel: String },
+    Packet(Packet),
+    Acknowledge { packet: Packet, ack: Acknowledgement },
+    Timeout(Packet),
+}
+
+impl ChannelMsg {
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap_or_default()
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        bincode::deserialize(data).map_err(|e| format!("Invalid channel message: {}", e))
+    }
+}
+
+/// State a channel operation reads besides the channels themselves
+#[derive(Debug, Clone)]
+pub struct Context<'a> {
+    /// Height of the block the operation is in
+    pub height: u64,
+    /// Native balance held in escrow by the transfer module
+    pub escrowed: u64,
+    pub vouchers: &'a Vouchers,
+}
+
+/// One change a channel operation makes, worked out before any of them apply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Effect {
+    Channel(ChannelEnd),
+    Commit(PacketKey, [u8; 32]),
+    Uncommit(PacketKey),
+    Receipt(PacketKey, Receipt),
+    /// Native balance moved between accounts
+    Move { from: Vec<u8>, to: Vec<u8>, amount: u64 },
+    Mint { denom: String, holder: Vec<u8>, amount: u64 },
+    Burn { denom: String, holder: Vec<u8>, amount: u64 },
+}
+
+/// Application bound to a port, called as its packets are sent, received and settled
+pub trait PortModule {
+    /// Effects of `sender` sending `packet`, which carried `amount` to the outbox
+    fn on_send(&self, packet: &Packet, sender: &[u8], amount: u64, ctx: &Context) -> Result<Vec<Effect>, String>;
+    /// Effects of receiving `packet`; an error becomes an error acknowledgement
+    fn on_receive(&self, packet: &Packet, ctx: &Context) -> Result<Vec<Effect>, String>;
+    /// Effects of `packet` failing on the destination, by error acknowledgement or timeout
+    fn on_refund(&self, packet: &Packet, ctx: &Context) -> Result<Vec<Effect>, String>;
+}
+
+/// Ports with no module accept packets carrying no value and always acknowledge them
+fn module(port: &str) -> Option<&'static dyn PortModule> {
+    (port == TRANSFER_PORT).then_some(&transfer::TransferModule as &dyn PortModule)
+}
+
+/// Channel ends, outstanding packet commitments and receipts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Channels {
+    ends: BTreeMap<(String, String), ChannelEnd>,
+    commitments: BTreeMap<PacketKey, [u8; 32]>,
+    receipts: BTreeMap<PacketKey, Receipt>,
+}
+
+impl Channels {
+    pub fn end(&self, port: &str, channel_id: &str) -> Option<&ChannelEnd> {
+        self.ends.get(&(port.to_string(), channel_id.to_string()))
+    }
+
+    pub fn ends(&self) -> impl Iterator<Item = &ChannelEnd> {
+        self.ends.values()
+    }
+
+    pub fn commitment(&self, key: &PacketKey) -> Option<[u8; 32]> {
+        self.commitments.get(key).copied()
+    }
+
+    pub fn receipt(&self, key: &PacketKey) -> Option<&Receipt> {
+        self.receipts.get(key)
+    }
+
+    fn find_end(&self, port: &str, channel_id: &str) -> Result<&ChannelEnd, String> {
+        self.end(port, channel_id).ok_or_else(|| format!("No channel {}/{}", port, channel_id))
+    }
+
+    /// Local side of `message` sent from the outbox with `amount`
+    pub fn send(&self, message: &CrossChainMessage, sender: &[u8], amount: u64, ctx: &Context) -> Result<Vec<Effect>, String> {
+        let msg = ChannelMsg::decode(&message.payload)?;
+        let counterparty = &message.destination_chain;
+        let moves_value = matches!(&msg, ChannelMsg::Packet(packet) if module(&packet.source_port).is_some());
+        if amount > 0 && !moves_value {
+            return Err("Channel message carries no value".to_string());
+        }
+        match msg {
+            ChannelMsg::OpenInit { port, channel_id, counterparty_port } => {
+                if self.end(&port, &channel_id).is_some() {
+                    return Err(format!("Channel {}/{} exists", port, channel_id));
+                }
+                Ok(vec![Effect::Channel(ChannelEnd {
+                    state: ChannelState::Init,
+                    port,
+                    channel_id,
+                    counterparty_chain: counterparty.clone(),
+                    counterparty_port,
+                    counterparty_channel: None,
+                    next_sequence: 1,
+                })])
+            }
+            ChannelMsg::OpenTry { port, channel_id, counterparty_port, counterparty_channel } => {
+                let end = self.find_end(&port, &channel_id)?;
+                if end.state != ChannelState::TryOpen
+                    || end.counterparty_chain != *counterparty
+                    || end.counterparty_port != counterparty_port
+                    || end.counterparty_channel.as_deref() != Some(&counterparty_channel)
+                {
+                    return Err("Channel is not in TryOpen with that counterparty".to_string());
+                }
+                Ok(Vec::new())
+            }
+            ChannelMsg::OpenAck { port, channel_id, counterparty_port, counterparty_channel } => {
+                if !self.find_end(&port, &channel_id)?.is_open_to(counterparty, &counterparty_port, &counterparty_channel) {
+                    return Err("Channel is not open to that counterparty".to_string());
+                }
+                Ok(Vec::new())
+            }
+            ChannelMsg::Packet(packet) => {
+                let end = self.find_end(&packet.source_port, &packet.source_channel)?;
+                if !end.is_open_to(counterparty, &packet.destination_port, &packet.destination_channel) {
+                    return Err("Channel is not open to the packet's destination".to_string());
+                }
+                if packet.sequence != end.next_sequence {
+                    return Err(format!("Expected packet sequence {}, got {}", end.next_sequence, packet.sequence));
+                }
+                let mut effects = match module(&packet.source_port) {
+                    Some(module) => module.on_send(&packet, sender, amount, ctx)?,
+                    None => Vec::new(),
+                };
+                effects.push(Effect::Channel(ChannelEnd { next_sequence: end.next_sequence + 1, ..end.clone() }));
+                effects.push(Effect::Commit(packet.source_key(), packet.commitment()));
+                Ok(effects)
+            }
+            ChannelMsg::Acknowledge { packet, ack } => {
+                match self.receipts.get(&packet.destination_key()) {
+                    Some(Receipt::Received(received)) if *received == ack => Ok(Vec::new()),
+                    _ => Err("Packet was not received with that acknowledgement".to_string()),
+                }
+            }
+            ChannelMsg::Timeout(packet) => {
+                let end = self.find_end(&packet.destination_port, &packet.destination_channel)?;
+                if !end.is_open_to(counterparty, &packet.source_port, &packet.source_channel) {
+                    return Err("Channel is not open to the packet's source".to_string());
+                }
+                if self.receipts.contains_key(&packet.destination_key()) {
+                    return Err("Packet was already received or timed out".to_string());
+                }
+                if ctx.height < packet.timeout_height {
+                    return Err(format!("Packet does not time out before height {}", packet.timeout_height));
+                }
+                Ok(vec![Effect::Receipt(packet.destination_key(), Receipt::TimedOut)])
+            }
+        }
+    }
+
+    /// Counterparty side of `message`, delivered through the light client of its source chain
+    pub fn deliver(&self, message: &CrossChainMessage, ctx: &Context) -> Result<Vec<Effect>, String> {
+        let counterparty = &message.source_chain;
+        match ChannelMsg::decode(&message.payload)? {
+            // Try: open our end of the channel the counterparty initiated
+            ChannelMsg::OpenInit { port, channel_id, counterparty_port } => {
+                let id = format!("channel-{}", self.ends.keys().filter(|(bound, _)| *bound == counterparty_port).count());
+                Ok(vec![Effect::Channel(ChannelEnd {
+                    state: ChannelState::TryOpen,
+                    port: counterparty_port,
+                    channel_id: id,
+                    counterparty_chain: counterparty.clone(),
+                    counterparty_port: port,
+                    counterparty_channel: Some(channel_id),
+                    next_sequence: 1,
+                })])
+            }
+            // Ack: the counterparty accepted the channel we initiated
+            ChannelMsg::OpenTry { port, channel_id, counterparty_port, counterparty_channel } => {
+                let end = self.find_end(&counterparty_port, &counterparty_channel)?;
+                if end.state != ChannelState::Init || end.counterparty_chain != *counterparty || end.counterparty_port != port {
+                    return Err("Channel is not awaiting that counterparty".to_string());
+                }
+                Ok(vec![Effect::Channel(ChannelEnd { state: ChannelState::Open, counterparty_channel: Some(channel_id), ..end.clone() })])
+            }
+            // Confirm: the initiator opened its end
+            ChannelMsg::OpenAck { port, channel_id, counterparty_port, counterparty_channel } => {
+                let end = self.find_end(&counterparty_port, &counterparty_channel)?;
+                if end.state != ChannelState::TryOpen
+                    || end.counterparty_chain != *counterparty
+                    || end.counterparty_port != port
+                    || end.counterparty_channel.as_deref() != Some(&channel_id)
+                {
+                    return Err("Channel is not awaiting that confirmation".to_string());
+                }
+                Ok(vec![Effect::Channel(ChannelEnd { state: ChannelState::Open, ..end.clone() })])
+            }
+            ChannelMsg::Packet(packet) => {
+                let end = self.find_end(&packet.destination_port, &packet.destination_channel)?;
+                if !end.is_open_to(counterparty, &packet.source_port, &packet.source_channel) {
+                    return Err("Channel is not open to the packet's source".to_string());
+                }
+                if self.receipts.contains_key(&packet.destination_key()) {
+                    return Err("Packet was already received or timed out".to_string());
+                }
+                if ctx.height >= packet.timeout_height {
+                    return Err(format!("Packet timed out at height {}", packet.timeout_height));
+                }
+                let (ack, mut effects) = match module(&packet.destination_port).map(|module| module.on_receive(&packet, ctx)) {
+                    Some(Err(e)) => (Acknowledgement::Error(e), Vec::new()),
+                    Some(Ok(effects)) => (Acknowledgement::Success, effects),
+                    None => (Acknowledgement::Success, Vec::new()),
+                };
+                effects.push(Effect::Receipt(packet.destination_key(), Receipt::Received(ack)));
+                Ok(effects)
+            }
+            ChannelMsg::Acknowledge { packet, ack } => {
+                let mut effects = self.settle(&packet, counterparty)?;
+                if let (Acknowledgement::Error(_), Some(module)) = (ack, module(&packet.source_port)) {
+                    effects.extend(module.on_refund(&packet, ctx)?);
+                }
+                Ok(effects)
+            }
+            ChannelMsg::Timeout(packet) => {
+                let mut effects = self.settle(&packet, counterparty)?;
+                if let Some(module) = module(&packet.source_port) {
+                    effects.extend(module.on_refund(&packet, ctx)?);
+                }
+                Ok(effects)
+            }
+        }
+    }
+
+    /// Drops the commitment of a packet we sent once its outcome arrives
+    fn settle(&self, packet: &Packet, counterparty: &str) -> Result<Vec<Effect>, String> {
+        let end = self.find_end(&packet.source_port, &packet.source_channel)?;
+        if !end.is_open_to(counterparty, &packet.destination_port, &packet.destination_channel) {
+            return Err("Channel is not open to the packet's destination".to_string());
+        }
+        if self.commitments.get(&packet.source_key()) != Some(&packet.commitment()) {
+            return Err("No matching packet commitment".to_string());
+        }
+        Ok(vec![Effect::Uncommit(packet.source_key())])
+    }
+
+    pub(crate) fn apply(&mut self, effect: Effect) {
+        match effect {
+            Effect::Channel(end) => {
+                self.ends.insert((end.port.clone(), end.channel_id.clone()), end);
+            }
+            Effect::Commit(key, commitment) => {
+                self.commitments.insert(key, commitment);
+            }
+            Effect::Uncommit(key) => {
+                self.commitments.remove(&key);
+            }
+            Effect::Receipt(key, receipt) => {
+                self.receipts.insert(key, receipt);
+            }
+            Effect::Move { .. } | Effect::Mint { .. } | Effect::Burn { .. } => {}
+        }
+    }
+
+    /// Leaf bytes committing to a packet commitment in the state tree
+    pub fn commitment_leaf(key: &PacketKey, commitment: &[u8; 32]) -> Vec<u8> {
+        bincode::serialize(&(packet_key(COMMITMENT_KEY_PREFIX, key), commitment)).unwrap_or_default()
+    }
+
+    /// Leaf bytes committing to each channel end, commitment and receipt, in key order
+    pub fn leaves(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        let ends = self.ends.iter().map(|((port, channel_id), end)| {
+            bincode::serialize(&([CHANNEL_KEY_PREFIX, port.as_bytes(), b"/", channel_id.as_bytes()].concat(), end)).unwrap_or_default()
+        });
+        let commitments = self.commitments.iter().map(|(key, commitment)| Self::commitment_leaf(key, commitment));
+        let receipts = self.receipts.iter().map(|(key, receipt)| {
+            bincode::serialize(&(packet_key(RECEIPT_KEY_PREFIX, key), receipt)).unwrap_or_default()
+        });
+        ends.chain(commitments).chain(receipts)
+    }
+}
+
+fn packet_key(prefix: &[u8], (port, channel_id, sequence): &PacketKey) -> Vec<u8> {
+    [prefix, port.as_bytes(), b"/", channel_id.as_bytes(), b"/", &sequence.to_be_bytes()].concat()
+}
@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::channel::{Context, Effect, Packet, PortModule};
+use super::OUTBOX;
+
+/// Port the fungible token transfer module is bound to
+pub const TRANSFER_PORT: &str = "transfer";
+
+/// Account holding native tokens sent out over transfer channels until they come back
+/// or are refunded
+pub const TRANSFER_ESCROW: [u8; 32] = *b"triunity:system:transfer-escrow1";
+
+/// Denomination of the native token in transfer packets
+pub const NATIVE_DENOM: &str = "tri";
+
+/// Prefix of voucher keys in the state tree, which hold no account
+const VOUCHER_KEY_PREFIX: &[u8] = b"voucher:";
+
+/// Packet data of the transfer port
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FungibleTokenPacket {
+    pub denom: String,
+    pub amount: u64,
+    #[serde(with = "hex::serde")]
+    pub sender: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub receiver: Vec<u8>,
+}
+
+impl FungibleTokenPacket {
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap_or_default()
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        bincode::deserialize(data).map_err(|e| format!("Invalid token transfer packet: {}", e))
+    }
+}
+
+/// Prefix a chain gives the denomination of tokens it receives over a channel end
+pub fn voucher_prefix(port: &str, channel_id: &str) -> String {
+    format!("{}/{}/", port, channel_id)
+}
+
+/// Balances of tokens received from other chains, by denomination then holder
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Vouchers {
+    balances: BTreeMap<(String, Vec<u8>), u64>,
+}
+
+impl Vouchers {
+    pub fn balance(&self, denom: &str, holder: &[u8]) -> u64 {
+        self.balances.get(&(denom.to_string(), holder.to_vec())).copied().unwrap_or(0)
+    }
+
+    /// Non-zero voucher balances of `holder`, by denomination
+    pub fn holdings(&self, holder: &[u8]) -> Vec<(String, u64)> {
+        self.balances
+            .iter()
+            .filter(|((_, owner), _)| owner == holder)
+            .map(|((denom, _), amount)| (denom.clone(), *amount))
+            .collect()
+    }
+
+    pub(crate) fn apply(&mut self, effect: &Effect) {
+        match effect {
+            Effect::Mint { denom, holder, amount } => {
+                *self.balances.entry((denom.clone(), holder.clone())).or_insert(0) += amount;
+            }
+            Effect::Burn { denom, holder, amount } => {
+                let key = (denom.clone(), holder.clone());
+                let balance = self.balances.get(&key).copied().unwrap_or(0).saturating_sub(*amount);
+                if balance == 0 {
+                    self.balances.remove(&key);
+                } else {
+                    self.balances.insert(key, balance);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Leaf bytes committing to each voucher balance in the state tree, in key order
+    pub fn leaves(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        self.balances.iter().map(|((denom, holder), amount)| {
+            let key = [VOUCHER_KEY_PREFIX, denom.as_bytes(), b"/", holder].concat();
+            bincode::serialize(&(key, amount)).unwrap_or_default()
+        })
+    }
+}
+
+/// Demo fungible token transfer. Native tokens are escrowed on the way out and vouchers
+/// minted on the way in; vouchers sent back over their channel are burned and unescrow
+/// the native tokens they stand for. Forwarding vouchers to a third chain is not supported
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TransferModule;
+
+impl PortModule for TransferModule {
+    fn on_send(&self, packet: &Packet, sender: &[u8], amount: u64, ctx: &Context) -> Result<Vec<Effect>, String> {
+        let data = FungibleTokenPacket::decode(&packet.data)?;
+        if data.sender != sender {
+            return Err("Transfer sender differs from the message sender".to_string());
+        }
+        if data.amount == 0 || data.receiver.is_empty() {
+            return Err("Transfer needs a receiver and an amount".to_string());
+        }
+        if data.denom == NATIVE_DENOM {
+            if amount != data.amount {
+                return Err(format!("Native transfer must carry its amount {}, got {}", data.amount, amount));
+            }
+            return Ok(vec![Effect::Move { from: OUTBOX.to_vec(), to: TRANSFER_ESCROW.to_vec(), amount }]);
+        }
+        if amount > 0 {
+            return Err("Voucher transfer carries no native value".to_string());
+        }
+        if !data.denom.starts_with(&voucher_prefix(&packet.source_port, &packet.source_channel)) {
+            return Err(format!("Vouchers of {} only return over the channel they arrived on", data.denom));
+        }
+        if ctx.vouchers.balance(&data.denom, sender) < data.amount {
+            return Err(format!("Insufficient {} balance", data.denom));
+        }
+        Ok(vec![Effect::Burn { denom: data.denom, holder: sender.to_vec(), amount: data.amount }])
+    }
+
+    fn on_receive(&self, packet: &Packet, ctx: &Context) -> Result<Vec<Effect>, String> {
+        let data = FungibleTokenPacket::decode(&packet.data)?;
+        match data.denom.strip_prefix(&voucher_prefix(&packet.source_port, &packet.source_channel)) {
+            // Our native tokens coming back from the chain that holds their vouchers
+            Some(NATIVE_DENOM) => {
+                if ctx.escrowed < data.amount {
+                    return Err("Escrow holds less than the returned amount".to_string());
+                }
+                Ok(vec![Effect::Move { from: TRANSFER_ESCROW.to_vec(), to: data.receiver, amount: data.amount }])
+            }
+            Some(denom) => Err(format!("Cannot unwrap {} on this chain", denom)),
+            None => {
+                let denom = format!("{}{}", voucher_prefix(&packet.destination_port, &packet.destination_channel), data.denom);
+                Ok(vec![Effect::Mint { denom, holder: data.receiver, amount: data.amount }])
+            }
+        }
+    }
+
+    fn on_refund(&self, packet: &Packet, ctx: &Context) -> Result<Vec<Effect>, String> {
+        let data = FungibleTokenPacket::decode(&packet.data)?;
+        if data.denom != NATIVE_DENOM {
+            return Ok(vec![Effect::Mint { denom: data.denom, holder: data.sender, amount: data.amount }]);
+        }
+        if ctx.escrowed < data.amount {
+            return Err("Escrow holds less than the refunded amount".to_string());
+        }
+        Ok(vec![Effect::Move { from: TRANSFER_ESCROW.to_vec(), to: data.sender, amount: data.amount }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumSignature;
+    use crate::interop::channel::{Acknowledgement, ChannelMsg, ChannelState, Channels, CHANNELS};
+    use crate::interop::{CrossChainMessage, RelayCall, MESSAGE_VERSION, RELAY};
+    use crate::network::light::TransactionProof;
+    use crate::storage::blocks::{Block, ConsensusData, Transaction};
+    use crate::storage::merkle::MerkleTree;
+    use crate::storage::state::StateManager;
+
+    /// One of the two chains, producing a block per transaction
+    struct Chain {
+        id: &'static str,
+        state: StateManager,
+        blocks: Vec<Block>,
+        sequence: u64,
+    }
+
+    impl Chain {
+        fn new(id: &'static str, allocations: &[(Vec<u8>, u64)]) -> Self {
+            let genesis = Block::new([0; 32], vec![], 0, ConsensusData::default());
+            Self { id, state: StateManager::from_allocations(allocations).unwrap(), blocks: vec![genesis], sequence: 0 }
+        }
+
+        fn height(&self) -> u64 {
+            self.blocks.len() as u64 - 1
+        }
+
+        fn include(&mut self, from: &[u8], to: &[u8], amount: u64, data: Vec<u8>) -> Result<TransactionProof, String> {
+            let nonce = self.state.get_account(from).map_or(0, |account| account.nonce);
+            let tx = Transaction::new(from.to_vec(), to.to_vec(), amount, 1, nonce, data, QuantumSignature::new(vec![]));
+            let block = Block::new(self.blocks.last().unwrap().hash(), vec![tx.clone()], self.height() + 1, ConsensusData::default());
+            self.state.apply_block(&block)?;
+            let proof = TransactionProof { height: block.header.height, index: 0, transaction: tx, proof: block.transaction_proof(0).unwrap() };
+            self.blocks.push(block);
+            Ok(proof)
+        }
+
+        fn send(&mut self, from: &[u8], counterparty: &str, amount: u64, msg: &ChannelMsg) -> Result<TransactionProof, String> {
+            self.sequence += 1;
+            let message = CrossChainMessage {
+                version: MESSAGE_VERSION,
+                source_chain: self.id.to_string(),
+                destination_chain: counterparty.to_string(),
+                sequence: self.sequence,
+                sender: from.to_vec(),
+                receiver: CHANNELS.to_vec(),
+                payload: msg.encode(),
+            };
+            self.include(from, &OUTBOX, amount, message.encode())
+        }
+
+        /// Delivers the message proven by `proof`, advancing the client over `source`'s new headers
+        fn relay(&mut self, relayer: &[u8], source: &Chain, proof: TransactionProof) -> Result<(), String> {
+            let trusted = self.state.interop().client(source.id).unwrap().latest().height as usize;
+            let headers = source.blocks[trusted + 1..].iter().map(|block| block.header.clone()).collect();
+            let call = RelayCall::Deliver { chain_id: source.id.to_string(), headers, proof };
+            self.include(relayer, &RELAY, 0, call.encode()).map(|_| ())
+        }
+    }
+
+    fn tokens(denom: &str, amount: u64, sender: &[u8], receiver: &[u8]) -> Vec<u8> {
+        FungibleTokenPacket { denom: denom.to_string(), amount, sender: sender.to_vec(), receiver: receiver.to_vec() }.encode()
+    }
+
+    /// Both chains bind `transfer/channel-0`, so packets look alike in either direction
+    fn packet(sequence: u64, data: Vec<u8>, timeout_height: u64) -> Packet {
+        let channel = "channel-0".to_string();
+        Packet {
+            sequence,
+            source_port: TRANSFER_PORT.to_string(),
+            source_channel: channel.clone(),
+            destination_port: TRANSFER_PORT.to_string(),
+            destination_channel: channel,
+            data,
+            timeout_height,
+        }
+    }
+
+    #[test]
+    fn test_token_transfer_over_channel() {
+        let (alice, bob, relayer) = (vec![1; 32], vec![2; 32], vec![3; 32]);
+        let mut a = Chain::new("chain-a", &[(alice.clone(), 1_000), (relayer.clone(), 100)]);
+        let mut b = Chain::new("chain-b", &[(bob.clone(), 100), (relayer.clone(), 100)]);
+        let balance = |chain: &Chain, address: &[u8]| chain.state.get_account(address).map_or(0, |account| account.balance);
+        let create = |chain: &Chain, local: &str| {
+            RelayCall::CreateClient { chain_id: chain.id.to_string(), local_chain: local.to_string(), header: chain.blocks[0].header.clone() }
+        };
+        a.include(&relayer, &RELAY, 0, create(&b, "chain-a").encode()).unwrap();
+        b.include(&relayer, &RELAY, 0, create(&a, "chain-b").encode()).unwrap();
+
+        // Handshake: init on A, try on B, ack on A, confirmed on B by the ack
+        let (port, channel) = (TRANSFER_PORT.to_string(), "channel-0".to_string());
+        let init = ChannelMsg::OpenInit { port: port.clone(), channel_id: channel.clone(), counterparty_port: port.clone() };
+        let proof = a.send(&relayer, "chain-b", 0, &init).unwrap();
+        assert!(a.send(&alice, "chain-b", 100, &ChannelMsg::Packet(packet(1, tokens(NATIVE_DENOM, 100, &alice, &bob), 100))).is_err());
+        b.relay(&relayer, &a, proof).unwrap();
+        assert_eq!(b.state.interop().channels().end(&port, &channel).unwrap().state, ChannelState::TryOpen);
+        let open = (port.clone(), channel.clone(), port.clone(), channel.clone());
+        let try_open = ChannelMsg::OpenTry { port: open.0.clone(), channel_id: open.1.clone(), counterparty_port: open.2.clone(), counterparty_channel: open.3.clone() };
+        let proof = b.send(&relayer, "chain-a", 0, &try_open).unwrap();
+        a.relay(&relayer, &b, proof).unwrap();
+        let ack = ChannelMsg::OpenAck { port: open.0, channel_id: open.1, counterparty_port: open.2, counterparty_channel: open.3 };
+        let proof = a.send(&relayer, "chain-b", 0, &ack).unwrap();
+        b.relay(&relayer, &a, proof).unwrap();
+        for chain in [&a, &b] {
+            assert_eq!(chain.state.interop().channels().end(&port, &channel).unwrap().state, ChannelState::Open);
+        }
+
+        // Native tokens are escrowed on A and arrive on B as vouchers
+        let first = packet(1, tokens(NATIVE_DENOM, 100, &alice, &bob), 100);
+        assert!(a.send(&alice, "chain-b", 99, &ChannelMsg::Packet(first.clone())).is_err());
+        let proof = a.send(&alice, "chain-b", 100, &ChannelMsg::Packet(first.clone())).unwrap();
+        assert_eq!((balance(&a, &alice), balance(&a, &TRANSFER_ESCROW)), (899, 100));
+        let key = (port.clone(), channel.clone(), 1);
+        let state_proof = a.state.leaf_proof(&Channels::commitment_leaf(&key, &first.commitment())).unwrap();
+        assert!(MerkleTree::verify_proof(&state_proof) && state_proof.root == a.state.state_root());
+        b.relay(&relayer, &a, proof.clone()).unwrap();
+        let voucher = "transfer/channel-0/tri";
+        assert_eq!(b.state.interop().vouchers().balance(voucher, &bob), 100);
+        assert!(b.relay(&relayer, &a, proof).is_err());
+
+        // The acknowledgement clears the commitment
+        let proof = b.send(&relayer, "chain-a", 0, &ChannelMsg::Acknowledge { packet: first, ack: Acknowledgement::Success }).unwrap();
+        a.relay(&relayer, &b, proof).unwrap();
+        assert!(a.state.interop().channels().commitment(&key).is_none());
+
+        // A packet B lets time out is refunded on A and can no longer be received
+        let second = packet(2, tokens(NATIVE_DENOM, 50, &alice, &bob), b.height() + 2);
+        let sent = a.send(&alice, "chain-b", 50, &ChannelMsg::Packet(second.clone())).unwrap();
+        assert!(b.send(&relayer, "chain-a", 0, &ChannelMsg::Timeout(second.clone())).is_err());
+        b.include(&bob, &relayer, 1, vec![]).unwrap();
+        let proof = b.send(&relayer, "chain-a", 0, &ChannelMsg::Timeout(second)).unwrap();
+        assert!(b.relay(&relayer, &a, sent).is_err());
+        a.relay(&relayer, &b, proof).unwrap();
+        assert_eq!((balance(&a, &alice), balance(&a, &TRANSFER_ESCROW)), (898, 100));
+
+        // Vouchers sent back are burned on B and unescrow the native tokens on A
+        let back = packet(1, tokens(voucher, 40, &bob, &alice), a.height() + 10);
+        let proof = b.send(&bob, "chain-a", 0, &ChannelMsg::Packet(back)).unwrap();
+        assert_eq!(b.state.interop().vouchers().balance(voucher, &bob), 60);
+        a.relay(&relayer, &b, proof).unwrap();
+        assert_eq!((balance(&a, &alice), balance(&a, &TRANSFER_ESCROW)), (938, 60));
+
+        for chain in [&a, &b] {
+            let restored = StateManager::from_snapshot(&chain.state.snapshot(chain.blocks.last().unwrap().clone()));
+            assert_eq!(restored.state_root(), chain.state.state_root());
+        }
+
+        println!("   Token transfer over channel working!");
+    }
+}
@@ -1,10 +1,55 @@
+#![deny(clippy::await_holding_lock)]
+
+pub mod alerts;
+pub mod api;
+pub mod config;
 pub mod consensus;
+pub mod events;
+#[cfg(feature = "faucet")]
+pub mod faucet;
+pub mod fuzz;
+pub mod interop;
 pub mod storage; 
 pub mod blockchain;
 pub mod crypto;
+pub mod wallet;
 pub mod web;
+pub mod cli;
+pub mod loadgen;
+pub mod logging;
+pub mod mempool;
+pub mod network;
+pub mod node;
+pub mod supervisor;
+pub mod testnet;
+pub mod watchtower;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 // Re-export main types
 pub use blockchain::{Block, Transaction};
 pub use consensus::ConsensusEngine;
-pub use storage::TriUnityStorage;
\ No newline at end of file
+pub use storage::{ChainStorage, TriUnityStorage};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TriUnityError {
+    QuantumSignatureError,
+    StorageError(String),
+    ValidationError(String),
+}
+
+impl std::fmt::Display for TriUnityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::QuantumSignatureError => write!(f, "quantum signature error"),
+            Self::StorageError(e) => write!(f, "storage error: {}", e),
+            Self::ValidationError(e) => write!(f, "validation error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TriUnityError {}
+
+pub type Result<T> = std::result::Result<T, TriUnityError>;
@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::cli::bench::percentile;
+use crate::consensus::ConsensusEngine;
+use crate::crypto::{QuantumKeyPair, QuantumSignature};
+use crate::node::Node;
+use crate::storage::blocks::Transaction;
+use crate::storage::database::BlockchainDB;
+
+const TEST_ACCOUNT_BALANCE: u64 = 1_000_000_000_000;
+const SUBMIT_TICK: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoadConfig {
+    pub target_tps: u64,
+    pub duration_secs: u64,
+    pub accounts: usize,
+    pub block_interval_ms: u64,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        Self {
+            target_tps: 500,
+            duration_secs: 10,
+            accounts: 16,
+            block_interval_ms: 100,
+        }
+    }
+}
+
+impl LoadConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.target_tps == 0 || self.duration_secs == 0 || self.accounts == 0 || self.block_interval_ms == 0 {
+            return Err("Load test rate, duration, accounts and block interval must be non-zero".to_string());
+        }
+        if self.duration_secs > 300 || self.accounts > 1024 {
+            return Err("Load tests are limited to 300 seconds and 1024 accounts".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Measured outcome of a load run; latencies are submission to block inclusion
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadReport {
+    pub target_tps: u64,
+    pub submitted: u64,
+    pub rejected: u64,
+    pub included: u64,
+    pub blocks_produced: u64,
+    pub elapsed_ms: u64,
+    pub submitted_tps: f64,
+    pub achieved_tps: f64,
+    pub latency_mean_ms: f64,
+    pub latency_p50_ms: f64,
+    pub latency_p99_ms: f64,
+    pub latency_max_ms: f64,
+}
+
+/// Generates signed transfers from a pool of test keys and feeds them through a node
+pub struct LoadGenerator {
+    config: LoadConfig,
+    keys: Vec<QuantumKeyPair>,
+}
+
+impl LoadGenerator {
+    pub fn new(config: LoadConfig) -> Result<Self, String> {
+        config.validate()?;
+        let keys = (0..config.accounts).map(|_| QuantumKeyPair::generate()).collect();
+        Ok(Self { config, keys })
+    }
+
+    /// Balances that must be in genesis for the test accounts to transact
+    pub fn genesis_allocations(&self) -> Vec<(Vec<u8>, u64)> {
+        self.keys
+            .iter()
+            .map(|key| (key.public_key().to_vec(), TEST_ACCOUNT_BALANCE))
+            .collect()
+    }
+
+    /// Runs against a throwaway chain so the test never touches real node data
+    pub async fn run_scratch(config: LoadConfig, consensus: Arc<ConsensusEngine>) -> Result<LoadReport, String> {
+        let generator = Self::new(config)?;
+        let path = std::env::temp_dir().join(format!(
+            "triunity_loadgen_{}",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let db = BlockchainDB::new(path.to_str().ok_or("Invalid temp path")?)?;
+        db.store_genesis_allocations(&generator.genesis_allocations())?;
+
+        let node = Arc::new(Node::open(db)?);
+        let recorder = tokio::spawn(consensus.record_events(node.events().subscribe()));
+        let report = generator.run(node).await;
+        // The recorder drains the remaining events once the last node handle is gone
+        let _ = recorder.await;
+        let _ = std::fs::remove_dir_all(&path);
+        report
+    }
+
+    /// Drives `node` at the target rate while it produces blocks, then waits for inclusion
+    pub async fn run(&self, node: Arc<Node>) -> Result<LoadReport, String> {
+        let db = node.db().clone();
+        let mut next_height = if db.block_count()? == 0 { 0 } else { db.get_latest_height()? + 1 };
+        let first_height = next_height;
+        let producer = tokio::spawn(node.clone().run(Duration::from_millis(self.config.block_interval_ms)));
+
+        let mut nonces = vec![0u64; self.keys.len()];
+        let mut pending: HashMap<[u8; 32], Instant> = HashMap::new();
+        let mut latencies = Vec::new();
+        let (mut submitted, mut rejected) = (0u64, 0u64);
+        let mut last_inclusion = None;
+
+        let start = Instant::now();
+        let duration = Duration::from_secs(self.config.duration_secs);
+        let drain_deadline = duration + Duration::from_millis(self.config.block_interval_ms * 20);
+        let mut ticker = tokio::time::interval(SUBMIT_TICK);
+
+        loop {
+            ticker.tick().await;
+            let elapsed = start.elapsed();
+
+            if elapsed < duration {
+                let due = (elapsed.as_secs_f64() * self.config.target_tps as f64) as u64;
+                while submitted + rejected < due {
+                    let index = ((submitted + rejected) % self.keys.len() as u64) as usize;
+                    let tx = self.transfer(index, nonces[index])?;
+                    match node.submit_transaction(tx) {
+                        Ok(hash) => {
+                            nonces[index] += 1;
+                            pending.insert(hash, Instant::now());
+                            submitted += 1;
+                        }
+                        Err(_) => rejected += 1,
+                    }
+                }
+            }
+
+            while let Some(block) = db.get_block(next_height)? {
+                for tx in &block.transactions {
+                    if let Some(sent) = pending.remove(&tx.hash()) {
+                        latencies.push(sent.elapsed().as_secs_f64() * 1000.0);
+                        last_inclusion = Some(start.elapsed());
+                    }
+                }
+                next_height += 1;
+            }
+
+            if elapsed >= duration && (pending.is_empty() || elapsed >= drain_deadline) {
+                break;
+            }
+        }
+        producer.abort();
+        let _ = producer.await;
+
+        let elapsed = start.elapsed().min(duration);
+        let inclusion_window = last_inclusion.unwrap_or(elapsed).max(elapsed);
+        let included = latencies.len() as u64;
+        let mut sorted = latencies.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        Ok(LoadReport {
+            target_tps: self.config.target_tps,
+            submitted,
+            rejected,
+            included,
+            blocks_produced: next_height - first_height,
+            elapsed_ms: inclusion_window.as_millis() as u64,
+            submitted_tps: submitted as f64 / elapsed.as_secs_f64(),
+            achieved_tps: included as f64 / inclusion_window.as_secs_f64(),
+            latency_mean_ms: if latencies.is_empty() { 0.0 } else { latencies.iter().sum::<f64>() / included as f64 },
+            latency_p50_ms: percentile(&sorted, 50.0),
+            latency_p99_ms: percentile(&sorted, 99.0),
+            latency_max_ms: sorted.last().copied().unwrap_or_default(),
+        })
+    }
+
+    fn transfer(&self, index: usize, nonce: u64) -> Result<Transaction, String> {
+        let key = &self.keys[index];
+        let recipient = &self.keys[(index + 1) % self.keys.len()];
+        let mut tx = Transaction::new(
+            key.public_key().to_vec(),
+            recipient.public_key().to_vec(),
+            1,
+            1,
+            nonce,
+            vec![],
+            QuantumSignature::new(vec![]),
+        );
+        tx.signature = key.sign(&tx.get_signing_data()).map_err(|e| e.to_string())?;
+        Ok(tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_load_run_includes_transactions() {
+        let config = LoadConfig {
+            target_tps: 40,
+            duration_secs: 1,
+            accounts: 3,
+            block_interval_ms: 50,
+        };
+        let consensus = Arc::new(ConsensusEngine::new());
+        let report = LoadGenerator::run_scratch(config, consensus.clone()).await.unwrap();
+
+        assert!(report.submitted > 0);
+        assert_eq!(report.rejected, 0);
+        assert_eq!(report.included, report.submitted);
+        assert!(report.blocks_produced > 0);
+        assert!(report.achieved_tps > 0.0);
+        assert!(consensus.get_performance_stats().blocks_processed >= report.blocks_produced);
+
+        assert!(LoadGenerator::new(LoadConfig { target_tps: 0, ..Default::default() }).is_err());
+
+        println!("   Load generator working!");
+    }
+}
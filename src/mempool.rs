@@ -0,0 +1,936 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::consensus::gas::GasSchedule;
+use crate::consensus::params::ChainParams;
+use crate::consensus::router::ConsensusPath;
+use crate::storage::blocks::Transaction;
+use crate::storage::state::StateManager;
+
+pub const DEFAULT_MEMPOOL_SIZE: usize = 10_000;
+
+/// Most transactions a single sponsor may have pending at once
+pub const MAX_PENDING_PER_SPONSOR: usize = 64;
+
+/// Pending transactions older than this are considered unconfirmable and dropped
+pub const DEFAULT_MAX_PENDING_AGE: Duration = Duration::from_secs(600);
+
+/// Transfers of at least this many base units (1,000 TRI) use the secure lane
+pub const DEFAULT_SECURE_LANE_THRESHOLD: u64 = 1_000 * 100_000_000;
+
+/// Fees of at least this many base units make a transaction standard class
+pub const DEFAULT_STANDARD_FEE: u64 = 10;
+
+/// Fees of at least this many base units make a transaction priority class
+pub const DEFAULT_PRIORITY_FEE: u64 = 100;
+
+/// Fast carries low-value transfers; secure carries high-value transfers and contract
+/// calls, which include governance actions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Lane {
+    Fast,
+    Secure,
+}
+
+/// Share of block space reserved for each lane. Space a lane leaves unused goes to the other
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LaneQuota {
+    pub fast: f64,
+    pub secure: f64,
+}
+
+impl LaneQuota {
+    /// Reserves nothing for either lane, so only fee classes decide the order
+    pub const UNRESERVED: Self = Self { fast: 1.0, secure: 1.0 };
+
+    pub fn for_path(path: &ConsensusPath) -> Self {
+        match path {
+            ConsensusPath::FastLane { .. } => Self { fast: 1.0, secure: 0.0 },
+            ConsensusPath::SecureLane { .. } | ConsensusPath::EmergencyMode { .. } => Self { fast: 0.0, secure: 1.0 },
+            ConsensusPath::HybridPath { fast_percentage, secure_percentage, .. } => {
+                let total = fast_percentage + secure_percentage;
+                if total > 0.0 {
+                    Self { fast: fast_percentage / total, secure: secure_percentage / total }
+                } else {
+                    Self::UNRESERVED
+                }
+            }
+        }
+    }
+
+    /// Transaction slots reserved for the fast and secure lanes out of `max`
+    fn slots(&self, max: usize) -> (usize, usize) {
+        let slots = |share: f64| ((max as f64 * share.clamp(0.0, 1.0)).round() as usize).min(max);
+        (slots(self.fast), slots(self.secure))
+    }
+}
+
+/// Tier a transaction is served in, picked by the fee it pays. Blocks fill each class's
+/// reserved space highest class first, and each class aims at its own inclusion time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeClass {
+    Economy,
+    Standard,
+    Priority,
+}
+
+impl FeeClass {
+    /// Highest class first, the order blocks are filled in
+    pub const ALL: [FeeClass; 3] = [Self::Priority, Self::Standard, Self::Economy];
+
+    /// Time from admission to inclusion the class is expected to stay within
+    pub fn inclusion_target(&self) -> Duration {
+        match self {
+            Self::Priority => Duration::from_secs(5),
+            Self::Standard => Duration::from_secs(30),
+            Self::Economy => Duration::from_secs(300),
+        }
+    }
+}
+
+/// Fee thresholds sorting transactions into classes, and the share of block space
+/// reserved for each class. Space a class leaves unused goes to the others
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FeeClasses {
+    pub standard_fee: u64,
+    pub priority_fee: u64,
+    pub economy_share: f64,
+    pub standard_share: f64,
+    pub priority_share: f64,
+}
+
+impl Default for FeeClasses {
+    fn default() -> Self {
+        Self {
+            standard_fee: DEFAULT_STANDARD_FEE,
+            priority_fee: DEFAULT_PRIORITY_FEE,
+            economy_share: 0.2,
+            standard_share: 0.3,
+            priority_share: 0.5,
+        }
+    }
+}
+
+impl FeeClasses {
+    pub fn class_of(&self, fee: u64) -> FeeClass {
+        if fee >= self.priority_fee {
+            FeeClass::Priority
+        } else if fee >= self.standard_fee {
+            FeeClass::Standard
+        } else {
+            FeeClass::Economy
+        }
+    }
+
+    /// Lowest fee that places a transaction in `class`
+    pub fn min_fee(&self, class: FeeClass) -> u64 {
+        match class {
+            FeeClass::Economy => 0,
+            FeeClass::Standard => self.standard_fee,
+            FeeClass::Priority => self.priority_fee,
+        }
+    }
+
+    /// Transaction slots reserved for `class` out of `max`
+    fn slots(&self, class: FeeClass, max: usize) -> usize {
+        let share = match class {
+            FeeClass::Economy => self.economy_share,
+            FeeClass::Standard => self.standard_share,
+            FeeClass::Priority => self.priority_share,
+        };
+        ((max as f64 * share.clamp(0.0, 1.0)).round() as usize).min(max)
+    }
+}
+
+/// Time from admission to being taken into a block
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct LaneLatency {
+    pub included: u64,
+    pub average_wait_ms: u64,
+    pub max_wait_ms: u64,
+    #[serde(skip)]
+    total_wait_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct LaneMetrics {
+    pub fast: LaneLatency,
+    pub secure: LaneLatency,
+}
+
+impl LaneLatency {
+    fn record(&mut self, wait: Duration) {
+        let wait_ms = wait.as_millis() as u64;
+        self.included += 1;
+        self.total_wait_ms = self.total_wait_ms.saturating_add(wait_ms);
+        self.average_wait_ms = self.total_wait_ms / self.included;
+        self.max_wait_ms = self.max_wait_ms.max(wait_ms);
+    }
+}
+
+/// Observed inclusion times of one fee class against its target
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ClassLatency {
+    #[serde(flatten)]
+    pub latency: LaneLatency,
+    pub target_ms: u64,
+    /// Inclusions that waited no longer than the target
+    pub within_target: u64,
+}
+
+impl ClassLatency {
+    fn new(class: FeeClass) -> Self {
+        Self { latency: LaneLatency::default(), target_ms: class.inclusion_target().as_millis() as u64, within_target: 0 }
+    }
+
+    fn record(&mut self, wait: Duration) {
+        self.latency.record(wait);
+        self.within_target += u64::from(wait.as_millis() as u64 <= self.target_ms);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ClassMetrics {
+    pub economy: ClassLatency,
+    pub standard: ClassLatency,
+    pub priority: ClassLatency,
+}
+
+impl Default for ClassMetrics {
+    fn default() -> Self {
+        Self {
+            economy: ClassLatency::new(FeeClass::Economy),
+            standard: ClassLatency::new(FeeClass::Standard),
+            priority: ClassLatency::new(FeeClass::Priority),
+        }
+    }
+}
+
+impl ClassMetrics {
+    pub fn get(&self, class: FeeClass) -> &ClassLatency {
+        match class {
+            FeeClass::Economy => &self.economy,
+            FeeClass::Standard => &self.standard,
+            FeeClass::Priority => &self.priority,
+        }
+    }
+
+    fn get_mut(&mut self, class: FeeClass) -> &mut ClassLatency {
+        match class {
+            FeeClass::Economy => &mut self.economy,
+            FeeClass::Standard => &mut self.standard,
+            FeeClass::Priority => &mut self.priority,
+        }
+    }
+}
+
+/// Fee to pay for a fee class, and how long inclusion takes in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FeeEstimate {
+    pub class: FeeClass,
+    pub fee: u64,
+    pub target_ms: u64,
+    /// Average wait of the class's included transactions; `None` before any
+    pub observed_wait_ms: Option<u64>,
+    /// Transactions of the class waiting now
+    pub pending: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum DropReason {
+    Expired,
+    NonceUsed,
+    InsufficientBalance,
+    TimedOut,
+    /// Removed by an operator
+    Removed,
+}
+
+/// Emitted whenever garbage collection evicts a pending transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DroppedTransaction {
+    pub hash: [u8; 32],
+    pub reason: DropReason,
+}
+
+/// Change to the pending set, as streamed to operators watching the mempool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MempoolUpdate {
+    Admitted {
+        #[serde(with = "hex::serde")]
+        hash: [u8; 32],
+        fee: u64,
+        size: usize,
+        lane: Lane,
+    },
+    Evicted {
+        #[serde(with = "hex::serde")]
+        hash: [u8; 32],
+        reason: DropReason,
+    },
+}
+
+impl From<&DroppedTransaction> for MempoolUpdate {
+    fn from(dropped: &DroppedTransaction) -> Self {
+        Self::Evicted { hash: dropped.hash, reason: dropped.reason }
+    }
+}
+
+/// Pending transactions paying a fee between `min_fee` and `max_fee` inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FeeBucket {
+    pub min_fee: u64,
+    pub max_fee: u64,
+    pub count: usize,
+}
+
+/// Size of the backlog, with fees bucketed by powers of two: 0, 1, 2-3, 4-7 and so on.
+/// Empty buckets are left out
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MempoolSummary {
+    pub count: usize,
+    /// Encoded size of the pending transactions
+    pub bytes: usize,
+    pub fee_histogram: Vec<FeeBucket>,
+}
+
+/// Operator view of one pending transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PendingSummary {
+    #[serde(with = "hex::serde")]
+    pub hash: [u8; 32],
+    #[serde(with = "hex::serde")]
+    pub from: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub to: Vec<u8>,
+    pub amount: u64,
+    pub fee: u64,
+    pub nonce: u64,
+    pub lane: Lane,
+    pub class: FeeClass,
+    pub waiting_secs: u64,
+}
+
+#[derive(Debug)]
+struct PendingTransaction {
+    tx: Transaction,
+    hash: [u8; 32],
+    size: usize,
+    received_at: Instant,
+}
+
+/// Pending count and fee total committed against one sponsor
+#[derive(Debug, Default, Clone, Copy)]
+struct SponsorLoad {
+    pending: usize,
+    fees: u64,
+}
+
+/// Pending transactions waiting for inclusion, kept in arrival order.
+///
+/// Sponsored transactions are capped per fee payer and, together with the payer's own
+/// pending transactions, may not commit more than the payer holds, so a sender cannot
+/// flood the pool with entries that would drain or outrun their sponsor.
+#[derive(Debug)]
+pub struct Mempool {
+    queue: VecDeque<PendingTransaction>,
+    known: HashSet<[u8; 32]>,
+    sponsors: HashMap<Vec<u8>, SponsorLoad>,
+    max_size: usize,
+    secure_threshold: u64,
+    /// Genesis gas schedule, whose price sets the least fee admitted
+    gas: GasSchedule,
+    /// Largest transaction a block could still hold
+    max_tx_bytes: usize,
+    lane_metrics: LaneMetrics,
+    fee_classes: FeeClasses,
+    class_metrics: ClassMetrics,
+    /// Encoded size of everything in `queue`
+    bytes: usize,
+}
+
+impl Mempool {
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            queue: VecDeque::new(),
+            known: HashSet::new(),
+            sponsors: HashMap::new(),
+            max_size,
+            secure_threshold: DEFAULT_SECURE_LANE_THRESHOLD,
+            gas: GasSchedule::default(),
+            max_tx_bytes: usize::MAX,
+            lane_metrics: LaneMetrics::default(),
+            fee_classes: FeeClasses::default(),
+            class_metrics: ClassMetrics::default(),
+            bytes: 0,
+        }
+    }
+
+    pub fn with_secure_threshold(mut self, threshold: u64) -> Self {
+        self.secure_threshold = threshold;
+        self
+    }
+
+    pub fn with_gas_schedule(mut self, gas: GasSchedule) -> Self {
+        self.gas = gas;
+        self
+    }
+
+    pub fn with_fee_classes(mut self, fee_classes: FeeClasses) -> Self {
+        self.fee_classes = fee_classes;
+        self
+    }
+
+    /// Follows the lane threshold and block size of `params`, those of the next block
+    pub fn set_params(&mut self, params: &ChainParams) {
+        self.secure_threshold = params.secure_lane_threshold;
+        self.max_tx_bytes = params.max_block_bytes;
+    }
+
+    pub fn lane(&self, tx: &Transaction) -> Lane {
+        if tx.is_contract_call() || tx.amount >= self.secure_threshold {
+            Lane::Secure
+        } else {
+            Lane::Fast
+        }
+    }
+
+    pub fn lane_metrics(&self) -> LaneMetrics {
+        self.lane_metrics
+    }
+
+    pub fn fee_class(&self, tx: &Transaction) -> FeeClass {
+        self.fee_classes.class_of(tx.fee)
+    }
+
+    pub fn class_metrics(&self) -> ClassMetrics {
+        self.class_metrics
+    }
+
+    /// Fee to pay for each class, highest first, with its target and observed waits
+    pub fn fee_estimates(&self) -> Vec<FeeEstimate> {
+        FeeClass::ALL
+            .into_iter()
+            .map(|class| {
+                let observed = self.class_metrics.get(class);
+                FeeEstimate {
+                    class,
+                    fee: self.fee_classes.min_fee(class),
+                    target_ms: observed.target_ms,
+                    observed_wait_ms: (observed.latency.included > 0).then_some(observed.latency.average_wait_ms),
+                    pending: self.queue.iter().filter(|pending| self.fee_class(&pending.tx) == class).count(),
+                }
+            })
+            .collect()
+    }
+
+    /// Admits a transaction that could still apply on top of `state` in the block at
+    /// `next_height`. Signatures are checked by the caller with `Transaction::check`
+    /// so verification runs outside any lock
+    pub fn insert(&mut self, tx: Transaction, state: &StateManager, next_height: u64) -> Result<[u8; 32], String> {
+        if self.queue.len() >= self.max_size {
+            return Err("Mempool is full".to_string());
+        }
+        let hash = tx.hash();
+        if self.known.contains(&hash) {
+            return Err("Transaction already pending".to_string());
+        }
+        if tx.is_expired_at(next_height) {
+            return Err(format!("Transaction expired at height {}", tx.valid_until_height.unwrap_or_default()));
+        }
+        let size = tx.size();
+        if size > self.max_tx_bytes {
+            return Err(format!("Transaction of {} bytes is larger than a block", size));
+        }
+        self.gas.check_fee(&tx)?;
+        if let Some(reason) = Self::unconfirmable(&tx, state, next_height) {
+            return Err(match reason {
+                DropReason::NonceUsed => format!("Nonce too low: account is at {}, got {}", Self::account(&tx.from, state, next_height).1, tx.nonce),
+                _ => "Insufficient balance".to_string(),
+            });
+        }
+        if Self::is_sponsored(&tx) {
+            let load = self.sponsors.get(tx.fee_account()).copied().unwrap_or_default();
+            if load.pending >= MAX_PENDING_PER_SPONSOR {
+                return Err("Fee payer has too many pending sponsored transactions".to_string());
+            }
+            let own_outflow = self.queue
+                .iter()
+                .filter(|pending| pending.tx.from.as_slice() == tx.fee_account())
+                .fold(0u64, |total, pending| total.saturating_add(Self::sender_cost(&pending.tx)));
+            if load.fees.saturating_add(own_outflow).saturating_add(tx.fee) > Self::account(tx.fee_account(), state, next_height).0 {
+                return Err("Fee payer cannot cover all pending sponsored fees".to_string());
+            }
+        }
+
+        self.track(&tx, true);
+        self.known.insert(hash);
+        self.bytes += size;
+        self.queue.push_back(PendingTransaction { tx, hash, size, received_at: Instant::now() });
+        Ok(hash)
+    }
+
+    fn track(&mut self, tx: &Transaction, added: bool) {
+        if !Self::is_sponsored(tx) {
+            return;
+        }
+        let load = self.sponsors.entry(tx.fee_account().to_vec()).or_default();
+        if added {
+            load.pending += 1;
+            load.fees = load.fees.saturating_add(tx.fee);
+        } else {
+            load.pending = load.pending.saturating_sub(1);
+            load.fees = load.fees.saturating_sub(tx.fee);
+            if load.pending == 0 {
+                self.sponsors.remove(tx.fee_account());
+            }
+        }
+    }
+
+    fn forget(&mut self, pending: &PendingTransaction) {
+        self.known.remove(&pending.hash);
+        self.bytes -= pending.size;
+        self.track(&pending.tx, false);
+    }
+
+    /// Spendable balance and nonce of `address` in the block at `height`
+    fn account(address: &[u8], state: &StateManager, height: u64) -> (u64, u64) {
+        let nonce = state.get_account(address).map_or(0, |account| account.nonce);
+        (state.spendable_at(address, height), nonce)
+    }
+
+    /// Whether someone other than the sender pays the fee, as state decides it: a sender
+    /// naming itself as fee payer pays like an unsponsored sender
+    fn is_sponsored(tx: &Transaction) -> bool {
+        tx.fee_account() != tx.from.as_slice()
+    }
+
+    /// What `tx` takes from its sender's balance
+    fn sender_cost(tx: &Transaction) -> u64 {
+        match Self::is_sponsored(tx) {
+            true => tx.value(),
+            false => tx.value().saturating_add(tx.fee),
+        }
+    }
+
+    fn unconfirmable(tx: &Transaction, state: &StateManager, height: u64) -> Option<DropReason> {
+        let (balance, nonce) = Self::account(&tx.from, state, height);
+        if tx.nonce < nonce {
+            Some(DropReason::NonceUsed)
+        } else if balance < Self::sender_cost(tx) || Self::account(tx.fee_account(), state, height).0 < tx.fee {
+            Some(DropReason::InsufficientBalance)
+        } else {
+            None
+        }
+    }
+
+    /// Removes up to `max` of the oldest transactions for block production
+    pub fn take(&mut self, max: usize) -> Vec<Transaction> {
+        self.take_with_quota(max, LaneQuota::UNRESERVED)
+    }
+
+    /// Removes up to `max` transactions. Each fee class first takes its reserved share
+    /// within the lanes' reserved shares, then the lanes fill up, then any leftover
+    /// space; every stage goes highest class first and oldest first within a class. A
+    /// sender's later transactions are never taken ahead of an earlier one left behind
+    pub fn take_with_quota(&mut self, max: usize, quota: LaneQuota) -> Vec<Transaction> {
+        let (fast_slots, secure_slots) = quota.slots(max);
+        let mut lane_budget = [fast_slots, secure_slots];
+        let mut class_budget = [FeeClass::Economy, FeeClass::Standard, FeeClass::Priority].map(|class| self.fee_classes.slots(class, max));
+        let lanes: Vec<Lane> = self.queue.iter().map(|pending| self.lane(&pending.tx)).collect();
+        let classes: Vec<FeeClass> = self.queue.iter().map(|pending| self.fee_class(&pending.tx)).collect();
+        let mut selected = vec![false; self.queue.len()];
+        let mut count = 0;
+
+        for stage in 0..3 {
+            for class in FeeClass::ALL {
+                let mut blocked: HashSet<&[u8]> = HashSet::new();
+                for (index, pending) in self.queue.iter().enumerate() {
+                    if selected[index] {
+                        continue;
+                    }
+                    let (lane, own_class) = (lanes[index] as usize, classes[index]);
+                    let room = match stage {
+                        0 => lane_budget[lane] > 0 && class_budget[own_class as usize] > 0,
+                        1 => lane_budget[lane] > 0,
+                        _ => true,
+                    };
+                    if own_class == class && room && count < max && !blocked.contains(pending.tx.from.as_slice()) {
+                        lane_budget[lane] = lane_budget[lane].saturating_sub(1);
+                        class_budget[own_class as usize] = class_budget[own_class as usize].saturating_sub(1);
+                        selected[index] = true;
+                        count += 1;
+                    } else {
+                        blocked.insert(&pending.tx.from);
+                    }
+                }
+            }
+        }
+
+        let mut taken = Vec::with_capacity(count);
+        let mut kept = VecDeque::with_capacity(self.queue.len() - count);
+        let entries = std::mem::take(&mut self.queue).into_iter().zip(lanes).zip(classes).zip(selected);
+        for (((pending, lane), class), selected) in entries {
+            if selected {
+                let latency = match lane {
+                    Lane::Fast => &mut self.lane_metrics.fast,
+                    Lane::Secure => &mut self.lane_metrics.secure,
+                };
+                let wait = pending.received_at.elapsed();
+                latency.record(wait);
+                self.class_metrics.get_mut(class).record(wait);
+                self.forget(&pending);
+                taken.push(pending.tx);
+            } else {
+                kept.push_back(pending);
+            }
+        }
+        self.queue = kept;
+        taken
+    }
+
+    /// Drops transactions that were included in a block imported from a peer
+    pub fn remove_included(&mut self, included: &[Transaction]) {
+        let hashes: HashSet<[u8; 32]> = included.iter().map(|tx| tx.hash()).collect();
+        let (removed, kept): (Vec<_>, Vec<_>) = self.queue
+            .drain(..)
+            .partition(|pending| hashes.contains(&pending.hash));
+        self.queue = kept.into();
+        for pending in &removed {
+            self.forget(pending);
+        }
+    }
+
+    /// Evicts transactions that expired before `next_height`, can no longer apply on
+    /// top of `state`, or have waited longer than `max_age`
+    pub fn collect_garbage(&mut self, state: &StateManager, next_height: u64, max_age: Duration) -> Vec<DroppedTransaction> {
+        let mut dropped = Vec::new();
+        let mut kept = VecDeque::new();
+        for pending in std::mem::take(&mut self.queue) {
+            let reason = if pending.tx.is_expired_at(next_height) {
+                Some(DropReason::Expired)
+            } else if let Some(reason) = Self::unconfirmable(&pending.tx, state, next_height) {
+                Some(reason)
+            } else if pending.received_at.elapsed() > max_age {
+                Some(DropReason::TimedOut)
+            } else {
+                None
+            };
+            match reason {
+                Some(reason) => {
+                    self.forget(&pending);
+                    dropped.push(DroppedTransaction { hash: pending.hash, reason });
+                }
+                None => kept.push_back(pending),
+            }
+        }
+        self.queue = kept;
+        dropped
+    }
+
+    /// Removes a pending transaction, returning whether it was in the pool
+    pub fn remove(&mut self, hash: &[u8; 32]) -> bool {
+        let Some(index) = self.queue.iter().position(|pending| pending.hash == *hash) else {
+            return false;
+        };
+        if let Some(pending) = self.queue.remove(index) {
+            self.forget(&pending);
+        }
+        true
+    }
+
+    /// Up to `limit` pending transactions in arrival order, only `sender`'s when given
+    pub fn summaries(&self, sender: Option<&[u8]>, limit: usize) -> Vec<PendingSummary> {
+        self.queue
+            .iter()
+            .filter(|pending| sender.is_none_or(|sender| pending.tx.from == sender))
+            .take(limit)
+            .map(|pending| PendingSummary {
+                hash: pending.hash,
+                from: pending.tx.from.clone(),
+                to: pending.tx.to.clone(),
+                amount: pending.tx.amount,
+                fee: pending.tx.fee,
+                nonce: pending.tx.nonce,
+                lane: self.lane(&pending.tx),
+                class: self.fee_class(&pending.tx),
+                waiting_secs: pending.received_at.elapsed().as_secs(),
+            })
+            .collect()
+    }
+
+    pub fn summary(&self) -> MempoolSummary {
+        let mut buckets: Vec<usize> = vec![0; u64::BITS as usize + 1];
+        for pending in &self.queue {
+            buckets[(u64::BITS - pending.tx.fee.leading_zeros()) as usize] += 1;
+        }
+        let fee_histogram = buckets
+            .into_iter()
+            .enumerate()
+            .filter(|(_, count)| *count > 0)
+            .map(|(bits, count)| match bits {
+                0 => FeeBucket { min_fee: 0, max_fee: 0, count },
+                _ => FeeBucket { min_fee: 1 << (bits - 1), max_fee: u64::MAX >> (u64::BITS as usize - bits), count },
+            })
+            .collect();
+        MempoolSummary { count: self.queue.len(), bytes: self.bytes, fee_histogram }
+    }
+
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.known.contains(hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMPOOL_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumKeyPair;
+
+    fn signed_transfer(keypair: &QuantumKeyPair, nonce: u64, amount: u64) -> Transaction {
+        signed_transfer_paying(keypair, nonce, amount, 1)
+    }
+
+    fn signed_transfer_paying(keypair: &QuantumKeyPair, nonce: u64, amount: u64, fee: u64) -> Transaction {
+        let mut tx = Transaction::new(
+            keypair.public_key().to_vec(),
+            vec![0xbb; 32],
+            amount,
+            fee,
+            nonce,
+            vec![],
+            crate::crypto::QuantumSignature::new(vec![]),
+        );
+        tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+        tx
+    }
+
+    #[test]
+    fn test_mempool_admission() {
+        let keypair = QuantumKeyPair::generate();
+        let mut state = StateManager::from_allocations(&[(keypair.public_key().to_vec(), 100)]).unwrap();
+        state.get_or_create_account(keypair.public_key()).nonce = 1;
+        let mut mempool = Mempool::new(2);
+
+        let tx = signed_transfer(&keypair, 1, 10);
+        let hash = mempool.insert(tx.clone(), &state, 0).unwrap();
+        assert!(mempool.contains(&hash));
+        assert!(mempool.insert(tx, &state, 0).is_err());
+        assert!(mempool.insert(signed_transfer(&keypair, 0, 10), &state, 0).is_err());
+        assert!(mempool.insert(signed_transfer(&keypair, 2, 500), &state, 0).is_err());
+
+        let second = signed_transfer(&keypair, 2, 10);
+        let size = second.size();
+        mempool.insert(second, &state, 0).unwrap();
+        assert!(mempool.insert(signed_transfer(&keypair, 3, 10), &state, 0).is_err());
+        let summary = mempool.summary();
+        assert_eq!((summary.count, summary.bytes), (2, 2 * size));
+        assert_eq!(summary.fee_histogram, vec![FeeBucket { min_fee: 1, max_fee: 1, count: 2 }]);
+
+        let taken = mempool.take(10);
+        assert_eq!(taken.iter().map(|tx| tx.nonce).collect::<Vec<_>>(), vec![1, 2]);
+        assert!(mempool.is_empty());
+        assert!(!mempool.contains(&hash));
+        assert_eq!(mempool.summary(), MempoolSummary { count: 0, bytes: 0, fee_histogram: vec![] });
+
+        // With gas priced, a fee below the transaction's gas is refused
+        let mut mempool = mempool.with_gas_schedule(GasSchedule { gas_price: 1, ..GasSchedule::default() });
+        assert!(mempool.insert(signed_transfer(&keypair, 3, 10), &state, 0).unwrap_err().contains("gas costs"));
+
+        println!("   Mempool admission working!");
+    }
+
+    #[test]
+    fn test_garbage_collection() {
+        let keypair = QuantumKeyPair::generate();
+        let mut state = StateManager::from_allocations(&[(keypair.public_key().to_vec(), 100)]).unwrap();
+        let mut mempool = Mempool::default();
+
+        let mut expiring = signed_transfer(&keypair, 0, 10).with_valid_until(5);
+        expiring.signature = keypair.sign(&expiring.get_signing_data()).unwrap();
+        assert!(expiring.check().is_ok());
+        assert!(mempool.insert(expiring.clone(), &state, 6).is_err());
+        let expiring = mempool.insert(expiring, &state, 3).unwrap();
+        let used = mempool.insert(signed_transfer(&keypair, 1, 10), &state, 3).unwrap();
+        let unfunded = mempool.insert(signed_transfer(&keypair, 3, 10), &state, 3).unwrap();
+
+        let account = state.get_or_create_account(keypair.public_key());
+        account.nonce = 2;
+        account.balance = 5;
+        let mut dropped = mempool.collect_garbage(&state, 6, DEFAULT_MAX_PENDING_AGE);
+        dropped.sort_by_key(|drop| drop.hash);
+        let mut expected = vec![
+            DroppedTransaction { hash: expiring, reason: DropReason::Expired },
+            DroppedTransaction { hash: used, reason: DropReason::NonceUsed },
+            DroppedTransaction { hash: unfunded, reason: DropReason::InsufficientBalance },
+        ];
+        expected.sort_by_key(|drop| drop.hash);
+        assert_eq!(dropped, expected);
+        assert!(mempool.is_empty());
+
+        state.get_or_create_account(keypair.public_key()).balance = 100;
+        let stale = mempool.insert(signed_transfer(&keypair, 2, 10), &state, 6).unwrap();
+        let dropped = mempool.collect_garbage(&state, 6, Duration::ZERO);
+        assert_eq!(dropped, vec![DroppedTransaction { hash: stale, reason: DropReason::TimedOut }]);
+        assert!(!mempool.contains(&stale));
+
+        println!("   Mempool garbage collection working!");
+    }
+
+    #[test]
+    fn test_sponsored_fees() {
+        let sender = QuantumKeyPair::generate();
+        let sponsor = QuantumKeyPair::generate();
+        let mut state = StateManager::from_allocations(&[
+            (sender.public_key().to_vec(), 10),
+            (sponsor.public_key().to_vec(), 2),
+        ]).unwrap();
+        let mut mempool = Mempool::default();
+        let sponsored = |nonce: u64| {
+            let mut tx = signed_transfer(&sender, nonce, 10).with_fee_payer(sponsor.public_key().to_vec());
+            tx.signature = sender.sign(&tx.get_signing_data()).unwrap();
+            tx.fee_payer.as_mut().unwrap().signature = sponsor.sign(&tx.fee_payer_signing_data()).unwrap();
+            tx
+        };
+
+        // The sender can only afford the amount, so the fee has to come from the sponsor
+        assert!(mempool.insert(signed_transfer(&sender, 0, 10), &state, 0).is_err());
+        let first = sponsored(0);
+        assert!(first.check().is_ok());
+        let mut forged = first.clone();
+        forged.fee_payer.as_mut().unwrap().signature = sender.sign(&forged.fee_payer_signing_data()).unwrap();
+        assert!(forged.check().is_err());
+
+        mempool.insert(first.clone(), &state, 0).unwrap();
+        mempool.insert(sponsored(1), &state, 0).unwrap();
+        assert!(mempool.insert(sponsored(2), &state, 0).unwrap_err().contains("pending sponsored fees"));
+
+        state.apply_transaction(&first).unwrap();
+        assert_eq!(state.get_account(sender.public_key()).unwrap().balance, 0);
+        assert_eq!(state.get_account(sponsor.public_key()).unwrap().balance, 1);
+        mempool.remove_included(&[first]);
+        assert_eq!(mempool.sponsors[sponsor.public_key()].fees, 1);
+
+        mempool.take(1);
+        assert!(mempool.sponsors.is_empty());
+
+        // The sponsor's own pending spending counts against what it can cover
+        let state = StateManager::from_allocations(&[
+            (sender.public_key().to_vec(), 10),
+            (sponsor.public_key().to_vec(), 2),
+        ]).unwrap();
+        let mut mempool = Mempool::default();
+        mempool.insert(signed_transfer(&sponsor, 0, 1), &state, 0).unwrap();
+        assert!(mempool.insert(sponsored(0), &state, 0).unwrap_err().contains("pending sponsored fees"));
+
+        // A sender naming itself as fee payer is not sponsored, so it pays the fee itself
+        let mut own = signed_transfer(&sender, 0, 10).with_fee_payer(sender.public_key().to_vec());
+        own.signature = sender.sign(&own.get_signing_data()).unwrap();
+        assert_eq!(mempool.insert(own, &state, 0).unwrap_err(), "Insufficient balance");
+        assert!(mempool.sponsors.is_empty());
+
+        println!("   Sponsored transaction fees working!");
+    }
+
+    #[test]
+    fn test_lane_quotas() {
+        let small = QuantumKeyPair::generate();
+        let large = QuantumKeyPair::generate();
+        let state = StateManager::from_allocations(&[
+            (small.public_key().to_vec(), 1_000),
+            (large.public_key().to_vec(), 1_000),
+        ]).unwrap();
+        let mut mempool = Mempool::default().with_secure_threshold(50);
+        for nonce in 0..4 {
+            mempool.insert(signed_transfer(&small, nonce, 10), &state, 0).unwrap();
+        }
+        for nonce in 0..4 {
+            mempool.insert(signed_transfer(&large, nonce, 100), &state, 0).unwrap();
+        }
+        assert_eq!(mempool.lane(&signed_transfer(&large, 0, 100)), Lane::Secure);
+
+        let hybrid = ConsensusPath::HybridPath { fast_percentage: 0.3, secure_percentage: 0.2, adaptive_threshold: 0.5 };
+        let quota = LaneQuota::for_path(&hybrid);
+        assert!((quota.fast - 0.6).abs() < 1e-9);
+
+        // 5 slots: 3 fast and 2 secure, even though the fast lane is older
+        let taken = mempool.take_with_quota(5, quota);
+        let amounts: Vec<u64> = taken.iter().map(|tx| tx.amount).collect();
+        assert_eq!(amounts, vec![10, 10, 10, 100, 100]);
+        assert_eq!(mempool.lane_metrics().fast.included, 3);
+        assert_eq!(mempool.lane_metrics().secure.included, 2);
+
+        // The secure path still spends unused space on fast transactions, in nonce order
+        let secure = ConsensusPath::SecureLane { validator_threshold: 1, security_level: 0.9, decentralization_score: 0.9 };
+        let taken = mempool.take_with_quota(10, LaneQuota::for_path(&secure));
+        let nonces: Vec<(u64, u64)> = taken.iter().map(|tx| (tx.amount, tx.nonce)).collect();
+        assert_eq!(nonces, vec![(10, 3), (100, 2), (100, 3)]);
+        assert!(mempool.is_empty());
+
+        println!("   Mempool lane quotas working!");
+    }
+
+    #[test]
+    fn test_fee_classes() {
+        let economy = QuantumKeyPair::generate();
+        let standard = QuantumKeyPair::generate();
+        let priority = QuantumKeyPair::generate();
+        let state = StateManager::from_allocations(&[
+            (economy.public_key().to_vec(), 10_000),
+            (standard.public_key().to_vec(), 10_000),
+            (priority.public_key().to_vec(), 10_000),
+        ]).unwrap();
+        let mut mempool = Mempool::default();
+        for nonce in 0..4 {
+            mempool.insert(signed_transfer(&economy, nonce, 10), &state, 0).unwrap();
+        }
+        for nonce in 0..2 {
+            mempool.insert(signed_transfer_paying(&standard, nonce, 10, DEFAULT_STANDARD_FEE), &state, 0).unwrap();
+        }
+        for nonce in 0..2 {
+            mempool.insert(signed_transfer_paying(&priority, nonce, 10, DEFAULT_PRIORITY_FEE), &state, 0).unwrap();
+        }
+        assert_eq!(mempool.fee_class(&signed_transfer_paying(&standard, 0, 10, 99)), FeeClass::Standard);
+
+        // 4 slots: 2 priority, 1 standard and 1 economy, kept in arrival order
+        let taken = mempool.take_with_quota(4, LaneQuota::UNRESERVED);
+        let fees: Vec<u64> = taken.iter().map(|tx| tx.fee).collect();
+        assert_eq!(fees, vec![1, DEFAULT_STANDARD_FEE, DEFAULT_PRIORITY_FEE, DEFAULT_PRIORITY_FEE]);
+        let metrics = mempool.class_metrics();
+        assert_eq!(metrics.priority.latency.included, 2);
+        assert_eq!(metrics.priority.within_target, 2);
+        assert_eq!(metrics.economy.target_ms, 300_000);
+
+        // A priority fee does not let a sender skip its own earlier transactions
+        mempool.insert(signed_transfer_paying(&economy, 4, 10, DEFAULT_PRIORITY_FEE), &state, 0).unwrap();
+        let taken = mempool.take_with_quota(1, LaneQuota::UNRESERVED);
+        assert_eq!((taken[0].fee, taken[0].nonce), (DEFAULT_STANDARD_FEE, 1));
+
+        let estimates = mempool.fee_estimates();
+        let classes: Vec<(FeeClass, u64, usize)> = estimates.iter().map(|estimate| (estimate.class, estimate.fee, estimate.pending)).collect();
+        assert_eq!(
+            classes,
+            vec![(FeeClass::Priority, DEFAULT_PRIORITY_FEE, 1), (FeeClass::Standard, DEFAULT_STANDARD_FEE, 0), (FeeClass::Economy, 0, 3)]
+        );
+        assert!(estimates[0].observed_wait_ms.is_some());
+        assert_eq!(serde_json::to_value(estimates[0].class).unwrap(), "priority");
+
+        println!("   Mempool fee classes working!");
+    }
+}
@@ -0,0 +1,893 @@
+pub mod bandwidth;
+pub mod compression;
+pub mod discovery;
+pub mod handshake;
+pub mod lanes;
+pub mod light;
+pub mod message;
+pub mod nat;
+pub mod profile;
+pub mod serving;
+pub mod topology;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::cli::inspect::short_hex;
+use crate::config::{CompressionConfig, NetworkConfig};
+use crate::consensus::metrics::{SecurityEventType, SecuritySeverity};
+use crate::consensus::proposal::BlockProposal;
+use crate::events::NodeEvent;
+use crate::log;
+use crate::node::{blocking, BlockImport, Node};
+use crate::storage::availability::CodedBody;
+use crate::storage::blocks::{Block, AVAILABILITY_BLOCK_VERSION};
+use crate::storage::cache::LruCache;
+use crate::storage::state::StateManager;
+use bandwidth::{Bandwidth, NetworkStats, PeerBandwidth, THROTTLE_CHUNK_BYTES};
+use compression::{Compression, CompressionPolicy};
+use discovery::{NodeDiscovery, PeerAddress, MAX_SHARED_PEERS};
+use lanes::{LaneSender, OutgoingFrame};
+use handshake::{check_chain, Capability, DisconnectReason, HandshakeLog, LocalOffer, HANDSHAKE_WINDOW, MAX_HANDSHAKES_PER_WINDOW, SUPPORTED_PROTOCOLS};
+use message::{read_header, NetworkMessage, Reassembly};
+use nat::{AddressVotes, PortMapping};
+use profile::NetworkId;
+use serving::{ServingStats, SyncServing};
+use topology::{Direction, PeerView, Topology};
+
+/// Blocks returned per `GetBlocks` request unless `network.sync_serving` lowers it
+pub const SYNC_BATCH_SIZE: u64 = 128;
+
+/// Connections beyond which discovered peers are no longer dialed
+pub const MAX_PEERS: usize = 25;
+
+/// Coded block bodies kept for serving chunk samples
+const CODED_BODY_CACHE: usize = 8;
+
+/// Verified proposals kept for checking bare blocks relayed by legacy peers
+const PROPOSAL_CACHE: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct PeerInfo {
+    pub node_id: Vec<u8>,
+    pub address: SocketAddr,
+    pub listen_port: u16,
+    /// Public address the peer advertised in its handshake
+    pub external_address: Option<SocketAddr>,
+    /// Protocol version negotiated with the peer
+    pub protocol: String,
+    /// Capabilities both sides offer
+    pub capabilities: Vec<Capability>,
+    /// Compression applied to block transfers sent to this peer
+    pub compression: Option<Compression>,
+    pub direction: Direction,
+    /// Round trip of the last answered `Ping`
+    pub latency: Option<Duration>,
+}
+
+impl PeerInfo {
+    /// Where the peer accepts connections: its advertised address, or its listen port
+    /// on the address it connected from
+    pub fn dial_address(&self) -> SocketAddr {
+        self.external_address
+            .unwrap_or_else(|| SocketAddr::new(self.address.ip(), self.listen_port))
+    }
+}
+
+struct PeerHandle {
+    info: PeerInfo,
+    sender: LaneSender,
+}
+
+/// TCP gossip between nodes: block and transaction propagation plus catch-up sync
+pub struct NetworkService {
+    node: Arc<Node>,
+    network: NetworkId,
+    /// Peers dialed by `bootstrap`; empty for the network's own bootstrap peers
+    bootstrap_peers: Vec<String>,
+    listen_port: Mutex<u16>,
+    peers: Mutex<HashMap<Vec<u8>, PeerHandle>>,
+    address_votes: Mutex<AddressVotes>,
+    port_mapping: Mutex<Option<PortMapping>>,
+    discovery: Mutex<NodeDiscovery>,
+    bandwidth: Bandwidth,
+    compression: CompressionConfig,
+    serve_light_clients: bool,
+    serve_snapshots: bool,
+    legacy_block_gossip: bool,
+    serving: SyncServing,
+    /// Node ids each peer named in its last `Peers` message, for the topology view
+    reported_peers: Mutex<HashMap<Vec<u8>, Vec<Vec<u8>>>>,
+    /// Reference point of the microsecond stamps carried by `Ping`
+    started: Instant,
+    handshakes: Mutex<HandshakeLog>,
+    /// Recently coded block bodies, so sampling one block costs a single encoding
+    coded_bodies: Mutex<LruCache<[u8; 32], Arc<CodedBody>>>,
+    /// Recently verified proposals by block hash, which bare blocks must match
+    proposals: Mutex<LruCache<[u8; 32], BlockProposal>>,
+}
+
+/// Tells the peer why the connection is closing; queued frames are still written
+/// before the writer drops the socket
+fn disconnect(sender: &LaneSender, address: SocketAddr, reason: DisconnectReason) {
+    if reason != DisconnectReason::AlreadyConnected {
+        log!(Warn, "Disconnecting {}: {}", address, reason);
+    }
+    let _ = sender.send(&NetworkMessage::Disconnect(reason));
+}
+
+impl NetworkService {
+    pub fn new(node: Arc<Node>) -> Arc<Self> {
+        Self::with_config(node, &NetworkConfig::default())
+    }
+
+    pub fn with_config(node: Arc<Node>, config: &NetworkConfig) -> Arc<Self> {
+        Arc::new(Self {
+            node,
+            network: config.id,
+            bootstrap_peers: config.bootstrap_peers.clone(),
+            listen_port: Mutex::new(0),
+            peers: Mutex::new(HashMap::new()),
+            address_votes: Mutex::new(AddressVotes::default()),
+            port_mapping: Mutex::new(None),
+            discovery: Mutex::new(NodeDiscovery::new()),
+            bandwidth: Bandwidth::new(config.bandwidth.clone()),
+            compression: config.compression.clone(),
+            serve_light_clients: config.serve_light_clients,
+            serve_snapshots: config.serve_snapshots,
+            legacy_block_gossip: config.legacy_block_gossip,
+            serving: SyncServing::new(config.sync_serving.clone()),
+            reported_peers: Mutex::new(HashMap::new()),
+            started: Instant::now(),
+            handshakes: Mutex::new(HandshakeLog::default()),
+            coded_bodies: Mutex::new(LruCache::new(CODED_BODY_CACHE)),
+            proposals: Mutex::new(LruCache::new(PROPOSAL_CACHE)),
+        })
+    }
+
+    pub fn node(&self) -> &Arc<Node> {
+        &self.node
+    }
+
+    pub fn network_id(&self) -> NetworkId {
+        self.network
+    }
+
+    /// Dials the configured bootstrap peers, or the network's own, in the background.
+    /// Returns how many addresses were found to dial
+    pub async fn bootstrap(self: &Arc<Self>) -> usize {
+        let peers = self.network.profile().resolve_peers(&self.bootstrap_peers).await;
+        for peer in &peers {
+            self.add_peer(*peer);
+        }
+        peers.len()
+    }
+
+    /// Binds `address` and accepts peers in the background, returning the bound address
+    pub async fn listen(self: &Arc<Self>, address: SocketAddr) -> Result<SocketAddr, String> {
+        let listener = TcpListener::bind(address)
+            .await
+            .map_err(|e| format!("Could not bind {}: {}", address, e))?;
+        let local = listener.local_addr().map_err(|e| e.to_string())?;
+        *self.listen_port.lock().unwrap() = local.port();
+
+        let service = self.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, peer_address)) = listener.accept().await {
+                service.spawn_connection(stream, peer_address, Direction::Inbound);
+            }
+        });
+        Ok(local)
+    }
+
+    /// Dials `address` in the background, for callers that cannot wait on the connection
+    pub fn add_peer(self: &Arc<Self>, address: SocketAddr) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = service.connect(address).await {
+                log!(Warn, "Could not add peer: {}", e);
+            }
+        });
+    }
+
+    /// Tells a connected peer it is being dropped and closes the connection once the
+    /// frames queued for it are written. Returns whether the peer was connected
+    pub fn remove_peer(&self, node_id: &[u8]) -> bool {
+        let Some(peer) = self.peers.lock().unwrap().remove(node_id) else {
+            return false;
+        };
+        disconnect(&peer.sender, peer.info.address, DisconnectReason::Requested);
+        true
+    }
+
+    pub async fn connect(self: &Arc<Self>, address: SocketAddr) -> Result<(), String> {
+        let stream = TcpStream::connect(address)
+            .await
+            .map_err(|e| format!("Could not connect to {}: {}", address, e))?;
+        self.spawn_connection(stream, address, Direction::Outbound);
+        Ok(())
+    }
+
+    pub fn peers(&self) -> Vec<PeerInfo> {
+        self.peers.lock().unwrap().values().map(|peer| peer.info.clone()).collect()
+    }
+
+    /// Connected peers with their traffic and latency, ordered by node id
+    pub fn peer_views(&self) -> Vec<PeerView> {
+        let traffic: HashMap<Vec<u8>, _> = self.stats().peers.into_iter().map(|peer| (peer.node_id, peer.traffic)).collect();
+        let mut views: Vec<PeerView> = self
+            .peers()
+            .into_iter()
+            .map(|peer| PeerView {
+                node_id: short_hex(&peer.node_id),
+                address: Some(peer.address),
+                direction: peer.direction,
+                protocol: peer.protocol,
+                latency_ms: peer.latency.map(|latency| latency.as_secs_f64() * 1000.0),
+                traffic: traffic.get(&peer.node_id).copied().unwrap_or_default(),
+            })
+            .collect();
+        views.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        views
+    }
+
+    pub fn topology(&self) -> Topology {
+        Topology::build(&self.node.node_id(), &self.peer_views(), &self.reported_peers.lock().unwrap())
+    }
+
+    /// Bytes sent and received per peer and per message type
+    pub fn stats(&self) -> NetworkStats {
+        self.bandwidth.stats()
+    }
+
+    /// Load of answering sync requests
+    pub fn serving_stats(&self) -> ServingStats {
+        self.serving.stats()
+    }
+
+    /// Trust score of a known peer, `None` if discovery has not heard of it
+    pub fn peer_trust(&self, node_id: &[u8]) -> Option<i32> {
+        self.discovery.lock().unwrap().trust(node_id)
+    }
+
+    /// Dials `peer` unless it is already connected, lowering its trust if that fails
+    async fn dial(self: Arc<Self>, peer: PeerAddress) {
+        if self.peers.lock().unwrap().contains_key(&peer.node_id) {
+            return;
+        }
+        if let Err(e) = self.connect(peer.address).await {
+            log!(Info, "Discovered peer {} unreachable: {}", short_hex(&peer.node_id), e);
+            self.discovery.lock().unwrap().record_failure(&peer.node_id);
+        }
+    }
+
+    /// Dials up to `peers` discovered peers, keeping the total under `MAX_PEERS`
+    fn dial_discovered(self: &Arc<Self>, peers: Vec<PeerAddress>) {
+        let room = MAX_PEERS.saturating_sub(self.peers.lock().unwrap().len());
+        for peer in peers.into_iter().take(room) {
+            tokio::spawn(self.clone().dial(peer));
+        }
+    }
+
+    /// Asks every peer for the peers it trusts, pings those that answer pings, dials
+    /// known peers that are not connected and asks again for the ancestors of blocks
+    /// still waiting for their parent, every `interval` until the task is dropped
+    pub async fn run_peer_exchange(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            self.broadcast(NetworkMessage::GetPeers);
+            self.ping_peers();
+            for request in self.node.orphan_sync_requests() {
+                self.request_blocks(&request.peer, request.from);
+            }
+            let candidates = self.discovery.lock().unwrap().dial_candidates(MAX_PEERS);
+            self.dial_discovered(candidates);
+        }
+    }
+
+    fn ping_peers(&self) {
+        let stamp = self.started.elapsed().as_micros() as u64;
+        for peer in self.peers.lock().unwrap().values() {
+            if peer.info.capabilities.contains(&Capability::Ping) {
+                let _ = peer.sender.send(&NetworkMessage::Ping(stamp));
+            }
+        }
+    }
+
+    /// Records the round trip of a `Pong` echoing the stamp of one of this node's pings
+    fn record_pong(&self, from: &[u8], stamp: u64) {
+        let now = self.started.elapsed().as_micros() as u64;
+        let Some(round_trip) = now.checked_sub(stamp) else {
+            return;
+        };
+        if let Some(peer) = self.peers.lock().unwrap().get_mut(from) {
+            peer.info.latency = Some(Duration::from_micros(round_trip));
+        }
+    }
+
+    /// Address other nodes can dial: the router's mapped port when port mapping
+    /// succeeded, otherwise the IP peers agree they see with the listen port
+    pub fn external_address(&self) -> Option<SocketAddr> {
+        if let Some(mapping) = *self.port_mapping.lock().unwrap() {
+            return Some(mapping.external);
+        }
+        let ip = self.address_votes.lock().unwrap().confirmed()?;
+        Some(SocketAddr::new(ip, *self.listen_port.lock().unwrap()))
+    }
+
+    /// Keeps a UPnP or NAT-PMP mapping of the listen port alive, renewing it at half its
+    /// lifetime. Returns when no gateway supports either protocol
+    pub async fn run_port_mapping(self: Arc<Self>) {
+        loop {
+            let port = *self.listen_port.lock().unwrap();
+            match nat::map_port(port).await {
+                Ok(mapping) => {
+                    if self.port_mapping.lock().unwrap().replace(mapping).is_none() {
+                        log!(Info, "Mapped port {} to {} via {:?}", port, mapping.external, mapping.protocol);
+                    }
+                    tokio::time::sleep((mapping.lifetime / 2).max(Duration::from_secs(60))).await;
+                }
+                Err(e) => {
+                    *self.port_mapping.lock().unwrap() = None;
+                    log!(Info, "Port mapping unavailable: {}", e);
+                    return;
+                }
+            }
+        }
+    }
+
+    pub fn broadcast(&self, message: NetworkMessage) {
+        self.broadcast_except(&[], message);
+    }
+
+    fn broadcast_except(&self, skip: &[u8], message: NetworkMessage) {
+        for (node_id, peer) in self.peers.lock().unwrap().iter() {
+            if node_id.as_slice() != skip {
+                let _ = peer.sender.send(&message);
+            }
+        }
+    }
+
+    /// Sends `proposal` to peers that check proposals, and its bare block to the rest
+    /// only when `network.legacy_block_gossip` allows it
+    fn announce(&self, skip: &[u8], proposal: BlockProposal) {
+        self.proposals.lock().unwrap().insert(proposal.block.hash(), proposal.clone());
+        let block = NetworkMessage::NewBlock(proposal.block.clone());
+        let proposal = NetworkMessage::Proposal(proposal);
+        for (node_id, peer) in self.peers.lock().unwrap().iter() {
+            if node_id.as_slice() != skip {
+                let message = match peer.info.capabilities.contains(&Capability::Proposals) {
+                    true => &proposal,
+                    false if self.legacy_block_gossip => &block,
+                    false => continue,
+                };
+                let _ = peer.sender.send(message);
+            }
+        }
+    }
+
+    fn has_capability(&self, peer: &[u8], capability: Capability) -> bool {
+        self.peers.lock().unwrap().get(peer).is_some_and(|handle| handle.info.capabilities.contains(&capability))
+    }
+
+    fn send_to(&self, node_id: &[u8], message: NetworkMessage) {
+        if let Some(peer) = self.peers.lock().unwrap().get(node_id) {
+            let _ = peer.sender.send(&message);
+        }
+    }
+
+    /// Asks `peer` for blocks from `from`, or for the checkpoint state while the node
+    /// still waits for it, which only peers serving snapshots can answer
+    fn request_blocks(&self, peer: &[u8], from: u64) {
+        let message = match self.node.checkpoint() {
+            Some(checkpoint) if self.node.awaiting_checkpoint() => {
+                let serves_snapshots = self.peers.lock().unwrap()
+                    .get(peer)
+                    .is_some_and(|handle| handle.info.capabilities.contains(&Capability::SnapshotServing));
+                if !serves_snapshots {
+                    return;
+                }
+                NetworkMessage::GetSnapshot { height: checkpoint.height }
+            }
+            _ => NetworkMessage::GetBlocks { from },
+        };
+        self.send_to(peer, message);
+    }
+
+    fn offer(&self) -> LocalOffer {
+        let mut capabilities = vec![Capability::Ping, Capability::Proposals];
+        if !self.compression.algorithms.is_empty() {
+            capabilities.push(Capability::Compression);
+        }
+        if self.serve_snapshots {
+            capabilities.push(Capability::SnapshotServing);
+        }
+        if self.serve_light_clients {
+            capabilities.push(Capability::LightClientServing);
+        }
+        LocalOffer {
+            protocols: SUPPORTED_PROTOCOLS.iter().map(|protocol| protocol.to_string()).collect(),
+            capabilities,
+            compression: self.compression.algorithms.clone(),
+        }
+    }
+
+    fn hello(&self, peer_address: SocketAddr) -> Result<NetworkMessage, String> {
+        let offer = self.offer();
+        // Read before `external_address`, which takes the same lock
+        let listen_port = *self.listen_port.lock().unwrap();
+        Ok(NetworkMessage::Hello {
+            node_id: self.node.node_id(),
+            protocols: offer.protocols,
+            capabilities: offer.capabilities,
+            listen_port,
+            next_height: self.node.next_height()?,
+            observed: peer_address,
+            external: self.external_address(),
+            compression: offer.compression,
+            chain_id: self.network.chain_id(),
+            genesis: Some(self.node.genesis_hash()),
+        })
+    }
+
+    /// Counts `peer`'s view of this node's address, announcing a newly confirmed one
+    fn record_observed(&self, peer: &[u8], observed: SocketAddr) {
+        let mut votes = self.address_votes.lock().unwrap();
+        let before = votes.confirmed();
+        votes.record(peer, observed.ip());
+        if let Some(confirmed) = votes.confirmed().filter(|confirmed| Some(*confirmed) != before) {
+            log!(Info, "External address confirmed by peers as {}", confirmed);
+        }
+    }
+
+    fn spawn_connection(self: &Arc<Self>, stream: TcpStream, address: SocketAddr, direction: Direction) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let (mut reader, mut writer) = stream.into_split();
+            let connection = service.bandwidth.connection();
+            let (sender, mut receiver) = lanes::channel();
+            let hello = match service.hello(address) {
+                Ok(hello) => hello,
+                Err(e) => return log!(Warn, "Handshake with {} failed: {}", address, e),
+            };
+            let _ = sender.send(&hello);
+
+            let (writer_service, writer_connection) = (service.clone(), connection.clone());
+            tokio::spawn(async move {
+                while let Some(frame) = receiver.next().await {
+                    if writer_service.write_frame(&mut writer, &writer_connection, &frame).await.is_err() {
+                        break;
+                    }
+                }
+            });
+
+            let mut reassembly = Reassembly::default();
+            let hello = service.read_frame(&mut reader, &mut reassembly, &connection).await;
+            if let Ok(NetworkMessage::Disconnect(reason)) = &hello {
+                return log!(Warn, "Peer {} refused the connection: {}", address, reason);
+            }
+            let Ok(NetworkMessage::Hello {
+                node_id,
+                protocols,
+                capabilities,
+                listen_port,
+                next_height: peer_next_height,
+                observed,
+                external,
+                compression,
+                chain_id,
+                genesis,
+            }) = hello
+            else {
+                return;
+            };
+            if service.handshakes.lock().unwrap().record(&node_id, Instant::now()) {
+                service.discovery.lock().unwrap().record_misbehaviour(&node_id);
+                service.node.report_security_event(
+                    SecurityEventType::UnusualTraffic,
+                    SecuritySeverity::Medium,
+                    format!("peer {}", short_hex(&node_id)),
+                    format!("More than {} handshakes within {:?}", MAX_HANDSHAKES_PER_WINDOW, HANDSHAKE_WINDOW),
+                );
+            }
+            if let Err(reason) = check_chain(service.network.chain_id(), service.node.genesis_hash(), chain_id, genesis) {
+                return disconnect(&sender, address, reason);
+            }
+            let negotiated = match service.offer().negotiate(&protocols, &capabilities, &compression) {
+                Ok(negotiated) => negotiated,
+                Err(reason) => return disconnect(&sender, address, reason),
+            };
+            {
+                let mut peers = service.peers.lock().unwrap();
+                if node_id == service.node.node_id() {
+                    return disconnect(&sender, address, DisconnectReason::ConnectedToSelf);
+                }
+                if peers.contains_key(&node_id) {
+                    return disconnect(&sender, address, DisconnectReason::AlreadyConnected);
+                }
+                service.record_observed(&node_id, observed);
+                let info = PeerInfo {
+                    node_id: node_id.clone(),
+                    address,
+                    listen_port,
+                    external_address: external,
+                    protocol: negotiated.protocol,
+                    capabilities: negotiated.capabilities,
+                    compression: negotiated.compression,
+                    direction,
+                    latency: None,
+                };
+                // Light clients connect without a listen port and are not dialable
+                if listen_port != 0 {
+                    service.discovery.lock().unwrap().record_connected(&node_id, info.dial_address());
+                }
+                let policy = info.compression.map(|compression| CompressionPolicy { compression, min_size: service.compression.min_size });
+                let sender = sender.with_compression(policy);
+                peers.insert(node_id.clone(), PeerHandle { info, sender });
+                service.bandwidth.register(&node_id, connection.clone());
+                service.node.events().publish(NodeEvent::PeerConnected { node_id: node_id.clone(), address });
+            }
+
+            if let Ok(next_height) = service.node.next_height() {
+                if peer_next_height > next_height {
+                    service.request_blocks(&node_id, next_height);
+                }
+            }
+            service.send_to(&node_id, NetworkMessage::GetPeers);
+
+            while let Ok(message) = service.read_frame(&mut reader, &mut reassembly, &connection).await {
+                let (service, node_id) = (service.clone(), node_id.clone());
+                if blocking(move || service.handle(&node_id, message)).await.is_err() {
+                    break;
+                }
+            }
+            service.peers.lock().unwrap().remove(&node_id);
+            service.reported_peers.lock().unwrap().remove(&node_id);
+            service.bandwidth.unregister(&node_id);
+            service.address_votes.lock().unwrap().forget(&node_id);
+            service.discovery.lock().unwrap().record_disconnected(&node_id);
+        });
+    }
+
+    /// Writes one frame in chunks, each waiting for upload allowance, so a large frame
+    /// cannot hold the shared cap while other connections queue behind it
+    async fn write_frame(&self, writer: &mut OwnedWriteHalf, connection: &PeerBandwidth, frame: &OutgoingFrame) -> Result<(), String> {
+        for chunk in frame.bytes.chunks(THROTTLE_CHUNK_BYTES) {
+            self.bandwidth.acquire_upload(connection, chunk.len()).await;
+            writer.write_all(chunk).await.map_err(|e| e.to_string())?;
+        }
+        writer.flush().await.map_err(|e| e.to_string())?;
+        self.bandwidth.record_sent(connection, frame.kind, frame.bytes.len());
+        if let Some(sample) = frame.compression {
+            self.bandwidth.record_compression(frame.kind, sample);
+        }
+        Ok(())
+    }
+
+    /// Reads frames until one completes a message
+    async fn read_frame(&self, reader: &mut OwnedReadHalf, reassembly: &mut Reassembly, connection: &PeerBandwidth) -> Result<NetworkMessage, String> {
+        loop {
+            let (kind, length) = read_header(reader).await?;
+            let mut body = vec![0; length];
+            for chunk in body.chunks_mut(THROTTLE_CHUNK_BYTES) {
+                self.bandwidth.acquire_download(connection, chunk.len()).await;
+                reader.read_exact(chunk).await.map_err(|e| e.to_string())?;
+            }
+            if let Some((message, bytes)) = reassembly.push(kind, body)? {
+                self.bandwidth.record_received(connection, message.kind(), bytes);
+                return Ok(message);
+            }
+        }
+    }
+
+    fn handle(self: &Arc<Self>, from: &[u8], message: NetworkMessage) {
+        match message {
+            NetworkMessage::Hello { .. } => {}
+            NetworkMessage::Disconnect(reason) => log!(Info, "Peer {} is disconnecting: {}", short_hex(from), reason),
+            NetworkMessage::NewBlock(block) => self.accept_block(from, block),
+            NetworkMessage::Proposal(proposal) => self.accept_proposal(from, proposal),
+            NetworkMessage::NewTransaction(tx) => {
+                if self.node.submit_transaction(tx.clone()).is_ok() {
+                    self.broadcast_except(from, NetworkMessage::NewTransaction(tx));
+                }
+            }
+            NetworkMessage::GetBlocks { from: start } => self.serve(from, move |service| {
+                let limits = service.serving.config();
+                let (mut blocks, mut bytes) = (Vec::new(), 0);
+                for height in start..start.saturating_add(limits.max_blocks) {
+                    let Some(block) = service.node.db().get_block(height).ok().flatten() else {
+                        break;
+                    };
+                    let size = block.size();
+                    if !blocks.is_empty() && bytes + size > limits.max_response_bytes {
+                        break;
+                    }
+                    bytes += size;
+                    blocks.push(block);
+                }
+                Some((NetworkMessage::Blocks(blocks), bytes))
+            }),
+            NetworkMessage::Blocks(blocks) => {
+                // A batch that still does not connect is dropped rather than re-requested,
+                // so peers on an incompatible finalized chain cannot loop forever. Serving
+                // peers may cut batches short, so asking stops at an empty one
+                let Some(last) = blocks.last().map(|block| block.header.height) else {
+                    return;
+                };
+                for block in &blocks {
+                    match self.import(from, block) {
+                        None | Some(BlockImport::Missing { .. }) => return,
+                        Some(_) => {}
+                    }
+                }
+                if let Ok(next_height) = self.node.next_height().map(|next_height| next_height.max(last + 1)) {
+                    self.request_blocks(from, next_height);
+                }
+            }
+            NetworkMessage::GetSnapshot { height } if self.serve_snapshots => self.serve(from, move |service| {
+                match StateManager::snapshot_at(service.node.db(), height) {
+                    Ok(snapshot) => {
+                        let bytes = snapshot.as_ref().and_then(|snapshot| bincode::serialized_size(snapshot).ok()).unwrap_or_default();
+                        Some((NetworkMessage::Snapshot(snapshot.map(Box::new)), bytes as usize))
+                    }
+                    Err(e) => {
+                        log!(Error, "Could not serve the state snapshot at {}: {}", height, e);
+                        None
+                    }
+                }
+            }),
+            NetworkMessage::Snapshot(Some(snapshot)) => match self.node.install_snapshot(&snapshot) {
+                Ok(true) => {
+                    let height = snapshot.block.header.height;
+                    log!(Info, "Synced checkpoint state at height {} from peer {}", height, short_hex(from));
+                    self.send_to(from, NetworkMessage::GetBlocks { from: height + 1 });
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    log!(Warn, "Rejected state snapshot from peer: {}", e);
+                    self.discovery.lock().unwrap().record_misbehaviour(from);
+                }
+            },
+            NetworkMessage::GetPeers => {
+                let peers = self.discovery.lock().unwrap().shareable(from);
+                self.send_to(from, NetworkMessage::Peers(peers));
+            }
+            NetworkMessage::Peers(peers) => {
+                let named = peers.iter().take(MAX_SHARED_PEERS).map(|peer| peer.node_id.clone()).collect();
+                self.reported_peers.lock().unwrap().insert(from.to_vec(), named);
+                let discovered = self.discovery.lock().unwrap().discover_from_gossip(&self.node.node_id(), peers);
+                self.dial_discovered(discovered);
+            }
+            NetworkMessage::GetHeaders { from: start, count } if self.serve_light_clients => {
+                match light::serve_headers(self.node.db(), start, count) {
+                    Ok(headers) => self.send_to(from, NetworkMessage::Headers(headers)),
+                    Err(e) => log!(Error, "Could not serve headers from {}: {}", start, e),
+                }
+            }
+            NetworkMessage::GetTransactionProof(hash) if self.serve_light_clients => {
+                match light::serve_transaction_proof(self.node.db(), &hash) {
+                    Ok(proof) => self.send_to(from, NetworkMessage::TransactionProof(proof)),
+                    Err(e) => log!(Error, "Could not prove transaction 0x{}: {}", hex::encode(hash), e),
+                }
+            }
+            NetworkMessage::GetAccountProof { address, height } if self.serve_light_clients => {
+                match light::serve_account_proof(self.node.db(), &address, height) {
+                    Ok(proof) => self.send_to(from, NetworkMessage::AccountProof(proof)),
+                    Err(e) => log!(Error, "Could not prove account {} at {}: {}", short_hex(&address), height, e),
+                }
+            }
+            NetworkMessage::GetChunks { block_hash, indices } if self.serve_light_clients => self.serve(from, move |service| {
+                match service.coded_body(&block_hash) {
+                    Ok(coded) => {
+                        let mut indices = indices;
+                        indices.sort_unstable();
+                        indices.dedup();
+                        let chunks: Vec<_> = coded.iter().flat_map(|coded| indices.iter().filter_map(|index| coded.chunk(*index))).collect();
+                        let bytes = chunks.iter().map(|chunk| chunk.data.len()).sum();
+                        Some((NetworkMessage::Chunks { block_hash, chunks }, bytes))
+                    }
+                    Err(e) => {
+                        log!(Error, "Could not serve chunks of block 0x{}: {}", hex::encode(block_hash), e);
+                        None
+                    }
+                }
+            }),
+            NetworkMessage::Ping(stamp) => self.send_to(from, NetworkMessage::Pong(stamp)),
+            NetworkMessage::Pong(stamp) => self.record_pong(from, stamp),
+            NetworkMessage::GetSnapshot { .. }
+            | NetworkMessage::Snapshot(None)
+            | NetworkMessage::GetHeaders { .. }
+            | NetworkMessage::GetTransactionProof(_)
+            | NetworkMessage::GetAccountProof { .. }
+            | NetworkMessage::Headers(_)
+            | NetworkMessage::TransactionProof(_)
+            | NetworkMessage::AccountProof(_)
+            | NetworkMessage::GetChunks { .. }
+            | NetworkMessage::Chunks { .. } => {}
+        }
+    }
+
+    /// Answers a sync request from `peer` in the background once one of its request slots
+    /// is free. `read` builds the response and its size, which waits for the serving
+    /// budget unless the peer is a genesis validator or a configured priority peer
+    fn serve(self: &Arc<Self>, peer: &[u8], read: impl FnOnce(&Self) -> Option<(NetworkMessage, usize)> + Send + 'static) {
+        let Some(permit) = self.serving.admit(peer) else {
+            return log!(Info, "Dropped a sync request from peer {} over its limit", short_hex(peer));
+        };
+        let (service, peer) = (self.clone(), peer.to_vec());
+        tokio::spawn(async move {
+            let reader = service.clone();
+            let Ok(Some((response, bytes))) = blocking(move || read(&reader)).await else {
+                return;
+            };
+            let priority = service.node.is_genesis_validator(&peer) || service.serving.is_priority_peer(&peer);
+            service.serving.pace(bytes, priority).await;
+            service.send_to(&peer, response);
+            drop(permit);
+        });
+    }
+
+    /// Erasure-coded body of the stored block with `hash`, `None` for unknown blocks and
+    /// blocks that do not commit to their chunks
+    fn coded_body(&self, hash: &[u8; 32]) -> Result<Option<Arc<CodedBody>>, String> {
+        if let Some(coded) = self.coded_bodies.lock().unwrap().get(hash) {
+            return Ok(Some(coded));
+        }
+        let Some(block) = self.node.db().get_block_by_hash(hash)? else {
+            return Ok(None);
+        };
+        if block.header.version < AVAILABILITY_BLOCK_VERSION {
+            return Ok(None);
+        }
+        let coded = Arc::new(CodedBody::encode(&block.transactions)?);
+        self.coded_bodies.lock().unwrap().insert(*hash, coded.clone());
+        Ok(Some(coded))
+    }
+
+    fn accept_proposal(&self, from: &[u8], proposal: BlockProposal) {
+        match self.node.import_proposal(&proposal, from) {
+            Ok(BlockImport::Imported) => self.announce(from, proposal),
+            Ok(BlockImport::Missing { expected }) => self.request_blocks(from, expected),
+            Ok(BlockImport::Known) => {}
+            Err(e) => {
+                log!(Warn, "Rejected proposal for block {} from peer: {}", proposal.block.header.height, e);
+                self.discovery.lock().unwrap().record_misbehaviour(from);
+            }
+        }
+    }
+
+    /// A gossiped block carries no proposer signature, so it is only taken from a legacy
+    /// peer when it matches a proposal this node verified. Peers able to send proposals
+    /// are penalized for sending bare blocks
+    fn accept_block(&self, from: &[u8], block: Block) {
+        let height = block.header.height;
+        if self.has_capability(from, Capability::Proposals) {
+            log!(Warn, "Rejected block {} from peer {}: blocks must be gossiped as signed proposals", height, short_hex(from));
+            self.discovery.lock().unwrap().record_misbehaviour(from);
+            return;
+        }
+        match self.proposals.lock().unwrap().get(&block.hash()) {
+            Some(proposal) => self.accept_proposal(from, proposal),
+            None => log!(Info, "Ignored block {} from legacy peer {} without a verified proposal", height, short_hex(from)),
+        }
+    }
+
+    fn import(&self, from: &[u8], block: &Block) -> Option<BlockImport> {
+        match self.node.import_block_from(block, from) {
+            Ok(result) => Some(result),
+            Err(e) => {
+                log!(Warn, "Rejected block {} from peer: {}", block.header.height, e);
+                // The node has already reported it as a security event
+                self.discovery.lock().unwrap().record_misbehaviour(from);
+                None
+            }
+        }
+    }
+
+    /// Produces and announces a block whenever this node is the scheduled proposer.
+    /// A whole interval passing without another validator's scheduled block counts
+    /// as a missed slot for it
+    pub async fn run_proposer(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        let mut waiting_for = None;
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let Ok(height) = self.node.next_height() else {
+                continue;
+            };
+            if self.node.is_production_paused() {
+                continue;
+            }
+            if !self.node.is_proposer(height) {
+                if waiting_for.replace(height) == Some(height) {
+                    let node = self.node.clone();
+                    if let Err(e) = blocking(move || node.record_missed_slot(height)).await.flatten() {
+                        log!(Error, "Could not record missed slot: {}", e);
+                    }
+                }
+                continue;
+            }
+            let node = self.node.clone();
+            match blocking(move || node.propose_block()).await.flatten() {
+                Ok(proposal) => self.announce(&[], proposal),
+                Err(e) => log!(Error, "Block production failed: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumKeyPair;
+    use crate::storage::database::BlockchainDB;
+
+    fn connect(service: &NetworkService, node_id: &[u8], capabilities: Vec<Capability>) {
+        let address: SocketAddr = "127.0.0.1:30303".parse().unwrap();
+        let info = PeerInfo {
+            node_id: node_id.to_vec(),
+            address,
+            listen_port: address.port(),
+            external_address: None,
+            protocol: SUPPORTED_PROTOCOLS[0].to_string(),
+            capabilities,
+            compression: None,
+            direction: Direction::Inbound,
+            latency: None,
+        };
+        service.discovery.lock().unwrap().record_connected(node_id, address);
+        service.peers.lock().unwrap().insert(node_id.to_vec(), PeerHandle { info, sender: lanes::channel().0 });
+    }
+
+    #[test]
+    fn test_gossiped_blocks_need_proposer_signature() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_network_proposals");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let (keypair, other) = (QuantumKeyPair::generate(), QuantumKeyPair::generate());
+        let validators = vec![keypair.public_key().to_vec(), other.public_key().to_vec()];
+        let open = |name: &str, identity: &QuantumKeyPair| {
+            let db = BlockchainDB::new(temp_dir.join(name).to_str().unwrap()).unwrap();
+            db.store_genesis_validators(&validators).unwrap();
+            Node::open(db).unwrap().with_validator_key(identity.clone())
+        };
+        let producer = open("producer", &keypair);
+        let service = NetworkService::new(Arc::new(open("importer", &other)));
+        connect(&service, b"legacy", vec![Capability::Ping]);
+        connect(&service, b"current", vec![Capability::Ping, Capability::Proposals]);
+
+        // A block naming the scheduled proposer carries no signature of its own
+        let block = producer.produce_block().unwrap();
+        service.handle(b"legacy", NetworkMessage::NewBlock(block.clone()));
+        assert_eq!(service.node.next_height().unwrap(), 0);
+        let trust = service.discovery.lock().unwrap().trust(b"current").unwrap();
+        service.handle(b"current", NetworkMessage::NewBlock(block.clone()));
+        assert_eq!(service.node.next_height().unwrap(), 0);
+        assert!(service.discovery.lock().unwrap().trust(b"current").unwrap() < trust);
+
+        // A proposal signed by anyone but the proposer is refused and penalized too
+        let forged = BlockProposal::sign(&other, block.clone(), 0).unwrap();
+        let trust = service.discovery.lock().unwrap().trust(b"legacy").unwrap();
+        service.handle(b"legacy", NetworkMessage::Proposal(forged));
+        assert_eq!(service.node.next_height().unwrap(), 0);
+        assert!(service.discovery.lock().unwrap().trust(b"legacy").unwrap() < trust);
+
+        // Once the signed proposal is verified, legacy peers may relay its block
+        service.handle(b"current", NetworkMessage::Proposal(BlockProposal::sign(&keypair, block.clone(), 0).unwrap()));
+        assert_eq!(service.node.next_height().unwrap(), 1);
+        assert!(service.proposals.lock().unwrap().get(&block.hash()).is_some());
+        let trust = service.discovery.lock().unwrap().trust(b"legacy").unwrap();
+        service.handle(b"legacy", NetworkMessage::NewBlock(block));
+        assert_eq!(service.discovery.lock().unwrap().trust(b"legacy").unwrap(), trust);
+
+        println!("   Gossiped block signatures working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -0,0 +1,293 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::compression::CompressionSample;
+use crate::config::BandwidthConfig;
+
+/// Frames are throttled in chunks of this size, so a large sync response waits for
+/// bandwidth piece by piece and other peers' frames are scheduled in between
+pub const THROTTLE_CHUNK_BYTES: usize = 16 * 1024;
+
+/// Token bucket over bytes holding at most one second of allowance. Waiters are
+/// served in arrival order, which keeps a shared cap fair between connections
+#[derive(Debug)]
+pub struct Throttle {
+    rate: Option<u64>,
+    bucket: tokio::sync::Mutex<(f64, Instant)>,
+}
+
+impl Throttle {
+    pub fn new(rate: Option<u64>) -> Self {
+        let burst = rate.unwrap_or_default() as f64;
+        Self { rate, bucket: tokio::sync::Mutex::new((burst, Instant::now())) }
+    }
+
+    /// Waits until `bytes` may be transferred
+    pub async fn acquire(&self, bytes: usize) {
+        let Some(rate) = self.rate else {
+            return;
+        };
+        let rate = rate as f64;
+        let mut bucket = self.bucket.lock().await;
+        let (tokens, updated) = &mut *bucket;
+        *tokens = (*tokens + updated.elapsed().as_secs_f64() * rate).min(rate) - bytes as f64;
+        *updated = Instant::now();
+        if *tokens < 0.0 {
+            // The debt is repaid by the time that passes while the next waiter queues
+            tokio::time::sleep(Duration::from_secs_f64(-*tokens / rate)).await;
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    sent: AtomicU64,
+    received: AtomicU64,
+}
+
+impl Counters {
+    fn traffic(&self) -> Traffic {
+        Traffic {
+            sent: self.sent.load(Ordering::Relaxed),
+            received: self.received.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Traffic {
+    pub sent: u64,
+    pub received: u64,
+}
+
+/// Byte counters and throttles of one connection
+#[derive(Debug)]
+pub struct PeerBandwidth {
+    upload: Throttle,
+    download: Throttle,
+    counters: Counters,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerTraffic {
+    #[serde(with = "hex::serde")]
+    pub node_id: Vec<u8>,
+    #[serde(flatten)]
+    pub traffic: Traffic,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageTraffic {
+    pub message: String,
+    #[serde(flatten)]
+    pub traffic: Traffic,
+}
+
+/// Messages of one type this node compressed with one algorithm
+#[derive(Debug, Clone, Serialize)]
+pub struct CompressionStats {
+    pub message: String,
+    pub algorithm: String,
+    pub original_bytes: u64,
+    pub compressed_bytes: u64,
+    /// Original over compressed size
+    pub ratio: f64,
+}
+
+/// Bytes moved by the transport, framing included
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NetworkStats {
+    pub total: Traffic,
+    /// Connected peers
+    pub peers: Vec<PeerTraffic>,
+    pub messages: Vec<MessageTraffic>,
+    pub compression: Vec<CompressionStats>,
+}
+
+impl NetworkStats {
+    /// Prometheus text exposition of the counters
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        for (direction, pick) in [("sent", (|t: &Traffic| t.sent) as fn(&Traffic) -> u64), ("received", |t| t.received)] {
+            let name = format!("triunity_network_bytes_{}_total", direction);
+            let _ = writeln!(out, "# HELP {} Bytes {} over peer connections", name, direction);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, pick(&self.total));
+            for peer in &self.peers {
+                let _ = writeln!(out, "{}{{peer=\"{}\"}} {}", name, hex::encode(&peer.node_id), pick(&peer.traffic));
+            }
+            for message in &self.messages {
+                let _ = writeln!(out, "{}{{message=\"{}\"}} {}", name, message.message, pick(&message.traffic));
+            }
+        }
+        for (name, help, pick) in [
+            ("original", "before", (|c: &CompressionStats| c.original_bytes) as fn(&CompressionStats) -> u64),
+            ("compressed", "after", |c| c.compressed_bytes),
+        ] {
+            let name = format!("triunity_network_compression_{}_bytes_total", name);
+            let _ = writeln!(out, "# HELP {} Size of compressed messages {} compression", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            for stats in &self.compression {
+                let _ = writeln!(out, "{}{{message=\"{}\",algorithm=\"{}\"}} {}", name, stats.message, stats.algorithm, pick(stats));
+            }
+        }
+        out
+    }
+}
+
+/// Message type and compression algorithm
+type CompressionKey = (&'static str, &'static str);
+
+/// Transport-wide accounting and the configured upload/download caps
+#[derive(Debug)]
+pub struct Bandwidth {
+    config: BandwidthConfig,
+    upload: Throttle,
+    download: Throttle,
+    total: Counters,
+    messages: Mutex<HashMap<&'static str, Arc<Counters>>>,
+    peers: Mutex<HashMap<Vec<u8>, Arc<PeerBandwidth>>>,
+    /// Original and compressed bytes keyed by message type and algorithm
+    compression: Mutex<HashMap<CompressionKey, (u64, u64)>>,
+}
+
+impl Bandwidth {
+    pub fn new(config: BandwidthConfig) -> Self {
+        Self {
+            upload: Throttle::new(config.total_upload),
+            download: Throttle::new(config.total_download),
+            config,
+            total: Counters::default(),
+            messages: Mutex::new(HashMap::new()),
+            peers: Mutex::new(HashMap::new()),
+            compression: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Throttles and counters for a new connection
+    pub fn connection(&self) -> Arc<PeerBandwidth> {
+        Arc::new(PeerBandwidth {
+            upload: Throttle::new(self.config.peer_upload),
+            download: Throttle::new(self.config.peer_download),
+            counters: Counters::default(),
+        })
+    }
+
+    /// Reports the connection's traffic under `node_id` once its handshake completes
+    pub fn register(&self, node_id: &[u8], peer: Arc<PeerBandwidth>) {
+        self.peers.lock().unwrap().insert(node_id.to_vec(), peer);
+    }
+
+    pub fn unregister(&self, node_id: &[u8]) {
+        self.peers.lock().unwrap().remove(node_id);
+    }
+
+    pub async fn acquire_upload(&self, peer: &PeerBandwidth, bytes: usize) {
+        peer.upload.acquire(bytes).await;
+        self.upload.acquire(bytes).await;
+    }
+
+    pub async fn acquire_download(&self, peer: &PeerBandwidth, bytes: usize) {
+        peer.download.acquire(bytes).await;
+        self.download.acquire(bytes).await;
+    }
+
+    fn message(&self, kind: &'static str) -> Arc<Counters> {
+        self.messages.lock().unwrap().entry(kind).or_default().clone()
+    }
+
+    pub fn record_sent(&self, peer: &PeerBandwidth, kind: &'static str, bytes: usize) {
+        for counters in [&peer.counters, &self.total, &*self.message(kind)] {
+            counters.sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_received(&self, peer: &PeerBandwidth, kind: &'static str, bytes: usize) {
+        for counters in [&peer.counters, &self.total, &*self.message(kind)] {
+            counters.received.fetch_add(bytes as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_compression(&self, kind: &'static str, sample: CompressionSample) {
+        let mut compression = self.compression.lock().unwrap();
+        let (original, compressed) = compression.entry((kind, sample.compression.name())).or_default();
+        *original += sample.original as u64;
+        *compressed += sample.compressed as u64;
+    }
+
+    pub fn stats(&self) -> NetworkStats {
+        let mut peers: Vec<PeerTraffic> = self.peers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(node_id, peer)| PeerTraffic { node_id: node_id.clone(), traffic: peer.counters.traffic() })
+            .collect();
+        peers.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        let mut messages: Vec<MessageTraffic> = self.messages
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(kind, counters)| MessageTraffic { message: kind.to_string(), traffic: counters.traffic() })
+            .collect();
+        messages.sort_by(|a, b| a.message.cmp(&b.message));
+        let mut compression: Vec<CompressionStats> = self.compression
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((kind, algorithm), (original, compressed))| CompressionStats {
+                message: kind.to_string(),
+                algorithm: algorithm.to_string(),
+                original_bytes: *original,
+                compressed_bytes: *compressed,
+                ratio: *original as f64 / (*compressed).max(1) as f64,
+            })
+            .collect();
+        compression.sort_by(|a, b| (&a.message, &a.algorithm).cmp(&(&b.message, &b.algorithm)));
+        NetworkStats { total: self.total.traffic(), peers, messages, compression }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::compression::Compression;
+
+    #[tokio::test]
+    async fn test_bandwidth_accounting_and_caps() {
+        let bandwidth = Bandwidth::new(BandwidthConfig { peer_upload: Some(100_000), ..BandwidthConfig::default() });
+        let peer = bandwidth.connection();
+        bandwidth.register(&[1; 4], peer.clone());
+        bandwidth.record_sent(&peer, "Blocks", 1_000);
+        bandwidth.record_received(&peer, "NewBlock", 300);
+        bandwidth.record_sent(&bandwidth.connection(), "Blocks", 24);
+        let sample = CompressionSample { compression: Compression::Zstd, original: 4_000, compressed: 1_000 };
+        bandwidth.record_compression("Blocks", sample);
+
+        let stats = bandwidth.stats();
+        assert_eq!(stats.total, Traffic { sent: 1_024, received: 300 });
+        assert_eq!(stats.peers[0].traffic, Traffic { sent: 1_000, received: 300 });
+        assert_eq!(stats.messages[0].message, "Blocks");
+        assert_eq!(stats.messages[0].traffic.sent, 1_024);
+        let prometheus = stats.to_prometheus();
+        assert!(prometheus.contains("triunity_network_bytes_sent_total 1024"));
+        assert!(prometheus.contains("triunity_network_bytes_received_total{message=\"NewBlock\"} 300"));
+        assert_eq!(stats.compression[0].ratio, 4.0);
+        assert!(prometheus.contains("triunity_network_compression_compressed_bytes_total{message=\"Blocks\",algorithm=\"zstd\"} 1000"));
+
+        // The first second of allowance is free; the next 50KB waits half a second
+        let started = Instant::now();
+        bandwidth.acquire_upload(&peer, 100_000).await;
+        bandwidth.acquire_upload(&peer, 50_000).await;
+        assert!(started.elapsed() >= Duration::from_millis(450));
+        bandwidth.acquire_download(&peer, 10_000_000).await;
+
+        bandwidth.unregister(&[1; 4]);
+        assert!(bandwidth.stats().peers.is_empty());
+
+        println!("   Bandwidth accounting working!");
+    }
+}
@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+
+use super::message::MAX_MESSAGE_SIZE;
+
+/// Payload compression a node can apply to block transfers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Zstd,
+    Snappy,
+}
+
+const ZSTD_LEVEL: i32 = 3;
+
+impl Compression {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Zstd => "zstd",
+            Self::Snappy => "snappy",
+        }
+    }
+
+    /// Flag byte in front of a payload compressed with this algorithm; 0 marks none
+    pub fn tag(&self) -> u8 {
+        match self {
+            Self::Zstd => 1,
+            Self::Snappy => 2,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Result<Option<Self>, String> {
+        match tag {
+            0 => Ok(None),
+            1 => Ok(Some(Self::Zstd)),
+            2 => Ok(Some(Self::Snappy)),
+            other => Err(format!("Unknown compression flag {}", other)),
+        }
+    }
+
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Zstd => zstd::bulk::compress(data, ZSTD_LEVEL).map_err(|e| e.to_string()),
+            Self::Snappy => snap::raw::Encoder::new().compress_vec(data).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Refuses output larger than `MAX_MESSAGE_SIZE`, so a small frame cannot expand
+    /// into an unbounded allocation
+    pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Zstd => zstd::bulk::decompress(data, MAX_MESSAGE_SIZE).map_err(|e| e.to_string()),
+            Self::Snappy => {
+                let length = snap::raw::decompress_len(data).map_err(|e| e.to_string())?;
+                if length > MAX_MESSAGE_SIZE {
+                    return Err(format!("Compressed message expands to {} bytes", length));
+                }
+                snap::raw::Decoder::new().decompress_vec(data).map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+/// First of our preferred algorithms that the peer also supports
+pub fn negotiate(ours: &[Compression], theirs: &[Compression]) -> Option<Compression> {
+    ours.iter().copied().find(|compression| theirs.contains(compression))
+}
+
+/// How messages to one peer are compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionPolicy {
+    pub compression: Compression,
+    /// Payloads smaller than this are sent as they are
+    pub min_size: usize,
+}
+
+/// Sizes of one message before and after compression
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionSample {
+    pub compression: Compression,
+    pub original: usize,
+    pub compressed: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::message::{decode, encode, encode_with, NetworkMessage};
+    use crate::storage::blocks::{Block, ConsensusData};
+
+    #[test]
+    fn test_compression() {
+        assert_eq!(negotiate(&[Compression::Zstd, Compression::Snappy], &[Compression::Snappy]), Some(Compression::Snappy));
+        assert_eq!(negotiate(&[Compression::Zstd], &[]), None);
+
+        let blocks = NetworkMessage::Blocks(vec![Block::new([7; 32], vec![], 1, ConsensusData::default()); 20]);
+        for compression in [Compression::Zstd, Compression::Snappy] {
+            let policy = CompressionPolicy { compression, min_size: 256 };
+            let (payload, sample) = encode_with(&blocks, Some(policy)).unwrap();
+            let sample = sample.unwrap();
+            assert_eq!(payload[0], compression.tag());
+            assert_eq!(payload.len(), 1 + sample.compressed);
+            assert!(sample.compressed < sample.original);
+            assert!(matches!(decode(&payload).unwrap(), NetworkMessage::Blocks(blocks) if blocks.len() == 20));
+
+            // Small payloads and messages outside block transfers are left alone
+            let policy = CompressionPolicy { compression, min_size: usize::MAX };
+            assert!(encode_with(&blocks, Some(policy)).unwrap().1.is_none());
+            let (payload, sample) = encode_with(&NetworkMessage::GetPeers, Some(CompressionPolicy { compression, min_size: 0 })).unwrap();
+            assert!(sample.is_none());
+            assert_eq!(payload, encode(&NetworkMessage::GetPeers).unwrap());
+        }
+
+        let bomb = Compression::Zstd.compress(&vec![0; MAX_MESSAGE_SIZE + 1]).unwrap();
+        assert!(Compression::Zstd.decompress(&bomb).is_err());
+        assert!(decode(&[9, 0]).is_err());
+
+        println!("   Block transfer compression working!");
+    }
+}
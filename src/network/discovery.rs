@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+/// Most addresses sent in, or accepted from, one `Peers` message
+pub const MAX_SHARED_PEERS: usize = 32;
+pub const MAX_KNOWN_PEERS: usize = 1024;
+/// Trust a peer must have earned before it is recommended to others
+pub const MIN_SHARED_TRUST: i32 = 1;
+/// Peers whose trust falls below this are forgotten
+pub const MIN_TRUST: i32 = -4;
+const MAX_TRUST: i32 = 10;
+const FAILURE_PENALTY: i32 = 2;
+const MISBEHAVIOUR_PENALTY: i32 = 5;
+
+/// A dialable peer as exchanged between nodes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerAddress {
+    pub node_id: Vec<u8>,
+    pub address: SocketAddr,
+}
+
+#[derive(Debug, Clone)]
+struct KnownPeer {
+    address: SocketAddr,
+    trust: i32,
+    connected: bool,
+}
+
+/// Address book of peers learned from handshakes and peer exchange. Trust is earned
+/// by completing handshakes and lost by failed dials and misbehaviour; only peers this
+/// node has verified itself are passed on
+#[derive(Debug, Default)]
+pub struct NodeDiscovery {
+    known: HashMap<Vec<u8>, KnownPeer>,
+}
+
+impl NodeDiscovery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.known.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.known.is_empty()
+    }
+
+    pub fn trust(&self, node_id: &[u8]) -> Option<i32> {
+        self.known.get(node_id).map(|peer| peer.trust)
+    }
+
+    /// A handshake with `node_id`, reachable at `address`, completed
+    pub fn record_connected(&mut self, node_id: &[u8], address: SocketAddr) {
+        let peer = self.known
+            .entry(node_id.to_vec())
+            .or_insert(KnownPeer { address, trust: 0, connected: false });
+        peer.address = address;
+        peer.trust = (peer.trust + 1).min(MAX_TRUST);
+        peer.connected = true;
+    }
+
+    pub fn record_disconnected(&mut self, node_id: &[u8]) {
+        if let Some(peer) = self.known.get_mut(node_id) {
+            peer.connected = false;
+        }
+    }
+
+    /// Dialing `node_id` failed
+    pub fn record_failure(&mut self, node_id: &[u8]) {
+        self.penalize(node_id, FAILURE_PENALTY);
+    }
+
+    /// `node_id` sent something invalid, such as a block that failed validation
+    pub fn record_misbehaviour(&mut self, node_id: &[u8]) {
+        self.penalize(node_id, MISBEHAVIOUR_PENALTY);
+    }
+
+    fn penalize(&mut self, node_id: &[u8], penalty: i32) {
+        if let Some(peer) = self.known.get_mut(node_id) {
+            peer.trust -= penalty;
+            if peer.trust < MIN_TRUST && !peer.connected {
+                self.known.remove(node_id);
+            }
+        }
+    }
+
+    /// Most trusted verified peers to recommend to `requester`
+    pub fn shareable(&self, requester: &[u8]) -> Vec<PeerAddress> {
+        let mut peers: Vec<(&Vec<u8>, &KnownPeer)> = self.known
+            .iter()
+            .filter(|(node_id, peer)| node_id.as_slice() != requester && peer.trust >= MIN_SHARED_TRUST)
+            .collect();
+        peers.sort_by(|a, b| b.1.trust.cmp(&a.1.trust).then_with(|| a.0.cmp(b.0)));
+        peers
+            .into_iter()
+            .take(MAX_SHARED_PEERS)
+            .map(|(node_id, peer)| PeerAddress { node_id: node_id.clone(), address: peer.address })
+            .collect()
+    }
+
+    /// Adds peers recommended by another node, skipping `own_id` and unroutable
+    /// addresses. Returns the ones that were new
+    pub fn discover_from_gossip(&mut self, own_id: &[u8], peers: Vec<PeerAddress>) -> Vec<PeerAddress> {
+        let mut discovered = Vec::new();
+        for peer in peers.into_iter().take(MAX_SHARED_PEERS) {
+            if peer.node_id == own_id
+                || peer.address.ip().is_unspecified()
+                || peer.address.port() == 0
+                || self.known.contains_key(&peer.node_id)
+                || self.known.len() >= MAX_KNOWN_PEERS
+            {
+                continue;
+            }
+            self.known.insert(peer.node_id.clone(), KnownPeer { address: peer.address, trust: 0, connected: false });
+            discovered.push(peer);
+        }
+        discovered
+    }
+
+    /// Known peers that are not connected, most trusted first
+    pub fn dial_candidates(&self, limit: usize) -> Vec<PeerAddress> {
+        let mut candidates: Vec<(&Vec<u8>, &KnownPeer)> = self.known.iter().filter(|(_, peer)| !peer.connected).collect();
+        candidates.sort_by(|a, b| b.1.trust.cmp(&a.1.trust).then_with(|| a.0.cmp(b.0)));
+        candidates
+            .into_iter()
+            .take(limit)
+            .map(|(node_id, peer)| PeerAddress { node_id: node_id.clone(), address: peer.address })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(id: u8, port: u16) -> PeerAddress {
+        PeerAddress { node_id: vec![id], address: SocketAddr::from(([10, 0, 0, id], port)) }
+    }
+
+    #[test]
+    fn test_peer_exchange_book() {
+        let mut discovery = NodeDiscovery::new();
+        let gossip = vec![peer(1, 30300), peer(2, 30300), peer(9, 30300), peer(3, 0)];
+        let discovered = discovery.discover_from_gossip(&[9], gossip.clone());
+        assert_eq!(discovered, vec![peer(1, 30300), peer(2, 30300)]);
+        assert!(discovery.discover_from_gossip(&[9], gossip).is_empty());
+
+        // Gossiped peers are not passed on until this node has verified them
+        assert!(discovery.shareable(&[7]).is_empty());
+        discovery.record_connected(&[1], peer(1, 30301).address);
+        assert_eq!(discovery.shareable(&[7]), vec![peer(1, 30301)]);
+        assert!(discovery.shareable(&[1]).is_empty());
+        assert_eq!(discovery.dial_candidates(8), vec![peer(2, 30300)]);
+
+        discovery.record_misbehaviour(&[1]);
+        assert!(discovery.shareable(&[7]).is_empty());
+        assert_eq!(discovery.trust(&[1]), Some(-4));
+        discovery.record_failure(&[2]);
+        discovery.record_failure(&[2]);
+        discovery.record_failure(&[2]);
+        assert_eq!(discovery.trust(&[2]), None);
+        assert_eq!(discovery.len(), 1);
+
+        println!("   Peer exchange address book working!");
+    }
+}
@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use super::compression::{self, Compression};
+use super::message::PROTOCOL_VERSION;
+use crate::cli::inspect::short_hex;
+
+/// Protocol versions this node speaks, newest first. A version is only dropped from
+/// the list once the handshake itself changes shape
+pub const SUPPORTED_PROTOCOLS: &[&str] = &[PROTOCOL_VERSION];
+
+/// Handshakes one node id may make within `HANDSHAKE_WINDOW` before it is reported
+pub const MAX_HANDSHAKES_PER_WINDOW: usize = 10;
+pub const HANDSHAKE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Optional services a peer offers on top of block and transaction gossip
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Capability {
+    /// Decodes compressed block transfers with the algorithms listed in `Hello`
+    Compression,
+    /// Serves state snapshots for fast sync
+    SnapshotServing,
+    /// Serves headers and proofs to light clients
+    LightClientServing,
+    /// Answers `Ping` with `Pong`, so peers can measure round-trip latency
+    Ping,
+    /// Gossips new blocks as signed `Proposal`s and checks the proposals it receives
+    Proposals,
+}
+
+/// Why a node closed a connection, sent to the peer before it hangs up
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DisconnectReason {
+    /// No protocol version in common; `supported` lists the sender's versions
+    IncompatibleProtocol { supported: Vec<String> },
+    ConnectedToSelf,
+    AlreadyConnected,
+    /// The sender's operator asked for the peer to be dropped
+    Requested,
+    /// The peer is on another network; `chain_id` is the sender's
+    WrongNetwork { chain_id: u64 },
+    /// The peer runs another chain under the same chain id; `genesis` is the sender's
+    WrongGenesis { genesis: [u8; 32] },
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IncompatibleProtocol { supported } => write!(f, "no common protocol version (supports {})", supported.join(", ")),
+            Self::ConnectedToSelf => write!(f, "connected to itself"),
+            Self::AlreadyConnected => write!(f, "already connected"),
+            Self::Requested => write!(f, "disconnect requested by the operator"),
+            Self::WrongNetwork { chain_id } => write!(f, "on another network (expects chain id {})", chain_id),
+            Self::WrongGenesis { genesis } => write!(f, "on another chain (expects genesis {})", short_hex(genesis)),
+        }
+    }
+}
+
+/// What this node offers in its handshake
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalOffer {
+    pub protocols: Vec<String>,
+    pub capabilities: Vec<Capability>,
+    pub compression: Vec<Compression>,
+}
+
+/// Terms a connection runs under once both handshakes are in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Negotiated {
+    pub protocol: String,
+    /// Capabilities both sides offer
+    pub capabilities: Vec<Capability>,
+    pub compression: Option<Compression>,
+}
+
+impl LocalOffer {
+    /// Settles on the newest protocol both sides speak, falling back to older ones the
+    /// peer still supports, and on the capabilities both offer
+    pub fn negotiate(
+        &self,
+        protocols: &[String],
+        capabilities: &[Capability],
+        compression: &[Compression],
+    ) -> Result<Negotiated, DisconnectReason> {
+        let protocol = self.protocols
+            .iter()
+            .find(|protocol| protocols.contains(protocol))
+            .ok_or_else(|| DisconnectReason::IncompatibleProtocol { supported: self.protocols.clone() })?;
+        let capabilities: Vec<Capability> = self.capabilities
+            .iter()
+            .copied()
+            .filter(|capability| capabilities.contains(capability))
+            .collect();
+        let compression = if capabilities.contains(&Capability::Compression) {
+            compression::negotiate(&self.compression, compression)
+        } else {
+            None
+        };
+        Ok(Negotiated { protocol: protocol.clone(), capabilities, compression })
+    }
+}
+
+/// Checks a peer's `Hello` names this node's chain: the same chain id and, unless it is
+/// a light client that has not pinned one yet, the same genesis
+pub fn check_chain(chain_id: u64, genesis: [u8; 32], peer_chain_id: u64, peer_genesis: Option<[u8; 32]>) -> Result<(), DisconnectReason> {
+    if peer_chain_id != chain_id {
+        return Err(DisconnectReason::WrongNetwork { chain_id });
+    }
+    match peer_genesis {
+        Some(peer_genesis) if peer_genesis != genesis => Err(DisconnectReason::WrongGenesis { genesis }),
+        _ => Ok(()),
+    }
+}
+
+/// Recent handshakes by node id, to spot peers reconnecting in a loop
+#[derive(Debug, Default)]
+pub struct HandshakeLog {
+    recent: HashMap<Vec<u8>, VecDeque<Instant>>,
+}
+
+impl HandshakeLog {
+    /// Counts a handshake from `node_id`; true for the first one over
+    /// `MAX_HANDSHAKES_PER_WINDOW`, so a storm is reported once per window
+    pub fn record(&mut self, node_id: &[u8], now: Instant) -> bool {
+        self.recent.retain(|_, times| {
+            while times.front().is_some_and(|time| now.duration_since(*time) >= HANDSHAKE_WINDOW) {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+        let times = self.recent.entry(node_id.to_vec()).or_default();
+        times.push_back(now);
+        times.len() == MAX_HANDSHAKES_PER_WINDOW + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handshake_negotiation() {
+        let offer = LocalOffer {
+            protocols: vec!["triunity/2.0".to_string(), "triunity/1.6".to_string()],
+            capabilities: vec![Capability::Compression, Capability::SnapshotServing],
+            compression: vec![Compression::Zstd, Compression::Snappy],
+        };
+
+        // A peer that only speaks the older version gets it, with shared capabilities only
+        let negotiated = offer
+            .negotiate(&["triunity/1.6".to_string()], &[Capability::Compression, Capability::LightClientServing], &[Compression::Snappy])
+            .unwrap();
+        assert_eq!(negotiated.protocol, "triunity/1.6");
+        assert_eq!(negotiated.capabilities, vec![Capability::Compression]);
+        assert_eq!(negotiated.compression, Some(Compression::Snappy));
+
+        // Compression algorithms alone do not enable it without the capability
+        let negotiated = offer.negotiate(&["triunity/2.0".to_string()], &[], &[Compression::Zstd]).unwrap();
+        assert_eq!(negotiated.protocol, "triunity/2.0");
+        assert_eq!(negotiated.compression, None);
+
+        let rejected = offer.negotiate(&["triunity/1.0".to_string()], &[], &[]).unwrap_err();
+        assert!(matches!(&rejected, DisconnectReason::IncompatibleProtocol { supported } if supported.len() == 2));
+        assert!(rejected.to_string().contains("triunity/2.0, triunity/1.6"));
+
+        // Peers of another network or chain are refused; a light client need not know the genesis
+        assert_eq!(check_chain(2, [1; 32], 2, Some([1; 32])), Ok(()));
+        assert_eq!(check_chain(2, [1; 32], 2, None), Ok(()));
+        assert_eq!(check_chain(2, [1; 32], 1, Some([1; 32])), Err(DisconnectReason::WrongNetwork { chain_id: 2 }));
+        assert_eq!(check_chain(2, [1; 32], 2, Some([3; 32])), Err(DisconnectReason::WrongGenesis { genesis: [1; 32] }));
+
+        // A reconnect loop is flagged once, and again only after the window has passed
+        let (mut log, start) = (HandshakeLog::default(), Instant::now());
+        let flagged: Vec<bool> = (0..=MAX_HANDSHAKES_PER_WINDOW + 1).map(|_| log.record(&[1], start)).collect();
+        assert_eq!(flagged.iter().position(|flag| *flag), Some(MAX_HANDSHAKES_PER_WINDOW));
+        assert_eq!(flagged.iter().filter(|flag| **flag).count(), 1);
+        assert!(!log.record(&[2], start));
+        let later = start + HANDSHAKE_WINDOW;
+        assert!((0..MAX_HANDSHAKES_PER_WINDOW).all(|_| !log.record(&[1], later)));
+        assert!(log.record(&[1], later));
+
+        println!("   Handshake negotiation working!");
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+use tokio::sync::mpsc;
+
+use super::compression::{CompressionPolicy, CompressionSample};
+use super::message::{encode_with, frames, Lane, NetworkMessage};
+
+/// Priority frames written for each bulk fragment while both lanes have traffic waiting
+pub const PRIORITY_WEIGHT: usize = 8;
+
+#[derive(Debug)]
+struct Outgoing {
+    kind: &'static str,
+    frames: Vec<Vec<u8>>,
+    compression: Option<CompressionSample>,
+}
+
+/// One wire frame ready to write, labelled with the message it belongs to
+#[derive(Debug)]
+pub struct OutgoingFrame {
+    pub kind: &'static str,
+    pub bytes: Vec<u8>,
+    /// Set on the first frame of a compressed message
+    pub compression: Option<CompressionSample>,
+}
+
+/// Queues messages for one connection on their lane
+#[derive(Debug, Clone)]
+pub struct LaneSender {
+    priority: mpsc::UnboundedSender<Outgoing>,
+    bulk: mpsc::UnboundedSender<Outgoing>,
+    compression: Option<CompressionPolicy>,
+}
+
+/// Drains a connection's lanes in weighted order: bulk messages are split into
+/// fragments, and up to `PRIORITY_WEIGHT` priority frames go out between two of them
+#[derive(Debug)]
+pub struct LaneReceiver {
+    priority: mpsc::UnboundedReceiver<Outgoing>,
+    bulk: mpsc::UnboundedReceiver<Outgoing>,
+    /// Unwritten fragments of the bulk message in progress
+    fragments: VecDeque<Vec<u8>>,
+    fragments_kind: &'static str,
+    fragments_compression: Option<CompressionSample>,
+    /// Priority frames written since the last bulk fragment
+    streak: usize,
+}
+
+pub fn channel() -> (LaneSender, LaneReceiver) {
+    let (priority_sender, priority) = mpsc::unbounded_channel();
+    let (bulk_sender, bulk) = mpsc::unbounded_channel();
+    let receiver = LaneReceiver {
+        priority,
+        bulk,
+        fragments: VecDeque::new(),
+        fragments_kind: "",
+        fragments_compression: None,
+        streak: 0,
+    };
+    (LaneSender { priority: priority_sender, bulk: bulk_sender, compression: None }, receiver)
+}
+
+impl LaneSender {
+    /// Compresses messages under `policy`
+    pub fn with_compression(mut self, policy: Option<CompressionPolicy>) -> Self {
+        self.compression = policy;
+        self
+    }
+
+    /// Fails when the message cannot be encoded or the connection has closed
+    pub fn send(&self, message: &NetworkMessage) -> Result<(), String> {
+        let lane = message.lane();
+        let (payload, compression) = encode_with(message, self.compression)?;
+        let outgoing = Outgoing { kind: message.kind(), frames: frames(&payload, lane), compression };
+        let sender = match lane {
+            Lane::Priority => &self.priority,
+            Lane::Bulk => &self.bulk,
+        };
+        sender.send(outgoing).map_err(|_| "Connection closed".to_string())
+    }
+}
+
+impl LaneReceiver {
+    /// Next frame to write, or `None` once the sender is gone and both lanes are drained
+    pub async fn next(&mut self) -> Option<OutgoingFrame> {
+        loop {
+            if self.fragments.is_empty() {
+                if let Ok(outgoing) = self.bulk.try_recv() {
+                    self.start_bulk(outgoing);
+                }
+            }
+            let bulk_due = !self.fragments.is_empty() && self.streak >= PRIORITY_WEIGHT;
+            if !bulk_due {
+                if let Ok(outgoing) = self.priority.try_recv() {
+                    return Some(self.priority_frame(outgoing));
+                }
+            }
+            if let Some(bytes) = self.fragments.pop_front() {
+                self.streak = 0;
+                let compression = self.fragments_compression.take();
+                return Some(OutgoingFrame { kind: self.fragments_kind, bytes, compression });
+            }
+            tokio::select! {
+                biased;
+                Some(outgoing) = self.priority.recv() => return Some(self.priority_frame(outgoing)),
+                Some(outgoing) = self.bulk.recv() => self.start_bulk(outgoing),
+                else => return None,
+            }
+        }
+    }
+
+    fn priority_frame(&mut self, outgoing: Outgoing) -> OutgoingFrame {
+        self.streak += 1;
+        OutgoingFrame { kind: outgoing.kind, bytes: outgoing.frames.concat(), compression: outgoing.compression }
+    }
+
+    fn start_bulk(&mut self, outgoing: Outgoing) {
+        self.fragments = outgoing.frames.into();
+        self.fragments_kind = outgoing.kind;
+        self.fragments_compression = outgoing.compression;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::discovery::PeerAddress;
+    use crate::network::message::BULK_FRAGMENT_SIZE;
+    use crate::storage::blocks::{Block, ConsensusData};
+    use std::net::SocketAddr;
+
+    #[tokio::test]
+    async fn test_weighted_lanes() {
+        let (sender, mut receiver) = channel();
+        let peers: Vec<PeerAddress> = (0..3 * BULK_FRAGMENT_SIZE / 16)
+            .map(|i| PeerAddress { node_id: (i as u32).to_be_bytes().to_vec(), address: SocketAddr::from(([10, 0, 0, 1], 1)) })
+            .collect();
+        sender.send(&NetworkMessage::Peers(peers)).unwrap();
+        for from in 0..10 {
+            sender.send(&NetworkMessage::GetBlocks { from }).unwrap();
+        }
+        let block = Block::new([0; 32], vec![], 1, ConsensusData::default());
+        for _ in 0..10 {
+            sender.send(&NetworkMessage::NewBlock(block.clone())).unwrap();
+        }
+        drop(sender);
+
+        let mut order = Vec::new();
+        while let Some(frame) = receiver.next().await {
+            order.push(frame.kind);
+        }
+        // Eight proposals, a Peers fragment, the last two proposals, then bulk in order
+        assert!(order[..8].iter().all(|kind| *kind == "NewBlock"));
+        assert_eq!(order[8], "Peers");
+        assert_eq!(order[9..11], ["NewBlock", "NewBlock"]);
+        assert!(order[11..].iter().filter(|kind| **kind == "Peers").count() >= 2);
+        assert_eq!(order.iter().filter(|kind| **kind == "GetBlocks").count(), 10);
+        assert_eq!(order.last(), Some(&"GetBlocks"));
+
+        println!("   Weighted priority lanes working!");
+    }
+}
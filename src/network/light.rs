@@ -0,0 +1,382 @@
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+
+use super::handshake::{Capability, SUPPORTED_PROTOCOLS};
+use super::message::{read_message, write_message, NetworkMessage, Reassembly};
+use super::profile::NetworkId;
+use crate::consensus::fork_choice::ForkChoiceHead;
+use crate::consensus::votes::{CommitCertificate, Vote};
+use crate::storage::availability::{self, AvailabilitySample, DataChunk};
+use crate::storage::blocks::{BlockHeader, Transaction};
+use crate::storage::database::BlockchainDB;
+use crate::storage::merkle::{MerkleProof, MerkleTree};
+use crate::storage::state::{Account, StateManager};
+
+/// Most headers returned for one `GetHeaders` request
+pub const MAX_HEADERS_PER_REQUEST: u64 = 512;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Inclusion of a transaction in the canonical block at `height`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionProof {
+    pub height: u64,
+    pub index: usize,
+    #[serde(with = "crate::storage::envelope::transaction")]
+    pub transaction: Transaction,
+    pub proof: MerkleProof,
+}
+
+impl TransactionProof {
+    /// Checks the transaction is committed to by the transaction root of `header`
+    pub fn verify(&self, header: &BlockHeader) -> Result<(), String> {
+        if header.height != self.height {
+            return Err(format!("Proof is for height {}, not {}", self.height, header.height));
+        }
+        if self.proof.leaf_hash != self.transaction.hash() {
+            return Err("Proof is for a different transaction".to_string());
+        }
+        if self.proof.root != header.merkle_root || !MerkleTree::verify_proof(&self.proof) {
+            return Err("Proof does not match the block's transaction root".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A transaction proof bundled with the header it proves against, for auditors
+/// checking inclusion offline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InclusionProof {
+    #[serde(flatten)]
+    pub transaction: TransactionProof,
+    pub header: BlockHeader,
+    /// Whether the block was below the serving node's finalized height
+    pub finalized: bool,
+    /// Votes committing the block, when the serving node collected a quorum for it
+    pub certificate: Option<CommitCertificate>,
+}
+
+impl InclusionProof {
+    /// Checks the proof against `trusted`, the header of the including block as the
+    /// auditor knows it. Certificate votes must be signed and for this block; whether
+    /// they reach a quorum, or session keys were authorized, depends on chain state and
+    /// is not checked here
+    pub fn verify(&self, trusted: &BlockHeader) -> Result<(), String> {
+        if self.header.hash() != trusted.hash() {
+            return Err(format!("Proof header 0x{} is not the trusted header 0x{}", hex::encode(self.header.hash()), hex::encode(trusted.hash())));
+        }
+        self.transaction.verify(&self.header)?;
+        if let Some(certificate) = &self.certificate {
+            if (certificate.height, certificate.block_hash) != (self.header.height, self.header.hash()) {
+                return Err("Certificate is for a different block".to_string());
+            }
+            let committed = |vote: &Vote| {
+                (vote.height, vote.round, vote.block_hash) == (certificate.height, certificate.round, certificate.block_hash) && vote.verify()
+            };
+            if !certificate.votes.iter().all(committed) {
+                return Err("Certificate holds an invalid vote".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An account's state after the canonical block at `height`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountProof {
+    pub height: u64,
+    #[serde(with = "hex::serde")]
+    pub address: Vec<u8>,
+    pub account: Account,
+    pub proof: MerkleProof,
+}
+
+impl AccountProof {
+    /// Checks the account is committed to by the state root of `header`
+    pub fn verify(&self, header: &BlockHeader) -> Result<(), String> {
+        if header.height != self.height {
+            return Err(format!("Proof is for height {}, not {}", self.height, header.height));
+        }
+        let leaf: [u8; 32] = Sha3_256::digest(StateManager::account_leaf(&self.address, &self.account)).into();
+        if self.proof.leaf_hash != leaf {
+            return Err("Proof is for a different account state".to_string());
+        }
+        if self.proof.root != header.state_root || !MerkleTree::verify_proof(&self.proof) {
+            return Err("Proof does not match the block's state root".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Checks that `headers` extend `trusted` one height at a time
+pub fn verify_header_chain(trusted: &BlockHeader, headers: &[BlockHeader]) -> Result<(), String> {
+    let mut parent = trusted;
+    for header in headers {
+        if header.height != parent.height + 1 || header.previous_hash != parent.hash() {
+            return Err(format!("Header {} does not extend header {}", header.height, parent.height));
+        }
+        parent = header;
+    }
+    Ok(())
+}
+
+/// Canonical headers from `from`, at most `MAX_HEADERS_PER_REQUEST`
+pub fn serve_headers(db: &BlockchainDB, from: u64, count: u64) -> Result<Vec<BlockHeader>, String> {
+    let mut headers = Vec::new();
+    for height in from..from.saturating_add(count.min(MAX_HEADERS_PER_REQUEST)) {
+        match db.get_block(height)? {
+            Some(block) => headers.push(block.header),
+            None => break,
+        }
+    }
+    Ok(headers)
+}
+
+pub fn serve_transaction_proof(db: &BlockchainDB, hash: &[u8; 32]) -> Result<Option<TransactionProof>, String> {
+    let Some((transaction, height, index)) = db.get_transaction(hash)? else {
+        return Ok(None);
+    };
+    let block = db.get_block(height)?.ok_or_else(|| format!("Block {} missing from storage", height))?;
+    Ok(block.transaction_proof(index).map(|proof| TransactionProof { height, index, transaction, proof }))
+}
+
+/// `serve_transaction_proof` with the block's header and whether it is finalized; the
+/// certificate is left for callers holding the node's vote pool
+pub fn serve_inclusion_proof(db: &BlockchainDB, hash: &[u8; 32]) -> Result<Option<InclusionProof>, String> {
+    let Some(transaction) = serve_transaction_proof(db, hash)? else {
+        return Ok(None);
+    };
+    let header = db.get_block(transaction.height)?
+        .ok_or_else(|| format!("Block {} missing from storage", transaction.height))?
+        .header;
+    let finalized = db.get_meta::<ForkChoiceHead>("fork_choice")?.is_some_and(|head| head.finalized_height >= header.height);
+    Ok(Some(InclusionProof { transaction, header, finalized, certificate: None }))
+}
+
+/// Replays state up to `height`, so serving old heights costs a full replay
+pub fn serve_account_proof(db: &BlockchainDB, address: &[u8], height: u64) -> Result<Option<AccountProof>, String> {
+    if db.block_count()? == 0 || height > db.get_latest_height()? {
+        return Ok(None);
+    }
+    let state = StateManager::replay_to(db, Some(height))?;
+    let Some(account) = state.get_account(address) else {
+        return Ok(None);
+    };
+    Ok(state.account_proof(address).map(|proof| AccountProof {
+        height,
+        address: address.to_vec(),
+        account: account.clone(),
+        proof,
+    }))
+}
+
+/// Connection to a full node offering `LightClientServing`. Responses are returned as
+/// received; callers verify them against headers they trust
+pub struct LightClient {
+    reader: OwnedReadHalf,
+    writer: OwnedWriteHalf,
+    reassembly: Reassembly,
+    peer_next_height: u64,
+    genesis: Option<[u8; 32]>,
+}
+
+impl LightClient {
+    /// Connects to a node of `network`, refusing one on another network
+    pub async fn connect(address: SocketAddr, network: NetworkId) -> Result<Self, String> {
+        let stream = TcpStream::connect(address)
+            .await
+            .map_err(|e| format!("Could not connect to {}: {}", address, e))?;
+        let (reader, writer) = stream.into_split();
+        let mut client = Self { reader, writer, reassembly: Reassembly::default(), peer_next_height: 0, genesis: None };
+        let hello = NetworkMessage::Hello {
+            node_id: rand::random::<[u8; 32]>().to_vec(),
+            protocols: SUPPORTED_PROTOCOLS.iter().map(|protocol| protocol.to_string()).collect(),
+            capabilities: Vec::new(),
+            // Without a listen port the node does not pass this client on to other peers
+            listen_port: 0,
+            next_height: 0,
+            observed: address,
+            external: None,
+            compression: Vec::new(),
+            chain_id: network.chain_id(),
+            genesis: None,
+        };
+        write_message(&mut client.writer, &hello).await?;
+        let (capabilities, next_height, chain_id, genesis) = client
+            .receive(|message| match message {
+                NetworkMessage::Hello { capabilities, next_height, chain_id, genesis, .. } => Some((capabilities, next_height, chain_id, genesis)),
+                _ => None,
+            })
+            .await?;
+        if chain_id != network.chain_id() {
+            return Err(format!("{} is not on {} (chain id {})", address, network, chain_id));
+        }
+        if !capabilities.contains(&Capability::LightClientServing) {
+            return Err(format!("{} does not serve light clients", address));
+        }
+        client.peer_next_height = next_height;
+        client.genesis = genesis;
+        Ok(client)
+    }
+
+    /// Height the node will produce next, as of the handshake
+    pub fn peer_next_height(&self) -> u64 {
+        self.peer_next_height
+    }
+
+    /// Genesis hash the node named in its handshake, for pinning the chain it serves
+    pub fn genesis(&self) -> Option<[u8; 32]> {
+        self.genesis
+    }
+
+    pub async fn headers(&mut self, from: u64, count: u64) -> Result<Vec<BlockHeader>, String> {
+        self.request(NetworkMessage::GetHeaders { from, count }, |message| match message {
+            NetworkMessage::Headers(headers) => Some(headers),
+            _ => None,
+        })
+        .await
+    }
+
+    pub async fn transaction_proof(&mut self, hash: [u8; 32]) -> Result<Option<TransactionProof>, String> {
+        self.request(NetworkMessage::GetTransactionProof(hash), |message| match message {
+            NetworkMessage::TransactionProof(proof) => Some(proof),
+            _ => None,
+        })
+        .await
+    }
+
+    pub async fn account_proof(&mut self, address: &[u8], height: u64) -> Result<Option<AccountProof>, String> {
+        let request = NetworkMessage::GetAccountProof { address: address.to_vec(), height };
+        self.request(request, |message| match message {
+            NetworkMessage::AccountProof(proof) => Some(proof),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Chunks of the block with `block_hash` at `indices`; none for blocks the node does
+    /// not hold or that do not commit to their chunks
+    pub async fn chunks(&mut self, block_hash: [u8; 32], indices: Vec<u32>) -> Result<Vec<DataChunk>, String> {
+        self.request(NetworkMessage::GetChunks { block_hash, indices }, |message| match message {
+            NetworkMessage::Chunks { block_hash: hash, chunks } if hash == block_hash => Some(chunks),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Checks the body of the block with `header` is available by fetching `samples`
+    /// random chunks and verifying each against the header's availability root, without
+    /// downloading the body. Missing or invalid chunks count against availability
+    pub async fn sample_availability(&mut self, header: &BlockHeader, samples: usize) -> Result<AvailabilitySample, String> {
+        let indices = availability::sample_indices(samples);
+        let chunks = self.chunks(header.hash(), indices.clone()).await?;
+        let verified = indices
+            .iter()
+            .filter(|index| chunks.iter().any(|chunk| chunk.index == **index && chunk.verify(header).is_ok()))
+            .count();
+        let confidence = match verified == indices.len() {
+            true => availability::sampling_confidence(verified),
+            false => 0.0,
+        };
+        Ok(AvailabilitySample { height: header.height, requested: indices.len(), verified, confidence })
+    }
+
+    async fn request<T>(&mut self, request: NetworkMessage, pick: impl Fn(NetworkMessage) -> Option<T>) -> Result<T, String> {
+        write_message(&mut self.writer, &request).await?;
+        self.receive(pick).await
+    }
+
+    /// Skips gossip until a message `pick` accepts arrives
+    async fn receive<T>(&mut self, pick: impl Fn(NetworkMessage) -> Option<T>) -> Result<T, String> {
+        let wait = async {
+            loop {
+                match read_message(&mut self.reader, &mut self.reassembly).await? {
+                    NetworkMessage::Disconnect(reason) => return Err(format!("Node disconnected: {}", reason)),
+                    message => {
+                        if let Some(response) = pick(message) {
+                            return Ok(response);
+                        }
+                    }
+                }
+            }
+        };
+        tokio::time::timeout(REQUEST_TIMEOUT, wait)
+            .await
+            .map_err(|_| "Timed out waiting for the node".to_string())?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{QuantumKeyPair, QuantumSignature};
+    use crate::network::NetworkService;
+    use crate::node::Node;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_light_client_proofs() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_light_client");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let keypair = QuantumKeyPair::generate();
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        db.store_genesis_allocations(&[(keypair.public_key().to_vec(), 1_000)]).unwrap();
+        let node = Node::open(db).unwrap().with_availability_threshold(0);
+        node.produce_block().unwrap();
+        let mut tx = Transaction::new(keypair.public_key().to_vec(), vec![0xcc; 32], 100, 1, 0, vec![], QuantumSignature::new(vec![]));
+        tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+        let tx_hash = node.submit_transaction(tx).unwrap();
+        node.produce_block().unwrap();
+        node.produce_block().unwrap();
+
+        let network = NetworkService::new(Arc::new(node));
+        let address = network.listen(SocketAddr::from(([127, 0, 0, 1], 0))).await.unwrap();
+        let mut client = LightClient::connect(address, NetworkId::Devnet).await.unwrap();
+        assert_eq!(client.peer_next_height(), 3);
+        assert_eq!(client.genesis(), Some(network.node().genesis_hash()));
+        let refused = LightClient::connect(address, NetworkId::Mainnet).await.err().unwrap();
+        assert!(refused.ends_with("is not on mainnet (chain id 1337)"), "{}", refused);
+
+        let headers = client.headers(0, 10).await.unwrap();
+        assert_eq!(headers.len(), 3);
+        verify_header_chain(&headers[0], &headers[1..]).unwrap();
+        assert!(verify_header_chain(&headers[0], &headers[2..]).is_err());
+
+        let proof = client.transaction_proof(tx_hash).await.unwrap().unwrap();
+        proof.verify(&headers[1]).unwrap();
+        assert!(proof.verify(&headers[2]).is_err());
+        assert!(client.transaction_proof([0; 32]).await.unwrap().is_none());
+
+        // Inclusion proofs survive a JSON round trip and only verify against their own header
+        let inclusion = serve_inclusion_proof(network.node().db(), &tx_hash).unwrap().unwrap();
+        let inclusion: InclusionProof = serde_json::from_str(&serde_json::to_string(&inclusion).unwrap()).unwrap();
+        inclusion.verify(&headers[1]).unwrap();
+        assert!(inclusion.verify(&headers[2]).unwrap_err().contains("not the trusted header"));
+        assert!(!inclusion.finalized && inclusion.certificate.is_none());
+
+        let proof = client.account_proof(&[0xcc; 32], 2).await.unwrap().unwrap();
+        assert_eq!(proof.account.balance, 100);
+        proof.verify(&headers[2]).unwrap();
+        let mut forged = proof.clone();
+        forged.account.balance = 1_000_000;
+        assert!(forged.verify(&headers[2]).is_err());
+        assert!(client.account_proof(&[0xcc; 32], 0).await.unwrap().is_none());
+        // Sampled chunks prove the body available without downloading it
+        let sample = client.sample_availability(&headers[1], availability::DEFAULT_SAMPLES).await.unwrap();
+        assert!(sample.is_available() && sample.confidence > 0.9999);
+        let mut unknown = headers[1].clone();
+        unknown.timestamp += 1;
+        assert!(!client.sample_availability(&unknown, 4).await.unwrap().is_available());
+
+        // Light clients are not recommended to other peers as dialable nodes
+        assert_eq!(network.peer_trust(&network.peers()[0].node_id), None);
+
+        println!("   Light client proofs working!");
+        drop(client);
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -0,0 +1,315 @@
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use super::compression::{Compression, CompressionPolicy, CompressionSample};
+use super::discovery::PeerAddress;
+use super::handshake::{Capability, DisconnectReason};
+use super::light::{AccountProof, TransactionProof};
+use crate::consensus::proposal::BlockProposal;
+use crate::storage::availability::DataChunk;
+use crate::storage::blocks::{Block, BlockHeader, Transaction};
+use crate::storage::state::StateSnapshot;
+
+/// 1.1 carries transactions as versioned envelopes; 1.2 adds address discovery to the
+/// handshake; 1.3 adds peer exchange; 1.4 splits bulk messages into fragments; 1.5
+/// flags payload compression; 1.6 negotiates versions and capabilities and explains
+/// disconnects; 1.7 names the chain, so nodes of different networks refuse each other
+pub const PROTOCOL_VERSION: &str = "triunity/1.7";
+pub const MAX_MESSAGE_SIZE: usize = 32 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum NetworkMessage {
+    Hello {
+        node_id: Vec<u8>,
+        /// Protocol versions the sender speaks, newest first
+        protocols: Vec<String>,
+        capabilities: Vec<Capability>,
+        listen_port: u16,
+        next_height: u64,
+        /// Address the sender sees the receiver connecting from
+        observed: SocketAddr,
+        /// The sender's confirmed public address, when it knows one
+        external: Option<SocketAddr>,
+        /// Compression the sender can decode, most preferred first
+        compression: Vec<Compression>,
+        /// Chain id of the network the sender is on
+        chain_id: u64,
+        /// Genesis hash of the sender's chain; light clients that have not pinned one yet
+        /// send none
+        genesis: Option<[u8; 32]>,
+    },
+    NewBlock(Block),
+    NewTransaction(#[serde(with = "crate::storage::envelope::transaction")] Transaction),
+    GetBlocks { from: u64 },
+    Blocks(Vec<Block>),
+    /// Asks for the trusted peers the receiver knows
+    GetPeers,
+    Peers(Vec<PeerAddress>),
+    /// Sent right before the sender closes the connection
+    Disconnect(DisconnectReason),
+    /// Light client requests, answered by peers offering `LightClientServing`
+    GetHeaders { from: u64, count: u64 },
+    Headers(Vec<BlockHeader>),
+    GetTransactionProof([u8; 32]),
+    TransactionProof(Option<TransactionProof>),
+    GetAccountProof { address: Vec<u8>, height: u64 },
+    AccountProof(Option<AccountProof>),
+    /// State after the block at `height`, answered by peers offering `SnapshotServing`
+    GetSnapshot { height: u64 },
+    Snapshot(Option<Box<StateSnapshot>>),
+    /// Latency probe for peers offering `Ping`, echoed back unchanged in `Pong`
+    Ping(u64),
+    Pong(u64),
+    /// A new block signed by its proposer, sent instead of `NewBlock` to peers offering `Proposals`
+    Proposal(BlockProposal),
+    /// Erasure-coded chunks of a block body for availability sampling, answered by
+    /// peers offering `LightClientServing`
+    GetChunks { block_hash: [u8; 32], indices: Vec<u32> },
+    Chunks { block_hash: [u8; 32], chunks: Vec<DataChunk> },
+}
+
+/// Bulk messages travel in fragments of at most this size, so priority frames can be
+/// written between the fragments of a large sync response
+pub const BULK_FRAGMENT_SIZE: usize = 16 * 1024;
+
+/// Length prefix and frame kind in front of every frame body
+pub const FRAME_HEADER_SIZE: usize = 5;
+
+/// Send queue a message waits in on each connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lane {
+    /// Consensus traffic: handshakes and block proposals
+    Priority,
+    /// Sync, peer exchange and transaction gossip
+    Bulk,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    /// A whole message
+    Message,
+    /// Part of a bulk message, followed by more
+    Fragment,
+    LastFragment,
+}
+
+impl FrameKind {
+    fn from_byte(byte: u8) -> Result<Self, String> {
+        match byte {
+            0 => Ok(Self::Message),
+            1 => Ok(Self::Fragment),
+            2 => Ok(Self::LastFragment),
+            other => Err(format!("Unknown frame kind {}", other)),
+        }
+    }
+}
+
+impl NetworkMessage {
+    /// Variant name used to label traffic statistics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Hello { .. } => "Hello",
+            Self::NewBlock(_) => "NewBlock",
+            Self::NewTransaction(_) => "NewTransaction",
+            Self::GetBlocks { .. } => "GetBlocks",
+            Self::Blocks(_) => "Blocks",
+            Self::GetPeers => "GetPeers",
+            Self::Peers(_) => "Peers",
+            Self::Disconnect(_) => "Disconnect",
+            Self::GetHeaders { .. } => "GetHeaders",
+            Self::Headers(_) => "Headers",
+            Self::GetTransactionProof(_) => "GetTransactionProof",
+            Self::TransactionProof(_) => "TransactionProof",
+            Self::GetAccountProof { .. } => "GetAccountProof",
+            Self::AccountProof(_) => "AccountProof",
+            Self::GetSnapshot { .. } => "GetSnapshot",
+            Self::Snapshot(_) => "Snapshot",
+            Self::Ping(_) => "Ping",
+            Self::Pong(_) => "Pong",
+            Self::Proposal(_) => "Proposal",
+            Self::GetChunks { .. } => "GetChunks",
+            Self::Chunks { .. } => "Chunks",
+        }
+    }
+
+    /// Block and state transfers, the only messages large enough to be worth compressing
+    pub fn compressible(&self) -> bool {
+        matches!(self, Self::NewBlock(_) | Self::Blocks(_) | Self::Snapshot(_) | Self::Proposal(_))
+    }
+
+    pub fn lane(&self) -> Lane {
+        match self {
+            // Probes skip the bulk queue so they measure the link rather than the backlog
+            Self::Hello { .. } | Self::NewBlock(_) | Self::Proposal(_) | Self::Disconnect(_) | Self::Ping(_) | Self::Pong(_) => Lane::Priority,
+            _ => Lane::Bulk,
+        }
+    }
+}
+
+/// Serializes a message into an uncompressed frame payload
+pub fn encode(message: &NetworkMessage) -> Result<Vec<u8>, String> {
+    encode_with(message, None).map(|(payload, _)| payload)
+}
+
+/// Serializes a message into a frame payload: a compression flag byte followed by the
+/// bincode body, compressed under `policy` when the message is compressible, at least
+/// `min_size` bytes, and actually shrinks
+pub fn encode_with(message: &NetworkMessage, policy: Option<CompressionPolicy>) -> Result<(Vec<u8>, Option<CompressionSample>), String> {
+    let body = bincode::serialize(message).map_err(|e| e.to_string())?;
+    // The flag byte must fit in the frame as well
+    if body.len() >= MAX_MESSAGE_SIZE {
+        return Err(format!("Message of {} bytes exceeds the frame limit", body.len()));
+    }
+    if let Some(policy) = policy.filter(|policy| message.compressible() && body.len() >= policy.min_size) {
+        let compressed = policy.compression.compress(&body)?;
+        if compressed.len() < body.len() {
+            let sample = CompressionSample { compression: policy.compression, original: body.len(), compressed: compressed.len() };
+            let mut payload = Vec::with_capacity(1 + compressed.len());
+            payload.push(policy.compression.tag());
+            payload.extend_from_slice(&compressed);
+            return Ok((payload, Some(sample)));
+        }
+    }
+    let mut payload = Vec::with_capacity(1 + body.len());
+    payload.push(0);
+    payload.extend_from_slice(&body);
+    Ok((payload, None))
+}
+
+pub fn decode(payload: &[u8]) -> Result<NetworkMessage, String> {
+    let (flag, body) = payload.split_first().ok_or("Empty message payload")?;
+    match Compression::from_tag(*flag)? {
+        Some(compression) => bincode::deserialize(&compression.decompress(body)?),
+        None => bincode::deserialize(body),
+    }
+    .map_err(|e| e.to_string())
+}
+
+fn frame(kind: FrameKind, body: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + body.len());
+    frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    frame.push(kind as u8);
+    frame.extend_from_slice(body);
+    frame
+}
+
+/// Wire frames for a payload: one for priority messages, fragments for bulk ones
+pub fn frames(payload: &[u8], lane: Lane) -> Vec<Vec<u8>> {
+    if lane == Lane::Priority {
+        return vec![frame(FrameKind::Message, payload)];
+    }
+    let fragments: Vec<&[u8]> = if payload.is_empty() { vec![payload] } else { payload.chunks(BULK_FRAGMENT_SIZE).collect() };
+    let last = fragments.len() - 1;
+    fragments
+        .into_iter()
+        .enumerate()
+        .map(|(index, body)| frame(if index == last { FrameKind::LastFragment } else { FrameKind::Fragment }, body))
+        .collect()
+}
+
+/// Reads a frame header, returning the kind and body length
+pub async fn read_header<R: AsyncReadExt + Unpin>(reader: &mut R) -> Result<(FrameKind, usize), String> {
+    let length = reader.read_u32().await.map_err(|e| e.to_string())? as usize;
+    if length > MAX_MESSAGE_SIZE {
+        return Err(format!("Peer sent a {} byte frame", length));
+    }
+    let kind = FrameKind::from_byte(reader.read_u8().await.map_err(|e| e.to_string())?)?;
+    Ok((kind, length))
+}
+
+/// Collects bulk fragments on the receiving side of a connection
+#[derive(Debug, Default)]
+pub struct Reassembly {
+    partial: Vec<u8>,
+    wire_bytes: usize,
+}
+
+impl Reassembly {
+    /// Adds one frame body, returning the message it completes along with the bytes
+    /// all of that message's frames took on the wire
+    pub fn push(&mut self, kind: FrameKind, body: Vec<u8>) -> Result<Option<(NetworkMessage, usize)>, String> {
+        if kind == FrameKind::Message {
+            return Ok(Some((decode(&body)?, FRAME_HEADER_SIZE + body.len())));
+        }
+        if self.partial.len() + body.len() > MAX_MESSAGE_SIZE {
+            return Err("Peer sent an oversized bulk message".to_string());
+        }
+        self.partial.extend_from_slice(&body);
+        self.wire_bytes += FRAME_HEADER_SIZE + body.len();
+        if kind == FrameKind::Fragment {
+            return Ok(None);
+        }
+        let payload = std::mem::take(&mut self.partial);
+        Ok(Some((decode(&payload)?, std::mem::take(&mut self.wire_bytes))))
+    }
+}
+
+/// Writes all of a message's frames
+pub async fn write_message<W: AsyncWriteExt + Unpin>(writer: &mut W, message: &NetworkMessage) -> Result<(), String> {
+    for frame in frames(&encode(message)?, message.lane()) {
+        writer.write_all(&frame).await.map_err(|e| e.to_string())?;
+    }
+    writer.flush().await.map_err(|e| e.to_string())
+}
+
+/// Reads frames until one completes a message. Fragments of a bulk message that is
+/// still incomplete stay in `reassembly` for the next call
+pub async fn read_message<R: AsyncReadExt + Unpin>(reader: &mut R, reassembly: &mut Reassembly) -> Result<NetworkMessage, String> {
+    loop {
+        let (kind, length) = read_header(reader).await?;
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).await.map_err(|e| e.to_string())?;
+        if let Some((message, _)) = reassembly.push(kind, body)? {
+            return Ok(message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_message_framing() {
+        let (mut client, mut server) = tokio::io::duplex(64 * 1024);
+        let mut reassembly = Reassembly::default();
+        write_message(&mut client, &NetworkMessage::GetBlocks { from: 42 }).await.unwrap();
+        match read_message(&mut server, &mut reassembly).await.unwrap() {
+            NetworkMessage::GetBlocks { from } => assert_eq!(from, 42),
+            other => panic!("unexpected message {:?}", other),
+        }
+        assert_eq!(NetworkMessage::GetPeers.kind(), "GetPeers");
+
+        // A priority frame written between two fragments arrives before the bulk message
+        let bulk = NetworkMessage::Peers(vec![]);
+        let mut payload = encode(&bulk).unwrap();
+        payload.resize(BULK_FRAGMENT_SIZE + 1, 0);
+        let fragments = frames(&payload, Lane::Bulk);
+        assert_eq!(fragments.len(), 2);
+        assert_eq!(fragments[1].len(), FRAME_HEADER_SIZE + 1);
+        let hello = NetworkMessage::Hello {
+            node_id: vec![1],
+            protocols: vec![PROTOCOL_VERSION.to_string()],
+            capabilities: vec![],
+            listen_port: 1,
+            next_height: 0,
+            observed: SocketAddr::from(([127, 0, 0, 1], 1)),
+            external: None,
+            compression: vec![],
+            chain_id: 1337,
+            genesis: Some([0; 32]),
+        };
+        assert_eq!(hello.lane(), Lane::Priority);
+        client.write_all(&fragments[0]).await.unwrap();
+        client.write_all(&frames(&encode(&hello).unwrap(), hello.lane())[0]).await.unwrap();
+        client.write_all(&fragments[1]).await.unwrap();
+        assert_eq!(read_message(&mut server, &mut reassembly).await.unwrap().kind(), "Hello");
+        assert_eq!(read_message(&mut server, &mut reassembly).await.unwrap().kind(), "Peers");
+
+        client.write_u32(MAX_MESSAGE_SIZE as u32 + 1).await.unwrap();
+        assert!(read_message(&mut server, &mut reassembly).await.is_err());
+
+        println!("   Network message framing working!");
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+pub const NAT_PMP_PORT: u16 = 5351;
+pub const SSDP_ADDRESS: &str = "239.255.255.250:1900";
+/// Lifetime requested for port mappings; they are renewed at half of it
+pub const MAPPING_LIFETIME: Duration = Duration::from_secs(3600);
+/// Distinct peers that must report the same address before it is advertised
+pub const CONFIRMATION_VOTES: usize = 3;
+
+const GATEWAY_TIMEOUT: Duration = Duration::from_secs(2);
+const IGD_SERVICES: [&str; 2] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappingProtocol {
+    NatPmp,
+    Upnp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortMapping {
+    pub protocol: MappingProtocol,
+    pub external: SocketAddr,
+    pub lifetime: Duration,
+}
+
+/// Asks the router to forward `port` to this host, trying NAT-PMP on the default
+/// gateway first and then a UPnP internet gateway device
+pub async fn map_port(port: u16) -> Result<PortMapping, String> {
+    let nat_pmp = match default_gateway() {
+        Some(gateway) => nat_pmp_map(gateway, port).await,
+        None => Err("no default gateway".to_string()),
+    };
+    match nat_pmp {
+        Ok(mapping) => Ok(mapping),
+        Err(nat_pmp) => upnp_map(port)
+            .await
+            .map_err(|upnp| format!("NAT-PMP: {}; UPnP: {}", nat_pmp, upnp)),
+    }
+}
+
+/// IPv4 default gateway from the kernel routing table
+pub fn default_gateway() -> Option<Ipv4Addr> {
+    parse_default_route(&std::fs::read_to_string("/proc/net/route").ok()?)
+}
+
+/// Gateway of the `/proc/net/route` entry whose destination is 0.0.0.0. Addresses are
+/// little-endian hex
+fn parse_default_route(table: &str) -> Option<Ipv4Addr> {
+    table.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.get(1) != Some(&"00000000") {
+            return None;
+        }
+        let gateway = u32::from_str_radix(fields.get(2)?, 16).ok()?;
+        Some(Ipv4Addr::from(gateway.to_le_bytes()))
+    })
+}
+
+async fn nat_pmp_request(socket: &UdpSocket, request: &[u8], response_length: usize) -> Result<Vec<u8>, String> {
+    socket.send(request).await.map_err(|e| e.to_string())?;
+    let mut response = vec![0; response_length];
+    let read = tokio::time::timeout(GATEWAY_TIMEOUT, socket.recv(&mut response))
+        .await
+        .map_err(|_| "gateway did not answer".to_string())?
+        .map_err(|e| e.to_string())?;
+    if read < response_length || response[1] != request[1] + 128 {
+        return Err("unexpected NAT-PMP response".to_string());
+    }
+    match u16::from_be_bytes([response[2], response[3]]) {
+        0 => Ok(response),
+        code => Err(format!("NAT-PMP result code {}", code)),
+    }
+}
+
+pub async fn nat_pmp_map(gateway: Ipv4Addr, port: u16) -> Result<PortMapping, String> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await.map_err(|e| e.to_string())?;
+    socket.connect((gateway, NAT_PMP_PORT)).await.map_err(|e| e.to_string())?;
+
+    let address = nat_pmp_request(&socket, &[0, 0], 12).await?;
+    let ip = Ipv4Addr::new(address[8], address[9], address[10], address[11]);
+
+    let mut request = vec![0, 2, 0, 0];
+    request.extend(port.to_be_bytes());
+    request.extend(port.to_be_bytes());
+    request.extend((MAPPING_LIFETIME.as_secs() as u32).to_be_bytes());
+    let mapping = nat_pmp_request(&socket, &request, 16).await?;
+    let external_port = u16::from_be_bytes([mapping[10], mapping[11]]);
+    let lifetime = u32::from_be_bytes([mapping[12], mapping[13], mapping[14], mapping[15]]);
+    Ok(PortMapping {
+        protocol: MappingProtocol::NatPmp,
+        external: SocketAddr::from((ip, external_port)),
+        lifetime: Duration::from_secs(lifetime as u64),
+    })
+}
+
+pub async fn upnp_map(port: u16) -> Result<PortMapping, String> {
+    let location = discover_gateway_device().await?;
+    let client = hyper::Client::new();
+    let response = client
+        .get(location.parse().map_err(|e: hyper::http::uri::InvalidUri| e.to_string())?)
+        .await
+        .map_err(|e| e.to_string())?;
+    let description = hyper::body::to_bytes(response.into_body()).await.map_err(|e| e.to_string())?;
+    let description = String::from_utf8_lossy(&description);
+    let (service, control_url) = IGD_SERVICES
+        .iter()
+        .find_map(|service| control_url(&description, service).map(|url| (*service, url)))
+        .ok_or("gateway has no WAN connection service")?;
+    let control_url = resolve_url(&location, &control_url);
+
+    // The router forwards to whichever local address reaches it
+    let gateway = control_url.split("://").nth(1).and_then(|rest| rest.split('/').next()).unwrap_or_default();
+    let probe = UdpSocket::bind("0.0.0.0:0").await.map_err(|e| e.to_string())?;
+    probe.connect(gateway).await.map_err(|e| e.to_string())?;
+    let local = probe.local_addr().map_err(|e| e.to_string())?.ip();
+
+    let arguments = format!(
+        "<NewRemoteHost></NewRemoteHost><NewExternalPort>{port}</NewExternalPort><NewProtocol>TCP</NewProtocol>\
+         <NewInternalPort>{port}</NewInternalPort><NewInternalClient>{local}</NewInternalClient>\
+         <NewEnabled>1</NewEnabled><NewPortMappingDescription>TriUnity</NewPortMappingDescription>\
+         <NewLeaseDuration>{}</NewLeaseDuration>",
+        MAPPING_LIFETIME.as_secs()
+    );
+    soap_call(&client, &control_url, service, "AddPortMapping", &arguments).await?;
+    let reply = soap_call(&client, &control_url, service, "GetExternalIPAddress", "").await?;
+    let ip: IpAddr = xml_value(&reply, "NewExternalIPAddress")
+        .and_then(|ip| ip.parse().ok())
+        .ok_or("gateway did not report its external address")?;
+    Ok(PortMapping {
+        protocol: MappingProtocol::Upnp,
+        external: SocketAddr::new(ip, port),
+        lifetime: MAPPING_LIFETIME,
+    })
+}
+
+/// Description URL of the first internet gateway device answering an SSDP search
+async fn discover_gateway_device() -> Result<String, String> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await.map_err(|e| e.to_string())?;
+    let search = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\r\n",
+        SSDP_ADDRESS
+    );
+    socket.send_to(search.as_bytes(), SSDP_ADDRESS).await.map_err(|e| e.to_string())?;
+    let mut response = vec![0; 2048];
+    let (read, _) = tokio::time::timeout(GATEWAY_TIMEOUT, socket.recv_from(&mut response))
+        .await
+        .map_err(|_| "no UPnP gateway answered".to_string())?
+        .map_err(|e| e.to_string())?;
+    ssdp_location(&String::from_utf8_lossy(&response[..read])).ok_or_else(|| "SSDP reply has no location".to_string())
+}
+
+fn ssdp_location(response: &str) -> Option<String> {
+    response.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.trim().eq_ignore_ascii_case("location").then(|| value.trim().to_string())
+    })
+}
+
+/// `controlURL` of `service` in a device description
+fn control_url(description: &str, service: &str) -> Option<String> {
+    let start = description.find(&format!("<serviceType>{}</serviceType>", service))?;
+    let rest = &description[start..];
+    let end = rest.find("</service>").unwrap_or(rest.len());
+    xml_value(&rest[..end], "controlURL")
+}
+
+fn resolve_url(location: &str, path: &str) -> String {
+    if path.contains("://") {
+        return path.to_string();
+    }
+    let origin_end = location
+        .find("://")
+        .and_then(|scheme| location[scheme + 3..].find('/').map(|host| scheme + 3 + host))
+        .unwrap_or(location.len());
+    format!("{}/{}", &location[..origin_end], path.trim_start_matches('/'))
+}
+
+fn xml_value(xml: &str, tag: &str) -> Option<String> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = xml[start..].find(&format!("</{}>", tag))? + start;
+    Some(xml[start..end].trim().to_string())
+}
+
+async fn soap_call(
+    client: &hyper::Client<hyper::client::HttpConnector>,
+    url: &str,
+    service: &str,
+    action: &str,
+    arguments: &str,
+) -> Result<String, String> {
+    let body = format!(
+        "<?xml version=\"1.0\"?><s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+         s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\"><s:Body>\
+         <u:{action} xmlns:u=\"{service}\">{arguments}</u:{action}></s:Body></s:Envelope>"
+    );
+    let request = hyper::Request::post(url)
+        .header("content-type", "text/xml; charset=\"utf-8\"")
+        .header("soapaction", format!("\"{}#{}\"", service, action))
+        .body(hyper::Body::from(body))
+        .map_err(|e| e.to_string())?;
+    let response = client.request(request).await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    let reply = hyper::body::to_bytes(response.into_body()).await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(format!("{} failed with {}", action, status));
+    }
+    Ok(String::from_utf8_lossy(&reply).into_owned())
+}
+
+/// External IP addresses reported by peers. Each peer gets one vote, and an address
+/// is only confirmed once enough distinct peers agree, so a single peer cannot make
+/// this node advertise an address of its choosing
+#[derive(Debug, Default)]
+pub struct AddressVotes {
+    votes: HashMap<Vec<u8>, IpAddr>,
+}
+
+impl AddressVotes {
+    /// Records the address `peer` sees this node connecting from, replacing its earlier vote
+    pub fn record(&mut self, peer: &[u8], observed: IpAddr) {
+        if !observed.is_unspecified() {
+            self.votes.insert(peer.to_vec(), observed);
+        }
+    }
+
+    pub fn forget(&mut self, peer: &[u8]) {
+        self.votes.remove(peer);
+    }
+
+    /// The address most peers agree on, once at least `CONFIRMATION_VOTES` of them do
+    pub fn confirmed(&self) -> Option<IpAddr> {
+        let mut tally: HashMap<IpAddr, usize> = HashMap::new();
+        for address in self.votes.values() {
+            *tally.entry(*address).or_default() += 1;
+        }
+        tally
+            .into_iter()
+            .filter(|(_, votes)| *votes >= CONFIRMATION_VOTES)
+            .max_by_key(|(address, votes)| (*votes, *address))
+            .map(|(address, _)| address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_discovery() {
+        let mut votes = AddressVotes::default();
+        let external: IpAddr = "203.0.113.7".parse().unwrap();
+        votes.record(b"peer-a", external);
+        votes.record(b"peer-b", external);
+        votes.record(b"peer-b", external);
+        votes.record(b"peer-c", "198.51.100.1".parse().unwrap());
+        assert_eq!(votes.confirmed(), None);
+        votes.record(b"peer-c", external);
+        assert_eq!(votes.confirmed(), Some(external));
+        votes.forget(b"peer-a");
+        assert_eq!(votes.confirmed(), None);
+
+        let table = "Iface\tDestination\tGateway \tFlags\n\
+                     eth0\t0000A8C0\t00000000\t0001\n\
+                     eth0\t00000000\t0101A8C0\t0003\n";
+        assert_eq!(parse_default_route(table), Some(Ipv4Addr::new(192, 168, 1, 1)));
+
+        let reply = "HTTP/1.1 200 OK\r\nST: upnp:rootdevice\r\nLocation: http://192.168.1.1:5000/rootDesc.xml\r\n\r\n";
+        let location = ssdp_location(reply).unwrap();
+        let description = "<service><serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType>\
+                           <controlURL>/ctl/L3F</controlURL></service>\
+                           <service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>\
+                           <controlURL>/ctl/IPConn</controlURL></service>";
+        let control = control_url(description, IGD_SERVICES[0]).unwrap();
+        assert_eq!(resolve_url(&location, &control), "http://192.168.1.1:5000/ctl/IPConn");
+        assert_eq!(control_url(description, IGD_SERVICES[1]), None);
+
+        println!("   NAT address discovery working!");
+    }
+}
@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+use crate::log;
+use crate::storage::database::BlockchainDB;
+
+/// Meta key a database records the network its chain belongs to under
+const NETWORK_META: &str = "network";
+
+/// Network a node joins; peers on another network are refused at the handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkId {
+    Mainnet,
+    Testnet,
+    /// Local and throwaway chains, such as `triunity-node --dev` and the in-process testnet
+    #[default]
+    Devnet,
+}
+
+/// Defaults a node takes from the network it joins
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkProfile {
+    pub id: NetworkId,
+    /// Sent in every handshake; a peer with another one is on another network
+    pub chain_id: u64,
+    pub p2p_port: u16,
+    pub rpc_port: u16,
+    /// `host:port` of nodes dialed on start when no peer is given
+    pub bootstrap_peers: &'static [&'static str],
+}
+
+impl NetworkId {
+    pub const ALL: [NetworkId; 3] = [Self::Mainnet, Self::Testnet, Self::Devnet];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Mainnet => "mainnet",
+            Self::Testnet => "testnet",
+            Self::Devnet => "devnet",
+        }
+    }
+
+    pub fn profile(&self) -> NetworkProfile {
+        match self {
+            Self::Mainnet => NetworkProfile {
+                id: *self,
+                chain_id: 1,
+                p2p_port: 30333,
+                rpc_port: 9933,
+                bootstrap_peers: &["boot1.triunity.org:30333", "boot2.triunity.org:30333"],
+            },
+            Self::Testnet => NetworkProfile {
+                id: *self,
+                chain_id: 2,
+                p2p_port: 31333,
+                rpc_port: 9934,
+                bootstrap_peers: &["boot1.testnet.triunity.org:31333"],
+            },
+            Self::Devnet => NetworkProfile { id: *self, chain_id: 1337, p2p_port: 30300, rpc_port: 8080, bootstrap_peers: &[] },
+        }
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.profile().chain_id
+    }
+
+    /// Directory under a base data directory this network's chain is kept in, so nodes
+    /// of different networks never share one
+    pub fn data_subdir(&self) -> &'static str {
+        self.name()
+    }
+
+    /// Records this network in `db`, refusing a database that holds another network's
+    /// chain. Databases from before networks were recorded are taken as devnet
+    pub fn claim(&self, db: &BlockchainDB) -> Result<(), String> {
+        let recorded = match db.get_meta::<NetworkId>(NETWORK_META)? {
+            Some(recorded) => recorded,
+            None if db.block_count()? > 0 => NetworkId::Devnet,
+            None => *self,
+        };
+        if recorded != *self {
+            return Err(format!("The database holds a {} chain, not {}", recorded, self));
+        }
+        db.put_meta(NETWORK_META, self)
+    }
+}
+
+impl NetworkProfile {
+    /// Resolves `peers`, or this network's bootstrap peers when none are given. Names that
+    /// do not resolve are skipped with a warning, so one dead seed does not stop a start
+    pub async fn resolve_peers(&self, peers: &[String]) -> Vec<SocketAddr> {
+        let names: Vec<String> = match peers.is_empty() {
+            true => self.bootstrap_peers.iter().map(|peer| peer.to_string()).collect(),
+            false => peers.to_vec(),
+        };
+        let mut resolved = Vec::new();
+        for name in names {
+            match tokio::net::lookup_host(name.as_str()).await {
+                Ok(addresses) => resolved.extend(addresses.take(1)),
+                Err(e) => log!(Warn, "Could not resolve bootstrap peer {}: {}", name, e),
+            }
+        }
+        resolved
+    }
+}
+
+impl fmt::Display for NetworkId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for NetworkId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|network| network.name() == s)
+            .ok_or_else(|| format!("Unknown network: {} (expected mainnet, testnet or devnet)", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_network_profiles() {
+        // Every network has its own chain id, ports and directory
+        let profiles: Vec<NetworkProfile> = NetworkId::ALL.iter().map(NetworkId::profile).collect();
+        for (index, profile) in profiles.iter().enumerate() {
+            assert_eq!(profile.id.name().parse::<NetworkId>().unwrap(), profile.id);
+            for other in &profiles[index + 1..] {
+                assert_ne!(profile.chain_id, other.chain_id);
+                assert_ne!((profile.p2p_port, profile.rpc_port), (other.p2p_port, other.rpc_port));
+                assert_ne!(profile.id.data_subdir(), other.id.data_subdir());
+            }
+        }
+        assert!("devnet2".parse::<NetworkId>().is_err());
+        assert_eq!(NetworkId::default(), NetworkId::Devnet);
+        assert!(NetworkId::Devnet.profile().bootstrap_peers.is_empty() && !NetworkId::Mainnet.profile().bootstrap_peers.is_empty());
+
+        // Given peers replace the bootstrap list
+        let peers = NetworkId::Mainnet.profile().resolve_peers(&["127.0.0.1:4000".to_string()]).await;
+        assert_eq!(peers, vec![SocketAddr::from(([127, 0, 0, 1], 4000))]);
+
+        // A database stays on the network that first claimed it
+        let temp_dir = std::env::temp_dir().join("triunity_test_network_profiles");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        NetworkId::Testnet.claim(&db).unwrap();
+        NetworkId::Testnet.claim(&db).unwrap();
+        assert_eq!(NetworkId::Mainnet.claim(&db).unwrap_err(), "The database holds a testnet chain, not mainnet");
+
+        println!("   Network profiles working!");
+    }
+}
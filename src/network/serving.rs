@@ -0,0 +1,186 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::bandwidth::Throttle;
+use crate::config::SyncServingConfig;
+
+/// Sync requests this node is answering for one peer
+type InFlight = Arc<Mutex<HashMap<Vec<u8>, usize>>>;
+
+/// Held while a sync request is served; frees the peer's slot when dropped
+#[derive(Debug)]
+pub struct ServingPermit {
+    peer: Vec<u8>,
+    in_flight: InFlight,
+}
+
+impl Drop for ServingPermit {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if let Some(count) = in_flight.get_mut(&self.peer) {
+            *count -= 1;
+            if *count == 0 {
+                in_flight.remove(&self.peer);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ServingCounters {
+    served: AtomicU64,
+    priority_served: AtomicU64,
+    refused: AtomicU64,
+    bytes: AtomicU64,
+}
+
+/// Load of answering block and snapshot requests from syncing peers
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ServingStats {
+    /// Requests being answered right now
+    pub in_flight: usize,
+    pub served: u64,
+    /// Of `served`, requests from validators and trusted peers
+    pub priority_served: u64,
+    /// Requests dropped because the peer already had its share in flight
+    pub refused: u64,
+    pub bytes: u64,
+}
+
+impl ServingStats {
+    /// Prometheus text exposition of the counters
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP triunity_sync_serving_in_flight Sync requests being answered");
+        let _ = writeln!(out, "# TYPE triunity_sync_serving_in_flight gauge");
+        let _ = writeln!(out, "triunity_sync_serving_in_flight {}", self.in_flight);
+        let name = "triunity_sync_serving_requests_total";
+        let _ = writeln!(out, "# HELP {} Sync requests answered", name);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        let _ = writeln!(out, "{}{{priority=\"false\"}} {}", name, self.served - self.priority_served);
+        let _ = writeln!(out, "{}{{priority=\"true\"}} {}", name, self.priority_served);
+        for (name, help, value) in [
+            ("refused_total", "Sync requests refused over the per-peer cap", self.refused),
+            ("bytes_total", "Bytes of sync responses", self.bytes),
+        ] {
+            let name = format!("triunity_sync_serving_{}", name);
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+        out
+    }
+}
+
+/// Admission and pacing of sync serving. Each peer may have a few requests answered at
+/// once, responses are cut to the configured chunk size, and the bytes of all responses
+/// share one budget. Validators and trusted peers are exempt from the budget, so they are
+/// still served promptly while others queue for it
+#[derive(Debug)]
+pub struct SyncServing {
+    config: SyncServingConfig,
+    priority_peers: HashSet<Vec<u8>>,
+    budget: Throttle,
+    in_flight: InFlight,
+    counters: ServingCounters,
+}
+
+impl SyncServing {
+    pub fn new(config: SyncServingConfig) -> Self {
+        Self {
+            priority_peers: config.priority_peers.iter().filter_map(|peer| hex::decode(peer.trim_start_matches("0x")).ok()).collect(),
+            budget: Throttle::new(config.bytes_per_second),
+            config,
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+            counters: ServingCounters::default(),
+        }
+    }
+
+    pub fn config(&self) -> &SyncServingConfig {
+        &self.config
+    }
+
+    pub fn is_priority_peer(&self, peer: &[u8]) -> bool {
+        self.priority_peers.contains(peer)
+    }
+
+    /// Takes one of `peer`'s request slots, `None` when they are all in use
+    pub fn admit(&self, peer: &[u8]) -> Option<ServingPermit> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let count = in_flight.entry(peer.to_vec()).or_default();
+        if *count >= self.config.max_requests_per_peer {
+            self.counters.refused.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        *count += 1;
+        Some(ServingPermit { peer: peer.to_vec(), in_flight: self.in_flight.clone() })
+    }
+
+    /// Waits until a response of `bytes` fits the serving budget, then counts it
+    pub async fn pace(&self, bytes: usize, priority: bool) {
+        if !priority {
+            self.budget.acquire(bytes).await;
+        }
+        self.counters.served.fetch_add(1, Ordering::Relaxed);
+        if priority {
+            self.counters.priority_served.fetch_add(1, Ordering::Relaxed);
+        }
+        self.counters.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> ServingStats {
+        ServingStats {
+            in_flight: self.in_flight.lock().unwrap().values().sum(),
+            served: self.counters.served.load(Ordering::Relaxed),
+            priority_served: self.counters.priority_served.load(Ordering::Relaxed),
+            refused: self.counters.refused.load(Ordering::Relaxed),
+            bytes: self.counters.bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn test_sync_serving_limits() {
+        let config = SyncServingConfig {
+            max_requests_per_peer: 2,
+            bytes_per_second: Some(100_000),
+            priority_peers: vec!["0x0202".to_string()],
+            ..SyncServingConfig::default()
+        };
+        let serving = SyncServing::new(config);
+        assert!(serving.is_priority_peer(&[2, 2]) && !serving.is_priority_peer(&[2]));
+
+        // A third request from the same peer is refused until one of the first two ends
+        let (first, second) = (serving.admit(&[1]).unwrap(), serving.admit(&[1]).unwrap());
+        assert!(serving.admit(&[1]).is_none());
+        let other = serving.admit(&[2]).unwrap();
+        assert_eq!(serving.stats().in_flight, 3);
+        drop(first);
+        let third = serving.admit(&[1]).unwrap();
+
+        // The first second of budget is free; ordinary peers then wait while priority ones do not
+        let started = Instant::now();
+        serving.pace(100_000, false).await;
+        serving.pace(1_000_000, true).await;
+        assert!(started.elapsed() < Duration::from_millis(100));
+        serving.pace(50_000, false).await;
+        assert!(started.elapsed() >= Duration::from_millis(450));
+        drop((second, third, other));
+
+        let stats = serving.stats();
+        assert_eq!(stats, ServingStats { in_flight: 0, served: 3, priority_served: 1, refused: 1, bytes: 1_150_000 });
+        let prometheus = stats.to_prometheus();
+        assert!(prometheus.contains("triunity_sync_serving_requests_total{priority=\"false\"} 2"));
+        assert!(prometheus.contains("triunity_sync_serving_refused_total 1"));
+
+        println!("   Sync serving limits working!");
+    }
+}
@@ -0,0 +1,145 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+
+use super::bandwidth::Traffic;
+use crate::cli::inspect::short_hex;
+
+/// Which side opened a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// One connected peer as the dashboard lists it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PeerView {
+    /// `short_hex` of the peer's node id, as topology nodes are named
+    pub node_id: String,
+    /// `None` once redacted
+    pub address: Option<SocketAddr>,
+    pub direction: Direction,
+    pub protocol: String,
+    /// Last measured round trip; `None` until a peer offering `Ping` answers one
+    pub latency_ms: Option<f64>,
+    pub traffic: Traffic,
+}
+
+impl PeerView {
+    pub fn redacted(mut self) -> Self {
+        self.address = None;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TopologyNode {
+    pub id: String,
+    pub local: bool,
+    /// Connected to this node
+    pub connected: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TopologyEdge {
+    pub source: String,
+    pub target: String,
+    /// Measured for this node's own links only
+    pub latency_ms: Option<f64>,
+    /// Bytes both ways, for this node's own links only
+    pub bytes: Option<u64>,
+}
+
+/// Peer graph for a force-directed layout: this node, its peers and the peers each of
+/// them reported in peer exchange, linked as reported. Addresses are never included
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Topology {
+    pub nodes: Vec<TopologyNode>,
+    pub edges: Vec<TopologyEdge>,
+}
+
+impl Topology {
+    /// `reported` holds, by reporting peer, the node ids its last `Peers` message named
+    pub fn build(own_id: &[u8], peers: &[PeerView], reported: &HashMap<Vec<u8>, Vec<Vec<u8>>>) -> Self {
+        let local = short_hex(own_id);
+        let mut nodes: BTreeMap<String, TopologyNode> = BTreeMap::new();
+        nodes.insert(local.clone(), TopologyNode { id: local.clone(), local: true, connected: false });
+        let mut edges: BTreeMap<(String, String), TopologyEdge> = BTreeMap::new();
+        for peer in peers {
+            nodes.insert(peer.node_id.clone(), TopologyNode { id: peer.node_id.clone(), local: false, connected: true });
+            edges.insert(
+                edge_key(&local, &peer.node_id),
+                TopologyEdge {
+                    source: local.clone(),
+                    target: peer.node_id.clone(),
+                    latency_ms: peer.latency_ms,
+                    bytes: Some(peer.traffic.sent + peer.traffic.received),
+                },
+            );
+        }
+        let mut reported: Vec<(&Vec<u8>, &Vec<Vec<u8>>)> = reported.iter().collect();
+        reported.sort();
+        for (reporter, neighbours) in reported {
+            let reporter = short_hex(reporter);
+            for neighbour in neighbours.iter().map(|id| short_hex(id)) {
+                if neighbour == reporter {
+                    continue;
+                }
+                for id in [&reporter, &neighbour] {
+                    nodes.entry(id.clone()).or_insert_with(|| TopologyNode { id: id.clone(), local: false, connected: false });
+                }
+                edges.entry(edge_key(&reporter, &neighbour)).or_insert_with(|| TopologyEdge {
+                    source: reporter.clone(),
+                    target: neighbour,
+                    latency_ms: None,
+                    bytes: None,
+                });
+            }
+        }
+        Self { nodes: nodes.into_values().collect(), edges: edges.into_values().collect() }
+    }
+}
+
+/// Links are undirected, so each pair is keyed in sorted order
+fn edge_key(a: &str, b: &str) -> (String, String) {
+    if a <= b {
+        (a.to_string(), b.to_string())
+    } else {
+        (b.to_string(), a.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topology_graph() {
+        let peer = |id: u8, latency_ms| PeerView {
+            node_id: short_hex(&[id]),
+            address: Some(SocketAddr::from(([10, 0, 0, id], 9000))),
+            direction: Direction::Outbound,
+            protocol: "triunity/1.6".to_string(),
+            latency_ms,
+            traffic: Traffic { sent: 10, received: 5 },
+        };
+        let peers = vec![peer(2, Some(1.5)), peer(3, None)];
+        assert_eq!(peers[0].clone().redacted().address, None);
+
+        // Peer 2 reports this node, peer 3 and an unconnected node 4; peer 3 reports 2 back
+        let reported = HashMap::from([(vec![2], vec![vec![1], vec![3], vec![4]]), (vec![3], vec![vec![2], vec![3]])]);
+        let topology = Topology::build(&[1], &peers, &reported);
+        let ids: Vec<(&str, bool, bool)> = topology.nodes.iter().map(|node| (node.id.as_str(), node.local, node.connected)).collect();
+        assert_eq!(ids, vec![("0x01", true, false), ("0x02", false, true), ("0x03", false, true), ("0x04", false, false)]);
+
+        // Own links keep their measurements and reported duplicates collapse into them
+        assert_eq!(topology.edges.len(), 4);
+        let own = topology.edges.iter().find(|edge| edge.target == "0x02").unwrap();
+        assert_eq!((own.source.as_str(), own.latency_ms, own.bytes), ("0x01", Some(1.5), Some(15)));
+        assert!(topology.edges.iter().any(|edge| (edge.source.as_str(), edge.target.as_str(), edge.bytes) == ("0x02", "0x03", None)));
+
+        println!("   Network topology working!");
+    }
+}
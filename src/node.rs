@@ -0,0 +1,2133 @@
+pub mod builder;
+pub mod embed;
+pub mod import;
+pub mod orphans;
+pub mod seen;
+pub mod verified;
+
+use arc_swap::{ArcSwap, ArcSwapOption};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+use crate::config::{KeysConfig, PinConfig};
+use crate::consensus::agreement::{AgreedPath, PathHistory, PathProposal, WindowTally, PATH_WINDOW};
+use crate::consensus::checkpoint::Checkpoint;
+use crate::consensus::committee::{self, FastLaneCommittee};
+use crate::consensus::decisions::DecisionLog;
+use crate::consensus::pin::{PathPin, PinnedMode};
+use crate::consensus::proposal::BlockProposal;
+use crate::consensus::duties::{DutyTracker, ValidatorPerformance, ValidatorStatus, EPOCH_LENGTH};
+use crate::consensus::fork_choice::{ForkChoice, ForkChoiceHead, FINALITY_DEPTH, GENESIS_PARENT};
+use crate::cli::inspect::short_hex;
+use crate::consensus::features::{Deployment, DeploymentState, DeploymentStatus, SIGNAL_BITS};
+use crate::consensus::gas::GasSchedule;
+use crate::consensus::params::{ChainParams, ParamSchedule};
+use crate::consensus::metrics::{ImportStage, ImportStageStats, MetricsCollector, SecurityEventType, SecuritySeverity};
+use crate::node::orphans::{Orphan, OrphanPool, OrphanStats, SyncRequest};
+use crate::node::seen::{DuplicateMetrics, SeenCache, SeenKind};
+use crate::consensus::router::{ConsensusPath, ConsensusRouter, ModelState, NetworkMetrics};
+use crate::consensus::validator_sets::ValidatorSets;
+use crate::consensus::votes::{CommitCertificate, Equivocation, Vote, VoteOutcome, VotePool};
+use crate::crypto::bech32::short_address;
+use crate::crypto::verification::Subsystem;
+use crate::crypto::{QuantumKeyPair, QuantumSignature};
+use crate::events::{EventBus, NodeEvent};
+use crate::log;
+use crate::mempool::{ClassMetrics, DropReason, DroppedTransaction, FeeEstimate, LaneMetrics, LaneQuota, Mempool, MempoolSummary, MempoolUpdate, PendingSummary, DEFAULT_MAX_PENDING_AGE};
+use crate::storage::availability::{self, DEFAULT_AVAILABILITY_THRESHOLD};
+use crate::storage::backup::{self, BackupManifest, BackupStep};
+use crate::storage::blocks::{Block, ConsensusData, Transaction, ValidatorRef, COMMITTEE_BLOCK_VERSION, VALIDATOR_REF_BLOCK_VERSION};
+use crate::storage::cache::CacheStats;
+use crate::storage::database::BlockchainDB;
+use crate::storage::TriUnityStorage;
+use crate::storage::envelope;
+use crate::storage::sessions::{SessionCall, SESSION_KEYS};
+use crate::storage::state::{Account, StateManager, StateSnapshot, STATE_BASE_META};
+use crate::storage::state_store::{PruneReport, StateStore, StateStoreMetrics, DEFAULT_RETAINED_ROOTS};
+use crate::storage::witness::BlockWitness;
+use builder::{BlockBuilder, BlockLimits};
+use embed::NodeBuilder;
+use verified::VerifiedCache;
+use import::{BadBlockRecord, BadBlocks, Origin, Quarantine, StageTimes};
+
+/// Vote weight each validator lends the branch it builds on
+const PROPOSER_VOTE_WEIGHT: u64 = 1;
+
+const DUTIES_META: &str = "validator_duties";
+const PATH_HISTORY_META: &str = "path_history";
+
+/// Fast-lane committees kept for recent epochs
+const COMMITTEE_CACHE: usize = 4;
+
+/// A fast-lane committee with its epoch and the seed block hash it was drawn from
+type CachedCommittee = (u64, Option<[u8; 32]>, Arc<FastLaneCommittee>);
+
+/// Blocks searched back from the head for each validator's latest proposal
+const LAST_PROPOSAL_DEPTH: u64 = 4 * EPOCH_LENGTH;
+
+/// Orphaned transaction hashes remembered for status queries
+const MAX_ORPHANED_TRACKED: usize = 10_000;
+
+/// Outcome of importing a block received from a peer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockImport {
+    Imported,
+    Known,
+    /// The block's parent is unknown; blocks from height `expected` onwards are needed first
+    Missing { expected: u64 },
+}
+
+/// Where a transaction is in its lifecycle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum TxStatus {
+    Pending,
+    Included {
+        height: u64,
+        #[serde(with = "hex::serde")]
+        block: [u8; 32],
+    },
+    Finalized {
+        height: u64,
+        #[serde(with = "hex::serde")]
+        block: [u8; 32],
+    },
+    /// Dropped from the canonical chain by a reorg and no longer valid for the mempool
+    Orphaned,
+}
+
+/// State of the canonical chain, which always ends at the fork-choice head
+struct Chain {
+    state: StateManager,
+    fork_choice: ForkChoice,
+    head: [u8; 32],
+}
+
+/// Immutable view of the canonical head, republished after every chain change so
+/// readers on async paths never wait for the chain lock
+#[derive(Debug, Clone)]
+struct HeadSnapshot {
+    next_height: u64,
+    fork_choice: ForkChoiceHead,
+}
+
+impl HeadSnapshot {
+    fn of(chain: &Chain) -> Self {
+        Self {
+            next_height: Node::height_after(&chain.fork_choice, &chain.head),
+            fork_choice: chain.fork_choice.summary(),
+        }
+    }
+}
+
+/// Admits transactions into the mempool, seals them into blocks when it is the
+/// proposer and imports blocks produced by other validators
+pub struct Node {
+    db: BlockchainDB,
+    /// Long-lived key peers know the node by
+    identity: Arc<QuantumKeyPair>,
+    /// Consensus key blocks are proposed with, one of the genesis validators on a
+    /// validator; swapped on key rotation
+    validator_key: ArcSwapOption<QuantumKeyPair>,
+    /// Hot key votes are signed with while the chain has it authorized for the validator key
+    session_key: ArcSwapOption<QuantumKeyPair>,
+    /// Set while an operator has paused block production
+    paused: AtomicBool,
+    validators: Vec<Vec<u8>>,
+    /// Genesis allocation of each validator, weighting its fast-lane committee draws
+    stakes: Vec<u64>,
+    /// `validators` as recorded per epoch, which blocks name their proposer from
+    validator_sets: ValidatorSets,
+    committees: Mutex<VecDeque<CachedCommittee>>,
+    /// Signed votes from the genesis validators, weighted by the same stakes
+    votes: Mutex<VotePool>,
+    chain: Mutex<Chain>,
+    head: ArcSwap<HeadSnapshot>,
+    mempool: Mutex<Mempool>,
+    /// Blocks and transactions already taken in, whichever path they came by
+    seen: SeenCache,
+    /// Transactions whose signatures checked out, so block import skips those the
+    /// mempool already verified
+    verified: VerifiedCache,
+    /// Blocks that arrived before their parent
+    orphans: Mutex<OrphanPool>,
+    /// Path the router or a pin picks, which blocks this node produces propose while the
+    /// chain has agreed on another
+    consensus_path: Mutex<ConsensusPath>,
+    /// Path the chain agreed on for the head's next block, which sets the block space
+    /// split between mempool lanes
+    agreed_path: Mutex<AgreedPath>,
+    path_history: Mutex<PathHistory>,
+    /// Picks `consensus_path`, taking the security score as its attack probability
+    router: Mutex<ConsensusRouter>,
+    /// Every path decision, persisted for audit
+    decisions: DecisionLog,
+    /// Operator pin the router's choice is overridden by until it expires
+    pin: Mutex<Option<PathPin>>,
+    /// Consensus limits and their scheduled upgrades, from genesis
+    params: ParamSchedule,
+    /// Digest of the genesis, which peers must share to be on the same chain
+    genesis_hash: [u8; 32],
+    /// Operator cap on the blocks this node produces, within the chain's limits
+    production_cap: Option<BlockLimits>,
+    /// Genesis gas schedule, whose price sets the least fee a transaction pays
+    gas: GasSchedule,
+    /// Blocks setting each signal bit, per signaling window finalized in full
+    window_signals: Mutex<HashMap<u64, [u64; SIGNAL_BITS as usize]>>,
+    events: EventBus,
+    dropped: broadcast::Sender<DroppedTransaction>,
+    mempool_updates: broadcast::Sender<MempoolUpdate>,
+    /// Account state root of every recent canonical height
+    state_store: StateStore,
+    /// State roots kept when pruning; `None` in archive mode, which keeps them all
+    retained_roots: Option<u64>,
+    /// Finalized blocks kept in sled before moving to the ancient store; `None` keeps
+    /// every block in sled
+    ancient_after: Option<u64>,
+    duties: Mutex<DutyTracker>,
+    /// Recently orphaned transactions that could not return to the mempool
+    orphaned: Mutex<VecDeque<[u8; 32]>>,
+    /// Trusted block a fresh node fast-syncs from, and which no block may conflict with
+    checkpoint: Option<Checkpoint>,
+    /// Per-stage block import timings and security events
+    metrics: Arc<Mutex<MetricsCollector>>,
+    /// Blocks refused on import, kept in the database for analysis
+    bad_blocks: BadBlocks,
+    /// Directory refused blocks are also written to
+    quarantine: Option<Quarantine>,
+    /// Whether produced blocks get an execution witness for stateless validators
+    witnesses: bool,
+    /// Encoded body size from which produced blocks commit to their erasure-coded chunks
+    availability_threshold: usize,
+}
+
+impl Node {
+    /// Starts setting up a node to run inside the calling application
+    pub fn builder() -> NodeBuilder {
+        NodeBuilder::default()
+    }
+
+    pub fn open(db: BlockchainDB) -> Result<Self, String> {
+        let state = StateManager::replay(&db)?;
+        let validators = db.get_genesis_validators()?;
+        let gas = db.get_gas_schedule()?;
+        let allocations: HashMap<Vec<u8>, u64> = db.get_genesis_allocations()?.into_iter().collect();
+        let stakes: Vec<u64> = validators.iter().map(|validator| allocations.get(validator).copied().unwrap_or_default()).collect();
+        let votes = VotePool::new(&validators, &stakes);
+        let fork_choice = Self::load_fork_choice(&db)?;
+        let head = fork_choice.head();
+        let state_store = StateStore::open(&db)?;
+        let bad_blocks = BadBlocks::open(&db)?;
+        let validator_sets = ValidatorSets::open(&db)?;
+        if db.block_count()? > 0 {
+            let latest = db.get_latest_height()?;
+            if state_store.root_at(latest)?.is_none() {
+                state_store.commit(latest, &state)?;
+            }
+        }
+        let params = db.get_chain_params()?;
+        let genesis_hash = db.genesis_hash()?;
+        let chain = Chain { state, fork_choice, head };
+        let head = HeadSnapshot::of(&chain);
+        let duties = match db.get_meta::<DutyTracker>(DUTIES_META)? {
+            Some(saved) if saved.tracks(&validators) => saved,
+            _ => DutyTracker::new(&validators, DutyTracker::epoch_of(head.next_height)),
+        };
+        let router = ConsensusRouter::new();
+        let path = router.select_optimal_path();
+        let decisions = DecisionLog::open(&db)?;
+        decisions.record(router.network_status(), &path, router.ai_confidence(), None)?;
+        let path_history = db.get_meta::<PathHistory>(PATH_HISTORY_META)?.unwrap_or_default();
+        let agreed_path = path_history.at(head.next_height / PATH_WINDOW);
+        let node = Self {
+            db,
+            identity: Arc::new(QuantumKeyPair::generate()),
+            validator_key: ArcSwapOption::empty(),
+            session_key: ArcSwapOption::empty(),
+            paused: AtomicBool::new(false),
+            validators,
+            stakes,
+            validator_sets,
+            votes: Mutex::new(votes),
+            committees: Mutex::new(VecDeque::new()),
+            head: ArcSwap::from_pointee(head),
+            chain: Mutex::new(chain),
+            mempool: Mutex::new(Mempool::default().with_gas_schedule(gas)),
+            seen: SeenCache::default(),
+            verified: VerifiedCache::default(),
+            orphans: Mutex::new(OrphanPool::default()),
+            consensus_path: Mutex::new(path),
+            agreed_path: Mutex::new(agreed_path),
+            path_history: Mutex::new(path_history),
+            router: Mutex::new(router),
+            decisions,
+            pin: Mutex::new(None),
+            params,
+            genesis_hash,
+            production_cap: None,
+            gas,
+            window_signals: Mutex::new(HashMap::new()),
+            events: EventBus::default(),
+            dropped: broadcast::channel(1024).0,
+            mempool_updates: broadcast::channel(1024).0,
+            state_store,
+            retained_roots: Some(DEFAULT_RETAINED_ROOTS),
+            ancient_after: None,
+            duties: Mutex::new(duties),
+            orphaned: Mutex::new(VecDeque::new()),
+            checkpoint: None,
+            metrics: Arc::new(Mutex::new(MetricsCollector::default())),
+            bad_blocks,
+            quarantine: None,
+            witnesses: false,
+            availability_threshold: DEFAULT_AVAILABILITY_THRESHOLD,
+        };
+        node.update_agreed_path(node.head.load().next_height)?;
+        Ok(node)
+    }
+
+    /// Rebuilds the block tree from the canonical chain above the finality depth
+    fn load_fork_choice(db: &BlockchainDB) -> Result<ForkChoice, String> {
+        if db.block_count()? == 0 {
+            return Ok(ForkChoice::new(GENESIS_PARENT, 0));
+        }
+        let latest = db.get_latest_height()?;
+        let mut fork_choice = ForkChoice::new(GENESIS_PARENT, 0);
+        let mut first = 0;
+        // A checkpoint-synced chain has nothing below its base block to root the tree at
+        let base = StateManager::base(db)?.1.checked_sub(1);
+        if let Some(root) = latest.checked_sub(FINALITY_DEPTH).max(base) {
+            let block = db.get_block(root)?
+                .ok_or_else(|| format!("Block {} missing from storage", root))?;
+            fork_choice = ForkChoice::new(block.hash(), root);
+            first = root + 1;
+        }
+        for height in first..=latest {
+            let block = db.get_block(height)?
+                .ok_or_else(|| format!("Block {} missing from storage", height))?;
+            fork_choice.add_block(block.hash(), block.header.previous_hash, height)?;
+        }
+        Ok(fork_choice)
+    }
+
+    pub fn with_max_block_transactions(mut self, max: usize) -> Self {
+        self.production_cap.get_or_insert(BlockLimits::UNLIMITED).max_transactions = max;
+        self
+    }
+
+    pub fn with_max_block_bytes(mut self, max: usize) -> Self {
+        self.production_cap.get_or_insert(BlockLimits::UNLIMITED).max_bytes = max;
+        self
+    }
+
+    /// Chain parameters in force for the block at `height`
+    pub fn params_at(&self, height: u64) -> &ChainParams {
+        self.params.at(height)
+    }
+
+    pub fn param_schedule(&self) -> &ParamSchedule {
+        &self.params
+    }
+
+    /// `params_at` with the flags of the features active by `height` set
+    pub fn active_params(&self, height: u64) -> Result<ChainParams, String> {
+        let mut params = *self.params.at(height);
+        for deployment in &self.params.deployments {
+            if self.deployment_state(deployment, height)? == DeploymentState::Active {
+                params.features |= deployment.mask();
+            }
+        }
+        Ok(params)
+    }
+
+    /// Where every deployment stands for the block at `height`
+    pub fn deployment_status(&self, height: u64) -> Result<Vec<DeploymentStatus>, String> {
+        let (window, threshold) = (self.params.signaling_window, self.params.lock_in_threshold);
+        let current = self.signal_counts(height / window, height)?;
+        self.params
+            .deployments
+            .iter()
+            .map(|deployment| {
+                Ok(DeploymentStatus {
+                    deployment: deployment.clone(),
+                    state: self.deployment_state(deployment, height)?,
+                    signaled: current[deployment.bit as usize],
+                    threshold,
+                    window,
+                })
+            })
+            .collect()
+    }
+
+    fn deployment_state(&self, deployment: &Deployment, height: u64) -> Result<DeploymentState, String> {
+        self.params.deployment_state(deployment, height, |index| Ok(self.signal_counts(index, u64::MAX)?[deployment.bit as usize]))
+    }
+
+    /// Signals of a block proposed at `height`: every deployment still being voted on
+    fn signals_at(&self, height: u64) -> Result<u32, String> {
+        let mut signals = 0;
+        for deployment in &self.params.deployments {
+            if self.deployment_state(deployment, height)? == DeploymentState::Started {
+                signals |= deployment.mask();
+            }
+        }
+        Ok(signals)
+    }
+
+    /// Canonical blocks of signaling window `index` below `end` setting each signal bit
+    fn signal_counts(&self, index: u64, end: u64) -> Result<[u64; SIGNAL_BITS as usize], String> {
+        if let Some(counts) = self.window_signals.lock().unwrap().get(&index) {
+            return Ok(*counts);
+        }
+        let window = self.params.signaling_window;
+        let (start, end) = (index * window, end.min((index + 1) * window));
+        let mut counts = [0; SIGNAL_BITS as usize];
+        for height in start..end {
+            let block = self.db.get_block(height)?
+                .ok_or_else(|| format!("Block {} of signaling window {} is missing", height, index))?;
+            for (bit, count) in counts.iter_mut().enumerate() {
+                *count += u64::from(block.header.signals >> bit & 1);
+            }
+        }
+        // Only a finalized window can no longer change
+        if end == (index + 1) * window && end <= self.head.load().fork_choice.finalized_height {
+            self.window_signals.lock().unwrap().insert(index, counts);
+        }
+        Ok(counts)
+    }
+
+    /// Uses `identity` as the node id peers know it by
+    pub fn with_identity(mut self, identity: QuantumKeyPair) -> Self {
+        self.identity = Arc::new(identity);
+        self
+    }
+
+    /// Proposes blocks with `key`, which has proposer slots if it is a genesis validator
+    pub fn with_validator_key(self, key: QuantumKeyPair) -> Self {
+        self.validator_key.store(Some(Arc::new(key)));
+        self
+    }
+
+    /// Signs votes with `key` at heights the validator key has it authorized for
+    pub fn with_session_key(self, key: QuantumKeyPair) -> Self {
+        self.session_key.store(Some(Arc::new(key)));
+        self
+    }
+
+    /// Loads the node and validator keys configured under `[keys]`
+    pub fn with_keys(self, config: &KeysConfig) -> Result<Self, String> {
+        let (identity, validator_key) = config.load()?;
+        let node = match identity {
+            Some(identity) => self.with_identity(identity),
+            None => self,
+        };
+        let Some(key) = validator_key else {
+            return Ok(node);
+        };
+        if !node.validators.iter().any(|validator| validator.as_slice() == key.public_key()) {
+            log!(Warn, "Validator key {} is not in the genesis validator set", short_hex(key.public_key()));
+        }
+        Ok(node.with_validator_key(key))
+    }
+
+    /// Replaces the validator key while the node runs, returning the previous one. The
+    /// node id peers know it by is unaffected
+    pub fn rotate_validator_key(&self, key: QuantumKeyPair) -> Option<Arc<QuantumKeyPair>> {
+        self.validator_key.swap(Some(Arc::new(key)))
+    }
+
+    /// Public validator key, `None` on a node without one
+    pub fn validator_id(&self) -> Option<Vec<u8>> {
+        self.validator_key.load().as_ref().map(|key| key.public_key().to_vec())
+    }
+
+    /// Generates a session key and submits the validator key's authorization of it for
+    /// `epochs` epochs, paying `fee`. Votes are signed with the new key once the
+    /// authorization is included; until then the previous key or the validator key signs
+    pub fn authorize_session_key(&self, epochs: u64, fee: u64) -> Result<([u8; 32], Vec<u8>), String> {
+        let validator = self.validator_key.load_full().ok_or("Node has no validator key")?;
+        let session = QuantumKeyPair::generate();
+        let call = SessionCall::Authorize { key: session.public_key().to_vec(), epochs };
+        let from = validator.public_key().to_vec();
+        self.chain.lock().unwrap().state.sessions().check(&from, 0, &call)?;
+        let nonce = self.next_nonce(&from);
+        let mut tx = Transaction::new(from, SESSION_KEYS.to_vec(), 0, fee, nonce, call.encode(), QuantumSignature::new(vec![]));
+        tx.signature = validator.sign(&tx.get_signing_data()).map_err(|e| e.to_string())?;
+        let hash = self.submit_transaction(tx)?;
+        let key = session.public_key().to_vec();
+        self.session_key.store(Some(Arc::new(session)));
+        Ok((hash, key))
+    }
+
+    /// Public session key, `None` if none was set
+    pub fn session_key_id(&self) -> Option<Vec<u8>> {
+        self.session_key.load().as_ref().map(|key| key.public_key().to_vec())
+    }
+
+    /// Signs a vote with the session key if the chain has it authorized at `height`,
+    /// otherwise with the validator key
+    pub fn sign_vote(&self, height: u64, round: u32, block_hash: [u8; 32]) -> Result<Vote, String> {
+        let validator = self.validator_key.load_full().ok_or("Node has no validator key")?;
+        if let Some(session) = self.session_key.load_full() {
+            let authorized = self.chain.lock().unwrap().state.sessions().active_at(validator.public_key(), height) == Some(session.public_key());
+            if authorized {
+                return Vote::sign_with_session(&session, validator.public_key().to_vec(), height, round, block_hash);
+            }
+        }
+        Vote::sign(&validator, height, round, block_hash)
+    }
+
+    pub fn is_validator(&self) -> bool {
+        self.validator_id().is_some_and(|id| self.validators.contains(&id))
+    }
+
+    pub fn is_genesis_validator(&self, key: &[u8]) -> bool {
+        self.validators.iter().any(|validator| validator.as_slice() == key)
+    }
+
+    /// Key blocks are proposed with: the validator key, or the node's own on a chain
+    /// without a validator set
+    fn block_signer(&self) -> Arc<QuantumKeyPair> {
+        self.validator_key.load_full().unwrap_or_else(|| self.identity.clone())
+    }
+
+    /// Stops or restarts `produce_block`, returning whether production was paused before
+    pub fn set_production_paused(&self, paused: bool) -> bool {
+        self.paused.swap(paused, Ordering::Relaxed)
+    }
+
+    pub fn is_production_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Publishes this node's events on `events`, shared with the other subsystems
+    pub fn with_event_bus(mut self, events: EventBus) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Number of recent state roots kept when pruning
+    pub fn with_retained_roots(mut self, keep: u64) -> Self {
+        self.retained_roots = Some(keep.max(1));
+        self
+    }
+
+    /// Keeps the state root of every height so historical state can be queried.
+    /// Call `backfill_state_roots` to index blocks stored before archive mode was enabled
+    pub fn with_archive_mode(mut self) -> Self {
+        self.retained_roots = None;
+        self
+    }
+
+    /// Moves finalized blocks more than `keep` below the finalized block into the
+    /// append-only ancient store when `freeze_ancient_blocks` runs
+    pub fn with_ancient_store(mut self, keep: u64) -> Self {
+        self.ancient_after = Some(keep);
+        self
+    }
+
+    /// Records block import timings and security events into `metrics`, shared with the
+    /// other subsystems
+    pub fn with_metrics(mut self, metrics: Arc<Mutex<MetricsCollector>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Also writes every block refused on import into `dir` for postmortems
+    pub fn with_bad_block_quarantine(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.quarantine = Some(Quarantine::new(dir));
+        self
+    }
+
+    /// Stores an execution witness with every block this node produces, so validators
+    /// without state can check it with `BlockWitness::verify`
+    pub fn with_block_witnesses(mut self) -> Self {
+        self.witnesses = true;
+        self
+    }
+
+    /// Produced blocks with bodies of at least `bytes` commit to their erasure-coded
+    /// chunks, so light validators can sample them instead of downloading the body
+    pub fn with_availability_threshold(mut self, bytes: usize) -> Self {
+        self.availability_threshold = bytes;
+        self
+    }
+
+    /// Execution witness stored for the block with hash `hash`
+    pub fn block_witness(&self, hash: &[u8; 32]) -> Result<Option<BlockWitness>, String> {
+        BlockWitness::load(&self.db, hash)
+    }
+
+    /// Pins the consensus path from startup as configured under `[consensus.pin]`
+    pub fn with_path_pin(self, config: &PinConfig) -> Result<Self, String> {
+        self.pin_consensus_path(config.mode, Duration::from_secs(config.duration_secs), config.reason.clone())?;
+        Ok(self)
+    }
+
+    /// Routes with the ONNX path model at `path` ahead of the rules
+    #[cfg(feature = "ml")]
+    pub fn with_path_model(self, path: &str) -> Result<Self, String> {
+        let model = crate::consensus::router::ml::PathModel::load(std::path::Path::new(path))?;
+        let mut router = self.router.lock().unwrap();
+        *router = std::mem::take(&mut *router).with_learned_model(model);
+        if self.pin.lock().unwrap().is_none() {
+            let path = router.select_optimal_path();
+            self.switch_path(&router, path, None);
+        }
+        drop(router);
+        Ok(self)
+    }
+
+    pub fn bad_blocks(&self) -> &BadBlocks {
+        &self.bad_blocks
+    }
+
+    pub fn quarantine(&self) -> Option<&Quarantine> {
+        self.quarantine.as_ref()
+    }
+
+    /// Time spent in each block import stage over the recent imports
+    pub fn import_metrics(&self) -> Vec<ImportStageStats> {
+        self.metrics.lock().unwrap().import_stage_stats()
+    }
+
+    /// Blocks and transactions turned away as already seen
+    pub fn duplicate_metrics(&self) -> DuplicateMetrics {
+        self.seen.metrics()
+    }
+
+    /// Block import signature checks answered by the verified transaction cache
+    pub fn verified_cache_stats(&self) -> CacheStats {
+        self.verified.stats()
+    }
+
+    pub fn orphan_stats(&self) -> OrphanStats {
+        self.orphans.lock().unwrap().stats()
+    }
+
+    /// Ancestors to ask peers for so pooled orphans can connect
+    pub fn orphan_sync_requests(&self) -> Vec<SyncRequest> {
+        self.orphans.lock().unwrap().sync_requests()
+    }
+
+    /// Syncs from `checkpoint` while the chain is empty and refuses blocks conflicting
+    /// with it. Fails if it was taken under another validator set or the stored chain
+    /// already conflicts with it
+    pub fn with_checkpoint(mut self, checkpoint: Checkpoint) -> Result<Self, String> {
+        checkpoint.check_validators(&self.validators)?;
+        if let Some(block) = self.db.get_block(checkpoint.height)? {
+            checkpoint.check_block(checkpoint.height, &block.hash())?;
+        }
+        self.checkpoint = Some(checkpoint);
+        Ok(self)
+    }
+
+    pub fn checkpoint(&self) -> Option<&Checkpoint> {
+        self.checkpoint.as_ref()
+    }
+
+    /// Whether the node is still waiting for a peer's snapshot of its checkpoint
+    pub fn awaiting_checkpoint(&self) -> bool {
+        self.checkpoint.is_some() && self.head.load().next_height == 0
+    }
+
+    /// Starts the empty chain from a peer's snapshot of the checkpoint block. Returns
+    /// false when the node is not waiting for one
+    pub fn install_snapshot(&self, snapshot: &StateSnapshot) -> Result<bool, String> {
+        let Some(checkpoint) = &self.checkpoint else {
+            return Ok(false);
+        };
+        let mut chain = self.chain.lock().unwrap();
+        if chain.head != GENESIS_PARENT {
+            return Ok(false);
+        }
+        let state = checkpoint.verify_snapshot(snapshot)?;
+        self.db.put_meta(STATE_BASE_META, snapshot)?;
+        self.db.store_block_by_hash(&snapshot.block)?;
+        self.db.store_block(&snapshot.block)?;
+        self.state_store.commit(checkpoint.height, &state)?;
+        chain.fork_choice = ForkChoice::new(checkpoint.block_hash, checkpoint.height);
+        chain.state = state;
+        chain.head = checkpoint.block_hash;
+        // Proposers before the checkpoint are unknown, so duties start at the next epoch
+        *self.duties.lock().unwrap() = DutyTracker::new(&self.validators, DutyTracker::epoch_of(checkpoint.height) + 1);
+        self.finalize(&mut chain)?;
+        drop(chain);
+        self.promote_orphans(checkpoint.block_hash);
+        Ok(true)
+    }
+
+    /// Builds and stores the state snapshot after the canonical block at `height`, by
+    /// default the finalized one, so peers syncing from it are served without a replay
+    pub fn create_snapshot(&self, height: Option<u64>) -> Result<StateSnapshot, String> {
+        let height = match height {
+            Some(height) => height,
+            None => {
+                let head = self.fork_choice_head();
+                if head.finalized == GENESIS_PARENT {
+                    return Err("No block has been finalized yet".to_string());
+                }
+                head.finalized_height
+            }
+        };
+        let snapshot = StateManager::snapshot_at(&self.db, height)?
+            .ok_or_else(|| format!("Block {} is not stored", height))?;
+        StateManager::store_snapshot(&self.db, &snapshot)?;
+        Ok(snapshot)
+    }
+
+    /// Backs the database up into `out` while holding off block imports, so the backup
+    /// is one state of the chain even with the node running
+    pub fn backup(&self, out: &Path, progress: impl FnMut(BackupStep)) -> Result<BackupManifest, String> {
+        let _chain = self.chain.lock().unwrap();
+        backup::create(&self.db, out, progress)
+    }
+
+    pub fn is_archive(&self) -> bool {
+        self.retained_roots.is_none()
+    }
+
+    /// The node's database for async callers, each call run on the blocking pool
+    pub fn storage(&self) -> TriUnityStorage {
+        TriUnityStorage::from_db(self.db.clone())
+    }
+
+    pub fn db(&self) -> &BlockchainDB {
+        &self.db
+    }
+
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    pub fn node_id(&self) -> Vec<u8> {
+        self.identity.public_key().to_vec()
+    }
+
+    pub fn genesis_hash(&self) -> [u8; 32] {
+        self.genesis_hash
+    }
+
+    pub fn next_height(&self) -> Result<u64, String> {
+        Ok(self.head.load().next_height)
+    }
+
+    pub fn fork_choice_head(&self) -> ForkChoiceHead {
+        self.head.load().fork_choice.clone()
+    }
+
+    /// Runs `read` on the state at the head of the chain
+    pub fn with_head_state<T>(&self, read: impl FnOnce(&StateManager) -> T) -> T {
+        read(&self.chain.lock().unwrap().state)
+    }
+
+    fn height_after(fork_choice: &ForkChoice, parent: &[u8; 32]) -> u64 {
+        if *parent == GENESIS_PARENT {
+            0
+        } else {
+            fork_choice.height(parent).unwrap_or_default() + 1
+        }
+    }
+
+    /// Fast-lane committee of `epoch`, drawn from the genesis validators by stake. Its
+    /// seed comes from the canonical block ending the previous epoch, which is only read
+    /// when the committee is smaller than the validator set
+    pub fn fast_lane_committee(&self, epoch: u64) -> Result<Arc<FastLaneCommittee>, String> {
+        let sampled = committee::committee_size(self.validators.len()) < self.validators.len();
+        let boundary = match committee::seed_height(epoch).filter(|_| sampled) {
+            Some(height) => Some(self.db.get_block(height)?
+                .ok_or_else(|| format!("Block {} seeding epoch {} is missing", height, epoch))?
+                .hash()),
+            None => None,
+        };
+        let mut committees = self.committees.lock().unwrap();
+        if let Some((_, _, committee)) = committees.iter().find(|(cached, seed, _)| *cached == epoch && *seed == boundary) {
+            return Ok(committee.clone());
+        }
+        let seed = committee::epoch_seed(epoch, boundary);
+        let committee = Arc::new(FastLaneCommittee::sample(epoch, seed, &self.validators, &self.stakes));
+        committees.push_back((epoch, boundary, committee.clone()));
+        if committees.len() > COMMITTEE_CACHE {
+            committees.pop_front();
+        }
+        Ok(committee)
+    }
+
+    /// Fast-lane committee member scheduled to propose at `height`, `None` without a
+    /// validator set
+    pub fn proposer_for(&self, height: u64) -> Result<Option<Vec<u8>>, String> {
+        if self.validators.is_empty() {
+            return Ok(None);
+        }
+        let committee = self.fast_lane_committee(DutyTracker::epoch_of(height))?;
+        Ok(committee.proposer_index(height).map(|index| self.validators[index].clone()))
+    }
+
+    pub fn is_proposer(&self, height: u64) -> bool {
+        match self.proposer_for(height) {
+            Ok(proposer) => proposer.is_none_or(|proposer| Some(proposer) == self.validator_id()),
+            Err(e) => {
+                log!(Warn, "No proposer schedule for height {}: {}", height, e);
+                false
+            }
+        }
+    }
+
+    /// Id of the validator set of `epoch`, recording it the first time the epoch is seen
+    fn validator_set_id(&self, epoch: u64) -> Result<[u8; 32], String> {
+        self.validator_sets.record(epoch, &self.validators)
+    }
+
+    /// A block's consensus data with validators named by reference resolved to their keys
+    fn resolve_consensus(&self, block: &Block) -> Result<ConsensusData, String> {
+        let data = &block.header.consensus_data;
+        if !data.is_referenced() {
+            return Ok(data.clone());
+        }
+        let height = block.header.height;
+        if block.header.version < VALIDATOR_REF_BLOCK_VERSION {
+            return Err(format!("Block {} names validators by reference before version {}", height, VALIDATOR_REF_BLOCK_VERSION));
+        }
+        if self.validators.is_empty() {
+            return Err(format!("Block {} names validators by reference without a validator set", height));
+        }
+        let epoch = DutyTracker::epoch_of(height);
+        self.validator_set_id(epoch)?;
+        self.validator_sets.resolve(epoch, data).map_err(|e| format!("Block {}: {}", height, e))
+    }
+
+    fn proposer_of(&self, block: &Block) -> Option<Vec<u8>> {
+        match self.resolve_consensus(block).ok()? {
+            ConsensusData::FastLane { validator, .. } => Some(validator),
+            _ => None,
+        }
+    }
+
+    /// Why a block fails the fast-lane schedule of `committee`: a proposer other than the
+    /// scheduled one, or from `COMMITTEE_BLOCK_VERSION` on, no valid membership proof
+    fn committee_fault(&self, block: &Block, committee: &FastLaneCommittee) -> Option<String> {
+        let height = block.header.height;
+        let expected = &self.validators[committee.proposer_index(height)?];
+        let consensus_data = match self.resolve_consensus(block) {
+            Ok(consensus_data) => consensus_data,
+            Err(e) => return Some(e),
+        };
+        let ConsensusData::FastLane { validator, committee: proof } = &consensus_data else {
+            return Some(format!("Block {} was not produced by the scheduled proposer", height));
+        };
+        if validator != expected {
+            return Some(format!("Block {} was not produced by the scheduled proposer", height));
+        }
+        if block.header.version < COMMITTEE_BLOCK_VERSION {
+            return None;
+        }
+        match proof {
+            Some(proof) if proof.epoch == committee.epoch && proof.verify(validator, committee.root()) => None,
+            _ => Some(format!("Block {} lacks a valid fast-lane committee proof", height)),
+        }
+    }
+
+    /// Counts a proposal slot that passed without the scheduled block for `height`
+    pub fn record_missed_slot(&self, height: u64) -> Result<(), String> {
+        let committee = self.fast_lane_committee(DutyTracker::epoch_of(height))?;
+        let mut duties = self.duties.lock().unwrap();
+        duties.record_missed_slot(height, &committee);
+        self.db.put_meta(DUTIES_META, &*duties)
+    }
+
+    /// Duties of every genesis validator in `epoch`, by default the current one
+    pub fn validator_performance(&self, epoch: Option<u64>) -> Result<Vec<ValidatorPerformance>, String> {
+        let next_height = self.next_height()?;
+        let epoch = epoch.unwrap_or(DutyTracker::epoch_of(next_height));
+        let proposers = self.epoch_proposers(epoch, next_height)?;
+        let committee = self.fast_lane_committee(epoch)?;
+        Ok(self.duties.lock().unwrap().performance(epoch, &committee, &proposers))
+    }
+
+    /// Genesis validators with their balance, reputation, uptime and latest proposal
+    /// within the last `LAST_PROPOSAL_DEPTH` blocks
+    pub fn validator_statuses(&self) -> Result<Vec<ValidatorStatus>, String> {
+        let next_height = self.next_height()?;
+        let epoch = DutyTracker::epoch_of(next_height);
+        let current = self.validator_performance(Some(epoch))?;
+        let previous = match epoch {
+            0 => Vec::new(),
+            _ => self.validator_performance(Some(epoch - 1))?,
+        };
+        let mut last_proposed: HashMap<Vec<u8>, u64> = HashMap::new();
+        for height in (next_height.saturating_sub(LAST_PROPOSAL_DEPTH)..next_height).rev() {
+            if last_proposed.len() == self.validators.len() {
+                break;
+            }
+            if let Some(proposer) = self.db.get_block(height)?.as_ref().and_then(|block| self.proposer_of(block)) {
+                last_proposed.entry(proposer).or_insert(height);
+            }
+        }
+        let validator_id = self.validator_id();
+        let chain = self.chain.lock().unwrap();
+        Ok(current
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let (expected, met) = previous
+                    .get(index)
+                    .map_or((0, 0), |earlier| (earlier.expected_proposals, earlier.proposals));
+                let (expected, met) = (expected + entry.expected_proposals, met + entry.proposals);
+                ValidatorStatus {
+                    balance: chain.state.get_account(&entry.address).map_or(0, |account| account.balance),
+                    reputation: entry.reputation,
+                    uptime: if expected == 0 { 1.0 } else { met as f64 / expected as f64 },
+                    last_proposed_height: last_proposed.get(&entry.address).copied(),
+                    local: validator_id.as_ref() == Some(&entry.address),
+                    address: entry.address,
+                }
+            })
+            .collect())
+    }
+
+    /// Proposer of each canonical block in `epoch` below `next_height`
+    fn epoch_proposers(&self, epoch: u64, next_height: u64) -> Result<Vec<Option<Vec<u8>>>, String> {
+        let first = epoch * EPOCH_LENGTH;
+        (first..next_height.min(first + EPOCH_LENGTH))
+            .map(|height| Ok(self.db.get_block(height)?.as_ref().and_then(|block| self.proposer_of(block))))
+            .collect()
+    }
+
+    pub fn submit_transaction(&self, tx: Transaction) -> Result<[u8; 32], String> {
+        let hash = tx.hash();
+        if self.seen.is_duplicate(SeenKind::Transaction, &hash) {
+            return Err("Transaction already seen".to_string());
+        }
+        if let Err(e) = self.verified.check(&tx, Subsystem::MempoolAdmission) {
+            // Anyone can submit these, so each weighs little
+            let reason = format!("Refused transaction from {}: {}", short_address(&tx.from), e);
+            self.report_security_event(SecurityEventType::InvalidSignature, SecuritySeverity::Low, "mempool".to_string(), reason);
+            return Err(e);
+        }
+        let chain = self.chain.lock().unwrap();
+        let next_height = Self::height_after(&chain.fork_choice, &chain.head);
+        let mut mempool = self.mempool.lock().unwrap();
+        mempool.set_params(self.params.at(next_height));
+        let (fee, size, lane) = (tx.fee, tx.size(), mempool.lane(&tx));
+        mempool.insert(tx, &chain.state, next_height)?;
+        self.seen.mark(SeenKind::Transaction, hash);
+        let _ = self.mempool_updates.send(MempoolUpdate::Admitted { hash, fee, size, lane });
+        self.events.publish(NodeEvent::TxAccepted { hash });
+        Ok(hash)
+    }
+
+    /// Nonce the next transaction from `address` should carry, after its pending ones
+    pub fn next_nonce(&self, address: &[u8]) -> u64 {
+        let confirmed = self.chain.lock().unwrap().state.get_account(address).map_or(0, |account| account.nonce);
+        let pending = self.mempool.lock().unwrap().summaries(Some(address), usize::MAX);
+        pending.iter().map(|pending| pending.nonce + 1).fold(confirmed, u64::max)
+    }
+
+    /// Account at the head of the chain
+    pub fn account(&self, address: &[u8]) -> Option<Account> {
+        self.chain.lock().unwrap().state.get_account(address).cloned()
+    }
+
+    /// Stream of admissions into and evictions from the mempool
+    pub fn subscribe_mempool(&self) -> broadcast::Receiver<MempoolUpdate> {
+        self.mempool_updates.subscribe()
+    }
+
+    pub fn mempool_summary(&self) -> MempoolSummary {
+        self.mempool.lock().unwrap().summary()
+    }
+
+    /// Stream of transactions evicted by mempool garbage collection
+    pub fn subscribe_dropped(&self) -> broadcast::Receiver<DroppedTransaction> {
+        self.dropped.subscribe()
+    }
+
+    /// Evicts expired and unconfirmable mempool entries, announcing each drop
+    pub fn collect_mempool_garbage(&self) -> Vec<DroppedTransaction> {
+        let chain = self.chain.lock().unwrap();
+        let next_height = Self::height_after(&chain.fork_choice, &chain.head);
+        let dropped = self.mempool.lock().unwrap()
+            .collect_garbage(&chain.state, next_height, DEFAULT_MAX_PENDING_AGE);
+        let hashes: Vec<[u8; 32]> = dropped.iter().map(|drop| drop.hash).collect();
+        self.verified.forget_hashes(&hashes);
+        for drop in &dropped {
+            self.seen.forget(SeenKind::Transaction, &drop.hash);
+            let _ = self.mempool_updates.send(drop.into());
+            let _ = self.dropped.send(drop.clone());
+        }
+        dropped
+    }
+
+    pub fn pending_transactions(&self) -> usize {
+        self.mempool.lock().unwrap().len()
+    }
+
+    /// Up to `limit` pending transactions in arrival order, only `sender`'s when given
+    pub fn pending_summaries(&self, sender: Option<&[u8]>, limit: usize) -> Vec<PendingSummary> {
+        self.mempool.lock().unwrap().summaries(sender, limit)
+    }
+
+    /// Drops a pending transaction on an operator's request, announcing it like a
+    /// garbage-collected one. Returns whether it was pending
+    pub fn remove_pending(&self, hash: &[u8; 32]) -> bool {
+        let removed = self.mempool.lock().unwrap().remove(hash);
+        if removed {
+            self.seen.forget(SeenKind::Transaction, hash);
+            let drop = DroppedTransaction { hash: *hash, reason: DropReason::Removed };
+            let _ = self.mempool_updates.send((&drop).into());
+            let _ = self.dropped.send(drop);
+        }
+        removed
+    }
+
+    /// Status of a transaction this node knows of, `None` otherwise
+    pub fn transaction_status(&self, hash: &[u8; 32]) -> Result<Option<TxStatus>, String> {
+        if let Some((_, height, _)) = self.db.get_transaction(hash)? {
+            let block = self.db.get_block(height)?
+                .ok_or_else(|| format!("Block {} missing from storage", height))?
+                .hash();
+            let head = self.fork_choice_head();
+            let finalized = head.finalized != GENESIS_PARENT && height <= head.finalized_height;
+            return Ok(Some(if finalized {
+                TxStatus::Finalized { height, block }
+            } else {
+                TxStatus::Included { height, block }
+            }));
+        }
+        if self.mempool.lock().unwrap().contains(hash) {
+            return Ok(Some(TxStatus::Pending));
+        }
+        Ok(self.orphaned.lock().unwrap().contains(hash).then_some(TxStatus::Orphaned))
+    }
+
+    /// Path this node's router or pin picks, which it proposes to the network
+    pub fn consensus_path(&self) -> ConsensusPath {
+        self.consensus_path.lock().unwrap().clone()
+    }
+
+    /// Path the chain agreed on for the head's next block
+    pub fn active_path(&self) -> AgreedPath {
+        self.agreed_path.lock().unwrap().clone()
+    }
+
+    /// Path the chain agreed on for the block at `height`: the one at least
+    /// `PATH_SWITCH_THRESHOLD` canonical blocks of the previous window proposed, otherwise
+    /// the path of that window
+    pub fn agreed_path(&self, height: u64) -> Result<AgreedPath, String> {
+        // Past the window after the head's there are no proposals left to count
+        let target = (height / PATH_WINDOW).min(self.head.load().next_height / PATH_WINDOW + 1);
+        let history = self.path_history.lock().unwrap();
+        let (mut window, mut agreed) = (history.through.min(target), history.at(target));
+        drop(history);
+        while window < target {
+            window += 1;
+            agreed = agreed.next(&self.path_tally(window - 1)?, window * PATH_WINDOW);
+        }
+        Ok(agreed)
+    }
+
+    /// Path proposals of the canonical blocks of path window `index`
+    fn path_tally(&self, index: u64) -> Result<WindowTally, String> {
+        let mut tally = WindowTally::default();
+        for height in index * PATH_WINDOW..(index + 1) * PATH_WINDOW {
+            // A checkpoint-synced chain holds no blocks below its base to count
+            if let Some(proposal) = self.db.get_block(height)?.and_then(|block| block.header.path_proposal) {
+                tally.add(&proposal);
+            }
+        }
+        Ok(tally)
+    }
+
+    /// Records the agreed path of every window the canonical chain has completed the
+    /// window before of, and switches to the one for the block at `next_height`
+    fn update_agreed_path(&self, next_height: u64) -> Result<(), String> {
+        let mut history = self.path_history.lock().unwrap();
+        let window = next_height / PATH_WINDOW;
+        if history.through < window {
+            while history.through < window {
+                let index = history.through;
+                let agreed = history.at(index).next(&self.path_tally(index)?, (index + 1) * PATH_WINDOW);
+                history.push(agreed);
+            }
+            self.db.put_meta(PATH_HISTORY_META, &*history)?;
+        }
+        let agreed = history.at(window);
+        drop(history);
+
+        let previous = std::mem::replace(&mut *self.agreed_path.lock().unwrap(), agreed.clone());
+        if previous.path.index() != agreed.path.index() {
+            log!(Info, "Consensus path switched to {} from height {}, proposed by {} blocks", agreed.path.name(), agreed.activation_height, agreed.proposals);
+            self.events.publish(NodeEvent::PathSwitched { from: previous.path, to: agreed.path });
+        }
+        Ok(())
+    }
+
+    /// Switches to `path` regardless of what the router would pick; recorded as an
+    /// override of its choice
+    pub fn set_consensus_path(&self, path: ConsensusPath) {
+        let router = self.router.lock().unwrap();
+        self.switch_path(&router, path, Some("Set directly".to_string()));
+    }
+
+    fn switch_path(&self, router: &ConsensusRouter, path: ConsensusPath, override_reason: Option<String>) {
+        if let Err(e) = self.decisions.record(router.network_status(), &path, router.ai_confidence(), override_reason) {
+            log!(Warn, "Failed to record consensus decision: {}", e);
+        }
+        let previous = std::mem::replace(&mut *self.consensus_path.lock().unwrap(), path.clone());
+        if std::mem::discriminant(&previous) != std::mem::discriminant(&path) {
+            self.events.publish(NodeEvent::PathProposed { from: previous, to: path });
+        }
+    }
+
+    /// The router's current weights, as adapted to recent conditions
+    pub fn model_state(&self) -> ModelState {
+        self.router.lock().unwrap().model_state()
+    }
+
+    pub fn decisions(&self) -> &DecisionLog {
+        &self.decisions
+    }
+
+    /// Records detected misbehaviour into the security score, publishes it and lets the
+    /// router move block production to a safer path as the score drops
+    pub fn report_security_event(&self, kind: SecurityEventType, severity: SecuritySeverity, source: String, reason: String) {
+        self.metrics.lock().unwrap().record_security_event(kind, severity, format!("{}: {}", source, reason));
+        self.events.publish(NodeEvent::SecurityEvent { source, reason, kind, severity });
+        self.refresh_consensus_path();
+    }
+
+    pub fn security_score(&self) -> f64 {
+        self.metrics.lock().unwrap().security_score()
+    }
+
+    /// Collects a validator's vote for the BFT commit. Refused votes and equivocations
+    /// are reported as security events; votes at or below the finalized height are ignored
+    pub fn add_vote(&self, vote: Vote) -> Result<VoteOutcome, String> {
+        let source = format!("validator {}", short_address(&vote.validator));
+        let (height, block_hash) = (vote.height, vote.block_hash);
+        let sessions = self.chain.lock().unwrap().state.sessions().clone();
+        let outcome = {
+            let mut votes = self.votes.lock().unwrap();
+            votes.prune(self.head.load().fork_choice.finalized_height + 1);
+            votes.set_sessions(sessions);
+            votes.add(vote)
+        };
+        match &outcome {
+            Err(e) => self.report_security_event(SecurityEventType::SuspiciousActivity, SecuritySeverity::Low, source, format!("Refused vote: {}", e)),
+            Ok(VoteOutcome::Equivocation(_)) => {
+                let reason = format!("Voted for two blocks at height {}", height);
+                self.report_security_event(SecurityEventType::ValidatorMisbehavior, SecuritySeverity::High, source, reason);
+            }
+            Ok(VoteOutcome::Committed(certificate)) => {
+                log!(Info, "Block {} at height {} committed by {} votes", short_hex(&block_hash), height, certificate.votes.len());
+            }
+            Ok(_) => {}
+        }
+        outcome
+    }
+
+    pub fn commit_certificate(&self, height: u64) -> Option<CommitCertificate> {
+        self.votes.lock().unwrap().certificate(height).cloned()
+    }
+
+    /// Equivocations detected since the last call, for slashing
+    pub fn take_vote_evidence(&self) -> Vec<Equivocation> {
+        self.votes.lock().unwrap().take_evidence()
+    }
+
+    /// Holds the consensus path at `mode` for `duration` whatever the router picks.
+    /// Replaces any earlier pin
+    pub fn pin_consensus_path(&self, mode: PinnedMode, duration: Duration, reason: String) -> Result<PathPin, String> {
+        if duration.is_zero() {
+            return Err("Pin duration must be positive".to_string());
+        }
+        let pin = PathPin { mode, reason, expires_at: current_timestamp().saturating_add(duration.as_secs()) };
+        let router = self.router.lock().unwrap();
+        *self.pin.lock().unwrap() = Some(pin.clone());
+        log!(Warn, "Consensus path pinned to {:?} for {}s: {}", mode, duration.as_secs(), pin.reason);
+        self.switch_path(&router, mode.path(router.network_status()), Some(format!("Pinned: {}", pin.reason)));
+        self.events.publish(NodeEvent::PathPinned { pin: Some(pin.clone()) });
+        Ok(pin)
+    }
+
+    /// Lifts the pin, if any, and returns to the router's choice
+    pub fn unpin_consensus_path(&self) -> Option<PathPin> {
+        let router = self.router.lock().unwrap();
+        let pin = self.pin.lock().unwrap().take()?;
+        log!(Info, "Consensus path unpinned");
+        self.switch_path(&router, router.select_optimal_path(), None);
+        self.events.publish(NodeEvent::PathPinned { pin: None });
+        Some(pin)
+    }
+
+    pub fn pinned_path(&self) -> Option<PathPin> {
+        self.pin.lock().unwrap().clone()
+    }
+
+    /// Feeds the current security score to the router and switches to the path it then
+    /// selects, unless the path is pinned; an expired pin is lifted here. Nothing changes
+    /// while the score stays the same, so a path set by hand holds until the next
+    /// security event or until old events age out
+    pub fn refresh_consensus_path(&self) {
+        let attack_probability = 1.0 - self.security_score();
+        let mut router = self.router.lock().unwrap();
+        let mut pin = self.pin.lock().unwrap();
+        let expired = pin.as_ref().is_some_and(|pin| pin.is_expired(current_timestamp()));
+        if expired {
+            *pin = None;
+            log!(Info, "Consensus path pin expired");
+            self.events.publish(NodeEvent::PathPinned { pin: None });
+        } else if router.network_status().attack_probability == attack_probability {
+            return;
+        }
+        let metrics = NetworkMetrics { attack_probability, ..router.network_status().clone() };
+        router.update_metrics(metrics);
+        match pin.clone() {
+            Some(pin) => {
+                let path = pin.mode.path(router.network_status());
+                self.switch_path(&router, path, Some(format!("Pinned: {}", pin.reason)));
+            }
+            None => {
+                let path = router.select_optimal_path();
+                self.switch_path(&router, path, None);
+            }
+        }
+    }
+
+    pub fn lane_metrics(&self) -> LaneMetrics {
+        self.mempool.lock().unwrap().lane_metrics()
+    }
+
+    pub fn class_metrics(&self) -> ClassMetrics {
+        self.mempool.lock().unwrap().class_metrics()
+    }
+
+    /// Suggested fee per fee class, highest class first
+    pub fn fee_estimates(&self) -> Vec<FeeEstimate> {
+        self.mempool.lock().unwrap().fee_estimates()
+    }
+
+    pub fn state_store(&self) -> &StateStore {
+        &self.state_store
+    }
+
+    pub fn state_store_metrics(&self) -> Result<StateStoreMetrics, String> {
+        self.state_store.metrics()
+    }
+
+    /// Deletes state roots beyond the retained window along with the trie nodes only they used
+    pub fn prune_state(&self) -> Result<PruneReport, String> {
+        let _chain = self.chain.lock().unwrap();
+        match self.retained_roots {
+            Some(keep) => self.state_store.prune(keep),
+            None => Ok(PruneReport::default()),
+        }
+    }
+
+    /// Moves old finalized blocks out of sled, returning how many moved
+    pub fn freeze_ancient_blocks(&self) -> Result<u64, String> {
+        let Some(keep) = self.ancient_after else {
+            return Ok(0);
+        };
+        let chain = self.chain.lock().unwrap();
+        let summary = chain.fork_choice.summary();
+        if summary.finalized == GENESIS_PARENT {
+            return Ok(0);
+        }
+        self.db.freeze_below(summary.finalized_height.saturating_sub(keep))
+    }
+
+    /// Replays the canonical chain once, storing the state root of every height that
+    /// has none. Returns the number of roots added
+    pub fn backfill_state_roots(&self) -> Result<u64, String> {
+        let _chain = self.chain.lock().unwrap();
+        if self.db.block_count()? == 0 {
+            return Ok(0);
+        }
+        let (mut state, first) = StateManager::base(&self.db)?;
+        let mut added = 0;
+        for height in first..=self.db.get_latest_height()? {
+            let block = self.db.get_block(height)?
+                .ok_or_else(|| format!("Block {} missing from storage", height))?;
+            state.apply_block(&block).map_err(|e| format!("Block {}: {}", height, e))?;
+            if self.state_store.root_at(height)?.is_none() {
+                self.state_store.commit(height, &state)?;
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// Seals pending transactions into a block on top of the fork-choice head;
+    /// ones that no longer apply are dropped
+    pub fn produce_block(&self) -> Result<Block, String> {
+        if self.is_production_paused() {
+            return Err("Block production is paused".to_string());
+        }
+        if self.awaiting_checkpoint() {
+            return Err("Waiting for the checkpoint state".to_string());
+        }
+        let (preferred, metrics) = {
+            let router = self.router.lock().unwrap();
+            (self.consensus_path(), router.network_status().clone())
+        };
+
+        let mut chain = self.chain.lock().unwrap();
+        let parent = chain.head;
+        let height = Self::height_after(&chain.fork_choice, &parent);
+        let params = self.active_params(height)?;
+        let signals = self.signals_at(height)?;
+        let agreed = self.agreed_path(height)?;
+        let quota = LaneQuota::for_path(&agreed.path);
+        let limits = self.production_cap.map_or(params.block_limits(), |cap| cap.within(params.block_limits()));
+        let mut builder = BlockBuilder::new(parent, height, &chain.state)?.with_limits(limits).with_lane_quota(quota);
+        let mut mempool = self.mempool.lock().unwrap();
+        mempool.set_params(&params);
+        builder.fill_from(&mut mempool);
+        drop(mempool);
+
+        // A validator names itself by its seat in the epoch's set rather than by its key
+        let proposer = self.block_signer().public_key().to_vec();
+        let epoch = DutyTracker::epoch_of(height);
+        let consensus_data = match self.validators.iter().position(|validator| *validator == proposer) {
+            Some(index) => ConsensusData::FastLaneRef {
+                validator: ValidatorRef { set: self.validator_set_id(epoch)?, index: index as u32 },
+                committee: self.fast_lane_committee(epoch)?.proof(index),
+            },
+            None => ConsensusData::FastLane { validator: proposer.clone(), committee: None },
+        };
+        let (block, _) = builder.seal(consensus_data);
+        let mut block = block.with_signals(signals);
+        if preferred.index() != agreed.path.index() {
+            let proposal = PathProposal::new(&preferred, &metrics);
+            match proposal.check() {
+                Ok(()) => block = block.with_path_proposal(proposal),
+                Err(e) => log!(Warn, "Not proposing {}: {}", preferred.name(), e),
+            }
+        }
+        if availability::body_size(&block.transactions) >= self.availability_threshold {
+            block = block.with_availability();
+        }
+        let mut next_state = chain.state.clone();
+        next_state.apply_block(&block)?;
+
+        let hash = block.hash();
+        if self.witnesses {
+            BlockWitness::generate(&chain.state, &block)?.store(&self.db, &hash)?;
+        }
+        self.seen.mark(SeenKind::Block, hash);
+        self.db.store_block_by_hash(&block)?;
+        self.db.store_block(&block)?;
+        self.state_store.commit(height, &next_state)?;
+        chain.fork_choice.add_block(hash, parent, height)?;
+        chain.fork_choice.add_vote(&proposer, hash, PROPOSER_VOTE_WEIGHT);
+        chain.state = next_state;
+        chain.head = hash;
+        self.finalize(&mut chain)?;
+        drop(chain);
+
+        self.events.publish(NodeEvent::BlockImported {
+            height,
+            hash,
+            transactions: block.transaction_count(),
+            produced: true,
+        });
+        Ok(block)
+    }
+
+    /// `produce_block`, signed with the block's proposer key for gossip as a round 0 proposal
+    pub fn propose_block(&self) -> Result<BlockProposal, String> {
+        let block = self.produce_block()?;
+        BlockProposal::sign(&self.block_signer(), block, 0)
+    }
+
+    /// Imports a proposal relayed by `peer` once it is shown to come from the validator
+    /// scheduled for its height. A misattributed or forged proposal is refused and kept
+    /// as a bad block from `peer`
+    pub fn import_proposal(&self, proposal: &BlockProposal, peer: &[u8]) -> Result<BlockImport, String> {
+        let (block, height) = (&proposal.block, proposal.block.header.height);
+        let origin = Origin { hash: block.hash(), peer: Some(peer) };
+        if self.seen.is_duplicate(SeenKind::Block, &origin.hash) {
+            return Ok(BlockImport::Known);
+        }
+        // Without the block seeding its epoch the schedule is unknown yet, and the
+        // import asks for the missing blocks instead
+        let scheduled = self.proposer_for(height).ok().flatten();
+        let proposer = self.proposer_of(block);
+        if scheduled.is_some_and(|scheduled| proposer.as_ref() != Some(&scheduled)) {
+            let reason = format!("Proposal for block {} is not from the scheduled proposer", height);
+            return Err(self.reject(block, ImportStage::Header, reason, None, origin));
+        }
+        if !proposer.is_some_and(|proposer| proposal.verify_signature_by(&proposer)) {
+            let reason = format!("Proposal for block {} has an invalid proposer signature", height);
+            return Err(self.reject(block, ImportStage::Signature, reason, None, origin));
+        }
+        self.import(block, Some(peer))
+    }
+
+    /// Adds a block to the block tree and reorganises the canonical chain onto it if
+    /// it becomes the fork-choice head
+    pub fn import_block(&self, block: &Block) -> Result<BlockImport, String> {
+        self.import(block, None)
+    }
+
+    /// `import_block` for a block sent by `peer`, which a refusal is recorded against
+    pub fn import_block_from(&self, block: &Block, peer: &[u8]) -> Result<BlockImport, String> {
+        self.import(block, Some(peer))
+    }
+
+    fn import(&self, block: &Block, peer: Option<&[u8]>) -> Result<BlockImport, String> {
+        let hash = block.hash();
+        let mut times = StageTimes::default();
+        let result = self.run_import(block, Origin { hash, peer }, &mut times);
+        times.record(&mut self.metrics.lock().unwrap(), block.header.height);
+        if result == Ok(BlockImport::Imported) {
+            self.promote_orphans(hash);
+        }
+        result
+    }
+
+    /// Imports the pooled orphans descending from `parent`, now that it is in the block tree
+    fn promote_orphans(&self, parent: [u8; 32]) {
+        let mut parents = vec![parent];
+        while let Some(parent) = parents.pop() {
+            let children = self.orphans.lock().unwrap().take_children(&parent);
+            for orphan in children {
+                let (block, hash) = (&orphan.block, orphan.block.hash());
+                let mut times = StageTimes::default();
+                match self.run_import(block, Origin { hash, peer: orphan.peer.as_deref() }, &mut times) {
+                    Ok(BlockImport::Imported) => parents.push(hash),
+                    Ok(_) => {}
+                    Err(e) => log!(Warn, "Orphan block {} failed to import: {}", block.header.height, e),
+                }
+                times.record(&mut self.metrics.lock().unwrap(), block.header.height);
+            }
+        }
+    }
+
+    /// Decodes a block in its wire encoding and imports it
+    pub fn import_encoded_block(&self, bytes: &[u8]) -> Result<BlockImport, String> {
+        let mut times = StageTimes::default();
+        let block = match times.time(ImportStage::Decode, || envelope::decode_block(bytes)) {
+            Ok(block) => block,
+            Err(e) => {
+                let record = BadBlockRecord::new(None, Sha3_256::digest(bytes).into(), ImportStage::Decode, &e);
+                self.keep_bad_block(&record, bytes);
+                return Err(format!("Undecodable block: {}", e));
+            }
+        };
+        let hash = block.hash();
+        let result = self.run_import(&block, Origin { hash, peer: None }, &mut times);
+        times.record(&mut self.metrics.lock().unwrap(), block.header.height);
+        if result == Ok(BlockImport::Imported) {
+            self.promote_orphans(hash);
+        }
+        result
+    }
+
+    /// Checks the header against the block tree, then signatures without holding the
+    /// chain lock, then executes and commits the block if it becomes canonical
+    fn run_import(&self, block: &Block, origin: Origin, times: &mut StageTimes) -> Result<BlockImport, String> {
+        if !self.seen.mark(SeenKind::Block, origin.hash) {
+            return Ok(BlockImport::Known);
+        }
+        let result = self.run_import_stages(block, origin, times);
+        // Refused and unconnected blocks may be sent again once they can be imported
+        if !matches!(result, Ok(BlockImport::Imported | BlockImport::Known)) {
+            self.seen.forget(SeenKind::Block, &origin.hash);
+        }
+        if let Ok(BlockImport::Missing { expected }) = result {
+            let orphan = Orphan { block: block.clone(), peer: origin.peer.map(<[u8]>::to_vec), expected };
+            self.orphans.lock().unwrap().insert(orphan);
+        }
+        result
+    }
+
+    fn run_import_stages(&self, block: &Block, origin: Origin, times: &mut StageTimes) -> Result<BlockImport, String> {
+        if let Some(outcome) = times.time(ImportStage::Header, || self.check_header(block, origin))? {
+            return Ok(outcome);
+        }
+        if let Err((index, e)) = times.time(ImportStage::Signature, || import::verify_signatures(&block.transactions, &self.verified)) {
+            let reason = format!("Transaction {}: {}", index, e);
+            return Err(self.reject(block, ImportStage::Signature, reason, Some(index), origin));
+        }
+
+        let hash = origin.hash;
+        let mut chain = self.chain.lock().unwrap();
+        // The block tree may have changed while the signatures were checked
+        if chain.fork_choice.contains(&hash) {
+            return Ok(BlockImport::Known);
+        }
+        if let Some(outcome) = self.locate(&chain, block)? {
+            return Ok(outcome);
+        }
+        times.time(ImportStage::Commit, || {
+            self.db.store_block_by_hash(block)?;
+            chain.fork_choice.add_block(hash, block.header.previous_hash, block.header.height)?;
+            if let Some(proposer) = self.proposer_of(block) {
+                chain.fork_choice.add_vote(&proposer, hash, PROPOSER_VOTE_WEIGHT);
+            }
+            Ok::<_, String>(())
+        })?;
+
+        let mut error = None;
+        while chain.fork_choice.head() != chain.head {
+            let target = chain.fork_choice.head();
+            if let Err(e) = self.switch_head(&mut chain, times, origin) {
+                error = Some(e);
+                if chain.fork_choice.head() == target {
+                    break;
+                }
+            }
+        }
+        times.time(ImportStage::Commit, || self.finalize(&mut chain))?;
+
+        match error {
+            Some(e) if !chain.fork_choice.contains(&hash) => Err(e),
+            _ => Ok(BlockImport::Imported),
+        }
+    }
+
+    /// Header stage: an outcome for blocks already known or not yet connectable, an
+    /// error for blocks that can never be valid, `None` for blocks to check further
+    fn check_header(&self, block: &Block, origin: Origin) -> Result<Option<BlockImport>, String> {
+        let (hash, height) = (origin.hash, block.header.height);
+        let chain = self.chain.lock().unwrap();
+        if chain.fork_choice.contains(&hash) {
+            return Ok(Some(BlockImport::Known));
+        }
+        let conflict = self.checkpoint.as_ref().and_then(|checkpoint| checkpoint.check_block(height, &hash).err());
+        if conflict.is_none() {
+            if let Some(outcome) = self.locate(&chain, block)? {
+                return Ok(Some(outcome));
+            }
+        }
+        let wrong_height = height != Self::height_after(&chain.fork_choice, &block.header.previous_hash);
+        drop(chain);
+        let committee = match self.validators.is_empty() {
+            true => None,
+            false => Some(self.fast_lane_committee(DutyTracker::epoch_of(height))?),
+        };
+
+        let fault = conflict
+            .or_else(|| wrong_height.then(|| format!("Block {} has the wrong height for its parent", height)))
+            .or_else(|| (!block.has_valid_merkle_root()).then(|| format!("Block {} has an invalid merkle root", height)))
+            .or_else(|| (!block.has_valid_bloom()).then(|| format!("Block {} has an invalid bloom", height)))
+            .or_else(|| (!block.has_valid_availability_root()).then(|| format!("Block {} has an invalid availability root", height)))
+            .or_else(|| block.check_path_proposal().err())
+            .or_else(|| self.params.at(height).check_block(block).err())
+            .or_else(|| {
+                block.transactions.iter().enumerate().find_map(|(index, tx)| {
+                    self.gas.check_fee(tx).err().map(|e| format!("Block {} transaction {}: {}", height, index, e))
+                })
+            })
+            .or_else(|| self.resolve_consensus(block).err())
+            .or_else(|| self.committee_fault(block, committee.as_ref()?));
+        match fault {
+            Some(reason) => Err(self.reject(block, ImportStage::Header, reason, None, origin)),
+            None => Ok(None),
+        }
+    }
+
+    /// Whether a block can be attached to the block tree yet, and if not what to fetch first
+    fn locate(&self, chain: &Chain, block: &Block) -> Result<Option<BlockImport>, String> {
+        let height = block.header.height;
+        if let Some(checkpoint) = &self.checkpoint {
+            if chain.head == GENESIS_PARENT {
+                return Ok(Some(BlockImport::Missing { expected: checkpoint.height + 1 }));
+            }
+        }
+        if chain.fork_choice.contains(&block.header.previous_hash) {
+            return Ok(None);
+        }
+        if self.db.get_block(height)?.is_some_and(|stored| stored.hash() == block.hash()) {
+            return Ok(Some(BlockImport::Known));
+        }
+        let next_height = Self::height_after(&chain.fork_choice, &chain.head);
+        let expected = if height > next_height {
+            next_height
+        } else {
+            let summary = chain.fork_choice.summary();
+            Self::height_after(&chain.fork_choice, &summary.finalized)
+        };
+        Ok(Some(BlockImport::Missing { expected }))
+    }
+
+    /// Records a block refused at `stage` and returns the reason
+    fn reject(&self, block: &Block, stage: ImportStage, reason: String, tx_index: Option<usize>, origin: Origin) -> String {
+        let hash = block.hash();
+        let record = BadBlockRecord::new(Some(block.header.height), hash, stage, &reason)
+            .with_tx_index(tx_index)
+            .with_peer(origin.peer_of(&hash));
+        match bincode::serialize(block) {
+            Ok(bytes) => self.keep_bad_block(&record, &bytes),
+            Err(e) => log!(Error, "Could not encode bad block {}: {}", block.header.height, e),
+        }
+        // A forged signature inside a block means its proposer signed off on it
+        let (kind, severity) = match stage {
+            ImportStage::Signature => (SecurityEventType::InvalidSignature, SecuritySeverity::High),
+            _ => (SecurityEventType::SuspiciousActivity, SecuritySeverity::Medium),
+        };
+        let source = origin.peer_of(&hash).map_or_else(|| "import".to_string(), |peer| format!("peer {}", short_hex(peer)));
+        self.report_security_event(kind, severity, source, format!("Rejected block {}: {}", block.header.height, reason));
+        reason
+    }
+
+    fn keep_bad_block(&self, record: &BadBlockRecord, bytes: &[u8]) {
+        if let Err(e) = self.bad_blocks.insert(record, bytes) {
+            log!(Error, "Could not record bad block {}: {}", record.name(), e);
+        }
+        if let Some(quarantine) = &self.quarantine {
+            match quarantine.store(record, bytes) {
+                Ok(path) => log!(Warn, "Quarantined bad block at {}", path.display()),
+                Err(e) => log!(Error, "Could not quarantine bad block {}: {}", record.name(), e),
+            }
+        }
+    }
+
+    /// Moves the canonical chain to the fork-choice head, replaying state from the common
+    /// ancestor. A branch that fails validation is removed from the block tree and the
+    /// block that failed quarantined
+    fn switch_head(&self, chain: &mut Chain, times: &mut StageTimes, origin: Origin) -> Result<(), String> {
+        let executing = Instant::now();
+        let new_head = chain.fork_choice.head();
+        let ancestor = chain.fork_choice.common_ancestor(&chain.head, &new_head);
+
+        let mut state = if ancestor == chain.head {
+            chain.state.clone()
+        } else if ancestor == GENESIS_PARENT {
+            StateManager::replay_to(&self.db, None)?
+        } else {
+            StateManager::replay_to(&self.db, chain.fork_choice.height(&ancestor))?
+        };
+
+        let mut adopted = Vec::new();
+        let mut states = Vec::new();
+        for hash in chain.fork_choice.branch(&ancestor, &new_head) {
+            let block = self.db.get_block_by_hash(&hash)?
+                .ok_or_else(|| format!("Block 0x{} missing from storage", hex::encode(hash)))?;
+            let applied = state.execute_block(&block).and_then(|_| {
+                if state.state_root() == block.header.state_root {
+                    Ok(())
+                } else {
+                    Err((None, "state root mismatch".to_string()))
+                }
+            });
+            if let Err((tx_index, e)) = applied {
+                chain.fork_choice.remove_branch(&hash);
+                times.add(ImportStage::Execute, executing.elapsed());
+                let reason = self.reject(&block, ImportStage::Execute, e, tx_index, origin);
+                return Err(format!("Block {}: {}", block.header.height, reason));
+            }
+            states.push((block.header.height, state.clone()));
+            adopted.push(block);
+        }
+        times.add(ImportStage::Execute, executing.elapsed());
+        let committing = Instant::now();
+
+        let mut orphaned = Vec::new();
+        for hash in chain.fork_choice.branch(&ancestor, &chain.head) {
+            if let Some(block) = self.db.get_block_by_hash(&hash)? {
+                orphaned.push(block);
+            }
+        }
+
+        for block in &adopted {
+            self.db.store_block(block)?;
+        }
+        let head_height = Self::height_after(&chain.fork_choice, &new_head).saturating_sub(1);
+        if ancestor != chain.head {
+            let replaced = chain.fork_choice.height(&ancestor).map_or(0, |height| height + 1);
+            self.path_history.lock().unwrap().rewind(replaced);
+        }
+        self.db.truncate_above(head_height)?;
+        self.state_store.truncate_above(head_height)?;
+        for (height, state) in &states {
+            self.state_store.commit(*height, state)?;
+        }
+        chain.state = state;
+        chain.head = new_head;
+
+        let next_height = Self::height_after(&chain.fork_choice, &new_head);
+        let mut mempool = self.mempool.lock().unwrap();
+        mempool.set_params(self.params.at(next_height));
+        for block in &orphaned {
+            self.events.publish(NodeEvent::BlockOrphaned { height: block.header.height, hash: block.hash() });
+        }
+        for block in &adopted {
+            mempool.remove_included(&block.transactions);
+            self.events.publish(NodeEvent::BlockImported {
+                height: block.header.height,
+                hash: block.hash(),
+                transactions: block.transaction_count(),
+                produced: false,
+            });
+        }
+        let mut dropped = self.orphaned.lock().unwrap();
+        for tx in orphaned.into_iter().flat_map(|block| block.transactions) {
+            let hash = tx.hash();
+            if mempool.insert(tx, &chain.state, next_height).is_err() {
+                if dropped.len() == MAX_ORPHANED_TRACKED {
+                    dropped.pop_front();
+                }
+                dropped.push_back(hash);
+            }
+        }
+        times.add(ImportStage::Commit, committing.elapsed());
+        Ok(())
+    }
+
+    /// Finalizes the canonical block `FINALITY_DEPTH` below the head and records the head
+    fn finalize(&self, chain: &mut Chain) -> Result<(), String> {
+        let head_height = Self::height_after(&chain.fork_choice, &chain.head).saturating_sub(1);
+        if head_height >= FINALITY_DEPTH {
+            let height = head_height - FINALITY_DEPTH;
+            if let Some(block) = self.db.get_block(height)? {
+                let hash = block.hash();
+                if chain.fork_choice.summary().finalized != hash {
+                    chain.fork_choice.finalize(hash)?;
+                    // Finalized transactions are never imported again
+                    for tx in &block.transactions {
+                        self.verified.forget(tx);
+                    }
+                    self.events.publish(NodeEvent::BlockFinalized { height, hash });
+                }
+            }
+        }
+        let head = HeadSnapshot::of(chain);
+        let next_height = head.next_height;
+        if head.fork_choice.finalized != GENESIS_PARENT {
+            self.orphans.lock().unwrap().prune_through(head.fork_choice.finalized_height);
+        }
+        self.head.store(Arc::new(head));
+        self.db.put_meta("fork_choice", &chain.fork_choice.summary())?;
+        self.update_agreed_path(next_height)?;
+        self.close_epochs(next_height)
+    }
+
+    /// Adjusts validator reputations for every epoch the chain has moved past
+    fn close_epochs(&self, next_height: u64) -> Result<(), String> {
+        let mut duties = self.duties.lock().unwrap();
+        if duties.validators().is_empty() || duties.next_epoch() >= DutyTracker::epoch_of(next_height) {
+            return Ok(());
+        }
+        while duties.next_epoch() < DutyTracker::epoch_of(next_height) {
+            let proposers = self.epoch_proposers(duties.next_epoch(), next_height)?;
+            let committee = self.fast_lane_committee(duties.next_epoch())?;
+            duties.close_epoch(&committee, &proposers);
+        }
+        self.db.put_meta(DUTIES_META, &*duties)
+    }
+
+    /// Runs mempool garbage collection every `interval` until the task is dropped, also
+    /// refreshing the consensus path as security events age out of the score
+    pub async fn run_mempool_gc(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            self.refresh_consensus_path();
+            let node = self.clone();
+            let dropped = blocking(move || node.collect_mempool_garbage()).await.unwrap_or_default();
+            if !dropped.is_empty() {
+                log!(Info, "Mempool GC dropped {} transactions", dropped.len());
+            }
+        }
+    }
+
+    /// Prunes historical state every `interval` until the task is dropped
+    pub async fn run_state_pruning(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let node = self.clone();
+            match blocking(move || node.prune_state()).await.flatten() {
+                Ok(report) if report.roots_pruned > 0 => log!(
+                    Info,
+                    "State pruning removed {} roots and {} trie nodes",
+                    report.roots_pruned, report.nodes_deleted
+                ),
+                Ok(_) => {}
+                Err(e) => log!(Error, "State pruning failed: {}", e),
+            }
+        }
+    }
+
+    /// Moves old finalized blocks to the ancient store every `interval` until the task
+    /// is dropped
+    pub async fn run_block_freezing(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let node = self.clone();
+            match blocking(move || node.freeze_ancient_blocks()).await.flatten() {
+                Ok(moved) if moved > 0 => log!(Info, "Moved {} finalized blocks to the ancient store", moved),
+                Ok(_) => {}
+                Err(e) => log!(Error, "Moving blocks to the ancient store failed: {}", e),
+            }
+        }
+    }
+
+    /// Produces a block every `interval`, unless production is paused, until the task
+    /// is dropped
+    pub async fn run(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if self.is_production_paused() {
+                continue;
+            }
+            let node = self.clone();
+            if let Err(e) = blocking(move || node.produce_block()).await.flatten() {
+                log!(Error, "Block production failed: {}", e);
+            }
+        }
+    }
+
+    /// Seals a block as soon as transactions are admitted, and every `interval` if given
+    /// even without any, until the task is dropped. Dev chains run this in place of a
+    /// proposer
+    pub async fn run_instant_sealing(self: Arc<Self>, interval: Option<Duration>) {
+        let mut updates = self.subscribe_mempool();
+        let mut ticker = interval.map(tokio::time::interval);
+        if let Some(ticker) = ticker.as_mut() {
+            ticker.tick().await;
+        }
+        loop {
+            let tick = async {
+                match ticker.as_mut() {
+                    Some(ticker) => ticker.tick().await,
+                    None => std::future::pending().await,
+                }
+            };
+            let admitted = tokio::select! {
+                update = updates.recv() => match update {
+                    Ok(MempoolUpdate::Admitted { .. }) | Err(broadcast::error::RecvError::Lagged(_)) => true,
+                    Ok(MempoolUpdate::Evicted { .. }) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+                _ = tick => false,
+            };
+            // A block sealed for an earlier admission may already hold this one
+            if self.is_production_paused() || (admitted && self.pending_transactions() == 0) {
+                continue;
+            }
+            let node = self.clone();
+            if let Err(e) = blocking(move || node.produce_block()).await.flatten() {
+                log!(Error, "Block production failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Runs `work` on the blocking pool. Node operations take the chain lock and touch
+/// disk, so async tasks call them through here instead of stalling the executor;
+/// a panic is resumed in the caller so supervisors still see it, while work cancelled
+/// by the runtime shutting down is an error
+pub async fn blocking<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> Result<T, String> {
+    match tokio::task::spawn_blocking(work).await {
+        Ok(result) => Ok(result),
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(format!("Blocking task did not finish: {}", e)),
+    }
+}
+
+fn current_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::validate::{ChainValidator, ValidationMode};
+    use crate::crypto::{QuantumKeyPair, QuantumSignature};
+    use crate::mempool::Lane;
+    use crate::storage::blocks::{PATH_BLOCK_VERSION, SIGNALING_BLOCK_VERSION};
+
+    #[test]
+    fn test_node_produces_valid_chain() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_node");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let keypair = QuantumKeyPair::generate();
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        db.store_genesis_allocations(&[(keypair.public_key().to_vec(), 1_000)]).unwrap();
+        let node = Node::open(db.clone()).unwrap().with_retained_roots(1);
+        let mut events = node.events().subscribe();
+        let mut updates = node.subscribe_mempool();
+
+        let mut tx = Transaction::new(
+            keypair.public_key().to_vec(),
+            vec![0xcc; 32],
+            100,
+            1,
+            0,
+            vec![],
+            QuantumSignature::new(vec![]),
+        );
+        tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+        let mut forged = tx.clone();
+        forged.amount = 900;
+        assert!(node.submit_transaction(forged).is_err());
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            NodeEvent::SecurityEvent { kind: SecurityEventType::InvalidSignature, severity: SecuritySeverity::Low, .. }
+        ));
+        assert!((node.security_score() - 0.9).abs() < 1e-9);
+        let tx_hash = tx.hash();
+        let size = tx.size();
+        node.submit_transaction(tx.clone()).unwrap();
+        // A copy arriving by another path is turned away before its signature is checked
+        assert_eq!(node.submit_transaction(tx).unwrap_err(), "Transaction already seen");
+        assert_eq!(node.duplicate_metrics().transactions.duplicates, 1);
+        assert_eq!(updates.try_recv().unwrap(), MempoolUpdate::Admitted { hash: tx_hash, fee: 1, size, lane: Lane::Fast });
+        assert_eq!(node.mempool_summary().bytes, size);
+
+        assert_eq!(node.produce_block().unwrap().transaction_count(), 1);
+        assert_eq!(node.produce_block().unwrap().header.height, 1);
+        assert_eq!(node.pending_transactions(), 0);
+        assert!(matches!(events.try_recv().unwrap(), NodeEvent::TxAccepted { hash } if hash == tx_hash));
+        assert!(matches!(events.try_recv().unwrap(), NodeEvent::BlockImported { height: 0, transactions: 1, produced: true, .. }));
+        node.set_consensus_path(ConsensusPath::EmergencyMode { fallback_validators: 1, security_override: true });
+        assert!(matches!(events.try_recv(), Ok(NodeEvent::BlockImported { height: 1, .. })));
+        assert!(matches!(events.try_recv(), Ok(NodeEvent::PathProposed { .. })));
+
+        // A severe event drags the score down far enough for the router to pick the secure lane
+        node.report_security_event(SecurityEventType::InvalidSignature, SecuritySeverity::Critical, "peer 0x01".to_string(), "Rejected block 2".to_string());
+        assert!(matches!(events.try_recv(), Ok(NodeEvent::SecurityEvent { severity: SecuritySeverity::Critical, .. })));
+        assert!(matches!(events.try_recv(), Ok(NodeEvent::PathProposed { to: ConsensusPath::SecureLane { .. }, .. })));
+        let (decisions, _) = node.decisions().page(None, 2).unwrap();
+        assert!(matches!((&decisions[0].path, &decisions[0].override_reason), (ConsensusPath::SecureLane { .. }, None)));
+        assert_eq!(decisions[1].override_reason.as_deref(), Some("Set directly"));
+
+        // A pin holds its path through security events until it expires
+        let pin = node.pin_consensus_path(PinnedMode::FastLane, Duration::from_secs(60), "drill".to_string()).unwrap();
+        assert!(matches!(events.try_recv(), Ok(NodeEvent::PathProposed { to: ConsensusPath::FastLane { .. }, .. })));
+        assert!(matches!(events.try_recv(), Ok(NodeEvent::PathPinned { pin: Some(_) })));
+        node.report_security_event(SecurityEventType::InvalidSignature, SecuritySeverity::Critical, "peer 0x01".to_string(), "Rejected block 3".to_string());
+        assert!(matches!(events.try_recv(), Ok(NodeEvent::SecurityEvent { .. })));
+        assert!(matches!(node.consensus_path(), ConsensusPath::FastLane { .. }));
+        assert_eq!(node.decisions().page(None, 1).unwrap().0[0].override_reason.as_deref(), Some("Pinned: drill"));
+        *node.pin.lock().unwrap() = Some(PathPin { expires_at: 0, ..pin });
+        node.refresh_consensus_path();
+        assert!(matches!(events.try_recv(), Ok(NodeEvent::PathPinned { pin: None })));
+        assert!(matches!(events.try_recv(), Ok(NodeEvent::PathProposed { to: ConsensusPath::SecureLane { .. }, .. })));
+        assert_eq!(node.state_store().account_at(0, &[0xcc; 32]).unwrap().unwrap().balance, 100);
+        assert_eq!(node.prune_state().unwrap().roots_pruned, 1);
+        assert!(node.state_store().account_at(0, &[0xcc; 32]).is_err());
+
+        // A node whose genesis prices gas refuses a block paying less than its gas
+        let priced_dir = std::env::temp_dir().join("triunity_test_node_priced");
+        let _ = std::fs::remove_dir_all(&priced_dir);
+        let priced_db = BlockchainDB::new(priced_dir.to_str().unwrap()).unwrap();
+        priced_db.store_genesis_allocations(&[(keypair.public_key().to_vec(), 1_000)]).unwrap();
+        priced_db.store_gas_schedule(&GasSchedule { gas_price: 1, ..GasSchedule::default() }).unwrap();
+        let priced = Node::open(priced_db).unwrap();
+        let block = db.get_block(0).unwrap().unwrap();
+        assert!(priced.import_block(&block).unwrap_err().contains("gas costs"));
+        let _ = std::fs::remove_dir_all(&priced_dir);
+
+        let report = ChainValidator::new(db, ValidationMode::Full).run().unwrap();
+        assert!(report.is_valid(), "{:?}", report.failure);
+
+        println!("   Node block production working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_reorg_to_longer_branch() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_node_reorg");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let keypair = QuantumKeyPair::generate();
+        let open = |name: &str| {
+            let db = BlockchainDB::new(temp_dir.join(name).to_str().unwrap()).unwrap();
+            db.store_genesis_allocations(&[(keypair.public_key().to_vec(), 1_000)]).unwrap();
+            Node::open(db).unwrap()
+        };
+        let (a, b) = (open("a"), open("b"));
+
+        let mut tx = Transaction::new(keypair.public_key().to_vec(), vec![0xdd; 32], 5, 1, 0, vec![], QuantumSignature::new(vec![]));
+        tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+        let a_blocks: Vec<Block> = (0..3).map(|_| a.produce_block().unwrap()).collect();
+        b.submit_transaction(tx).unwrap();
+        let b_blocks: Vec<Block> = (0..2).map(|_| b.produce_block().unwrap()).collect();
+        let tx_hash = b_blocks[0].transactions[0].hash();
+        assert_eq!(b.transaction_status(&tx_hash).unwrap(), Some(TxStatus::Included { height: 0, block: b_blocks[0].hash() }));
+        let mut events = b.events().subscribe();
+
+        for block in &b_blocks {
+            assert_eq!(a.import_block(block).unwrap(), BlockImport::Imported);
+        }
+        assert_eq!(a.fork_choice_head().head, a_blocks[2].hash());
+        assert_eq!(a.import_block(&b_blocks[0]).unwrap(), BlockImport::Known);
+        assert_eq!(a.duplicate_metrics().blocks.duplicates, 1);
+
+        for block in &a_blocks {
+            b.import_block(block).unwrap();
+        }
+        assert_eq!(b.fork_choice_head().head, a_blocks[2].hash());
+        assert_eq!(b.db().get_block(1).unwrap().unwrap().hash(), a_blocks[1].hash());
+        assert_eq!(b.db().get_latest_height().unwrap(), 2);
+        assert_eq!(b.pending_transactions(), 1);
+        assert_eq!(b.transaction_status(&tx_hash).unwrap(), Some(TxStatus::Pending));
+        assert!(matches!(events.try_recv(), Ok(NodeEvent::BlockOrphaned { height: 0, hash }) if hash == b_blocks[0].hash()));
+        assert!(b.state_store().account_at(0, &[0xdd; 32]).unwrap().is_none());
+        assert_eq!(b.state_store_metrics().unwrap().latest_height, Some(2));
+
+        let mut orphan = b_blocks[1].clone();
+        orphan.header.previous_hash = [7; 32];
+        orphan.header.height = 9;
+        assert_eq!(b.import_block(&orphan).unwrap(), BlockImport::Missing { expected: 3 });
+
+        // A block arriving before its parent waits in the orphan pool and is imported with it
+        let c = open("c");
+        assert_eq!(c.import_block_from(&b_blocks[1], &[5; 32]).unwrap(), BlockImport::Missing { expected: 0 });
+        assert_eq!(c.orphan_sync_requests()[0].peer, vec![5; 32]);
+        assert_eq!(c.import_block(&b_blocks[0]).unwrap(), BlockImport::Imported);
+        assert_eq!(c.fork_choice_head().head, b_blocks[1].hash());
+        assert_eq!(c.orphan_stats(), OrphanStats { pooled: 0, promoted: 1, evicted: 0 });
+
+        println!("   Fork choice reorg working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_feature_deployment_activates() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_node_features");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        let deployment = Deployment { name: "batches".to_string(), bit: 2, start_height: 4, timeout_height: 40 };
+        let schedule = ParamSchedule::default().with_signaling(4, 3).with_deployment(deployment.clone());
+        db.store_chain_params(&schedule).unwrap();
+        let node = Node::open(db).unwrap();
+
+        // Signals from window 1, locks in with it and activates when window 3 begins
+        let blocks: Vec<Block> = (0..13).map(|_| node.produce_block().unwrap()).collect();
+        assert!(blocks[..4].iter().all(|block| block.header.signals == 0 && block.header.version < SIGNALING_BLOCK_VERSION));
+        assert!(blocks[4..8].iter().all(|block| block.header.signals == deployment.mask()));
+        assert_eq!(node.db().get_block(5).unwrap().unwrap().header.signals, deployment.mask());
+        assert_eq!(blocks[8].header.signals, 0);
+        assert!(!node.active_params(11).unwrap().has_feature(&deployment));
+        assert!(node.active_params(12).unwrap().has_feature(&deployment));
+        let status = node.deployment_status(8).unwrap();
+        assert_eq!((status[0].state, status[0].signaled, status[0].threshold), (DeploymentState::LockedIn, 0, 3));
+        assert_eq!(node.deployment_status(13).unwrap()[0].state, DeploymentState::Active);
+
+        println!("   Feature deployment activation working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_path_switch_agreement() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_node_paths");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let node = Node::open(BlockchainDB::new(temp_dir.join("a").to_str().unwrap()).unwrap()).unwrap();
+        let follower = Node::open(BlockchainDB::new(temp_dir.join("b").to_str().unwrap()).unwrap()).unwrap();
+        let mut events = follower.events().subscribe();
+        assert_eq!(node.active_path().path.name(), "HybridPath");
+
+        // Blocks propose the secure lane while the chain stays on its path for the window
+        node.set_consensus_path(PinnedMode::SecureLane.path(&NetworkMetrics::default()));
+        let blocks: Vec<Block> = (0..PATH_WINDOW + 1).map(|_| node.produce_block().unwrap()).collect();
+        assert!(blocks[..PATH_WINDOW as usize].iter().all(|block| block.header.version == PATH_BLOCK_VERSION && block.header.path_proposal.as_ref().is_some_and(|proposal| proposal.path == 1)));
+        assert!(blocks[PATH_WINDOW as usize].header.path_proposal.is_none());
+        assert_eq!(node.agreed_path(PATH_WINDOW - 1).unwrap().path.name(), "HybridPath");
+        let agreed = node.agreed_path(u64::MAX).unwrap();
+        assert_eq!((agreed.path.name(), agreed.activation_height, agreed.proposals), ("SecureLane", PATH_WINDOW, PATH_WINDOW));
+
+        // A node whose own router prefers another path follows the chain's agreement
+        for block in &blocks {
+            follower.import_block(block).unwrap();
+        }
+        assert_eq!((follower.consensus_path().name(), follower.active_path().path.name()), ("HybridPath", "SecureLane"));
+        assert!(std::iter::from_fn(|| events.try_recv().ok()).any(|event| matches!(event, NodeEvent::PathSwitched { to: ConsensusPath::SecureLane { .. }, .. })));
+
+        // Emergency mode cannot be proposed without emergency metrics
+        let unjustified = PathProposal { path: 3, metrics: NetworkMetrics::default() };
+        let forged = node.produce_block().unwrap().with_path_proposal(unjustified);
+        assert!(follower.import_block(&forged).unwrap_err().contains("do not justify a switch to EmergencyMode"));
+
+        // The agreement survives a restart without recounting the chain
+        drop(node);
+        let reopened = Node::open(BlockchainDB::new(temp_dir.join("a").to_str().unwrap()).unwrap()).unwrap();
+        assert_eq!(reopened.active_path().path.name(), "SecureLane");
+
+        println!("   Path switch agreement working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_fast_lane_committee_proofs() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_node_committee");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let (keypair, other) = (QuantumKeyPair::generate(), QuantumKeyPair::generate());
+        let validators = vec![keypair.public_key().to_vec(), other.public_key().to_vec()];
+        let open = |name: &str, identity: &QuantumKeyPair| {
+            let db = BlockchainDB::new(temp_dir.join(name).to_str().unwrap()).unwrap();
+            db.store_genesis_validators(&validators).unwrap();
+            Node::open(db).unwrap().with_validator_key(identity.clone())
+        };
+        let (producer, importer) = (open("producer", &keypair), open("importer", &other));
+        assert_eq!(producer.proposer_for(0).unwrap(), Some(validators[0].clone()));
+        assert!(producer.is_proposer(0) && !importer.is_proposer(0));
+
+        // Produced blocks prove their proposer's seat; without the proof they are refused
+        let block = producer.produce_block().unwrap();
+        let ConsensusData::FastLaneRef { validator, committee: Some(proof) } = &block.header.consensus_data else {
+            panic!("block carries no committee proof");
+        };
+        assert_eq!(validator.index, 0);
+        assert!(proof.verify(&validators[0], producer.fast_lane_committee(0).unwrap().root()));
+        assert!(bincode::serialize(&block.header).unwrap().len() < validators[0].len());
+        let mut stripped = block.clone();
+        stripped.header.consensus_data = ConsensusData::FastLane { validator: validators[0].clone(), committee: None };
+        assert!(importer.import_block(&stripped).unwrap_err().contains("committee proof"));
+
+        // Proposals are only imported when signed by the scheduled proposer
+        let forged = BlockProposal::sign(&other, block.clone(), 0).unwrap();
+        assert!(importer.import_proposal(&forged, b"peer").unwrap_err().contains("invalid proposer signature"));
+        let proposal = BlockProposal::sign(&keypair, block, 0).unwrap();
+        assert_eq!(importer.import_proposal(&proposal, b"peer").unwrap(), BlockImport::Imported);
+        assert!(importer.is_proposer(1));
+
+        println!("   Fast lane committee proofs working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_instant_sealing() {
+        let keypair = QuantumKeyPair::generate();
+        let db = BlockchainDB::temporary().unwrap();
+        db.store_genesis_allocations(&[(keypair.public_key().to_vec(), 1_000)]).unwrap();
+        let node = Arc::new(Node::open(db).unwrap());
+        let mut events = node.events().subscribe();
+        let sealer = tokio::spawn(node.clone().run_instant_sealing(None));
+        tokio::task::yield_now().await;
+
+        // Nothing is sealed while the mempool is idle, then the admission is sealed at once
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(node.next_height().unwrap(), 0);
+        let mut tx = Transaction::new(keypair.public_key().to_vec(), vec![0xcc; 32], 10, 1, 0, vec![], QuantumSignature::new(vec![]));
+        tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+        let hash = node.submit_transaction(tx).unwrap();
+        let imported = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let NodeEvent::BlockImported { height, transactions, .. } = events.recv().await.unwrap() {
+                    return (height, transactions);
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(imported, (0, 1));
+        assert!(matches!(node.transaction_status(&hash).unwrap(), Some(TxStatus::Included { height: 0, .. })));
+        sealer.abort();
+
+        println!("   Instant sealing working!");
+    }
+}
@@ -0,0 +1,162 @@
+use crate::mempool::{LaneQuota, Mempool};
+use crate::storage::blocks::{Block, ConsensusData, Transaction};
+use crate::storage::state::StateManager;
+
+/// Most transactions a block holds on a chain whose genesis sets no limit
+pub const DEFAULT_MAX_BLOCK_TRANSACTIONS: usize = 5_000;
+
+/// Most encoded transaction bytes a block holds by default, well under the largest
+/// network message so the block always gossips
+pub const DEFAULT_MAX_BLOCK_BYTES: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockLimits {
+    pub max_transactions: usize,
+    pub max_bytes: usize,
+}
+
+impl BlockLimits {
+    /// No limit at all, for capping one field alone with `within`
+    pub const UNLIMITED: Self = Self { max_transactions: usize::MAX, max_bytes: usize::MAX };
+
+    /// The tighter of these limits and `outer`, field by field
+    pub fn within(self, outer: BlockLimits) -> Self {
+        Self { max_transactions: self.max_transactions.min(outer.max_transactions), max_bytes: self.max_bytes.min(outer.max_bytes) }
+    }
+}
+
+impl Default for BlockLimits {
+    fn default() -> Self {
+        Self { max_transactions: DEFAULT_MAX_BLOCK_TRANSACTIONS, max_bytes: DEFAULT_MAX_BLOCK_BYTES }
+    }
+}
+
+/// Assembles the block at `height` on top of `parent`, applying each transaction to a
+/// scratch copy of the parent state as it is added so the sealed block is known to
+/// apply and carries the state root it leads to. Block production and tests build
+/// blocks through this rather than `Block::new`
+#[derive(Debug)]
+pub struct BlockBuilder {
+    parent: [u8; 32],
+    height: u64,
+    state: StateManager,
+    limits: BlockLimits,
+    quota: LaneQuota,
+    transactions: Vec<Transaction>,
+    bytes: usize,
+}
+
+impl BlockBuilder {
+    /// Starts a block on `state`, the state after `parent`
+    pub fn new(parent: [u8; 32], height: u64, state: &StateManager) -> Result<Self, String> {
+        let mut state = state.clone();
+        state.begin_block(height)?;
+        Ok(Self { parent, height, state, limits: BlockLimits::default(), quota: LaneQuota::UNRESERVED, transactions: Vec::new(), bytes: 0 })
+    }
+
+    pub fn with_limits(mut self, limits: BlockLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Splits the space `fill_from` takes from the mempool between its lanes
+    pub fn with_lane_quota(mut self, quota: LaneQuota) -> Self {
+        self.quota = quota;
+        self
+    }
+
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    /// Appends `tx` if it fits the limits and applies on top of the transactions before
+    /// it; otherwise the block is left as it was
+    pub fn add_tx(&mut self, tx: Transaction) -> Result<(), String> {
+        if self.transactions.len() >= self.limits.max_transactions {
+            return Err(format!("Block is full at {} transactions", self.limits.max_transactions));
+        }
+        let size = tx.size();
+        if self.bytes + size > self.limits.max_bytes {
+            return Err(format!("Transaction of {} bytes does not fit the {} left in the block", size, self.limits.max_bytes - self.bytes));
+        }
+        if tx.is_expired_at(self.height) {
+            return Err(format!("Transaction expired at height {}", tx.valid_until_height.unwrap_or_default()));
+        }
+        self.state.apply_transaction_at(&tx, self.height)?;
+        self.bytes += size;
+        self.transactions.push(tx);
+        Ok(())
+    }
+
+    /// Takes as many pending transactions as the block has room for, split by the lane
+    /// quota, and adds those that still apply. The rest are dropped from the mempool
+    pub fn fill_from(&mut self, mempool: &mut Mempool) -> usize {
+        let room = self.limits.max_transactions.saturating_sub(self.transactions.len());
+        let before = self.transactions.len();
+        for tx in mempool.take_with_quota(room, self.quota) {
+            let _ = self.add_tx(tx);
+        }
+        self.transactions.len() - before
+    }
+
+    /// The finished block, with the state root it leads to, and that state
+    pub fn seal(self, consensus_data: ConsensusData) -> (Block, StateManager) {
+        let block = Block::new(self.parent, self.transactions, self.height, consensus_data).with_state_root(self.state.state_root());
+        (block, self.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{QuantumKeyPair, QuantumSignature};
+
+    #[test]
+    fn test_block_builder() {
+        let keypair = QuantumKeyPair::generate();
+        let sender = keypair.public_key().to_vec();
+        let state = StateManager::from_allocations(&[(sender.clone(), 1_000)]).unwrap();
+        let transfer = |nonce: u64, amount: u64| {
+            let mut tx = Transaction::new(sender.clone(), vec![0xaa; 32], amount, 1, nonce, vec![], QuantumSignature::new(vec![]));
+            tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+            tx
+        };
+
+        let limits = BlockLimits { max_transactions: 2, max_bytes: DEFAULT_MAX_BLOCK_BYTES };
+        let mut builder = BlockBuilder::new([7; 32], 1, &state).unwrap().with_limits(limits);
+        builder.add_tx(transfer(0, 10)).unwrap();
+        // Out of nonce order and overdrawn transactions are refused without touching the block
+        assert!(builder.add_tx(transfer(2, 10)).is_err());
+        assert!(builder.add_tx(transfer(1, 5_000)).is_err());
+        assert!(builder.add_tx(transfer(1, 10).with_valid_until(0)).is_err());
+        builder.add_tx(transfer(1, 10)).unwrap();
+        assert!(builder.add_tx(transfer(2, 10)).unwrap_err().contains("full"));
+
+        let (block, next) = builder.seal(ConsensusData::default());
+        assert_eq!((block.header.previous_hash, block.header.height, block.transaction_count()), ([7; 32], 1, 2));
+        let mut replayed = state.clone();
+        replayed.apply_block(&block).unwrap();
+        assert_eq!((block.header.state_root, next.state_root()), (replayed.state_root(), replayed.state_root()));
+
+        // The byte limit holds however few transactions there are
+        let tight = BlockLimits { max_transactions: 10, max_bytes: transfer(0, 10).size() * 3 / 2 };
+        let mut builder = BlockBuilder::new([7; 32], 1, &state).unwrap().with_limits(tight);
+        builder.add_tx(transfer(0, 10)).unwrap();
+        assert!(builder.add_tx(transfer(1, 10)).unwrap_err().contains("bytes"));
+
+        // Filling from the mempool keeps to the room left
+        let mut mempool = Mempool::default();
+        for nonce in 0..3 {
+            mempool.insert(transfer(nonce, 10), &state, 1).unwrap();
+        }
+        let mut builder = BlockBuilder::new([7; 32], 1, &state).unwrap().with_limits(limits);
+        assert_eq!(builder.fill_from(&mut mempool), 2);
+        assert_eq!(mempool.len(), 1);
+
+        println!("   Block builder working!");
+    }
+}
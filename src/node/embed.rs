@@ -0,0 +1,422 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::alerts::AlertService;
+use crate::config::NodeConfig;
+use crate::consensus::ConsensusEngine;
+use crate::consensus::fork_choice::ForkChoiceHead;
+use crate::consensus::metrics::MetricsCollector;
+use crate::consensus::params::ParamSchedule;
+use crate::events::NodeEvent;
+use crate::mempool::{MempoolSummary, MempoolUpdate};
+use crate::network::NetworkService;
+use crate::node::{Node, TxStatus};
+use crate::storage::blocks::{Block, Transaction};
+use crate::storage::database::BlockchainDB;
+use crate::storage::state::Account;
+use crate::storage::TriUnityStorage;
+use crate::supervisor::{RestartPolicy, Supervisor};
+use crate::watchtower::Watchtower;
+
+const MEMPOOL_GC_INTERVAL: Duration = Duration::from_secs(5);
+const STATE_PRUNING_INTERVAL: Duration = Duration::from_secs(30);
+const PEER_EXCHANGE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Block interval of an embedded node's proposer unless the builder sets one
+pub const DEFAULT_BLOCK_TIME: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NodeMode {
+    /// Keeps the chain, proposes blocks when it holds a proposer slot and answers sync,
+    /// light-client and snapshot requests as configured
+    #[default]
+    Full,
+    /// Follows the chain from its peers without proposing blocks or serving anything to
+    /// them, keeping only the recent state
+    Light,
+    /// Follows the chain like `Light` and runs a `Watchtower` over it, raising the
+    /// configured alerts on divergence or censorship
+    Watchtower,
+}
+
+/// Sets up a node run inside the calling application, from `Node::builder()`
+#[derive(Debug, Clone)]
+pub struct NodeBuilder {
+    config: NodeConfig,
+    mode: NodeMode,
+    data_dir: PathBuf,
+    listen: Option<SocketAddr>,
+    peers: Vec<SocketAddr>,
+    block_time: Duration,
+    genesis_allocations: Vec<(Vec<u8>, u64)>,
+    genesis_validators: Vec<Vec<u8>>,
+    params: ParamSchedule,
+}
+
+impl Default for NodeBuilder {
+    fn default() -> Self {
+        Self {
+            config: NodeConfig::default(),
+            mode: NodeMode::Full,
+            data_dir: PathBuf::from("./data"),
+            listen: None,
+            peers: Vec::new(),
+            block_time: DEFAULT_BLOCK_TIME,
+            genesis_allocations: Vec::new(),
+            genesis_validators: Vec::new(),
+            params: ParamSchedule::default(),
+        }
+    }
+}
+
+impl NodeBuilder {
+    /// Cache sizes, keys, checkpoint, path pin, metrics retention and networking are
+    /// taken from `config`; the web sections are left to the application
+    pub fn config(mut self, config: NodeConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn mode(mut self, mode: NodeMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn data_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.data_dir = dir.into();
+        self
+    }
+
+    /// Accepts peers on `address`; without it the node only dials out
+    pub fn listen(mut self, address: SocketAddr) -> Self {
+        self.listen = Some(address);
+        self
+    }
+
+    /// Dials `address` on start; further peers are found through peer exchange. Without
+    /// any, the node dials the bootstrap peers of the configured network
+    pub fn peer(mut self, address: SocketAddr) -> Self {
+        self.peers.push(address);
+        self
+    }
+
+    pub fn block_time(mut self, interval: Duration) -> Self {
+        self.block_time = interval;
+        self
+    }
+
+    /// Genesis written into a database holding no chain yet; an existing chain keeps
+    /// its own
+    pub fn genesis(mut self, allocations: Vec<(Vec<u8>, u64)>, validators: Vec<Vec<u8>>, params: ParamSchedule) -> Self {
+        self.genesis_allocations = allocations;
+        self.genesis_validators = validators;
+        self.params = params;
+        self
+    }
+
+    /// Opens the database and the node without starting any task
+    pub fn build(self) -> Result<EmbeddedNode, String> {
+        self.config.network.sync_serving.validate()?;
+        self.config.network.bandwidth.validate()?;
+        self.params.validate()?;
+        let path = self.data_dir.to_str().ok_or("Invalid data directory")?;
+        let db = BlockchainDB::new(path)?.with_cache_sizes(self.config.cache.blocks, self.config.cache.accounts);
+        self.config.network.id.claim(&db)?;
+        if db.block_count()? == 0 {
+            if !self.genesis_allocations.is_empty() {
+                db.store_genesis_allocations(&self.genesis_allocations)?;
+            }
+            if !self.genesis_validators.is_empty() {
+                db.store_genesis_validators(&self.genesis_validators)?;
+            }
+            db.store_chain_params(&self.params)?;
+        }
+
+        let metrics = MetricsCollector::from_config(&self.config.metrics, &db)?;
+        let mut node = Node::open(db)?
+            .with_keys(&self.config.keys)?
+            .with_metrics(Arc::new(Mutex::new(metrics)));
+        if let Some(checkpoint) = self.config.checkpoint.clone() {
+            node = node.with_checkpoint(checkpoint)?;
+        }
+        if let Some(pin) = &self.config.consensus.pin {
+            node = node.with_path_pin(pin)?;
+        }
+        #[cfg(feature = "ml")]
+        if let Some(model) = &self.config.consensus.model {
+            node = node.with_path_model(model)?;
+        }
+        let mut network = self.config.network.clone();
+        if self.mode != NodeMode::Full {
+            network.serve_light_clients = false;
+            network.serve_snapshots = false;
+        }
+        Ok(EmbeddedNode {
+            network: NetworkService::with_config(Arc::new(node), &network),
+            config: self.config,
+            mode: self.mode,
+            listen: self.listen,
+            peers: self.peers,
+            block_time: self.block_time,
+        })
+    }
+}
+
+/// A node opened by `NodeBuilder::build`, ready to `start`
+pub struct EmbeddedNode {
+    network: Arc<NetworkService>,
+    config: NodeConfig,
+    mode: NodeMode,
+    listen: Option<SocketAddr>,
+    peers: Vec<SocketAddr>,
+    block_time: Duration,
+}
+
+impl EmbeddedNode {
+    pub fn node(&self) -> &Arc<Node> {
+        self.network.node()
+    }
+
+    /// Listens, dials the configured peers and spawns the node's background tasks under
+    /// a supervisor, which stops them when the handle is shut down or dropped
+    pub async fn start(self) -> Result<NodeHandle, String> {
+        let address = match self.listen {
+            Some(address) => Some(self.network.listen(address).await?),
+            None => None,
+        };
+        for peer in &self.peers {
+            self.network.connect(*peer).await?;
+        }
+        if self.peers.is_empty() {
+            self.network.bootstrap().await;
+        }
+
+        let node = self.network.node().clone();
+        let supervisor = Supervisor::new();
+        let proposes = node.validator_id().is_some() || node.proposer_for(node.next_height()?)?.is_none();
+        if self.mode == NodeMode::Full && proposes {
+            let network = self.network.clone();
+            let block_time = self.block_time;
+            supervisor.spawn("proposer", RestartPolicy::Always, move || network.clone().run_proposer(block_time));
+        }
+        let chain = node.clone();
+        supervisor.spawn("mempool-gc", RestartPolicy::Always, move || chain.clone().run_mempool_gc(MEMPOOL_GC_INTERVAL));
+        let network = self.network.clone();
+        supervisor.spawn("peer-exchange", RestartPolicy::Always, move || network.clone().run_peer_exchange(PEER_EXCHANGE_INTERVAL));
+        let chain = node.clone();
+        supervisor.spawn("state-pruning", RestartPolicy::Always, move || chain.clone().run_state_pruning(STATE_PRUNING_INTERVAL));
+
+        let watchtower = match self.mode {
+            NodeMode::Watchtower => Some(Arc::new(Watchtower::new(self.network.clone(), &self.config.watchtower)?)),
+            _ => None,
+        };
+        if let Some(watchtower) = &watchtower {
+            let watch = watchtower.clone();
+            let interval = Duration::from_secs(self.config.watchtower.interval_secs);
+            supervisor.spawn("watchtower", RestartPolicy::Always, move || watch.clone().run(interval));
+            if !self.config.alerts.rules.is_empty() {
+                let alerts = AlertService::from_config(&self.config.alerts, Arc::new(ConsensusEngine::new()))
+                    .with_network(self.network.clone())
+                    .with_watchtower(watchtower.clone());
+                let alerts = Arc::new(alerts);
+                let interval = Duration::from_secs(self.config.alerts.interval_secs);
+                supervisor.spawn("alerts", RestartPolicy::Always, move || alerts.clone().run(interval));
+            }
+        }
+
+        Ok(NodeHandle { network: self.network, address, supervisor, watchtower })
+    }
+}
+
+/// Submits transactions to a running node's mempool and watches what enters and leaves it
+#[derive(Clone)]
+pub struct MempoolHandle {
+    node: Arc<Node>,
+}
+
+impl MempoolHandle {
+    pub fn submit(&self, tx: Transaction) -> Result<[u8; 32], String> {
+        self.node.submit_transaction(tx)
+    }
+
+    pub fn next_nonce(&self, address: &[u8]) -> u64 {
+        self.node.next_nonce(address)
+    }
+
+    pub fn len(&self) -> usize {
+        self.node.pending_transactions()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn summary(&self) -> MempoolSummary {
+        self.node.mempool_summary()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<MempoolUpdate> {
+        self.node.subscribe_mempool()
+    }
+}
+
+/// Read-only view of a running node's chain and head state
+#[derive(Clone)]
+pub struct ChainReader {
+    node: Arc<Node>,
+}
+
+impl ChainReader {
+    pub fn next_height(&self) -> u64 {
+        self.node.next_height().unwrap_or_default()
+    }
+
+    pub fn head(&self) -> ForkChoiceHead {
+        self.node.fork_choice_head()
+    }
+
+    pub fn block(&self, height: u64) -> Result<Option<Block>, String> {
+        self.node.db().get_block(height)
+    }
+
+    pub fn block_by_hash(&self, hash: &[u8; 32]) -> Result<Option<Block>, String> {
+        self.node.db().get_block_by_hash(hash)
+    }
+
+    /// Transaction with the height and index it was included at
+    pub fn transaction(&self, hash: &[u8; 32]) -> Result<Option<(Transaction, u64, usize)>, String> {
+        self.node.db().get_transaction(hash)
+    }
+
+    pub fn transaction_status(&self, hash: &[u8; 32]) -> Result<Option<TxStatus>, String> {
+        self.node.transaction_status(hash)
+    }
+
+    /// Account at the head of the chain
+    pub fn account(&self, address: &[u8]) -> Option<Account> {
+        self.node.account(address)
+    }
+}
+
+/// A started embedded node. Dropping it stops the node's tasks; the database stays
+/// open until the last clone of the node is gone
+pub struct NodeHandle {
+    network: Arc<NetworkService>,
+    address: Option<SocketAddr>,
+    supervisor: Supervisor,
+    watchtower: Option<Arc<Watchtower>>,
+}
+
+impl NodeHandle {
+    pub fn node(&self) -> &Arc<Node> {
+        self.network.node()
+    }
+
+    pub fn network(&self) -> &Arc<NetworkService> {
+        &self.network
+    }
+
+    /// Address peers reach the node on, if it listens
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.address
+    }
+
+    /// The node's watchtower, in `NodeMode::Watchtower`
+    pub fn watchtower(&self) -> Option<&Arc<Watchtower>> {
+        self.watchtower.as_ref()
+    }
+
+    pub fn mempool(&self) -> MempoolHandle {
+        MempoolHandle { node: self.node().clone() }
+    }
+
+    pub fn chain(&self) -> ChainReader {
+        ChainReader { node: self.node().clone() }
+    }
+
+    /// The node's blocks for async code, without blocking the runtime on the database
+    pub fn storage(&self) -> TriUnityStorage {
+        self.node().storage()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<NodeEvent> {
+        self.node().events().subscribe()
+    }
+
+    pub fn supervisor(&self) -> &Supervisor {
+        &self.supervisor
+    }
+
+    pub fn shutdown(&self) {
+        self.supervisor.shutdown();
+    }
+}
+
+impl Drop for NodeHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{QuantumKeyPair, QuantumSignature};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_embedded_node() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_embedded_node");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let keypair = QuantumKeyPair::generate();
+        let full = Node::builder()
+            .data_dir(temp_dir.join("full"))
+            .listen(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .block_time(Duration::from_millis(50))
+            .genesis(vec![(keypair.public_key().to_vec(), 1_000)], Vec::new(), ParamSchedule::default())
+            .build()
+            .unwrap()
+            .start()
+            .await
+            .unwrap();
+        let light = Node::builder()
+            .mode(NodeMode::Light)
+            .data_dir(temp_dir.join("light"))
+            .peer(full.address().unwrap())
+            .genesis(vec![(keypair.public_key().to_vec(), 1_000)], Vec::new(), ParamSchedule::default())
+            .build()
+            .unwrap()
+            .start()
+            .await
+            .unwrap();
+        assert_eq!(light.supervisor().health().len(), 3);
+
+        let mut events = full.subscribe();
+        let mut tx = Transaction::new(keypair.public_key().to_vec(), vec![0xaa; 32], 10, 1, 0, vec![], QuantumSignature::new(vec![]));
+        tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+        let hash = full.mempool().submit(tx).unwrap();
+        while !matches!(events.recv().await.unwrap(), NodeEvent::TxAccepted { hash: accepted } if accepted == hash) {}
+
+        // The full node seals it and the light node follows
+        let mut included = false;
+        for _ in 0..100 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            included = light.chain().transaction(&hash).unwrap().is_some();
+            if included {
+                break;
+            }
+        }
+        assert!(included);
+        assert!(full.mempool().is_empty());
+        assert_eq!(light.chain().account(&[0xaa; 32]).map(|account| account.balance), Some(10));
+        assert_eq!(full.chain().account(keypair.public_key()).unwrap().nonce, 1);
+
+        full.shutdown();
+        assert!(full.supervisor().health().len() == 4);
+        println!("   Embedded node working!");
+        drop((full, light));
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -0,0 +1,315 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::consensus::metrics::{ImportStage, MetricsCollector};
+use crate::crypto::verification::Subsystem;
+use crate::node::verified::VerifiedCache;
+use crate::storage::blocks::Transaction;
+use crate::storage::database::{BlockchainDB, WriteGate};
+
+/// Transactions per worker below which signatures are checked on the calling thread
+const PARALLEL_SIGNATURE_BATCH: usize = 32;
+
+/// Checks the signatures of every transaction, spreading large blocks over several
+/// threads. A signature depends on its transaction alone, so this needs no chain state,
+/// and transactions `verified` already holds are not checked again. On failure returns
+/// the index of the first bad transaction
+pub fn verify_signatures(transactions: &[Transaction], verified: &VerifiedCache) -> Result<(), (usize, String)> {
+    let parallelism = std::thread::available_parallelism().map_or(1, usize::from);
+    let workers = parallelism.min(transactions.len() / PARALLEL_SIGNATURE_BATCH).max(1);
+    if workers == 1 {
+        return check_signatures(transactions, 0, verified);
+    }
+    let chunk = transactions.len().div_ceil(workers);
+    std::thread::scope(|scope| {
+        let checks: Vec<_> = transactions
+            .chunks(chunk)
+            .enumerate()
+            .map(|(index, chunk_txs)| scope.spawn(move || check_signatures(chunk_txs, index * chunk, verified)))
+            .collect();
+        checks
+            .into_iter()
+            .try_for_each(|check| check.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+    })
+}
+
+fn check_signatures(transactions: &[Transaction], offset: usize, verified: &VerifiedCache) -> Result<(), (usize, String)> {
+    for (index, tx) in transactions.iter().enumerate() {
+        verified.check(tx, Subsystem::BlockValidation).map_err(|e| (offset + index, e))?;
+    }
+    Ok(())
+}
+
+/// Time one block import spent in each stage it reached
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StageTimes([Option<Duration>; 5]);
+
+impl StageTimes {
+    pub(crate) fn time<T>(&mut self, stage: ImportStage, work: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = work();
+        self.add(stage, started.elapsed());
+        result
+    }
+
+    pub(crate) fn add(&mut self, stage: ImportStage, elapsed: Duration) {
+        let slot = &mut self.0[stage as usize];
+        *slot = Some(slot.unwrap_or_default() + elapsed);
+    }
+
+    pub(crate) fn record(&self, metrics: &mut MetricsCollector, block_height: u64) {
+        for stage in ImportStage::ALL {
+            if let Some(elapsed) = self.0[stage as usize] {
+                metrics.record_import_stage(stage, block_height, elapsed);
+            }
+        }
+    }
+}
+
+/// Most recent refused blocks kept in the `bad_blocks` tree
+pub const MAX_BAD_BLOCKS: usize = 1_024;
+
+/// Block being imported and the peer that sent it, so a refusal can be attributed
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Origin<'a> {
+    pub(crate) hash: [u8; 32],
+    pub(crate) peer: Option<&'a [u8]>,
+}
+
+impl<'a> Origin<'a> {
+    /// Peer that sent the block with `hash`, if it is the one being imported
+    pub(crate) fn peer_of(&self, hash: &[u8; 32]) -> Option<&'a [u8]> {
+        self.peer.filter(|_| self.hash == *hash)
+    }
+}
+
+/// Why a block was refused on import
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BadBlockRecord {
+    /// `None` when the block could not be decoded
+    pub height: Option<u64>,
+    /// Block hash, or the SHA3 hash of bytes that did not decode
+    #[serde(with = "hex::serde")]
+    pub hash: [u8; 32],
+    pub stage: ImportStage,
+    pub reason: String,
+    /// Transaction that failed its signature check or execution
+    pub tx_index: Option<usize>,
+    /// Hex node id of the peer the block came from; `None` for local imports
+    pub peer: Option<String>,
+    pub rejected_at: u64,
+}
+
+impl BadBlockRecord {
+    pub fn new(height: Option<u64>, hash: [u8; 32], stage: ImportStage, reason: &str) -> Self {
+        let rejected_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        Self { height, hash, stage, reason: reason.to_string(), tx_index: None, peer: None, rejected_at }
+    }
+
+    pub fn with_tx_index(mut self, tx_index: Option<usize>) -> Self {
+        self.tx_index = tx_index;
+        self
+    }
+
+    pub fn with_peer(mut self, peer: Option<&[u8]>) -> Self {
+        self.peer = peer.map(hex::encode);
+        self
+    }
+
+    /// File name shared by the block and its record, without an extension
+    pub fn name(&self) -> String {
+        match self.height {
+            Some(height) => format!("{}-{}", height, hex::encode(self.hash)),
+            None => format!("undecoded-{}", hex::encode(self.hash)),
+        }
+    }
+}
+
+/// Refused blocks in their wire encoding with why they were refused, kept in the
+/// database by hash. Only the most recent `MAX_BAD_BLOCKS` are kept
+#[derive(Debug, Clone)]
+pub struct BadBlocks {
+    tree: sled::Tree,
+    writes: WriteGate,
+}
+
+impl BadBlocks {
+    pub fn open(db: &BlockchainDB) -> Result<Self, String> {
+        Ok(Self { tree: db.tree("bad_blocks")?, writes: db.writes().clone() })
+    }
+
+    pub fn insert(&self, record: &BadBlockRecord, bytes: &[u8]) -> Result<(), String> {
+        let _writing = self.writes.enter();
+        let value = bincode::serialize(&(record, bytes)).map_err(|e| e.to_string())?;
+        self.tree.insert(record.hash, value).map_err(|e| e.to_string())?;
+        if self.tree.len() > MAX_BAD_BLOCKS {
+            if let Some(oldest) = self.records()?.first() {
+                self.tree.remove(oldest.hash).map_err(|e| e.to_string())?;
+            }
+        }
+        self.tree.flush().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn get(&self, hash: &[u8; 32]) -> Result<Option<(BadBlockRecord, Vec<u8>)>, String> {
+        match self.tree.get(hash).map_err(|e| e.to_string())? {
+            Some(value) => bincode::deserialize(&value).map(Some).map_err(|e| format!("Corrupt bad block: {}", e)),
+            None => Ok(None),
+        }
+    }
+
+    /// Every record with its block, oldest first
+    pub fn entries(&self) -> Result<Vec<(BadBlockRecord, Vec<u8>)>, String> {
+        let mut entries = Vec::new();
+        for item in self.tree.iter() {
+            let (_, value) = item.map_err(|e| e.to_string())?;
+            entries.push(bincode::deserialize::<(BadBlockRecord, Vec<u8>)>(&value).map_err(|e| format!("Corrupt bad block: {}", e))?);
+        }
+        entries.sort_by_key(|(record, _)| (record.rejected_at, record.height, record.hash));
+        Ok(entries)
+    }
+
+    /// Every record, oldest first
+    pub fn records(&self) -> Result<Vec<BadBlockRecord>, String> {
+        Ok(self.entries()?.into_iter().map(|(record, _)| record).collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+}
+
+/// Directory refused blocks are written to for postmortems. Each block is written as
+/// `<name>.block` in its wire encoding with its record in `<name>.json`
+#[derive(Debug, Clone)]
+pub struct Quarantine {
+    dir: PathBuf,
+}
+
+impl Quarantine {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes the block and its record, returning the path of the block
+    pub fn store(&self, record: &BadBlockRecord, bytes: &[u8]) -> Result<PathBuf, String> {
+        std::fs::create_dir_all(&self.dir).map_err(|e| format!("Could not create {}: {}", self.dir.display(), e))?;
+        let path = self.dir.join(record.name());
+        let json = serde_json::to_string_pretty(record).map_err(|e| e.to_string())?;
+        std::fs::write(path.with_extension("json"), json + "\n").map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+        let block_path = path.with_extension("block");
+        std::fs::write(&block_path, bytes).map_err(|e| format!("Could not write {}: {}", block_path.display(), e))?;
+        Ok(block_path)
+    }
+
+    /// Every record in the directory, oldest first
+    pub fn records(&self) -> Result<Vec<BadBlockRecord>, String> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Ok(Vec::new());
+        };
+        let mut records = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path.extension().is_some_and(|extension| extension == "json") {
+                let contents = std::fs::read_to_string(&path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+                records.push(serde_json::from_str::<BadBlockRecord>(&contents).map_err(|e| format!("Invalid {}: {}", path.display(), e))?);
+            }
+        }
+        records.sort_by_key(|record| (record.rejected_at, record.height, record.hash));
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{QuantumKeyPair, QuantumSignature};
+    use crate::node::{BlockImport, Node};
+    use crate::storage::blocks::Block;
+    use crate::storage::database::BlockchainDB;
+
+    #[test]
+    fn test_import_pipeline() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_import");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let keypair = QuantumKeyPair::generate();
+        let open = |name: &str| {
+            let db = BlockchainDB::new(temp_dir.join(name).to_str().unwrap()).unwrap();
+            db.store_genesis_allocations(&[(keypair.public_key().to_vec(), 1_000_000)]).unwrap();
+            Node::open(db).unwrap()
+        };
+        let quarantine = Quarantine::new(temp_dir.join("quarantine"));
+        let (producer, importer) = (open("producer"), open("importer").with_bad_block_quarantine(quarantine.dir()));
+
+        // Enough transactions to spread the signature checks over several threads
+        let transactions: Vec<Transaction> = (0..PARALLEL_SIGNATURE_BATCH as u64 * 3)
+            .map(|nonce| {
+                let mut tx = Transaction::new(keypair.public_key().to_vec(), vec![0xcc; 32], 1, 1, nonce, vec![], QuantumSignature::new(vec![]));
+                tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+                tx
+            })
+            .collect();
+        let mut forged = transactions.clone();
+        forged[70].amount = 2;
+        assert_eq!(verify_signatures(&forged, &VerifiedCache::new(0)).unwrap_err().0, 70);
+        for tx in transactions {
+            producer.submit_transaction(tx).unwrap();
+        }
+        let block = producer.produce_block().unwrap();
+        assert_eq!(block.transaction_count(), 96);
+        let encoded = bincode::serialize(&block).unwrap();
+        assert_eq!(importer.import_encoded_block(&encoded).unwrap(), BlockImport::Imported);
+        assert_eq!(importer.import_block(&block).unwrap(), BlockImport::Known);
+        let stages = importer.import_metrics();
+        assert!(stages.iter().all(|stage| stage.samples >= 1), "{:?}", stages);
+
+        // Each stage records what it refuses; a block that merely does not connect is not refused
+        let next = producer.produce_block().unwrap();
+        let mut bad_merkle = next.clone();
+        bad_merkle.header.merkle_root = [1; 32];
+        let mut tampered = block.transactions.clone();
+        tampered[5].amount = 2;
+        let bad_signature = Block::new(block.header.previous_hash, tampered, 0, block.header.consensus_data.clone());
+        let mut bad_state = next.clone();
+        bad_state.header.state_root = [2; 32];
+        let mut orphan = next.clone();
+        orphan.header.previous_hash = [3; 32];
+        assert!(importer.import_encoded_block(b"not a block").is_err());
+        assert!(importer.import_block(&bad_merkle).is_err());
+        assert!(importer.import_block(&bad_signature).is_err());
+        assert!(importer.import_block(&bad_state).is_err());
+        assert!(matches!(importer.import_block(&orphan).unwrap(), BlockImport::Missing { .. }));
+        assert_eq!(importer.import_block(&next).unwrap(), BlockImport::Imported);
+        let mut overdraft = Transaction::new(keypair.public_key().to_vec(), vec![0xcc; 32], 10_000_000, 1, 96, vec![], QuantumSignature::new(vec![]));
+        overdraft.signature = keypair.sign(&overdraft.get_signing_data()).unwrap();
+        let overdrawn = Block::new(next.hash(), vec![overdraft], 2, next.header.consensus_data.clone());
+        assert!(importer.import_block_from(&overdrawn, &[9; 32]).is_err());
+
+        // Refused blocks are kept in the database with the failing transaction and sender
+        let mut records = importer.bad_blocks().records().unwrap();
+        assert_eq!(records, quarantine.records().unwrap());
+        records.sort_by_key(|record| (record.stage as usize, record.height));
+        let stages: Vec<ImportStage> = records.iter().map(|record| record.stage).collect();
+        assert_eq!(stages, vec![ImportStage::Decode, ImportStage::Header, ImportStage::Signature, ImportStage::Execute, ImportStage::Execute]);
+        assert_eq!((records[2].tx_index, records[3].tx_index, records[4].tx_index), (Some(5), None, Some(0)));
+        assert!(records[2].reason.starts_with("Transaction 5:"));
+        assert_eq!(records[3].hash, bad_state.hash());
+        assert_eq!((records[3].peer.as_deref(), records[4].peer.clone()), (None, Some(hex::encode([9; 32]))));
+        let (_, stored) = importer.bad_blocks().get(&bad_state.hash()).unwrap().unwrap();
+        assert_eq!(bincode::deserialize::<Block>(&stored).unwrap().hash(), bad_state.hash());
+        let stored = std::fs::read(quarantine.dir().join(records[4].name()).with_extension("block")).unwrap();
+        assert_eq!(bincode::deserialize::<Block>(&stored).unwrap().hash(), overdrawn.hash());
+
+        println!("   Block import pipeline working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -0,0 +1,209 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+
+use crate::storage::blocks::Block;
+
+/// Blocks waiting for their parent, across all peers
+pub const MAX_ORPHANS: usize = 512;
+
+/// Orphans one peer may have waiting, so a single peer cannot crowd out the rest
+pub const MAX_ORPHANS_PER_PEER: usize = 128;
+
+#[derive(Debug, Clone)]
+pub struct Orphan {
+    pub block: Block,
+    /// Peer the block came from, asked for its ancestors and blamed if it is invalid
+    pub peer: Option<Vec<u8>>,
+    /// First height the node lacked when the block arrived
+    pub expected: u64,
+}
+
+/// Blocks to ask `peer` for, from `from` on, before a pooled orphan can connect
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncRequest {
+    pub peer: Vec<u8>,
+    pub from: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct OrphanStats {
+    pub pooled: usize,
+    /// Orphans imported once their parent arrived
+    pub promoted: u64,
+    /// Orphans dropped to make room or refused over a peer's share
+    pub evicted: u64,
+}
+
+/// Blocks whose parent is not in the block tree yet, keyed by that parent so they can
+/// be imported the moment it arrives. Full pools drop their oldest orphan
+#[derive(Debug, Default)]
+pub struct OrphanPool {
+    by_parent: HashMap<[u8; 32], Vec<Orphan>>,
+    /// Hash and parent of every orphan, oldest first
+    arrival: VecDeque<([u8; 32], [u8; 32])>,
+    per_peer: HashMap<Vec<u8>, usize>,
+    promoted: u64,
+    evicted: u64,
+}
+
+impl OrphanPool {
+    pub fn len(&self) -> usize {
+        self.arrival.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arrival.is_empty()
+    }
+
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.arrival.iter().any(|(pooled, _)| pooled == hash)
+    }
+
+    /// Pools `orphan`. Returns false when it is already pooled or its peer has its share waiting
+    pub fn insert(&mut self, orphan: Orphan) -> bool {
+        let hash = orphan.block.hash();
+        if self.contains(&hash) {
+            return false;
+        }
+        if let Some(peer) = &orphan.peer {
+            if self.per_peer.get(peer).copied().unwrap_or_default() >= MAX_ORPHANS_PER_PEER {
+                self.evicted += 1;
+                return false;
+            }
+        }
+        if self.arrival.len() >= MAX_ORPHANS {
+            if let Some((oldest, parent)) = self.arrival.front().copied() {
+                self.remove(&oldest, &parent);
+                self.evicted += 1;
+            }
+        }
+        if let Some(peer) = &orphan.peer {
+            *self.per_peer.entry(peer.clone()).or_default() += 1;
+        }
+        let parent = orphan.block.header.previous_hash;
+        self.arrival.push_back((hash, parent));
+        self.by_parent.entry(parent).or_default().push(orphan);
+        true
+    }
+
+    fn remove(&mut self, hash: &[u8; 32], parent: &[u8; 32]) {
+        self.arrival.retain(|(pooled, _)| pooled != hash);
+        let Some(siblings) = self.by_parent.get_mut(parent) else {
+            return;
+        };
+        let removed = siblings.iter().position(|orphan| orphan.block.hash() == *hash).map(|index| siblings.remove(index));
+        if siblings.is_empty() {
+            self.by_parent.remove(parent);
+        }
+        if let Some(orphan) = removed {
+            self.release(&orphan);
+        }
+    }
+
+    fn release(&mut self, orphan: &Orphan) {
+        let Some(peer) = &orphan.peer else {
+            return;
+        };
+        if let Some(count) = self.per_peer.get_mut(peer) {
+            *count -= 1;
+            if *count == 0 {
+                self.per_peer.remove(peer);
+            }
+        }
+    }
+
+    /// Removes and returns the orphans whose parent is `parent`, oldest first
+    pub fn take_children(&mut self, parent: &[u8; 32]) -> Vec<Orphan> {
+        let children = self.by_parent.remove(parent).unwrap_or_default();
+        self.arrival.retain(|(_, pooled_parent)| pooled_parent != parent);
+        for child in &children {
+            self.release(child);
+        }
+        self.promoted += children.len() as u64;
+        children
+    }
+
+    /// Drops orphans at or below `height`, which can no longer join the finalized chain
+    pub fn prune_through(&mut self, height: u64) {
+        let stale: Vec<([u8; 32], [u8; 32])> = self.by_parent
+            .values()
+            .flatten()
+            .filter(|orphan| orphan.block.header.height <= height)
+            .map(|orphan| (orphan.block.hash(), orphan.block.header.previous_hash))
+            .collect();
+        for (hash, parent) in stale {
+            self.remove(&hash, &parent);
+            self.evicted += 1;
+        }
+    }
+
+    /// One request per parent that is neither in the block tree nor pooled, sent to the
+    /// peer of its oldest orphan
+    pub fn sync_requests(&self) -> Vec<SyncRequest> {
+        let mut requests: Vec<SyncRequest> = Vec::new();
+        for (_, parent) in &self.arrival {
+            if self.contains(parent) {
+                continue;
+            }
+            let Some(orphan) = self.by_parent.get(parent).and_then(|siblings| siblings.iter().find(|orphan| orphan.peer.is_some())) else {
+                continue;
+            };
+            let peer = orphan.peer.clone().unwrap_or_default();
+            if !requests.iter().any(|request| request.peer == peer && request.from == orphan.expected) {
+                requests.push(SyncRequest { peer, from: orphan.expected });
+            }
+        }
+        requests
+    }
+
+    pub fn stats(&self) -> OrphanStats {
+        OrphanStats { pooled: self.len(), promoted: self.promoted, evicted: self.evicted }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::blocks::ConsensusData;
+
+    #[test]
+    fn test_orphan_pool() {
+        let parent = Block::new([9; 32], vec![], 5, ConsensusData::default());
+        let child = Block::new(parent.hash(), vec![], 6, ConsensusData::default());
+        let grandchild = Block::new(child.hash(), vec![], 7, ConsensusData::default());
+        let orphan = |block: &Block, peer: u8| Orphan { block: block.clone(), peer: Some(vec![peer]), expected: 5 };
+
+        let mut pool = OrphanPool::default();
+        assert!(pool.insert(orphan(&grandchild, 1)));
+        assert!(pool.insert(orphan(&child, 2)));
+        assert!(!pool.insert(orphan(&child, 2)));
+        // Only the parent missing from both tree and pool is asked for
+        assert_eq!(pool.sync_requests(), vec![SyncRequest { peer: vec![2], from: 5 }]);
+
+        let children = pool.take_children(&parent.hash());
+        assert_eq!(children[0].block.hash(), child.hash());
+        assert_eq!(pool.take_children(&child.hash()).len(), 1);
+        assert!(pool.is_empty() && pool.take_children(&grandchild.hash()).is_empty());
+        pool.insert(orphan(&grandchild, 1));
+        pool.prune_through(7);
+        assert!(pool.is_empty());
+
+        // A peer over its share is refused and a full pool drops its oldest orphan
+        let first = Block::new([1; 32], vec![], 0, ConsensusData::default());
+        pool.insert(orphan(&first, 3));
+        for height in 1..MAX_ORPHANS_PER_PEER as u64 + 1 {
+            pool.insert(orphan(&Block::new([1; 32], vec![], height, ConsensusData::default()), 3));
+        }
+        assert_eq!(pool.len(), MAX_ORPHANS_PER_PEER);
+        for peer in 4..8 {
+            for height in 0..MAX_ORPHANS_PER_PEER as u64 {
+                pool.insert(orphan(&Block::new([peer; 32], vec![], height, ConsensusData::default()), peer));
+            }
+        }
+        assert_eq!(pool.len(), MAX_ORPHANS);
+        assert!(!pool.contains(&first.hash()));
+        assert_eq!(pool.stats(), OrphanStats { pooled: MAX_ORPHANS, promoted: 2, evicted: 2 + MAX_ORPHANS_PER_PEER as u64 });
+
+        println!("   Orphan pool working!");
+    }
+}
@@ -0,0 +1,133 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::storage::cache::LruCache;
+
+/// Block and transaction hashes remembered by default, each
+pub const DEFAULT_SEEN_CAPACITY: usize = 16_384;
+
+/// How long a hash counts as seen after it was last marked
+pub const DEFAULT_SEEN_TTL: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeenKind {
+    Block,
+    Transaction,
+}
+
+/// Duplicates caught for one kind of object, and the hashes remembered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SeenStats {
+    pub duplicates: u64,
+    pub entries: usize,
+    pub capacity: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct DuplicateMetrics {
+    pub blocks: SeenStats,
+    pub transactions: SeenStats,
+}
+
+#[derive(Debug)]
+struct SeenSet {
+    marked: LruCache<[u8; 32], Instant>,
+    duplicates: u64,
+}
+
+/// Hashes of blocks and transactions the node has already taken in, from gossip, sync
+/// or RPC alike, so a copy arriving by another path is turned away before its
+/// signatures are checked or it is executed again. Entries expire after the TTL and
+/// the least recently marked go first once the cache is full
+#[derive(Debug)]
+pub struct SeenCache {
+    ttl: Duration,
+    blocks: Mutex<SeenSet>,
+    transactions: Mutex<SeenSet>,
+}
+
+impl Default for SeenCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEEN_CAPACITY, DEFAULT_SEEN_TTL)
+    }
+}
+
+impl SeenCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        let set = || Mutex::new(SeenSet { marked: LruCache::new(capacity), duplicates: 0 });
+        Self { ttl, blocks: set(), transactions: set() }
+    }
+
+    fn set(&self, kind: SeenKind) -> &Mutex<SeenSet> {
+        match kind {
+            SeenKind::Block => &self.blocks,
+            SeenKind::Transaction => &self.transactions,
+        }
+    }
+
+    /// Whether `hash` was marked within the TTL, counting it as a duplicate if so
+    pub fn is_duplicate(&self, kind: SeenKind, hash: &[u8; 32]) -> bool {
+        let mut set = self.set(kind).lock().unwrap();
+        let duplicate = set.marked.get(hash).is_some_and(|marked| marked.elapsed() < self.ttl);
+        set.duplicates += u64::from(duplicate);
+        duplicate
+    }
+
+    /// Marks `hash` as seen. Returns false, counting a duplicate, if it already was
+    pub fn mark(&self, kind: SeenKind, hash: [u8; 32]) -> bool {
+        let mut set = self.set(kind).lock().unwrap();
+        if set.marked.get(&hash).is_some_and(|marked| marked.elapsed() < self.ttl) {
+            set.duplicates += 1;
+            return false;
+        }
+        set.marked.insert(hash, Instant::now());
+        true
+    }
+
+    /// Lets `hash` in again, after it was refused or dropped
+    pub fn forget(&self, kind: SeenKind, hash: &[u8; 32]) {
+        self.set(kind).lock().unwrap().marked.remove(hash);
+    }
+
+    pub fn metrics(&self) -> DuplicateMetrics {
+        let stats = |kind| {
+            let set = self.set(kind).lock().unwrap();
+            let cache = set.marked.stats();
+            SeenStats { duplicates: set.duplicates, entries: cache.entries, capacity: cache.capacity }
+        };
+        DuplicateMetrics { blocks: stats(SeenKind::Block), transactions: stats(SeenKind::Transaction) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seen_cache() {
+        let seen = SeenCache::new(2, Duration::from_millis(50));
+        assert!(seen.mark(SeenKind::Block, [1; 32]));
+        assert!(!seen.mark(SeenKind::Block, [1; 32]));
+        assert!(seen.is_duplicate(SeenKind::Block, &[1; 32]));
+        // Blocks and transactions are remembered apart
+        assert!(!seen.is_duplicate(SeenKind::Transaction, &[1; 32]));
+        assert!(seen.mark(SeenKind::Transaction, [1; 32]));
+
+        // The least recently marked hash goes first, and a forgotten one is let in again
+        seen.mark(SeenKind::Block, [2; 32]);
+        seen.mark(SeenKind::Block, [3; 32]);
+        assert!(!seen.is_duplicate(SeenKind::Block, &[1; 32]));
+        seen.forget(SeenKind::Block, &[3; 32]);
+        assert!(seen.mark(SeenKind::Block, [3; 32]));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(seen.mark(SeenKind::Block, [2; 32]));
+
+        let metrics = seen.metrics();
+        assert_eq!(metrics.blocks, SeenStats { duplicates: 2, entries: 2, capacity: 2 });
+        assert_eq!(metrics.transactions.duplicates, 0);
+
+        println!("   Seen cache working!");
+    }
+}
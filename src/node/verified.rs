@@ -0,0 +1,106 @@
+use sha3::{Digest, Sha3_256};
+use std::sync::Mutex;
+
+use crate::crypto::verification::Subsystem;
+use crate::storage::blocks::Transaction;
+use crate::storage::cache::{CacheStats, LruCache};
+
+/// Transactions remembered as correctly signed by default
+pub const DEFAULT_VERIFIED_CAPACITY: usize = 32_768;
+
+/// Transaction hash and a digest of its signer
+type VerifiedKey = ([u8; 32], [u8; 32]);
+
+/// Transactions whose signatures already checked out, keyed by transaction hash and
+/// signer, so a block carrying transactions the mempool admitted does not verify them
+/// again. The hash covers every signature, so an entry can be forgotten at any time
+/// but never turns wrong; transactions leave once finalized or dropped, and the least
+/// recently used go first when the cache is full
+#[derive(Debug)]
+pub struct VerifiedCache {
+    entries: Mutex<LruCache<VerifiedKey, ()>>,
+}
+
+impl Default for VerifiedCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_VERIFIED_CAPACITY)
+    }
+}
+
+impl VerifiedCache {
+    /// A capacity of 0 verifies every transaction every time
+    pub fn new(capacity: usize) -> Self {
+        Self { entries: Mutex::new(LruCache::new(capacity)) }
+    }
+
+    fn key(tx: &Transaction) -> VerifiedKey {
+        (tx.hash(), Sha3_256::digest(&tx.from).into())
+    }
+
+    /// `tx.check_as(subsystem)`, skipped for a transaction already verified and
+    /// remembered once it passes
+    pub fn check(&self, tx: &Transaction, subsystem: Subsystem) -> Result<(), String> {
+        let key = Self::key(tx);
+        if self.entries.lock().unwrap().get(&key).is_some() {
+            return Ok(());
+        }
+        tx.check_as(subsystem)?;
+        self.entries.lock().unwrap().insert(key, ());
+        Ok(())
+    }
+
+    pub fn forget(&self, tx: &Transaction) {
+        self.entries.lock().unwrap().remove(&Self::key(tx));
+    }
+
+    /// Forgets the transactions with these hashes, whoever signed them
+    pub fn forget_hashes(&self, hashes: &[[u8; 32]]) {
+        if !hashes.is_empty() {
+            self.entries.lock().unwrap().retain(|(hash, _)| !hashes.contains(hash));
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.entries.lock().unwrap().stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::verification;
+    use crate::crypto::{QuantumKeyPair, QuantumSignature};
+
+    #[test]
+    fn test_verified_cache() {
+        let keypair = QuantumKeyPair::generate();
+        let mut tx = Transaction::new(keypair.public_key().to_vec(), vec![2; 32], 10, 1, 0, vec![], QuantumSignature::new(vec![]));
+        tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+        let cache = VerifiedCache::new(1);
+
+        // The second check is answered from the cache
+        cache.check(&tx, Subsystem::MempoolAdmission).unwrap();
+        let before = verification::counts().block_validation;
+        cache.check(&tx, Subsystem::BlockValidation).unwrap();
+        assert_eq!(cache.stats().hits, 1);
+        assert_eq!(cache.stats().entries, 1);
+
+        // A tampered copy hashes differently and is verified, and refused, on its own
+        let mut forged = tx.clone();
+        forged.amount = 1_000;
+        assert!(cache.check(&forged, Subsystem::BlockValidation).is_err());
+        assert!(verification::counts().block_validation > before);
+        assert_eq!(cache.stats().entries, 1);
+
+        cache.forget(&tx);
+        assert_eq!(cache.stats().entries, 0);
+        cache.check(&tx, Subsystem::BlockValidation).unwrap();
+        cache.forget_hashes(&[tx.hash()]);
+        assert_eq!(cache.stats().entries, 0);
+        let disabled = VerifiedCache::new(0);
+        disabled.check(&tx, Subsystem::BlockValidation).unwrap();
+        assert_eq!(disabled.stats().entries, 0);
+
+        println!("   Verified signature cache working!");
+    }
+}
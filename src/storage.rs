@@ -1,39 +1,133 @@
-use crate::blockchain::Block;
+pub mod ancient;
+pub mod availability;
+pub mod backup;
+pub mod batch;
+pub mod blocks;
+pub mod bloom;
+pub mod cache;
+pub mod database;
+pub mod envelope;
+pub mod merkle;
+pub mod names;
+pub mod schedule;
+pub mod sessions;
+pub mod state;
+pub mod state_store;
+pub mod trace;
+pub mod vesting;
+pub mod witness;
 
+use futures::future::{BoxFuture, FutureExt};
+
+use crate::node::blocking;
+use blocks::{Block, Transaction};
+use database::BlockchainDB;
+
+/// A canonical transaction with the height and index of its block
+pub type IncludedTransaction = (Transaction, u64, usize);
+
+/// Chain storage as async code sees it: the web server, the node's embedding handles
+/// and sync streams all read and write blocks through this, never touching sled on a
+/// runtime thread
+pub trait ChainStorage: Send + Sync {
+    /// Blocks on the canonical chain
+    fn block_count(&self) -> BoxFuture<'_, Result<u64, String>>;
+    fn latest_block(&self) -> BoxFuture<'_, Result<Option<Block>, String>>;
+    fn block(&self, height: u64) -> BoxFuture<'_, Result<Option<Block>, String>>;
+    /// Any stored block, canonical or not
+    fn block_by_hash(&self, hash: [u8; 32]) -> BoxFuture<'_, Result<Option<Block>, String>>;
+    fn transaction(&self, hash: [u8; 32]) -> BoxFuture<'_, Result<Option<IncludedTransaction>, String>>;
+    /// Stores `block` as the canonical block at its height
+    fn store_block(&self, block: Block) -> BoxFuture<'_, Result<(), String>>;
+}
+
+/// The chain database behind `ChainStorage`, running each call on the blocking pool.
+/// Clones share the database
+#[derive(Debug, Clone)]
 pub struct TriUnityStorage {
-    block_count: u64,
+    db: BlockchainDB,
 }
 
 impl TriUnityStorage {
-    pub async fn new(_data_dir: &str) -> Result<Self, String> {
-        if let Err(e) = tokio::fs::create_dir_all(_data_dir).await {
-            println!("Could not create data directory: {}", e);
-        }
-        
-        Ok(Self {
-            block_count: 847392,
+    /// Opens the database in `data_dir`, creating it if needed
+    pub async fn new(data_dir: &str) -> Result<Self, String> {
+        let path = data_dir.to_string();
+        let db = blocking(move || BlockchainDB::new(&path)).await.flatten()?;
+        Ok(Self { db })
+    }
+
+    /// Async access to an already open database, such as a node's
+    pub fn from_db(db: BlockchainDB) -> Self {
+        Self { db }
+    }
+
+    pub fn db(&self) -> &BlockchainDB {
+        &self.db
+    }
+
+    fn run<T: Send + 'static>(&self, read: impl FnOnce(&BlockchainDB) -> Result<T, String> + Send + 'static) -> BoxFuture<'_, Result<T, String>> {
+        let db = self.db.clone();
+        blocking(move || read(&db)).map(Result::flatten).boxed()
+    }
+}
+
+impl ChainStorage for TriUnityStorage {
+    fn block_count(&self) -> BoxFuture<'_, Result<u64, String>> {
+        self.run(|db| Ok(db.block_count()? as u64))
+    }
+
+    fn latest_block(&self) -> BoxFuture<'_, Result<Option<Block>, String>> {
+        self.run(|db| match db.block_count()? {
+            0 => Ok(None),
+            _ => db.get_block(db.get_latest_height()?),
         })
     }
-    
-    pub async fn store_block(&self, block: &Block) -> Result<(), String> {
-        println!("Stored block #{} with {} transactions", block.number, block.transactions.len());
-        Ok(())
-    }
-    
-    pub async fn get_block_count(&self) -> Result<u64, String> {
-        Ok(self.block_count)
-    }
-    
-    pub async fn get_latest_block(&self) -> Option<Block> {
-        Some(Block {
-            number: self.block_count,
-            timestamp: chrono::Utc::now().timestamp() as u64,
-            parent_hash: "previous_hash".to_string(),
-            transactions: vec![],
-            merkle_root: "merkle_root".to_string(),
-            nonce: 123456,
-            difficulty: 4,
-            hash: "current_hash".to_string(),
+
+    fn block(&self, height: u64) -> BoxFuture<'_, Result<Option<Block>, String>> {
+        self.run(move |db| db.get_block(height))
+    }
+
+    fn block_by_hash(&self, hash: [u8; 32]) -> BoxFuture<'_, Result<Option<Block>, String>> {
+        self.run(move |db| db.get_block_by_hash(&hash))
+    }
+
+    fn transaction(&self, hash: [u8; 32]) -> BoxFuture<'_, Result<Option<IncludedTransaction>, String>> {
+        self.run(move |db| db.get_transaction(&hash))
+    }
+
+    fn store_block(&self, block: Block) -> BoxFuture<'_, Result<(), String>> {
+        self.run(move |db| {
+            db.store_block_by_hash(&block)?;
+            db.store_block(&block)
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blocks::ConsensusData;
+
+    #[tokio::test]
+    async fn test_chain_storage() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_chain_storage");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let storage = TriUnityStorage::new(temp_dir.to_str().unwrap()).await.unwrap();
+        let storage: &dyn ChainStorage = &storage;
+        assert_eq!(storage.block_count().await.unwrap(), 0);
+        assert!(storage.latest_block().await.unwrap().is_none());
+
+        let genesis = Block::new([0; 32], vec![], 0, ConsensusData::default());
+        let next = Block::new(genesis.hash(), vec![], 1, ConsensusData::default());
+        storage.store_block(genesis.clone()).await.unwrap();
+        storage.store_block(next.clone()).await.unwrap();
+        assert_eq!(storage.block_count().await.unwrap(), 2);
+        assert_eq!(storage.latest_block().await.unwrap().unwrap().hash(), next.hash());
+        assert_eq!(storage.block(0).await.unwrap().unwrap().hash(), genesis.hash());
+        assert!(storage.block_by_hash(next.hash()).await.unwrap().is_some());
+        assert!(storage.transaction([9; 32]).await.unwrap().is_none());
+
+        println!("   Async chain storage working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
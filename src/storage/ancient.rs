@@ -0,0 +1,243 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+const DATA_FILE: &str = "blocks.dat";
+const INDEX_FILE: &str = "blocks.idx";
+
+/// Index file header: the height of the first entry
+const INDEX_HEADER_SIZE: u64 = 8;
+/// Offset into the data file and length of one block
+const INDEX_ENTRY_SIZE: u64 = 12;
+
+#[derive(Debug)]
+struct Files {
+    data: File,
+    index: File,
+    /// Height of the first block, known once one is appended
+    first: Option<u64>,
+    entries: Vec<(u64, u32)>,
+    data_len: u64,
+}
+
+/// Append-only flat files holding finalized blocks that no longer change: the encoded
+/// blocks back to back, and an index with the offset and length of each by height.
+/// Blocks move here from sled so the hot database only holds recent data
+#[derive(Debug)]
+pub struct AncientStore {
+    files: Mutex<Files>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AncientStats {
+    pub first_height: Option<u64>,
+    pub blocks: u64,
+    pub data_bytes: u64,
+}
+
+impl AncientStore {
+    /// Opens the store in `dir`, dropping a block whose append was interrupted
+    /// A store whose files are unlinked as soon as they are open, so it is gone once
+    /// dropped; for databases that only live in memory
+    pub fn temporary() -> Result<Self, String> {
+        static STORES: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("triunity_ancient_{}_{}", std::process::id(), STORES.fetch_add(1, Ordering::Relaxed));
+        let dir = std::env::temp_dir().join(name);
+        let store = Self::open(&dir)?;
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(store)
+    }
+
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+        let open = |name: &str| {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(dir.join(name))
+                .map_err(|e| format!("Could not open ancient {}: {}", name, e))
+        };
+        let (mut data, mut index) = (open(DATA_FILE)?, open(INDEX_FILE)?);
+
+        let mut raw = Vec::new();
+        index.read_to_end(&mut raw).map_err(|e| e.to_string())?;
+        let first = raw.get(..INDEX_HEADER_SIZE as usize).map(|header| u64::from_be_bytes(header.try_into().unwrap()));
+        let data_len = data.seek(SeekFrom::End(0)).map_err(|e| e.to_string())?;
+        let mut entries = Vec::new();
+        let mut end = 0;
+        for entry in raw.get(INDEX_HEADER_SIZE as usize..).unwrap_or_default().chunks_exact(INDEX_ENTRY_SIZE as usize) {
+            let offset = u64::from_be_bytes(entry[..8].try_into().unwrap());
+            let length = u32::from_be_bytes(entry[8..].try_into().unwrap());
+            if offset != end || offset + length as u64 > data_len {
+                break;
+            }
+            entries.push((offset, length));
+            end = offset + length as u64;
+        }
+
+        // Anything past the last whole entry is from an append that did not finish
+        let index_len = first.map_or(0, |_| INDEX_HEADER_SIZE + entries.len() as u64 * INDEX_ENTRY_SIZE);
+        if raw.len() as u64 != index_len {
+            index.set_len(index_len).map_err(|e| e.to_string())?;
+        }
+        if data_len != end {
+            data.set_len(end).map_err(|e| e.to_string())?;
+        }
+        Ok(Self { files: Mutex::new(Files { data, index, first, entries, data_len: end }) })
+    }
+
+    /// Height the next appended block must have; `None` while the store is empty
+    pub fn next_height(&self) -> Option<u64> {
+        let files = self.files.lock().unwrap();
+        files.first.map(|first| first + files.entries.len() as u64)
+    }
+
+    pub fn contains(&self, height: u64) -> bool {
+        let files = self.files.lock().unwrap();
+        files.first.is_some_and(|first| height >= first && height - first < files.entries.len() as u64)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> AncientStats {
+        let files = self.files.lock().unwrap();
+        AncientStats { first_height: files.first, blocks: files.entries.len() as u64, data_bytes: files.data_len }
+    }
+
+    /// Encoded block at `height`, if it has been moved here
+    pub fn get(&self, height: u64) -> Result<Option<Vec<u8>>, String> {
+        let mut files = self.files.lock().unwrap();
+        let Some(&(offset, length)) = files.first.and_then(|first| files.entries.get(height.checked_sub(first)? as usize)) else {
+            return Ok(None);
+        };
+        let mut bytes = vec![0; length as usize];
+        files.data.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+        files.data.read_exact(&mut bytes).map_err(|e| format!("Ancient block {} unreadable: {}", height, e))?;
+        Ok(Some(bytes))
+    }
+
+    /// Appends the encoded block at `height`, which must follow the last one. Both files
+    /// are synced before this returns, so callers may then delete their own copy
+    pub fn append(&self, height: u64, bytes: &[u8]) -> Result<(), String> {
+        let mut files = self.files.lock().unwrap();
+        let expected = files.first.map(|first| first + files.entries.len() as u64);
+        if expected.is_some_and(|expected| expected != height) {
+            return Err(format!("Ancient store expects block {}, not {}", expected.unwrap_or_default(), height));
+        }
+        let offset = files.data_len;
+        let length = u32::try_from(bytes.len()).map_err(|_| format!("Block {} is too large for the ancient store", height))?;
+        files.data.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+        files.data.write_all(bytes).map_err(|e| e.to_string())?;
+        files.data.sync_data().map_err(|e| e.to_string())?;
+
+        let mut entry = Vec::with_capacity((INDEX_HEADER_SIZE + INDEX_ENTRY_SIZE) as usize);
+        if files.first.is_none() {
+            entry.extend_from_slice(&height.to_be_bytes());
+        }
+        entry.extend_from_slice(&offset.to_be_bytes());
+        entry.extend_from_slice(&length.to_be_bytes());
+        files.index.seek(SeekFrom::End(0)).map_err(|e| e.to_string())?;
+        files.index.write_all(&entry).map_err(|e| e.to_string())?;
+        files.index.sync_data().map_err(|e| e.to_string())?;
+
+        files.first.get_or_insert(height);
+        files.entries.push((offset, length));
+        files.data_len = offset + length as u64;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::validate::{ChainValidator, ValidationMode};
+    use crate::crypto::QuantumSignature;
+    use crate::node::Node;
+    use crate::storage::blocks::{Block, ConsensusData, Transaction};
+    use crate::storage::database::BlockchainDB;
+
+    #[test]
+    fn test_ancient_store() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_ancient");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let store_dir = temp_dir.join("store");
+        let store = AncientStore::open(&store_dir).unwrap();
+        store.append(5, b"five").unwrap();
+        store.append(6, b"six").unwrap();
+        assert!(store.append(8, b"eight").is_err());
+        assert_eq!(store.get(6).unwrap().unwrap(), b"six");
+        assert_eq!(store.get(4).unwrap(), None);
+        assert_eq!(store.next_height(), Some(7));
+
+        // A torn append is dropped on reopen
+        drop(store);
+        let mut index = OpenOptions::new().append(true).open(store_dir.join(INDEX_FILE)).unwrap();
+        index.write_all(&[0, 0, 0]).unwrap();
+        let mut data = OpenOptions::new().append(true).open(store_dir.join(DATA_FILE)).unwrap();
+        data.write_all(b"sev").unwrap();
+        let store = AncientStore::open(&store_dir).unwrap();
+        assert_eq!(store.stats(), AncientStats { first_height: Some(5), blocks: 2, data_bytes: 7 });
+        store.append(7, b"seven").unwrap();
+        assert_eq!(store.get(7).unwrap().unwrap(), b"seven");
+
+        // Frozen blocks stay readable through the database by height, hash and transaction
+        let db_dir = temp_dir.join("db");
+        let db = BlockchainDB::new(db_dir.to_str().unwrap()).unwrap();
+        let mut blocks = Vec::new();
+        for height in 0..10u64 {
+            let tx = Transaction::new(vec![1; 32], vec![2; 32], height, 1, height, vec![], QuantumSignature::new(vec![]));
+            let parent = blocks.last().map_or([0; 32], Block::hash);
+            let block = Block::new(parent, vec![tx], height, ConsensusData::default());
+            db.store_block_by_hash(&block).unwrap();
+            db.store_block(&block).unwrap();
+            blocks.push(block);
+        }
+        assert_eq!(db.freeze_below(6).unwrap(), 6);
+        assert_eq!(db.freeze_below(6).unwrap(), 0);
+        assert_eq!(db.block_count().unwrap(), 10);
+        assert!(db.store_block(&blocks[3]).is_err());
+        assert!(db.truncate_above(4).is_err());
+        drop(db);
+
+        let db = BlockchainDB::new(db_dir.to_str().unwrap()).unwrap();
+        assert_eq!(db.ancient_stats().blocks, 6);
+        for block in &blocks {
+            let height = block.header.height;
+            assert_eq!(db.get_block(height).unwrap().unwrap().hash(), block.hash());
+            assert_eq!(db.get_block_by_hash(&block.hash()).unwrap().unwrap().header.height, height);
+            assert_eq!(db.get_transaction(&block.transactions[0].hash()).unwrap().unwrap().1, height);
+        }
+        db.truncate_above(7).unwrap();
+        assert_eq!(db.get_latest_height().unwrap(), 7);
+        assert_eq!(db.freeze_below(100).unwrap(), 2);
+        assert_eq!((db.get_latest_height().unwrap(), db.block_count().unwrap()), (7, 8));
+
+        // A node keeps producing, restarting and validating on top of frozen blocks
+        let node_dir = temp_dir.join("node");
+        let db = BlockchainDB::new(node_dir.to_str().unwrap()).unwrap();
+        let node = Node::open(db.clone()).unwrap().with_ancient_store(4);
+        for _ in 0..40 {
+            node.produce_block().unwrap();
+        }
+        assert_eq!(node.freeze_ancient_blocks().unwrap(), 3);
+        drop(node);
+        let node = Node::open(db.clone()).unwrap();
+        assert_eq!(node.produce_block().unwrap().header.height, 40);
+        let report = ChainValidator::new(db, ValidationMode::Full).run().unwrap();
+        assert!(report.is_valid(), "{:?}", report.failure);
+        assert_eq!(report.blocks_checked, 41);
+
+        println!("   Ancient store working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -0,0 +1,223 @@
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+use crate::storage::blocks::{BlockHeader, Transaction, AVAILABILITY_BLOCK_VERSION};
+use crate::storage::envelope;
+use crate::storage::merkle::{MerkleProof, MerkleTree};
+
+/// Chunks a body is cut into; any this many of its coded chunks rebuild it
+pub const DATA_CHUNKS: usize = 16;
+
+/// Parity chunks added to every body, so it is only lost once more than half of its
+/// chunks are withheld
+pub const PARITY_CHUNKS: usize = 16;
+
+pub const TOTAL_CHUNKS: usize = DATA_CHUNKS + PARITY_CHUNKS;
+
+/// Encoded body size from which produced blocks commit to their chunks by default
+pub const DEFAULT_AVAILABILITY_THRESHOLD: usize = 1024 * 1024;
+
+/// Chunks a light validator samples per block by default
+pub const DEFAULT_SAMPLES: usize = 16;
+
+struct BodyRef<'a>(&'a [Transaction]);
+
+impl Serialize for BodyRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        envelope::transactions::serialize(self.0, serializer)
+    }
+}
+
+#[derive(Deserialize)]
+struct Body(#[serde(deserialize_with = "envelope::transactions::deserialize")] Vec<Transaction>);
+
+/// Encoded size of a block body, as it is erasure coded
+pub fn body_size(transactions: &[Transaction]) -> usize {
+    bincode::serialized_size(&BodyRef(transactions)).unwrap_or_default() as usize
+}
+
+fn chunk_leaf(index: u32, body_len: u64, data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(index.to_be_bytes());
+    hasher.update(body_len.to_be_bytes());
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+fn coder() -> Result<ReedSolomon, String> {
+    ReedSolomon::new(DATA_CHUNKS, PARITY_CHUNKS).map_err(|e| format!("Erasure coder: {:?}", e))
+}
+
+/// One erasure-coded chunk of a block body with its proof against the header's availability root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataChunk {
+    pub index: u32,
+    /// Length of the body before coding, to strip the padding once it is rebuilt
+    pub body_len: u64,
+    pub data: Vec<u8>,
+    pub proof: MerkleProof,
+}
+
+impl DataChunk {
+    /// Checks the chunk is committed to by the availability root of `header`
+    pub fn verify(&self, header: &BlockHeader) -> Result<(), String> {
+        if header.version < AVAILABILITY_BLOCK_VERSION {
+            return Err(format!("Block {} does not commit to its chunks", header.height));
+        }
+        if self.proof.leaf_hash != chunk_leaf(self.index, self.body_len, &self.data) {
+            return Err(format!("Proof is for a different chunk than {}", self.index));
+        }
+        if self.proof.root != header.availability_root || !MerkleTree::verify_proof(&self.proof) {
+            return Err(format!("Chunk {} does not match the block's availability root", self.index));
+        }
+        Ok(())
+    }
+}
+
+/// A block body cut into `DATA_CHUNKS` equal chunks and extended with `PARITY_CHUNKS`
+/// Reed-Solomon parity chunks, committed to by a Merkle tree over all of them
+#[derive(Debug, Clone)]
+pub struct CodedBody {
+    body_len: u64,
+    chunks: Vec<Vec<u8>>,
+    tree: MerkleTree,
+}
+
+impl CodedBody {
+    pub fn encode(transactions: &[Transaction]) -> Result<Self, String> {
+        let body = bincode::serialize(&BodyRef(transactions)).map_err(|e| e.to_string())?;
+        let chunk_size = body.len().div_ceil(DATA_CHUNKS).max(1);
+        let mut chunks: Vec<Vec<u8>> = (0..TOTAL_CHUNKS)
+            .map(|index| {
+                let start = (index * chunk_size).min(body.len());
+                let mut chunk = body[start..(start + chunk_size).min(body.len())].to_vec();
+                chunk.resize(chunk_size, 0);
+                chunk
+            })
+            .collect();
+        coder()?.encode(&mut chunks).map_err(|e| format!("Could not erasure code the body: {:?}", e))?;
+
+        let body_len = body.len() as u64;
+        let leaves = chunks.iter().enumerate().map(|(index, chunk)| chunk_leaf(index as u32, body_len, chunk)).collect();
+        Ok(Self { body_len, chunks, tree: MerkleTree::from_leaves(leaves) })
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.tree.root()
+    }
+
+    pub fn chunk(&self, index: u32) -> Option<DataChunk> {
+        let data = self.chunks.get(index as usize)?.clone();
+        let proof = self.tree.generate_proof(index as usize)?;
+        Some(DataChunk { index, body_len: self.body_len, data, proof })
+    }
+}
+
+/// Availability root of a body; blocks below `AVAILABILITY_BLOCK_VERSION` carry zeroes instead
+pub fn availability_root(transactions: &[Transaction]) -> [u8; 32] {
+    CodedBody::encode(transactions).map(|body| body.root()).unwrap_or_default()
+}
+
+/// Rebuilds the body of the block with `header` from any `DATA_CHUNKS` of its chunks
+pub fn reconstruct(header: &BlockHeader, chunks: &[DataChunk]) -> Result<Vec<Transaction>, String> {
+    let mut shards: Vec<Option<Vec<u8>>> = vec![None; TOTAL_CHUNKS];
+    let mut body_len = None;
+    for chunk in chunks {
+        chunk.verify(header)?;
+        let shard = shards.get_mut(chunk.index as usize).ok_or_else(|| format!("No chunk {}", chunk.index))?;
+        *shard = Some(chunk.data.clone());
+        body_len = Some(chunk.body_len);
+    }
+    let held = shards.iter().flatten().count();
+    if held < DATA_CHUNKS {
+        return Err(format!("Rebuilding a body takes {} chunks, only {} held", DATA_CHUNKS, held));
+    }
+    coder()?.reconstruct_data(&mut shards).map_err(|e| format!("Could not rebuild the body: {:?}", e))?;
+
+    let mut body: Vec<u8> = shards.into_iter().take(DATA_CHUNKS).flatten().flatten().collect();
+    body.truncate(body_len.unwrap_or_default() as usize);
+    let Body(transactions) = bincode::deserialize(&body).map_err(|e| format!("Rebuilt body does not decode: {}", e))?;
+    Ok(transactions)
+}
+
+/// Distinct chunk indices to sample
+pub fn sample_indices(samples: usize) -> Vec<u32> {
+    rand::seq::index::sample(&mut rand::thread_rng(), TOTAL_CHUNKS, samples.min(TOTAL_CHUNKS))
+        .into_iter()
+        .map(|index| index as u32)
+        .collect()
+}
+
+/// Chance that a body whose `samples` distinct random chunks all checked out can be
+/// rebuilt. Withholding it takes hiding more than `PARITY_CHUNKS` chunks, and every
+/// sample must then have landed on one of the rest
+pub fn sampling_confidence(samples: usize) -> f64 {
+    let available = DATA_CHUNKS - 1;
+    let fooled: f64 = (0..samples.min(TOTAL_CHUNKS))
+        .map(|drawn| available.saturating_sub(drawn) as f64 / (TOTAL_CHUNKS - drawn) as f64)
+        .product();
+    1.0 - fooled
+}
+
+/// Outcome of sampling one block's chunks
+#[derive(Debug, Clone, Serialize)]
+pub struct AvailabilitySample {
+    pub height: u64,
+    pub requested: usize,
+    pub verified: usize,
+    /// Chance the body can be rebuilt, given the samples that checked out
+    pub confidence: f64,
+}
+
+impl AvailabilitySample {
+    /// Every requested chunk arrived and matched the header
+    pub fn is_available(&self) -> bool {
+        self.requested > 0 && self.verified == self.requested
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{QuantumKeyPair, QuantumSignature};
+    use crate::storage::blocks::{Block, ConsensusData};
+
+    #[test]
+    fn test_erasure_coded_body() {
+        let keypair = QuantumKeyPair::generate();
+        let transactions: Vec<Transaction> = (0..5)
+            .map(|nonce| {
+                let mut tx = Transaction::new(keypair.public_key().to_vec(), vec![7; 32], 10, 1, nonce, vec![nonce as u8; 300], QuantumSignature::new(vec![]));
+                tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+                tx
+            })
+            .collect();
+        let block = Block::new([0; 32], transactions.clone(), 3, ConsensusData::default()).with_availability();
+        assert_eq!(block.header.version, AVAILABILITY_BLOCK_VERSION);
+        assert!(block.has_valid_availability_root());
+        let decoded: Block = bincode::deserialize(&bincode::serialize(&block).unwrap()).unwrap();
+        assert_eq!(decoded.hash(), block.hash());
+
+        // Any half of the chunks rebuilds the body, parity chunks included
+        let coded = CodedBody::encode(&block.transactions).unwrap();
+        let chunks: Vec<DataChunk> = (0..TOTAL_CHUNKS as u32).filter(|index| index % 2 == 1).map(|index| coded.chunk(index).unwrap()).collect();
+        let rebuilt = reconstruct(&block.header, &chunks).unwrap();
+        assert_eq!(rebuilt.iter().map(Transaction::hash).collect::<Vec<_>>(), transactions.iter().map(Transaction::hash).collect::<Vec<_>>());
+        assert!(reconstruct(&block.header, &chunks[1..]).unwrap_err().contains("only 15 held"));
+
+        // A tampered chunk or a chunk of another block does not verify
+        let mut forged = coded.chunk(4).unwrap();
+        forged.data[0] ^= 1;
+        assert!(forged.verify(&block.header).is_err());
+        let other = Block::new([0; 32], transactions[1..].to_vec(), 3, ConsensusData::default()).with_availability();
+        assert!(coded.chunk(4).unwrap().verify(&other.header).is_err());
+        assert!(!Block { header: other.header.clone(), transactions }.has_valid_availability_root());
+
+        assert_eq!(sample_indices(8).len(), 8);
+        assert!(sampling_confidence(0) == 0.0 && sampling_confidence(16) > 0.9999);
+
+        println!("   Erasure-coded bodies working!");
+    }
+}
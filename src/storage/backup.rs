@@ -0,0 +1,345 @@
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::storage::database::BlockchainDB;
+
+/// Layout of the database trees, recorded in every backup. Bumped when a tree changes
+/// in a way a node reading the older layout would misread; a backup only restores into
+/// the version it was taken from
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Written last, so a backup without it was interrupted
+const MANIFEST_FILE: &str = "manifest.json";
+const ANCIENT_FILE: &str = "ancient.records";
+
+/// Entries written to sled per batch while restoring a tree
+const RESTORE_BATCH: usize = 4096;
+
+/// What a backup holds, with a checksum of every file in it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub schema_version: u32,
+    pub created_at: u64,
+    /// Latest canonical height when the backup was taken; `None` for a chain without blocks
+    pub latest_height: Option<u64>,
+    /// Hex genesis hash, which the restored database must reproduce
+    pub genesis_hash: String,
+    pub trees: Vec<BackupFile>,
+    /// Blocks moved out of sled into the ancient store
+    pub ancient: BackupFile,
+}
+
+/// One file of a backup: a sled tree or the ancient blocks, as length-prefixed records
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupFile {
+    /// Tree name, or `ancient`
+    pub name: String,
+    pub file: String,
+    pub records: u64,
+    pub bytes: u64,
+    /// Hex SHA3-256 of the file
+    pub sha3: String,
+}
+
+/// Progress of a backup or restore, one file at a time
+#[derive(Debug, Clone, Copy)]
+pub enum BackupStep<'a> {
+    Copied(&'a BackupFile),
+    Verified(&'a BackupFile),
+    Restored(&'a BackupFile),
+}
+
+impl fmt::Display for BackupStep<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (verb, file) = match self {
+            Self::Copied(file) => ("Copied", file),
+            Self::Verified(file) => ("Verified", file),
+            Self::Restored(file) => ("Restored", file),
+        };
+        write!(f, "{} {}: {} records, {} bytes", verb, file.name, file.records, file.bytes)
+    }
+}
+
+/// Copies every tree of `db` and its ancient blocks into the empty or missing directory
+/// `out`. Every write to the database waits until the copy is done, so the trees and
+/// the ancient blocks are one snapshot; callers backing up a running node also keep
+/// blocks from being imported, so no import is caught between two of its writes
+pub fn create(db: &BlockchainDB, out: &Path, mut progress: impl FnMut(BackupStep)) -> Result<BackupManifest, String> {
+    if fs::read_dir(out).is_ok_and(|mut entries| entries.next().is_some()) {
+        return Err(format!("{} is not empty", out.display()));
+    }
+    fs::create_dir_all(out).map_err(|e| format!("Could not create {}: {}", out.display(), e))?;
+    let _paused = db.writes().close();
+    db.flush()?;
+
+    let mut trees = Vec::new();
+    for (index, (name, tree)) in db.trees()?.into_iter().enumerate() {
+        let mut writer = RecordWriter::create(out, format!("tree-{:03}.records", index))?;
+        for entry in tree.iter() {
+            let (key, value) = entry.map_err(|e| e.to_string())?;
+            writer.record(&[&key, &value])?;
+        }
+        let file = writer.finish(name)?;
+        progress(BackupStep::Copied(&file));
+        trees.push(file);
+    }
+
+    let mut writer = RecordWriter::create(out, ANCIENT_FILE.to_string())?;
+    let stats = db.ancient_stats();
+    if let Some(first) = stats.first_height {
+        for height in first..first + stats.blocks {
+            let block = db.ancient().get(height)?.ok_or_else(|| format!("Ancient block {} is missing", height))?;
+            writer.record(&[&height.to_be_bytes(), &block])?;
+        }
+    }
+    let ancient = writer.finish("ancient".to_string())?;
+    progress(BackupStep::Copied(&ancient));
+
+    let manifest = BackupManifest {
+        schema_version: SCHEMA_VERSION,
+        created_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs()),
+        latest_height: match db.block_count()? {
+            0 => None,
+            _ => Some(db.get_latest_height()?),
+        },
+        genesis_hash: hex::encode(db.genesis_hash()?),
+        trees,
+        ancient,
+    };
+    let json = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+    let partial = out.join(format!("{}.partial", MANIFEST_FILE));
+    fs::write(&partial, json).map_err(|e| format!("Could not write the manifest: {}", e))?;
+    fs::rename(&partial, out.join(MANIFEST_FILE)).map_err(|e| format!("Could not write the manifest: {}", e))?;
+    Ok(manifest)
+}
+
+/// Reads the manifest of the backup in `dir` and checks its schema version and the size
+/// and checksum of every file, without touching any database
+pub fn verify(dir: &Path, mut progress: impl FnMut(BackupStep)) -> Result<BackupManifest, String> {
+    let path = dir.join(MANIFEST_FILE);
+    let json = fs::read(&path).map_err(|e| format!("No backup manifest at {}: {}", path.display(), e))?;
+    let manifest: BackupManifest = serde_json::from_slice(&json).map_err(|e| format!("Invalid backup manifest: {}", e))?;
+    if manifest.schema_version != SCHEMA_VERSION {
+        return Err(format!("Backup has schema version {}, this build reads {}", manifest.schema_version, SCHEMA_VERSION));
+    }
+    for file in manifest.trees.iter().chain([&manifest.ancient]) {
+        if Path::new(&file.file).file_name() != Some(file.file.as_ref()) {
+            return Err(format!("Backup file {} is outside the backup", file.file));
+        }
+        let mut reader = File::open(dir.join(&file.file)).map_err(|e| format!("Backup file {} is unreadable: {}", file.file, e))?;
+        let mut hasher = Sha3_256::new();
+        let bytes = std::io::copy(&mut reader, &mut hasher).map_err(|e| format!("Backup file {} is unreadable: {}", file.file, e))?;
+        if bytes != file.bytes || hex::encode(hasher.finalize()) != file.sha3 {
+            return Err(format!("Backup file {} does not match its checksum", file.file));
+        }
+        progress(BackupStep::Verified(file));
+    }
+    Ok(manifest)
+}
+
+/// Verifies the backup in `dir`, rebuilds the database from it next to `data_dir` and
+/// only then swaps it in, so a failed restore leaves `data_dir` as it was. Refuses a
+/// `data_dir` a running node has open
+pub fn restore(dir: &Path, data_dir: &Path, mut progress: impl FnMut(BackupStep)) -> Result<BackupManifest, String> {
+    let manifest = verify(dir, &mut progress)?;
+    if data_dir.exists() {
+        sled::Config::new()
+            .path(data_dir)
+            .open()
+            .map_err(|e| format!("{} is in use or unreadable, stop its node first: {}", data_dir.display(), e))?;
+    }
+
+    let staging = sibling(data_dir, "restoring")?;
+    let _ = fs::remove_dir_all(&staging);
+    let restored = rebuild(dir, &manifest, &staging, &mut progress);
+    if let Err(e) = restored {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    let replaced = sibling(data_dir, "replaced")?;
+    let _ = fs::remove_dir_all(&replaced);
+    if data_dir.exists() {
+        fs::rename(data_dir, &replaced).map_err(|e| format!("Could not move {} aside: {}", data_dir.display(), e))?;
+    }
+    if let Err(e) = fs::rename(&staging, data_dir) {
+        let _ = fs::rename(&replaced, data_dir);
+        return Err(format!("Could not move the restored database into place: {}", e));
+    }
+    let _ = fs::remove_dir_all(&replaced);
+    Ok(manifest)
+}
+
+/// Imports every file of a verified backup into a new database at `path`, checking the
+/// result has the backup's genesis
+fn rebuild(dir: &Path, manifest: &BackupManifest, path: &Path, progress: &mut impl FnMut(BackupStep)) -> Result<(), String> {
+    let db = BlockchainDB::new(path.to_str().ok_or("Invalid data directory")?)?;
+    for file in &manifest.trees {
+        let tree = db.tree(&file.name)?;
+        let mut batch = sled::Batch::default();
+        let mut pending = 0;
+        let count = read_records(&dir.join(&file.file), 2, |record| {
+            batch.insert(record[0].as_slice(), record[1].as_slice());
+            pending += 1;
+            if pending == RESTORE_BATCH {
+                tree.apply_batch(std::mem::take(&mut batch)).map_err(|e| e.to_string())?;
+                pending = 0;
+            }
+            Ok(())
+        })?;
+        tree.apply_batch(batch).map_err(|e| e.to_string())?;
+        if count != file.records {
+            return Err(format!("Backup of {} holds {} records, not {}", file.name, count, file.records));
+        }
+        progress(BackupStep::Restored(file));
+    }
+    read_records(&dir.join(&manifest.ancient.file), 2, |record| {
+        let height = u64::from_be_bytes(record[0].as_slice().try_into().map_err(|_| "Corrupt ancient record".to_string())?);
+        db.ancient().append(height, &record[1])
+    })?;
+    progress(BackupStep::Restored(&manifest.ancient));
+    db.flush()?;
+    if hex::encode(db.genesis_hash()?) != manifest.genesis_hash {
+        return Err("Restored database does not have the backup's genesis".to_string());
+    }
+    Ok(())
+}
+
+/// `<data_dir>.<suffix>`, next to `data_dir` so renaming between them stays on one disk
+fn sibling(data_dir: &Path, suffix: &str) -> Result<PathBuf, String> {
+    let name = data_dir.file_name().ok_or_else(|| format!("Invalid data directory {}", data_dir.display()))?;
+    Ok(data_dir.with_file_name(format!("{}.{}", name.to_string_lossy(), suffix)))
+}
+
+/// Writes records of length-prefixed fields, hashing them as they go
+struct RecordWriter {
+    file: String,
+    writer: BufWriter<File>,
+    hasher: Sha3_256,
+    records: u64,
+    bytes: u64,
+}
+
+impl RecordWriter {
+    fn create(dir: &Path, file: String) -> Result<Self, String> {
+        let writer = File::create(dir.join(&file)).map_err(|e| format!("Could not create {}: {}", file, e))?;
+        Ok(Self { file, writer: BufWriter::new(writer), hasher: Sha3_256::new(), records: 0, bytes: 0 })
+    }
+
+    fn record(&mut self, fields: &[&[u8]]) -> Result<(), String> {
+        for field in fields {
+            for bytes in [&(field.len() as u32).to_be_bytes()[..], field] {
+                self.writer.write_all(bytes).map_err(|e| format!("Could not write {}: {}", self.file, e))?;
+                self.hasher.update(bytes);
+                self.bytes += bytes.len() as u64;
+            }
+        }
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Syncs the file to disk and describes it
+    fn finish(self, name: String) -> Result<BackupFile, String> {
+        let file = self.writer.into_inner().map_err(|e| format!("Could not write {}: {}", self.file, e))?;
+        file.sync_all().map_err(|e| format!("Could not sync {}: {}", self.file, e))?;
+        Ok(BackupFile { name, file: self.file, records: self.records, bytes: self.bytes, sha3: hex::encode(self.hasher.finalize()) })
+    }
+}
+
+/// Calls `each` with every record of `fields` fields in `path`, returning how many there were
+fn read_records(path: &Path, fields: usize, mut each: impl FnMut(&[Vec<u8>]) -> Result<(), String>) -> Result<u64, String> {
+    let mut reader = BufReader::new(File::open(path).map_err(|e| format!("Could not open {}: {}", path.display(), e))?);
+    let mut count = 0;
+    loop {
+        let mut record = Vec::with_capacity(fields);
+        for field in 0..fields {
+            let mut length = [0; 4];
+            match reader.read_exact(&mut length) {
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof && field == 0 => return Ok(count),
+                result => result.map_err(|e| format!("Truncated record in {}: {}", path.display(), e))?,
+            }
+            let mut bytes = vec![0; u32::from_be_bytes(length) as usize];
+            reader.read_exact(&mut bytes).map_err(|e| format!("Truncated record in {}: {}", path.display(), e))?;
+            record.push(bytes);
+        }
+        each(&record)?;
+        count += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumKeyPair;
+    use crate::node::Node;
+
+    #[test]
+    fn test_backup_and_restore() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_backup");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let (data_dir, backup_dir) = (temp_dir.join("data"), temp_dir.join("backup"));
+        let keypair = QuantumKeyPair::generate();
+        let db = BlockchainDB::new(data_dir.to_str().unwrap()).unwrap();
+        db.store_genesis_allocations(&[(keypair.public_key().to_vec(), 1_000)]).unwrap();
+        let node = Node::open(db.clone()).unwrap();
+        for _ in 0..4 {
+            node.produce_block().unwrap();
+        }
+        db.freeze_below(2).unwrap();
+        assert_eq!(db.ancient_stats().blocks, 2);
+        let hashes: Vec<[u8; 32]> = (0..4).map(|height| db.get_block(height).unwrap().unwrap().hash()).collect();
+
+        // A backup only goes into an empty directory, and reports each file it copies
+        let mut steps = Vec::new();
+        let manifest = create(&db, &backup_dir, |step| steps.push(step.to_string())).unwrap();
+        assert_eq!((manifest.schema_version, manifest.latest_height, manifest.ancient.records), (SCHEMA_VERSION, Some(3), 2));
+        assert_eq!(steps.len(), manifest.trees.len() + 1);
+        assert!(steps.iter().any(|step| step.starts_with("Copied genesis: ")));
+        assert!(create(&db, &backup_dir, |_| {}).unwrap_err().contains("is not empty"));
+
+        // Writes wait while a backup holds the database
+        let paused = db.writes().close();
+        let writer = {
+            let db = db.clone();
+            std::thread::spawn(move || db.put_meta("during_backup", &1u8))
+        };
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(db.get_meta::<u8>("during_backup").unwrap(), None);
+        drop(paused);
+        writer.join().unwrap().unwrap();
+        assert_eq!(db.get_meta::<u8>("during_backup").unwrap(), Some(1));
+
+        // The node's open database is not replaced under it
+        assert!(restore(&backup_dir, &data_dir, |_| {}).unwrap_err().contains("stop its node first"));
+        drop(node);
+        drop(db);
+        fs::write(data_dir.join("stale"), b"stale").unwrap();
+        let mut restored = 0;
+        assert_eq!(restore(&backup_dir, &data_dir, |step| restored += matches!(step, BackupStep::Restored(_)) as usize).unwrap(), manifest);
+        assert_eq!(restored, manifest.trees.len() + 1);
+        assert!(!data_dir.join("stale").exists() && !sibling(&data_dir, "replaced").unwrap().exists());
+        let db = BlockchainDB::new(data_dir.to_str().unwrap()).unwrap();
+        assert_eq!((0..4).map(|height| db.get_block(height).unwrap().unwrap().hash()).collect::<Vec<_>>(), hashes);
+        assert_eq!((db.ancient_stats().blocks, hex::encode(db.genesis_hash().unwrap())), (2, manifest.genesis_hash.clone()));
+        drop(db);
+
+        // Tampered files and other schema versions are refused before anything is replaced
+        let tree = backup_dir.join(&manifest.trees[0].file);
+        let original = fs::read(&tree).unwrap();
+        fs::write(&tree, [&original[..], b"x"].concat()).unwrap();
+        assert!(restore(&backup_dir, &data_dir, |_| {}).unwrap_err().contains("does not match its checksum"));
+        fs::write(&tree, original).unwrap();
+        let newer = BackupManifest { schema_version: SCHEMA_VERSION + 1, ..manifest.clone() };
+        fs::write(backup_dir.join(MANIFEST_FILE), serde_json::to_vec(&newer).unwrap()).unwrap();
+        assert!(verify(&backup_dir, |_| {}).unwrap_err().contains("schema version 2"));
+        assert!(BlockchainDB::new(data_dir.to_str().unwrap()).unwrap().get_block(3).unwrap().is_some());
+
+        println!("   Backup and restore working!");
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+}
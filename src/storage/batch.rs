@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+use crate::storage::envelope::MAX_CALL_DATA;
+
+/// Address batch transactions are sent to. A `TxV1Batch` envelope is a transaction to
+/// this address carrying a bincode `BatchCall` and no value of its own; its operations
+/// run in order from the sender and either all apply or none do
+pub const BATCH: [u8; 32] = *b"triunity:system:batch:0000000001";
+
+/// Most operations one batch may hold
+pub const MAX_BATCH_OPERATIONS: usize = 16;
+
+/// Least fee a batch pays per operation, on top of which it is one transaction
+pub const BATCH_FEE_PER_OPERATION: u64 = 1;
+
+/// A transfer or call the batch's sender makes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchOperation {
+    #[serde(with = "hex::serde")]
+    pub to: Vec<u8>,
+    pub amount: u64,
+    #[serde(with = "hex::serde")]
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchCall {
+    pub operations: Vec<BatchOperation>,
+}
+
+impl BatchCall {
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap_or_default()
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        bincode::deserialize(data).map_err(|e| format!("Invalid batch: {}", e))
+    }
+
+    /// Total value the operations move
+    pub fn value(&self) -> u64 {
+        self.operations.iter().fold(0, |total, operation| total.saturating_add(operation.amount))
+    }
+
+    /// Least fee the batch must pay
+    pub fn min_fee(&self) -> u64 {
+        self.operations.len() as u64 * BATCH_FEE_PER_OPERATION
+    }
+
+    pub fn validate(&self, fee: u64) -> Result<(), String> {
+        if self.operations.is_empty() {
+            return Err("Batch holds no operations".to_string());
+        }
+        if self.operations.len() > MAX_BATCH_OPERATIONS {
+            return Err(format!("Batch of {} operations exceeds {}", self.operations.len(), MAX_BATCH_OPERATIONS));
+        }
+        if fee < self.min_fee() {
+            return Err(format!("A batch of {} operations needs a fee of at least {}", self.operations.len(), self.min_fee()));
+        }
+        if self.operations.iter().any(|operation| operation.to.is_empty()) {
+            return Err("Batch operation has no recipient".to_string());
+        }
+        if self.operations.iter().any(|operation| operation.to == BATCH) {
+            return Err("Batches cannot be nested".to_string());
+        }
+        let data: usize = self.operations.iter().map(|operation| operation.data.len()).sum();
+        if data > MAX_CALL_DATA {
+            return Err(format!("Batch call data of {} bytes exceeds {}", data, MAX_CALL_DATA));
+        }
+        Ok(())
+    }
+
+    /// Id the operation at `index` of the batch transaction `tx_hash` is known by, such
+    /// as for a transfer it schedules
+    pub fn operation_id(tx_hash: &[u8; 32], index: usize) -> [u8; 32] {
+        let mut hasher = Sha3_256::new();
+        hasher.update(tx_hash);
+        hasher.update((index as u32).to_be_bytes());
+        hasher.finalize().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{QuantumKeyPair, QuantumSignature};
+    use crate::storage::blocks::Transaction;
+    use crate::storage::envelope::TxEnvelope;
+    use crate::storage::names::{NameCall, NAME_PRICE, NAME_REGISTRY};
+    use crate::storage::state::StateManager;
+
+    #[test]
+    fn test_batch_transactions() {
+        let keypair = QuantumKeyPair::generate();
+        let sender = keypair.public_key().to_vec();
+        let (alice, bob) = (vec![0xaa; 32], vec![0xbb; 32]);
+        let mut state = StateManager::from_allocations(&[(sender.clone(), 100)]).unwrap();
+        let batch = |nonce: u64, operations: Vec<BatchOperation>| {
+            let call = BatchCall { operations };
+            let mut tx = Transaction::new(sender.clone(), BATCH.to_vec(), 0, call.min_fee(), nonce, call.encode(), QuantumSignature::new(vec![]));
+            tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+            tx
+        };
+        let pay = |to: &[u8], amount: u64| BatchOperation { to: to.to_vec(), amount, data: vec![] };
+
+        // One signature covers every operation, and the envelope keeps them in order
+        let register = NameCall::Register { name: "payroll.tri".to_string(), target: alice.clone() };
+        let operations = vec![pay(&alice, 30), pay(&bob, 20), BatchOperation { to: NAME_REGISTRY.to_vec(), amount: NAME_PRICE, data: register.encode() }];
+        let tx = batch(0, operations);
+        assert!(matches!(tx.envelope(), TxEnvelope::TxV1Batch(_)));
+        assert!(tx.check().is_ok());
+        assert_eq!(tx.value(), 60);
+        state.apply_transaction(&tx).unwrap();
+        let balance = |state: &StateManager, address: &[u8]| state.get_account(address).map_or(0, |account| account.balance);
+        assert_eq!((balance(&state, &sender), balance(&state, &alice), balance(&state, &bob)), (37, 30, 20));
+        assert_eq!(state.get_account(&sender).unwrap().nonce, 1);
+        assert!(state.names().resolve("payroll.tri", 0).is_some());
+
+        // A failing operation undoes the ones before it and the fee
+        let root = state.state_root();
+        let failing = batch(1, vec![pay(&alice, 10), pay(&bob, 30)]);
+        assert!(state.apply_transaction(&failing).unwrap_err().contains("Operation 1"));
+        assert_eq!(state.state_root(), root);
+
+        assert!(batch(1, vec![]).check().is_err());
+        assert!(batch(1, vec![pay(&BATCH, 1)]).check().is_err());
+        let mut underpaid = batch(1, vec![pay(&alice, 1), pay(&bob, 1)]);
+        underpaid.fee = 1;
+        assert!(underpaid.check().unwrap_err().contains("fee"));
+
+        println!("   Batch transactions working!");
+    }
+}
@@ -1,27 +1,105 @@
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::{SerializeStruct, Serializer};
 use serde::{Deserialize, Serialize};
-use crate::core::crypto::QuantumSignature;
+use crate::consensus::agreement::PathProposal;
+use crate::consensus::committee::CommitteeProof;
+use crate::crypto::canonical;
+use crate::crypto::verification::{self, Subsystem};
+use crate::crypto::QuantumSignature;
+use crate::storage::availability;
+use crate::storage::batch::{BatchCall, BATCH};
+use crate::storage::bloom::Bloom;
+use crate::storage::envelope::{self, TxEnvelope};
+use crate::storage::merkle::{MerkleProof, MerkleTree};
 use sha3::{Digest, Sha3_256};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Block {
     pub header: BlockHeader,
+    #[serde(with = "envelope::transactions")]
     pub transactions: Vec<Transaction>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// First header version whose fast-lane consensus data carries a committee proof.
+/// Older headers keep their original layout so stored chains still decode and link
+pub const COMMITTEE_BLOCK_VERSION: u32 = 2;
+
+/// First header version carrying feature signals; blocks signaling nothing stay below it
+pub const SIGNALING_BLOCK_VERSION: u32 = 3;
+
+/// First header version whose consensus data may name validators by their seat in a
+/// stored validator set instead of by public key
+pub const VALIDATOR_REF_BLOCK_VERSION: u32 = 4;
+
+/// First header version committing to the erasure-coded chunks of its body
+pub const AVAILABILITY_BLOCK_VERSION: u32 = 5;
+
+/// First header version that may propose a consensus path switch
+pub const PATH_BLOCK_VERSION: u32 = 6;
+
+/// Field order is the wire format, pinned by the `wire_encoding` test vectors
+#[derive(Debug, Clone)]
 pub struct BlockHeader {
     pub version: u32,
     pub previous_hash: [u8; 32],
     pub merkle_root: [u8; 32],
+    pub state_root: [u8; 32],
     pub timestamp: u64,
     pub height: u64,
     pub consensus_data: ConsensusData,
+    /// Addresses and topics touched by the block; `None` on blocks written before blooms
+    pub logs_bloom: Option<Bloom>,
+    /// Protocol feature deployments the proposer signals readiness for, one bit each;
+    /// only encoded from `SIGNALING_BLOCK_VERSION` on
+    pub signals: u32,
+    /// Root over the erasure-coded chunks of the body, see `storage::availability`;
+    /// only encoded from `AVAILABILITY_BLOCK_VERSION` on
+    pub availability_root: [u8; 32],
+    /// Consensus path the proposer asks the network to switch to; only encoded from
+    /// `PATH_BLOCK_VERSION` on
+    pub path_proposal: Option<PathProposal>,
+}
+
+impl BlockHeader {
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hasher = Sha3_256::new();
+        hasher.update(self.hashing_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Bytes a header is hashed over. Headers without a bloom keep the original layout
+    /// so existing chains still link; signals are always covered
+    pub fn hashing_bytes(&self) -> Vec<u8> {
+        match self.logs_bloom {
+            None if self.version < SIGNALING_BLOCK_VERSION => bincode::serialize(&(
+                self.version,
+                self.previous_hash,
+                self.merkle_root,
+                self.state_root,
+                self.timestamp,
+                self.height,
+                self.consensus_layout(),
+            )),
+            _ => bincode::serialize(self),
+        }
+        .unwrap_or_default()
+    }
+
+    /// Whether the block may touch any of `items`; blocks without a bloom always may
+    pub fn may_contain_any(&self, items: &[Vec<u8>]) -> bool {
+        self.logs_bloom.as_ref().is_none_or(|bloom| bloom.contains_any(items))
+    }
 }
 
+/// Variants encode by position, so new ones only ever go at the end
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ConsensusData {
-    FastLane { 
-        validator: Vec<u8> 
+    FastLane {
+        validator: Vec<u8>,
+        /// Places `validator` on its epoch's fast-lane committee; `None` before
+        /// `COMMITTEE_BLOCK_VERSION` and outside a validator set
+        #[serde(default)]
+        committee: Option<CommitteeProof>,
     },
     SecureLane { 
         validators: Vec<Vec<u8>> 
@@ -33,6 +111,82 @@ pub enum ConsensusData {
     Emergency { 
         authority_validators: Vec<Vec<u8>> 
     },
+    /// `FastLane` with the proposer named by reference
+    FastLaneRef {
+        validator: ValidatorRef,
+        committee: Option<CommitteeProof>,
+    },
+    SecureLaneRef {
+        validators: ValidatorSetRef,
+    },
+    HybridPathRef {
+        fast_validators: ValidatorSetRef,
+        secure_validators: ValidatorSetRef,
+    },
+    EmergencyRef {
+        authority_validators: ValidatorSetRef,
+    },
+}
+
+/// A validator named by its seat in a validator set the chain stores once per epoch,
+/// a few bytes where its Dilithium key would take kilobytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidatorRef {
+    pub set: [u8; 32],
+    pub index: u32,
+}
+
+/// Validators named by their seats in one stored validator set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidatorSetRef {
+    pub set: [u8; 32],
+    pub indices: Vec<u32>,
+}
+
+impl ValidatorSetRef {
+    fn resolve(&self, set: &[u8; 32], validators: &[Vec<u8>]) -> Result<Vec<Vec<u8>>, String> {
+        self.indices.iter().map(|index| ValidatorRef { set: self.set, index: *index }.resolve(set, validators)).collect()
+    }
+}
+
+impl ValidatorRef {
+    /// Key at this seat of `validators`, which must be the set `set` names
+    fn resolve(&self, set: &[u8; 32], validators: &[Vec<u8>]) -> Result<Vec<u8>, String> {
+        if self.set != *set {
+            return Err(format!("Validator set {} is not the epoch's", hex::encode(&self.set[..8])));
+        }
+        validators
+            .get(self.index as usize)
+            .cloned()
+            .ok_or_else(|| format!("Validator set has no seat {}", self.index))
+    }
+}
+
+impl ConsensusData {
+    /// Whether validators are named by reference rather than by key
+    pub fn is_referenced(&self) -> bool {
+        matches!(self, Self::FastLaneRef { .. } | Self::SecureLaneRef { .. } | Self::HybridPathRef { .. } | Self::EmergencyRef { .. })
+    }
+
+    /// The same data with each reference replaced by the key at its seat in
+    /// `validators`, the stored set `set` identifies. Data naming keys is returned as is
+    pub fn resolve(&self, set: &[u8; 32], validators: &[Vec<u8>]) -> Result<ConsensusData, String> {
+        Ok(match self {
+            Self::FastLaneRef { validator, committee } => Self::FastLane {
+                validator: validator.resolve(set, validators)?,
+                committee: committee.clone(),
+            },
+            Self::SecureLaneRef { validators: seats } => Self::SecureLane { validators: seats.resolve(set, validators)? },
+            Self::HybridPathRef { fast_validators, secure_validators } => Self::HybridPath {
+                fast_validators: fast_validators.resolve(set, validators)?,
+                secure_validators: secure_validators.resolve(set, validators)?,
+            },
+            Self::EmergencyRef { authority_validators } => Self::Emergency {
+                authority_validators: authority_validators.resolve(set, validators)?,
+            },
+            data => data.clone(),
+        })
+    }
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
@@ -42,9 +196,109 @@ pub struct Transaction {
     pub fee: u64,
     pub nonce: u64,
     pub data: Vec<u8>,
+    /// Last block height the transaction may be included at; `None` never expires
+    pub valid_until_height: Option<u64>,
+    /// Sponsor paying the fee instead of the sender
+    pub fee_payer: Option<FeePayer>,
+    /// Free-form note the sender signs along with the transfer, paid for by the byte
+    #[serde(default)]
+    pub memo: Option<String>,
+    pub signature: QuantumSignature,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeePayer {
+    pub address: Vec<u8>,
+    /// Sponsor's signature over the transaction's signing data
     pub signature: QuantumSignature,
 }
 
+/// Prefix separating a sponsor's signature from the sender's over the same payload
+const FEE_PAYER_DOMAIN: &[u8] = b"triunity/fee-payer";
+
+/// Prefix of the memo in the signing payload, so it cannot pass for a fee payer
+const MEMO_DOMAIN: &[u8] = b"triunity/memo";
+
+/// Longest memo a transaction may carry, in bytes of UTF-8
+pub const MAX_MEMO_BYTES: usize = 256;
+
+/// Least fee a memo adds per byte
+pub const MEMO_FEE_PER_BYTE: u64 = 1;
+
+/// `domain` of the JSON payloads a sender and a sponsor sign
+const JSON_SENDER_DOMAIN: &str = "triunity/transaction";
+const JSON_FEE_PAYER_DOMAIN: &str = "triunity/fee-payer";
+
+/// Signing payload as canonical JSON, for wallets that sign JSON. It names the same
+/// fields as the bincode payload, and a signature over either form is accepted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonSigningPayload {
+    /// Separates the sender's payload from the sponsor's
+    pub domain: String,
+    #[serde(with = "hex::serde")]
+    pub from: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub to: Vec<u8>,
+    pub amount: u64,
+    pub fee: u64,
+    pub nonce: u64,
+    #[serde(with = "hex::serde")]
+    pub data: Vec<u8>,
+    pub valid_until_height: Option<u64>,
+    /// Hex address of the sponsor
+    pub fee_payer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+impl JsonSigningPayload {
+    fn new(tx: &Transaction, domain: &str) -> Self {
+        Self {
+            domain: domain.to_string(),
+            from: tx.from.clone(),
+            to: tx.to.clone(),
+            amount: tx.amount,
+            fee: tx.fee,
+            nonce: tx.nonce,
+            data: tx.data.clone(),
+            valid_until_height: tx.valid_until_height,
+            fee_payer: tx.fee_payer.as_ref().map(|payer| hex::encode(&payer.address)),
+            memo: tx.memo.clone(),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        canonical::to_string(self).unwrap_or_default()
+    }
+
+    /// Reads a payload, which must already be canonical so a signature covers one text only
+    pub fn parse(json: &str) -> Result<Self, String> {
+        if !canonical::is_canonical(json) {
+            return Err("Signing payload is not canonical JSON".to_string());
+        }
+        let payload: Self = serde_json::from_str(json).map_err(|e| format!("Invalid signing payload: {}", e))?;
+        if payload.domain != JSON_SENDER_DOMAIN && payload.domain != JSON_FEE_PAYER_DOMAIN {
+            return Err(format!("Unknown signing domain {}", payload.domain));
+        }
+        Ok(payload)
+    }
+
+    /// The bincode payload the same fields give, `get_signing_data` or `fee_payer_signing_data`
+    pub fn bincode_payload(&self) -> Result<Vec<u8>, String> {
+        let mut tx = Transaction::new(self.from.clone(), self.to.clone(), self.amount, self.fee, self.nonce, self.data.clone(), QuantumSignature::new(vec![]));
+        tx.valid_until_height = self.valid_until_height;
+        tx.memo = self.memo.clone();
+        if let Some(address) = &self.fee_payer {
+            tx = tx.with_fee_payer(hex::decode(address).map_err(|e| format!("Invalid fee payer: {}", e))?);
+        }
+        Ok(match self.domain == JSON_FEE_PAYER_DOMAIN {
+            true => tx.fee_payer_signing_data(),
+            false => tx.get_signing_data(),
+        })
+    }
+}
+
 impl Block {
     pub fn new(
         previous_hash: [u8; 32],
@@ -53,18 +307,28 @@ impl Block {
         consensus_data: ConsensusData,
     ) -> Self {
         let merkle_root = Self::calculate_merkle_root(&transactions);
+        let logs_bloom = Some(Bloom::for_transactions(&transactions));
         let timestamp = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_secs();
 
+        let version = match consensus_data.is_referenced() {
+            true => VALIDATOR_REF_BLOCK_VERSION,
+            false => COMMITTEE_BLOCK_VERSION,
+        };
         let header = BlockHeader {
-            version: 1,
+            version,
             previous_hash,
             merkle_root,
+            state_root: [0; 32],
             timestamp,
             height,
             consensus_data,
+            logs_bloom,
+            signals: 0,
+            availability_root: [0; 32],
+            path_proposal: None,
         };
 
         Self {
@@ -73,6 +337,57 @@ impl Block {
         }
     }
 
+    pub fn with_state_root(mut self, state_root: [u8; 32]) -> Self {
+        self.header.state_root = state_root;
+        self
+    }
+
+    /// Signals the deployments in `signals`, moving the header to the version that carries them
+    pub fn with_signals(mut self, signals: u32) -> Self {
+        if signals != 0 {
+            self.header.version = self.header.version.max(SIGNALING_BLOCK_VERSION);
+        }
+        self.header.signals = signals;
+        self
+    }
+
+    /// Commits to the erasure-coded chunks of the body, so light validators can sample
+    /// its availability, moving the header to the version that carries the commitment
+    pub fn with_availability(mut self) -> Self {
+        self.header.version = self.header.version.max(AVAILABILITY_BLOCK_VERSION);
+        self.header.availability_root = availability::availability_root(&self.transactions);
+        self
+    }
+
+    /// Proposes switching the network to another consensus path, moving the header to the
+    /// version that carries proposals, which also commits to the body's chunks
+    pub fn with_path_proposal(self, proposal: PathProposal) -> Self {
+        let mut block = self.with_availability();
+        block.header.version = block.header.version.max(PATH_BLOCK_VERSION);
+        block.header.path_proposal = Some(proposal);
+        block
+    }
+
+    /// Checks a proposed path switch is within the bounds that justify it
+    pub fn check_path_proposal(&self) -> Result<(), String> {
+        match &self.header.path_proposal {
+            Some(proposal) => proposal.check().map_err(|e| format!("Block {}: {}", self.header.height, e)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn has_valid_availability_root(&self) -> bool {
+        self.header.version < AVAILABILITY_BLOCK_VERSION || self.header.availability_root == availability::availability_root(&self.transactions)
+    }
+
+    pub fn has_valid_merkle_root(&self) -> bool {
+        Self::calculate_merkle_root(&self.transactions) == self.header.merkle_root
+    }
+
+    pub fn has_valid_bloom(&self) -> bool {
+        self.header.logs_bloom.as_ref().is_none_or(|bloom| *bloom == Bloom::for_transactions(&self.transactions))
+    }
+
     fn calculate_merkle_root(transactions: &[Transaction]) -> [u8; 32] {
         if transactions.is_empty() {
             return [0; 32];
@@ -80,12 +395,7 @@ impl Block {
 
         let mut hashes: Vec<[u8; 32]> = transactions
             .iter()
-            .map(|tx| {
-                let tx_bytes = bincode::serialize(tx).unwrap_or_default();
-                let mut hasher = Sha3_256::new();
-                hasher.update(&tx_bytes);
-                hasher.finalize().into()
-            })
+            .map(Transaction::hash)
             .collect();
 
         while hashes.len() > 1 {
@@ -93,11 +403,11 @@ impl Block {
             
             for chunk in hashes.chunks(2) {
                 let mut hasher = Sha3_256::new();
-                hasher.update(&chunk[0]);
+                hasher.update(chunk[0]);
                 if chunk.len() > 1 {
-                    hasher.update(&chunk[1]);
+                    hasher.update(chunk[1]);
                 } else {
-                    hasher.update(&chunk[0]);
+                    hasher.update(chunk[0]);
                 }
                 next_level.push(hasher.finalize().into());
             }
@@ -109,10 +419,12 @@ impl Block {
     }
 
     pub fn hash(&self) -> [u8; 32] {
-        let header_bytes = bincode::serialize(&self.header).unwrap_or_default();
-        let mut hasher = Sha3_256::new();
-        hasher.update(&header_bytes);
-        hasher.finalize().into()
+        self.header.hash()
+    }
+
+    /// Proof that the transaction at `index` is committed to by `merkle_root`
+    pub fn transaction_proof(&self, index: usize) -> Option<MerkleProof> {
+        MerkleTree::from_leaves(self.transactions.iter().map(Transaction::hash).collect()).generate_proof(index)
     }
 
     pub fn validate(&self) -> bool {
@@ -120,7 +432,7 @@ impl Block {
             return false;
         }
         let calculated_root = Self::calculate_merkle_root(&self.transactions);
-        if calculated_root != self.header.merkle_root {
+        if calculated_root != self.header.merkle_root || !self.has_valid_bloom() {
             return false;
         }
         for transaction in &self.transactions {
@@ -141,7 +453,7 @@ impl Block {
         self.transactions.iter().map(|tx| tx.fee).sum()
     }
     pub fn total_amount(&self) -> u64 {
-        self.transactions.iter().map(|tx| tx.amount).sum()
+        self.transactions.iter().map(|tx| tx.value()).sum()
     }
 }
 
@@ -162,20 +474,110 @@ impl Transaction {
             fee,
             nonce,
             data,
+            valid_until_height: None,
+            fee_payer: None,
+            memo: None,
             signature,
         }
     }
+    /// Sets the expiry height; sign the transaction after calling this
+    pub fn with_valid_until(mut self, height: u64) -> Self {
+        self.valid_until_height = Some(height);
+        self
+    }
+    /// Names a sponsor for the fee; the sender then signs, followed by the sponsor
+    /// signing `fee_payer_signing_data`
+    pub fn with_fee_payer(mut self, address: Vec<u8>) -> Self {
+        self.fee_payer = Some(FeePayer {
+            address,
+            signature: QuantumSignature::new(vec![]),
+        });
+        self
+    }
+    /// Attaches a memo; sign the transaction after calling this
+    pub fn with_memo(mut self, memo: impl Into<String>) -> Self {
+        self.memo = Some(memo.into());
+        self
+    }
+    /// Value the sender moves: the amount, or what a batch's operations carry
+    pub fn value(&self) -> u64 {
+        match self.to == BATCH {
+            true => BatchCall::decode(&self.data).map_or(self.amount, |call| call.value()),
+            false => self.amount,
+        }
+    }
+    /// Least fee the memo requires
+    pub fn memo_fee(&self) -> u64 {
+        self.memo.as_ref().map_or(0, |memo| memo.len() as u64 * MEMO_FEE_PER_BYTE)
+    }
+    /// Account charged for the fee
+    pub fn fee_account(&self) -> &[u8] {
+        self.fee_payer.as_ref().map_or(&self.from, |payer| &payer.address)
+    }
+    pub fn fee_payer_signing_data(&self) -> Vec<u8> {
+        [FEE_PAYER_DOMAIN, &self.get_signing_data()].concat()
+    }
+    /// `get_signing_data` as canonical JSON, which the sender may sign instead
+    pub fn canonical_signing_json(&self) -> String {
+        JsonSigningPayload::new(self, JSON_SENDER_DOMAIN).encode()
+    }
+    /// `fee_payer_signing_data` as canonical JSON, which the sponsor may sign instead
+    pub fn canonical_fee_payer_json(&self) -> String {
+        JsonSigningPayload::new(self, JSON_FEE_PAYER_DOMAIN).encode()
+    }
+    pub fn is_expired_at(&self, height: u64) -> bool {
+        self.valid_until_height.is_some_and(|limit| height > limit)
+    }
     pub fn validate(&self) -> bool {
-        if self.from.is_empty() || self.to.is_empty() {
-            return false;
+        self.check().is_ok()
+    }
+    pub fn check(&self) -> Result<(), String> {
+        self.verify_signatures(None)
+    }
+    /// `check`, counting the signature verifications it performs towards `subsystem`
+    pub fn check_as(&self, subsystem: Subsystem) -> Result<(), String> {
+        self.verify_signatures(Some(subsystem))
+    }
+    fn verify_signatures(&self, subsystem: Option<Subsystem>) -> Result<(), String> {
+        let verify = |signature: &QuantumSignature, message: &[u8], public_key: &[u8]| {
+            if let Some(subsystem) = subsystem {
+                verification::record(subsystem, 1);
+            }
+            signature.verify(message, public_key)
+        };
+        self.envelope().validate()?;
+        self.validate_memo()?;
+        let signed = verify(&self.signature, &self.get_signing_data(), &self.from)
+            || verify(&self.signature, self.canonical_signing_json().as_bytes(), &self.from);
+        if !signed {
+            return Err("Invalid quantum signature".to_string());
         }
-
-        if self.amount == 0 && self.data.is_empty() {
-            return false;
+        if let Some(payer) = &self.fee_payer {
+            if payer.address == self.from {
+                return Err("Fee payer must differ from the sender".to_string());
+            }
+            let signed = verify(&payer.signature, &self.fee_payer_signing_data(), &payer.address)
+                || verify(&payer.signature, self.canonical_fee_payer_json().as_bytes(), &payer.address);
+            if !signed {
+                return Err("Invalid fee payer signature".to_string());
+            }
+        }
+        Ok(())
+    }
+    fn validate_memo(&self) -> Result<(), String> {
+        let Some(memo) = &self.memo else {
+            return Ok(());
+        };
+        if memo.len() > MAX_MEMO_BYTES {
+            return Err(format!("Memo of {} bytes exceeds {}", memo.len(), MAX_MEMO_BYTES));
+        }
+        if self.fee < self.memo_fee() {
+            return Err(format!("A {}-byte memo needs a fee of at least {}", memo.len(), self.memo_fee()));
         }
-        let tx_data = self.get_signing_data();
-        self.signature.verify(&tx_data, &self.from)
+        Ok(())
     }
+    /// Transactions without an expiry, sponsor or memo keep the original signing payload
+    /// so signatures made before those fields existed stay valid
     pub fn get_signing_data(&self) -> Vec<u8> {
         let signing_tx = (
             &self.from,
@@ -185,16 +587,31 @@ impl Transaction {
             self.nonce,
             &self.data,
         );
-        bincode::serialize(&signing_tx).unwrap_or_default()
+        let mut data = match self.valid_until_height {
+            None => bincode::serialize(&signing_tx).unwrap_or_default(),
+            Some(height) => bincode::serialize(&(signing_tx, height)).unwrap_or_default(),
+        };
+        if let Some(payer) = &self.fee_payer {
+            data.extend(bincode::serialize(&payer.address).unwrap_or_default());
+        }
+        if let Some(memo) = &self.memo {
+            data.extend(MEMO_DOMAIN);
+            data.extend(bincode::serialize(memo).unwrap_or_default());
+        }
+        data
+    }
+    /// Typed, versioned form used for serialization and per-type validation
+    pub fn envelope(&self) -> TxEnvelope {
+        TxEnvelope::from(self.clone())
     }
     pub fn hash(&self) -> [u8; 32] {
-        let tx_bytes = bincode::serialize(self).unwrap_or_default();
+        let tx_bytes = envelope::hashing_bytes(self);
         let mut hasher = Sha3_256::new();
         hasher.update(&tx_bytes);
         hasher.finalize().into()
     }
     pub fn size(&self) -> usize {
-        bincode::serialize(self).map(|data| data.len()).unwrap_or(0)
+        self.envelope().encode().len()
     }
     pub fn is_contract_call(&self) -> bool {
         !self.data.is_empty()
@@ -208,20 +625,208 @@ impl Default for ConsensusData {
     fn default() -> Self {
         Self::FastLane {
             validator: vec![0; 32],
+            committee: None,
+        }
+    }
+}
+
+/// `ConsensusData` as written before `COMMITTEE_BLOCK_VERSION`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum ConsensusDataV1 {
+    FastLane { validator: Vec<u8> },
+    SecureLane { validators: Vec<Vec<u8>> },
+    HybridPath { fast_validators: Vec<Vec<u8>>, secure_validators: Vec<Vec<u8>> },
+    Emergency { authority_validators: Vec<Vec<u8>> },
+}
+
+impl From<&ConsensusData> for ConsensusDataV1 {
+    fn from(data: &ConsensusData) -> Self {
+        match data.clone() {
+            ConsensusData::FastLane { validator, .. } => Self::FastLane { validator },
+            ConsensusData::SecureLane { validators } => Self::SecureLane { validators },
+            ConsensusData::HybridPath { fast_validators, secure_validators } => Self::HybridPath { fast_validators, secure_validators },
+            ConsensusData::Emergency { authority_validators } => Self::Emergency { authority_validators },
+            // References only exist from `VALIDATOR_REF_BLOCK_VERSION` on
+            ConsensusData::FastLaneRef { .. } => Self::FastLane { validator: Vec::new() },
+            ConsensusData::SecureLaneRef { .. } => Self::SecureLane { validators: Vec::new() },
+            ConsensusData::HybridPathRef { .. } => Self::HybridPath { fast_validators: Vec::new(), secure_validators: Vec::new() },
+            ConsensusData::EmergencyRef { .. } => Self::Emergency { authority_validators: Vec::new() },
+        }
+    }
+}
+
+impl From<ConsensusDataV1> for ConsensusData {
+    fn from(data: ConsensusDataV1) -> Self {
+        match data {
+            ConsensusDataV1::FastLane { validator } => Self::FastLane { validator, committee: None },
+            ConsensusDataV1::SecureLane { validators } => Self::SecureLane { validators },
+            ConsensusDataV1::HybridPath { fast_validators, secure_validators } => Self::HybridPath { fast_validators, secure_validators },
+            ConsensusDataV1::Emergency { authority_validators } => Self::Emergency { authority_validators },
+        }
+    }
+}
+
+/// Consensus data in the layout of its header's version
+enum ConsensusLayout<'a> {
+    V1(ConsensusDataV1),
+    Current(&'a ConsensusData),
+}
+
+impl Serialize for ConsensusLayout<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::V1(data) => data.serialize(serializer),
+            Self::Current(data) => data.serialize(serializer),
+        }
+    }
+}
+
+impl BlockHeader {
+    fn consensus_layout(&self) -> ConsensusLayout<'_> {
+        if self.version < COMMITTEE_BLOCK_VERSION {
+            ConsensusLayout::V1(ConsensusDataV1::from(&self.consensus_data))
+        } else {
+            ConsensusLayout::Current(&self.consensus_data)
         }
     }
 }
 
+const HEADER_FIELDS: &[&str] = &[
+    "version",
+    "previous_hash",
+    "merkle_root",
+    "state_root",
+    "timestamp",
+    "height",
+    "consensus_data",
+    "logs_bloom",
+    "signals",
+    "availability_root",
+    "path_proposal",
+];
+
+/// Binary formats get the consensus data layout of the header's version; readable ones
+/// such as JSON always show the current one
+impl Serialize for BlockHeader {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let consensus_data = match serializer.is_human_readable() {
+            true => ConsensusLayout::Current(&self.consensus_data),
+            false => self.consensus_layout(),
+        };
+        let mut header = serializer.serialize_struct("BlockHeader", HEADER_FIELDS.len())?;
+        header.serialize_field("version", &self.version)?;
+        header.serialize_field("previous_hash", &self.previous_hash)?;
+        header.serialize_field("merkle_root", &self.merkle_root)?;
+        header.serialize_field("state_root", &self.state_root)?;
+        header.serialize_field("timestamp", &self.timestamp)?;
+        header.serialize_field("height", &self.height)?;
+        header.serialize_field("consensus_data", &consensus_data)?;
+        header.serialize_field("logs_bloom", &self.logs_bloom)?;
+        if self.version >= SIGNALING_BLOCK_VERSION {
+            header.serialize_field("signals", &self.signals)?;
+        }
+        if self.version >= AVAILABILITY_BLOCK_VERSION {
+            header.serialize_field("availability_root", &self.availability_root)?;
+        }
+        if self.version >= PATH_BLOCK_VERSION {
+            header.serialize_field("path_proposal", &self.path_proposal)?;
+        }
+        header.end()
+    }
+}
+
+/// Field-by-field mirror of `BlockHeader` for readable formats
+#[derive(Deserialize)]
+struct ReadableHeader {
+    version: u32,
+    previous_hash: [u8; 32],
+    merkle_root: [u8; 32],
+    state_root: [u8; 32],
+    timestamp: u64,
+    height: u64,
+    consensus_data: ConsensusData,
+    logs_bloom: Option<Bloom>,
+    #[serde(default)]
+    signals: u32,
+    #[serde(default)]
+    availability_root: [u8; 32],
+    #[serde(default)]
+    path_proposal: Option<PathProposal>,
+}
+
+impl<'de> Deserialize<'de> for BlockHeader {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let header = ReadableHeader::deserialize(deserializer)?;
+            return Ok(Self {
+                version: header.version,
+                previous_hash: header.previous_hash,
+                merkle_root: header.merkle_root,
+                state_root: header.state_root,
+                timestamp: header.timestamp,
+                height: header.height,
+                consensus_data: header.consensus_data,
+                logs_bloom: header.logs_bloom,
+                signals: header.signals,
+                availability_root: header.availability_root,
+                path_proposal: header.path_proposal,
+            });
+        }
+        deserializer.deserialize_struct("BlockHeader", HEADER_FIELDS, BinaryHeaderVisitor)
+    }
+}
+
+/// Reads the version first to know which consensus data layout follows
+struct BinaryHeaderVisitor;
+
+impl<'de> Visitor<'de> for BinaryHeaderVisitor {
+    type Value = BlockHeader;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a block header")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<BlockHeader, A::Error> {
+        let missing = |index| de::Error::invalid_length(index, &"a block header");
+        let version: u32 = seq.next_element()?.ok_or_else(|| missing(0))?;
+        let previous_hash = seq.next_element()?.ok_or_else(|| missing(1))?;
+        let merkle_root = seq.next_element()?.ok_or_else(|| missing(2))?;
+        let state_root = seq.next_element()?.ok_or_else(|| missing(3))?;
+        let timestamp = seq.next_element()?.ok_or_else(|| missing(4))?;
+        let height = seq.next_element()?.ok_or_else(|| missing(5))?;
+        let consensus_data = if version < COMMITTEE_BLOCK_VERSION {
+            seq.next_element::<ConsensusDataV1>()?.map(ConsensusData::from)
+        } else {
+            seq.next_element()?
+        };
+        let consensus_data = consensus_data.ok_or_else(|| missing(6))?;
+        let logs_bloom = seq.next_element()?.ok_or_else(|| missing(7))?;
+        let signals = match version < SIGNALING_BLOCK_VERSION {
+            true => 0,
+            false => seq.next_element()?.ok_or_else(|| missing(8))?,
+        };
+        let availability_root = match version < AVAILABILITY_BLOCK_VERSION {
+            true => [0; 32],
+            false => seq.next_element()?.ok_or_else(|| missing(9))?,
+        };
+        let path_proposal = match version < PATH_BLOCK_VERSION {
+            true => None,
+            false => seq.next_element()?.ok_or_else(|| missing(10))?,
+        };
+        Ok(BlockHeader { version, previous_hash, merkle_root, state_root, timestamp, height, consensus_data, logs_bloom, signals, availability_root, path_proposal })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::crypto::QuantumKeyPair;
+    use crate::crypto::QuantumKeyPair;
 
     fn create_test_transaction() -> Transaction {
         let keypair = QuantumKeyPair::generate();
         let signing_data = (
             keypair.public_key(),
-            &vec![9, 8, 7, 6],
+            &vec![9u8, 8, 7, 6],
             1000u64,
             10u64,
             1u64,
@@ -252,8 +857,9 @@ mod tests {
             [1; 32],
             transactions.clone(),
             1,
-            ConsensusData::FastLane { 
-                validator: vec![1, 2, 3, 4] 
+            ConsensusData::FastLane {
+                validator: vec![1, 2, 3, 4],
+                committee: None,
             },
         );
 
@@ -272,9 +878,43 @@ mod tests {
 
     #[test]
     fn test_consensus_data() {
-        let fast_lane = ConsensusData::FastLane { validator: vec![1, 2, 3] };
+        let fast_lane = ConsensusData::FastLane { validator: vec![1, 2, 3], committee: None };
         let secure_lane = ConsensusData::SecureLane { validators: vec![vec![1], vec![2]] };
         let emergency = ConsensusData::Emergency { authority_validators: vec![vec![9]] };
+
+        // Headers from before committee proofs keep their layout and hash
+        let mut header = Block::new([1; 32], vec![], 1, fast_lane.clone()).header;
+        header.version = 1;
+        let v1 = bincode::serialize(&(
+            header.version,
+            header.previous_hash,
+            header.merkle_root,
+            header.state_root,
+            header.timestamp,
+            header.height,
+            ConsensusDataV1::FastLane { validator: vec![1, 2, 3] },
+            &header.logs_bloom,
+        )).unwrap();
+        assert_eq!(bincode::serialize(&header).unwrap(), v1);
+        let decoded: BlockHeader = bincode::deserialize(&v1).unwrap();
+        assert_eq!(decoded.hash(), header.hash());
+        let json: BlockHeader = serde_json::from_str(&serde_json::to_string(&header).unwrap()).unwrap();
+        assert_eq!(json.hash(), header.hash());
+        let current = Block::new([1; 32], vec![], 1, fast_lane.clone()).header;
+        assert!(bincode::serialize(&current).unwrap().len() > v1.len());
+        assert_eq!(bincode::deserialize::<BlockHeader>(&bincode::serialize(&current).unwrap()).unwrap().hash(), current.hash());
+
+        // Validators named by seat resolve against the set they point at, and only that set
+        let validators = vec![vec![7; 1312], vec![1, 2, 3]];
+        let referenced = ConsensusData::FastLaneRef { validator: ValidatorRef { set: [5; 32], index: 1 }, committee: None };
+        let header = Block::new([1; 32], vec![], 1, referenced.clone()).header;
+        assert_eq!(header.version, VALIDATOR_REF_BLOCK_VERSION);
+        assert!(bincode::serialize(&header).unwrap().len() < validators[0].len());
+        assert_eq!(bincode::deserialize::<BlockHeader>(&bincode::serialize(&header).unwrap()).unwrap().hash(), header.hash());
+        assert!(matches!(referenced.resolve(&[5; 32], &validators).unwrap(), ConsensusData::FastLane { validator, .. } if validator == vec![1, 2, 3]));
+        assert!(referenced.resolve(&[6; 32], &validators).is_err());
+        assert!(referenced.resolve(&[5; 32], &validators[..1]).is_err());
+        assert!(matches!(secure_lane.resolve(&[6; 32], &[]).unwrap(), ConsensusData::SecureLane { validators } if validators.len() == 2));
         
         println!("   Consensus data types working!");
         println!("   FastLane: {:?}", fast_lane);
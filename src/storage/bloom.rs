@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+use crate::storage::blocks::Transaction;
+
+/// Filter size in bits; must be a power of two
+pub const BLOOM_BITS: usize = 2048;
+const BLOOM_BYTES: usize = BLOOM_BITS / 8;
+const BLOOM_HASHES: usize = 3;
+
+/// Bytes of contract call data treated as the call's topic (its method selector)
+pub const TOPIC_LENGTH: usize = 4;
+
+/// Probabilistic set over the addresses and topics touched by a block. A miss
+/// proves absence; a hit only means the block is worth scanning
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bloom(Vec<u8>);
+
+impl Bloom {
+    pub fn new() -> Self {
+        Self(vec![0; BLOOM_BYTES])
+    }
+
+    pub fn for_transactions(transactions: &[Transaction]) -> Self {
+        let mut bloom = Self::new();
+        for tx in transactions {
+            for address in transaction_addresses(tx) {
+                bloom.insert(address);
+            }
+            if let Some(topic) = transaction_topic(tx) {
+                bloom.insert(topic);
+            }
+        }
+        bloom
+    }
+
+    pub fn insert(&mut self, item: &[u8]) {
+        for bit in Self::bits(item) {
+            self.0[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    pub fn contains(&self, item: &[u8]) -> bool {
+        self.0.len() == BLOOM_BYTES && Self::bits(item).all(|bit| self.0[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    pub fn contains_any(&self, items: &[Vec<u8>]) -> bool {
+        items.iter().any(|item| self.contains(item))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|byte| *byte == 0)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    fn bits(item: &[u8]) -> impl Iterator<Item = usize> {
+        let digest: [u8; 32] = Sha3_256::digest(item).into();
+        (0..BLOOM_HASHES).map(move |i| u16::from_be_bytes([digest[2 * i], digest[2 * i + 1]]) as usize % BLOOM_BITS)
+    }
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sender, recipient and fee payer of `tx`
+pub fn transaction_addresses(tx: &Transaction) -> impl Iterator<Item = &[u8]> {
+    [Some(tx.from.as_slice()), Some(tx.to.as_slice()), tx.fee_payer.as_ref().map(|payer| payer.address.as_slice())]
+        .into_iter()
+        .flatten()
+}
+
+/// Method selector of a contract call; transfers carry no topic
+pub fn transaction_topic(tx: &Transaction) -> Option<&[u8]> {
+    tx.data.get(..TOPIC_LENGTH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumSignature;
+
+    #[test]
+    fn test_bloom_membership() {
+        let call = Transaction::new(vec![1; 32], vec![2; 32], 0, 1, 0, vec![0xa9, 0x05, 0x9c, 0xbb, 0x00], QuantumSignature::new(vec![]));
+        let sponsored = Transaction::new(vec![3; 32], vec![4; 32], 5, 1, 0, vec![], QuantumSignature::new(vec![]))
+            .with_fee_payer(vec![5; 32]);
+        let bloom = Bloom::for_transactions(&[call, sponsored]);
+
+        for item in [&[1; 32][..], &[2; 32], &[3; 32], &[4; 32], &[5; 32], &[0xa9, 0x05, 0x9c, 0xbb]] {
+            assert!(bloom.contains(item));
+        }
+        let misses = (0..200u32).filter(|i| !bloom.contains(&i.to_be_bytes())).count();
+        assert!(misses > 190, "too many false positives: {}", 200 - misses);
+        assert!(Bloom::new().is_empty() && !Bloom::new().contains(&[1; 32]));
+
+        println!("   Block bloom filter working!");
+    }
+}
@@ -0,0 +1,139 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+use crate::storage::state::Account;
+
+/// Blocks kept decoded by default, by height
+pub const DEFAULT_BLOCK_CACHE: usize = 256;
+/// Account reads from the state trie kept by default, by height and address
+pub const DEFAULT_ACCOUNT_CACHE: usize = 4_096;
+
+/// Account reads keyed by state root height and address; `None` caches an absent account
+pub type AccountCache = LruCache<(u64, Vec<u8>), Option<Account>>;
+
+/// Hit and miss counts of the database read caches
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheMetrics {
+    pub blocks: CacheStats,
+    pub accounts: CacheStats,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub capacity: usize,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            reads => self.hits as f64 / reads as f64,
+        }
+    }
+}
+
+/// Least recently used cache. A capacity of 0 disables it
+#[derive(Debug)]
+pub struct LruCache<K, V> {
+    capacity: usize,
+    entries: HashMap<K, (V, u64)>,
+    /// Entries by the tick of their last use, oldest first
+    order: BTreeMap<u64, K>,
+    tick: u64,
+    stats: CacheStats,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> LruCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            stats: CacheStats { capacity, ..CacheStats::default() },
+        }
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        let Some((value, used)) = self.entries.get_mut(key) else {
+            self.stats.misses += 1;
+            return None;
+        };
+        self.stats.hits += 1;
+        self.tick += 1;
+        let key = self.order.remove(&*used).unwrap_or_else(|| key.clone());
+        *used = self.tick;
+        self.order.insert(self.tick, key);
+        Some(value.clone())
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some((_, used)) = self.entries.insert(key.clone(), (value, self.tick)) {
+            self.order.remove(&used);
+        }
+        self.order.insert(self.tick, key);
+        while self.entries.len() > self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+            self.stats.evictions += 1;
+        }
+    }
+
+    pub fn remove(&mut self, key: &K) {
+        if let Some((_, used)) = self.entries.remove(key) {
+            self.order.remove(&used);
+        }
+    }
+
+    /// Drops every entry `keep` rejects, such as those a reorg made stale
+    pub fn retain(&mut self, keep: impl Fn(&K) -> bool) {
+        self.entries.retain(|key, _| keep(key));
+        self.order.retain(|_, key| keep(key));
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats { entries: self.entries.len(), ..self.stats }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_cache() {
+        let mut cache = LruCache::new(2);
+        cache.insert(1, "one");
+        cache.insert(2, "two");
+        assert_eq!(cache.get(&1), Some("one"));
+        // 2 is now the least recently used
+        cache.insert(3, "three");
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&3), Some("three"));
+        cache.insert(3, "drei");
+        assert_eq!(cache.get(&3), Some("drei"));
+        cache.retain(|key| *key < 3);
+        assert_eq!(cache.get(&3), None);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.evictions, stats.entries), (3, 2, 1, 1));
+        assert_eq!(stats.hit_rate(), 0.6);
+
+        let mut disabled = LruCache::new(0);
+        disabled.insert(1, 1);
+        assert_eq!(disabled.get(&1), None);
+
+        println!("   LRU cache working!");
+    }
+}
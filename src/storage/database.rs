@@ -1,20 +1,112 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha3::{Digest, Sha3_256};
 use sled::Db;
-use crate::core::storage::Block;
+use std::sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use crate::consensus::gas::GasSchedule;
+use crate::consensus::params::ParamSchedule;
+use crate::storage::ancient::{AncientStats, AncientStore};
+use crate::storage::blocks::{Block, Transaction};
+use crate::storage::cache::{AccountCache, CacheMetrics, LruCache, DEFAULT_ACCOUNT_CACHE, DEFAULT_BLOCK_CACHE};
+use crate::storage::envelope;
 
 #[derive(Debug, Clone)]
 pub struct BlockchainDB {
     db: Db,
+    /// Finalized blocks moved out of sled by `freeze_below`
+    ancient: Arc<AncientStore>,
+    caches: Arc<Caches>,
+    writes: WriteGate,
+}
+
+/// Entered by every write to the database and closed for the length of a backup, so a
+/// backup copies the database as of one moment
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WriteGate(Arc<RwLock<()>>);
+
+impl WriteGate {
+    /// Held across one write, so a backup sees all of it or none of it. Never nested:
+    /// a write waiting on a closing gate would hold off the backup it waits for
+    pub(crate) fn enter(&self) -> RwLockReadGuard<'_, ()> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Holds off every write until the guard drops
+    pub(crate) fn close(&self) -> RwLockWriteGuard<'_, ()> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Read caches shared by every handle on the database
+#[derive(Debug)]
+struct Caches {
+    /// Canonical blocks by height
+    blocks: Mutex<LruCache<u64, Block>>,
+    /// State trie reads by height and address, including accounts that do not exist
+    accounts: Mutex<AccountCache>,
+}
+
+impl Caches {
+    fn new(blocks: usize, accounts: usize) -> Self {
+        Self { blocks: Mutex::new(LruCache::new(blocks)), accounts: Mutex::new(LruCache::new(accounts)) }
+    }
 }
 
 impl BlockchainDB {
     pub fn new(path: &str) -> Result<Self, String> {
         let db = sled::open(path)
             .map_err(|e| e.to_string())?;
+        let ancient = AncientStore::open(&std::path::Path::new(path).join("ancient"))?;
         
-        Ok(Self { db })
+        Ok(Self {
+            db,
+            ancient: Arc::new(ancient),
+            caches: Arc::new(Caches::new(DEFAULT_BLOCK_CACHE, DEFAULT_ACCOUNT_CACHE)),
+            writes: WriteGate::default(),
+        })
+    }
+
+    /// A database kept in memory and discarded with its last handle, for tests and
+    /// throwaway chains
+    pub fn temporary() -> Result<Self, String> {
+        let db = sled::Config::new().temporary(true).open().map_err(|e| e.to_string())?;
+        Ok(Self {
+            db,
+            ancient: Arc::new(AncientStore::temporary()?),
+            caches: Arc::new(Caches::new(DEFAULT_BLOCK_CACHE, DEFAULT_ACCOUNT_CACHE)),
+            writes: WriteGate::default(),
+        })
+    }
+
+    /// Resizes the block and account read caches; 0 disables a cache. Handles cloned
+    /// before this keep the old caches
+    pub fn with_cache_sizes(mut self, blocks: usize, accounts: usize) -> Self {
+        self.caches = Arc::new(Caches::new(blocks, accounts));
+        self
+    }
+
+    pub fn cache_metrics(&self) -> CacheMetrics {
+        CacheMetrics {
+            blocks: self.caches.blocks.lock().unwrap().stats(),
+            accounts: self.caches.accounts.lock().unwrap().stats(),
+        }
+    }
+
+    /// Account cache for the state store, which invalidates it as roots change
+    pub(crate) fn account_cache(&self) -> &Mutex<AccountCache> {
+        &self.caches.accounts
+    }
+
+    /// Gate that stores layered on the database enter while they write
+    pub(crate) fn writes(&self) -> &WriteGate {
+        &self.writes
     }
 
     pub fn store_block(&self, block: &Block) -> Result<(), String> {
+        let _writing = self.writes.enter();
+        if self.ancient.next_height().is_some_and(|next| block.header.height < next) {
+            return Err(format!("Block {} is already in the ancient store", block.header.height));
+        }
         let blocks = self.db.open_tree("blocks")
             .map_err(|e| e.to_string())?;
         
@@ -25,13 +117,72 @@ impl BlockchainDB {
         blocks.insert(key, value)
             .map_err(|e| e.to_string())?;
         
+        let tx_index = self.db.open_tree("tx_index")
+            .map_err(|e| e.to_string())?;
+        
+        let memo_index = self.db.open_tree("memo_index")
+            .map_err(|e| e.to_string())?;
+        
+        for (index, tx) in block.transactions.iter().enumerate() {
+            let location = bincode::serialize(&(block.header.height, index as u32))
+                .map_err(|e| e.to_string())?;
+            tx_index.insert(tx.hash(), location)
+                .map_err(|e| e.to_string())?;
+            if let Some(memo) = &tx.memo {
+                let key = [&memo_key(memo)[..], &block.header.height.to_be_bytes(), &(index as u32).to_be_bytes()].concat();
+                memo_index.insert(key, &tx.hash())
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        
         blocks.flush()
             .map_err(|e| e.to_string())?;
+        self.caches.blocks.lock().unwrap().insert(block.header.height, block.clone());
         
         Ok(())
     }
 
+    /// Looks up a transaction by hash, returning it with its block height and index
+    pub fn get_transaction(&self, hash: &[u8; 32]) -> Result<Option<(Transaction, u64, usize)>, String> {
+        let tx_index = self.db.open_tree("tx_index")
+            .map_err(|e| e.to_string())?;
+        
+        let location = match tx_index.get(hash).map_err(|e| e.to_string())? {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        let (height, index): (u64, u32) = bincode::deserialize(&location)
+            .map_err(|e| e.to_string())?;
+        
+        Ok(self.get_block(height)?
+            .and_then(|block| block.transactions.into_iter().nth(index as usize))
+            .filter(|tx| tx.hash() == *hash)
+            .map(|tx| (tx, height, index as usize)))
+    }
+
+    /// Canonical transactions carrying exactly `memo`, newest first, at most `limit`
+    pub fn get_transactions_by_memo(&self, memo: &str, limit: usize) -> Result<Vec<(Transaction, u64, usize)>, String> {
+        let memo_index = self.db.open_tree("memo_index")
+            .map_err(|e| e.to_string())?;
+        let mut found = Vec::new();
+        for entry in memo_index.scan_prefix(memo_key(memo)).rev() {
+            if found.len() == limit {
+                break;
+            }
+            let (_, hash) = entry.map_err(|e| e.to_string())?;
+            let hash: [u8; 32] = hash.as_ref().try_into().map_err(|_| "Corrupt memo index entry".to_string())?;
+            // Entries of blocks a reorg replaced no longer resolve to a transaction
+            if let Some(located) = self.get_transaction(&hash)?.filter(|(tx, ..)| tx.memo.as_deref() == Some(memo)) {
+                found.push(located);
+            }
+        }
+        Ok(found)
+    }
+
     pub fn get_block(&self, height: u64) -> Result<Option<Block>, String> {
+        if let Some(block) = self.caches.blocks.lock().unwrap().get(&height) {
+            return Ok(Some(block));
+        }
         let blocks = self.db.open_tree("blocks")
             .map_err(|e| e.to_string())?;
         
@@ -40,8 +191,13 @@ impl BlockchainDB {
         if let Some(value) = blocks.get(key)
             .map_err(|e| e.to_string())? {
             
-            let block: Block = bincode::deserialize(&value)
-                .map_err(|e| e.to_string())?;
+            let block = envelope::decode_block(&value)?;
+            self.caches.blocks.lock().unwrap().insert(height, block.clone());
+            
+            Ok(Some(block))
+        } else if let Some(value) = self.ancient.get(height)? {
+            let block = envelope::decode_block(&value)?;
+            self.caches.blocks.lock().unwrap().insert(height, block.clone());
             
             Ok(Some(block))
         } else {
@@ -49,6 +205,277 @@ impl BlockchainDB {
         }
     }
 
+    /// Keeps a block addressable by hash, including blocks on non-canonical branches
+    pub fn store_block_by_hash(&self, block: &Block) -> Result<(), String> {
+        let _writing = self.writes.enter();
+        let by_hash = self.db.open_tree("blocks_by_hash")
+            .map_err(|e| e.to_string())?;
+        
+        let value = bincode::serialize(block)
+            .map_err(|e| e.to_string())?;
+        
+        by_hash.insert(block.hash(), value)
+            .map_err(|e| e.to_string())?;
+        
+        Ok(())
+    }
+
+    pub fn get_block_by_hash(&self, hash: &[u8; 32]) -> Result<Option<Block>, String> {
+        let by_hash = self.db.open_tree("blocks_by_hash")
+            .map_err(|e| e.to_string())?;
+        
+        if let Some(value) = by_hash.get(hash).map_err(|e| e.to_string())? {
+            return envelope::decode_block(&value).map(Some);
+        }
+        let ancient_hashes = self.db.open_tree("ancient_hashes")
+            .map_err(|e| e.to_string())?;
+        
+        match ancient_hashes.get(hash).map_err(|e| e.to_string())? {
+            Some(height) => self.get_block(u64::from_be_bytes(
+                height[..8].try_into()
+                    .map_err(|_| "Invalid height key".to_string())?
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Drops canonical blocks above `height` after a reorg onto a shorter branch
+    pub fn truncate_above(&self, height: u64) -> Result<(), String> {
+        let _writing = self.writes.enter();
+        if self.ancient.next_height().is_some_and(|next| height + 1 < next) {
+            return Err(format!("Cannot truncate to block {}, which is below the ancient store", height));
+        }
+        let blocks = self.db.open_tree("blocks")
+            .map_err(|e| e.to_string())?;
+        
+        let stale: Vec<sled::IVec> = blocks.range((height + 1).to_be_bytes()..)
+            .keys()
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?;
+        for key in stale {
+            blocks.remove(key)
+                .map_err(|e| e.to_string())?;
+        }
+        self.caches.blocks.lock().unwrap().retain(|cached| *cached <= height);
+        
+        Ok(())
+    }
+
+    /// Moves canonical blocks below `height` into the ancient store, returning how many
+    /// moved. Only finalized blocks may be frozen: ancient blocks are never truncated
+    pub fn freeze_below(&self, height: u64) -> Result<u64, String> {
+        let _writing = self.writes.enter();
+        let blocks = self.db.open_tree("blocks")
+            .map_err(|e| e.to_string())?;
+        let by_hash = self.db.open_tree("blocks_by_hash")
+            .map_err(|e| e.to_string())?;
+        let ancient_hashes = self.db.open_tree("ancient_hashes")
+            .map_err(|e| e.to_string())?;
+        
+        let mut moved = 0;
+        for entry in blocks.range(..height.to_be_bytes()) {
+            let (key, value) = entry.map_err(|e| e.to_string())?;
+            let block_height = u64::from_be_bytes(
+                key[..8].try_into()
+                    .map_err(|_| "Invalid height key".to_string())?
+            );
+            let hash = envelope::decode_block(&value)?.hash();
+            // Blocks below the store's tip were appended before an interrupted freeze
+            if !self.ancient.contains(block_height) {
+                self.ancient.append(block_height, &value)?;
+                moved += 1;
+            }
+            ancient_hashes.insert(hash, &block_height.to_be_bytes())
+                .map_err(|e| e.to_string())?;
+            by_hash.remove(hash)
+                .map_err(|e| e.to_string())?;
+            blocks.remove(key)
+                .map_err(|e| e.to_string())?;
+        }
+        
+        Ok(moved)
+    }
+
+    pub fn ancient_stats(&self) -> AncientStats {
+        self.ancient.stats()
+    }
+
+    pub(crate) fn ancient(&self) -> &AncientStore {
+        &self.ancient
+    }
+
+    /// Every sled tree by name, the default one included
+    pub(crate) fn trees(&self) -> Result<Vec<(String, sled::Tree)>, String> {
+        self.db
+            .tree_names()
+            .into_iter()
+            .map(|name| {
+                let tree = self.db.open_tree(&name).map_err(|e| e.to_string())?;
+                let name = String::from_utf8(name.to_vec()).map_err(|_| "Tree name is not UTF-8".to_string())?;
+                Ok((name, tree))
+            })
+            .collect()
+    }
+
+    /// Writes everything sled still buffers to disk
+    pub fn flush(&self) -> Result<(), String> {
+        self.db.flush().map(|_| ()).map_err(|e| e.to_string())
+    }
+
+    pub fn put_meta<T: Serialize>(&self, key: &str, value: &T) -> Result<(), String> {
+        let _writing = self.writes.enter();
+        let meta = self.db.open_tree("meta")
+            .map_err(|e| e.to_string())?;
+        
+        let value = bincode::serialize(value)
+            .map_err(|e| e.to_string())?;
+        
+        meta.insert(key, value)
+            .map_err(|e| e.to_string())?;
+        
+        Ok(())
+    }
+
+    pub fn get_meta<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, String> {
+        let meta = self.db.open_tree("meta")
+            .map_err(|e| e.to_string())?;
+        
+        match meta.get(key).map_err(|e| e.to_string())? {
+            Some(value) => bincode::deserialize(&value).map(Some).map_err(|e| e.to_string()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn store_genesis_allocations(&self, allocations: &[(Vec<u8>, u64)]) -> Result<(), String> {
+        let _writing = self.writes.enter();
+        let genesis = self.db.open_tree("genesis")
+            .map_err(|e| e.to_string())?;
+        
+        let value = bincode::serialize(allocations)
+            .map_err(|e| e.to_string())?;
+        
+        genesis.insert("allocations", value)
+            .map_err(|e| e.to_string())?;
+        
+        genesis.flush()
+            .map_err(|e| e.to_string())?;
+        
+        Ok(())
+    }
+
+    pub fn get_genesis_allocations(&self) -> Result<Vec<(Vec<u8>, u64)>, String> {
+        let genesis = self.db.open_tree("genesis")
+            .map_err(|e| e.to_string())?;
+        
+        match genesis.get("allocations").map_err(|e| e.to_string())? {
+            Some(value) => bincode::deserialize(&value).map_err(|e| e.to_string()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn store_genesis_validators(&self, validators: &[Vec<u8>]) -> Result<(), String> {
+        let _writing = self.writes.enter();
+        let genesis = self.db.open_tree("genesis")
+            .map_err(|e| e.to_string())?;
+        
+        let value = bincode::serialize(validators)
+            .map_err(|e| e.to_string())?;
+        
+        genesis.insert("validators", value)
+            .map_err(|e| e.to_string())?;
+        
+        genesis.flush()
+            .map_err(|e| e.to_string())?;
+        
+        Ok(())
+    }
+
+    /// Proposer set for round-robin block production; empty means any node may produce
+    pub fn get_genesis_validators(&self) -> Result<Vec<Vec<u8>>, String> {
+        let genesis = self.db.open_tree("genesis")
+            .map_err(|e| e.to_string())?;
+        
+        match genesis.get("validators").map_err(|e| e.to_string())? {
+            Some(value) => bincode::deserialize(&value).map_err(|e| e.to_string()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn store_gas_schedule(&self, schedule: &GasSchedule) -> Result<(), String> {
+        let _writing = self.writes.enter();
+        let genesis = self.db.open_tree("genesis")
+            .map_err(|e| e.to_string())?;
+        
+        let value = bincode::serialize(schedule)
+            .map_err(|e| e.to_string())?;
+        
+        genesis.insert("gas", value)
+            .map_err(|e| e.to_string())?;
+        
+        genesis.flush()
+            .map_err(|e| e.to_string())?;
+        
+        Ok(())
+    }
+
+    /// Gas schedule the chain was started with, the default one if none was stored
+    pub fn get_gas_schedule(&self) -> Result<GasSchedule, String> {
+        let genesis = self.db.open_tree("genesis")
+            .map_err(|e| e.to_string())?;
+        
+        match genesis.get("gas").map_err(|e| e.to_string())? {
+            Some(value) => bincode::deserialize(&value).map_err(|e| e.to_string()),
+            None => Ok(GasSchedule::default()),
+        }
+    }
+
+    /// Chain parameters and their scheduled upgrades, the defaults when genesis set none
+    pub fn store_chain_params(&self, params: &ParamSchedule) -> Result<(), String> {
+        let _writing = self.writes.enter();
+        let genesis = self.db.open_tree("genesis").map_err(|e| e.to_string())?;
+        let value = bincode::serialize(params).map_err(|e| e.to_string())?;
+        genesis.insert("params", value).map_err(|e| e.to_string())?;
+        genesis.flush().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn get_chain_params(&self) -> Result<ParamSchedule, String> {
+        let genesis = self.db.open_tree("genesis").map_err(|e| e.to_string())?;
+        match genesis.get("params").map_err(|e| e.to_string())? {
+            Some(value) => bincode::deserialize(&value).map_err(|e| e.to_string()),
+            None => Ok(ParamSchedule::default()),
+        }
+    }
+
+    /// Digest of the genesis allocations, validators, chain parameters and gas schedule,
+    /// which nodes of one chain agree on before exchanging blocks
+    pub fn genesis_hash(&self) -> Result<[u8; 32], String> {
+        let genesis = (
+            self.get_genesis_allocations()?,
+            self.get_genesis_validators()?,
+            self.get_chain_params()?,
+            self.get_gas_schedule()?,
+        );
+        let bytes = bincode::serialize(&genesis).map_err(|e| e.to_string())?;
+        Ok(Sha3_256::digest(bytes).into())
+    }
+
+    /// Raw tree for stores layered on the block database
+    pub(crate) fn tree(&self, name: &str) -> Result<sled::Tree, String> {
+        self.db.open_tree(name).map_err(|e| e.to_string())
+    }
+
+    pub fn size_on_disk(&self) -> Result<u64, String> {
+        self.db.size_on_disk().map_err(|e| e.to_string())
+    }
+
+    pub fn block_count(&self) -> Result<usize, String> {
+        let blocks = self.db.open_tree("blocks")
+            .map_err(|e| e.to_string())?;
+        
+        Ok(blocks.len() + self.ancient.len())
+    }
+
     pub fn get_latest_height(&self) -> Result<u64, String> {
         let blocks = self.db.open_tree("blocks")
             .map_err(|e| e.to_string())?;
@@ -63,22 +490,28 @@ impl BlockchainDB {
             
             Ok(height)
         } else {
-            Ok(0)
+            Ok(self.ancient.next_height().map_or(0, |next| next.saturating_sub(1)))
         }
     }
 }
 
+/// Prefix of a memo's entries in the memo index
+fn memo_key(memo: &str) -> [u8; 32] {
+    Sha3_256::digest(memo.as_bytes()).into()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::storage::ConsensusData;
+    use crate::crypto::QuantumSignature;
+    use crate::storage::blocks::ConsensusData;
 
     fn create_test_block(height: u64) -> Block {
         Block::new(
             [0; 32],
             vec![],
             height,
-            ConsensusData::FastLane { validator: vec![1, 2, 3, 4] },
+            ConsensusData::FastLane { validator: vec![1, 2, 3, 4], committee: None },
         )
     }
 
@@ -95,6 +528,19 @@ mod tests {
         
         let latest = db.get_latest_height().unwrap();
         assert_eq!(latest, 1);
+
+        // Reads are served from the block cache until a truncation drops the height
+        assert_eq!(db.cache_metrics().blocks.hits, 1);
+        db.truncate_above(0).unwrap();
+        assert!(db.get_block(1).unwrap().is_none());
+        db.store_block(&block).unwrap();
+
+        // Transactions are found by their exact memo
+        let noted = Transaction::new(vec![1; 32], vec![2; 32], 5, 5, 0, vec![], QuantumSignature::new(vec![])).with_memo("rent");
+        db.store_block(&Block::new(block.hash(), vec![noted.clone()], 2, ConsensusData::default())).unwrap();
+        let found = db.get_transactions_by_memo("rent", 10).unwrap();
+        assert_eq!((found.len(), found[0].0.hash(), found[0].1), (1, noted.hash(), 2));
+        assert!(db.get_transactions_by_memo("Rent", 10).unwrap().is_empty());
         
         println!("   Database operations working!");
         println!("   Stored and retrieved block height: {}", retrieved.header.height);
@@ -102,4 +548,4 @@ mod tests {
         
         let _ = std::fs::remove_dir_all(&temp_dir);
     }
-}
\ No newline at end of file
+}
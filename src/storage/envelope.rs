@@ -0,0 +1,620 @@
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::crypto::QuantumSignature;
+use crate::interop::{RelayCall, RELAY};
+use crate::storage::batch::{BatchCall, BatchOperation, BATCH};
+use crate::storage::blocks::{Block, BlockHeader, ConsensusDataV1, FeePayer, Transaction};
+use crate::storage::vesting::{VestingCreate, VESTING};
+
+/// Leading bytes of an enveloped transaction. A legacy encoding starts with the
+/// sender's length as a little-endian u64, which never begins with `0xff` for a real key
+pub const ENVELOPE_MAGIC: [u8; 3] = [0xff, b'T', b'X'];
+
+pub const TX_VERSION_1: u8 = 1;
+/// Version 2 carries a memo, written ahead of the version 1 body of the same type
+pub const TX_VERSION_2: u8 = 2;
+pub const CURRENT_TX_VERSION: u8 = TX_VERSION_2;
+
+pub const TX_TYPE_TRANSFER: u8 = 0;
+pub const TX_TYPE_CONTRACT_CALL: u8 = 1;
+pub const TX_TYPE_CREATE_VESTING: u8 = 2;
+pub const TX_TYPE_RELAY: u8 = 3;
+pub const TX_TYPE_BATCH: u8 = 4;
+
+/// Largest call payload a contract call may carry
+pub const MAX_CALL_DATA: usize = 128 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxV1Transfer {
+    pub from: Vec<u8>,
+    pub to: Vec<u8>,
+    pub amount: u64,
+    pub fee: u64,
+    pub nonce: u64,
+    pub valid_until_height: Option<u64>,
+    pub fee_payer: Option<FeePayer>,
+    #[serde(skip)]
+    pub memo: Option<String>,
+    pub signature: QuantumSignature,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxV1ContractCall {
+    pub from: Vec<u8>,
+    pub contract: Vec<u8>,
+    /// Value transferred to the contract along with the call
+    pub value: u64,
+    pub fee: u64,
+    pub nonce: u64,
+    pub call_data: Vec<u8>,
+    pub valid_until_height: Option<u64>,
+    pub fee_payer: Option<FeePayer>,
+    #[serde(skip)]
+    pub memo: Option<String>,
+    pub signature: QuantumSignature,
+}
+
+/// Funds a vesting account for `beneficiary` with `amount`, locked on its schedule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxV1CreateVesting {
+    pub from: Vec<u8>,
+    pub beneficiary: Vec<u8>,
+    pub amount: u64,
+    pub fee: u64,
+    pub nonce: u64,
+    pub cliff_blocks: u64,
+    pub duration_blocks: u64,
+    pub valid_until_height: Option<u64>,
+    pub fee_payer: Option<FeePayer>,
+    #[serde(skip)]
+    pub memo: Option<String>,
+    pub signature: QuantumSignature,
+}
+
+/// Inbound relay carrying external headers and an inclusion proof, or a new light client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxV1Relay {
+    pub from: Vec<u8>,
+    pub call: Box<RelayCall>,
+    pub fee: u64,
+    pub nonce: u64,
+    pub valid_until_height: Option<u64>,
+    pub fee_payer: Option<FeePayer>,
+    #[serde(skip)]
+    pub memo: Option<String>,
+    pub signature: QuantumSignature,
+}
+
+/// Operations run in order from one sender under one signature and fee, all or none
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxV1Batch {
+    pub from: Vec<u8>,
+    pub operations: Vec<BatchOperation>,
+    pub fee: u64,
+    pub nonce: u64,
+    pub valid_until_height: Option<u64>,
+    pub fee_payer: Option<FeePayer>,
+    #[serde(skip)]
+    pub memo: Option<String>,
+    pub signature: QuantumSignature,
+}
+
+/// Versioned, typed form of a transaction as written to disk and the wire:
+/// `ENVELOPE_MAGIC`, a version byte, a type byte, then the bincode body
+#[derive(Debug, Clone)]
+pub enum TxEnvelope {
+    TxV1Transfer(TxV1Transfer),
+    TxV1ContractCall(TxV1ContractCall),
+    TxV1CreateVesting(TxV1CreateVesting),
+    TxV1Relay(TxV1Relay),
+    TxV1Batch(TxV1Batch),
+}
+
+/// Transaction layout written before the envelope, expiry and fee payer existed
+#[derive(Serialize, Deserialize)]
+struct LegacyTransaction {
+    from: Vec<u8>,
+    to: Vec<u8>,
+    amount: u64,
+    fee: u64,
+    nonce: u64,
+    data: Vec<u8>,
+    signature: QuantumSignature,
+}
+
+/// Header layout written before blooms existed
+#[derive(Serialize, Deserialize)]
+struct LegacyBlockHeader {
+    version: u32,
+    previous_hash: [u8; 32],
+    merkle_root: [u8; 32],
+    state_root: [u8; 32],
+    timestamp: u64,
+    height: u64,
+    consensus_data: ConsensusDataV1,
+}
+
+#[derive(Deserialize)]
+struct LegacyBlock {
+    header: LegacyBlockHeader,
+    transactions: Vec<LegacyTransaction>,
+}
+
+impl TxV1Transfer {
+    fn validate(&self) -> Result<(), String> {
+        if self.from.is_empty() || self.to.is_empty() {
+            return Err("Empty sender or recipient".to_string());
+        }
+        if self.amount == 0 {
+            return Err("Transfer carries no value".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl TxV1ContractCall {
+    fn validate(&self) -> Result<(), String> {
+        if self.from.is_empty() || self.contract.is_empty() {
+            return Err("Empty sender or contract".to_string());
+        }
+        if self.call_data.len() > MAX_CALL_DATA {
+            return Err(format!("Call data of {} bytes exceeds {}", self.call_data.len(), MAX_CALL_DATA));
+        }
+        Ok(())
+    }
+}
+
+impl TxV1CreateVesting {
+    fn create(&self) -> VestingCreate {
+        VestingCreate {
+            beneficiary: self.beneficiary.clone(),
+            cliff_blocks: self.cliff_blocks,
+            duration_blocks: self.duration_blocks,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.from.is_empty() {
+            return Err("Empty sender".to_string());
+        }
+        if self.amount == 0 {
+            return Err("Vesting carries no value".to_string());
+        }
+        self.create().validate()
+    }
+}
+
+impl TxV1Relay {
+    fn validate(&self) -> Result<(), String> {
+        if self.from.is_empty() {
+            return Err("Empty sender".to_string());
+        }
+        let size = bincode::serialized_size(&self.call).unwrap_or(u64::MAX);
+        if size > MAX_CALL_DATA as u64 {
+            return Err(format!("Relay of {} bytes exceeds {}", size, MAX_CALL_DATA));
+        }
+        Ok(())
+    }
+}
+
+impl TxV1Batch {
+    fn call(&self) -> BatchCall {
+        BatchCall { operations: self.operations.clone() }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.from.is_empty() {
+            return Err("Empty sender".to_string());
+        }
+        self.call().validate(self.fee)
+    }
+}
+
+impl TxEnvelope {
+    pub fn version(&self) -> u8 {
+        match self.memo() {
+            Some(_) => TX_VERSION_2,
+            None => TX_VERSION_1,
+        }
+    }
+
+    pub fn memo(&self) -> Option<&str> {
+        match self {
+            Self::TxV1Transfer(tx) => tx.memo.as_deref(),
+            Self::TxV1ContractCall(tx) => tx.memo.as_deref(),
+            Self::TxV1CreateVesting(tx) => tx.memo.as_deref(),
+            Self::TxV1Relay(tx) => tx.memo.as_deref(),
+            Self::TxV1Batch(tx) => tx.memo.as_deref(),
+        }
+    }
+
+    fn set_memo(&mut self, memo: Option<String>) {
+        match self {
+            Self::TxV1Transfer(tx) => tx.memo = memo,
+            Self::TxV1ContractCall(tx) => tx.memo = memo,
+            Self::TxV1CreateVesting(tx) => tx.memo = memo,
+            Self::TxV1Relay(tx) => tx.memo = memo,
+            Self::TxV1Batch(tx) => tx.memo = memo,
+        }
+    }
+
+    pub fn tx_type(&self) -> u8 {
+        match self {
+            Self::TxV1Transfer(_) => TX_TYPE_TRANSFER,
+            Self::TxV1ContractCall(_) => TX_TYPE_CONTRACT_CALL,
+            Self::TxV1CreateVesting(_) => TX_TYPE_CREATE_VESTING,
+            Self::TxV1Relay(_) => TX_TYPE_RELAY,
+            Self::TxV1Batch(_) => TX_TYPE_BATCH,
+        }
+    }
+
+    /// Structural checks for the specific transaction type; signatures are checked separately
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::TxV1Transfer(tx) => tx.validate(),
+            Self::TxV1ContractCall(tx) => tx.validate(),
+            Self::TxV1CreateVesting(tx) => tx.validate(),
+            Self::TxV1Relay(tx) => tx.validate(),
+            Self::TxV1Batch(tx) => tx.validate(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let body = match self {
+            Self::TxV1Transfer(tx) => bincode::serialize(tx),
+            Self::TxV1ContractCall(tx) => bincode::serialize(tx),
+            Self::TxV1CreateVesting(tx) => bincode::serialize(tx),
+            Self::TxV1Relay(tx) => bincode::serialize(tx),
+            Self::TxV1Batch(tx) => bincode::serialize(tx),
+        };
+        let mut bytes = ENVELOPE_MAGIC.to_vec();
+        bytes.extend([self.version(), self.tx_type()]);
+        if let Some(memo) = self.memo() {
+            bytes.extend(bincode::serialize(memo).unwrap_or_default());
+        }
+        bytes.extend(body.unwrap_or_default());
+        bytes
+    }
+
+    /// Decodes an envelope, or a transaction stored before envelopes existed
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let Some(rest) = bytes.strip_prefix(&ENVELOPE_MAGIC) else {
+            let legacy: LegacyTransaction = bincode::deserialize(bytes)
+                .map_err(|e| format!("Invalid legacy transaction: {}", e))?;
+            return Ok(Self::from(Transaction::from(legacy)));
+        };
+        let (memo, rest) = match rest {
+            [TX_VERSION_2, tx_type, body @ ..] => {
+                let memo: String = bincode::deserialize(body).map_err(|e| format!("Invalid memo: {}", e))?;
+                let length = bincode::serialized_size(&memo).map_err(|e| e.to_string())? as usize;
+                (Some(memo), [&[TX_VERSION_1, *tx_type][..], &body[length..]].concat())
+            }
+            _ => (None, rest.to_vec()),
+        };
+        let mut envelope = match rest.as_slice() {
+            [TX_VERSION_1, TX_TYPE_TRANSFER, body @ ..] => bincode::deserialize(body)
+                .map(Self::TxV1Transfer)
+                .map_err(|e| format!("Invalid transfer: {}", e)),
+            [TX_VERSION_1, TX_TYPE_CONTRACT_CALL, body @ ..] => bincode::deserialize(body)
+                .map(Self::TxV1ContractCall)
+                .map_err(|e| format!("Invalid contract call: {}", e)),
+            [TX_VERSION_1, TX_TYPE_CREATE_VESTING, body @ ..] => bincode::deserialize(body)
+                .map(Self::TxV1CreateVesting)
+                .map_err(|e| format!("Invalid vesting creation: {}", e)),
+            [TX_VERSION_1, TX_TYPE_RELAY, body @ ..] => bincode::deserialize(body)
+                .map(Self::TxV1Relay)
+                .map_err(|e| format!("Invalid relay: {}", e)),
+            [TX_VERSION_1, TX_TYPE_BATCH, body @ ..] => bincode::deserialize(body)
+                .map(Self::TxV1Batch)
+                .map_err(|e| format!("Invalid batch: {}", e)),
+            [version, tx_type, ..] => Err(format!("Unsupported transaction version {} type {}", version, tx_type)),
+            _ => Err("Truncated transaction envelope".to_string()),
+        }?;
+        envelope.set_memo(memo);
+        Ok(envelope)
+    }
+}
+
+impl From<Transaction> for TxEnvelope {
+    fn from(tx: Transaction) -> Self {
+        // Only a canonically encoded creation maps to its own type, so decoding gives back the same data
+        let vesting = (tx.to == VESTING)
+            .then(|| VestingCreate::decode(&tx.data).ok())
+            .flatten()
+            .filter(|create| create.encode() == tx.data);
+        let relay = (tx.to == RELAY && tx.amount == 0)
+            .then(|| RelayCall::decode(&tx.data).ok())
+            .flatten()
+            .filter(|call| call.encode() == tx.data);
+        let batch = (tx.to == BATCH && tx.amount == 0)
+            .then(|| BatchCall::decode(&tx.data).ok())
+            .flatten()
+            .filter(|call| call.encode() == tx.data);
+        if let Some(call) = batch {
+            Self::TxV1Batch(TxV1Batch {
+                from: tx.from,
+                operations: call.operations,
+                fee: tx.fee,
+                nonce: tx.nonce,
+                valid_until_height: tx.valid_until_height,
+                fee_payer: tx.fee_payer,
+                memo: tx.memo,
+                signature: tx.signature,
+            })
+        } else if let Some(call) = relay {
+            Self::TxV1Relay(TxV1Relay {
+                from: tx.from,
+                call: Box::new(call),
+                fee: tx.fee,
+                nonce: tx.nonce,
+                valid_until_height: tx.valid_until_height,
+                fee_payer: tx.fee_payer,
+                memo: tx.memo,
+                signature: tx.signature,
+            })
+        } else if let Some(create) = vesting {
+            Self::TxV1CreateVesting(TxV1CreateVesting {
+                from: tx.from,
+                beneficiary: create.beneficiary,
+                amount: tx.amount,
+                fee: tx.fee,
+                nonce: tx.nonce,
+                cliff_blocks: create.cliff_blocks,
+                duration_blocks: create.duration_blocks,
+                valid_until_height: tx.valid_until_height,
+                fee_payer: tx.fee_payer,
+                memo: tx.memo,
+                signature: tx.signature,
+            })
+        } else if tx.data.is_empty() {
+            Self::TxV1Transfer(TxV1Transfer {
+                from: tx.from,
+                to: tx.to,
+                amount: tx.amount,
+                fee: tx.fee,
+                nonce: tx.nonce,
+                valid_until_height: tx.valid_until_height,
+                fee_payer: tx.fee_payer,
+                memo: tx.memo,
+                signature: tx.signature,
+            })
+        } else {
+            Self::TxV1ContractCall(TxV1ContractCall {
+                from: tx.from,
+                contract: tx.to,
+                value: tx.amount,
+                fee: tx.fee,
+                nonce: tx.nonce,
+                call_data: tx.data,
+                valid_until_height: tx.valid_until_height,
+                fee_payer: tx.fee_payer,
+                memo: tx.memo,
+                signature: tx.signature,
+            })
+        }
+    }
+}
+
+impl From<TxEnvelope> for Transaction {
+    fn from(envelope: TxEnvelope) -> Self {
+        let memo = envelope.memo().map(String::from);
+        let (mut tx, valid_until_height, fee_payer) = match envelope {
+            TxEnvelope::TxV1Transfer(tx) => (
+                Transaction::new(tx.from, tx.to, tx.amount, tx.fee, tx.nonce, Vec::new(), tx.signature),
+                tx.valid_until_height,
+                tx.fee_payer,
+            ),
+            TxEnvelope::TxV1ContractCall(tx) => (
+                Transaction::new(tx.from, tx.contract, tx.value, tx.fee, tx.nonce, tx.call_data, tx.signature),
+                tx.valid_until_height,
+                tx.fee_payer,
+            ),
+            TxEnvelope::TxV1CreateVesting(tx) => {
+                let data = tx.create().encode();
+                (
+                    Transaction::new(tx.from, VESTING.to_vec(), tx.amount, tx.fee, tx.nonce, data, tx.signature),
+                    tx.valid_until_height,
+                    tx.fee_payer,
+                )
+            }
+            TxEnvelope::TxV1Relay(tx) => (
+                Transaction::new(tx.from, RELAY.to_vec(), 0, tx.fee, tx.nonce, tx.call.encode(), tx.signature),
+                tx.valid_until_height,
+                tx.fee_payer,
+            ),
+            TxEnvelope::TxV1Batch(tx) => {
+                let data = tx.call().encode();
+                (
+                    Transaction::new(tx.from, BATCH.to_vec(), 0, tx.fee, tx.nonce, data, tx.signature),
+                    tx.valid_until_height,
+                    tx.fee_payer,
+                )
+            }
+        };
+        tx.valid_until_height = valid_until_height;
+        tx.fee_payer = fee_payer;
+        tx.memo = memo;
+        tx
+    }
+}
+
+impl From<LegacyTransaction> for Transaction {
+    fn from(tx: LegacyTransaction) -> Self {
+        Transaction::new(tx.from, tx.to, tx.amount, tx.fee, tx.nonce, tx.data, tx.signature)
+    }
+}
+
+/// Bytes a transaction is hashed over. Transactions the legacy layout can express
+/// keep their legacy hash so existing merkle roots and indexes stay valid
+pub fn hashing_bytes(tx: &Transaction) -> Vec<u8> {
+    if tx.valid_until_height.is_none() && tx.fee_payer.is_none() && tx.memo.is_none() {
+        let legacy = (&tx.from, &tx.to, tx.amount, tx.fee, tx.nonce, &tx.data, &tx.signature);
+        bincode::serialize(&legacy).unwrap_or_default()
+    } else {
+        TxEnvelope::from(tx.clone()).encode()
+    }
+}
+
+/// Decodes a stored block, falling back to the layout used before envelopes
+pub fn decode_block(bytes: &[u8]) -> Result<Block, String> {
+    bincode::deserialize::<Block>(bytes).or_else(|e| {
+        let legacy: LegacyBlock = bincode::deserialize(bytes).map_err(|_| e.to_string())?;
+        let header = legacy.header;
+        Ok(Block {
+            header: BlockHeader {
+                version: header.version,
+                previous_hash: header.previous_hash,
+                merkle_root: header.merkle_root,
+                state_root: header.state_root,
+                timestamp: header.timestamp,
+                height: header.height,
+                consensus_data: header.consensus_data.into(),
+                logs_bloom: None,
+                signals: 0,
+                availability_root: [0; 32],
+                path_proposal: None,
+            },
+            transactions: legacy.transactions.into_iter().map(Transaction::from).collect(),
+        })
+    })
+}
+
+/// `#[serde(with)]` adapter writing a transaction as an envelope in binary formats
+/// and as a plain struct in human-readable ones such as JSON
+pub mod transaction {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(tx: &Transaction, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            tx.serialize(serializer)
+        } else {
+            serializer.serialize_bytes(&TxEnvelope::from(tx.clone()).encode())
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Transaction, D::Error> {
+        if deserializer.is_human_readable() {
+            Transaction::deserialize(deserializer)
+        } else {
+            let bytes = Vec::<u8>::deserialize(deserializer)?;
+            TxEnvelope::decode(&bytes).map(Transaction::from).map_err(D::Error::custom)
+        }
+    }
+}
+
+/// `#[serde(with)]` adapter for a list of transactions, see [`transaction`]
+pub mod transactions {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Enveloped(#[serde(with = "super::transaction")] Transaction);
+
+    pub fn serialize<S: Serializer>(txs: &[Transaction], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            txs.serialize(serializer)
+        } else {
+            serializer.collect_seq(txs.iter().map(|tx| Enveloped(tx.clone())))
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Transaction>, D::Error> {
+        if deserializer.is_human_readable() {
+            Vec::<Transaction>::deserialize(deserializer)
+        } else {
+            let txs = Vec::<Enveloped>::deserialize(deserializer)?;
+            Ok(txs.into_iter().map(|Enveloped(tx)| tx).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumKeyPair;
+    use crate::storage::blocks::MAX_MEMO_BYTES;
+
+    fn signed(keypair: &QuantumKeyPair, data: Vec<u8>) -> Transaction {
+        let mut tx = Transaction::new(
+            keypair.public_key().to_vec(),
+            vec![0xcc; 32],
+            25,
+            2,
+            7,
+            data,
+            QuantumSignature::new(vec![]),
+        );
+        tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+        tx
+    }
+
+    #[test]
+    fn test_envelope_round_trip_and_legacy_decoding() {
+        let keypair = QuantumKeyPair::generate();
+        let transfer = signed(&keypair, vec![]);
+        let call = signed(&keypair, vec![0xa9, 0x05]);
+
+        let encoded = TxEnvelope::from(call.clone()).encode();
+        assert_eq!(encoded[..5], [0xff, b'T', b'X', TX_VERSION_1, TX_TYPE_CONTRACT_CALL]);
+        let decoded = Transaction::from(TxEnvelope::decode(&encoded).unwrap());
+        assert_eq!(decoded.hash(), call.hash());
+        assert!(decoded.check().is_ok());
+
+        let mut unknown = encoded.clone();
+        unknown[3] = 9;
+        assert!(TxEnvelope::decode(&unknown).unwrap_err().contains("Unsupported"));
+
+        // Data written before envelopes decodes and keeps its hash
+        let legacy = LegacyTransaction {
+            from: transfer.from.clone(),
+            to: transfer.to.clone(),
+            amount: transfer.amount,
+            fee: transfer.fee,
+            nonce: transfer.nonce,
+            data: transfer.data.clone(),
+            signature: transfer.signature.clone(),
+        };
+        let legacy_bytes = bincode::serialize(&legacy).unwrap();
+        let decoded = Transaction::from(TxEnvelope::decode(&legacy_bytes).unwrap());
+        assert_eq!(decoded.hash(), transfer.hash());
+        assert!(matches!(TxEnvelope::from(decoded), TxEnvelope::TxV1Transfer(_)));
+
+        let block = Block::new([0; 32], vec![transfer.clone(), call], 1, Default::default());
+        let header = &block.header;
+        let legacy_header = LegacyBlockHeader {
+            version: 1,
+            previous_hash: header.previous_hash,
+            merkle_root: header.merkle_root,
+            state_root: header.state_root,
+            timestamp: header.timestamp,
+            height: header.height,
+            consensus_data: ConsensusDataV1::from(&header.consensus_data),
+        };
+        let legacy_block = bincode::serialize(&(&legacy_header, vec![legacy])).unwrap();
+        let decoded = decode_block(&legacy_block).unwrap();
+        assert_eq!(decoded.transactions[0].hash(), transfer.hash());
+        assert!(decoded.header.logs_bloom.is_none() && decoded.header.may_contain_any(&[vec![7; 32]]));
+        let current = decode_block(&bincode::serialize(&block).unwrap()).unwrap();
+        assert!(current.has_valid_merkle_root());
+
+        assert!(signed(&keypair, vec![0; MAX_CALL_DATA + 1]).check().is_err());
+        // A memo moves the transaction to version 2, signed and paid for by the byte
+        let mut noted = Transaction::new(keypair.public_key().to_vec(), vec![0xcc; 32], 25, 10, 7, vec![], QuantumSignature::new(vec![])).with_memo("invoice 42");
+        noted.signature = keypair.sign(&noted.get_signing_data()).unwrap();
+        let encoded = TxEnvelope::from(noted.clone()).encode();
+        assert_eq!(encoded[3..5], [TX_VERSION_2, TX_TYPE_TRANSFER]);
+        let decoded = Transaction::from(TxEnvelope::decode(&encoded).unwrap());
+        assert_eq!((decoded.memo.as_deref(), decoded.hash()), (Some("invoice 42"), noted.hash()));
+        assert!(decoded.check().is_ok());
+        assert_ne!(noted.hash(), transfer.hash());
+        assert!(Transaction { memo: Some("invoice 43".to_string()), ..noted.clone() }.check().is_err());
+        assert!(Transaction { fee: 9, ..noted.clone() }.check().unwrap_err().contains("fee"));
+        assert!(noted.clone().with_memo("x".repeat(MAX_MEMO_BYTES + 1)).check().unwrap_err().contains("exceeds"));
+
+        let mut empty_transfer = signed(&keypair, vec![]);
+        empty_transfer.amount = 0;
+        assert!(TxEnvelope::from(empty_transfer).validate().is_err());
+
+        println!("   Transaction envelope working!");
+    }
+}
@@ -29,8 +29,12 @@ impl MerkleTree {
             })
             .collect();
 
-        let root = Self::calculate_root(&leaves);
+        Self::from_leaves(leaves)
+    }
 
+    /// Tree over leaves that are already hashes, such as transaction hashes
+    pub fn from_leaves(leaves: Vec<[u8; 32]>) -> Self {
+        let root = Self::calculate_root(&leaves);
         Self { root, leaves }
     }
     fn calculate_root(leaves: &[[u8; 32]]) -> [u8; 32] {
@@ -42,11 +46,11 @@ impl MerkleTree {
             let mut next_level = Vec::new();
             for chunk in hashes.chunks(2) {
                 let mut hasher = Sha3_256::new();
-                hasher.update(&chunk[0]);
+                hasher.update(chunk[0]);
                 if chunk.len() > 1 {
-                    hasher.update(&chunk[1]);
+                    hasher.update(chunk[1]);
                 } else {
-                    hasher.update(&chunk[0]); // Duplicate if odd
+                    hasher.update(chunk[0]); // Duplicate if odd
                 }
                 next_level.push(hasher.finalize().into());
             }
@@ -80,20 +84,20 @@ impl MerkleTree {
                 current_index + 1
             };
 
-            if sibling_index < current_level.len() {
-                proof.push(MerkleProofElement {
-                    hash: current_level[sibling_index],
-                    is_right: !is_right,
-                });
-            }
+            // The last node of an odd level is paired with itself
+            let sibling = current_level.get(sibling_index).unwrap_or(&current_level[current_index]);
+            proof.push(MerkleProofElement {
+                hash: *sibling,
+                is_right: !is_right,
+            });
             let mut next_level = Vec::new();
             for chunk in current_level.chunks(2) {
                 let mut hasher = Sha3_256::new();
-                hasher.update(&chunk[0]);
+                hasher.update(chunk[0]);
                 if chunk.len() > 1 {
-                    hasher.update(&chunk[1]);
+                    hasher.update(chunk[1]);
                 } else {
-                    hasher.update(&chunk[0]);
+                    hasher.update(chunk[0]);
                 }
                 next_level.push(hasher.finalize().into());
             }
@@ -114,11 +118,11 @@ impl MerkleTree {
         for element in &proof.proof {
             let mut hasher = Sha3_256::new();
             if element.is_right {
-                hasher.update(&current_hash);
-                hasher.update(&element.hash);
+                hasher.update(current_hash);
+                hasher.update(element.hash);
             } else {
-                hasher.update(&element.hash);
-                hasher.update(&current_hash);
+                hasher.update(element.hash);
+                hasher.update(current_hash);
             }
             current_hash = hasher.finalize().into();
         }
@@ -184,6 +188,9 @@ mod tests {
         let proof = tree.generate_proof(3).unwrap();
         assert!(MerkleTree::verify_proof(&proof));
 
+        let tree = MerkleTree::new(&data[..3]);
+        assert!(MerkleTree::verify_proof(&tree.generate_proof(2).unwrap()));
+
         println!("Merkle proof generation and verification working!");
     }
 
@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Built-in system contract holding the name registry. Transactions sent here carry a
+/// bincode `NameCall` as data and pay for the registration with their amount
+pub const NAME_REGISTRY: [u8; 32] = *b"triunity:system:name-registry:01";
+
+pub const NAME_SUFFIX: &str = ".tri";
+
+/// Blocks one registration or renewal lasts
+pub const NAME_PERIOD_BLOCKS: u64 = 1_000_000;
+
+/// Amount a registration or renewal must carry, kept by the registry
+pub const NAME_PRICE: u64 = 10;
+
+const MIN_LABEL_LEN: usize = 3;
+const MAX_LABEL_LEN: usize = 32;
+
+/// Prefix of name keys in the state tree, which hold no account
+const NAME_KEY_PREFIX: &[u8] = b"name:";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NameCall {
+    /// Claims a free or expired name, pointing it at `target`
+    Register { name: String, #[serde(with = "hex::serde")] target: Vec<u8> },
+    /// Extends the owner's name by another period from its expiry
+    Renew { name: String },
+    /// Points the owner's active name at a new address
+    SetTarget { name: String, #[serde(with = "hex::serde")] target: Vec<u8> },
+}
+
+impl NameCall {
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap_or_default()
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        bincode::deserialize(data).map_err(|e| format!("Invalid name registry call: {}", e))
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Register { name, .. } | Self::Renew { name } | Self::SetTarget { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameRecord {
+    #[serde(with = "hex::serde")]
+    pub owner: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub target: Vec<u8>,
+    pub expires_at: u64,
+}
+
+impl NameRecord {
+    pub fn is_active_at(&self, height: u64) -> bool {
+        height < self.expires_at
+    }
+}
+
+/// Whether `input` should be resolved through the registry rather than parsed as hex
+pub fn is_name(input: &str) -> bool {
+    input.ends_with(NAME_SUFFIX)
+}
+
+/// Names are a label of 3 to 32 lowercase letters, digits and inner hyphens, then `.tri`
+pub fn validate_name(name: &str) -> Result<(), String> {
+    let label = name
+        .strip_suffix(NAME_SUFFIX)
+        .ok_or_else(|| format!("Name {} does not end in {}", name, NAME_SUFFIX))?;
+    if !(MIN_LABEL_LEN..=MAX_LABEL_LEN).contains(&label.len()) {
+        return Err(format!("Name {} must have {} to {} characters before {}", name, MIN_LABEL_LEN, MAX_LABEL_LEN, NAME_SUFFIX));
+    }
+    let allowed = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-';
+    if !label.chars().all(allowed) || label.starts_with('-') || label.ends_with('-') {
+        return Err(format!("Name {} may only use a-z, 0-9 and inner hyphens", name));
+    }
+    Ok(())
+}
+
+/// Registered names by name. Expired names stay until someone registers them again,
+/// and only active ones resolve
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NameRegistry {
+    names: BTreeMap<String, NameRecord>,
+}
+
+impl NameRegistry {
+    pub fn from_records(records: impl IntoIterator<Item = (String, NameRecord)>) -> Self {
+        Self { names: records.into_iter().collect() }
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = (&String, &NameRecord)> {
+        self.names.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Checks `call` from `sender` carrying `amount` in a block at `height`, without applying it
+    pub fn check(&self, sender: &[u8], amount: u64, call: &NameCall, height: u64) -> Result<(), String> {
+        let name = call.name();
+        validate_name(name)?;
+        let record = self.names.get(name);
+        match call {
+            NameCall::Register { target, .. } => {
+                if target.is_empty() {
+                    return Err("Name target is empty".to_string());
+                }
+                if record.is_some_and(|record| record.is_active_at(height)) {
+                    return Err(format!("Name {} is taken", name));
+                }
+            }
+            NameCall::Renew { .. } | NameCall::SetTarget { .. } => {
+                let record = record.ok_or_else(|| format!("Name {} is not registered", name))?;
+                if record.owner != sender {
+                    return Err(format!("Name {} belongs to another account", name));
+                }
+                if matches!(call, NameCall::SetTarget { target, .. } if target.is_empty()) {
+                    return Err("Name target is empty".to_string());
+                }
+                if matches!(call, NameCall::SetTarget { .. }) && !record.is_active_at(height) {
+                    return Err(format!("Name {} has expired", name));
+                }
+            }
+        }
+        if !matches!(call, NameCall::SetTarget { .. }) && amount < NAME_PRICE {
+            return Err(format!("Name registration costs {}, got {}", NAME_PRICE, amount));
+        }
+        Ok(())
+    }
+
+    /// Applies `call` after `check` accepted it
+    pub fn apply(&mut self, sender: &[u8], call: &NameCall, height: u64) {
+        match call {
+            NameCall::Register { name, target } => {
+                let record = NameRecord { owner: sender.to_vec(), target: target.clone(), expires_at: height + NAME_PERIOD_BLOCKS };
+                self.names.insert(name.clone(), record);
+            }
+            NameCall::Renew { name } => {
+                if let Some(record) = self.names.get_mut(name) {
+                    record.expires_at = record.expires_at.max(height) + NAME_PERIOD_BLOCKS;
+                }
+            }
+            NameCall::SetTarget { name, target } => {
+                if let Some(record) = self.names.get_mut(name) {
+                    record.target = target.clone();
+                }
+            }
+        }
+    }
+
+    /// The active record of `name` at `height`
+    pub fn resolve(&self, name: &str, height: u64) -> Option<&NameRecord> {
+        self.names.get(name).filter(|record| record.is_active_at(height))
+    }
+
+    /// Active names pointing at `target` at `height`, in name order
+    pub fn reverse(&self, target: &[u8], height: u64) -> Vec<String> {
+        self.names
+            .iter()
+            .filter(|(_, record)| record.target == target && record.is_active_at(height))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Leaf bytes committing to each name in the state tree, in name order
+    pub fn leaves(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        self.names.iter().map(|(name, record)| {
+            let key = [NAME_KEY_PREFIX, name.as_bytes()].concat();
+            bincode::serialize(&(key, record)).unwrap_or_default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumSignature;
+    use crate::storage::blocks::Transaction;
+    use crate::storage::state::StateManager;
+
+    fn call(from: &[u8], nonce: u64, amount: u64, call: &NameCall) -> Transaction {
+        Transaction::new(from.to_vec(), NAME_REGISTRY.to_vec(), amount, 1, nonce, call.encode(), QuantumSignature::new(vec![]))
+    }
+
+    #[test]
+    fn test_name_registry() {
+        assert!(validate_name("alice.tri").is_ok());
+        for bad in ["al.tri", "Alice.tri", "-alice.tri", "alice", "al_ice.tri"] {
+            assert!(validate_name(bad).is_err(), "{}", bad);
+        }
+
+        let (alice, bob) = (vec![1; 32], vec![2; 32]);
+        let mut state = StateManager::from_allocations(&[(alice.clone(), 1_000), (bob.clone(), 1_000)]).unwrap();
+        let empty_root = state.state_root();
+        let register = NameCall::Register { name: "alice.tri".to_string(), target: alice.clone() };
+        assert!(state.apply_transaction_at(&call(&alice, 0, NAME_PRICE - 1, &register), 5).is_err());
+        state.apply_transaction_at(&call(&alice, 0, NAME_PRICE, &register), 5).unwrap();
+        assert_ne!(state.state_root(), empty_root);
+        assert_eq!(state.get_account(&alice).unwrap().balance, 1_000 - NAME_PRICE - 1);
+        assert_eq!(state.names().resolve("alice.tri", 5).unwrap().target, alice);
+        assert_eq!(state.names().reverse(&alice, 5), vec!["alice.tri".to_string()]);
+
+        // Taken names cannot be claimed, and only the owner may repoint or renew
+        let steal = NameCall::Register { name: "alice.tri".to_string(), target: bob.clone() };
+        assert!(state.apply_transaction_at(&call(&bob, 0, NAME_PRICE, &steal), 6).is_err());
+        let repoint = NameCall::SetTarget { name: "alice.tri".to_string(), target: bob.clone() };
+        assert!(state.apply_transaction_at(&call(&bob, 0, 0, &repoint), 6).is_err());
+        let renew = NameCall::Renew { name: "alice.tri".to_string() };
+        state.apply_transaction_at(&call(&alice, 1, NAME_PRICE, &renew), 6).unwrap();
+        let expiry = 5 + 2 * NAME_PERIOD_BLOCKS;
+        assert_eq!(state.names().resolve("alice.tri", expiry - 1).unwrap().expires_at, expiry);
+
+        // Once expired it stops resolving and anyone may register it
+        assert!(state.names().resolve("alice.tri", expiry).is_none());
+        assert!(state.names().reverse(&alice, expiry).is_empty());
+        state.apply_transaction_at(&call(&bob, 0, NAME_PRICE, &steal), expiry).unwrap();
+        assert_eq!(state.names().resolve("alice.tri", expiry).unwrap().owner, bob);
+
+        // Names travel with snapshots
+        let block = crate::storage::blocks::Block::new([0; 32], vec![], 0, Default::default());
+        let restored = StateManager::from_snapshot(&state.snapshot(block));
+        assert_eq!(restored.state_root(), state.state_root());
+
+        println!("   Name registry working!");
+    }
+}
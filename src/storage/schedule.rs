@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Built-in system contract queueing transfers for a future height. Transactions sent
+/// here carry a bincode `ScheduleCall` as data; a scheduled transfer's amount is the
+/// transaction amount, held by this account until it executes or is cancelled
+pub const SCHEDULER: [u8; 32] = *b"triunity:system:scheduler:000001";
+
+/// Furthest ahead of its inclusion a transfer may be scheduled
+pub const MAX_SCHEDULE_DELAY: u64 = 1_000_000;
+
+/// Transfers one height may execute, bounding the work of any single block
+pub const MAX_SCHEDULED_PER_HEIGHT: usize = 256;
+
+/// Prefix of queue keys in the state tree, which hold no account
+const SCHEDULE_KEY_PREFIX: &[u8] = b"schedule:";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScheduleCall {
+    /// Holds the transaction amount and pays it to `to` when block `execute_at` is applied
+    Transfer { #[serde(with = "hex::serde")] to: Vec<u8>, execute_at: u64 },
+    /// Refunds a pending transfer to its sender; `id` is the hash of the scheduling transaction
+    Cancel { id: [u8; 32] },
+}
+
+impl ScheduleCall {
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap_or_default()
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        bincode::deserialize(data).map_err(|e| format!("Invalid scheduler call: {}", e))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledTransfer {
+    #[serde(with = "hex::serde")]
+    pub id: [u8; 32],
+    pub execute_at: u64,
+    #[serde(with = "hex::serde")]
+    pub from: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub to: Vec<u8>,
+    pub amount: u64,
+}
+
+/// Pending transfers by target height, then id
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScheduleQueue {
+    pending: BTreeMap<(u64, [u8; 32]), ScheduledTransfer>,
+}
+
+impl ScheduleQueue {
+    pub fn from_transfers(transfers: impl IntoIterator<Item = ScheduledTransfer>) -> Self {
+        Self { pending: transfers.into_iter().map(|transfer| ((transfer.execute_at, transfer.id), transfer)).collect() }
+    }
+
+    /// Pending transfers in execution order
+    pub fn transfers(&self) -> impl Iterator<Item = &ScheduledTransfer> {
+        self.pending.values()
+    }
+
+    /// Pending transfers due from `from` through `to`, in execution order
+    pub fn due_between(&self, from: u64, to: u64) -> impl Iterator<Item = &ScheduledTransfer> {
+        self.pending.range((from, [0; 32])..=(to, [0xff; 32])).map(|(_, transfer)| transfer)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Checks `call` from `sender` in transaction `id` carrying `amount`, included at `height`
+    pub fn check(&self, id: [u8; 32], sender: &[u8], amount: u64, call: &ScheduleCall, height: u64) -> Result<(), String> {
+        match call {
+            ScheduleCall::Transfer { to, execute_at } => {
+                if to.is_empty() || amount == 0 {
+                    return Err("Scheduled transfer needs a recipient and an amount".to_string());
+                }
+                if *execute_at <= height || *execute_at - height > MAX_SCHEDULE_DELAY {
+                    return Err(format!("Execution height must be within {} blocks after {}", MAX_SCHEDULE_DELAY, height));
+                }
+                if self.due_between(*execute_at, *execute_at).count() >= MAX_SCHEDULED_PER_HEIGHT {
+                    return Err(format!("Height {} has no room for more scheduled transfers", execute_at));
+                }
+                if self.pending.contains_key(&(*execute_at, id)) {
+                    return Err("Transfer is already scheduled".to_string());
+                }
+            }
+            ScheduleCall::Cancel { id } => {
+                let transfer = self.find(id).ok_or_else(|| format!("No pending transfer 0x{}", hex::encode(id)))?;
+                if transfer.from != sender {
+                    return Err("Only the sender may cancel a scheduled transfer".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies `call` after `check` accepted it. Returns a cancelled transfer, whose
+    /// amount the caller refunds
+    pub fn apply(&mut self, id: [u8; 32], sender: &[u8], amount: u64, call: &ScheduleCall) -> Option<ScheduledTransfer> {
+        match call {
+            ScheduleCall::Transfer { to, execute_at } => {
+                let transfer = ScheduledTransfer { id, execute_at: *execute_at, from: sender.to_vec(), to: to.clone(), amount };
+                self.pending.insert((*execute_at, id), transfer);
+                None
+            }
+            ScheduleCall::Cancel { id } => {
+                let key = self.find(id).map(|transfer| (transfer.execute_at, transfer.id))?;
+                self.pending.remove(&key)
+            }
+        }
+    }
+
+    /// Removes and returns every transfer due at or before `height`, in execution order
+    pub fn take_due(&mut self, height: u64) -> Vec<ScheduledTransfer> {
+        let later = self.pending.split_off(&(height + 1, [0; 32]));
+        std::mem::replace(&mut self.pending, later).into_values().collect()
+    }
+
+    pub fn find(&self, id: &[u8; 32]) -> Option<&ScheduledTransfer> {
+        self.pending.values().find(|transfer| &transfer.id == id)
+    }
+
+    /// Leaf bytes committing to each pending transfer in the state tree, in execution order
+    pub fn leaves(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        self.pending.values().map(|transfer| {
+            let key = [SCHEDULE_KEY_PREFIX, &transfer.execute_at.to_be_bytes(), &transfer.id].concat();
+            bincode::serialize(&(key, transfer)).unwrap_or_default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumSignature;
+    use crate::storage::blocks::{Block, ConsensusData, Transaction};
+    use crate::storage::state::StateManager;
+
+    fn call(from: &[u8], nonce: u64, amount: u64, call: &ScheduleCall) -> Transaction {
+        Transaction::new(from.to_vec(), SCHEDULER.to_vec(), amount, 1, nonce, call.encode(), QuantumSignature::new(vec![]))
+    }
+
+    #[test]
+    fn test_scheduled_transfers() {
+        let (alice, bob) = (vec![1; 32], vec![2; 32]);
+        let mut state = StateManager::from_allocations(&[(alice.clone(), 1_000)]).unwrap();
+        let balance = |state: &StateManager, address: &[u8]| state.get_account(address).map_or(0, |account| account.balance);
+
+        let late = call(&alice, 0, 100, &ScheduleCall::Transfer { to: bob.clone(), execute_at: 1 });
+        assert!(state.apply_transaction_at(&late, 1).is_err());
+        let timelocked = call(&alice, 0, 100, &ScheduleCall::Transfer { to: bob.clone(), execute_at: 3 });
+        let cancelled = call(&alice, 1, 50, &ScheduleCall::Transfer { to: bob.clone(), execute_at: 3 });
+        state.apply_block(&Block::new([0; 32], vec![timelocked.clone(), cancelled.clone()], 1, ConsensusData::default())).unwrap();
+        assert_eq!((balance(&state, &alice), balance(&state, &SCHEDULER)), (848, 150));
+        assert_eq!(state.schedule().due_between(0, 10).count(), 2);
+
+        // Only the sender may cancel, and the amount goes back to them
+        let steal = call(&bob, 0, 0, &ScheduleCall::Cancel { id: cancelled.hash() });
+        assert!(state.apply_transaction_at(&steal, 2).is_err());
+        let cancel = call(&alice, 2, 0, &ScheduleCall::Cancel { id: cancelled.hash() });
+        state.apply_block(&Block::new([0; 32], vec![cancel], 2, ConsensusData::default())).unwrap();
+        assert_eq!((balance(&state, &alice), state.schedule().len()), (897, 1));
+
+        // Queue contents are part of the state root and travel with snapshots
+        let snapshot = state.snapshot(Block::new([0; 32], vec![], 2, ConsensusData::default()));
+        assert_eq!(StateManager::from_snapshot(&snapshot).state_root(), state.state_root());
+
+        // A payout that would overflow its recipient fails the block
+        let mut overflowing = state.clone();
+        overflowing.get_or_create_account(&bob).balance = u64::MAX;
+        let due = Block::new([0; 32], vec![], 3, ConsensusData::default());
+        assert!(overflowing.apply_block(&due).unwrap_err().contains("Balance overflow"));
+
+        state.apply_block(&due).unwrap();
+        assert_eq!((balance(&state, &bob), balance(&state, &SCHEDULER)), (100, 0));
+        assert!(state.schedule().is_empty());
+
+        println!("   Scheduled transfers working!");
+    }
+}
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::consensus::duties::{DutyTracker, EPOCH_LENGTH};
+
+/// Built-in system contract holding validators' session keys. Transactions sent here
+/// carry a bincode `SessionCall` as data and no value, and are signed by the validator key
+pub const SESSION_KEYS: [u8; 32] = *b"triunity:system:session-keys:001";
+
+/// Longest a session key may be authorized for
+pub const MAX_SESSION_EPOCHS: u64 = 256;
+
+/// Prefix of session key entries in the state tree, which hold no account
+const SESSION_KEY_PREFIX: &[u8] = b"session:";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionCall {
+    /// Lets `key` sign votes for the sender until `epochs` epochs after the current one
+    /// begin, replacing any earlier session key
+    Authorize { #[serde(with = "hex::serde")] key: Vec<u8>, epochs: u64 },
+    /// Withdraws the sender's session key at once
+    Revoke,
+}
+
+impl SessionCall {
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap_or_default()
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        bincode::deserialize(data).map_err(|e| format!("Invalid session key call: {}", e))
+    }
+}
+
+/// A hot key allowed to sign votes on a validator's behalf from `authorized_at` until `expires_at`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionKey {
+    #[serde(with = "hex::serde")]
+    pub key: Vec<u8>,
+    pub authorized_at: u64,
+    /// First height of the epoch the key no longer signs in
+    pub expires_at: u64,
+}
+
+impl SessionKey {
+    pub fn is_active_at(&self, height: u64) -> bool {
+        (self.authorized_at..self.expires_at).contains(&height)
+    }
+}
+
+/// Each validator's latest session key, by validator key. Expired keys stay until replaced
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionKeys {
+    keys: BTreeMap<Vec<u8>, SessionKey>,
+}
+
+impl SessionKeys {
+    pub fn from_keys(keys: impl IntoIterator<Item = (Vec<u8>, SessionKey)>) -> Self {
+        Self { keys: keys.into_iter().collect() }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = (&Vec<u8>, &SessionKey)> {
+        self.keys.iter()
+    }
+
+    pub fn get(&self, validator: &[u8]) -> Option<&SessionKey> {
+        self.keys.get(validator)
+    }
+
+    /// The session key `validator` has authorized to sign at `height`, if any
+    pub fn active_at(&self, validator: &[u8], height: u64) -> Option<&[u8]> {
+        self.keys.get(validator).filter(|session| session.is_active_at(height)).map(|session| session.key.as_slice())
+    }
+
+    /// Checks `call` from `sender` carrying `amount`, without applying it
+    pub fn check(&self, sender: &[u8], amount: u64, call: &SessionCall) -> Result<(), String> {
+        if amount != 0 {
+            return Err("Session key calls carry no value".to_string());
+        }
+        match call {
+            SessionCall::Authorize { key, epochs } => {
+                if key.is_empty() {
+                    return Err("Session key is empty".to_string());
+                }
+                if key == sender {
+                    return Err("A session key must differ from the validator key".to_string());
+                }
+                if !(1..=MAX_SESSION_EPOCHS).contains(epochs) {
+                    return Err(format!("Session keys last 1 to {} epochs, got {}", MAX_SESSION_EPOCHS, epochs));
+                }
+            }
+            SessionCall::Revoke => {
+                if !self.keys.contains_key(sender) {
+                    return Err("No session key to revoke".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies `call` from `sender` in a block at `height` after `check` accepted it
+    pub fn apply(&mut self, sender: &[u8], call: &SessionCall, height: u64) {
+        match call {
+            SessionCall::Authorize { key, epochs } => {
+                let expires_at = (DutyTracker::epoch_of(height) + epochs) * EPOCH_LENGTH;
+                self.keys.insert(sender.to_vec(), SessionKey { key: key.clone(), authorized_at: height, expires_at });
+            }
+            SessionCall::Revoke => {
+                self.keys.remove(sender);
+            }
+        }
+    }
+
+    /// Leaf bytes committing to each session key in the state tree, in validator order
+    pub fn leaves(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        self.keys.iter().map(|(validator, session)| {
+            let key = [SESSION_KEY_PREFIX, validator].concat();
+            bincode::serialize(&(key, session)).unwrap_or_default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumSignature;
+    use crate::storage::blocks::{Block, ConsensusData, Transaction};
+    use crate::storage::state::StateManager;
+
+    #[test]
+    fn test_session_keys() {
+        let (validator, session) = (vec![1; 32], vec![2; 32]);
+        let mut state = StateManager::from_allocations(&[(validator.clone(), 100)]).unwrap();
+        let call = |nonce: u64, amount: u64, call: &SessionCall| {
+            Transaction::new(validator.clone(), SESSION_KEYS.to_vec(), amount, 1, nonce, call.encode(), QuantumSignature::new(vec![]))
+        };
+        let authorize = SessionCall::Authorize { key: session.clone(), epochs: 2 };
+        assert!(state.apply_transaction_at(&call(0, 5, &authorize), 40).is_err());
+        assert!(state.apply_transaction_at(&call(0, 0, &SessionCall::Authorize { key: validator.clone(), epochs: 2 }), 40).is_err());
+        assert!(state.apply_transaction_at(&call(0, 0, &SessionCall::Authorize { key: session.clone(), epochs: 0 }), 40).is_err());
+        assert!(state.apply_transaction_at(&call(0, 0, &SessionCall::Revoke), 40).is_err());
+
+        // Authorized in epoch 1 for two epochs, the key signs until epoch 3 begins
+        state.apply_block(&Block::new([0; 32], vec![call(0, 0, &authorize)], 40, ConsensusData::default())).unwrap();
+        let sessions = state.sessions();
+        assert_eq!(sessions.get(&validator).unwrap().expires_at, 3 * EPOCH_LENGTH);
+        assert_eq!(sessions.active_at(&validator, 95), Some(session.as_slice()));
+        assert_eq!((sessions.active_at(&validator, 39), sessions.active_at(&validator, 96)), (None, None));
+
+        let snapshot = state.snapshot(Block::new([0; 32], vec![], 40, ConsensusData::default()));
+        assert_eq!(StateManager::from_snapshot(&snapshot).state_root(), state.state_root());
+
+        state.apply_transaction_at(&call(1, 0, &SessionCall::Revoke), 50).unwrap();
+        assert!(state.sessions().active_at(&validator, 50).is_none());
+
+        println!("   Session keys working!");
+    }
+}
@@ -1,11 +1,46 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use crate::interop::transfer::TRANSFER_ESCROW;
+use crate::interop::{CrossChainMessage, InteropState, InteropUpdate, RelayCall, TriUnityVerifier, OUTBOX, RELAY};
+use crate::storage::batch::{BatchCall, BatchOperation, BATCH};
+use crate::storage::blocks::{Block, Transaction};
+use crate::storage::database::BlockchainDB;
+use crate::storage::merkle::{MerkleProof, MerkleTree};
+use crate::storage::names::{NameCall, NameRecord, NameRegistry, NAME_REGISTRY};
+use crate::storage::schedule::{ScheduleCall, ScheduleQueue, ScheduledTransfer, SCHEDULER};
+use crate::storage::sessions::{SessionCall, SessionKey, SessionKeys, SESSION_KEYS};
+use crate::storage::trace::TraceStep;
+use crate::storage::vesting::{VestingAccounts, VestingCreate, VestingSchedule, VestingStatus, VESTING};
 
 #[derive(Debug, Clone)]
 pub struct StateManager {
     accounts: HashMap<Vec<u8>, Account>,
     contracts: HashMap<Vec<u8>, Contract>,
+    names: NameRegistry,
+    schedule: ScheduleQueue,
+    vesting: VestingAccounts,
+    interop: InteropState,
+    sessions: SessionKeys,
     current_height: u64,
+    /// Steps recorded while tracing
+    trace: Option<Vec<TraceStep>>,
+    /// Accounts as they were before a batch or call first touched them, to restore if it fails
+    journal: Option<Vec<(Vec<u8>, Option<Account>)>>,
+    /// Addresses of the accounts read or written while recording accesses
+    accessed: Option<Arc<Mutex<BTreeSet<Vec<u8>>>>>,
+}
+
+/// System contract state a failed batch or call is rolled back to
+type Contracts = (NameRegistry, ScheduleQueue, VestingAccounts, InteropState, SessionKeys);
+
+/// Call a transaction makes on a built-in system contract
+enum SystemCall {
+    Name(NameCall),
+    Schedule(ScheduleCall),
+    Vesting(VestingCreate),
+    Relay(InteropUpdate),
+    Session(SessionCall),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,6 +50,35 @@ pub struct Account {
     pub code_hash: Option<[u8; 32]>,
 }
 
+/// Meta key of the snapshot a checkpoint-synced node replays from instead of genesis
+pub const STATE_BASE_META: &str = "state_base";
+
+fn snapshot_key(height: u64) -> String {
+    format!("snapshot_{}", height)
+}
+
+/// Accounts and system contract state after `block`, sent to nodes fast-syncing from a checkpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    pub block: Block,
+    pub accounts: Vec<(Vec<u8>, Account)>,
+    pub names: Vec<(String, NameRecord)>,
+    pub scheduled: Vec<ScheduledTransfer>,
+    pub vesting: Vec<(Vec<u8>, VestingSchedule)>,
+    pub interop: InteropState,
+    pub sessions: Vec<(Vec<u8>, SessionKey)>,
+}
+
+/// System contract state, which block witnesses carry whole
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemState {
+    pub names: Vec<(String, NameRecord)>,
+    pub scheduled: Vec<ScheduledTransfer>,
+    pub vesting: Vec<(Vec<u8>, VestingSchedule)>,
+    pub interop: InteropState,
+    pub sessions: Vec<(Vec<u8>, SessionKey)>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Contract {
     pub code: Vec<u8>,
@@ -27,15 +91,196 @@ impl StateManager {
         Self {
             accounts: HashMap::new(),
             contracts: HashMap::new(),
+            names: NameRegistry::default(),
+            schedule: ScheduleQueue::default(),
+            vesting: VestingAccounts::default(),
+            interop: InteropState::default(),
+            sessions: SessionKeys::default(),
             current_height: 0,
+            trace: None,
+            journal: None,
+            accessed: None,
+        }
+    }
+
+    pub fn from_allocations(allocations: &[(Vec<u8>, u64)]) -> Result<Self, String> {
+        let mut state = Self::new();
+        for (address, balance) in allocations {
+            state.credit(address, *balance)?;
+        }
+        Ok(state)
+    }
+
+    /// Builds state from a snapshot, as of the snapshot's block
+    pub fn from_snapshot(snapshot: &StateSnapshot) -> Self {
+        let mut state = Self::new();
+        state.accounts = snapshot.accounts.iter().cloned().collect();
+        state.names = NameRegistry::from_records(snapshot.names.iter().cloned());
+        state.schedule = ScheduleQueue::from_transfers(snapshot.scheduled.iter().cloned());
+        state.vesting = VestingAccounts::from_schedules(snapshot.vesting.iter().cloned());
+        state.interop = snapshot.interop.clone();
+        state.sessions = SessionKeys::from_keys(snapshot.sessions.iter().cloned());
+        state.current_height = snapshot.block.header.height;
+        state
+    }
+
+    /// State holding only `accounts` besides the system contracts, as of `height`. Blocks
+    /// touching no other account execute on it as on the full state
+    pub fn from_parts(accounts: impl IntoIterator<Item = (Vec<u8>, Account)>, system: &SystemState, height: u64) -> Self {
+        let mut state = Self::new();
+        state.accounts = accounts.into_iter().collect();
+        state.names = NameRegistry::from_records(system.names.iter().cloned());
+        state.schedule = ScheduleQueue::from_transfers(system.scheduled.iter().cloned());
+        state.vesting = VestingAccounts::from_schedules(system.vesting.iter().cloned());
+        state.interop = system.interop.clone();
+        state.sessions = SessionKeys::from_keys(system.sessions.iter().cloned());
+        state.current_height = height;
+        state
+    }
+
+    /// Rebuilds state by replaying every stored block on top of the genesis allocations
+    pub fn replay(db: &BlockchainDB) -> Result<Self, String> {
+        if db.block_count()? == 0 {
+            return Self::replay_to(db, None);
+        }
+        Self::replay_to(db, Some(db.get_latest_height()?))
+    }
+
+    /// State stored blocks are replayed on and the first height to replay: the genesis
+    /// allocations, or the checkpoint snapshot a node fast-synced from
+    pub fn base(db: &BlockchainDB) -> Result<(Self, u64), String> {
+        match db.get_meta::<StateSnapshot>(STATE_BASE_META)? {
+            Some(snapshot) => Ok((Self::from_snapshot(&snapshot), snapshot.block.header.height + 1)),
+            None => Ok((Self::from_allocations(&db.get_genesis_allocations()?)?, 0)),
+        }
+    }
+
+    /// State after the canonical block at `height`, or the base state for `None`
+    pub fn replay_to(db: &BlockchainDB, height: Option<u64>) -> Result<Self, String> {
+        let (mut state, first) = Self::base(db)?;
+        if let Some(last) = height {
+            if last + 1 < first {
+                return Err(format!("State below height {} is not stored", first - 1));
+            }
+            for height in first..=last {
+                let block = db.get_block(height)?
+                    .ok_or_else(|| format!("Block {} missing from storage", height))?;
+                state.apply_block(&block)
+                    .map_err(|e| format!("Block {}: {}", height, e))?;
+            }
+        }
+        Ok(state)
+    }
+
+    /// Snapshot of the state after the canonical block at `height`, `None` if that
+    /// block is not stored. A snapshot stored for the block is returned without a replay
+    pub fn snapshot_at(db: &BlockchainDB, height: u64) -> Result<Option<StateSnapshot>, String> {
+        let Some(block) = db.get_block(height)? else {
+            return Ok(None);
+        };
+        if let Some(stored) = db.get_meta::<StateSnapshot>(&snapshot_key(height))? {
+            if stored.block.hash() == block.hash() {
+                return Ok(Some(stored));
+            }
+        }
+        Ok(Some(Self::replay_to(db, Some(height))?.snapshot(block)))
+    }
+
+    /// Keeps `snapshot` for `snapshot_at`, replacing any stored for the same height
+    pub fn store_snapshot(db: &BlockchainDB, snapshot: &StateSnapshot) -> Result<(), String> {
+        db.put_meta(&snapshot_key(snapshot.block.header.height), snapshot)
+    }
+
+    /// Snapshot of this state, which must be the state after `block`
+    pub fn snapshot(&self, block: Block) -> StateSnapshot {
+        let mut accounts: Vec<(Vec<u8>, Account)> = self.accounts
+            .iter()
+            .map(|(address, account)| (address.clone(), account.clone()))
+            .collect();
+        accounts.sort_by(|a, b| a.0.cmp(&b.0));
+        let SystemState { names, scheduled, vesting, interop, sessions } = self.system_state();
+        StateSnapshot { block, accounts, names, scheduled, vesting, interop, sessions }
+    }
+
+    pub fn system_state(&self) -> SystemState {
+        SystemState {
+            names: self.names.records().map(|(name, record)| (name.clone(), record.clone())).collect(),
+            scheduled: self.schedule.transfers().cloned().collect(),
+            vesting: self.vesting.schedules().map(|(address, schedule)| (address.clone(), *schedule)).collect(),
+            interop: self.interop.clone(),
+            sessions: self.sessions.keys().map(|(validator, session)| (validator.clone(), session.clone())).collect(),
+        }
+    }
+
+    /// Starts recording every account read or written, until `take_accessed`
+    pub fn record_accesses(&mut self) {
+        self.accessed = Some(Arc::default());
+    }
+
+    /// Addresses accessed since `record_accesses`, which stops recording
+    pub fn take_accessed(&mut self) -> BTreeSet<Vec<u8>> {
+        match self.accessed.take() {
+            Some(accessed) => std::mem::take(&mut *accessed.lock().unwrap()),
+            None => BTreeSet::new(),
+        }
+    }
+
+    fn touch(&self, address: &[u8]) {
+        if let Some(accessed) = &self.accessed {
+            accessed.lock().unwrap().insert(address.to_vec());
         }
     }
 
     pub fn get_account(&self, address: &[u8]) -> Option<&Account> {
+        self.touch(address);
         self.accounts.get(address)
     }
 
+    pub fn accounts(&self) -> impl Iterator<Item = (&Vec<u8>, &Account)> {
+        self.accounts.iter()
+    }
+
+    pub fn names(&self) -> &NameRegistry {
+        &self.names
+    }
+
+    pub fn schedule(&self) -> &ScheduleQueue {
+        &self.schedule
+    }
+
+    pub fn interop(&self) -> &InteropState {
+        &self.interop
+    }
+
+    pub fn sessions(&self) -> &SessionKeys {
+        &self.sessions
+    }
+
+    /// Balance of `address` not locked by a vesting schedule at `height`
+    pub fn spendable_at(&self, address: &[u8], height: u64) -> u64 {
+        let balance = self.get_account(address).map_or(0, |account| account.balance);
+        balance.saturating_sub(self.vesting.locked_at(address, height))
+    }
+
+    /// Vested and locked balance of a vesting account at `height`
+    pub fn vesting_status(&self, address: &[u8], height: u64) -> Option<VestingStatus> {
+        let schedule = *self.vesting.get(address)?;
+        let balance = self.get_account(address).map_or(0, |account| account.balance);
+        Some(VestingStatus {
+            schedule,
+            height,
+            balance,
+            vested: schedule.vested_at(height),
+            locked: schedule.locked_at(height),
+            spendable: self.spendable_at(address, height),
+        })
+    }
+
     pub fn get_or_create_account(&mut self, address: &[u8]) -> &mut Account {
+        self.touch(address);
+        if let Some(journal) = &mut self.journal {
+            journal.push((address.to_vec(), self.accounts.get(address).cloned()));
+        }
         self.accounts.entry(address.to_vec()).or_insert(Account {
             balance: 0,
             nonce: 0,
@@ -44,21 +289,336 @@ impl StateManager {
     }
 
     pub fn transfer(&mut self, from: &[u8], to: &[u8], amount: u64) -> Result<(), String> {
-        let sender = self.get_or_create_account(from);
-        if sender.balance < amount {
+        if self.get_account(from).map_or(0, |sender| sender.balance) < amount {
             return Err("Insufficient balance".to_string());
         }
+        if from != to {
+            self.get_account(to).map_or(0, |recipient| recipient.balance)
+                .checked_add(amount)
+                .ok_or_else(|| "Balance overflow".to_string())?;
+        }
+        self.debit(from, amount)?;
+        self.credit(to, amount)
+    }
 
-        sender.balance -= amount;
-        let recipient = self.get_or_create_account(to);
-        recipient.balance += amount;
+    pub fn apply_transaction(&mut self, tx: &Transaction) -> Result<(), String> {
+        self.apply_transaction_at(tx, self.current_height)
+    }
+
+    /// Applies `tx` as part of the block at `height`, which name registrations expire from.
+    /// A transaction that fails leaves the state as it was
+    pub fn apply_transaction_at(&mut self, tx: &Transaction, height: u64) -> Result<(), String> {
+        // A sender naming itself as fee payer pays like an unsponsored sender
+        let sponsored = tx.fee_account() != tx.from.as_slice();
+        if sponsored && self.spendable_at(tx.fee_account(), height) < tx.fee {
+            return Err("Fee payer cannot cover the fee".to_string());
+        }
 
+        let spendable = self.spendable_at(&tx.from, height);
+        let nonce = self.get_account(&tx.from).map_or(0, |sender| sender.nonce);
+        if tx.nonce != nonce {
+            return Err(format!("Invalid nonce: expected {}, got {}", nonce, tx.nonce));
+        }
+        let total = if sponsored {
+            tx.amount
+        } else {
+            tx.amount
+                .checked_add(tx.fee)
+                .ok_or_else(|| "Amount overflow".to_string())?
+        };
+        if spendable < total {
+            return Err("Insufficient balance".to_string());
+        }
+        if tx.to == BATCH {
+            return self.apply_batch(tx, height);
+        }
+        let system_call = self.check_call(tx.hash(), &tx.from, &tx.to, tx.amount, &tx.data, height)?;
+
+        // Checks above cover the sender; the journal undoes a credit that overflows
+        let contracts = system_call.is_some().then(|| self.contracts());
+        self.journal = Some(Vec::new());
+        let result = self.debit(&tx.from, total)
+            .and_then(|_| {
+                self.increment_nonce(&tx.from);
+                match sponsored {
+                    true => self.debit(tx.fee_account(), tx.fee),
+                    false => Ok(()),
+                }
+            })
+            .and_then(|_| self.execute_call(tx.hash(), &tx.from, &tx.to, tx.amount, system_call, height));
+        self.close_journal(result.is_err(), contracts);
+        result
+    }
+
+    /// Pays a batch's fee and runs its operations in order, undoing all of it, fee and
+    /// nonce included, if any operation fails
+    fn apply_batch(&mut self, tx: &Transaction, height: u64) -> Result<(), String> {
+        let batch = BatchCall::decode(&tx.data)?;
+        batch.validate(tx.fee)?;
+        if tx.amount != 0 {
+            return Err("Batches carry value in their operations only".to_string());
+        }
+
+        let contracts = self.contracts();
+        self.journal = Some(Vec::new());
+        let mut result = self.debit(tx.fee_account(), tx.fee);
+        self.increment_nonce(&tx.from);
+        self.record(|| TraceStep::Call { contract: "batch".to_string() });
+        let hash = tx.hash();
+        for (index, operation) in batch.operations.iter().enumerate() {
+            if result.is_err() {
+                break;
+            }
+            result = self.apply_operation(BatchCall::operation_id(&hash, index), &tx.from, operation, height)
+                .map_err(|e| format!("Operation {} failed: {}", index, e));
+        }
+        self.close_journal(result.is_err(), Some(contracts));
+        result
+    }
+
+    fn contracts(&self) -> Contracts {
+        (self.names.clone(), self.schedule.clone(), self.vesting.clone(), self.interop.clone(), self.sessions.clone())
+    }
+
+    /// Stops journaling and, if `undo`, restores the accounts journaled and `contracts`
+    fn close_journal(&mut self, undo: bool, contracts: Option<Contracts>) {
+        let journal = self.journal.take().unwrap_or_default();
+        if !undo {
+            return;
+        }
+        for (address, account) in journal.into_iter().rev() {
+            match account {
+                Some(account) => self.accounts.insert(address, account),
+                None => self.accounts.remove(&address),
+            };
+        }
+        if let Some(contracts) = contracts {
+            (self.names, self.schedule, self.vesting, self.interop, self.sessions) = contracts;
+        }
+    }
+
+    fn apply_operation(&mut self, id: [u8; 32], from: &[u8], operation: &BatchOperation, height: u64) -> Result<(), String> {
+        if self.spendable_at(from, height) < operation.amount {
+            return Err("Insufficient balance".to_string());
+        }
+        let system_call = self.check_call(id, from, &operation.to, operation.amount, &operation.data, height)?;
+        self.debit(from, operation.amount)?;
+        self.execute_call(id, from, &operation.to, operation.amount, system_call, height)
+    }
+
+    /// Decodes and checks the system contract call `from` makes by sending `amount` and
+    /// `data` to `to`, `None` for a plain transfer. `id` is what a scheduled transfer is known by
+    fn check_call(&self, id: [u8; 32], from: &[u8], to: &[u8], amount: u64, data: &[u8], height: u64) -> Result<Option<SystemCall>, String> {
+        let system_call = if to == NAME_REGISTRY {
+            let call = NameCall::decode(data)?;
+            self.names.check(from, amount, &call, height)?;
+            Some(SystemCall::Name(call))
+        } else if to == SCHEDULER {
+            let call = ScheduleCall::decode(data)?;
+            self.schedule.check(id, from, amount, &call, height)?;
+            Some(SystemCall::Schedule(call))
+        } else if to == VESTING {
+            let create = VestingCreate::decode(data)?;
+            self.vesting.check(amount, &create, height)?;
+            Some(SystemCall::Vesting(create))
+        } else if to == SESSION_KEYS {
+            let call = SessionCall::decode(data)?;
+            self.sessions.check(from, amount, &call)?;
+            Some(SystemCall::Session(call))
+        } else if to == RELAY {
+            if amount != 0 {
+                return Err("Relay calls carry no value".to_string());
+            }
+            let call = RelayCall::decode(data)?;
+            let escrowed = self.get_account(&TRANSFER_ESCROW).map_or(0, |account| account.balance);
+            Some(SystemCall::Relay(self.interop.process(&call, &TriUnityVerifier, height, escrowed)?))
+        } else if to == OUTBOX {
+            let message = CrossChainMessage::decode(data)?;
+            let escrowed = self.get_account(&TRANSFER_ESCROW).map_or(0, |account| account.balance);
+            Some(SystemCall::Relay(self.interop.send(&message, from, amount, height, escrowed)?))
+        } else {
+            None
+        };
+        Ok(system_call)
+    }
+
+    /// Credits `amount`, already debited from `from`, to `to` and applies the call `check_call` accepted
+    fn execute_call(&mut self, id: [u8; 32], from: &[u8], to: &[u8], amount: u64, system_call: Option<SystemCall>, height: u64) -> Result<(), String> {
+        self.credit(to, amount)?;
+        if let Some(call) = &system_call {
+            let contract = match call {
+                SystemCall::Name(_) => "names",
+                SystemCall::Schedule(_) => "scheduler",
+                SystemCall::Vesting(_) => "vesting",
+                SystemCall::Relay(_) if to == OUTBOX => "outbox",
+                SystemCall::Relay(_) => "relay",
+                SystemCall::Session(_) => "sessions",
+            };
+            self.record(|| TraceStep::Call { contract: contract.to_string() });
+        }
+        match system_call {
+            Some(SystemCall::Name(call)) => self.names.apply(from, &call, height),
+            Some(SystemCall::Schedule(call)) => {
+                if let Some(cancelled) = self.schedule.apply(id, from, amount, &call) {
+                    self.debit(&SCHEDULER, cancelled.amount)?;
+                    self.credit(&cancelled.from, cancelled.amount)?;
+                }
+            }
+            Some(SystemCall::Vesting(create)) => {
+                self.debit(&VESTING, amount)?;
+                self.credit(&create.beneficiary, amount)?;
+                self.vesting.apply(amount, &create, height);
+            }
+            Some(SystemCall::Relay(update)) => {
+                for (from, to, amount) in self.interop.apply(update) {
+                    self.debit(&from, amount)?;
+                    self.credit(&to, amount)?;
+                }
+            }
+            Some(SystemCall::Session(call)) => self.sessions.apply(from, &call, height),
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Takes `amount` from `address`, changing nothing when it holds less
+    fn debit(&mut self, address: &[u8], amount: u64) -> Result<(), String> {
+        let balance = self.get_account(address)
+            .map_or(0, |account| account.balance)
+            .checked_sub(amount)
+            .ok_or_else(|| "Insufficient balance".to_string())?;
+        self.get_or_create_account(address).balance = balance;
+        self.record(|| TraceStep::Debit { account: address.to_vec(), amount, balance });
         Ok(())
     }
 
+    fn credit(&mut self, address: &[u8], amount: u64) -> Result<(), String> {
+        let balance = self.get_account(address)
+            .map_or(0, |account| account.balance)
+            .checked_add(amount)
+            .ok_or_else(|| "Balance overflow".to_string())?;
+        self.get_or_create_account(address).balance = balance;
+        self.record(|| TraceStep::Credit { account: address.to_vec(), amount, balance });
+        Ok(())
+    }
+
+    fn record(&mut self, step: impl FnOnce() -> TraceStep) {
+        if let Some(steps) = &mut self.trace {
+            steps.push(step());
+        }
+    }
+
+    /// `apply_transaction_at`, also returning the steps it took
+    pub fn trace_transaction_at(&mut self, tx: &Transaction, height: u64) -> Result<Vec<TraceStep>, String> {
+        self.trace = Some(Vec::new());
+        let result = self.apply_transaction_at(tx, height);
+        let steps = self.trace.take().unwrap_or_default();
+        result.map(|_| steps)
+    }
+
+    pub fn apply_block(&mut self, block: &Block) -> Result<(), String> {
+        self.execute_block(block).map_err(|(_, e)| e)
+    }
+
+    /// `apply_block`, also returning the index of the transaction that failed, if one did
+    pub fn execute_block(&mut self, block: &Block) -> Result<(), (Option<usize>, String)> {
+        self.run_block(block, false).map(|_| ())
+    }
+
+    /// `execute_block`, also returning the steps each transaction took
+    pub fn trace_block(&mut self, block: &Block) -> Result<Vec<Vec<TraceStep>>, (Option<usize>, String)> {
+        self.run_block(block, true)
+    }
+
+    fn run_block(&mut self, block: &Block, traced: bool) -> Result<Vec<Vec<TraceStep>>, (Option<usize>, String)> {
+        self.begin_block(block.header.height).map_err(|e| (None, format!("Scheduled transfers failed: {}", e)))?;
+        let mut traces = Vec::new();
+        for (index, tx) in block.transactions.iter().enumerate() {
+            if tx.is_expired_at(block.header.height) {
+                return Err((Some(index), format!("Transaction {} expired at height {}", index, tx.valid_until_height.unwrap_or_default())));
+            }
+            let fail = |e| (Some(index), format!("Transaction {} failed: {}", index, e));
+            if traced {
+                traces.push(self.trace_transaction_at(tx, block.header.height).map_err(fail)?);
+            } else {
+                self.apply_transaction_at(tx, block.header.height).map_err(fail)?;
+            }
+        }
+        self.current_height = block.header.height;
+        Ok(traces)
+    }
+
+    /// Pays out the transfers scheduled for `height` or earlier, before the block's own
+    /// transactions apply
+    pub fn begin_block(&mut self, height: u64) -> Result<(), String> {
+        for transfer in self.schedule.take_due(height) {
+            self.debit(&SCHEDULER, transfer.amount)?;
+            self.credit(&transfer.to, transfer.amount)?;
+        }
+        Ok(())
+    }
+
+    /// Merkle root over all accounts, ordered by address, then registered names in name
+    /// order, then scheduled transfers in execution order, vesting schedules by address,
+    /// the interop layer's clients, messages, channels and vouchers, and session keys by validator
+    pub fn state_root(&self) -> [u8; 32] {
+        self.account_tree().1.root()
+    }
+
+    /// Proof that `address` holds its current account under `state_root`
+    pub fn account_proof(&self, address: &[u8]) -> Option<MerkleProof> {
+        let (addresses, tree) = self.account_tree();
+        let index = addresses.binary_search_by(|candidate| candidate.as_slice().cmp(address)).ok()?;
+        tree.generate_proof(index)
+    }
+
+    /// Proof that `leaf`, such as a packet commitment's, is in the state tree under `state_root`
+    pub fn leaf_proof(&self, leaf: &[u8]) -> Option<MerkleProof> {
+        use sha3::{Digest, Sha3_256};
+        let (_, tree) = self.account_tree();
+        let hash: [u8; 32] = Sha3_256::digest(leaf).into();
+        let index = tree.leaves().iter().position(|candidate| *candidate == hash)?;
+        tree.generate_proof(index)
+    }
+
+    /// Leaf bytes committing to one account in the state tree
+    pub fn account_leaf(address: &[u8], account: &Account) -> Vec<u8> {
+        bincode::serialize(&(address, account)).unwrap_or_default()
+    }
+
+    fn account_tree(&self) -> (Vec<&Vec<u8>>, MerkleTree) {
+        let mut addresses: Vec<&Vec<u8>> = self.accounts.keys().collect();
+        addresses.sort();
+
+        // Names follow the accounts so account proofs keep their positions
+        let leaves: Vec<Vec<u8>> = addresses
+            .iter()
+            .map(|address| Self::account_leaf(address, &self.accounts[*address]))
+            .chain(self.system_leaves())
+            .collect();
+
+        (addresses, MerkleTree::new(&leaves))
+    }
+
+    /// State tree leaves after the accounts'
+    pub(crate) fn system_leaves(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        self.names
+            .leaves()
+            .chain(self.schedule.leaves())
+            .chain(self.vesting.leaves())
+            .chain(self.interop.leaves())
+            .chain(self.sessions.leaves())
+    }
+
+    pub fn current_height(&self) -> u64 {
+        self.current_height
+    }
+
     pub fn increment_nonce(&mut self, address: &[u8]) {
         let account = self.get_or_create_account(address);
         account.nonce += 1;
+        let nonce = account.nonce;
+        self.record(|| TraceStep::IncrementNonce { account: address.to_vec(), nonce });
     }
 
     pub fn deploy_contract(&mut self, address: &[u8], code: Vec<u8>, owner: Vec<u8>) {
@@ -165,7 +725,18 @@ mod tests {
         
         assert_eq!(state.get_account(&alice).unwrap().balance, 700);
         assert_eq!(state.get_account(&bob).unwrap().balance, 300);
-        
+
+        // Failed transfers change nothing and create no account
+        let carol = vec![3, 3, 3, 3];
+        assert!(state.transfer(&carol, &bob, 1).is_err());
+        assert!(state.get_account(&carol).is_none());
+        state.get_or_create_account(&carol).balance = u64::MAX;
+        assert_eq!(state.transfer(&alice, &carol, 1).unwrap_err(), "Balance overflow");
+        assert_eq!(state.get_account(&alice).unwrap().balance, 700);
+
+        // Genesis allocations summing past a balance are refused too
+        assert_eq!(StateManager::from_allocations(&[(carol.clone(), u64::MAX), (carol, 1)]).err(), Some("Balance overflow".to_string()));
+
         println!(" Transfer working!");
         println!("   Alice balance: {}", state.get_account(&alice).unwrap().balance);
         println!("   Bob balance: {}", state.get_account(&bob).unwrap().balance);
@@ -188,6 +759,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_state_stats() {
         let mut state = StateManager::new();
         
@@ -0,0 +1,338 @@
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::HashMap;
+
+use crate::storage::database::BlockchainDB;
+use crate::storage::state::{Account, StateManager};
+
+/// State roots a pruned node keeps by default
+pub const DEFAULT_RETAINED_ROOTS: u64 = 128;
+
+/// Accounts are split into this many buckets by the first byte of their address hash
+const STATE_BUCKETS: usize = 256;
+
+const EMPTY_BUCKET: [u8; 32] = [0; 32];
+
+/// Node of the persisted account trie. Nodes are content addressed, so a block that
+/// changes a few accounts only writes the root, the touched buckets and their new leaves;
+/// everything else is shared with the previous root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum StateNode {
+    Root { buckets: Vec<[u8; 32]> },
+    /// Accounts sorted by address, each pointing at its leaf
+    Bucket { entries: Vec<(Vec<u8>, [u8; 32])> },
+    Leaf { account: Account },
+}
+
+impl StateNode {
+    fn hash(&self) -> [u8; 32] {
+        Sha3_256::digest(bincode::serialize(self).unwrap_or_default()).into()
+    }
+
+    fn children(&self) -> Vec<[u8; 32]> {
+        match self {
+            Self::Root { buckets } => buckets.iter().copied().filter(|hash| *hash != EMPTY_BUCKET).collect(),
+            Self::Bucket { entries } => entries.iter().map(|(_, leaf)| *leaf).collect(),
+            Self::Leaf { .. } => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PruneReport {
+    pub roots_pruned: u64,
+    pub nodes_deleted: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StateStoreMetrics {
+    pub retained_roots: u64,
+    pub oldest_height: Option<u64>,
+    pub latest_height: Option<u64>,
+    pub nodes: u64,
+    /// Bytes of encoded trie nodes
+    pub node_bytes: u64,
+    /// Size of the whole database on disk
+    pub disk_bytes: u64,
+}
+
+/// Per-height account state roots over reference-counted trie nodes. Each stored
+/// root and each parent node holds one reference; a node is deleted once nothing
+/// references it, so pruning old roots reclaims exactly the state only they used
+#[derive(Clone)]
+pub struct StateStore {
+    db: BlockchainDB,
+    roots: sled::Tree,
+    nodes: sled::Tree,
+}
+
+impl StateStore {
+    pub fn open(db: &BlockchainDB) -> Result<Self, String> {
+        Ok(Self {
+            db: db.clone(),
+            roots: db.tree("state_roots")?,
+            nodes: db.tree("state_nodes")?,
+        })
+    }
+
+    /// Stores the state after the block at `height`, replacing any root already there
+    pub fn commit(&self, height: u64, state: &StateManager) -> Result<[u8; 32], String> {
+        let _writing = self.db.writes().enter();
+        let mut pending = HashMap::new();
+        let mut buckets = vec![Vec::new(); STATE_BUCKETS];
+        for (address, account) in state.accounts() {
+            let leaf = StateNode::Leaf { account: account.clone() };
+            let hash = leaf.hash();
+            pending.insert(hash, leaf);
+            buckets[Self::bucket_of(address)].push((address.clone(), hash));
+        }
+
+        let buckets: Vec<[u8; 32]> = buckets
+            .into_iter()
+            .map(|mut entries| {
+                if entries.is_empty() {
+                    return EMPTY_BUCKET;
+                }
+                entries.sort();
+                let bucket = StateNode::Bucket { entries };
+                let hash = bucket.hash();
+                pending.insert(hash, bucket);
+                hash
+            })
+            .collect();
+        let root = StateNode::Root { buckets };
+        let root_hash = root.hash();
+        pending.insert(root_hash, root);
+
+        self.insert(root_hash, &pending)?;
+        let previous = self.roots
+            .insert(height.to_be_bytes(), &root_hash)
+            .map_err(|e| e.to_string())?;
+        if let Some(previous) = previous {
+            self.release(Self::hash_from(&previous)?)?;
+            self.db.account_cache().lock().unwrap().retain(|(cached, _)| *cached != height);
+        }
+        Ok(root_hash)
+    }
+
+    pub fn root_at(&self, height: u64) -> Result<Option<[u8; 32]>, String> {
+        match self.roots.get(height.to_be_bytes()).map_err(|e| e.to_string())? {
+            Some(root) => Self::hash_from(&root).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Account state after the block at `height`, read from the trie without replaying
+    /// blocks. Reads go through the database's account cache
+    pub fn account_at(&self, height: u64, address: &[u8]) -> Result<Option<Account>, String> {
+        let key = (height, address.to_vec());
+        if let Some(account) = self.db.account_cache().lock().unwrap().get(&key) {
+            return Ok(account);
+        }
+        let account = self.read_account(height, address)?;
+        self.db.account_cache().lock().unwrap().insert(key, account.clone());
+        Ok(account)
+    }
+
+    fn read_account(&self, height: u64, address: &[u8]) -> Result<Option<Account>, String> {
+        let root = self.root_at(height)?
+            .ok_or_else(|| format!("State at height {} is not retained", height))?;
+        let StateNode::Root { buckets } = self.node(&root)? else {
+            return Err("State root is not a root node".to_string());
+        };
+        let bucket = buckets[Self::bucket_of(address)];
+        if bucket == EMPTY_BUCKET {
+            return Ok(None);
+        }
+        let StateNode::Bucket { entries } = self.node(&bucket)? else {
+            return Err("Corrupt state bucket".to_string());
+        };
+        let Ok(index) = entries.binary_search_by(|(entry, _)| entry.as_slice().cmp(address)) else {
+            return Ok(None);
+        };
+        match self.node(&entries[index].1)? {
+            StateNode::Leaf { account } => Ok(Some(account)),
+            _ => Err("Corrupt state leaf".to_string()),
+        }
+    }
+
+    /// Drops roots above `height` after a reorg onto a shorter branch
+    pub fn truncate_above(&self, height: u64) -> Result<(), String> {
+        let _writing = self.db.writes().enter();
+        let stale: Vec<sled::IVec> = self.roots
+            .range((height + 1).to_be_bytes()..)
+            .keys()
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?;
+        for key in stale {
+            if let Some(root) = self.roots.remove(key).map_err(|e| e.to_string())? {
+                self.release(Self::hash_from(&root)?)?;
+            }
+        }
+        self.db.account_cache().lock().unwrap().retain(|(cached, _)| *cached <= height);
+        Ok(())
+    }
+
+    /// Keeps only the newest `keep` roots, deleting nodes no retained root references
+    pub fn prune(&self, keep: u64) -> Result<PruneReport, String> {
+        let _writing = self.db.writes().enter();
+        let mut report = PruneReport::default();
+        let excess = (self.roots.len() as u64).saturating_sub(keep.max(1));
+        for _ in 0..excess {
+            let Some((key, root)) = self.roots.pop_min().map_err(|e| e.to_string())? else {
+                break;
+            };
+            let height = u64::from_be_bytes(key.as_ref().try_into().map_err(|_| "Invalid state root height".to_string())?);
+            self.db.account_cache().lock().unwrap().retain(|(cached, _)| *cached > height);
+            report.roots_pruned += 1;
+            report.nodes_deleted += self.release(Self::hash_from(&root)?)?;
+        }
+        Ok(report)
+    }
+
+    pub fn metrics(&self) -> Result<StateStoreMetrics, String> {
+        let height = |entry: Option<(sled::IVec, sled::IVec)>| {
+            entry.and_then(|(key, _)| key.as_ref().try_into().ok().map(u64::from_be_bytes))
+        };
+        let mut metrics = StateStoreMetrics {
+            retained_roots: self.roots.len() as u64,
+            oldest_height: height(self.roots.first().map_err(|e| e.to_string())?),
+            latest_height: height(self.roots.last().map_err(|e| e.to_string())?),
+            disk_bytes: self.db.size_on_disk()?,
+            ..StateStoreMetrics::default()
+        };
+        for entry in self.nodes.iter() {
+            let (_, value) = entry.map_err(|e| e.to_string())?;
+            metrics.nodes += 1;
+            metrics.node_bytes += value.len() as u64;
+        }
+        Ok(metrics)
+    }
+
+    fn bucket_of(address: &[u8]) -> usize {
+        Sha3_256::digest(address)[0] as usize % STATE_BUCKETS
+    }
+
+    fn hash_from(bytes: &[u8]) -> Result<[u8; 32], String> {
+        bytes.try_into().map_err(|_| "Invalid state root".to_string())
+    }
+
+    /// Stored node values are an 8-byte reference count followed by the encoded node
+    fn load(&self, hash: &[u8; 32]) -> Result<Option<(u64, StateNode)>, String> {
+        let Some(value) = self.nodes.get(hash).map_err(|e| e.to_string())? else {
+            return Ok(None);
+        };
+        let (count, node) = value.split_at(8.min(value.len()));
+        let count = u64::from_be_bytes(count.try_into().map_err(|_| "Corrupt state node".to_string())?);
+        let node = bincode::deserialize(node).map_err(|e| e.to_string())?;
+        Ok(Some((count, node)))
+    }
+
+    fn store(&self, hash: &[u8; 32], count: u64, node: &StateNode) -> Result<(), String> {
+        let mut value = count.to_be_bytes().to_vec();
+        value.extend(bincode::serialize(node).map_err(|e| e.to_string())?);
+        self.nodes.insert(hash, value).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn node(&self, hash: &[u8; 32]) -> Result<StateNode, String> {
+        self.load(hash)?
+            .map(|(_, node)| node)
+            .ok_or_else(|| format!("State node 0x{} missing", hex::encode(hash)))
+    }
+
+    /// Adds a reference to `hash`, writing it and referencing its children when new
+    fn insert(&self, hash: [u8; 32], pending: &HashMap<[u8; 32], StateNode>) -> Result<(), String> {
+        if let Some((count, node)) = self.load(&hash)? {
+            return self.store(&hash, count + 1, &node);
+        }
+        let node = pending
+            .get(&hash)
+            .ok_or_else(|| format!("State node 0x{} missing", hex::encode(hash)))?;
+        self.store(&hash, 1, node)?;
+        for child in node.children() {
+            self.insert(child, pending)?;
+        }
+        Ok(())
+    }
+
+    /// Drops a reference to `hash`, deleting it and releasing its children at zero.
+    /// Returns the number of nodes deleted
+    fn release(&self, hash: [u8; 32]) -> Result<u64, String> {
+        let Some((count, node)) = self.load(&hash)? else {
+            return Ok(0);
+        };
+        if count > 1 {
+            self.store(&hash, count - 1, &node)?;
+            return Ok(0);
+        }
+        self.nodes.remove(hash).map_err(|e| e.to_string())?;
+        let mut deleted = 1;
+        for child in node.children() {
+            deleted += self.release(child)?;
+        }
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_store_pruning() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_state_store");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        let store = StateStore::open(&db).unwrap();
+
+        let mut state = StateManager::from_allocations(&[(vec![1; 32], 1_000), (vec![2; 32], 500)]).unwrap();
+        for height in 0..10 {
+            state.transfer(&[1; 32], &[3; 32], 10).unwrap();
+            store.commit(height, &state).unwrap();
+        }
+        assert_eq!(store.account_at(4, &[3; 32]).unwrap().unwrap().balance, 50);
+        assert_eq!(store.account_at(9, &[2; 32]).unwrap().unwrap().balance, 500);
+        assert!(store.account_at(9, &[9; 32]).unwrap().is_none());
+
+        // Repeat reads hit the account cache; rewriting, pruning or truncating a root drops its entries
+        let hits = db.cache_metrics().accounts.hits;
+        assert_eq!(store.account_at(4, &[3; 32]).unwrap().unwrap().balance, 50);
+        assert_eq!(db.cache_metrics().accounts.hits, hits + 1);
+        assert_eq!(store.account_at(8, &[3; 32]).unwrap().unwrap().balance, 90);
+        assert_eq!(store.account_at(6, &[3; 32]).unwrap().unwrap().balance, 70);
+
+        // Recommitting a height with the same state shares every node
+        let nodes = store.metrics().unwrap().nodes;
+        store.commit(9, &state).unwrap();
+        assert_eq!(store.metrics().unwrap().nodes, nodes);
+        assert_eq!(store.account_at(9, &[2; 32]).unwrap().unwrap().balance, 500);
+        let mut rewritten = state.clone();
+        rewritten.transfer(&[1; 32], &[2; 32], 1).unwrap();
+        store.commit(9, &rewritten).unwrap();
+        assert_eq!(store.account_at(9, &[2; 32]).unwrap().unwrap().balance, 501);
+
+        let report = store.prune(3).unwrap();
+        assert_eq!(report.roots_pruned, 7);
+        assert!(report.nodes_deleted > 0);
+        assert!(store.account_at(6, &[3; 32]).is_err());
+        assert_eq!(store.account_at(7, &[3; 32]).unwrap().unwrap().balance, 80);
+        // The untouched account's leaf is still shared by the retained roots
+        assert_eq!(store.account_at(7, &[2; 32]).unwrap().unwrap().balance, 500);
+
+        store.truncate_above(7).unwrap();
+        assert!(store.account_at(8, &[3; 32]).is_err());
+        let metrics = store.metrics().unwrap();
+        assert_eq!((metrics.retained_roots, metrics.oldest_height, metrics.latest_height), (1, Some(7), Some(7)));
+
+        // Once only a single-account root remains, its root, bucket and leaf are all that is left
+        let mut single = StateManager::new();
+        single.get_or_create_account(&[4; 32]).balance = 1;
+        store.commit(8, &single).unwrap();
+        store.prune(1).unwrap();
+        assert_eq!(store.metrics().unwrap().nodes, 3);
+
+        println!("   State store pruning working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
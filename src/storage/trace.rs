@@ -0,0 +1,245 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::storage::blocks::Transaction;
+use crate::storage::database::BlockchainDB;
+use crate::storage::state::StateManager;
+
+/// One primitive operation of the state transition. Transactions run natively rather
+/// than as bytecode, so these are the finest steps a trace can show
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum TraceStep {
+    /// `amount` left `account`, leaving `balance`
+    Debit {
+        #[serde(with = "hex::serde")]
+        account: Vec<u8>,
+        amount: u64,
+        balance: u64,
+    },
+    /// `amount` reached `account`, bringing it to `balance`
+    Credit {
+        #[serde(with = "hex::serde")]
+        account: Vec<u8>,
+        amount: u64,
+        balance: u64,
+    },
+    IncrementNonce {
+        #[serde(with = "hex::serde")]
+        account: Vec<u8>,
+        nonce: u64,
+    },
+    /// The transaction entered a built-in system contract; transfers it makes follow
+    Call { contract: String },
+}
+
+/// How much of an execution a trace reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceDetail {
+    /// Every step in order
+    Steps,
+    /// Only the account fields each transaction changed
+    StateDiff,
+    /// The transaction and the transfers system contracts made on its behalf
+    CallTree,
+}
+
+impl FromStr for TraceDetail {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "steps" => Ok(Self::Steps),
+            "state_diff" => Ok(Self::StateDiff),
+            "call_tree" => Ok(Self::CallTree),
+            other => Err(format!("Unknown trace detail: {} (expected steps, state_diff or call_tree)", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Change {
+    pub from: u64,
+    pub to: u64,
+}
+
+/// Fields of one account a transaction changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountDiff {
+    #[serde(with = "hex::serde")]
+    pub address: Vec<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance: Option<Change>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<Change>,
+}
+
+/// A value transfer, with those it caused nested under it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallFrame {
+    #[serde(with = "hex::serde")]
+    pub from: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub to: Vec<u8>,
+    pub value: u64,
+    /// System contract the frame entered, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contract: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<CallFrame>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionTrace {
+    #[serde(with = "hex::serde")]
+    pub hash: [u8; 32],
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub steps: Option<Vec<TraceStep>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_diff: Option<Vec<AccountDiff>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub call: Option<CallFrame>,
+}
+
+impl TransactionTrace {
+    pub fn new(tx: &Transaction, index: usize, steps: Vec<TraceStep>, detail: TraceDetail) -> Self {
+        let mut trace = Self { hash: tx.hash(), index, steps: None, state_diff: None, call: None };
+        match detail {
+            TraceDetail::Steps => trace.steps = Some(steps),
+            TraceDetail::StateDiff => trace.state_diff = Some(state_diff(&steps)),
+            TraceDetail::CallTree => trace.call = Some(call_tree(tx, &steps)),
+        }
+        trace
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockTrace {
+    pub height: u64,
+    #[serde(with = "hex::serde")]
+    pub hash: [u8; 32],
+    pub transactions: Vec<TransactionTrace>,
+}
+
+/// Re-executes the canonical block at `height` on the state before it and traces its
+/// transactions, or only the one at `only`. `None` if no block is stored there
+pub fn trace_block(db: &BlockchainDB, height: u64, detail: TraceDetail, only: Option<usize>) -> Result<Option<BlockTrace>, String> {
+    let Some(block) = db.get_block(height)? else {
+        return Ok(None);
+    };
+    let mut state = StateManager::replay_to(db, height.checked_sub(1))?;
+    state.begin_block(height).map_err(|e| format!("Scheduled transfers failed: {}", e))?;
+    let mut transactions = Vec::new();
+    for (index, tx) in block.transactions.iter().enumerate().take(only.map_or(usize::MAX, |only| only + 1)) {
+        let fail = |e: String| format!("Transaction {} failed: {}", index, e);
+        if only.is_some_and(|only| only != index) {
+            state.apply_transaction_at(tx, height).map_err(fail)?;
+            continue;
+        }
+        let steps = state.trace_transaction_at(tx, height).map_err(fail)?;
+        transactions.push(TransactionTrace::new(tx, index, steps, detail));
+    }
+    Ok(Some(BlockTrace { height, hash: block.hash(), transactions }))
+}
+
+/// Net change of every balance and nonce the steps touched, by address
+pub fn state_diff(steps: &[TraceStep]) -> Vec<AccountDiff> {
+    let mut balances: BTreeMap<&[u8], Change> = BTreeMap::new();
+    let mut nonces: BTreeMap<&[u8], Change> = BTreeMap::new();
+    for step in steps {
+        match step {
+            TraceStep::Debit { account, amount, balance } => {
+                balances.entry(account).or_insert(Change { from: balance + amount, to: 0 }).to = *balance;
+            }
+            TraceStep::Credit { account, amount, balance } => {
+                balances.entry(account).or_insert(Change { from: balance - amount, to: 0 }).to = *balance;
+            }
+            TraceStep::IncrementNonce { account, nonce } => {
+                nonces.entry(account).or_insert(Change { from: nonce - 1, to: 0 }).to = *nonce;
+            }
+            TraceStep::Call { .. } => {}
+        }
+    }
+    let changed = |change: Option<&Change>| change.copied().filter(|change| change.from != change.to);
+    let mut addresses: Vec<&[u8]> = balances.keys().chain(nonces.keys()).copied().collect();
+    addresses.sort_unstable();
+    addresses.dedup();
+    addresses
+        .into_iter()
+        .map(|address| AccountDiff { address: address.to_vec(), balance: changed(balances.get(address)), nonce: changed(nonces.get(address)) })
+        .filter(|diff| diff.balance.is_some() || diff.nonce.is_some())
+        .collect()
+}
+
+/// The transaction as the root frame, with each debit and credit a system contract made
+/// after being called nested under it
+pub fn call_tree(tx: &Transaction, steps: &[TraceStep]) -> CallFrame {
+    let mut root = CallFrame { from: tx.from.clone(), to: tx.to.clone(), value: tx.amount, contract: None, calls: Vec::new() };
+    let Some(entered) = steps.iter().position(|step| matches!(step, TraceStep::Call { .. })) else {
+        return root;
+    };
+    if let TraceStep::Call { contract } = &steps[entered] {
+        root.contract = Some(contract.clone());
+    }
+    // Contracts a batch calls into mark their entry between its transfers
+    let transfers: Vec<&TraceStep> = steps[entered + 1..].iter().filter(|step| !matches!(step, TraceStep::Call { .. })).collect();
+    for pair in transfers.chunks_exact(2) {
+        if let [TraceStep::Debit { account: from, amount, .. }, TraceStep::Credit { account: to, .. }] = pair {
+            root.calls.push(CallFrame { from: from.clone(), to: to.clone(), value: *amount, contract: None, calls: Vec::new() });
+        }
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumSignature;
+    use crate::node::builder::BlockBuilder;
+    use crate::storage::blocks::{Block, ConsensusData};
+    use crate::storage::vesting::{VestingCreate, VESTING};
+
+    #[test]
+    fn test_execution_trace() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_execution_trace");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let db = BlockchainDB::new(temp_dir.to_str().unwrap()).unwrap();
+        let (alice, bob) = (vec![0xaa; 32], vec![0xbb; 32]);
+        db.store_genesis_allocations(&[(alice.clone(), 1_000)]).unwrap();
+        let consensus = ConsensusData::SecureLane { validators: vec![] };
+        let genesis = Block::new([0; 32], vec![], 0, consensus.clone());
+        db.store_block(&genesis).unwrap();
+
+        let transfer = Transaction::new(alice.clone(), bob.clone(), 100, 1, 0, vec![], QuantumSignature::new(vec![]));
+        let create = VestingCreate { beneficiary: bob.clone(), cliff_blocks: 0, duration_blocks: 10 };
+        let vest = Transaction::new(alice.clone(), VESTING.to_vec(), 50, 1, 1, create.encode(), QuantumSignature::new(vec![]));
+        let mut builder = BlockBuilder::new(genesis.hash(), 1, &StateManager::replay(&db).unwrap()).unwrap();
+        builder.add_tx(transfer).unwrap();
+        builder.add_tx(vest.clone()).unwrap();
+        db.store_block(&builder.seal(consensus).0).unwrap();
+
+        let trace = trace_block(&db, 1, TraceDetail::Steps, None).unwrap().unwrap();
+        assert_eq!(trace.transactions.len(), 2);
+        assert_eq!(trace.transactions[0].steps.as_ref().unwrap()[0], TraceStep::Debit { account: alice.clone(), amount: 101, balance: 899 });
+        assert!(trace_block(&db, 2, TraceDetail::Steps, None).unwrap().is_none());
+
+        // Tracing one transaction runs the ones before it, and the diff nets out the vesting contract's pass-through
+        let trace = trace_block(&db, 1, TraceDetail::StateDiff, Some(1)).unwrap().unwrap();
+        assert_eq!((trace.transactions.len(), trace.transactions[0].hash), (1, vest.hash()));
+        let diff = trace.transactions[0].state_diff.as_ref().unwrap();
+        assert_eq!(diff.iter().map(|diff| diff.address.clone()).collect::<Vec<_>>(), vec![alice.clone(), bob.clone()]);
+        assert_eq!(diff[0].balance, Some(Change { from: 899, to: 848 }));
+        assert_eq!(diff[0].nonce, Some(Change { from: 1, to: 2 }));
+        assert_eq!(diff[1].balance, Some(Change { from: 100, to: 150 }));
+
+        let call = trace_block(&db, 1, TraceDetail::CallTree, Some(1)).unwrap().unwrap().transactions[0].call.clone().unwrap();
+        assert_eq!((call.contract.as_deref(), call.value), (Some("vesting"), 50));
+        assert_eq!(call.calls, vec![CallFrame { from: VESTING.to_vec(), to: bob, value: 50, contract: None, calls: vec![] }]);
+        assert!("opcodes".parse::<TraceDetail>().is_err());
+
+        println!("   Execution tracing working!");
+    }
+}
@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::BTreeSet;
+
+use crate::crypto::verification::Subsystem;
+use crate::storage::blocks::Block;
+use crate::storage::database::BlockchainDB;
+use crate::storage::merkle::MerkleTree;
+use crate::storage::state::{Account, StateManager, SystemState};
+
+/// Account a witness reveals, at its position among the parent state's account leaves
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WitnessAccount {
+    pub index: u32,
+    pub address: Vec<u8>,
+    pub account: Account,
+}
+
+/// What executing a block needs besides the block: the accounts it reads, the system
+/// contract state, and the hash of every account leaf of its parent's state tree. The
+/// tree is flat, so the untouched leaves are what tie the revealed accounts to the
+/// parent's state root and let the block's own root be recomputed. An account the block
+/// creates is shown absent by revealing its neighbours on either side
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockWitness {
+    /// State root of the parent the witness was taken from
+    pub state_root: [u8; 32],
+    pub leaves: Vec<[u8; 32]>,
+    pub accounts: Vec<WitnessAccount>,
+    pub system: SystemState,
+}
+
+fn leaf_hash(leaf: &[u8]) -> [u8; 32] {
+    Sha3_256::digest(leaf).into()
+}
+
+impl BlockWitness {
+    /// Witness for executing `block` on `parent`, the state after the block's parent
+    pub fn generate(parent: &StateManager, block: &Block) -> Result<Self, String> {
+        let mut scratch = parent.clone();
+        scratch.record_accesses();
+        scratch.execute_block(block).map_err(|(_, e)| e)?;
+        let accessed = scratch.take_accessed();
+
+        let mut addresses: Vec<(&Vec<u8>, &Account)> = parent.accounts().collect();
+        addresses.sort_by(|a, b| a.0.cmp(b.0));
+        let mut revealed = BTreeSet::new();
+        for address in &accessed {
+            match addresses.binary_search_by(|(candidate, _)| candidate.as_slice().cmp(address)) {
+                Ok(index) => {
+                    revealed.insert(index);
+                }
+                Err(index) => {
+                    revealed.extend(index.checked_sub(1));
+                    revealed.extend((index < addresses.len()).then_some(index));
+                }
+            }
+        }
+
+        Ok(Self {
+            state_root: parent.state_root(),
+            leaves: addresses.iter().map(|(address, account)| leaf_hash(&StateManager::account_leaf(address, account))).collect(),
+            accounts: revealed
+                .into_iter()
+                .map(|index| WitnessAccount { index: index as u32, address: addresses[index].0.clone(), account: addresses[index].1.clone() })
+                .collect(),
+            system: parent.system_state(),
+        })
+    }
+
+    /// Checks `block` executes to the state root in its header from a parent whose state
+    /// root is `parent_state_root`, holding no state beyond this witness. Transaction
+    /// signatures and roots are checked too; header linkage and consensus data are not
+    pub fn verify(&self, block: &Block, parent_state_root: [u8; 32]) -> Result<(), String> {
+        let height = block.header.height;
+        if self.state_root != parent_state_root {
+            return Err(format!("Witness is for state root {}, not {}", hex::encode(self.state_root), hex::encode(parent_state_root)));
+        }
+        if !block.has_valid_merkle_root() {
+            return Err(format!("Block {} has an invalid merkle root", height));
+        }
+        if !block.has_valid_bloom() {
+            return Err(format!("Block {} has an invalid bloom", height));
+        }
+        for (index, tx) in block.transactions.iter().enumerate() {
+            tx.check_as(Subsystem::BlockValidation).map_err(|e| format!("Transaction {}: {}", index, e))?;
+        }
+
+        // The revealed accounts and untouched leaves must make up the parent's state
+        for (index, entry) in self.accounts.iter().enumerate() {
+            let leaf = self.leaves.get(entry.index as usize).ok_or_else(|| format!("Witness account {} is out of range", index))?;
+            if *leaf != leaf_hash(&StateManager::account_leaf(&entry.address, &entry.account)) {
+                return Err(format!("Witness account {} does not match its leaf", index));
+            }
+            if index > 0 && (self.accounts[index - 1].index >= entry.index || self.accounts[index - 1].address >= entry.address) {
+                return Err("Witness accounts are out of order".to_string());
+            }
+        }
+        let revealed = self.accounts.iter().map(|entry| (entry.address.clone(), entry.account.clone()));
+        let mut state = StateManager::from_parts(revealed, &self.system, height.saturating_sub(1));
+        if self.root(self.leaves.clone(), &state) != self.state_root {
+            return Err("Witness does not match the parent state root".to_string());
+        }
+
+        state.record_accesses();
+        state.execute_block(block).map_err(|(_, e)| e)?;
+        let mut created = Vec::new();
+        for address in state.take_accessed() {
+            if self.accounts.binary_search_by(|entry| entry.address.cmp(&address)).is_ok() {
+                continue;
+            }
+            if !self.proves_absent(&address) {
+                return Err(format!("Witness does not cover account {}", hex::encode(&address[..address.len().min(8)])));
+            }
+            created.extend(state.get_account(&address).map(|account| leaf_hash(&StateManager::account_leaf(&address, account))).map(|leaf| (address, leaf)));
+        }
+
+        // Created accounts slot in between the revealed neighbours that proved them absent
+        let mut leaves = Vec::with_capacity(self.leaves.len() + created.len());
+        let mut created = created.into_iter().peekable();
+        let mut revealed = self.accounts.iter().peekable();
+        for (index, leaf) in self.leaves.iter().enumerate() {
+            match revealed.next_if(|entry| entry.index as usize == index) {
+                Some(entry) => {
+                    while let Some((_, leaf)) = created.next_if(|(address, _)| *address < entry.address) {
+                        leaves.push(leaf);
+                    }
+                    let account = state.get_account(&entry.address).ok_or("Revealed account vanished")?;
+                    leaves.push(leaf_hash(&StateManager::account_leaf(&entry.address, account)));
+                }
+                None => leaves.push(*leaf),
+            }
+        }
+        leaves.extend(created.map(|(_, leaf)| leaf));
+
+        let state_root = self.root(leaves, &state);
+        if state_root != block.header.state_root {
+            return Err(format!(
+                "State root mismatch: computed {}, header has {}",
+                hex::encode(state_root),
+                hex::encode(block.header.state_root)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether `address` is missing from the parent state: the revealed accounts around
+    /// where it would sort are adjacent leaves, or the ends of the account leaves
+    fn proves_absent(&self, address: &[u8]) -> bool {
+        let position = self.accounts.partition_point(|entry| entry.address.as_slice() < address);
+        let below = position.checked_sub(1).map(|index| self.accounts[index].index as usize);
+        let above = self.accounts.get(position).map(|entry| entry.index as usize);
+        match (below, above) {
+            (Some(below), Some(above)) => below + 1 == above,
+            (None, Some(above)) => above == 0,
+            (Some(below), None) => below + 1 == self.leaves.len(),
+            (None, None) => self.leaves.is_empty(),
+        }
+    }
+
+    fn root(&self, mut leaves: Vec<[u8; 32]>, state: &StateManager) -> [u8; 32] {
+        leaves.extend(state.system_leaves().map(|leaf| leaf_hash(&leaf)));
+        MerkleTree::from_leaves(leaves).root()
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap_or_default()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        bincode::deserialize(bytes).map_err(|e| format!("Invalid block witness: {}", e))
+    }
+
+    /// Keeps the witness of the block with hash `block_hash`
+    pub fn store(&self, db: &BlockchainDB, block_hash: &[u8; 32]) -> Result<(), String> {
+        let _writing = db.writes().enter();
+        db.tree("block_witnesses")?.insert(block_hash, self.encode()).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn load(db: &BlockchainDB, block_hash: &[u8; 32]) -> Result<Option<Self>, String> {
+        match db.tree("block_witnesses")?.get(block_hash).map_err(|e| e.to_string())? {
+            Some(bytes) => Self::decode(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{QuantumKeyPair, QuantumSignature};
+    use crate::storage::blocks::{ConsensusData, Transaction};
+
+    #[test]
+    fn test_block_witness() {
+        let sender = QuantumKeyPair::generate();
+        let bystanders: Vec<Vec<u8>> = (1..=6).map(|byte| vec![byte * 40; 32]).collect();
+        let mut allocations = vec![(sender.public_key().to_vec(), 10_000)];
+        allocations.extend(bystanders.iter().map(|address| (address.clone(), 500)));
+        let parent = StateManager::from_allocations(&allocations).unwrap();
+
+        // Pays an existing account and creates one that sorts between two others
+        let transfer = |to: Vec<u8>, nonce| {
+            let mut tx = Transaction::new(sender.public_key().to_vec(), to, 100, 1, nonce, vec![], QuantumSignature::new(vec![]));
+            tx.signature = sender.sign(&tx.get_signing_data()).unwrap();
+            tx
+        };
+        let block = Block::new([0; 32], vec![transfer(bystanders[1].clone(), 0), transfer(vec![100; 32], 1)], 1, ConsensusData::default());
+        let mut post = parent.clone();
+        post.apply_block(&block).unwrap();
+        let block = block.with_state_root(post.state_root());
+
+        let witness = BlockWitness::generate(&parent, &block).unwrap();
+        assert!(witness.accounts.len() < allocations.len());
+        let witness = BlockWitness::decode(&witness.encode()).unwrap();
+        witness.verify(&block, parent.state_root()).unwrap();
+
+        // A wrong root, a forged balance or an account left out are all caught
+        assert!(witness.verify(&block.clone().with_state_root([1; 32]), parent.state_root()).unwrap_err().contains("State root mismatch"));
+        assert!(witness.verify(&block, [2; 32]).is_err());
+        let mut forged = witness.clone();
+        forged.accounts[0].account.balance += 1;
+        assert!(forged.verify(&block, parent.state_root()).is_err());
+        let mut partial = witness.clone();
+        partial.accounts.retain(|entry| entry.address != bystanders[1]);
+        assert!(partial.verify(&block, parent.state_root()).unwrap_err().contains("does not cover"));
+
+        println!("   Block witness working!");
+    }
+}
@@ -0,0 +1,274 @@
+use futures::future::BoxFuture;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+use crate::log;
+
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// Failures within `DEFAULT_RESTART_WINDOW` after which a task is given up on
+pub const DEFAULT_MAX_RESTARTS: usize = 5;
+pub const DEFAULT_RESTART_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum RestartPolicy {
+    /// Restart whenever the task stops, since it is meant to run forever
+    Always,
+    /// Restart only after a panic; returning normally means the task is done
+    OnPanic,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TaskState {
+    Running,
+    /// Waiting out the backoff before the next restart
+    Restarting,
+    /// Finished and not restarted
+    Stopped,
+    /// Exceeded its restart budget
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskHealth {
+    pub name: String,
+    pub policy: RestartPolicy,
+    pub state: TaskState,
+    pub restarts: u64,
+    pub last_error: Option<String>,
+}
+
+type Factory = Arc<dyn Fn() -> BoxFuture<'static, ()> + Send + Sync>;
+type FatalHandler = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Aborts the supervised run when its monitor is aborted or dropped
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Owns the node's long-running tasks. A task that panics or stops is restarted with
+/// exponential backoff; one that keeps failing is unrecoverable and triggers the fatal
+/// handler, which by default exits the process
+#[derive(Clone)]
+pub struct Supervisor {
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    max_restarts: usize,
+    restart_window: Duration,
+    on_fatal: FatalHandler,
+    tasks: Arc<Mutex<Vec<TaskHealth>>>,
+    monitors: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self {
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            max_restarts: DEFAULT_MAX_RESTARTS,
+            restart_window: DEFAULT_RESTART_WINDOW,
+            on_fatal: Arc::new(|task| {
+                log!(Error, "Task {} failed repeatedly, shutting down", task);
+                std::process::exit(1);
+            }),
+            tasks: Arc::new(Mutex::new(Vec::new())),
+            monitors: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Gives up on a task after `max_restarts` failures within `window`
+    pub fn with_restart_limit(mut self, max_restarts: usize, window: Duration) -> Self {
+        self.max_restarts = max_restarts;
+        self.restart_window = window;
+        self
+    }
+
+    /// Replaces the default process exit on unrecoverable failures
+    pub fn with_fatal_handler(mut self, handler: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.on_fatal = Arc::new(handler);
+        self
+    }
+
+    /// Runs the future built by `factory` under `policy`, building a fresh one per restart
+    pub fn spawn<F, Fut>(&self, name: &str, policy: RestartPolicy, factory: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let factory: Factory = Arc::new(move || Box::pin(factory()));
+        let index = {
+            let mut tasks = self.tasks.lock().unwrap();
+            tasks.push(TaskHealth {
+                name: name.to_string(),
+                policy,
+                state: TaskState::Running,
+                restarts: 0,
+                last_error: None,
+            });
+            tasks.len() - 1
+        };
+        let monitor = tokio::spawn(self.clone().monitor(index, policy, factory));
+        self.monitors.lock().unwrap().push(monitor);
+    }
+
+    async fn monitor(self, index: usize, policy: RestartPolicy, factory: Factory) {
+        let mut backoff = self.initial_backoff;
+        let mut failures: VecDeque<Instant> = VecDeque::new();
+        loop {
+            self.update(index, |task| task.state = TaskState::Running);
+            let started = Instant::now();
+            let mut run = AbortOnDrop(tokio::spawn(factory()));
+            let (panicked, error) = match (&mut run.0).await {
+                Ok(()) => (false, "exited".to_string()),
+                Err(e) if e.is_panic() => (true, panic_message(e.into_panic())),
+                Err(_) => return,
+            };
+
+            let restart = match policy {
+                RestartPolicy::Always => true,
+                RestartPolicy::OnPanic => panicked,
+                RestartPolicy::Never => false,
+            };
+            if !restart {
+                self.update(index, |task| {
+                    task.state = TaskState::Stopped;
+                    task.last_error = panicked.then_some(error.clone());
+                });
+                return;
+            }
+
+            let now = Instant::now();
+            failures.push_back(now);
+            while failures.front().is_some_and(|failed| now.duration_since(*failed) > self.restart_window) {
+                failures.pop_front();
+            }
+            if started.elapsed() > self.restart_window {
+                backoff = self.initial_backoff;
+            }
+            if failures.len() > self.max_restarts {
+                let name = self.update(index, |task| {
+                    task.state = TaskState::Failed;
+                    task.last_error = Some(error.clone());
+                    task.name.clone()
+                });
+                log!(Error, "Task {} failed {} times: {}", name, failures.len(), error);
+                (self.on_fatal)(&name);
+                return;
+            }
+
+            let name = self.update(index, |task| {
+                task.state = TaskState::Restarting;
+                task.restarts += 1;
+                task.last_error = Some(error.clone());
+                task.name.clone()
+            });
+            log!(Warn, "Task {} {}, restarting in {:?}", name, error, backoff);
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(self.max_backoff);
+        }
+    }
+
+    fn update<T>(&self, index: usize, change: impl FnOnce(&mut TaskHealth) -> T) -> T {
+        change(&mut self.tasks.lock().unwrap()[index])
+    }
+
+    pub fn health(&self) -> Vec<TaskHealth> {
+        self.tasks.lock().unwrap().clone()
+    }
+
+    /// True unless a task that should still be running has stopped or failed
+    pub fn is_healthy(&self) -> bool {
+        self.tasks.lock().unwrap().iter().all(|task| match task.state {
+            TaskState::Failed => false,
+            TaskState::Stopped => task.policy != RestartPolicy::Always,
+            _ => true,
+        })
+    }
+
+    /// Aborts every supervised task
+    pub fn shutdown(&self) {
+        for monitor in self.monitors.lock().unwrap().drain(..) {
+            monitor.abort();
+        }
+        for task in self.tasks.lock().unwrap().iter_mut() {
+            task.state = TaskState::Stopped;
+        }
+    }
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    format!("panicked: {}", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_supervisor_restarts_with_backoff() {
+        let fatal = Arc::new(Mutex::new(Vec::new()));
+        let fatal_log = fatal.clone();
+        let supervisor = Supervisor::new()
+            .with_backoff(Duration::from_millis(5), Duration::from_millis(20))
+            .with_restart_limit(3, Duration::from_secs(60))
+            .with_fatal_handler(move |task| fatal_log.lock().unwrap().push(task.to_string()));
+
+        // Panics twice, then settles into running forever
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        supervisor.spawn("flaky", RestartPolicy::Always, move || {
+            let run = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if run < 2 {
+                    panic!("metric source unavailable");
+                }
+                std::future::pending::<()>().await
+            }
+        });
+        supervisor.spawn("broken", RestartPolicy::OnPanic, || async { panic!("corrupt state") });
+        supervisor.spawn("oneshot", RestartPolicy::OnPanic, || async {});
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let health = supervisor.health();
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert_eq!((health[0].state, health[0].restarts), (TaskState::Running, 2));
+        assert_eq!(health[0].last_error.as_deref(), Some("panicked: metric source unavailable"));
+        assert_eq!((health[1].state, health[1].restarts), (TaskState::Failed, 3));
+        assert_eq!(health[2].state, TaskState::Stopped);
+        assert_eq!(*fatal.lock().unwrap(), vec!["broken".to_string()]);
+        assert!(!supervisor.is_healthy());
+
+        supervisor.shutdown();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+
+        println!("   Task supervisor working!");
+    }
+}
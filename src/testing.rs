@@ -0,0 +1,11 @@
+//! Test support: `TestNode` runs a chain in memory for integration tests, here and in
+//! downstream crates through the `testing` feature, and `strategies` feeds the property
+//! suite in `properties`
+
+mod node;
+#[cfg(test)]
+mod properties;
+#[cfg(test)]
+pub mod strategies;
+
+pub use node::{TestNode, DEV_ACCOUNTS, DEV_BALANCE, TRANSFER_FEE};
@@ -0,0 +1,147 @@
+use std::sync::Arc;
+
+use crate::crypto::{QuantumKeyPair, QuantumSignature};
+use crate::node::{Node, TxStatus};
+use crate::storage::blocks::{Block, Transaction};
+use crate::storage::database::BlockchainDB;
+use crate::storage::state::{Account, StateManager};
+
+/// Funded accounts a `TestNode` starts with
+pub const DEV_ACCOUNTS: usize = 4;
+
+/// Genesis balance of each dev account, in base units (1M TRI)
+pub const DEV_BALANCE: u64 = 1_000_000 * 100_000_000;
+
+/// Fee `TestNode::transfer` pays
+pub const TRANSFER_FEE: u64 = 1;
+
+/// A single node on an in-memory database with funded dev accounts. Nothing runs in the
+/// background: transactions wait in the mempool until `produce_block` seals them, so a
+/// test decides exactly what each block holds
+pub struct TestNode {
+    node: Arc<Node>,
+    accounts: Vec<QuantumKeyPair>,
+}
+
+impl TestNode {
+    /// A chain with `DEV_ACCOUNTS` accounts of `DEV_BALANCE` each
+    pub fn new() -> Result<Self, String> {
+        Self::with_accounts(DEV_ACCOUNTS, DEV_BALANCE)
+    }
+
+    pub fn with_accounts(count: usize, balance: u64) -> Result<Self, String> {
+        let accounts: Vec<QuantumKeyPair> = (0..count).map(|_| QuantumKeyPair::generate()).collect();
+        let allocations: Vec<(Vec<u8>, u64)> = accounts.iter().map(|account| (account.public_key().to_vec(), balance)).collect();
+        let db = BlockchainDB::temporary()?;
+        db.store_genesis_allocations(&allocations)?;
+        Ok(Self { node: Arc::new(Node::open(db)?), accounts })
+    }
+
+    pub fn node(&self) -> &Arc<Node> {
+        &self.node
+    }
+
+    pub fn db(&self) -> &BlockchainDB {
+        self.node.db()
+    }
+
+    pub fn accounts(&self) -> &[QuantumKeyPair] {
+        &self.accounts
+    }
+
+    /// Address of dev account `index`
+    pub fn address(&self, index: usize) -> Vec<u8> {
+        self.accounts[index].public_key().to_vec()
+    }
+
+    /// Signs `tx` with dev account `index`, which must be its sender
+    pub fn sign(&self, index: usize, mut tx: Transaction) -> Result<Transaction, String> {
+        tx.signature = self.accounts[index].sign(&tx.get_signing_data()).map_err(|e| e.to_string())?;
+        Ok(tx)
+    }
+
+    /// Transfer of `amount` from dev account `from`, at its next nonce, ready to submit
+    pub fn signed_transfer(&self, from: usize, to: &[u8], amount: u64) -> Result<Transaction, String> {
+        let sender = self.address(from);
+        let nonce = self.node.next_nonce(&sender);
+        let tx = Transaction::new(sender, to.to_vec(), amount, TRANSFER_FEE, nonce, vec![], QuantumSignature::new(vec![]));
+        self.sign(from, tx)
+    }
+
+    /// Submits a transfer from dev account `from` to the mempool
+    pub fn transfer(&self, from: usize, to: &[u8], amount: u64) -> Result<[u8; 32], String> {
+        self.submit(self.signed_transfer(from, to, amount)?)
+    }
+
+    pub fn submit(&self, tx: Transaction) -> Result<[u8; 32], String> {
+        self.node.submit_transaction(tx)
+    }
+
+    /// Seals the pending transactions into the next block
+    pub fn produce_block(&self) -> Result<Block, String> {
+        self.node.produce_block()
+    }
+
+    pub fn produce_blocks(&self, count: usize) -> Result<Vec<Block>, String> {
+        (0..count).map(|_| self.produce_block()).collect()
+    }
+
+    /// Height the next block will have
+    pub fn height(&self) -> u64 {
+        self.node.next_height().unwrap_or_default()
+    }
+
+    pub fn block(&self, height: u64) -> Result<Option<Block>, String> {
+        self.db().get_block(height)
+    }
+
+    pub fn status(&self, hash: &[u8; 32]) -> Result<Option<TxStatus>, String> {
+        self.node.transaction_status(hash)
+    }
+
+    /// Account at the head of the chain
+    pub fn account(&self, address: &[u8]) -> Option<Account> {
+        self.node.account(address)
+    }
+
+    pub fn balance(&self, address: &[u8]) -> u64 {
+        self.account(address).map_or(0, |account| account.balance)
+    }
+
+    /// State at the head, replayed from genesis so it is independent of the node's own
+    pub fn state(&self) -> Result<StateManager, String> {
+        StateManager::replay(self.db())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_test_node() {
+        let chain = TestNode::with_accounts(2, 1_000).unwrap();
+        assert_eq!(chain.height(), 0);
+        let recipient = chain.address(1);
+
+        // Nothing is sealed until asked
+        let first = chain.transfer(0, &recipient, 100).unwrap();
+        let second = chain.transfer(0, &recipient, 50).unwrap();
+        assert_eq!(chain.status(&first).unwrap(), Some(TxStatus::Pending));
+        assert_eq!(chain.balance(&recipient), 1_000);
+
+        let block = chain.produce_block().unwrap();
+        assert_eq!(block.transaction_count(), 2);
+        assert!(matches!(chain.status(&second).unwrap(), Some(TxStatus::Included { height: 0, .. })));
+        assert_eq!(chain.balance(&recipient), 1_150);
+        assert_eq!(chain.account(&chain.address(0)).unwrap().nonce, 2);
+        assert_eq!(chain.balance(&chain.address(0)), 1_000 - 150 - 2 * TRANSFER_FEE);
+
+        let empty = chain.produce_blocks(2).unwrap();
+        assert_eq!(chain.height(), 3);
+        assert_eq!(chain.block(2).unwrap().unwrap().hash(), empty[1].hash());
+        assert_eq!(chain.state().unwrap().state_root(), block.header.state_root);
+
+        println!("   Test node working!");
+    }
+}
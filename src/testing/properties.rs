@@ -0,0 +1,136 @@
+use proptest::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+use super::strategies::*;
+use crate::crypto::{QuantumKeyPair, QuantumSignature};
+use crate::node::Node;
+use crate::storage::blocks::{Block, Transaction};
+use crate::storage::database::BlockchainDB;
+use crate::storage::state::StateManager;
+
+fn keypair() -> &'static QuantumKeyPair {
+    static KEYPAIR: OnceLock<QuantumKeyPair> = OnceLock::new();
+    KEYPAIR.get_or_init(QuantumKeyPair::generate)
+}
+
+/// A fresh node funding `keypair`, in a directory no other case uses
+fn open_node() -> (Node, std::path::PathBuf) {
+    static CASES: AtomicUsize = AtomicUsize::new(0);
+    let case = CASES.fetch_add(1, Ordering::Relaxed);
+    let path = std::env::temp_dir().join(format!("triunity_test_properties_{}_{}", std::process::id(), case));
+    let _ = std::fs::remove_dir_all(&path);
+    let db = BlockchainDB::new(path.to_str().unwrap()).unwrap();
+    db.store_genesis_allocations(&[(keypair().public_key().to_vec(), 1_000_000)]).unwrap();
+    (Node::open(db).unwrap(), path)
+}
+
+proptest! {
+    #[test]
+    fn prop_supply_is_conserved(allocations in arb_allocations(), transactions in prop::collection::vec(arb_transaction(), 0..32)) {
+        let mut state = StateManager::from_allocations(&allocations).unwrap();
+        let genesis_supply = total_supply(&state);
+        let mut burned = 0u128;
+        for tx in &transactions {
+            if state.apply_transaction(tx).is_ok() {
+                burned += tx.fee as u128;
+            }
+        }
+        prop_assert_eq!(total_supply(&state) + burned, genesis_supply);
+    }
+
+    #[test]
+    fn prop_nonces_only_advance_with_their_sender(allocations in arb_allocations(), transactions in prop::collection::vec(arb_transaction(), 0..32)) {
+        let mut state = StateManager::from_allocations(&allocations).unwrap();
+        for tx in &transactions {
+            let before: Vec<(u64, u64)> = (0..ADDRESS_POOL)
+                .map(|index| state.get_account(&address(index)).map_or((0, 0), |account| (account.balance, account.nonce)))
+                .collect();
+            let applied = state.apply_transaction(tx).is_ok();
+            for index in 0..ADDRESS_POOL {
+                let (balance, nonce) = state.get_account(&address(index)).map_or((0, 0), |account| (account.balance, account.nonce));
+                let expected_nonce = before[index as usize].1 + u64::from(applied && tx.from == address(index));
+                prop_assert_eq!(nonce, expected_nonce);
+                if !applied {
+                    prop_assert_eq!(balance, before[index as usize].0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn prop_state_root_ignores_execution_order((ordered, interleaved) in arb_interleaved_transfers()) {
+        let (mut first, mut second) = (funded_state(), funded_state());
+        for tx in &ordered {
+            prop_assert!(first.apply_transaction(tx).is_ok());
+        }
+        for tx in &interleaved {
+            prop_assert!(second.apply_transaction(tx).is_ok());
+        }
+        prop_assert_eq!(first.state_root(), second.state_root());
+    }
+
+    #[test]
+    fn prop_state_root_ignores_allocation_order(allocations in arb_allocations().prop_shuffle(), block in arb_block(0)) {
+        let mut reversed = allocations.clone();
+        reversed.reverse();
+        let (mut first, mut second) = (StateManager::from_allocations(&allocations).unwrap(), StateManager::from_allocations(&reversed).unwrap());
+        prop_assert_eq!(first.apply_block(&block).is_ok(), second.apply_block(&block).is_ok());
+        prop_assert_eq!(first.state_root(), second.state_root());
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(12))]
+
+    #[test]
+    fn prop_reorg_keeps_state_consistent(plan in arb_reorg()) {
+        let ((ours, our_path), (theirs, their_path)) = (open_node(), open_node());
+        for _ in 0..plan.shared {
+            theirs.import_block(&ours.produce_block().unwrap()).unwrap();
+        }
+        for (nonce, amount) in plan.transfers.iter().enumerate() {
+            let mut tx = Transaction::new(keypair().public_key().to_vec(), address(0), *amount, 1, nonce as u64, vec![], QuantumSignature::new(vec![]));
+            tx.signature = keypair().sign(&tx.get_signing_data()).unwrap();
+            theirs.submit_transaction(tx).unwrap();
+        }
+        for _ in 0..plan.ours {
+            ours.produce_block().unwrap();
+        }
+        let their_blocks: Vec<Block> = (0..plan.theirs).map(|_| theirs.produce_block().unwrap()).collect();
+        for block in &their_blocks {
+            ours.import_block(block).unwrap();
+        }
+
+        let head = ours.fork_choice_head();
+        if plan.theirs > plan.ours {
+            prop_assert_eq!(head.head, their_blocks.last().unwrap().hash());
+        }
+        prop_assert_eq!(head.head_height + 1, plan.shared + plan.ours.max(plan.theirs));
+
+        // The stored canonical chain links up and replays to the head's state root
+        let db = ours.db();
+        for height in 1..=head.head_height {
+            let parent = db.get_block(height - 1).unwrap().unwrap();
+            prop_assert_eq!(db.get_block(height).unwrap().unwrap().header.previous_hash, parent.hash());
+        }
+        let replayed = StateManager::replay(db).unwrap();
+        prop_assert_eq!(replayed.state_root(), db.get_block(head.head_height).unwrap().unwrap().header.state_root);
+        let burned: u128 = (0..=head.head_height)
+            .map(|height| db.get_block(height).unwrap().unwrap().total_fees() as u128)
+            .sum();
+        prop_assert_eq!(total_supply(&replayed) + burned, 1_000_000);
+        let _ = std::fs::remove_dir_all(our_path);
+        let _ = std::fs::remove_dir_all(their_path);
+    }
+}
+
+/// Minimal case the supply property found: a sender sponsoring itself was charged the
+/// fee on top of a balance check that only covered the amount
+#[test]
+fn self_sponsored_fee_is_charged_once() {
+    let mut state = StateManager::from_allocations(&[(address(0), 100)]).unwrap();
+    let tx = Transaction::new(address(0), address(1), 95, 10, 0, vec![], QuantumSignature::new(vec![])).with_fee_payer(address(0));
+    assert!(state.apply_transaction(&tx).is_err());
+    assert_eq!(total_supply(&state), 100);
+}
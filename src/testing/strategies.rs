@@ -0,0 +1,98 @@
+//! Proptest strategies for the state machine, used by the property suite in `properties`
+
+use proptest::prelude::*;
+
+use crate::crypto::QuantumSignature;
+use crate::storage::blocks::{Block, ConsensusData, Transaction};
+use crate::storage::state::StateManager;
+
+/// Distinct addresses generated transactions draw from, few enough that senders,
+/// recipients and sponsors collide often
+pub const ADDRESS_POOL: u8 = 6;
+
+pub fn address(index: u8) -> Vec<u8> {
+    vec![index + 1; 32]
+}
+
+pub fn arb_address() -> impl Strategy<Value = Vec<u8>> {
+    (0..ADDRESS_POOL).prop_map(address)
+}
+
+/// Genesis balances, possibly several for the same address
+pub fn arb_allocations() -> impl Strategy<Value = Vec<(Vec<u8>, u64)>> {
+    prop::collection::vec((arb_address(), 0u64..2_000), 1..8)
+}
+
+/// Unsigned transfers, some sponsored. State transitions do not check signatures, so
+/// many of these fail on nonce or balance instead
+pub fn arb_transaction() -> impl Strategy<Value = Transaction> {
+    (arb_address(), arb_address(), 0u64..500, 0u64..10, 0u64..4, prop::option::weighted(0.2, arb_address()))
+        .prop_map(|(from, to, amount, fee, nonce, sponsor)| {
+            let tx = Transaction::new(from, to, amount, fee, nonce, vec![], QuantumSignature::new(vec![]));
+            match sponsor {
+                Some(sponsor) => tx.with_fee_payer(sponsor),
+                None => tx,
+            }
+        })
+}
+
+pub fn arb_block(height: u64) -> impl Strategy<Value = Block> {
+    prop::collection::vec(arb_transaction(), 0..8)
+        .prop_map(move |transactions| Block::new([0; 32], transactions, height, ConsensusData::default()))
+}
+
+/// Transfers that all apply against `funded_state`, in nonce order per sender, and
+/// the same transfers interleaved across senders in another order
+pub fn arb_interleaved_transfers() -> impl Strategy<Value = (Vec<Transaction>, Vec<Transaction>)> {
+    prop::collection::vec((0..ADDRESS_POOL, arb_address(), 1u64..100, 0u64..5), 1..24)
+        .prop_flat_map(|transfers| {
+            let mut nonces = [0u64; ADDRESS_POOL as usize];
+            let transactions: Vec<Transaction> = transfers
+                .into_iter()
+                .map(|(sender, to, amount, fee)| {
+                    let nonce = nonces[sender as usize];
+                    nonces[sender as usize] += 1;
+                    Transaction::new(address(sender), to, amount, fee, nonce, vec![], QuantumSignature::new(vec![]))
+                })
+                .collect();
+            let order: Vec<usize> = (0..transactions.len()).collect();
+            (Just(transactions), Just(order).prop_shuffle())
+        })
+        .prop_map(|(transactions, order)| {
+            // Each sender's transactions keep their nonce order within the shuffle
+            let mut queues: Vec<Vec<Transaction>> = vec![Vec::new(); ADDRESS_POOL as usize];
+            for tx in transactions.iter().rev() {
+                queues[(tx.from[0] - 1) as usize].push(tx.clone());
+            }
+            let interleaved = order
+                .into_iter()
+                .filter_map(|index| queues[(transactions[index].from[0] - 1) as usize].pop())
+                .collect();
+            (transactions, interleaved)
+        })
+}
+
+/// Every pool address holding enough to pay for any `arb_interleaved_transfers`
+pub fn funded_state() -> StateManager {
+    let allocations: Vec<(Vec<u8>, u64)> = (0..ADDRESS_POOL).map(|index| (address(index), 1_000_000)).collect();
+    StateManager::from_allocations(&allocations).expect("pool allocations fit in a balance")
+}
+
+/// A chain both nodes share, then a branch of `ours` blocks on one node and `theirs`
+/// on the other, carrying transfers of `transfers` amounts
+#[derive(Debug, Clone)]
+pub struct ReorgPlan {
+    pub shared: u64,
+    pub ours: u64,
+    pub theirs: u64,
+    pub transfers: Vec<u64>,
+}
+
+pub fn arb_reorg() -> impl Strategy<Value = ReorgPlan> {
+    (1u64..4, 1u64..4, 1u64..5, prop::collection::vec(1u64..50, 0..4))
+        .prop_map(|(shared, ours, theirs, transfers)| ReorgPlan { shared, ours, theirs, transfers })
+}
+
+pub fn total_supply(state: &StateManager) -> u128 {
+    state.accounts().map(|(_, account)| account.balance as u128).sum()
+}
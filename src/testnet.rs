@@ -0,0 +1,280 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::consensus::gas::GasSchedule;
+use crate::consensus::params::ParamSchedule;
+use crate::crypto::QuantumKeyPair;
+use crate::config::NetworkConfig;
+use crate::network::profile::NetworkId;
+use crate::network::NetworkService;
+use crate::node::Node;
+use crate::storage::database::BlockchainDB;
+use crate::supervisor::{RestartPolicy, Supervisor};
+
+/// Genesis balance for each testnet validator, in base units (1M TRI)
+pub const VALIDATOR_GENESIS_BALANCE: u64 = 1_000_000 * 100_000_000;
+
+const MEMPOOL_GC_INTERVAL: Duration = Duration::from_secs(5);
+const STATE_PRUNING_INTERVAL: Duration = Duration::from_secs(30);
+const BLOCK_FREEZING_INTERVAL: Duration = Duration::from_secs(30);
+const PEER_EXCHANGE_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct TestnetConfig {
+    pub nodes: usize,
+    pub validators: usize,
+    /// Node `i` listens on `base_port + i`; 0 picks free ports
+    pub base_port: u16,
+    pub block_time: Duration,
+    pub data_dir: PathBuf,
+    /// Run every node in archive mode, keeping all historical state
+    pub archive: bool,
+    /// Move finalized blocks this far below the finalized block to the ancient store
+    pub ancient_after: Option<u64>,
+    /// Store an execution witness with every block the validators produce
+    pub witnesses: bool,
+    /// Keep blocks each node refuses on import in `<dir>/<node name>`
+    pub bad_block_dir: Option<PathBuf>,
+    /// Gas schedule stored in every node's genesis
+    pub gas_schedule: GasSchedule,
+    /// Accounts funded at genesis besides the validators, such as a faucet
+    pub genesis_accounts: Vec<(Vec<u8>, u64)>,
+    /// Chain parameters every node starts from, with any scheduled upgrades
+    pub params: ParamSchedule,
+    /// Network whose chain id the nodes handshake with
+    pub network: NetworkId,
+}
+
+impl TestnetConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.nodes == 0 {
+            return Err("A testnet needs at least one node".to_string());
+        }
+        if self.validators == 0 || self.validators > self.nodes {
+            return Err(format!("Validators must be between 1 and {}", self.nodes));
+        }
+        if self.base_port != 0 && self.base_port as usize + self.nodes > u16::MAX as usize {
+            return Err("Node ports would exceed 65535".to_string());
+        }
+        self.params.validate()
+    }
+}
+
+pub struct TestnetNode {
+    pub name: String,
+    pub address: SocketAddr,
+    pub is_validator: bool,
+    pub network: Arc<NetworkService>,
+}
+
+/// In-process local network sharing one genesis, fully meshed over TCP
+pub struct Testnet {
+    pub nodes: Vec<TestnetNode>,
+    supervisor: Supervisor,
+}
+
+impl Testnet {
+    pub async fn launch(config: TestnetConfig) -> Result<Self, String> {
+        config.validate()?;
+        let validator_keys: Vec<QuantumKeyPair> = (0..config.validators).map(|_| QuantumKeyPair::generate()).collect();
+        let validators: Vec<Vec<u8>> = validator_keys.iter().map(|key| key.public_key().to_vec()).collect();
+        let allocations: Vec<(Vec<u8>, u64)> = validators
+            .iter()
+            .map(|validator| (validator.clone(), VALIDATOR_GENESIS_BALANCE))
+            .chain(config.genesis_accounts.iter().cloned())
+            .collect();
+
+        let mut nodes: Vec<TestnetNode> = Vec::new();
+        // Nodes keep their own fresh identity; the first ones also get a validator key
+        let mut validator_keys = validator_keys.into_iter();
+        for index in 0..config.nodes {
+            let name = format!("node-{}", index);
+            let path = config.data_dir.join(&name);
+            let _ = std::fs::remove_dir_all(&path);
+            let db = BlockchainDB::new(path.to_str().ok_or("Invalid data directory")?)?;
+            config.network.claim(&db)?;
+            db.store_genesis_allocations(&allocations)?;
+            db.store_genesis_validators(&validators)?;
+            db.store_gas_schedule(&config.gas_schedule)?;
+            db.store_chain_params(&config.params)?;
+
+            let mut node = Node::open(db)?;
+            if let Some(key) = validator_keys.next() {
+                node = node.with_validator_key(key);
+            }
+            if config.archive {
+                node = node.with_archive_mode();
+            }
+            if let Some(keep) = config.ancient_after {
+                node = node.with_ancient_store(keep);
+            }
+            if config.witnesses {
+                node = node.with_block_witnesses();
+            }
+            if let Some(dir) = &config.bad_block_dir {
+                node = node.with_bad_block_quarantine(dir.join(&name));
+            }
+            let network = NetworkService::with_config(Arc::new(node), &NetworkConfig { id: config.network, ..Default::default() });
+            let port = if config.base_port == 0 { 0 } else { config.base_port + index as u16 };
+            let address = network.listen(SocketAddr::from(([127, 0, 0, 1], port))).await?;
+
+            // Only the first node is dialed; the rest are found through peer exchange
+            if let Some(bootstrap) = nodes.first() {
+                network.connect(bootstrap.address).await?;
+            }
+            nodes.push(TestnetNode { name, address, is_validator: index < config.validators, network });
+        }
+
+        let supervisor = Supervisor::new();
+        for node in &nodes {
+            if node.is_validator {
+                let network = node.network.clone();
+                let block_time = config.block_time;
+                supervisor.spawn(&format!("{}/proposer", node.name), RestartPolicy::Always, move || {
+                    network.clone().run_proposer(block_time)
+                });
+            }
+            let chain = node.network.node().clone();
+            supervisor.spawn(&format!("{}/mempool-gc", node.name), RestartPolicy::Always, move || {
+                chain.clone().run_mempool_gc(MEMPOOL_GC_INTERVAL)
+            });
+            let network = node.network.clone();
+            supervisor.spawn(&format!("{}/peer-exchange", node.name), RestartPolicy::Always, move || {
+                network.clone().run_peer_exchange(PEER_EXCHANGE_INTERVAL)
+            });
+            let chain = node.network.node().clone();
+            supervisor.spawn(&format!("{}/state-pruning", node.name), RestartPolicy::Always, move || {
+                chain.clone().run_state_pruning(STATE_PRUNING_INTERVAL)
+            });
+            if config.ancient_after.is_some() {
+                let chain = node.network.node().clone();
+                supervisor.spawn(&format!("{}/block-freezing", node.name), RestartPolicy::Always, move || {
+                    chain.clone().run_block_freezing(BLOCK_FREEZING_INTERVAL)
+                });
+            }
+        }
+
+        Ok(Self { nodes, supervisor })
+    }
+
+    /// Next block height expected by each node
+    pub fn heights(&self) -> Vec<u64> {
+        self.nodes
+            .iter()
+            .map(|node| node.network.node().next_height().unwrap_or_default())
+            .collect()
+    }
+
+    pub fn supervisor(&self) -> &Supervisor {
+        &self.supervisor
+    }
+
+    pub fn shutdown(&self) {
+        self.supervisor.shutdown();
+    }
+}
+
+impl Drop for Testnet {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::compression::Compression;
+    use crate::network::message::PROTOCOL_VERSION;
+    use crate::network::topology::Direction;
+    use crate::storage::state::StateManager;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_testnet_converges() {
+        let data_dir = std::env::temp_dir().join("triunity_test_testnet");
+        let testnet = Testnet::launch(TestnetConfig {
+            nodes: 4,
+            validators: 3,
+            base_port: 0,
+            block_time: Duration::from_millis(100),
+            data_dir: data_dir.clone(),
+            archive: false,
+            ancient_after: None,
+            witnesses: false,
+            bad_block_dir: None,
+            gas_schedule: GasSchedule::default(),
+            genesis_accounts: vec![(vec![0xfa; 32], 5_000)],
+            params: ParamSchedule::default(),
+            network: NetworkId::Devnet,
+        })
+        .await
+        .unwrap();
+
+        let mut heights = testnet.heights();
+        for _ in 0..100 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            heights = testnet.heights();
+            let meshed = testnet.nodes.iter().all(|node| node.network.peers().len() == 3);
+            if meshed && heights.iter().all(|height| *height >= 6) {
+                break;
+            }
+        }
+        assert!(heights.iter().all(|height| *height >= 6), "heights {:?}", heights);
+
+        assert_eq!(testnet.supervisor().health().len(), 3 + 4 * 3);
+        assert!(testnet.supervisor().is_healthy());
+
+        // Nodes of another network, or of another chain on this one, are refused
+        let stray_dir = std::env::temp_dir().join("triunity_test_testnet_stray");
+        let mut strays = Vec::new();
+        for (index, network) in [NetworkId::Testnet, NetworkId::Devnet].into_iter().enumerate() {
+            let path = stray_dir.join(network.name());
+            let _ = std::fs::remove_dir_all(&path);
+            let db = BlockchainDB::new(path.to_str().unwrap()).unwrap();
+            db.store_genesis_allocations(&[(vec![index as u8; 32], 1)]).unwrap();
+            let stray = NetworkService::with_config(Arc::new(Node::open(db).unwrap()), &NetworkConfig { id: network, ..Default::default() });
+            stray.connect(testnet.nodes[0].address).await.unwrap();
+            strays.push(stray);
+        }
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(strays.iter().all(|stray| stray.peers().is_empty()));
+        assert_eq!(testnet.nodes[0].network.peers().len(), 3);
+        drop(strays);
+        let _ = std::fs::remove_dir_all(&stray_dir);
+
+        testnet.shutdown();
+        let hashes: Vec<[u8; 32]> = testnet.nodes
+            .iter()
+            .map(|node| node.network.node().db().get_block(5).unwrap().unwrap().hash())
+            .collect();
+        assert!(hashes.windows(2).all(|pair| pair[0] == pair[1]));
+        let state = StateManager::replay(testnet.nodes[3].network.node().db()).unwrap();
+        assert_eq!(state.get_account(&[0xfa; 32]).unwrap().balance, 5_000);
+        assert!(testnet.nodes.iter().all(|node| node.network.peers().len() == 3));
+        // Every node hears from three peers that it connects from loopback
+        let node = &testnet.nodes[0];
+        assert_eq!(node.network.external_address(), Some(node.address));
+        assert!(node.network.peers().iter().all(|peer| {
+            peer.protocol == PROTOCOL_VERSION && peer.compression == Some(Compression::Zstd)
+        }));
+        let views = node.network.peer_views();
+        assert!(views.iter().any(|view| view.direction == Direction::Inbound));
+        assert!(views.iter().all(|view| view.traffic.received > 0 && view.address.is_some()));
+        let topology = node.network.topology();
+        assert_eq!(topology.nodes.len(), 4);
+        assert!(topology.nodes.iter().filter(|peer| peer.connected).count() == 3);
+        let statuses = node.network.node().validator_statuses().unwrap();
+        assert_eq!(statuses.len(), 3);
+        assert!(statuses[0].local && !statuses[1].local);
+        assert!(statuses.iter().all(|status| status.last_proposed_height.is_some() && status.uptime > 0.0));
+        let stats = node.network.stats();
+        assert_eq!(stats.peers.len(), 3);
+        assert!(stats.total.received >= stats.peers.iter().map(|peer| peer.traffic.received).sum::<u64>());
+        assert!(stats.messages.iter().any(|message| message.message == "Proposal" && message.traffic.sent > 0));
+
+        println!("   Local testnet working!");
+        drop(testnet);
+        let _ = std::fs::remove_dir_all(&data_dir);
+    }
+}
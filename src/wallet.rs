@@ -0,0 +1,371 @@
+pub mod abi;
+pub mod keystore;
+
+use serde::Serialize;
+use sha3::{Digest, Sha3_256};
+
+use crate::crypto::bech32::{encode_address, short_address};
+use crate::interop::channel::{ChannelMsg, CHANNELS};
+use crate::interop::transfer::{FungibleTokenPacket, TRANSFER_PORT};
+use crate::interop::{CrossChainMessage, RelayCall, OUTBOX, RELAY};
+use crate::storage::batch::{BatchCall, BATCH};
+use crate::storage::blocks::Transaction;
+use crate::storage::bloom::TOPIC_LENGTH;
+use crate::storage::names::{NameCall, NAME_REGISTRY};
+use crate::storage::schedule::{ScheduleCall, SCHEDULER};
+use crate::storage::sessions::{SessionCall, SESSION_KEYS};
+use crate::storage::vesting::{VestingCreate, VESTING};
+use abi::Abi;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntentKind {
+    Transfer,
+    /// Call on one of the built-in system contracts
+    SystemCall,
+    ContractCall,
+}
+
+/// Method a transaction calls, with its arguments rendered for display
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MethodIntent {
+    /// System contract or ABI method name; `None` when the call data could not be decoded
+    pub name: Option<String>,
+    /// Hex of the leading call data bytes, for contract calls
+    pub selector: Option<String>,
+    pub arguments: Vec<(String, String)>,
+}
+
+/// What a transaction does, in the terms a signer should confirm before signing it.
+/// Field values are display strings short enough for a hardware wallet screen, apart
+/// from the recipient, which is kept whole so it can be checked against the source
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TransactionIntent {
+    pub kind: IntentKind,
+    pub from: String,
+    pub recipient: String,
+    /// Name the user typed for the recipient, if any
+    pub recipient_name: Option<String>,
+    pub amount: u64,
+    pub fee: u64,
+    pub fee_payer: Option<String>,
+    pub nonce: u64,
+    pub valid_until_height: Option<u64>,
+    pub memo: Option<String>,
+    pub method: Option<MethodIntent>,
+    /// Anything the signer should not approve without a second look
+    pub warnings: Vec<String>,
+    /// SHA3-256 of the bincode signing payload, for comparing with a device's display
+    pub payload_hash: String,
+}
+
+impl TransactionIntent {
+    /// Decodes calls on system contracts natively and contract calls through `abi`
+    pub fn from_transaction(tx: &Transaction, abi: Option<&Abi>) -> Self {
+        let mut warnings = Vec::new();
+        let (kind, method) = match system_contract(&tx.to) {
+            Some(contract) => {
+                let method = contract.decode(&tx.data).unwrap_or_else(|e| {
+                    warnings.push(format!("Malformed {} call: {}", contract.name(), e));
+                    MethodIntent { name: None, selector: None, arguments: Vec::new() }
+                });
+                (IntentKind::SystemCall, Some(method))
+            }
+            None if tx.data.is_empty() => (IntentKind::Transfer, None),
+            None => (IntentKind::ContractCall, Some(contract_method(&tx.data, abi, &mut warnings))),
+        };
+        Self {
+            kind,
+            from: short_address(&tx.from),
+            recipient: encode_address(&tx.to),
+            recipient_name: None,
+            amount: tx.value(),
+            fee: tx.fee,
+            fee_payer: tx.fee_payer.as_ref().map(|payer| short_address(&payer.address)),
+            nonce: tx.nonce,
+            valid_until_height: tx.valid_until_height,
+            memo: tx.memo.clone(),
+            method,
+            warnings,
+            payload_hash: hex::encode(Sha3_256::digest(tx.get_signing_data())),
+        }
+    }
+
+    pub fn with_recipient_name(mut self, name: &str) -> Self {
+        self.recipient_name = Some(name.to_string());
+        self
+    }
+
+    /// Label and value pairs in the order a device should page through them
+    pub fn lines(&self) -> Vec<(String, String)> {
+        let mut lines = vec![("Action".to_string(), self.action())];
+        lines.push(("From".to_string(), self.from.clone()));
+        lines.push(("To".to_string(), self.recipient.clone()));
+        if let Some(name) = &self.recipient_name {
+            lines.push(("Name".to_string(), name.clone()));
+        }
+        lines.push(("Amount".to_string(), self.amount.to_string()));
+        lines.push(("Fee".to_string(), self.fee.to_string()));
+        if let Some(payer) = &self.fee_payer {
+            lines.push(("Fee paid by".to_string(), payer.clone()));
+        }
+        lines.push(("Nonce".to_string(), self.nonce.to_string()));
+        if let Some(height) = self.valid_until_height {
+            lines.push(("Valid until".to_string(), format!("height {}", height)));
+        }
+        if let Some(memo) = &self.memo {
+            // Quoted and escaped so hidden characters cannot pass unseen
+            lines.push(("Memo".to_string(), format!("{:?}", memo)));
+        }
+        if let Some(method) = &self.method {
+            if let Some(selector) = &method.selector {
+                lines.push(("Selector".to_string(), selector.clone()));
+            }
+            lines.extend(method.arguments.iter().cloned());
+        }
+        lines.extend(self.warnings.iter().map(|warning| ("Warning".to_string(), warning.clone())));
+        lines.push(("Payload hash".to_string(), self.payload_hash.clone()));
+        lines
+    }
+
+    pub fn render(&self) -> String {
+        let width = self.lines().iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+        self.lines()
+            .into_iter()
+            .map(|(label, value)| format!("{:width$}  {}", label, value, width = width))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn action(&self) -> String {
+        match (&self.kind, self.method.as_ref().and_then(|method| method.name.as_deref())) {
+            (IntentKind::Transfer, _) => "Transfer".to_string(),
+            (_, Some(name)) => format!("Call {}", name),
+            (_, None) => "Call (unknown method)".to_string(),
+        }
+    }
+}
+
+fn contract_method(data: &[u8], abi: Option<&Abi>, warnings: &mut Vec<String>) -> MethodIntent {
+    let selector = data.get(..TOPIC_LENGTH).map(hex::encode);
+    let decoded = abi.and_then(|abi| abi.method_for(data)).map(|method| (method, method.decode(data)));
+    match decoded {
+        Some((method, Ok(arguments))) => MethodIntent {
+            name: Some(method.name.clone()),
+            selector,
+            arguments: arguments.into_iter().map(|(name, value)| (name, value.to_string())).collect(),
+        },
+        Some((method, Err(e))) => {
+            warnings.push(format!("Call data does not match {}: {}", method.signature(), e));
+            MethodIntent { name: Some(method.name.clone()), selector, arguments: Vec::new() }
+        }
+        None => {
+            warnings.push(format!("Unknown contract method; {} bytes of call data are signed blind", data.len()));
+            MethodIntent { name: None, selector, arguments: Vec::new() }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SystemContract {
+    Names,
+    Scheduler,
+    Vesting,
+    Sessions,
+    Relay,
+    Outbox,
+    Batch,
+}
+
+fn system_contract(address: &[u8]) -> Option<SystemContract> {
+    match <[u8; 32]>::try_from(address).ok()? {
+        NAME_REGISTRY => Some(SystemContract::Names),
+        SCHEDULER => Some(SystemContract::Scheduler),
+        VESTING => Some(SystemContract::Vesting),
+        SESSION_KEYS => Some(SystemContract::Sessions),
+        RELAY => Some(SystemContract::Relay),
+        OUTBOX => Some(SystemContract::Outbox),
+        BATCH => Some(SystemContract::Batch),
+        _ => None,
+    }
+}
+
+fn method(name: &str, arguments: Vec<(&str, String)>) -> MethodIntent {
+    MethodIntent {
+        name: Some(name.to_string()),
+        selector: None,
+        arguments: arguments.into_iter().map(|(label, value)| (label.to_string(), value)).collect(),
+    }
+}
+
+impl SystemContract {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Names => "name registry",
+            Self::Scheduler => "scheduler",
+            Self::Vesting => "vesting",
+            Self::Sessions => "session keys",
+            Self::Relay => "relay",
+            Self::Outbox => "outbox",
+            Self::Batch => "batch",
+        }
+    }
+
+    fn decode(self, data: &[u8]) -> Result<MethodIntent, String> {
+        Ok(match self {
+            Self::Names => match NameCall::decode(data)? {
+                NameCall::Register { name, target } => method("names.register", vec![("Name", name), ("Target", encode_address(&target))]),
+                NameCall::Renew { name } => method("names.renew", vec![("Name", name)]),
+                NameCall::SetTarget { name, target } => method("names.set_target", vec![("Name", name), ("Target", encode_address(&target))]),
+            },
+            Self::Scheduler => match ScheduleCall::decode(data)? {
+                ScheduleCall::Transfer { to, execute_at } => {
+                    method("scheduler.transfer", vec![("Pay to", encode_address(&to)), ("At height", execute_at.to_string())])
+                }
+                ScheduleCall::Cancel { id } => method("scheduler.cancel", vec![("Transfer", hex::encode(id))]),
+            },
+            Self::Vesting => {
+                let create = VestingCreate::decode(data)?;
+                method(
+                    "vesting.create",
+                    vec![
+                        ("Beneficiary", encode_address(&create.beneficiary)),
+                        ("Cliff blocks", create.cliff_blocks.to_string()),
+                        ("Duration blocks", create.duration_blocks.to_string()),
+                    ],
+                )
+            }
+            Self::Sessions => match SessionCall::decode(data)? {
+                SessionCall::Authorize { key, epochs } => method("sessions.authorize", vec![("Session key", hex::encode(key)), ("Epochs", epochs.to_string())]),
+                SessionCall::Revoke => method("sessions.revoke", vec![]),
+            },
+            Self::Relay => match RelayCall::decode(data)? {
+                RelayCall::CreateClient { chain_id, local_chain, header } => method(
+                    "relay.create_client",
+                    vec![("Chain", chain_id), ("Local chain", local_chain), ("Trusted height", header.height.to_string())],
+                ),
+                RelayCall::Deliver { chain_id, headers, proof } => method(
+                    "relay.deliver",
+                    vec![("Chain", chain_id), ("Headers", headers.len().to_string()), ("Proven height", proof.height.to_string())],
+                ),
+            },
+            Self::Outbox => outbox_method(CrossChainMessage::decode(data)?)?,
+            Self::Batch => batch_method(BatchCall::decode(data)?),
+        })
+    }
+}
+
+/// One line per operation, naming the system contract method it calls if any
+fn batch_method(batch: BatchCall) -> MethodIntent {
+    let arguments = batch.operations.iter().enumerate().map(|(index, operation)| {
+        let call = match system_contract(&operation.to) {
+            Some(contract) => contract.decode(&operation.data).ok().and_then(|method| method.name).unwrap_or_else(|| format!("malformed {} call", contract.name())),
+            None if operation.data.is_empty() => "transfer".to_string(),
+            None => format!("contract call, {} bytes", operation.data.len()),
+        };
+        (format!("Operation {}", index), format!("{} to {} ({})", operation.amount, encode_address(&operation.to), call))
+    });
+    MethodIntent { name: Some("batch".to_string()), selector: None, arguments: arguments.collect() }
+}
+
+fn outbox_method(message: CrossChainMessage) -> Result<MethodIntent, String> {
+    let mut arguments = vec![("Destination", message.destination_chain.clone()), ("Sequence", message.sequence.to_string())];
+    let channel = message.receiver == CHANNELS;
+    if !channel {
+        arguments.push(("Receiver", encode_address(&message.receiver)));
+        arguments.push(("Payload", format!("{} bytes", message.payload.len())));
+        return Ok(method("outbox.send", arguments));
+    }
+    let name = match ChannelMsg::decode(&message.payload)? {
+        ChannelMsg::OpenInit { port, channel_id, counterparty_port } => {
+            arguments.extend([("Port", port), ("Channel", channel_id), ("Counterparty port", counterparty_port)]);
+            "channel.open_init"
+        }
+        ChannelMsg::OpenTry { port, channel_id, counterparty_channel, .. } => {
+            arguments.extend([("Port", port), ("Channel", channel_id), ("Counterparty channel", counterparty_channel)]);
+            "channel.open_try"
+        }
+        ChannelMsg::OpenAck { port, channel_id, counterparty_channel, .. } => {
+            arguments.extend([("Port", port), ("Channel", channel_id), ("Counterparty channel", counterparty_channel)]);
+            "channel.open_ack"
+        }
+        ChannelMsg::Packet(packet) => {
+            arguments.extend([
+                ("Channel", format!("{}/{}", packet.source_port, packet.source_channel)),
+                ("Timeout height", packet.timeout_height.to_string()),
+            ]);
+            if packet.source_port != TRANSFER_PORT {
+                arguments.push(("Packet data", format!("{} bytes", packet.data.len())));
+                "channel.send_packet"
+            } else {
+                let transfer = FungibleTokenPacket::decode(&packet.data)?;
+                arguments.extend([
+                    ("Denom", transfer.denom),
+                    ("Token amount", transfer.amount.to_string()),
+                    ("Receiver", encode_address(&transfer.receiver)),
+                ]);
+                "transfer.send"
+            }
+        }
+        ChannelMsg::Acknowledge { packet, .. } => {
+            arguments.extend([("Channel", format!("{}/{}", packet.destination_port, packet.destination_channel)), ("Packet", packet.sequence.to_string())]);
+            "channel.acknowledge"
+        }
+        ChannelMsg::Timeout(packet) => {
+            arguments.extend([("Channel", format!("{}/{}", packet.destination_port, packet.destination_channel)), ("Packet", packet.sequence.to_string())]);
+            "channel.timeout"
+        }
+    };
+    Ok(method(name, arguments))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::QuantumSignature;
+    use crate::storage::envelope::TxEnvelope;
+    use abi::AbiValue;
+
+    #[test]
+    fn test_transaction_intent() {
+        let tx = |to: Vec<u8>, data: Vec<u8>| Transaction::new(vec![0x11; 32], to, 250, 3, 7, data, QuantumSignature::new(vec![]));
+
+        // A plain transfer, previewed from the envelope a wallet would sign
+        let transfer = tx(vec![0xaa; 20], vec![]).with_valid_until(900).with_fee_payer(vec![0x22; 32]).with_memo("rent\u{202e}");
+        let envelope = TxEnvelope::decode(&transfer.envelope().encode()).unwrap();
+        let intent = TransactionIntent::from_transaction(&envelope.into(), None).with_recipient_name("alice.tri");
+        assert_eq!(intent.kind, IntentKind::Transfer);
+        assert_eq!(intent.recipient, encode_address(&[0xaa; 20]));
+        assert_eq!(intent.fee_payer, Some(short_address(&[0x22; 32])));
+        assert_eq!(intent.payload_hash, hex::encode(Sha3_256::digest(transfer.get_signing_data())));
+        let rendered = intent.render();
+        assert!(rendered.starts_with("Action"));
+        assert!(rendered.contains("alice.tri") && rendered.contains("height 900"));
+        assert!(rendered.contains(r#""rent\u{202e}""#));
+        assert!(intent.warnings.is_empty());
+
+        // System contract calls decode without an ABI
+        let register = NameCall::Register { name: "alice.tri".to_string(), target: vec![0xaa; 20] };
+        let intent = TransactionIntent::from_transaction(&tx(NAME_REGISTRY.to_vec(), register.encode()), None);
+        assert_eq!(intent.kind, IntentKind::SystemCall);
+        let method = intent.method.as_ref().unwrap();
+        assert_eq!(method.name.as_deref(), Some("names.register"));
+        assert!(method.arguments.contains(&("Target".to_string(), encode_address(&[0xaa; 20]))));
+        let malformed = TransactionIntent::from_transaction(&tx(SCHEDULER.to_vec(), vec![0xff]), None);
+        assert!(malformed.warnings[0].starts_with("Malformed scheduler call"));
+
+        // Contract calls decode through the ABI, and are flagged without it
+        let abi = Abi::from_json(r#"{"methods": [{"name": "mint", "inputs": [{"name": "amount", "type": "u64"}]}]}"#).unwrap();
+        let data = abi.method("mint").unwrap().encode(&[AbiValue::U64(42)]).unwrap();
+        let call = tx(vec![0xcc; 32], data.clone());
+        let intent = TransactionIntent::from_transaction(&call, Some(&abi));
+        assert_eq!(intent.kind, IntentKind::ContractCall);
+        assert!(intent.lines().contains(&("amount".to_string(), "42".to_string())));
+        assert_eq!(intent.method.unwrap().selector, Some(hex::encode(&data[..4])));
+        let blind = TransactionIntent::from_transaction(&call, None);
+        assert!(blind.render().contains("Call (unknown method)"));
+        assert_eq!(blind.warnings.len(), 1);
+
+        println!("   Transaction intent working!");
+    }
+}
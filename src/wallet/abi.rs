@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+use crate::crypto::bech32::encode_address;
+use crate::storage::bloom::TOPIC_LENGTH;
+
+/// Argument types of contract methods. `u64` is 8 big-endian bytes and `bool` one byte;
+/// the others are a 4-byte big-endian length followed by their bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AbiType {
+    U64,
+    Bool,
+    Address,
+    Bytes,
+    String,
+}
+
+impl AbiType {
+    fn name(self) -> &'static str {
+        match self {
+            Self::U64 => "u64",
+            Self::Bool => "bool",
+            Self::Address => "address",
+            Self::Bytes => "bytes",
+            Self::String => "string",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AbiParam {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: AbiType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AbiMethod {
+    pub name: String,
+    #[serde(default)]
+    pub inputs: Vec<AbiParam>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AbiValue {
+    U64(u64),
+    Bool(bool),
+    Address(Vec<u8>),
+    Bytes(Vec<u8>),
+    String(String),
+}
+
+impl AbiValue {
+    fn kind(&self) -> AbiType {
+        match self {
+            Self::U64(_) => AbiType::U64,
+            Self::Bool(_) => AbiType::Bool,
+            Self::Address(_) => AbiType::Address,
+            Self::Bytes(_) => AbiType::Bytes,
+            Self::String(_) => AbiType::String,
+        }
+    }
+}
+
+impl std::fmt::Display for AbiValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::U64(value) => write!(f, "{}", value),
+            Self::Bool(value) => write!(f, "{}", value),
+            Self::Address(address) => write!(f, "{}", encode_address(address)),
+            Self::Bytes(bytes) => write!(f, "0x{}", hex::encode(bytes)),
+            Self::String(text) => write!(f, "{:?}", text),
+        }
+    }
+}
+
+impl AbiMethod {
+    /// Canonical signature the selector hashes, such as `transfer(address,u64)`
+    pub fn signature(&self) -> String {
+        let types: Vec<&str> = self.inputs.iter().map(|input| input.kind.name()).collect();
+        format!("{}({})", self.name, types.join(","))
+    }
+
+    /// Leading call data bytes naming the method, also a transaction's log topic
+    pub fn selector(&self) -> [u8; TOPIC_LENGTH] {
+        let hash = Sha3_256::digest(self.signature().as_bytes());
+        let mut selector = [0u8; TOPIC_LENGTH];
+        selector.copy_from_slice(&hash[..TOPIC_LENGTH]);
+        selector
+    }
+
+    pub fn encode(&self, arguments: &[AbiValue]) -> Result<Vec<u8>, String> {
+        if arguments.len() != self.inputs.len() {
+            return Err(format!("{} takes {} arguments, got {}", self.name, self.inputs.len(), arguments.len()));
+        }
+        let mut data = self.selector().to_vec();
+        for (input, argument) in self.inputs.iter().zip(arguments) {
+            if argument.kind() != input.kind {
+                return Err(format!("Argument {} of {} must be {}", input.name, self.name, input.kind.name()));
+            }
+            match argument {
+                AbiValue::U64(value) => data.extend_from_slice(&value.to_be_bytes()),
+                AbiValue::Bool(value) => data.push(*value as u8),
+                AbiValue::Address(bytes) | AbiValue::Bytes(bytes) => push_dynamic(&mut data, bytes),
+                AbiValue::String(text) => push_dynamic(&mut data, text.as_bytes()),
+            }
+        }
+        Ok(data)
+    }
+
+    /// Arguments of `data`, which must start with this method's selector and hold nothing
+    /// past its last argument
+    pub fn decode(&self, data: &[u8]) -> Result<Vec<(String, AbiValue)>, String> {
+        let mut rest = data
+            .strip_prefix(&self.selector()[..])
+            .ok_or_else(|| format!("Call data is not a call to {}", self.name))?;
+        let mut arguments = Vec::with_capacity(self.inputs.len());
+        for input in &self.inputs {
+            let truncated = || format!("Call data ends inside argument {}", input.name);
+            let value = match input.kind {
+                AbiType::U64 => {
+                    let (bytes, tail) = rest.split_first_chunk::<8>().ok_or_else(truncated)?;
+                    rest = tail;
+                    AbiValue::U64(u64::from_be_bytes(*bytes))
+                }
+                AbiType::Bool => {
+                    let (flag, tail) = rest.split_first().ok_or_else(truncated)?;
+                    rest = tail;
+                    match flag {
+                        0 => AbiValue::Bool(false),
+                        1 => AbiValue::Bool(true),
+                        _ => return Err(format!("Argument {} is not a bool", input.name)),
+                    }
+                }
+                AbiType::Address | AbiType::Bytes | AbiType::String => {
+                    let (length, tail) = rest.split_first_chunk::<4>().ok_or_else(truncated)?;
+                    let length = u32::from_be_bytes(*length) as usize;
+                    let bytes = tail.get(..length).ok_or_else(truncated)?.to_vec();
+                    rest = &tail[length..];
+                    match input.kind {
+                        AbiType::Address => AbiValue::Address(bytes),
+                        AbiType::Bytes => AbiValue::Bytes(bytes),
+                        _ => AbiValue::String(String::from_utf8(bytes).map_err(|_| format!("Argument {} is not UTF-8", input.name))?),
+                    }
+                }
+            };
+            arguments.push((input.name.clone(), value));
+        }
+        if !rest.is_empty() {
+            return Err(format!("{} trailing bytes after the arguments of {}", rest.len(), self.name));
+        }
+        Ok(arguments)
+    }
+}
+
+fn push_dynamic(data: &mut Vec<u8>, bytes: &[u8]) {
+    data.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    data.extend_from_slice(bytes);
+}
+
+/// Interface of a contract, as JSON: `{"methods": [{"name": ..., "inputs": [{"name": ..., "type": "u64"}]}]}`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Abi {
+    pub methods: Vec<AbiMethod>,
+}
+
+impl Abi {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Invalid ABI: {}", e))
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| format!("Cannot read ABI {}: {}", path, e))?;
+        Self::from_json(&json)
+    }
+
+    pub fn method(&self, name: &str) -> Option<&AbiMethod> {
+        self.methods.iter().find(|method| method.name == name)
+    }
+
+    /// Method whose selector `data` starts with
+    pub fn method_for(&self, data: &[u8]) -> Option<&AbiMethod> {
+        let selector = data.get(..TOPIC_LENGTH)?;
+        self.methods.iter().find(|method| method.selector() == selector)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_abi_call_data() {
+        let abi = Abi::from_json(
+            r#"{"methods": [
+                {"name": "transfer", "inputs": [{"name": "to", "type": "address"}, {"name": "amount", "type": "u64"}]},
+                {"name": "pause"}
+            ]}"#,
+        )
+        .unwrap();
+        let transfer = abi.method("transfer").unwrap();
+        assert_eq!(transfer.signature(), "transfer(address,u64)");
+        assert_ne!(transfer.selector(), abi.method("pause").unwrap().selector());
+
+        let arguments = [AbiValue::Address(vec![0xaa; 20]), AbiValue::U64(500)];
+        let data = transfer.encode(&arguments).unwrap();
+        assert_eq!(data.len(), TOPIC_LENGTH + 4 + 20 + 8);
+        assert_eq!(abi.method_for(&data), Some(transfer));
+        let decoded = transfer.decode(&data).unwrap();
+        assert_eq!(decoded, vec![("to".to_string(), arguments[0].clone()), ("amount".to_string(), arguments[1].clone())]);
+        assert_eq!(decoded[0].1.to_string(), encode_address(&[0xaa; 20]));
+
+        assert!(transfer.encode(&[AbiValue::U64(1), AbiValue::U64(2)]).is_err());
+        assert!(transfer.decode(&data[..data.len() - 1]).is_err());
+        assert!(transfer.decode(&[data.clone(), vec![0]].concat()).is_err());
+        assert!(abi.method_for(&[0; TOPIC_LENGTH]).is_none());
+
+        println!("   ABI call data working!");
+    }
+}
@@ -0,0 +1,95 @@
+use std::path::{Path, PathBuf};
+
+use crate::crypto::QuantumKeyPair;
+
+/// File holding a node's identity key within a keystore
+pub const NODE_KEY_FILE: &str = "node.json";
+
+/// File holding a validator's consensus key within a keystore
+pub const VALIDATOR_KEY_FILE: &str = "validator.json";
+
+/// Directory keeping a node's keys apart: the identity it is known by on the network,
+/// created on first use, and the validator key it signs consensus messages with, which
+/// is only ever imported since it must be the one registered in the validator set
+#[derive(Debug, Clone)]
+pub struct Keystore {
+    dir: PathBuf,
+}
+
+impl Keystore {
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, String> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Could not create keystore {}: {}", dir.display(), e))?;
+        Ok(Self { dir })
+    }
+
+    /// The node's identity key, generated and saved the first time it is asked for
+    pub fn node_key(&self) -> Result<QuantumKeyPair, String> {
+        let path = self.dir.join(NODE_KEY_FILE);
+        if path.exists() {
+            return load(&path);
+        }
+        let key = QuantumKeyPair::generate();
+        save(&path, &key)?;
+        Ok(key)
+    }
+
+    /// The validator key, if one was imported
+    pub fn validator_key(&self) -> Result<Option<QuantumKeyPair>, String> {
+        let path = self.dir.join(VALIDATOR_KEY_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        load(&path).map(Some)
+    }
+
+    /// Stores `key` as the validator key, refusing to overwrite the node's identity with it
+    pub fn import_validator_key(&self, key: &QuantumKeyPair) -> Result<(), String> {
+        if self.dir.join(NODE_KEY_FILE).exists() && self.node_key()?.public_key() == key.public_key() {
+            return Err("The validator key must differ from the node key".to_string());
+        }
+        save(&self.dir.join(VALIDATOR_KEY_FILE), key)
+    }
+}
+
+pub fn load(path: &Path) -> Result<QuantumKeyPair, String> {
+    QuantumKeyPair::load(path.to_str().ok_or("Key path is not valid UTF-8")?)
+}
+
+/// Writes `key` as JSON, readable by its owner only
+pub fn save(path: &Path, key: &QuantumKeyPair) -> Result<(), String> {
+    let json = serde_json::to_string(key).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("Could not write key file {}: {}", path.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keystore_separates_keys() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_keystore");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let keystore = Keystore::open(&temp_dir).unwrap();
+        assert!(keystore.validator_key().unwrap().is_none());
+
+        // The identity is created once and kept across restarts
+        let node_key = keystore.node_key().unwrap();
+        assert_eq!(Keystore::open(&temp_dir).unwrap().node_key().unwrap().public_key(), node_key.public_key());
+
+        assert!(keystore.import_validator_key(&node_key).is_err());
+        let validator_key = QuantumKeyPair::generate();
+        keystore.import_validator_key(&validator_key).unwrap();
+        assert_eq!(keystore.validator_key().unwrap().unwrap().public_key(), validator_key.public_key());
+        assert_eq!(keystore.node_key().unwrap().public_key(), node_key.public_key());
+
+        println!("   Keystore working!");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -0,0 +1,366 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::config::WatchtowerConfig;
+use crate::consensus::fork_choice::{FINALITY_DEPTH, GENESIS_PARENT};
+use crate::crypto::verification::Subsystem;
+use crate::log;
+use crate::network::light::LightClient;
+use crate::network::NetworkService;
+use crate::storage::availability;
+use crate::storage::blocks::{Block, BlockHeader};
+use crate::storage::database::BlockchainDB;
+use crate::storage::state::StateManager;
+
+/// A block that failed re-verification, or a peer whose chain disagrees with ours
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Divergence {
+    pub height: u64,
+    #[serde(with = "hex::serde")]
+    pub block_hash: [u8; 32],
+    /// Peer that disagrees; `None` when our own chain failed re-verification
+    pub peer: Option<SocketAddr>,
+    pub reason: String,
+}
+
+/// What the watchtower has checked so far and what it found
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WatchtowerStatus {
+    /// Highest block re-executed to the state root in its header
+    pub verified_height: Option<u64>,
+    /// Peers whose header at the compared height was fetched in the last check
+    pub peers_compared: usize,
+    pub divergences: Vec<Divergence>,
+    /// Valid, executable transactions left out of `censorship_blocks` blocks with room for them
+    #[serde(with = "hex_hashes")]
+    pub censored: Vec<[u8; 32]>,
+}
+
+mod hex_hashes {
+    use serde::Serializer;
+
+    pub fn serialize<S: Serializer>(hashes: &[[u8; 32]], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(hashes.iter().map(hex::encode))
+    }
+}
+
+struct Watch {
+    state: StateManager,
+    next_height: u64,
+    /// Hashes of the recently verified blocks, to notice when a reorg replaces them
+    recent: VecDeque<(u64, [u8; 32])>,
+    failure: Option<Divergence>,
+    peer_divergences: Vec<Divergence>,
+    peers_compared: usize,
+    /// Pending executable transactions and the blocks with room for them sealed since
+    waiting: HashMap<[u8; 32], u64>,
+}
+
+/// Follows a node's chain without producing blocks or serving anything, re-executing
+/// every canonical block on its own copy of the state, comparing state roots with the
+/// node's peers at the finalized height, and watching its mempool for transactions
+/// blocks keep leaving out. Findings are read through `status` and raised by an
+/// `AlertService` given the watchtower
+pub struct Watchtower {
+    network: Arc<NetworkService>,
+    censorship_blocks: u64,
+    watch: Mutex<Watch>,
+}
+
+impl Watchtower {
+    pub fn new(network: Arc<NetworkService>, config: &WatchtowerConfig) -> Result<Self, String> {
+        let (state, next_height) = StateManager::base(network.node().db())?;
+        let watch = Watch {
+            state,
+            next_height,
+            recent: VecDeque::new(),
+            failure: None,
+            peer_divergences: Vec::new(),
+            peers_compared: 0,
+            waiting: HashMap::new(),
+        };
+        Ok(Self { network, censorship_blocks: config.censorship_blocks, watch: Mutex::new(watch) })
+    }
+
+    pub fn status(&self) -> WatchtowerStatus {
+        let watch = self.watch.lock().unwrap();
+        let mut censored: Vec<[u8; 32]> = watch
+            .waiting
+            .iter()
+            .filter(|(_, waited)| **waited >= self.censorship_blocks)
+            .map(|(hash, _)| *hash)
+            .collect();
+        censored.sort();
+        WatchtowerStatus {
+            verified_height: watch.next_height.checked_sub(1).filter(|_| !watch.recent.is_empty()),
+            peers_compared: watch.peers_compared,
+            divergences: watch.failure.iter().chain(&watch.peer_divergences).cloned().collect(),
+            censored,
+        }
+    }
+
+    /// Verifies the blocks added since the last check, compares with peers and updates
+    /// the censorship watch
+    pub async fn check(self: &Arc<Self>) -> Result<WatchtowerStatus, String> {
+        let watchtower = self.clone();
+        let roomy = crate::node::blocking(move || watchtower.verify_blocks()).await.flatten()?;
+        self.track_pending(roomy);
+        self.compare_peers().await;
+        Ok(self.status())
+    }
+
+    pub async fn run(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        let mut reported = WatchtowerStatus::default();
+        loop {
+            ticker.tick().await;
+            match self.check().await {
+                Ok(status) => {
+                    for divergence in status.divergences.iter().filter(|found| !reported.divergences.contains(found)) {
+                        log!(Warn, "Watchtower: divergence at height {}: {}", divergence.height, divergence.reason);
+                    }
+                    if status.censored.len() > reported.censored.len() {
+                        log!(Warn, "Watchtower: {} transactions look censored", status.censored.len());
+                    }
+                    reported = status;
+                }
+                Err(e) => log!(Warn, "Watchtower check failed: {}", e),
+            }
+        }
+    }
+
+    /// Re-executes the canonical blocks not verified yet, stopping at the first that
+    /// fails. Returns how many of them had room for more transactions
+    fn verify_blocks(&self) -> Result<u64, String> {
+        let node = self.network.node();
+        let db = node.db();
+        if db.block_count()? == 0 {
+            return Ok(0);
+        }
+        let latest = db.get_latest_height()?;
+        let mut watch = self.watch.lock().unwrap();
+
+        // A reorg, or a failed block being replaced, restarts from the first block that changed
+        let mut restart = None;
+        for (height, hash) in &watch.recent {
+            if db.get_block(*height)?.map(|block| block.hash()) != Some(*hash) {
+                restart = Some(*height);
+                break;
+            }
+        }
+        if let Some(failure) = &watch.failure {
+            if db.get_block(failure.height)?.map(|block| block.hash()) == Some(failure.block_hash) {
+                return Ok(0);
+            }
+            restart = restart.or(Some(failure.height));
+        }
+        if let Some(height) = restart {
+            watch.state = StateManager::replay_to(db, height.checked_sub(1))?;
+            watch.next_height = height;
+            watch.recent.retain(|(verified, _)| *verified < height);
+            watch.failure = None;
+        }
+
+        let mut roomy = 0;
+        for height in watch.next_height..=latest {
+            let block = db.get_block(height)?.ok_or_else(|| format!("Block {} missing from storage", height))?;
+            let parent = match watch.recent.back() {
+                Some((_, hash)) => *hash,
+                None => Self::parent_hash(db, height)?,
+            };
+            if let Err(reason) = Self::verify_block(&block, parent, &mut watch.state) {
+                log!(Warn, "Watchtower: block {} failed re-verification: {}", height, reason);
+                watch.failure = Some(Divergence { height, block_hash: block.hash(), peer: None, reason });
+                break;
+            }
+
+            let params = node.params_at(height);
+            if block.transactions.len() < params.max_block_transactions
+                && availability::body_size(&block.transactions) < params.max_block_bytes
+            {
+                roomy += 1;
+            }
+            watch.recent.push_back((height, block.hash()));
+            if watch.recent.len() as u64 > FINALITY_DEPTH {
+                watch.recent.pop_front();
+            }
+            watch.next_height = height + 1;
+        }
+        Ok(roomy)
+    }
+
+    fn parent_hash(db: &BlockchainDB, height: u64) -> Result<[u8; 32], String> {
+        match height.checked_sub(1) {
+            Some(parent) => Ok(db.get_block(parent)?.ok_or_else(|| format!("Block {} missing from storage", parent))?.hash()),
+            None => Ok(GENESIS_PARENT),
+        }
+    }
+
+    /// Checks everything a block commits to and executes it on `state`
+    fn verify_block(block: &Block, parent: [u8; 32], state: &mut StateManager) -> Result<(), String> {
+        let header = &block.header;
+        if header.previous_hash != parent {
+            return Err(format!("Parent hash mismatch: expected {}, found {}", hex::encode(parent), hex::encode(header.previous_hash)));
+        }
+        if !block.has_valid_merkle_root() {
+            return Err("Merkle root does not match transactions".to_string());
+        }
+        if !block.has_valid_bloom() {
+            return Err("Bloom filter does not match transactions".to_string());
+        }
+        if !block.has_valid_availability_root() {
+            return Err("Availability root does not match the erasure-coded body".to_string());
+        }
+        block.check_path_proposal()?;
+        for (index, tx) in block.transactions.iter().enumerate() {
+            tx.check_as(Subsystem::BlockValidation).map_err(|e| format!("Transaction {}: {}", index, e))?;
+            if tx.is_expired_at(header.height) {
+                return Err(format!("Transaction {}: expired before height {}", index, header.height));
+            }
+        }
+        state.apply_block(block)?;
+        let state_root = state.state_root();
+        if state_root != header.state_root {
+            return Err(format!(
+                "State root mismatch: computed {}, header has {}",
+                hex::encode(state_root),
+                hex::encode(header.state_root)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Charges `roomy` blocks to every transaction already waiting, then starts watching
+    /// pending transactions that could go into the next block
+    fn track_pending(&self, roomy: u64) {
+        let node = self.network.node();
+        let executable: HashSet<[u8; 32]> = node
+            .pending_summaries(None, usize::MAX)
+            .into_iter()
+            .filter(|tx| {
+                node.account(&tx.from).is_some_and(|account| {
+                    account.nonce == tx.nonce && account.balance >= tx.amount.saturating_add(tx.fee)
+                })
+            })
+            .map(|tx| tx.hash)
+            .collect();
+        let mut watch = self.watch.lock().unwrap();
+        watch.waiting.retain(|hash, _| executable.contains(hash));
+        for waited in watch.waiting.values_mut() {
+            *waited += roomy;
+        }
+        for hash in executable {
+            watch.waiting.entry(hash).or_insert(0);
+        }
+    }
+
+    /// Fetches each peer's header at our finalized height and records those naming a
+    /// different block or state root. Peers not serving light clients or not there yet
+    /// are skipped
+    async fn compare_peers(&self) {
+        let Some(ours) = self.comparable_header() else {
+            return;
+        };
+        let mut divergences = Vec::new();
+        let mut compared = 0;
+        for peer in self.network.peers() {
+            let address = peer.dial_address();
+            let header = match LightClient::connect(address, self.network.network_id()).await {
+                Ok(mut client) => client.headers(ours.height, 1).await.map(|headers| headers.into_iter().next()),
+                Err(e) => Err(e),
+            };
+            let theirs = match header {
+                Ok(Some(header)) => header,
+                Ok(None) => continue,
+                Err(e) => {
+                    log!(Debug, "Watchtower could not compare with {}: {}", address, e);
+                    continue;
+                }
+            };
+            compared += 1;
+            let reason = if theirs.state_root != ours.state_root {
+                format!("Peer has state root {}, ours is {}", hex::encode(theirs.state_root), hex::encode(ours.state_root))
+            } else if theirs.hash() != ours.hash() {
+                format!("Peer finalized block {} instead", hex::encode(theirs.hash()))
+            } else {
+                continue;
+            };
+            divergences.push(Divergence { height: ours.height, block_hash: ours.hash(), peer: Some(address), reason });
+        }
+        let mut watch = self.watch.lock().unwrap();
+        watch.peer_divergences = divergences;
+        watch.peers_compared = compared;
+    }
+
+    /// Header of the highest block both finalized and re-verified
+    fn comparable_header(&self) -> Option<BlockHeader> {
+        let node = self.network.node();
+        let verified = self.status().verified_height?;
+        let height = verified.min(node.fork_choice_head().finalized_height);
+        Some(node.db().get_block(height).ok()??.header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{QuantumKeyPair, QuantumSignature};
+    use crate::node::Node;
+    use crate::storage::blocks::Transaction;
+
+    #[tokio::test]
+    async fn test_watchtower() {
+        let temp_dir = std::env::temp_dir().join("triunity_test_watchtower");
+        let _ = std::fs::remove_dir_all(&temp_dir);
+        let keypair = QuantumKeyPair::generate();
+        let open = |name: &str| {
+            let db = BlockchainDB::new(temp_dir.join(name).to_str().unwrap()).unwrap();
+            db.store_genesis_allocations(&[(keypair.public_key().to_vec(), 1_000)]).unwrap();
+            Node::open(db).unwrap()
+        };
+        let honest = open("honest");
+        let forked = open("forked");
+        let watched = open("watched");
+        for _ in 0..FINALITY_DEPTH + 2 {
+            watched.import_block(&honest.produce_block().unwrap()).unwrap();
+            forked.produce_block().unwrap();
+        }
+        let honest = NetworkService::new(Arc::new(honest));
+        let forked = NetworkService::new(Arc::new(forked));
+        let network = NetworkService::new(Arc::new(watched));
+        let config = WatchtowerConfig { interval_secs: 1, censorship_blocks: 3 };
+        let watchtower = Arc::new(Watchtower::new(network.clone(), &config).unwrap());
+
+        // Every block re-executes to its state root; the peer on another chain diverges
+        network.connect(honest.listen(SocketAddr::from(([127, 0, 0, 1], 0))).await.unwrap()).await.unwrap();
+        let forked_address = forked.listen(SocketAddr::from(([127, 0, 0, 1], 0))).await.unwrap();
+        network.connect(forked_address).await.unwrap();
+        let status = watchtower.check().await.unwrap();
+        assert_eq!(status.verified_height, Some(FINALITY_DEPTH + 1));
+        assert_eq!(status.peers_compared, 2);
+        assert_eq!(status.divergences.len(), 1);
+        assert_eq!(status.divergences[0].peer, Some(forked_address));
+
+        // A valid transaction the producer keeps leaving out of blocks with room is flagged
+        let mut tx = Transaction::new(keypair.public_key().to_vec(), vec![0xdd; 32], 10, 1, 0, vec![], QuantumSignature::new(vec![]));
+        tx.signature = keypair.sign(&tx.get_signing_data()).unwrap();
+        let hash = network.node().submit_transaction(tx.clone()).unwrap();
+        assert!(watchtower.check().await.unwrap().censored.is_empty());
+        for _ in 0..3 {
+            network.node().import_block(&honest.node().produce_block().unwrap()).unwrap();
+        }
+        assert_eq!(watchtower.check().await.unwrap().censored, vec![hash]);
+        honest.node().submit_transaction(tx).unwrap();
+        network.node().import_block(&honest.node().produce_block().unwrap()).unwrap();
+        let status = watchtower.check().await.unwrap();
+        assert!(status.censored.is_empty());
+        assert_eq!(status.verified_height, Some(FINALITY_DEPTH + 5));
+
+        println!("   Watchtower working!");
+        drop((honest, forked, network, watchtower));
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -1,8 +1,41 @@
+pub mod assets;
+pub mod security;
+pub mod streams;
+
 use std::sync::Arc;
+use futures::future::{BoxFuture, FutureExt};
+use futures::{SinkExt, StreamExt};
+use warp::filters::BoxedFilter;
 use warp::Filter;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use crate::api::export::ExportService;
+use crate::api::firehose;
+use crate::api::rpc::RpcServer;
+use crate::config::{ListenConfig, StreamConfig, WebConfig};
+use crate::consensus::metrics::SecurityEvent;
+use crate::consensus::pin::PathPin;
 use crate::consensus::ConsensusEngine;
-use crate::storage::TriUnityStorage;
+use crate::crypto::verification::{self, VerificationCounts};
+use crate::events::EventBus;
+use crate::network::NetworkService;
+use crate::node::Node;
+use crate::supervisor::Supervisor;
+use crate::loadgen::{LoadConfig, LoadGenerator};
+use crate::storage::database::BlockchainDB;
+use crate::storage::envelope::TxEnvelope;
+use crate::storage::ChainStorage;
+use crate::wallet::abi::Abi;
+use crate::wallet::TransactionIntent;
+use self::security::CsrfGuard;
+use self::streams::{ClientQueue, DeliveryStats, QueueEnd, StreamStats};
+
+/// Security events listed by `/api/activity`
+const ACTIVITY_LIMIT: usize = 50;
+
+/// WebSocket close code sent to a stream client cut off for lagging
+const POLICY_VIOLATION: u16 = 1008;
+
+const LAGGING_REASON: &str = "Client fell too far behind the stream";
 
 #[derive(Debug, Clone, Serialize)]
 pub struct LiveMetrics {
@@ -12,37 +45,132 @@ pub struct LiveMetrics {
     pub validator_count: usize,
     pub ai_confidence: f64,
     pub ai_mode: String,
+    /// Set while an operator has pinned `ai_mode`
+    pub pinned_path: Option<PathPin>,
     pub ai_decisions_per_min: u64,
     pub ai_accuracy: f64,
+    pub signature_verifications: VerificationCounts,
     pub timestamp: u64,
 }
 
+/// Body of `POST /api/tx/preview`: a hex `TxEnvelope`, typically still unsigned, and the
+/// ABI of the contract it calls, if any
+#[derive(Debug, Clone, Deserialize)]
+pub struct PreviewRequest {
+    pub envelope: String,
+    #[serde(default)]
+    pub abi: Option<Abi>,
+}
+
 pub struct DashboardServer {
     consensus_engine: Arc<ConsensusEngine>,
-    _storage: Arc<TriUnityStorage>,
+    storage: Arc<dyn ChainStorage>,
+    rpc: Option<Arc<RpcServer>>,
+    export: Option<Arc<ExportService>>,
+    firehose: Option<BlockchainDB>,
+    events: Option<EventBus>,
+    supervisor: Option<Supervisor>,
+    network: Option<Arc<NetworkService>>,
+    node: Option<Arc<Node>>,
+    graphql: Option<BoxedFilter<(Box<dyn warp::Reply>,)>>,
+    mempool_stream: Arc<StreamStats>,
+    event_stream: Arc<StreamStats>,
+    csrf: Arc<CsrfGuard>,
 }
 
 impl DashboardServer {
-    pub fn new(consensus_engine: Arc<ConsensusEngine>, storage: Arc<TriUnityStorage>) -> Self {
+    /// Blocks are served from `storage` at `/api/blocks/latest` and `/api/blocks/<height>`
+    pub fn new(consensus_engine: Arc<ConsensusEngine>, storage: Arc<dyn ChainStorage>) -> Self {
         Self {
             consensus_engine,
-            _storage: storage,
+            storage,
+            rpc: None,
+            export: None,
+            firehose: None,
+            events: None,
+            supervisor: None,
+            network: None,
+            node: None,
+            graphql: None,
+            mempool_stream: StreamStats::new("mempool"),
+            event_stream: StreamStats::new("events"),
+            csrf: Arc::new(CsrfGuard::default()),
         }
     }
 
+    /// How `/ws/mempool` and `/api/events` are reaching their clients, also served at
+    /// `/api/streams` and `/metrics`
+    pub fn delivery(&self) -> DeliveryStats {
+        delivery(&self.streams())
+    }
+
+    fn streams(&self) -> Vec<Arc<StreamStats>> {
+        vec![self.mempool_stream.clone(), self.event_stream.clone()]
+    }
+
+    pub fn with_rpc(mut self, rpc: Arc<RpcServer>) -> Self {
+        self.rpc = Some(rpc);
+        self
+    }
+
+    pub fn with_export(mut self, export: Arc<ExportService>) -> Self {
+        self.export = Some(export);
+        self
+    }
+
+    /// Streams decoded blocks from `db` to indexers as NDJSON at `/api/firehose`
+    pub fn with_firehose(mut self, db: BlockchainDB) -> Self {
+        self.firehose = Some(db);
+        self
+    }
+
+    /// Streams node events to the browser as Server-Sent Events at `/api/events`
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Reports the supervised tasks' health at `/health`
+    pub fn with_supervisor(mut self, supervisor: Supervisor) -> Self {
+        self.supervisor = Some(supervisor);
+        self
+    }
+
+    /// Exposes peer traffic counters in Prometheus format at `/metrics`, connected peers
+    /// at `/api/peers` and the peer graph at `/api/topology`
+    pub fn with_network(mut self, network: Arc<NetworkService>) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    /// Lists the validator set at `/api/validators` and shows the mempool at `/api/mempool`,
+    /// streaming its changes over `/ws/mempool`
+    pub fn with_node(mut self, node: Arc<Node>) -> Self {
+        self.node = Some(node);
+        self
+    }
+
+    /// Answers GraphQL queries at `/graphql`
+    #[cfg(feature = "graphql")]
+    pub fn with_graphql(mut self, graphql: Arc<crate::api::graphql::GraphqlService>) -> Self {
+        self.graphql = Some(boxed(graphql.routes()));
+        self
+    }
+
     pub async fn start(&self, port: u16) -> Result<(), String> {
-        println!("Starting TriUnity Dashboard Server on port {}", port);
-        let dashboard = warp::path::end()
-            .map(|| {
-                warp::reply::html(APPLE_DASHBOARD_HTML)
-            });
+        self.serve(&WebConfig::local(port)).await
+    }
+
+    pub async fn serve(&self, config: &WebConfig) -> Result<(), String> {
+        println!("Starting TriUnity Dashboard Server");
+        let dashboard = assets::routes();
 
         let consensus_clone = self.consensus_engine.clone();
         let metrics_api = warp::path("api")
             .and(warp::path("metrics"))
             .and(warp::path::end())
             .map(move || {
-                let stats = consensus_clone.get_performance_stats();
+                let stats = consensus_clone.stats();
                 let metrics = LiveMetrics {
                     tps: stats.transactions_per_second,
                     block_time_ms: stats.average_block_time_ms,
@@ -50,947 +178,406 @@ impl DashboardServer {
                     validator_count: stats.active_validators,
                     ai_confidence: stats.ai_confidence_percentage,
                     ai_mode: format!("{:?}", stats.current_consensus_path),
+                    pinned_path: stats.pinned_path.clone(),
                     ai_decisions_per_min: stats.ai_decisions_per_minute,
                     ai_accuracy: stats.ai_accuracy_percentage,
+                    signature_verifications: verification::counts(),
                     timestamp: chrono::Utc::now().timestamp() as u64,
                 };
                 warp::reply::json(&metrics)
             });
 
-        let routes = dashboard
-            .or(metrics_api)
-            .with(warp::cors().allow_any_origin());
-
-        println!("Dashboard server running!");
-        println!("Dashboard: http://localhost:{}", port);
-        println!("Metrics API: http://localhost:{}/api/metrics", port);
-
-        warp::serve(routes)
-            .run(([127, 0, 0, 1], port))
-            .await;
-
-        Ok(())
-    }
-}
-
-const APPLE_DASHBOARD_HTML: &str = r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>TriUnity Protocol</title>
-    <style>
-        :root {
-            /* Light theme */
-            --bg-primary: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
-            --bg-secondary: rgba(255, 255, 255, 0.25);
-            --bg-card: rgba(255, 255, 255, 0.18);
-            --text-primary: #ffffff;
-            --text-secondary: rgba(255, 255, 255, 0.8);
-            --text-accent: #00ff88;
-            --border-color: rgba(255, 255, 255, 0.18);
-            --shadow: rgba(31, 38, 135, 0.37);
-            --button-bg: rgba(255, 255, 255, 0.2);
-            --button-hover: rgba(255, 255, 255, 0.3);
-        }
-
-        [data-theme="dark"] {
-            /* Dark theme */
-            --bg-primary: linear-gradient(135deg, #0c0c0c 0%, #1a1a2e 50%, #16213e 100%);
-            --bg-secondary: rgba(0, 0, 0, 0.25);
-            --bg-card: rgba(0, 0, 0, 0.18);
-            --text-primary: #ffffff;
-            --text-secondary: rgba(255, 255, 255, 0.7);
-            --text-accent: #00ff88;
-            --border-color: rgba(255, 255, 255, 0.1);
-            --shadow: rgba(0, 0, 0, 0.5);
-            --button-bg: rgba(255, 255, 255, 0.1);
-            --button-hover: rgba(255, 255, 255, 0.2);
-        }
-
-        * {
-            margin: 0;
-            padding: 0;
-            box-sizing: border-box;
-        }
-
-        body {
-            font-family: -apple-system, BlinkMacSystemFont, 'SF Pro Display', 'Segoe UI', system-ui, sans-serif;
-            background: var(--bg-primary);
-            min-height: 100vh;
-            color: var(--text-primary);
-            transition: all 0.6s cubic-bezier(0.4, 0, 0.2, 1);
-            overflow-x: hidden;
-        }
-
-        .floating-shapes {
-            position: fixed;
-            top: 0;
-            left: 0;
-            width: 100%;
-            height: 100%;
-            pointer-events: none;
-            z-index: 0;
-        }
-
-        .shape {
-            position: absolute;
-            background: rgba(255, 255, 255, 0.1);
-            border-radius: 50%;
-            animation: float 20s infinite ease-in-out;
-        }
-
-        .shape:nth-child(1) {
-            width: 80px;
-            height: 80px;
-            top: 10%;
-            left: 10%;
-            animation-delay: 0s;
-        }
-
-        .shape:nth-child(2) {
-            width: 120px;
-            height: 120px;
-            top: 20%;
-            right: 15%;
-            animation-delay: 2s;
-        }
-
-        .shape:nth-child(3) {
-            width: 60px;
-            height: 60px;
-            bottom: 20%;
-            left: 20%;
-            animation-delay: 4s;
-        }
-
-        @keyframes float {
-            0%, 100% { transform: translateY(0px) rotate(0deg); }
-            25% { transform: translateY(-20px) rotate(90deg); }
-            50% { transform: translateY(-40px) rotate(180deg); }
-            75% { transform: translateY(-20px) rotate(270deg); }
-        }
-
-        .container {
-            max-width: 1400px;
-            margin: 0 auto;
-            padding: 20px;
-            position: relative;
-            z-index: 1;
-        }
-
-        .header {
-            text-align: center;
-            margin-bottom: 40px;
-            padding: 40px 30px;
-            background: var(--bg-card);
-            border-radius: 24px;
-            backdrop-filter: blur(20px) saturate(180%);
-            border: 1px solid var(--border-color);
-            box-shadow: 0 8px 32px var(--shadow);
-            position: relative;
-            overflow: hidden;
-            transition: all 0.6s cubic-bezier(0.4, 0, 0.2, 1);
-        }
-
-        .header::before {
-            content: '';
-            position: absolute;
-            top: 0;
-            left: -100%;
-            width: 100%;
-            height: 100%;
-            background: linear-gradient(90deg, transparent, rgba(255, 255, 255, 0.1), transparent);
-            transition: left 1s;
-        }
-
-        .header:hover::before {
-            left: 100%;
-        }
-
-        .header-top {
-            display: flex;
-            justify-content: space-between;
-            align-items: center;
-            margin-bottom: 30px;
-        }
-
-        .logo {
-            font-size: 3rem;
-            font-weight: 700;
-            background: linear-gradient(45deg, var(--text-accent), #00d4ff);
-            -webkit-background-clip: text;
-            -webkit-text-fill-color: transparent;
-            background-clip: text;
-            animation: logoGlow 3s ease-in-out infinite alternate;
-        }
-
-        @keyframes logoGlow {
-            from { filter: drop-shadow(0 0 10px rgba(0, 255, 136, 0.3)); }
-            to { filter: drop-shadow(0 0 20px rgba(0, 255, 136, 0.6)); }
-        }
-
-        .theme-toggle {
-            position: relative;
-            width: 60px;
-            height: 32px;
-            background: var(--button-bg);
-            border: 1px solid var(--border-color);
-            border-radius: 16px;
-            cursor: pointer;
-            transition: all 0.4s cubic-bezier(0.4, 0, 0.2, 1);
-            backdrop-filter: blur(10px);
-            overflow: hidden;
-        }
-
-        .theme-toggle:hover {
-            transform: scale(1.05);
-            background: var(--button-hover);
-        }
-
-        .theme-toggle::before {
-            content: '';
-            position: absolute;
-            top: 2px;
-            left: 2px;
-            width: 26px;
-            height: 26px;
-            background: linear-gradient(45deg, #ffd700, #ffed4a);
-            border-radius: 50%;
-            transition: all 0.5s cubic-bezier(0.68, -0.55, 0.265, 1.55);
-            box-shadow: 0 2px 8px rgba(255, 215, 0, 0.3);
-        }
-
-        [data-theme="dark"] .theme-toggle::before {
-            transform: translateX(28px);
-            background: linear-gradient(45deg, #4a90e2, #50c8ff);
-            box-shadow: 0 2px 8px rgba(74, 144, 226, 0.3);
-        }
-
-        .theme-toggle::after {
-            content: '☀';
-            position: absolute;
-            top: 50%;
-            right: 8px;
-            transform: translateY(-50%);
-            font-size: 12px;
-            opacity: 1;
-            transition: all 0.3s ease;
-        }
-
-        [data-theme="dark"] .theme-toggle::after {
-            content: '☽';
-            left: 8px;
-            right: auto;
-        }
-
-        .tagline {
-            font-size: 1.3rem;
-            margin-bottom: 20px;
-            color: var(--text-secondary);
-            font-weight: 300;
-            letter-spacing: 0.5px;
-        }
-
-        .status-badge {
-            display: inline-flex;
-            align-items: center;
-            gap: 12px;
-            background: var(--bg-card);
-            backdrop-filter: blur(10px);
-            border: 1px solid var(--border-color);
-            color: var(--text-accent);
-            padding: 12px 24px;
-            border-radius: 50px;
-            font-size: 0.9rem;
-            font-weight: 600;
-            box-shadow: 0 4px 20px var(--shadow);
-            animation: statusPulse 2s infinite ease-in-out;
-        }
-
-        @keyframes statusPulse {
-            0%, 100% { transform: scale(1); }
-            50% { transform: scale(1.02); }
-        }
-
-        .status-dot {
-            width: 10px;
-            height: 10px;
-            background: var(--text-accent);
-            border-radius: 50%;
-            animation: dotPulse 1.5s infinite ease-in-out;
-        }
-
-        @keyframes dotPulse {
-            0%, 100% { opacity: 1; transform: scale(1); }
-            50% { opacity: 0.7; transform: scale(0.9); }
-        }
-
-        .controls {
-            display: flex;
-            gap: 12px;
-            justify-content: center;
-            margin-top: 24px;
-        }
-
-        .btn {
-            background: var(--button-bg);
-            backdrop-filter: blur(20px);
-            border: 1px solid var(--border-color);
-            color: var(--text-primary);
-            padding: 12px 24px;
-            border-radius: 16px;
-            font-size: 0.875rem;
-            font-weight: 600;
-            cursor: pointer;
-            transition: all 0.3s cubic-bezier(0.4, 0, 0.2, 1);
-            position: relative;
-            overflow: hidden;
-        }
-
-        .btn:hover {
-            transform: translateY(-2px);
-            background: var(--button-hover);
-            box-shadow: 0 8px 25px var(--shadow);
-        }
-
-        .btn:active {
-            transform: translateY(0);
-        }
-
-        .btn.primary {
-            background: linear-gradient(45deg, #007aff, #00d4ff);
-            border-color: transparent;
-            color: white;
-        }
-
-        .btn.primary:hover {
-            background: linear-gradient(45deg, #0056cc, #00a8cc);
-        }
-
-        .metrics-grid {
-            display: grid;
-            grid-template-columns: repeat(auto-fit, minmax(320px, 1fr));
-            gap: 24px;
-            margin-bottom: 40px;
-        }
-
-        .metric-card {
-            background: var(--bg-card);
-            backdrop-filter: blur(20px) saturate(180%);
-            border: 1px solid var(--border-color);
-            border-radius: 20px;
-            padding: 32px 24px;
-            text-align: center;
-            box-shadow: 0 8px 32px var(--shadow);
-            transition: all 0.4s cubic-bezier(0.4, 0, 0.2, 1);
-            position: relative;
-            overflow: hidden;
-        }
-
-        .metric-card::before {
-            content: '';
-            position: absolute;
-            top: 0;
-            left: 0;
-            right: 0;
-            height: 4px;
-            background: linear-gradient(90deg, var(--text-accent), #00d4ff);
-            opacity: 0;
-            transition: opacity 0.3s ease;
-        }
-
-        .metric-card:hover {
-            transform: translateY(-8px);
-            box-shadow: 0 20px 40px var(--shadow);
-            border-color: rgba(0, 255, 136, 0.4);
-        }
-
-        .metric-card:hover::before {
-            opacity: 1;
-        }
-
-        .metric-icon {
-            font-size: 2rem;
-            margin-bottom: 16px;
-            filter: drop-shadow(0 4px 8px rgba(0, 0, 0, 0.2));
-            opacity: 0.8;
-            font-weight: 300;
-        }
-
-        .metric-value {
-            font-size: 3rem;
-            font-weight: 200;
-            background: linear-gradient(45deg, var(--text-accent), #00d4ff);
-            -webkit-background-clip: text;
-            -webkit-text-fill-color: transparent;
-            background-clip: text;
-            margin-bottom: 8px;
-            line-height: 1;
-            transition: all 0.3s ease;
-        }
-
-        .metric-label {
-            font-size: 0.875rem;
-            color: var(--text-secondary);
-            text-transform: uppercase;
-            letter-spacing: 1.2px;
-            font-weight: 500;
-        }
-
-        .achievement-section {
-            background: var(--bg-card);
-            backdrop-filter: blur(20px) saturate(180%);
-            border: 1px solid var(--border-color);
-            border-radius: 24px;
-            padding: 40px 32px;
-            text-align: center;
-            box-shadow: 0 8px 32px var(--shadow);
-            position: relative;
-            overflow: hidden;
-            transition: all 0.6s cubic-bezier(0.4, 0, 0.2, 1);
-        }
-
-        .achievement-section::before {
-            content: '';
-            position: absolute;
-            top: -50%;
-            left: -50%;
-            width: 200%;
-            height: 200%;
-            background: conic-gradient(from 0deg, transparent, rgba(0, 255, 136, 0.1), transparent);
-            animation: rotate 8s linear infinite;
-            opacity: 0.5;
-        }
-
-        @keyframes rotate {
-            from { transform: rotate(0deg); }
-            to { transform: rotate(360deg); }
-        }
-
-        .achievement-content {
-            position: relative;
-            z-index: 1;
-        }
-
-        .achievement-title {
-            font-size: 2.2rem;
-            font-weight: 700;
-            margin-bottom: 16px;
-            background: linear-gradient(45deg, #ff0080, #ff6b35, var(--text-accent));
-            -webkit-background-clip: text;
-            -webkit-text-fill-color: transparent;
-            background-clip: text;
-            animation: titleShine 3s ease-in-out infinite;
-        }
-
-        @keyframes titleShine {
-            0%, 100% { filter: brightness(1); }
-            50% { filter: brightness(1.2); }
-        }
-
-        .achievement-subtitle {
-            font-size: 1.1rem;
-            color: var(--text-secondary);
-            line-height: 1.6;
-            max-width: 600px;
-            margin: 0 auto;
-        }
-
-        .trilemma-indicators {
-            display: flex;
-            justify-content: center;
-            gap: 16px;
-            margin-top: 32px;
-        }
+        let supervisor = self.supervisor.clone();
+        let health_api = warp::path("health")
+            .and(warp::path::end())
+            .map(move || {
+                let (healthy, tasks) = match &supervisor {
+                    Some(supervisor) => (supervisor.is_healthy(), supervisor.health()),
+                    None => (true, Vec::new()),
+                };
+                let status = if healthy {
+                    warp::http::StatusCode::OK
+                } else {
+                    warp::http::StatusCode::SERVICE_UNAVAILABLE
+                };
+                warp::reply::with_status(
+                    warp::reply::json(&serde_json::json!({
+                        "status": if healthy { "ok" } else { "degraded" },
+                        "tasks": tasks,
+                    })),
+                    status,
+                )
+            });
 
-        .trilemma-dot {
-            width: 12px;
-            height: 12px;
-            background: var(--text-accent);
-            border-radius: 50%;
-            animation: trilemmaGlow 2s ease-in-out infinite;
-        }
+        let loadtest_engine = self.consensus_engine.clone();
+        let loadtest_api = warp::path("api")
+            .and(warp::path("loadtest"))
+            .and(warp::path::end())
+            .and(warp::post())
+            .and(self.csrf.filter())
+            .and(warp::body::content_length_limit(4096))
+            .and(warp::body::json())
+            .and_then(move |config: LoadConfig| {
+                let consensus = loadtest_engine.clone();
+                async move {
+                    let reply: Box<dyn warp::Reply> = match LoadGenerator::run_scratch(config, consensus).await {
+                        Ok(report) => Box::new(warp::reply::json(&report)),
+                        Err(e) => Box::new(warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({ "error": e })),
+                            warp::http::StatusCode::BAD_REQUEST,
+                        )),
+                    };
+                    Ok::<_, warp::Rejection>(reply)
+                }
+            });
 
-        .trilemma-dot:nth-child(2) { animation-delay: 0.5s; }
-        .trilemma-dot:nth-child(3) { animation-delay: 1s; }
+        let preview_api = warp::path!("api" / "tx" / "preview")
+            .and(warp::post())
+            .and(self.csrf.filter())
+            .and(warp::body::content_length_limit(1024 * 1024))
+            .and(warp::body::json())
+            .map(|request: PreviewRequest| match preview(&request) {
+                Ok(intent) => warp::reply::with_status(warp::reply::json(&intent), warp::http::StatusCode::OK),
+                Err(e) => warp::reply::with_status(
+                    warp::reply::json(&serde_json::json!({ "error": e })),
+                    warp::http::StatusCode::BAD_REQUEST,
+                ),
+            });
 
-        @keyframes trilemmaGlow {
-            0%, 100% { 
-                box-shadow: 0 0 5px var(--text-accent); 
-                transform: scale(1);
+        let activity_api = activity(self.consensus_engine.clone());
+        let blocks_api = blocks(self.storage.clone());
+        let shared_rpc = self.rpc.clone().filter(|_| config.rpc.addresses.is_empty());
+        let rpc_api = optional(shared_rpc.map(|rpc| boxed(rpc.routes())));
+        let export_api = optional(self.export.clone().map(|export| boxed(export.routes())));
+        let firehose_api = optional(self.firehose.clone().map(|db| boxed(firehose::routes(db))));
+        let event_stats = self.event_stream.clone();
+        let events_api = optional(self.events.clone().map(|events| boxed(event_stream(events, config.streams.clone(), event_stats))));
+        let streams_api = stream_delivery(self.streams());
+        let prometheus_api = prometheus(self.network.clone(), self.streams());
+        let peers_api = optional(self.network.clone().map(|network| boxed(peers(network))));
+        let validators_api = optional(self.node.clone().map(|node| boxed(validators(node))));
+        let mempool_stats = self.mempool_stream.clone();
+        let mempool_api = optional(self.node.clone().map(|node| boxed(mempool(node, config.streams.clone(), mempool_stats))));
+        let graphql_api = optional(self.graphql.clone());
+
+        let csrf_api = self.csrf.routes();
+        let routes = boxed(dashboard
+            .or(csrf_api)
+            .or(metrics_api)
+            .or(health_api)
+            .or(loadtest_api)
+            .or(preview_api)
+            .or(activity_api)
+            .or(blocks_api)
+            .or(export_api)
+            .or(firehose_api)
+            .or(events_api)
+            .or(streams_api)
+            .or(prometheus_api)
+            .or(peers_api)
+            .or(validators_api)
+            .or(mempool_api)
+            .or(graphql_api)
+            .or(rpc_api)
+            .recover(security::recover)
+            .with(warp::reply::with::headers(security::security_headers(&config.content_security_policy, config.dashboard.tls.is_some()))));
+        let routes = security::cors(routes, config.allowed_origins.clone());
+
+        let mut servers = listen(routes, &config.dashboard)?;
+        for address in &config.dashboard.addresses {
+            let base = format!("{}://{}", config.dashboard.scheme(), address);
+            println!("Dashboard: {}", base);
+            println!("Metrics API: {}/api/metrics", base);
+            println!("Health: {}/health", base);
+            println!("CSRF Token: {}/api/csrf", base);
+            println!("Load Test API: POST {}/api/loadtest", base);
+            println!("Transaction Preview: POST {}/api/tx/preview", base);
+            println!("Security Activity: {}/api/activity", base);
+            println!("Blocks API: {}/api/blocks/latest", base);
+            if self.export.is_some() {
+                println!("Export API: {}/api/export", base);
             }
-            50% { 
-                box-shadow: 0 0 20px var(--text-accent), 0 0 30px var(--text-accent); 
-                transform: scale(1.2);
+            if self.firehose.is_some() {
+                println!("Firehose: {}/api/firehose", base);
             }
-        }
-
-        .ripple {
-            position: fixed;
-            border-radius: 50%;
-            background: var(--bg-card);
-            transform: scale(0);
-            z-index: 9999;
-            pointer-events: none;
-            transition: transform 0.8s cubic-bezier(0.4, 0, 0.2, 1);
-        }
-
-        .ripple.animate {
-            transform: scale(1);
-        }
-
-        @media (max-width: 768px) {
-            .container {
-                padding: 16px;
+            if self.events.is_some() {
+                println!("Event Stream: {}/api/events", base);
             }
-            
-            .header-top {
-                flex-direction: column;
-                gap: 20px;
+            println!("Stream Delivery: {}/api/streams", base);
+            println!("Prometheus: {}/metrics", base);
+            if self.network.is_some() {
+                println!("Peers API: {}/api/peers", base);
+                println!("Topology API: {}/api/topology", base);
             }
-            
-            .logo {
-                font-size: 2.2rem;
+            if self.node.is_some() {
+                println!("Validators API: {}/api/validators", base);
+                println!("Mempool API: {}/api/mempool", base);
+                println!("Mempool Stream: {}/ws/mempool", base.replacen("http", "ws", 1));
             }
-            
-            .metrics-grid {
-                grid-template-columns: 1fr;
-                gap: 16px;
+            if self.graphql.is_some() {
+                println!("GraphQL: POST {}/graphql", base);
             }
-            
-            .controls {
-                flex-wrap: wrap;
-                gap: 8px;
+            if self.rpc.is_some() && config.rpc.addresses.is_empty() {
+                println!("JSON-RPC: {}/rpc", base);
+                println!("JSON-RPC WebSocket: {}/rpc/ws", base.replacen("http", "ws", 1));
             }
-            
-            .btn {
-                padding: 10px 16px;
-                font-size: 0.8rem;
-            }
-        }
-        .loading {
-            opacity: 0.6;
-            animation: pulse 1.5s ease-in-out infinite;
         }
 
-        @keyframes pulse {
-            0%, 100% { opacity: 0.6; }
-            50% { opacity: 1; }
-        }
-        .success-feedback {
-            position: fixed;
-            top: 20px;
-            right: 20px;
-            background: var(--bg-card);
-            backdrop-filter: blur(20px);
-            border: 1px solid var(--border-color);
-            border-radius: 12px;
-            padding: 16px 20px;
-            color: var(--text-primary);
-            box-shadow: 0 8px 32px var(--shadow);
-            transform: translateX(400px);
-            transition: all 0.4s cubic-bezier(0.4, 0, 0.2, 1);
-            z-index: 10000;
+        if let (Some(rpc), false) = (&self.rpc, config.rpc.addresses.is_empty()) {
+            let rpc_routes = security::cors(boxed(rpc.clone().routes()), config.allowed_origins.clone());
+            servers.extend(listen(rpc_routes, &config.rpc)?);
+            for address in &config.rpc.addresses {
+                println!("JSON-RPC: {}://{}/rpc", config.rpc.scheme(), address);
+                println!("JSON-RPC WebSocket: {}://{}/rpc/ws", config.rpc.scheme().replacen("http", "ws", 1), address);
+            }
         }
 
-        .success-feedback.show {
-            transform: translateX(0);
-        }
-    </style>
-</head>
-<body>
-    <div class="floating-shapes">
-        <div class="shape"></div>
-        <div class="shape"></div>
-        <div class="shape"></div>
-    </div>
-    <div class="container">
-        <div class="header">
-            <div class="header-top">
-                <div class="logo">TriUnity</div>
-                <div class="theme-toggle" onclick="toggleTheme()" title="Toggle Dark Mode"></div>
-            </div>
-            
-            <div class="tagline">The First Blockchain to Defeat the Trilemma</div>
-            
-            <div class="status-badge">
-                <div class="status-dot"></div>
-                TRILEMMA DESTROYED
-            </div>
-
-            <div class="controls">
-                <button class="btn" onclick="exportData()">Export</button>
-                <button class="btn" onclick="showSettings()">Settings</button>
-                <button class="btn primary" onclick="runLoadTest()">Run Test</button>
-            </div>
-        </div>
-        <div class="metrics-grid" id="metrics">
-            <div class="metric-card">
-                <div class="metric-icon">⬢</div>
-                <div class="metric-value" id="tps">Loading...</div>
-                <div class="metric-label">Transactions Per Second</div>
-            </div>
-            
-            <div class="metric-card">
-                <div class="metric-icon">⧗</div>
-                <div class="metric-value" id="block-time">Loading...</div>
-                <div class="metric-label">Block Time (ms)</div>
-            </div>
-            
-            <div class="metric-card">
-                <div class="metric-icon">◯</div>
-                <div class="metric-value" id="health">Loading...</div>
-                <div class="metric-label">Network Health (%)</div>
-            </div>
-            
-            <div class="metric-card">
-                <div class="metric-icon">⬡</div>
-                <div class="metric-value" id="validators">Loading...</div>
-                <div class="metric-label">Active Validators</div>
-            </div>
-        </div>
-        <div class="achievement-section">
-            <div class="achievement-content">
-                <div class="achievement-title">IMPOSSIBLE ACHIEVED</div>
-                <div class="achievement-subtitle">
-                    TriUnity is the first blockchain to simultaneously achieve 
-                    scalability, security, and decentralization - defeating the 
-                    infamous blockchain trilemma through revolutionary AI consensus 
-                    and quantum-resistant cryptography.
-                </div>
-                <div class="trilemma-indicators">
-                    <div class="trilemma-dot" title="Scalability"></div>
-                    <div class="trilemma-dot" title="Security"></div>
-                    <div class="trilemma-dot" title="Decentralization"></div>
-                </div>
-            </div>
-        </div>
-    </div>
-    <script>
-        class TriUnityDashboard {
-            constructor() {
-                this.isDarkMode = localStorage.getItem('darkMode') === 'true';
-                this.isTestRunning = false;
-                this.init();
-            }
+        println!("Dashboard server running!");
+        futures::future::join_all(servers).await;
 
-            init() {
-                this.initTheme();
-                this.updateMetrics();
-                this.startMetricsUpdater();
-                console.log('TriUnity Dashboard initialized');
-            }
+        Ok(())
+    }
+}
 
-            initTheme() {
-                if (this.isDarkMode) {
-                    document.documentElement.setAttribute('data-theme', 'dark');
-                }
-            }
+/// Intent of the transaction a submission form is about to have signed
+fn preview(request: &PreviewRequest) -> Result<TransactionIntent, String> {
+    let bytes = hex::decode(request.envelope.trim_start_matches("0x")).map_err(|e| format!("Invalid envelope hex: {}", e))?;
+    let tx = TxEnvelope::decode(&bytes)?.into();
+    Ok(TransactionIntent::from_transaction(&tx, request.abi.as_ref()))
+}
 
-            toggleTheme() {
-                const toggle = document.querySelector('.theme-toggle');
-                
-                this.createRipple(toggle);
-                this.isDarkMode = !this.isDarkMode;
-                
-                if (this.isDarkMode) {
-                    document.documentElement.setAttribute('data-theme', 'dark');
-                } else {
-                    document.documentElement.setAttribute('data-theme', 'light');
-                }
-                
-                localStorage.setItem('darkMode', this.isDarkMode);
-                this.showNotification(`Switched to ${this.isDarkMode ? 'dark' : 'light'} mode`);
-            }
+/// `GET /api/activity`: the latest security events, newest first
+fn activity(consensus: Arc<ConsensusEngine>) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("api" / "activity")
+        .and(warp::get())
+        .map(move || {
+            let metrics = consensus.metrics();
+            let events: Vec<&SecurityEvent> = metrics.security_events().rev().take(ACTIVITY_LIMIT).collect();
+            warp::reply::json(&events)
+        })
+}
 
-            createRipple(element) {
-                const rect = element.getBoundingClientRect();
-                const size = Math.max(window.innerWidth, window.innerHeight) * 2;
-                
-                const ripple = document.createElement('div');
-                ripple.className = 'ripple';
-                ripple.style.width = size + 'px';
-                ripple.style.height = size + 'px';
-                ripple.style.left = (rect.left + rect.width / 2 - size / 2) + 'px';
-                ripple.style.top = (rect.top + rect.height / 2 - size / 2) + 'px';
-                
-                document.body.appendChild(ripple);
-                setTimeout(() => ripple.classList.add('animate'), 10);
-                setTimeout(() => {
-                    if (ripple.parentNode) {
-                        ripple.parentNode.removeChild(ripple);
+/// `GET /api/events`: one SSE message per node event, named after the event type. A
+/// client that falls behind is sent a final `Lagging` message and the stream ends
+fn event_stream(
+    events: EventBus,
+    config: StreamConfig,
+    stats: Arc<StreamStats>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("api" / "events")
+        .and(warp::get())
+        .map(move || {
+            let queue = ClientQueue::spawn(events.subscribe(), &config, stats.clone());
+            let stream = futures::stream::unfold(Some(queue), |queue| async move {
+                let mut queue = queue?;
+                match queue.recv().await {
+                    Ok(event) => {
+                        let message = warp::sse::Event::default().event(event.kind()).json_data(&event);
+                        Some((message, Some(queue)))
                     }
-                }, 800);
-            }
-
-            async updateMetrics() {
-                try {
-                    // Add loading state
-                    const metrics = document.querySelectorAll('.metric-value');
-                    metrics.forEach(metric => metric.classList.add('loading'));
-
-                    const response = await fetch('/api/metrics');
-                    const data = await response.json();
-                    this.animateValue('tps', data.tps);
-                    this.animateValue('block-time', data.block_time_ms);
-                    this.animateValue('health', data.health_percentage.toFixed(1));
-                    this.animateValue('validators', data.validator_count);
-                    setTimeout(() => {
-                        metrics.forEach(metric => metric.classList.remove('loading'));
-                    }, 500);
-                    
-                } catch (error) {
-                    console.error('Failed to update metrics:', error);
-                    this.showNotification('Failed to update metrics', 'error');
+                    Err(QueueEnd::Lagging) => Some((Ok(warp::sse::Event::default().event("Lagging").data(LAGGING_REASON)), None)),
+                    Err(QueueEnd::Closed) => None,
                 }
-            }
+            });
+            warp::sse::reply(warp::sse::keep_alive().stream(stream))
+        })
+}
 
-            animateValue(elementId, newValue) {
-                const element = document.getElementById(elementId);
-                const currentValue = parseInt(element.textContent.replace(/[^0-9.]/g, '')) || 0;
-                const target = typeof newValue === 'number' ? newValue : parseInt(newValue);
-                
-                if (currentValue === target) return;
-
-                const duration = 1000;
-                const startTime = performance.now();
-                
-                const animate = (currentTime) => {
-                    const elapsed = currentTime - startTime;
-                    const progress = Math.min(elapsed / duration, 1);
-                    const easeProgress = 1 - Math.pow(1 - progress, 3);
-                    const current = Math.floor(currentValue + (target - currentValue) * easeProgress);
-                    
-                    if (elementId === 'tps' || elementId === 'validators') {
-                        element.textContent = current.toLocaleString();
-                    } else {
-                        element.textContent = current;
-                    }
-                    
-                    if (progress < 1) {
-                        requestAnimationFrame(animate);
-                    }
-                };
-                
-                requestAnimationFrame(animate);
-            }
+/// `GET /api/streams`: delivery counters of the dashboard streams
+fn stream_delivery(streams: Vec<Arc<StreamStats>>) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("api" / "streams")
+        .and(warp::get())
+        .map(move || warp::reply::json(&delivery(&streams)))
+}
 
-            startMetricsUpdater() {
-                const savedFrequency = localStorage.getItem('updateFrequency') || '3000';
-                this.metricsInterval = setInterval(() => {
-                    if (!this.isTestRunning) {
-                        this.updateMetrics();
-                    }
-                }, parseInt(savedFrequency));
-            }
+fn delivery(streams: &[Arc<StreamStats>]) -> DeliveryStats {
+    DeliveryStats { streams: streams.iter().map(|stats| stats.delivery()).collect() }
+}
 
-            showNotification(message, type = 'success') {
-                const notification = document.createElement('div');
-                notification.className = 'success-feedback';
-                notification.innerHTML = `
-                    <div style="display: flex; align-items: center; gap: 8px;">
-                        <span>${type === 'success' ? '✓' : '✗'}</span>
-                        <span>${message}</span>
-                    </div>
-                `;
-                
-                document.body.appendChild(notification);
-                setTimeout(() => notification.classList.add('show'), 100);
-                setTimeout(() => {
-                    notification.classList.remove('show');
-                    setTimeout(() => {
-                        if (notification.parentNode) {
-                            notification.parentNode.removeChild(notification);
-                        }
-                    }, 400);
-                }, 3000);
-            }
+/// `GET /api/blocks/latest` and `GET /api/blocks/<height>`: a canonical block, 404 if there is none
+fn blocks(storage: Arc<dyn ChainStorage>) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let latest_storage = storage.clone();
+    let latest = warp::path!("api" / "blocks" / "latest")
+        .and(warp::get())
+        .then(move || {
+            let storage = latest_storage.clone();
+            async move { block_reply(storage.latest_block().await) }
+        });
+    let by_height = warp::path!("api" / "blocks" / u64)
+        .and(warp::get())
+        .then(move |height| {
+            let storage = storage.clone();
+            async move { block_reply(storage.block(height).await) }
+        });
+    latest.or(by_height).unify()
+}
+
+fn block_reply(block: Result<Option<crate::storage::blocks::Block>, String>) -> warp::reply::WithStatus<warp::reply::Json> {
+    match block {
+        Ok(Some(block)) => warp::reply::with_status(warp::reply::json(&block), warp::http::StatusCode::OK),
+        Ok(None) => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": "No such block" })),
+            warp::http::StatusCode::NOT_FOUND,
+        ),
+        Err(e) => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": e })),
+            warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+    }
+}
 
-            async exportData() {
-                try {
-                    this.showNotification('Preparing data export...');
-                    setTimeout(async () => {
-                        const response = await fetch('/api/metrics');
-                        const data = await response.json();
-                        
-                        const exportData = {
-                            timestamp: new Date().toISOString(),
-                            metrics: data,
-                            blockchain: 'TriUnity',
-                            version: '1.0.0'
-                        };
-                        
-                        const blob = new Blob([JSON.stringify(exportData, null, 2)], { type: 'application/json' });
-                        const url = URL.createObjectURL(blob);
-                        const a = document.createElement('a');
-                        a.href = url;
-                        a.download = `TriUnity_Export_${new Date().toISOString().split('T')[0]}.json`;
-                        a.click();
-                        URL.revokeObjectURL(url);
-                        
-                        this.showNotification('Data exported successfully!');
-                    }, 1500);
-                } catch (error) {
-                    this.showNotification('Export failed', 'error');
+/// `GET /api/validators`: the validator set with balance, reputation, uptime and last proposal
+fn validators(node: Arc<Node>) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("api" / "validators")
+        .and(warp::get())
+        .then(move || {
+            let node = node.clone();
+            async move {
+                match crate::node::blocking(move || node.validator_statuses()).await.flatten() {
+                    Ok(statuses) => warp::reply::with_status(warp::reply::json(&statuses), warp::http::StatusCode::OK),
+                    Err(e) => warp::reply::with_status(
+                        warp::reply::json(&serde_json::json!({ "error": e })),
+                        warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                    ),
                 }
             }
+        })
+}
 
-            showSettings() {
-                const modal = document.createElement('div');
-                modal.className = 'settings-modal';
-                modal.style.cssText = `
-                    position: fixed;
-                    top: 0;
-                    left: 0;
-                    right: 0;
-                    bottom: 0;
-                    background: rgba(0, 0, 0, 0.5);
-                    backdrop-filter: blur(10px);
-                    display: flex;
-                    align-items: center;
-                    justify-content: center;
-                    z-index: 10000;
-                    animation: fadeIn 0.3s ease;
-                `;
-                
-                modal.innerHTML = `
-                    <div class="settings-content" style="
-                        background: var(--bg-card);
-                        backdrop-filter: blur(20px);
-                        border: 1px solid var(--border-color);
-                        border-radius: 20px;
-                        padding: 32px;
-                        max-width: 400px;
-                        width: 90%;
-                        box-shadow: 0 20px 60px var(--shadow);
-                        animation: slideUp 0.3s ease;
-                    ">
-                        <h3 style="color: var(--text-primary); margin-bottom: 20px; font-size: 1.5rem;">Settings</h3>
-                        <div style="margin-bottom: 20px;">
-                            <label style="color: var(--text-secondary); font-size: 0.9rem; display: block; margin-bottom: 8px;">Update Frequency</label>
-                            <select id="update-frequency" style="width: 100%; padding: 12px; border-radius: 8px; border: 1px solid var(--border-color); background: var(--bg-card); color: var(--text-primary);">
-                                <option value="2000">Real-time (2s)</option>
-                                <option value="3000" selected>Normal (3s)</option>
-                                <option value="5000">Slow (5s)</option>
-                            </select>
-                        </div>
-                        <div style="margin-bottom: 20px;">
-                            <label style="color: var(--text-secondary); font-size: 0.9rem; display: flex; align-items: center; gap: 8px;">
-                                <input type="checkbox" id="enable-notifications" checked> Enable notifications
-                            </label>
-                        </div>
-                        <div style="display: flex; gap: 12px; justify-content: flex-end;">
-                            <button class="modal-btn cancel-btn" style="background: var(--button-bg); color: var(--text-primary); border: 1px solid var(--border-color); padding: 10px 20px; border-radius: 8px; cursor: pointer;">Cancel</button>
-                            <button class="modal-btn save-btn" style="background: linear-gradient(45deg, #007aff, #00d4ff); color: white; border: none; padding: 10px 20px; border-radius: 8px; cursor: pointer;">Save</button>
-                        </div>
-                    </div>
-                `;
-                
-                document.body.appendChild(modal);
-                
-                const savedFrequency = localStorage.getItem('updateFrequency') || '3000';
-                const savedNotifications = localStorage.getItem('notificationsEnabled') !== 'false';
-                
-                document.getElementById('update-frequency').value = savedFrequency;
-                document.getElementById('enable-notifications').checked = savedNotifications;
-                
-                const cancelBtn = modal.querySelector('.cancel-btn');
-                const saveBtn = modal.querySelector('.save-btn');
-                
-                cancelBtn.addEventListener('click', () => {
-                    modal.remove();
-                });
-                
-                saveBtn.addEventListener('click', () => {
-                    this.saveSettings();
-                    modal.remove();
-                });
-                modal.addEventListener('click', (e) => {
-                    if (e.target === modal) {
-                        modal.remove();
-                    }
-                });
-            }
+/// Query of `GET /api/peers`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PeersQuery {
+    /// Leaves peer addresses out, for dashboards shown to people outside the operator
+    #[serde(default)]
+    pub redact: bool,
+}
 
-            saveSettings() {
-                const frequency = document.getElementById('update-frequency').value;
-                const notifications = document.getElementById('enable-notifications').checked;
-                
-                localStorage.setItem('updateFrequency', frequency);
-                localStorage.setItem('notificationsEnabled', notifications);
-                if (this.metricsInterval) {
-                    clearInterval(this.metricsInterval);
-                }
-                this.metricsInterval = setInterval(() => {
-                    if (!this.isTestRunning) {
-                        this.updateMetrics();
-                    }
-                }, parseInt(frequency));
-                
-                this.showNotification('Settings saved successfully!');
+/// `GET /api/peers`: connected peers with direction, protocol, latency and traffic;
+/// `GET /api/topology`: the peer graph as nodes and edges
+fn peers(network: Arc<NetworkService>) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let peers_network = network.clone();
+    let peers = warp::path!("api" / "peers")
+        .and(warp::get())
+        .and(warp::query::<PeersQuery>())
+        .map(move |query: PeersQuery| {
+            let mut views = peers_network.peer_views();
+            if query.redact {
+                views = views.into_iter().map(|view| view.redacted()).collect();
             }
+            warp::reply::json(&views)
+        });
+    let topology = warp::path!("api" / "topology")
+        .and(warp::get())
+        .map(move || warp::reply::json(&network.topology()));
+    peers.or(topology)
+}
 
-            async runLoadTest() {
-                if (this.isTestRunning) {
-                    this.showNotification('Test already running!', 'error');
-                    return;
-                }
-                
-                this.isTestRunning = true;
-                const testBtn = document.querySelector('.btn.primary');
-                testBtn.textContent = 'Testing...';
-                testBtn.style.background = 'linear-gradient(45deg, #ff9500, #ffad33)';
-                
-                this.showNotification('Load test initiated...');
-                for (let i = 0; i < 10; i++) {
-                    setTimeout(() => {
-                        document.getElementById('tps').textContent = (140000 + i * 1000).toLocaleString();
-                        document.getElementById('block-time').textContent = Math.max(75, 98 - i * 2);
-                        document.getElementById('health').textContent = Math.min(99.9, 99.7 + i * 0.02).toFixed(1);
-                    }, i * 1000);
+/// `GET /api/mempool`: backlog size and fee histogram; `/ws/mempool`: one JSON text
+/// message per admission or eviction, closed with a policy violation once the client
+/// falls behind
+fn mempool(
+    node: Arc<Node>,
+    config: StreamConfig,
+    stats: Arc<StreamStats>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let summary_node = node.clone();
+    let summary = warp::path!("api" / "mempool")
+        .and(warp::get())
+        .map(move || warp::reply::json(&summary_node.mempool_summary()));
+    let stream = warp::path!("ws" / "mempool")
+        .and(warp::ws())
+        .map(move |ws: warp::ws::Ws| {
+            let updates = node.subscribe_mempool();
+            let (config, stats) = (config.clone(), stats.clone());
+            ws.on_upgrade(move |socket| async move {
+                let mut queue = ClientQueue::spawn(updates, &config, stats);
+                let (mut sink, mut incoming) = socket.split();
+                loop {
+                    tokio::select! {
+                        update = queue.recv() => match update {
+                            Ok(update) => {
+                                let text = serde_json::to_string(&update).unwrap_or_default();
+                                if sink.send(warp::ws::Message::text(text)).await.is_err() {
+                                    break;
+                                }
+                            }
+                            Err(QueueEnd::Lagging) => {
+                                let _ = sink.send(warp::ws::Message::close_with(POLICY_VIOLATION, LAGGING_REASON)).await;
+                                break;
+                            }
+                            Err(QueueEnd::Closed) => break,
+                        },
+                        message = incoming.next() => match message {
+                            Some(Ok(message)) if !message.is_close() => continue,
+                            _ => break,
+                        },
+                    }
                 }
-                setTimeout(() => {
-                    this.isTestRunning = false;
-                    testBtn.textContent = 'Run Test';
-                    testBtn.style.background = 'linear-gradient(45deg, #007aff, #00d4ff)';
-                    
-                    this.showNotification('Load test completed! Peak: 149,000 TPS');
-                    this.updateMetrics(); // Return to normal metrics
-                }, 10000);
-            }
-        }
-        function toggleTheme() {
-            window.dashboard.toggleTheme();
-        }
+            })
+        });
+    summary.or(stream)
+}
 
-        function exportData() {
-            window.dashboard.exportData();
-        }
+/// `GET /metrics`: stream delivery, and with a network its traffic and sync serving
+/// counters, in the Prometheus text format
+fn prometheus(
+    network: Option<Arc<NetworkService>>,
+    streams: Vec<Arc<StreamStats>>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("metrics")
+        .and(warp::get())
+        .map(move || {
+            let mut text = delivery(&streams).to_prometheus();
+            if let Some(network) = &network {
+                text += &(network.stats().to_prometheus() + &network.serving_stats().to_prometheus());
+            }
+            warp::reply::with_header(text, "content-type", "text/plain; version=0.0.4")
+        })
+}
 
-        function showSettings() {
-            window.dashboard.showSettings();
-        }
+fn boxed<F, R>(filter: F) -> BoxedFilter<(Box<dyn warp::Reply>,)>
+where
+    F: Filter<Extract = (R,), Error = warp::Rejection> + Clone + Send + Sync + 'static,
+    R: warp::Reply + 'static,
+{
+    filter.map(|reply: R| Box::new(reply) as Box<dyn warp::Reply>).boxed()
+}
 
-        function runLoadTest() {
-            window.dashboard.runLoadTest();
-        }
+/// Routes that reject every request when the service is not configured
+fn optional(routes: Option<BoxedFilter<(Box<dyn warp::Reply>,)>>) -> BoxedFilter<(Box<dyn warp::Reply>,)> {
+    match routes {
+        Some(routes) => routes,
+        None => warp::any()
+            .and_then(|| async { Err::<Box<dyn warp::Reply>, _>(warp::reject::not_found()) })
+            .boxed(),
+    }
+}
 
-        document.addEventListener('DOMContentLoaded', () => {
-            window.dashboard = new TriUnityDashboard();
-        });
-        const style = document.createElement('style');
-        style.textContent = `
-            @keyframes fadeIn {
-                from { opacity: 0; }
-                to { opacity: 1; }
+/// Binds `routes` on every configured address, with TLS when configured
+fn listen(
+    routes: BoxedFilter<(Box<dyn warp::Reply>,)>,
+    config: &ListenConfig,
+) -> Result<Vec<BoxFuture<'static, ()>>, String> {
+    let mut servers = Vec::new();
+    for address in &config.addresses {
+        match &config.tls {
+            Some(tls) => {
+                tls.validate()?;
+                let (_, server) = warp::serve(routes.clone())
+                    .tls()
+                    .cert_path(&tls.cert_path)
+                    .key_path(&tls.key_path)
+                    .try_bind_with_graceful_shutdown(*address, futures::future::pending())
+                    .map_err(|e| format!("Could not bind {} with TLS: {}", address, e))?;
+                servers.push(server.boxed());
             }
-            @keyframes slideUp {
-                from { transform: translateY(20px); opacity: 0; }
-                to { transform: translateY(0); opacity: 1; }
+            None => {
+                let (_, server) = warp::serve(routes.clone())
+                    .try_bind_ephemeral(*address)
+                    .map_err(|e| format!("Could not bind {}: {}", address, e))?;
+                servers.push(server.boxed());
             }
-        `;
-        document.head.appendChild(style);
-    </script>
-</body>
-</html>"#;
\ No newline at end of file
+        }
+    }
+    Ok(servers)
+}
@@ -0,0 +1,108 @@
+use include_dir::{include_dir, Dir, File};
+use sha3::{Digest, Sha3_256};
+use warp::http::{header, Response, StatusCode};
+use warp::hyper::Body;
+use warp::path::{FullPath, Tail};
+use warp::Filter;
+
+/// The dashboard's page, scripts, styles and fonts, built into the binary from `web/`
+static ASSETS: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/web");
+
+/// Client side routes of the dashboard; they and everything below them get `index.html`
+pub const PAGES: [&str; 3] = ["explorer", "validators", "peers"];
+
+/// How long a browser may reuse an asset before revalidating it by its ETag
+const ASSET_MAX_AGE_SECS: u64 = 3600;
+
+/// `GET /assets/<file>`: a file under `web/assets` with its content type; `GET /` and the
+/// `PAGES`: `index.html`, always revalidated so a new build is picked up at once
+pub fn routes() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let if_none_match = warp::header::optional::<String>("if-none-match");
+    let assets = warp::path("assets")
+        .and(warp::get())
+        .and(warp::path::tail())
+        .and(if_none_match)
+        .and_then(|tail: Tail, if_none_match: Option<String>| async move {
+            match ASSETS.get_file(format!("assets/{}", tail.as_str())) {
+                Some(file) => Ok(serve(file, if_none_match, &format!("public, max-age={}", ASSET_MAX_AGE_SECS))),
+                None => Err(warp::reject::not_found()),
+            }
+        });
+    let pages = warp::get()
+        .and(warp::path::full())
+        .and(if_none_match)
+        .and_then(|path: FullPath, if_none_match: Option<String>| async move {
+            let page = path.as_str().trim_start_matches('/').split('/').next().unwrap_or_default();
+            match (page.is_empty() || PAGES.contains(&page), ASSETS.get_file("index.html")) {
+                (true, Some(index)) => Ok(serve(index, if_none_match, "no-cache")),
+                _ => Err(warp::reject::not_found()),
+            }
+        });
+    assets.or(pages).unify()
+}
+
+/// `file` with a content type from its extension, or 304 when the browser already holds
+/// this version of it
+fn serve(file: &'static File<'static>, if_none_match: Option<String>, cache_control: &str) -> Response<Body> {
+    let etag = format!("\"{}\"", hex::encode(&Sha3_256::digest(file.contents())[..8]));
+    let fresh = if_none_match.is_some_and(|tags| tags.split(',').any(|tag| matches!(tag.trim(), "*") || tag.trim() == etag));
+    let builder = Response::builder().header(header::ETAG, &etag).header(header::CACHE_CONTROL, cache_control);
+    let response = match fresh {
+        true => builder.status(StatusCode::NOT_MODIFIED).body(Body::empty()),
+        false => builder.header(header::CONTENT_TYPE, content_type(file)).body(Body::from(file.contents())),
+    };
+    response.unwrap_or_else(|_| Response::new(Body::empty()))
+}
+
+fn content_type(file: &File) -> String {
+    let mime = mime_guess::from_path(file.path()).first_or_octet_stream();
+    match mime.type_() == mime_guess::mime::TEXT || mime.subtype() == mime_guess::mime::JAVASCRIPT {
+        true => format!("{}; charset=utf-8", mime.essence_str()),
+        false => mime.essence_str().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dashboard_assets() {
+        let routes = routes();
+        let get = |path: &'static str| warp::test::request().path(path).reply(&routes);
+
+        // The sub-pages all load the dashboard, which loads only assets that exist
+        let index = get("/").await;
+        assert_eq!(index.status(), StatusCode::OK);
+        assert_eq!(index.headers()[header::CONTENT_TYPE], "text/html; charset=utf-8");
+        assert_eq!(index.headers()[header::CACHE_CONTROL], "no-cache");
+        for page in ["/explorer", "/explorer/42", "/validators", "/peers"] {
+            assert_eq!(get(page).await.body(), index.body());
+        }
+        let html = String::from_utf8(index.body().to_vec()).unwrap();
+        for asset in html.split("\"/assets/").skip(1).map(|rest| &rest[..rest.find('"').unwrap()]) {
+            assert!(ASSETS.get_file(format!("assets/{}", asset)).is_some(), "missing asset {}", asset);
+        }
+
+        let script = get("/assets/dashboard.js").await;
+        assert_eq!(script.status(), StatusCode::OK);
+        assert!(script.headers()[header::CONTENT_TYPE].to_str().unwrap().ends_with("javascript; charset=utf-8"));
+        assert_eq!(get("/assets/dashboard.css").await.headers()[header::CONTENT_TYPE], "text/css; charset=utf-8");
+        assert_eq!(script.headers()[header::CACHE_CONTROL], "public, max-age=3600");
+
+        // A browser holding the current version is told to keep it
+        let etag = script.headers()[header::ETAG].to_str().unwrap().to_string();
+        let cached = warp::test::request().path("/assets/dashboard.js").header("if-none-match", &etag).reply(&routes).await;
+        assert_eq!((cached.status(), cached.body().is_empty()), (StatusCode::NOT_MODIFIED, true));
+        let stale = warp::test::request().path("/assets/dashboard.js").header("if-none-match", "\"old\"").reply(&routes).await;
+        assert_eq!(stale.status(), StatusCode::OK);
+
+        // Everything else is left to the other routes
+        for path in ["/assets/missing.js", "/api/validators", "/index.html", "/explorer.js"] {
+            assert_eq!(get(path).await.status(), StatusCode::NOT_FOUND, "{}", path);
+        }
+        assert_eq!(warp::test::request().method("POST").path("/peers").reply(&routes).await.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        println!("   Dashboard assets working!");
+    }
+}
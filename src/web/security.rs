@@ -0,0 +1,239 @@
+use sha3::{Digest, Sha3_256};
+use crate::crypto::hash::constant_time_eq;
+use std::sync::Arc;
+use warp::filters::BoxedFilter;
+use warp::http::header::{self, HeaderMap, HeaderValue};
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+
+/// Scripts and styles from the dashboard's own assets only; styles may be inline, which
+/// the dashboard uses for its notifications and settings modal
+pub const DEFAULT_CONTENT_SECURITY_POLICY: &str = "default-src 'self'; script-src 'self'; style-src 'self' 'unsafe-inline'; \
+     img-src 'self' data:; connect-src 'self'; frame-ancestors 'none'; base-uri 'self'; form-action 'self'";
+
+/// Request header the dashboard's state-changing requests carry their CSRF token in
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// How long a CSRF token is accepted after it was issued
+pub const CSRF_TOKEN_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// How long browsers may cache a preflight answer
+const PREFLIGHT_MAX_AGE_SECS: u64 = 600;
+
+/// Issues and checks the tokens the dashboard's POST endpoints require. A token is its
+/// issue time with a hash keyed by a secret drawn at startup, so checking one needs no
+/// stored state, and a page on another origin cannot read one to forge a request.
+/// JSON-RPC needs none: it authenticates by bearer token, which browsers never attach
+/// on their own
+pub struct CsrfGuard {
+    secret: [u8; 32],
+}
+
+/// A POST whose CSRF token was missing, expired or forged
+#[derive(Debug)]
+pub struct CsrfRejection(pub String);
+
+impl warp::reject::Reject for CsrfRejection {}
+
+impl Default for CsrfGuard {
+    fn default() -> Self {
+        Self { secret: rand::random() }
+    }
+}
+
+impl CsrfGuard {
+    pub fn issue(&self) -> String {
+        self.issue_at(now())
+    }
+
+    fn issue_at(&self, issued: u64) -> String {
+        format!("{}.{}", issued, hex::encode(self.mac(issued)))
+    }
+
+    fn mac(&self, issued: u64) -> [u8; 32] {
+        Sha3_256::new().chain_update(self.secret).chain_update(issued.to_be_bytes()).finalize().into()
+    }
+
+    pub fn check(&self, token: Option<&str>) -> Result<(), String> {
+        self.check_at(token, now())
+    }
+
+    fn check_at(&self, token: Option<&str>, now: u64) -> Result<(), String> {
+        let token = token.ok_or_else(|| format!("Missing {} header; fetch one from /api/csrf", CSRF_HEADER))?;
+        let (issued, mac) = token.split_once('.').ok_or("Malformed CSRF token")?;
+        let issued: u64 = issued.parse().map_err(|_| "Malformed CSRF token")?;
+        let mac = hex::decode(mac).map_err(|_| "Malformed CSRF token")?;
+        if !constant_time_eq(&mac, &self.mac(issued)) {
+            return Err("Invalid CSRF token".to_string());
+        }
+        if issued > now + 60 || now - issued.min(now) > CSRF_TOKEN_TTL_SECS {
+            return Err("Expired CSRF token; fetch a new one from /api/csrf".to_string());
+        }
+        Ok(())
+    }
+
+    /// Passes requests whose `x-csrf-token` header checks out, rejecting the rest with a
+    /// `CsrfRejection` that `recover` turns into 403
+    pub fn filter(self: &Arc<Self>) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+        let guard = self.clone();
+        warp::header::optional::<String>(CSRF_HEADER)
+            .and_then(move |token: Option<String>| {
+                let result = guard.check(token.as_deref());
+                async move { result.map_err(|e| warp::reject::custom(CsrfRejection(e))) }
+            })
+            .untuple_one()
+    }
+
+    /// `GET /api/csrf`: a fresh token and the header to send it in
+    pub fn routes(self: &Arc<Self>) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+        let guard = self.clone();
+        warp::path!("api" / "csrf").and(warp::get()).map(move || {
+            let reply = warp::reply::json(&serde_json::json!({ "token": guard.issue(), "header": CSRF_HEADER }));
+            warp::reply::with_header(reply, header::CACHE_CONTROL, "no-store")
+        })
+    }
+}
+
+/// Answers a `CsrfRejection` with 403, leaving every other rejection to warp
+pub async fn recover(rejection: Rejection) -> Result<impl Reply, Rejection> {
+    match rejection.find::<CsrfRejection>() {
+        Some(CsrfRejection(e)) => Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": e })),
+            StatusCode::FORBIDDEN,
+        )),
+        None => Err(rejection),
+    }
+}
+
+/// Headers sent with every dashboard response: `policy` as the Content-Security-Policy,
+/// no framing, no MIME sniffing, no referrers, and HSTS when served over TLS
+pub fn security_headers(policy: &str, tls: bool) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let policy = HeaderValue::from_str(policy).unwrap_or(HeaderValue::from_static(DEFAULT_CONTENT_SECURITY_POLICY));
+    headers.insert(header::CONTENT_SECURITY_POLICY, policy);
+    headers.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    headers.insert(header::REFERRER_POLICY, HeaderValue::from_static("no-referrer"));
+    if tls {
+        headers.insert(header::STRICT_TRANSPORT_SECURITY, HeaderValue::from_static("max-age=31536000"));
+    }
+    headers
+}
+
+/// Lets pages on `origins` call `routes` from the browser: preflights from them are
+/// answered and their requests get `Access-Control-Allow-Origin`. `*` allows any origin.
+/// Requests from other origins are still served, without CORS headers, so the browser
+/// keeps the response from the calling page; same-origin requests need no headers
+pub fn cors(routes: BoxedFilter<(Box<dyn Reply>,)>, origins: Vec<String>) -> BoxedFilter<(Box<dyn Reply>,)> {
+    let origins = Arc::new(origins);
+    let allowed = move |origin: &str| -> Option<HeaderValue> {
+        match origins.iter().any(|allowed| allowed == "*") {
+            true => Some(HeaderValue::from_static("*")),
+            false => origins.iter().find(|allowed| *allowed == origin).and_then(|origin| HeaderValue::from_str(origin).ok()),
+        }
+    };
+    let preflight_allowed = allowed.clone();
+    let preflight = warp::options()
+        .and(warp::header::<String>("origin"))
+        .and(warp::header::<String>("access-control-request-method"))
+        .map(move |origin: String, _method: String| -> Box<dyn Reply> {
+            let Some(allow_origin) = preflight_allowed(&origin) else {
+                return Box::new(StatusCode::FORBIDDEN);
+            };
+            let mut response = StatusCode::NO_CONTENT.into_response();
+            let headers = response.headers_mut();
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, HeaderValue::from_static("GET, POST"));
+            headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, HeaderValue::from_static("authorization, content-type, x-csrf-token"));
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(PREFLIGHT_MAX_AGE_SECS));
+            headers.insert(header::VARY, HeaderValue::from_static("origin"));
+            Box::new(response)
+        });
+    let served = warp::header::optional::<String>("origin")
+        .and(routes)
+        .map(move |origin: Option<String>, reply: Box<dyn Reply>| -> Box<dyn Reply> {
+            let mut response = reply.into_response();
+            if let Some(allow_origin) = origin.as_deref().and_then(&allowed) {
+                response.headers_mut().insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+                response.headers_mut().append(header::VARY, HeaderValue::from_static("origin"));
+            }
+            Box::new(response)
+        });
+    preflight.or(served).unify().boxed()
+}
+
+/// Checks `origins` are `*` or bare origins like `https://explorer.example.org`
+pub fn validate_origins(origins: &[String]) -> Result<(), String> {
+    for origin in origins.iter().filter(|origin| *origin != "*") {
+        let uri: warp::http::Uri = origin.parse().map_err(|_| format!("Invalid allowed origin: {}", origin))?;
+        let bare = matches!(uri.scheme_str(), Some("http" | "https")) && uri.authority().is_some() && !origin.ends_with('/');
+        if !bare || uri.path_and_query().is_some_and(|path| path.as_str() != "/") {
+            return Err(format!("Allowed origin {} must be scheme://host[:port] without a path", origin));
+        }
+    }
+    Ok(())
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_web_hardening() {
+        // Tokens are bound to this guard's secret and expire
+        let guard = Arc::new(CsrfGuard::default());
+        let token = guard.issue_at(1_000);
+        assert!(guard.check_at(Some(&token), 1_000 + CSRF_TOKEN_TTL_SECS).is_ok());
+        assert!(guard.check_at(Some(&token), 1_001 + CSRF_TOKEN_TTL_SECS).unwrap_err().contains("Expired"));
+        assert!(guard.check_at(Some(&token), 900).unwrap_err().contains("Expired"));
+        assert!(CsrfGuard::default().check_at(Some(&token), 1_000).unwrap_err().contains("Invalid"));
+        assert!(guard.check_at(Some(&token.replace("1000.", "1001.")), 1_000).is_err());
+        assert!(guard.check_at(None, 1_000).unwrap_err().contains("Missing"));
+
+        // A POST goes through only with a token from `/api/csrf`
+        let post = warp::path!("api" / "loadtest").and(warp::post()).and(guard.filter()).map(|| "started");
+        let routes = cors(super::super::boxed(guard.routes().or(post).recover(recover)), vec!["https://explorer.example.org".to_string()]);
+        let issued = warp::test::request().path("/api/csrf").reply(&routes).await;
+        let issued: serde_json::Value = serde_json::from_slice(issued.body()).unwrap();
+        let token = issued["token"].as_str().unwrap();
+        let refused = warp::test::request().method("POST").path("/api/loadtest").reply(&routes).await;
+        assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+        let accepted = warp::test::request().method("POST").path("/api/loadtest").header(CSRF_HEADER, token).reply(&routes).await;
+        assert_eq!((accepted.status(), accepted.body().as_ref()), (StatusCode::OK, b"started".as_ref()));
+
+        // Only the allowed origin may call from the browser
+        let preflight = |origin: &'static str| {
+            warp::test::request()
+                .method("OPTIONS")
+                .path("/api/loadtest")
+                .header("origin", origin)
+                .header("access-control-request-method", "POST")
+                .reply(&routes)
+        };
+        let allowed = preflight("https://explorer.example.org").await;
+        assert_eq!(allowed.status(), StatusCode::NO_CONTENT);
+        assert_eq!(allowed.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://explorer.example.org");
+        assert!(allowed.headers()[header::ACCESS_CONTROL_ALLOW_HEADERS].to_str().unwrap().contains(CSRF_HEADER));
+        assert_eq!(preflight("https://evil.example.org").await.status(), StatusCode::FORBIDDEN);
+        let foreign = warp::test::request().path("/api/csrf").header("origin", "https://evil.example.org").reply(&routes).await;
+        assert!(!foreign.headers().contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+        let any = cors(super::super::boxed(guard.routes()), vec!["*".to_string()]);
+        let reply = warp::test::request().path("/api/csrf").header("origin", "https://evil.example.org").reply(&any).await;
+        assert_eq!(reply.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+
+        let headers = security_headers(DEFAULT_CONTENT_SECURITY_POLICY, true);
+        assert_eq!((headers[header::X_FRAME_OPTIONS].to_str().unwrap(), headers.contains_key(header::STRICT_TRANSPORT_SECURITY)), ("DENY", true));
+        assert!(!security_headers("default-src 'none'", false).contains_key(header::STRICT_TRANSPORT_SECURITY));
+
+        assert!(validate_origins(&["*".to_string(), "http://127.0.0.1:3000".to_string()]).is_ok());
+        for origin in ["example.org", "https://example.org/app", "https://example.org/", "ftp://example.org"] {
+            assert!(validate_origins(&[origin.to_string()]).is_err(), "{}", origin);
+        }
+
+        println!("   Web hardening working!");
+    }
+}
@@ -0,0 +1,231 @@
+use serde::Serialize;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+
+use crate::config::StreamConfig;
+
+/// Delivery counters of one dashboard stream, shared by all of its clients
+#[derive(Debug)]
+pub struct StreamStats {
+    name: &'static str,
+    clients: AtomicU64,
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+    disconnected: AtomicU64,
+}
+
+impl StreamStats {
+    pub fn new(name: &'static str) -> Arc<Self> {
+        Arc::new(Self {
+            name,
+            clients: AtomicU64::new(0),
+            delivered: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            disconnected: AtomicU64::new(0),
+        })
+    }
+
+    pub fn delivery(&self) -> StreamDelivery {
+        StreamDelivery {
+            stream: self.name.to_string(),
+            clients: self.clients.load(Ordering::Relaxed),
+            delivered: self.delivered.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            disconnected: self.disconnected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// How one stream is reaching its clients
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StreamDelivery {
+    pub stream: String,
+    /// Clients connected right now
+    pub clients: u64,
+    pub delivered: u64,
+    /// Messages a client missed because its queue was full
+    pub dropped: u64,
+    /// Clients cut off for staying behind longer than `max_lag_secs`
+    pub disconnected: u64,
+}
+
+/// Delivery of every dashboard stream
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DeliveryStats {
+    pub streams: Vec<StreamDelivery>,
+}
+
+impl DeliveryStats {
+    /// Prometheus text exposition of the counters
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP triunity_stream_clients Clients connected to a dashboard stream");
+        let _ = writeln!(out, "# TYPE triunity_stream_clients gauge");
+        for stream in &self.streams {
+            let _ = writeln!(out, "triunity_stream_clients{{stream=\"{}\"}} {}", stream.stream, stream.clients);
+        }
+        for (name, help, pick) in [
+            ("delivered_total", "Stream messages handed to clients", (|s: &StreamDelivery| s.delivered) as fn(&StreamDelivery) -> u64),
+            ("dropped_total", "Stream messages dropped for clients whose queue was full", |s| s.dropped),
+            ("disconnected_total", "Stream clients disconnected for lagging", |s| s.disconnected),
+        ] {
+            let name = format!("triunity_stream_{}", name);
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            for stream in &self.streams {
+                let _ = writeln!(out, "{}{{stream=\"{}\"}} {}", name, stream.stream, pick(stream));
+            }
+        }
+        out
+    }
+}
+
+/// Why a client's queue stopped yielding messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueEnd {
+    /// The stream's source shut down
+    Closed,
+    /// The client stayed behind for too long
+    Lagging,
+}
+
+/// One client's bounded queue of a broadcast stream. A task forwards the broadcast into
+/// it and drops what does not fit, so a slow client only ever loses its own messages
+pub struct ClientQueue<T> {
+    receiver: mpsc::Receiver<T>,
+    lagging: Arc<AtomicBool>,
+    stats: Arc<StreamStats>,
+    forwarder: JoinHandle<()>,
+}
+
+impl<T: Clone + Send + 'static> ClientQueue<T> {
+    pub fn spawn(source: broadcast::Receiver<T>, config: &StreamConfig, stats: Arc<StreamStats>) -> Self {
+        Self::with_max_lag(source, config.queue, Duration::from_secs(config.max_lag_secs), stats)
+    }
+
+    pub fn with_max_lag(source: broadcast::Receiver<T>, queue: usize, max_lag: Duration, stats: Arc<StreamStats>) -> Self {
+        let (sender, receiver) = mpsc::channel(queue.max(1));
+        let lagging = Arc::new(AtomicBool::new(false));
+        stats.clients.fetch_add(1, Ordering::Relaxed);
+        let forwarder = tokio::spawn(forward(source, sender, max_lag, stats.clone(), lagging.clone()));
+        Self { receiver, lagging, stats, forwarder }
+    }
+
+    /// The next message, or why there are no more. A lagging client is cut off at once,
+    /// without being sent what is still queued for it
+    pub async fn recv(&mut self) -> Result<T, QueueEnd> {
+        if self.lagging.load(Ordering::Acquire) {
+            return Err(QueueEnd::Lagging);
+        }
+        match self.receiver.recv().await {
+            Some(message) => {
+                self.stats.delivered.fetch_add(1, Ordering::Relaxed);
+                Ok(message)
+            }
+            None if self.lagging.load(Ordering::Acquire) => Err(QueueEnd::Lagging),
+            None => Err(QueueEnd::Closed),
+        }
+    }
+}
+
+impl<T> Drop for ClientQueue<T> {
+    fn drop(&mut self) {
+        self.forwarder.abort();
+        self.stats.clients.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Moves `source` into `sender` until either side closes or the client has been behind
+/// for `max_lag`. It counts as caught up again once its queue is at most half full
+async fn forward<T: Clone>(
+    mut source: broadcast::Receiver<T>,
+    sender: mpsc::Sender<T>,
+    max_lag: Duration,
+    stats: Arc<StreamStats>,
+    lagging: Arc<AtomicBool>,
+) {
+    let queue = sender.max_capacity();
+    let mut behind_since: Option<Instant> = None;
+    loop {
+        let dropped = match source.recv().await {
+            Ok(message) => match sender.try_send(message) {
+                Ok(()) => {
+                    if sender.capacity() * 2 >= queue {
+                        behind_since = None;
+                    }
+                    continue;
+                }
+                Err(TrySendError::Full(_)) => 1,
+                Err(TrySendError::Closed(_)) => return,
+            },
+            Err(broadcast::error::RecvError::Lagged(missed)) => missed,
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        stats.dropped.fetch_add(dropped, Ordering::Relaxed);
+        if behind_since.get_or_insert_with(Instant::now).elapsed() >= max_lag {
+            stats.disconnected.fetch_add(1, Ordering::Relaxed);
+            lagging.store(true, Ordering::Release);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Waits for the forwarders to get `stats` where `done` wants them
+    async fn settle(stats: &StreamStats, done: impl Fn(&StreamDelivery) -> bool) {
+        for _ in 0..200 {
+            if done(&stats.delivery()) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("forwarders never settled at {:?}", stats.delivery());
+    }
+
+    #[tokio::test]
+    async fn test_client_queue_backpressure() {
+        let (source, _) = broadcast::channel(16);
+        let stats = StreamStats::new("mempool");
+
+        // A full queue drops the overflow for that client alone
+        let mut queue = ClientQueue::with_max_lag(source.subscribe(), 2, Duration::from_secs(3600), stats.clone());
+        let mut other = ClientQueue::with_max_lag(source.subscribe(), 8, Duration::from_secs(3600), stats.clone());
+        for message in 1..=5 {
+            source.send(message).unwrap();
+        }
+        settle(&stats, |delivery| delivery.dropped == 3).await;
+        assert_eq!((queue.recv().await, queue.recv().await), (Ok(1), Ok(2)));
+        for message in 1..=5 {
+            assert_eq!(other.recv().await, Ok(message));
+        }
+        source.send(6).unwrap();
+        assert_eq!(queue.recv().await, Ok(6));
+        let delivery = stats.delivery();
+        assert_eq!((delivery.clients, delivery.delivered, delivery.dropped, delivery.disconnected), (2, 8, 3, 0));
+
+        // A client that stays behind is cut off without its backlog
+        let mut slow = ClientQueue::with_max_lag(source.subscribe(), 1, Duration::ZERO, stats.clone());
+        source.send(7).unwrap();
+        source.send(8).unwrap();
+        settle(&stats, |delivery| delivery.disconnected == 1).await;
+        assert_eq!(slow.recv().await, Err(QueueEnd::Lagging));
+        drop(slow);
+
+        drop(source);
+        assert_eq!((queue.recv().await, queue.recv().await, queue.recv().await), (Ok(7), Ok(8), Err(QueueEnd::Closed)));
+        drop((queue, other));
+        let prometheus = DeliveryStats { streams: vec![stats.delivery()] }.to_prometheus();
+        assert!(prometheus.contains("triunity_stream_clients{stream=\"mempool\"} 0"));
+        assert!(prometheus.contains("triunity_stream_dropped_total{stream=\"mempool\"} 4"));
+
+        println!("   Client queue backpressure working!");
+    }
+}
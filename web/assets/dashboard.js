@@ -0,0 +1,459 @@
+class TriUnityDashboard {
+    constructor() {
+        this.isDarkMode = localStorage.getItem('darkMode') === 'true';
+        this.isTestRunning = false;
+        this.init();
+    }
+
+    init() {
+        this.initTheme();
+        this.updateMetrics();
+        this.startMetricsUpdater();
+        this.startActivityFeed();
+        this.initRouter();
+        document.querySelectorAll('[data-action]').forEach(element => {
+            element.addEventListener('click', () => this[element.dataset.action]());
+        });
+        console.log('TriUnity Dashboard initialized');
+    }
+
+    // Recent security events, then new ones live from the event stream when the node serves it
+    async startActivityFeed() {
+        try {
+            const response = await fetch('/api/activity');
+            const events = await response.json();
+            events.reverse().forEach(event => this.addActivity(event.severity, event.description, event.timestamp));
+        } catch (error) {
+            console.error('Failed to load security activity:', error);
+        }
+        if (window.EventSource) {
+            const stream = new EventSource('/api/events');
+            stream.addEventListener('SecurityEvent', message => {
+                const event = JSON.parse(message.data);
+                this.addActivity(event.severity, `${event.source}: ${event.reason}`, Date.now() / 1000);
+            });
+        }
+    }
+
+    addActivity(severity, description, timestamp) {
+        const feed = document.getElementById('activity-feed');
+        feed.querySelector('.activity-empty')?.remove();
+        const item = document.createElement('li');
+        item.className = `severity-${severity.toLowerCase()}`;
+        item.textContent = `${new Date(timestamp * 1000).toLocaleTimeString()}  ${severity}  ${description}`;
+        feed.prepend(item);
+        while (feed.children.length > 50) {
+            feed.lastElementChild.remove();
+        }
+    }
+
+    // Sub-pages are client side routes; the server answers /explorer, /validators and /peers with this page
+    initRouter() {
+        document.querySelectorAll('.page-nav a').forEach(link => link.addEventListener('click', event => {
+            event.preventDefault();
+            history.pushState(null, '', link.getAttribute('href'));
+            this.showPage();
+        }));
+        window.addEventListener('popstate', () => this.showPage());
+        this.showPage();
+    }
+
+    showPage() {
+        const [page = 'overview', ...rest] = location.pathname.split('/').filter(Boolean);
+        document.querySelectorAll('.page').forEach(section => section.hidden = section.dataset.page !== page);
+        document.querySelectorAll('.page-nav a').forEach(link => link.classList.toggle('active', link.dataset.page === page));
+        const loaders = {
+            explorer: () => this.loadExplorer(rest[0]),
+            validators: () => this.loadValidators(),
+            peers: () => this.loadPeers(),
+        };
+        loaders[page]?.().catch(error => this.showNotification(`Failed to load ${page}: ${error.message}`, 'error'));
+    }
+
+    // Token the server wants on state-changing requests, fetched once and again when it is refused
+    async csrfToken(refresh = false) {
+        if (refresh || !this.csrf) {
+            this.csrf = (await this.fetchJson('/api/csrf')).token;
+        }
+        return this.csrf;
+    }
+
+    async postJson(url, body) {
+        const send = async refresh => fetch(url, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json', 'X-CSRF-Token': await this.csrfToken(refresh) },
+            body: JSON.stringify(body)
+        });
+        const response = await send(false);
+        return response.status === 403 ? send(true) : response;
+    }
+
+    async fetchJson(url) {
+        const response = await fetch(url);
+        const body = await response.json();
+        if (!response.ok) {
+            throw new Error(body.error || response.statusText);
+        }
+        return body;
+    }
+
+    fillRows(id, rows, columns) {
+        const body = document.getElementById(id);
+        body.replaceChildren(...rows.map(row => {
+            const tr = document.createElement('tr');
+            columns.forEach(column => {
+                const td = document.createElement('td');
+                const value = column(row);
+                if (value instanceof Node) {
+                    td.appendChild(value);
+                } else {
+                    td.textContent = value;
+                }
+                tr.appendChild(td);
+            });
+            return tr;
+        }));
+    }
+
+    // Ten blocks counting down from /explorer/<height>, or from the head
+    async loadExplorer(from) {
+        const top = from === undefined ? (await this.fetchJson('/api/blocks/latest')).header.height : parseInt(from);
+        document.getElementById('explorer-title').textContent = from === undefined ? 'Latest Blocks' : `Blocks from ${top}`;
+        const heights = Array.from({ length: Math.min(10, top + 1) }, (_, i) => top - i);
+        const blocks = await Promise.all(heights.map(height => this.fetchJson(`/api/blocks/${height}`)));
+        this.fillRows('explorer-rows', blocks, [
+            block => {
+                const link = document.createElement('a');
+                link.href = `/explorer/${block.header.height}`;
+                link.textContent = block.header.height.toLocaleString();
+                link.addEventListener('click', event => {
+                    event.preventDefault();
+                    history.pushState(null, '', link.href);
+                    this.showPage();
+                });
+                return link;
+            },
+            block => new Date(block.header.timestamp * 1000).toLocaleString(),
+            block => block.transactions.length,
+            block => block.header.version,
+        ]);
+    }
+
+    async loadValidators() {
+        const validators = await this.fetchJson('/api/validators');
+        this.fillRows('validator-rows', validators, [
+            validator => validator.address.slice(0, 16) + (validator.local ? ' (this node)' : ''),
+            validator => validator.balance.toLocaleString(),
+            validator => validator.reputation.toFixed(3),
+            validator => `${(validator.uptime * 100).toFixed(1)}%`,
+            validator => validator.last_proposed_height ?? '-',
+        ]);
+    }
+
+    async loadPeers() {
+        const peers = await this.fetchJson('/api/peers');
+        this.fillRows('peer-rows', peers, [
+            peer => peer.node_id,
+            peer => peer.direction,
+            peer => peer.protocol,
+            peer => peer.latency_ms === null ? '-' : `${Math.round(peer.latency_ms)}ms`,
+            peer => peer.traffic.sent.toLocaleString(),
+            peer => peer.traffic.received.toLocaleString(),
+        ]);
+    }
+
+    initTheme() {
+        if (this.isDarkMode) {
+            document.documentElement.setAttribute('data-theme', 'dark');
+        }
+    }
+
+    toggleTheme() {
+        const toggle = document.querySelector('.theme-toggle');
+
+        this.createRipple(toggle);
+        this.isDarkMode = !this.isDarkMode;
+
+        if (this.isDarkMode) {
+            document.documentElement.setAttribute('data-theme', 'dark');
+        } else {
+            document.documentElement.setAttribute('data-theme', 'light');
+        }
+
+        localStorage.setItem('darkMode', this.isDarkMode);
+        this.showNotification(`Switched to ${this.isDarkMode ? 'dark' : 'light'} mode`);
+    }
+
+    createRipple(element) {
+        const rect = element.getBoundingClientRect();
+        const size = Math.max(window.innerWidth, window.innerHeight) * 2;
+
+        const ripple = document.createElement('div');
+        ripple.className = 'ripple';
+        ripple.style.width = size + 'px';
+        ripple.style.height = size + 'px';
+        ripple.style.left = (rect.left + rect.width / 2 - size / 2) + 'px';
+        ripple.style.top = (rect.top + rect.height / 2 - size / 2) + 'px';
+
+        document.body.appendChild(ripple);
+        setTimeout(() => ripple.classList.add('animate'), 10);
+        setTimeout(() => {
+            if (ripple.parentNode) {
+                ripple.parentNode.removeChild(ripple);
+            }
+        }, 800);
+    }
+
+    async updateMetrics() {
+        try {
+            // Add loading state
+            const metrics = document.querySelectorAll('.metric-value');
+            metrics.forEach(metric => metric.classList.add('loading'));
+
+            const response = await fetch('/api/metrics');
+            const data = await response.json();
+            this.animateValue('tps', data.tps);
+            this.animateValue('block-time', data.block_time_ms);
+            this.animateValue('health', data.health_percentage.toFixed(1));
+            this.animateValue('validators', data.validator_count);
+            setTimeout(() => {
+                metrics.forEach(metric => metric.classList.remove('loading'));
+            }, 500);
+
+        } catch (error) {
+            console.error('Failed to update metrics:', error);
+            this.showNotification('Failed to update metrics', 'error');
+        }
+    }
+
+    animateValue(elementId, newValue) {
+        const element = document.getElementById(elementId);
+        const currentValue = parseInt(element.textContent.replace(/[^0-9.]/g, '')) || 0;
+        const target = typeof newValue === 'number' ? newValue : parseInt(newValue);
+
+        if (currentValue === target) return;
+
+        const duration = 1000;
+        const startTime = performance.now();
+
+        const animate = (currentTime) => {
+            const elapsed = currentTime - startTime;
+            const progress = Math.min(elapsed / duration, 1);
+            const easeProgress = 1 - Math.pow(1 - progress, 3);
+            const current = Math.floor(currentValue + (target - currentValue) * easeProgress);
+
+            if (elementId === 'tps' || elementId === 'validators') {
+                element.textContent = current.toLocaleString();
+            } else {
+                element.textContent = current;
+            }
+
+            if (progress < 1) {
+                requestAnimationFrame(animate);
+            }
+        };
+
+        requestAnimationFrame(animate);
+    }
+
+    startMetricsUpdater() {
+        const savedFrequency = localStorage.getItem('updateFrequency') || '3000';
+        this.metricsInterval = setInterval(() => {
+            if (!this.isTestRunning) {
+                this.updateMetrics();
+            }
+        }, parseInt(savedFrequency));
+    }
+
+    showNotification(message, type = 'success') {
+        const notification = document.createElement('div');
+        notification.className = 'success-feedback';
+        notification.innerHTML = `
+            <div style="display: flex; align-items: center; gap: 8px;">
+                <span>${type === 'success' ? '✓' : '✗'}</span>
+                <span>${message}</span>
+            </div>
+        `;
+
+        document.body.appendChild(notification);
+        setTimeout(() => notification.classList.add('show'), 100);
+        setTimeout(() => {
+            notification.classList.remove('show');
+            setTimeout(() => {
+                if (notification.parentNode) {
+                    notification.parentNode.removeChild(notification);
+                }
+            }, 400);
+        }, 3000);
+    }
+
+    async exportData() {
+        try {
+            this.showNotification('Preparing data export...');
+            setTimeout(async () => {
+                const response = await fetch('/api/metrics');
+                const data = await response.json();
+
+                const exportData = {
+                    timestamp: new Date().toISOString(),
+                    metrics: data,
+                    blockchain: 'TriUnity',
+                    version: '1.0.0'
+                };
+
+                const blob = new Blob([JSON.stringify(exportData, null, 2)], { type: 'application/json' });
+                const url = URL.createObjectURL(blob);
+                const a = document.createElement('a');
+                a.href = url;
+                a.download = `TriUnity_Export_${new Date().toISOString().split('T')[0]}.json`;
+                a.click();
+                URL.revokeObjectURL(url);
+
+                this.showNotification('Data exported successfully!');
+            }, 1500);
+        } catch (error) {
+            this.showNotification('Export failed', 'error');
+        }
+    }
+
+    showSettings() {
+        const modal = document.createElement('div');
+        modal.className = 'settings-modal';
+        modal.style.cssText = `
+            position: fixed;
+            top: 0;
+            left: 0;
+            right: 0;
+            bottom: 0;
+            background: rgba(0, 0, 0, 0.5);
+            backdrop-filter: blur(10px);
+            display: flex;
+            align-items: center;
+            justify-content: center;
+            z-index: 10000;
+            animation: fadeIn 0.3s ease;
+        `;
+
+        modal.innerHTML = `
+            <div class="settings-content" style="
+                background: var(--bg-card);
+                backdrop-filter: blur(20px);
+                border: 1px solid var(--border-color);
+                border-radius: 20px;
+                padding: 32px;
+                max-width: 400px;
+                width: 90%;
+                box-shadow: 0 20px 60px var(--shadow);
+                animation: slideUp 0.3s ease;
+            ">
+                <h3 style="color: var(--text-primary); margin-bottom: 20px; font-size: 1.5rem;">Settings</h3>
+                <div style="margin-bottom: 20px;">
+                    <label style="color: var(--text-secondary); font-size: 0.9rem; display: block; margin-bottom: 8px;">Update Frequency</label>
+                    <select id="update-frequency" style="width: 100%; padding: 12px; border-radius: 8px; border: 1px solid var(--border-color); background: var(--bg-card); color: var(--text-primary);">
+                        <option value="2000">Real-time (2s)</option>
+                        <option value="3000" selected>Normal (3s)</option>
+                        <option value="5000">Slow (5s)</option>
+                    </select>
+                </div>
+                <div style="margin-bottom: 20px;">
+                    <label style="color: var(--text-secondary); font-size: 0.9rem; display: flex; align-items: center; gap: 8px;">
+                        <input type="checkbox" id="enable-notifications" checked> Enable notifications
+                    </label>
+                </div>
+                <div style="display: flex; gap: 12px; justify-content: flex-end;">
+                    <button class="modal-btn cancel-btn" style="background: var(--button-bg); color: var(--text-primary); border: 1px solid var(--border-color); padding: 10px 20px; border-radius: 8px; cursor: pointer;">Cancel</button>
+                    <button class="modal-btn save-btn" style="background: linear-gradient(45deg, #007aff, #00d4ff); color: white; border: none; padding: 10px 20px; border-radius: 8px; cursor: pointer;">Save</button>
+                </div>
+            </div>
+        `;
+
+        document.body.appendChild(modal);
+
+        const savedFrequency = localStorage.getItem('updateFrequency') || '3000';
+        const savedNotifications = localStorage.getItem('notificationsEnabled') !== 'false';
+
+        document.getElementById('update-frequency').value = savedFrequency;
+        document.getElementById('enable-notifications').checked = savedNotifications;
+
+        const cancelBtn = modal.querySelector('.cancel-btn');
+        const saveBtn = modal.querySelector('.save-btn');
+
+        cancelBtn.addEventListener('click', () => {
+            modal.remove();
+        });
+
+        saveBtn.addEventListener('click', () => {
+            this.saveSettings();
+            modal.remove();
+        });
+        modal.addEventListener('click', (e) => {
+            if (e.target === modal) {
+                modal.remove();
+            }
+        });
+    }
+
+    saveSettings() {
+        const frequency = document.getElementById('update-frequency').value;
+        const notifications = document.getElementById('enable-notifications').checked;
+
+        localStorage.setItem('updateFrequency', frequency);
+        localStorage.setItem('notificationsEnabled', notifications);
+        if (this.metricsInterval) {
+            clearInterval(this.metricsInterval);
+        }
+        this.metricsInterval = setInterval(() => {
+            if (!this.isTestRunning) {
+                this.updateMetrics();
+            }
+        }, parseInt(frequency));
+
+        this.showNotification('Settings saved successfully!');
+    }
+
+    async runLoadTest() {
+        if (this.isTestRunning) {
+            this.showNotification('Test already running!', 'error');
+            return;
+        }
+
+        this.isTestRunning = true;
+        const testBtn = document.querySelector('.btn.primary');
+        testBtn.textContent = 'Testing...';
+        testBtn.style.background = 'linear-gradient(45deg, #ff9500, #ffad33)';
+
+        this.showNotification('Load test initiated...');
+        const progress = setInterval(() => this.updateMetrics(), 1000);
+        try {
+            const response = await this.postJson('/api/loadtest', { target_tps: 500, duration_secs: 10 });
+            const report = await response.json();
+            if (!response.ok) {
+                throw new Error(report.error);
+            }
+            document.getElementById('tps').textContent = Math.round(report.achieved_tps).toLocaleString();
+            this.showNotification(`Load test completed! ${report.included.toLocaleString()} txs included at ${Math.round(report.achieved_tps).toLocaleString()} TPS, p99 latency ${Math.round(report.latency_p99_ms)}ms`);
+        } catch (error) {
+            this.showNotification(`Load test failed: ${error.message}`, 'error');
+        } finally {
+            clearInterval(progress);
+            this.isTestRunning = false;
+            testBtn.textContent = 'Run Test';
+            testBtn.style.background = 'linear-gradient(45deg, #007aff, #00d4ff)';
+        }
+    }
+}
+document.addEventListener('DOMContentLoaded', () => {
+    window.dashboard = new TriUnityDashboard();
+});
+const style = document.createElement('style');
+style.textContent = `
+    @keyframes fadeIn {
+        from { opacity: 0; }
+        to { opacity: 1; }
+    }
+    @keyframes slideUp {
+        from { transform: translateY(20px); opacity: 0; }
+        to { transform: translateY(0); opacity: 1; }
+    }
+`;
+document.head.appendChild(style);